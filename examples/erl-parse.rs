@@ -0,0 +1,161 @@
+extern crate clap;
+extern crate erl_parse;
+#[macro_use]
+extern crate trackable;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use erl_parse::analysis::{elixir_dependencies, elixir_module_name};
+use erl_parse::workspace::{Location, SymbolKind, Workspace};
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let target = |name: &'static str| Arg::with_name(name).index(1).required(true);
+    let matches = App::new("erl-parse")
+        .arg(
+            Arg::with_name("ROOT")
+                .long("root")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .default_value("."),
+        )
+        .arg(Arg::with_name("INDEX_FILE").long("index").takes_value(true))
+        .subcommand(
+            SubCommand::with_name("defs")
+                .about(
+                    "Prints the definitions of a function (`mod:fun/arity`, `Mod.fun/arity`, ...)",
+                )
+                .arg(target("MFA")),
+        )
+        .subcommand(
+            SubCommand::with_name("callers")
+                .about("Prints the callers of a function (`mod:fun/arity`, `Mod.fun/arity`, ...)")
+                .arg(target("MFA")),
+        )
+        .subcommand(
+            SubCommand::with_name("elixir-deps")
+                .about("Prints the Elixir modules used by each Erlang module"),
+        )
+        .subcommand(
+            SubCommand::with_name("records")
+                .about("Prints the definitions of a record")
+                .arg(target("NAME")),
+        )
+        .subcommand(
+            SubCommand::with_name("symbols")
+                .about("Prints the functions, records, types and macros fuzzily matching a query")
+                .arg(target("QUERY")),
+        )
+        .get_matches();
+
+    let roots = matches.values_of("ROOT").unwrap().collect::<Vec<_>>();
+    let index_file = matches
+        .value_of("INDEX_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(roots[0]).join(".erl_parse_index"));
+
+    let mut workspace = Workspace::new();
+    for root in &roots {
+        track_try_unwrap!(workspace.add_root(root));
+    }
+    track_try_unwrap!(workspace.load_index(&index_file));
+    let updated = track_try_unwrap!(workspace.refresh_index());
+    if !updated.is_empty() {
+        track_try_unwrap!(workspace.save_index(&index_file));
+    }
+
+    let index = workspace.index();
+    match matches.subcommand() {
+        ("defs", Some(m)) => {
+            let (module, name, arity) = parse_mfa(m);
+            for (path, d) in index.find_functions(&module, name, arity) {
+                let text = display_mfa(&module, &d.name, d.arity.unwrap_or(0));
+                print_hit(path, d.location, &text);
+            }
+        }
+        ("callers", Some(m)) => {
+            let (module, name, arity) = parse_mfa(m);
+            for (path, c) in index.find_callers(&module, name, arity) {
+                let caller = index.get(path).and_then(|f| f.module.as_deref());
+                let callee = display_mfa(&module, &c.name, c.arity);
+                let text = format!("{} (in {})", callee, caller.unwrap_or("?"));
+                print_hit(path, c.location, &text);
+            }
+        }
+        ("elixir-deps", Some(_)) => {
+            for (module, used) in elixir_dependencies(index) {
+                let used = used.into_iter().collect::<Vec<_>>();
+                println!("{}: {}", module, used.join(", "));
+            }
+        }
+        ("records", Some(m)) => {
+            let name = m.value_of("NAME").unwrap();
+            for (path, d) in index.find_records(name) {
+                print_hit(path, d.location, &format!("#{}{{}}", d.name));
+            }
+        }
+        ("symbols", Some(m)) => {
+            let query = m.value_of("QUERY").unwrap();
+            for s in index.workspace_symbols(query) {
+                let d = s.definition;
+                let text = match (s.kind, d.arity) {
+                    (SymbolKind::Function, arity) => {
+                        display_mfa(s.module.unwrap_or("?"), &d.name, arity.unwrap_or(0))
+                    }
+                    (SymbolKind::Record, _) => format!("#{}{{}}", d.name),
+                    (SymbolKind::Type, arity) => {
+                        format!("{}/{} (type)", d.name, arity.unwrap_or(0))
+                    }
+                    (SymbolKind::Macro, None) => format!("?{}", d.name),
+                    (SymbolKind::Macro, Some(arity)) => format!("?{}/{}", d.name, arity),
+                };
+                print_hit(s.path, d.location, &text);
+            }
+        }
+        _ => {
+            eprintln!("{}", matches.usage());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses `mod:fun/arity` (or `Mod.fun/arity` for Elixir functions); the arity is optional.
+fn parse_mfa<'a>(matches: &'a ArgMatches) -> (String, &'a str, Option<usize>) {
+    let mfa = matches.value_of("MFA").unwrap();
+    let (module, rest) = if let Some(i) = mfa.find(':') {
+        (mfa[..i].to_owned(), &mfa[i + 1..])
+    } else if let Some(i) = mfa.rfind('.') {
+        (format!("Elixir.{}", &mfa[..i]), &mfa[i + 1..])
+    } else {
+        eprintln!("Expected `mod:fun/arity`: {:?}", mfa);
+        std::process::exit(1);
+    };
+    let mut parts = rest.splitn(2, '/');
+    let name = parts.next().unwrap();
+    let arity = parts.next().map(|a| {
+        a.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid arity: {:?}", a);
+            std::process::exit(1);
+        })
+    });
+    (module, name, arity)
+}
+
+/// Formats a function name, using the Elixir style for functions in Elixir modules.
+fn display_mfa(module: &str, name: &str, arity: usize) -> String {
+    if let Some(module) = elixir_module_name(module) {
+        format!("{}.{}/{}", module, name, arity)
+    } else {
+        format!("{}:{}/{}", module, name, arity)
+    }
+}
+
+fn print_hit(path: &Path, location: Location, text: &str) {
+    println!(
+        "{}:{}:{}: {}",
+        path.display(),
+        location.line,
+        location.column,
+        text
+    );
+}
@@ -0,0 +1,123 @@
+extern crate clap;
+extern crate erl_parse;
+extern crate erl_pp;
+extern crate erl_tokenize;
+#[macro_use]
+extern crate trackable;
+
+use clap::{App, Arg};
+use erl_parse::lint::{self, LintConfig};
+use erl_parse::{Diagnostic, Parser, TokenReader};
+use erl_pp::Preprocessor;
+use erl_tokenize::Lexer;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use trackable::error::{ErrorKindExt, Failed};
+
+fn main() {
+    let matches = App::new("lint")
+        .arg(Arg::with_name("PATH").index(1).multiple(true))
+        .arg(
+            Arg::with_name("CONFIG")
+                .long("config")
+                .takes_value(true)
+                .help("Configuration file (default: the nearest `.erlparse.toml`)"),
+        )
+        .get_matches();
+    let paths = matches.values_of("PATH").map_or_else(
+        || vec![PathBuf::from(".")],
+        |v| v.map(PathBuf::from).collect(),
+    );
+
+    let mut files = Vec::new();
+    for path in &paths {
+        collect_erl_files(path, &mut files);
+    }
+    files.sort();
+
+    let config = if let Some(path) = matches.value_of("CONFIG") {
+        track_try_unwrap!(LintConfig::load(path))
+    } else {
+        let dir = track_try_unwrap!(fs::canonicalize(&paths[0]).map_err(|e| Failed.cause(e)));
+        let dir = if dir.is_file() {
+            dir.parent().map(Path::to_path_buf).unwrap_or(dir)
+        } else {
+            dir
+        };
+        match track_try_unwrap!(LintConfig::discover(dir)) {
+            Some((path, config)) => {
+                eprintln!("Using {}", path.display());
+                config
+            }
+            None => LintConfig::default(),
+        }
+    };
+
+    let mut findings = 0;
+    for file in &files {
+        let text = track_try_unwrap!(fs::read_to_string(file).map_err(|e| Failed.cause(e)));
+        let mut lexer = Lexer::new(&text[..]);
+        lexer.set_filepath(file);
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(lexer)));
+        let (module, errors) = parser.parse_module_resilient();
+        let mut diagnostics = errors
+            .iter()
+            .map(|e| parser.error_diagnostic(e))
+            .collect::<Vec<_>>();
+        match lint::check_all(&text, &module, &config) {
+            Ok(ds) => diagnostics.extend(ds),
+            Err(e) => eprintln!("{}: {}", file.display(), e),
+        }
+        diagnostics.sort_by_key(|d| d.start_position.offset());
+        for d in &diagnostics {
+            print_diagnostic(file, &text, d);
+        }
+        findings += diagnostics.len();
+    }
+    eprintln!("{} finding(s) in {} file(s)", findings, files.len());
+    if findings > 0 {
+        process::exit(1);
+    }
+}
+
+fn collect_erl_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let entries = track_try_unwrap!(fs::read_dir(path).map_err(|e| Failed.cause(e)));
+    for entry in entries {
+        let path = track_try_unwrap!(entry.map_err(|e| Failed.cause(e))).path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with('.') || name == "_build" {
+            continue;
+        }
+        if path.is_dir() {
+            collect_erl_files(&path, files);
+        } else if name.ends_with(".erl") {
+            files.push(path);
+        }
+    }
+}
+
+/// Prints `d` followed by the source line with the range underlined.
+fn print_diagnostic(file: &Path, text: &str, d: &Diagnostic) {
+    println!("{}:{}", file.display(), d);
+    let line = match text.lines().nth(d.start_position.line() - 1) {
+        None => return,
+        Some(line) => line,
+    };
+    let start = d.start_position.column() - 1;
+    let end = if d.end_position.line() == d.start_position.line() {
+        d.end_position.column() - 1
+    } else {
+        line.chars().count()
+    };
+    println!("    {}", line);
+    println!(
+        "    {}{}",
+        " ".repeat(start),
+        "^".repeat(end.saturating_sub(start).max(1))
+    );
+}
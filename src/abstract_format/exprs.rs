@@ -0,0 +1,559 @@
+use erl_tokenize::tokens::SymbolToken;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::PositionRange;
+
+use super::{error, line, list, node, ToAbstract};
+use crate::cst::clauses::parts::WhenGuard;
+use crate::cst::clauses::{CaseClause, CatchClause, FunClause, IfClause, NamedFunClause};
+use crate::cst::commons::parts::{
+    Args, BinaryOp, BitsElem, BitsElemSpec, Clauses, ConsCellTail, MapField, RecordField, Sequence,
+    UnaryOp,
+};
+use crate::cst::commons::{self, AtomOrVariable, IntegerOrVariable, RecordName};
+use crate::cst::exprs::parts::{Body, Generator, GeneratorArrow, MapGenerator, Qualifier};
+use crate::cst::exprs::{self, Fun};
+use crate::cst::{Expr, GuardTest, Literal, Pattern};
+use crate::term::Term;
+use crate::traits::CollectTokens;
+
+impl ToAbstract for Literal {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Literal::Atom(ref x) => x.to_abstract(),
+            Literal::Char(ref x) => node("char", line(x), vec![Term::from(x.value() as u32)]),
+            Literal::Float(ref x) => node("float", line(x), vec![Term::from(x.value())]),
+            Literal::Integer(ref x) => x.to_abstract(),
+            Literal::String { .. } | Literal::TripleQuotedString(_) => {
+                let s = self.string_value().unwrap_or_default();
+                node("string", line(self), vec![Term::string(&s)])
+            }
+        }
+    }
+}
+impl ToAbstract for Expr {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Expr::Literal(ref x) => x.to_abstract(),
+            Expr::Variable(ref x) => x.to_abstract(),
+            Expr::Tuple(ref x) => tuple(x),
+            Expr::Map(ref x) => map(x),
+            Expr::MapUpdate(ref x) => node(
+                "map",
+                line(&x.update),
+                vec![x.map.to_abstract(), map_fields(&x.update)],
+            ),
+            Expr::Record(ref x) => record(x),
+            Expr::RecordUpdate(ref x) => node(
+                "record",
+                line(&x.update),
+                vec![
+                    x.record.to_abstract(),
+                    record_name(&x.update.name),
+                    record_fields(&x.update),
+                ],
+            ),
+            Expr::RecordFieldIndex(ref x) => record_field_index(x),
+            Expr::RecordFieldAccess(ref x) => record_field_access(x),
+            Expr::List(ref x) => cons(x),
+            Expr::ListComprehension(ref x) => node(
+                "lc",
+                line(x),
+                vec![x.element.to_abstract(), list(x.qualifiers.iter())],
+            ),
+            Expr::Bits(ref x) => bin(x),
+            Expr::BitsComprehension(ref x) => node(
+                "bc",
+                line(x),
+                vec![x.element.to_abstract(), list(x.qualifiers.iter())],
+            ),
+            Expr::MapComprehension(ref x) => node(
+                "mc",
+                line(x),
+                vec![
+                    node(
+                        "map_field_assoc",
+                        line(&x._relation),
+                        vec![x.key.to_abstract(), x.value.to_abstract()],
+                    ),
+                    list(x.qualifiers.iter()),
+                ],
+            ),
+            Expr::Fun(ref x) => x.to_abstract(),
+            Expr::Parenthesized(ref x) => x.item.to_abstract(),
+            Expr::FunCall(ref x) => call(x),
+            Expr::UnaryOpCall(ref x) => unary_op_call(x),
+            Expr::BinaryOpCall(ref x) => binary_op_call(x),
+            Expr::Match(ref x) => match_expr(x),
+            Expr::Block(ref x) => node("block", line(x), vec![x.body.to_abstract()]),
+            Expr::Catch(ref x) => node("catch", line(x), vec![single_expr(&x.expr)]),
+            Expr::If(ref x) => node("if", line(x), vec![list(x.clauses.iter())]),
+            Expr::Case(ref x) => node(
+                "case",
+                line(x),
+                vec![x.expr.to_abstract(), list(x.clauses.iter())],
+            ),
+            Expr::Receive(ref x) => {
+                let mut args = vec![list(x.clauses.iter())];
+                if let Some(ref t) = x.timeout {
+                    args.push(t.duration.to_abstract());
+                    args.push(t.body.to_abstract());
+                }
+                node("receive", line(x), args)
+            }
+            Expr::MacroCall(ref x) => macro_call(x),
+            Expr::Extension(ref x) => error(
+                &**x,
+                &format!("unsupported syntax extension {}", x.prefix.text()),
+            ),
+            Expr::Try(ref x) => node(
+                "try",
+                line(x),
+                vec![
+                    x.body.to_abstract(),
+                    list(x.branch.iter().flat_map(|b| b.clauses.iter())),
+                    list(x.catch.iter().flat_map(|c| c.clauses.iter())),
+                    x.after
+                        .as_ref()
+                        .map_or_else(|| Term::List(Vec::new()), |a| a.body.to_abstract()),
+                ],
+            ),
+            Expr::Maybe(ref x) => {
+                let mut args = vec![x.body.to_abstract()];
+                if let Some(ref e) = x.else_clauses {
+                    args.push(node("else", line(e), vec![list(e.clauses.iter())]));
+                }
+                node("maybe", line(x), args)
+            }
+            Expr::MaybeMatch(ref x) => node(
+                "maybe_match",
+                line(x),
+                vec![x.pattern.to_abstract(), x.value.to_abstract()],
+            ),
+        }
+    }
+}
+impl ToAbstract for Pattern {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Pattern::Literal(ref x) => x.to_abstract(),
+            Pattern::Variable(ref x) => x.to_abstract(),
+            Pattern::Tuple(ref x) => tuple(x),
+            Pattern::Map(ref x) => map(x),
+            Pattern::Record(ref x) => record(x),
+            Pattern::RecordFieldIndex(ref x) => record_field_index(x),
+            Pattern::List(ref x) => cons(x),
+            Pattern::Bits(ref x) => bin(x),
+            Pattern::Parenthesized(ref x) => x.item.to_abstract(),
+            Pattern::UnaryOpCall(ref x) => unary_op_call(x),
+            Pattern::BinaryOpCall(ref x) => binary_op_call(x),
+            Pattern::Match(ref x) => match_expr(x),
+            Pattern::MacroCall(ref x) => macro_call(x),
+        }
+    }
+}
+impl ToAbstract for GuardTest {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            GuardTest::Literal(ref x) => x.to_abstract(),
+            GuardTest::Variable(ref x) => x.to_abstract(),
+            GuardTest::Tuple(ref x) => tuple(x),
+            GuardTest::Map(ref x) => map(x),
+            GuardTest::Record(ref x) => record(x),
+            GuardTest::RecordFieldIndex(ref x) => record_field_index(x),
+            GuardTest::RecordFieldAccess(ref x) => record_field_access(x),
+            GuardTest::List(ref x) => cons(x),
+            GuardTest::Bits(ref x) => bin(x),
+            GuardTest::Parenthesized(ref x) => x.item.to_abstract(),
+            GuardTest::FunCall(ref x) => call(x),
+            GuardTest::UnaryOpCall(ref x) => unary_op_call(x),
+            GuardTest::BinaryOpCall(ref x) => binary_op_call(x),
+            GuardTest::MacroCall(ref x) => macro_call(x),
+        }
+    }
+}
+impl ToAbstract for Fun {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Fun::Defined(ref x) => {
+                let function = match (&x.module, &x.fun.name, &x.fun.arity) {
+                    (None, AtomOrVariable::Atom(name), IntegerOrVariable::Integer(arity)) => {
+                        Term::Tuple(vec![
+                            Term::atom("function"),
+                            Term::atom(name.value()),
+                            Term::from(num::BigInt::from(arity.value().clone())),
+                        ])
+                    }
+                    _ => {
+                        let mut function = vec![Term::atom("function")];
+                        function.extend(x.module.as_ref().map(|m| m.name.to_abstract()));
+                        function.push(x.fun.name.to_abstract());
+                        function.push(x.fun.arity.to_abstract());
+                        Term::Tuple(function)
+                    }
+                };
+                node("fun", line(x), vec![function])
+            }
+            Fun::Anonymous(ref x) => {
+                let clauses = Term::Tuple(vec![Term::atom("clauses"), list(x.clauses.iter())]);
+                node("fun", line(x), vec![clauses])
+            }
+            Fun::Named(ref x) => node(
+                "named_fun",
+                line(x),
+                vec![
+                    Term::atom(x.clauses.item.name.value()),
+                    list(x.clauses.iter()),
+                ],
+            ),
+        }
+    }
+}
+impl ToAbstract for Body {
+    fn to_abstract(&self) -> Term {
+        list(self.exprs.iter())
+    }
+}
+impl ToAbstract for Qualifier {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Qualifier::Generator(ref x) => x.to_abstract(),
+            Qualifier::MapGenerator(ref x) => x.to_abstract(),
+            Qualifier::Filter(ref x) => x.to_abstract(),
+        }
+    }
+}
+impl ToAbstract for Generator {
+    fn to_abstract(&self) -> Term {
+        let tag = match self.arrow {
+            GeneratorArrow::List(_) => "generate",
+            GeneratorArrow::Binary(_) => "b_generate",
+            GeneratorArrow::StrictList(..) => "generate_strict",
+            GeneratorArrow::StrictBinary(..) => "b_generate_strict",
+        };
+        node(
+            tag,
+            line(&self.arrow),
+            vec![self.pattern.to_abstract(), self.source.to_abstract()],
+        )
+    }
+}
+impl ToAbstract for MapGenerator {
+    fn to_abstract(&self) -> Term {
+        let field = node(
+            "map_field_exact",
+            line(&self._relation),
+            vec![self.key.to_abstract(), self.value.to_abstract()],
+        );
+        let tag = if self.arrow.is_strict() {
+            "m_generate_strict"
+        } else {
+            "m_generate"
+        };
+        node(
+            tag,
+            line(&self.arrow),
+            vec![field, self.source.to_abstract()],
+        )
+    }
+}
+impl ToAbstract for CaseClause {
+    fn to_abstract(&self) -> Term {
+        clause(
+            line(self),
+            list(Some(&self.pattern)),
+            self.guard.as_ref(),
+            &self.body,
+        )
+    }
+}
+impl ToAbstract for IfClause {
+    fn to_abstract(&self) -> Term {
+        node(
+            "clause",
+            line(self),
+            vec![
+                Term::List(Vec::new()),
+                guard_sequence(&self.guard),
+                self.body.to_abstract(),
+            ],
+        )
+    }
+}
+impl ToAbstract for CatchClause {
+    fn to_abstract(&self) -> Term {
+        let anno = line(self);
+        let class = self.class.as_ref().map_or_else(
+            || node("atom", anno.clone(), vec![Term::atom("throw")]),
+            |c| c.class.to_abstract(),
+        );
+        let stacktrace = self.stacktrace.as_ref().map_or_else(
+            || node("var", anno.clone(), vec![Term::atom("_")]),
+            |s| s.variable.to_abstract(),
+        );
+        let pattern = node(
+            "tuple",
+            anno.clone(),
+            vec![Term::List(vec![
+                class,
+                self.pattern.to_abstract(),
+                stacktrace,
+            ])],
+        );
+        clause(
+            anno,
+            Term::List(vec![pattern]),
+            self.guard.as_ref(),
+            &self.body,
+        )
+    }
+}
+impl ToAbstract for FunClause {
+    fn to_abstract(&self) -> Term {
+        clause(
+            line(self),
+            args(&self.patterns),
+            self.guard.as_ref(),
+            &self.body,
+        )
+    }
+}
+impl ToAbstract for NamedFunClause {
+    fn to_abstract(&self) -> Term {
+        clause(
+            line(&self.patterns),
+            args(&self.patterns),
+            self.guard.as_ref(),
+            &self.body,
+        )
+    }
+}
+
+/// Makes `{clause, Anno, Patterns, Guards, Body}`.
+pub fn clause(anno: Term, patterns: Term, guard: Option<&WhenGuard>, body: &Body) -> Term {
+    let guards = guard.map_or_else(|| Term::List(Vec::new()), |g| guard_sequence(&g.seq));
+    node("clause", anno, vec![patterns, guards, body.to_abstract()])
+}
+
+pub fn args<T: ToAbstract>(x: &Args<T>) -> Term {
+    list(x.args.iter().flat_map(|a| a.iter()))
+}
+
+fn guard_sequence(seq: &Clauses<Sequence<GuardTest>>) -> Term {
+    Term::List(seq.iter().map(|g| list(g.iter())).collect())
+}
+
+/// `catch` takes a single expression, but `Catch` holds a `Body`.
+fn single_expr(body: &Body) -> Term {
+    let mut exprs = body.exprs.iter();
+    match (exprs.next(), exprs.next()) {
+        (Some(e), None) => e.to_abstract(),
+        _ => node("block", line(body), vec![body.to_abstract()]),
+    }
+}
+
+fn tuple<T: ToAbstract + PositionRange>(x: &commons::Tuple<T>) -> Term {
+    node(
+        "tuple",
+        line(x),
+        vec![list(x.elements.iter().flat_map(|e| e.iter()))],
+    )
+}
+
+fn map<T: ToAbstract + PositionRange>(x: &commons::Map<T>) -> Term {
+    node("map", line(x), vec![map_fields(x)])
+}
+
+fn map_fields<T: ToAbstract + PositionRange>(x: &commons::Map<T>) -> Term {
+    Term::List(
+        x.fields
+            .iter()
+            .flat_map(|f| f.iter())
+            .map(map_field)
+            .collect(),
+    )
+}
+
+fn map_field<T: ToAbstract + PositionRange>(x: &MapField<T>) -> Term {
+    let tag = if x._relation.value() == Symbol::DoubleRightArrow {
+        "map_field_assoc"
+    } else {
+        "map_field_exact"
+    };
+    node(
+        tag,
+        line(&x._relation),
+        vec![x.key.to_abstract(), x.value.to_abstract()],
+    )
+}
+
+fn record<T: ToAbstract + PositionRange>(x: &commons::Record<T>) -> Term {
+    node(
+        "record",
+        line(x),
+        vec![record_name(&x.name), record_fields(x)],
+    )
+}
+
+fn record_fields<T: ToAbstract + PositionRange>(x: &commons::Record<T>) -> Term {
+    Term::List(
+        x.fields
+            .iter()
+            .flat_map(|f| f.iter())
+            .map(record_field)
+            .collect(),
+    )
+}
+
+fn record_field<T: ToAbstract + PositionRange>(x: &RecordField<T>) -> Term {
+    node(
+        "record_field",
+        line(x),
+        vec![x.key.to_abstract(), x.value.to_abstract()],
+    )
+}
+
+fn record_field_index(x: &commons::RecordFieldIndex) -> Term {
+    node(
+        "record_index",
+        line(x),
+        vec![record_name(&x.name), x.field.to_abstract()],
+    )
+}
+
+fn record_field_access<T: ToAbstract + PositionRange>(x: &exprs::RecordFieldAccess<T>) -> Term {
+    node(
+        "record_field",
+        line(&x.index),
+        vec![
+            x.record.to_abstract(),
+            record_name(&x.index.name),
+            x.index.field.to_abstract(),
+        ],
+    )
+}
+
+fn cons<T: ToAbstract + PositionRange>(x: &commons::List<T>) -> Term {
+    match x.elements {
+        None => node("nil", line(x), Vec::new()),
+        Some(ref cell) => cons_cell(line(x), &cell.item, cell.tail.as_ref(), &x._close),
+    }
+}
+
+fn cons_cell<T: ToAbstract + PositionRange>(
+    anno: Term,
+    item: &T,
+    tail: Option<&ConsCellTail<T>>,
+    close: &SymbolToken,
+) -> Term {
+    let tail = match tail {
+        None => node("nil", line(close), Vec::new()),
+        Some(ConsCellTail::Proper { item, tail, .. }) => {
+            cons_cell(line(item), item, tail.as_deref(), close)
+        }
+        Some(ConsCellTail::Improper { item, .. }) => item.to_abstract(),
+    };
+    node("cons", anno, vec![item.to_abstract(), tail])
+}
+
+fn bin<T: ToAbstract + PositionRange>(x: &commons::Bits<T>) -> Term {
+    node(
+        "bin",
+        line(x),
+        vec![Term::List(
+            x.elements
+                .iter()
+                .flat_map(|e| e.iter())
+                .map(bin_element)
+                .collect(),
+        )],
+    )
+}
+
+fn bin_element<T: ToAbstract + PositionRange>(x: &BitsElem<T>) -> Term {
+    let size = x
+        .size
+        .as_ref()
+        .map_or_else(|| Term::atom("default"), |s| s.size.to_abstract());
+    let specs = x.type_specs.as_ref().map_or_else(
+        || Term::atom("default"),
+        |s| {
+            Term::List(
+                s.specs
+                    .iter()
+                    .map(|spec| match *spec {
+                        BitsElemSpec::Type(ref t) => Term::atom(t.value()),
+                        BitsElemSpec::Unit { ref unit, .. } => Term::Tuple(vec![
+                            Term::atom("unit"),
+                            Term::from(num::BigInt::from(unit.value().clone())),
+                        ]),
+                    })
+                    .collect(),
+            )
+        },
+    );
+    node(
+        "bin_element",
+        line(x),
+        vec![x.element.to_abstract(), size, specs],
+    )
+}
+
+fn call<T: ToAbstract + PositionRange, A: ToAbstract>(x: &commons::Call<T, A>) -> Term {
+    let name = match x.module {
+        None => x.name.to_abstract(),
+        Some(ref m) => node(
+            "remote",
+            line(&m._colon),
+            vec![m.name.to_abstract(), x.name.to_abstract()],
+        ),
+    };
+    node("call", line(x), vec![name, args(&x.args)])
+}
+
+fn match_expr<T: ToAbstract + PositionRange>(x: &commons::Match<T>) -> Term {
+    node(
+        "match",
+        line(&x._match),
+        vec![x.pattern.to_abstract(), x.value.to_abstract()],
+    )
+}
+
+fn unary_op_call<T: ToAbstract + PositionRange>(x: &commons::UnaryOpCall<T>) -> Term {
+    node(
+        "op",
+        line(&x.op),
+        vec![unary_op_name(&x.op), x.operand.to_abstract()],
+    )
+}
+
+fn binary_op_call<T: ToAbstract + PositionRange>(x: &commons::BinaryOpCall<T>) -> Term {
+    node(
+        "op",
+        line(&x.op),
+        vec![
+            binary_op_name(&x.op),
+            x.left.to_abstract(),
+            x.right.to_abstract(),
+        ],
+    )
+}
+
+pub fn unary_op_name(op: &UnaryOp) -> Term {
+    Term::atom(op.tokens()[0].text())
+}
+
+pub fn binary_op_name(op: &BinaryOp) -> Term {
+    Term::atom(op.tokens()[0].text())
+}
+
+/// Makes an error term since macro calls (in no-preprocess mode) have no abstract format.
+pub(super) fn macro_call(x: &exprs::MacroCall) -> Term {
+    error(x, &format!("unexpanded macro call ?{}", x.name.text()))
+}
+
+pub(super) fn record_name(x: &RecordName) -> Term {
+    match *x {
+        RecordName::Atom(ref x) => Term::atom(x.value()),
+        RecordName::MacroCall(ref x) => macro_call(x),
+    }
+}
@@ -0,0 +1,391 @@
+//! Conversion from CSTs to the [Erlang Abstract Format].
+//!
+//! The conversion follows `erl_parse` of OTP (e.g., `-export` lists become `{F, A}` tuples and
+//! attribute values are normalized to terms), so that the result can be compared against
+//! `epp:parse_file/2` or passed to `compile:forms/2`.
+//! Annotations are line numbers.
+//! The `etf` module encodes the result in the binary External Term Format.
+//!
+//! [Erlang Abstract Format]: https://www.erlang.org/doc/apps/erts/absform.html
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::abstract_format::ToAbstract;
+//! use erl_parse::cst::ModuleDecl;
+//! use erl_parse::{Parser, TokenReader};
+//! use erl_pp::Preprocessor;
+//! use erl_tokenize::Lexer;
+//!
+//! let text = "-module(foo).\n-export([bar/0]).\nbar() -> ok.\n";
+//! let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+//! let module: ModuleDecl = parser.parse().unwrap();
+//! assert_eq!(
+//!     module.to_abstract().to_string(),
+//!     concat!(
+//!         "[{attribute,1,module,foo},",
+//!         "{attribute,2,export,[{bar,0}]},",
+//!         "{function,3,bar,0,[{clause,3,[],[],[{atom,3,ok}]}]},",
+//!         "{eof,3}]"
+//!     )
+//! );
+//! ```
+use erl_tokenize::tokens::{AtomToken, IntegerToken, SymbolToken, VariableToken};
+use erl_tokenize::values::Symbol;
+use erl_tokenize::PositionRange;
+use num::BigInt;
+
+use self::exprs::{args, clause};
+use crate::cst::clauses::FunDeclClause;
+use crate::cst::commons::parts::NameAndArity;
+use crate::cst::commons::{AtomOrVariable, IntegerOrVariable, ProperList};
+use crate::cst::forms::parts::RecordFieldDecl;
+use crate::cst::{Expr, Form, Literal, ModuleDecl};
+use crate::term::Term;
+use crate::traits::CollectTokens;
+
+mod exprs;
+mod types;
+
+/// Conversion into the abstract format.
+pub trait ToAbstract {
+    fn to_abstract(&self) -> Term;
+}
+impl ToAbstract for AtomToken {
+    fn to_abstract(&self) -> Term {
+        node("atom", line(self), vec![Term::atom(self.value())])
+    }
+}
+impl ToAbstract for VariableToken {
+    fn to_abstract(&self) -> Term {
+        node("var", line(self), vec![Term::atom(self.value())])
+    }
+}
+impl ToAbstract for IntegerToken {
+    fn to_abstract(&self) -> Term {
+        let value = BigInt::from(self.value().clone());
+        node("integer", line(self), vec![Term::from(value)])
+    }
+}
+impl ToAbstract for AtomOrVariable {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            AtomOrVariable::Atom(ref x) => x.to_abstract(),
+            AtomOrVariable::Variable(ref x) => x.to_abstract(),
+        }
+    }
+}
+impl ToAbstract for IntegerOrVariable {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            IntegerOrVariable::Integer(ref x) => x.to_abstract(),
+            IntegerOrVariable::Variable(ref x) => x.to_abstract(),
+        }
+    }
+}
+impl<T: ToAbstract> ToAbstract for Box<T> {
+    fn to_abstract(&self) -> Term {
+        (**self).to_abstract()
+    }
+}
+
+/// Returns the list of the forms followed by `{eof, Line}`.
+impl ToAbstract for ModuleDecl {
+    fn to_abstract(&self) -> Term {
+        let mut forms = self
+            .forms
+            .iter()
+            .map(ToAbstract::to_abstract)
+            .collect::<Vec<_>>();
+        let eof = self.forms.last().map_or(1, |f| f.end_position().line());
+        forms.push(Term::Tuple(vec![Term::atom("eof"), Term::from(eof)]));
+        Term::List(forms)
+    }
+}
+impl ToAbstract for Form {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Form::ModuleAttr(ref x) => {
+                attribute(&x._hyphen, "module", Term::atom(x.module_name.value()))
+            }
+            Form::ExportAttr(ref x) => attribute(&x._hyphen, "export", fun_list(&x.exports)),
+            Form::ExportTypeAttr(ref x) => {
+                attribute(&x._hyphen, "export_type", fun_list(&x.exports))
+            }
+            Form::ImportAttr(ref x) => {
+                let value = Term::Tuple(vec![
+                    Term::atom(x.module_name.value()),
+                    fun_list(&x.imports),
+                ]);
+                attribute(&x._hyphen, "import", value)
+            }
+            Form::FileAttr(ref x) => {
+                let value = Term::Tuple(vec![
+                    Term::string(x.file_name.value()),
+                    Term::from(BigInt::from(x.line_num.value().clone())),
+                ]);
+                attribute(&x._hyphen, "file", value)
+            }
+            Form::CompileAttr(ref x) => match normalise(&x.options) {
+                Some(value) => attribute(&x._hyphen, "compile", value),
+                None => error(&x._hyphen, "bad compile attribute"),
+            },
+            Form::VsnAttr(ref x) => match normalise(&x.vsn) {
+                Some(value) => attribute(&x._hyphen, "vsn", value),
+                None => error(&x._hyphen, "bad vsn attribute"),
+            },
+            Form::FeatureAttr(ref x) => {
+                let value = Term::Tuple(vec![
+                    Term::atom(x.feature_name.value()),
+                    Term::atom(x.action.value()),
+                ]);
+                attribute(&x._hyphen, "feature", value)
+            }
+            Form::BehaviourAttr(ref x) => attribute(
+                &x._hyphen,
+                x._behaviour.value(),
+                Term::atom(x.behaviour_name.value()),
+            ),
+            Form::OnLoadAttr(ref x) => {
+                let value = Term::Tuple(vec![
+                    Term::atom(x.fun.name.value()),
+                    Term::from(BigInt::from(x.fun.arity.value().clone())),
+                ]);
+                attribute(&x._hyphen, "on_load", value)
+            }
+            Form::DialyzerAttr(ref x) => match normalise(&x.options) {
+                Some(value) => attribute(&x._hyphen, "dialyzer", value),
+                None => error(&x._hyphen, "bad dialyzer attribute"),
+            },
+            Form::DocAttr(ref x) => match normalise(&x.value) {
+                Some(value) => attribute(&x._hyphen, x.attr_name.value(), value),
+                None => error(&x._hyphen, "bad attribute"),
+            },
+            Form::WildAttr(ref x) => match x.parse_value().as_ref().and_then(normalise) {
+                Some(value) => attribute(&x._hyphen, x.attr_name.value(), value),
+                None => error(&x._hyphen, "bad attribute"),
+            },
+            Form::FunSpec(ref x) => {
+                let arity = Term::from(x.arity());
+                let name = match x.module {
+                    None => Term::Tuple(vec![Term::atom(x.fun_name.value()), arity]),
+                    Some(ref m) => Term::Tuple(vec![
+                        Term::atom(m.name.value()),
+                        Term::atom(x.fun_name.value()),
+                        arity,
+                    ]),
+                };
+                let value = Term::Tuple(vec![name, list(x.clauses.iter())]);
+                attribute(&x._hyphen, "spec", value)
+            }
+            Form::CallbackSpec(ref x) => {
+                let name = Term::Tuple(vec![
+                    Term::atom(x.callback_name.value()),
+                    Term::from(x.arity()),
+                ]);
+                let value = Term::Tuple(vec![name, list(x.clauses.iter())]);
+                attribute(&x._hyphen, "callback", value)
+            }
+            Form::FunDecl(ref x) => node(
+                "function",
+                line(x),
+                vec![
+                    Term::atom(x.name()),
+                    Term::from(x.arity()),
+                    list(x.clauses.iter()),
+                ],
+            ),
+            Form::RecordDecl(ref x) => {
+                let fields = list(x.fields());
+                let value = Term::Tuple(vec![Term::atom(x.name()), fields]);
+                attribute(&x._hyphen, "record", value)
+            }
+            Form::TypeDecl(ref x) => {
+                let value = Term::Tuple(vec![
+                    Term::atom(x.name()),
+                    x.ty.to_abstract(),
+                    args(&x.variables),
+                ]);
+                attribute(&x._hyphen, x.type_kind.value(), value)
+            }
+            Form::Directive(ref x) => error(
+                x,
+                &format!("unprocessed preprocessor directive -{}", x.name.value()),
+            ),
+            Form::ExtensionAttr(ref x) => error(
+                x,
+                &format!("unsupported syntax extension -{}", x.attr_name.value()),
+            ),
+            Form::Error(ref x) => match x.tokens.first() {
+                Some(t) => error(t, "syntax error"),
+                None => error_at(Term::from(1usize), "syntax error"),
+            },
+            Form::Unparsed(ref x) => error(x, &x.reason),
+        }
+    }
+}
+impl ToAbstract for FunDeclClause {
+    fn to_abstract(&self) -> Term {
+        clause(
+            line(self),
+            args(&self.patterns),
+            self.guard.as_ref(),
+            &self.body,
+        )
+    }
+}
+impl ToAbstract for RecordFieldDecl {
+    fn to_abstract(&self) -> Term {
+        let mut args = vec![self.field_name.to_abstract()];
+        args.extend(self.field_default.as_ref().map(|d| d.value.to_abstract()));
+        let field = node("record_field", line(self), args);
+        match self.field_type {
+            None => field,
+            Some(ref t) => Term::Tuple(vec![
+                Term::atom("typed_record_field"),
+                field,
+                t.field_type.to_abstract(),
+            ]),
+        }
+    }
+}
+
+/// Makes `{Tag, Anno, Args...}`.
+fn node(tag: &str, anno: Term, args: Vec<Term>) -> Term {
+    let mut elements = vec![Term::atom(tag), anno];
+    elements.extend(args);
+    Term::Tuple(elements)
+}
+
+fn line<T: PositionRange>(x: &T) -> Term {
+    Term::from(x.start_position().line())
+}
+
+fn list<'a, T, I>(items: I) -> Term
+where
+    T: ToAbstract + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    Term::List(items.into_iter().map(ToAbstract::to_abstract).collect())
+}
+
+fn attribute(hyphen: &SymbolToken, name: &str, value: Term) -> Term {
+    node("attribute", line(hyphen), vec![Term::atom(name), value])
+}
+
+fn error<T: PositionRange>(x: &T, message: &str) -> Term {
+    error_at(line(x), message)
+}
+
+/// Makes `{error, {Anno, erl_parse, Message}}`.
+fn error_at(anno: Term, message: &str) -> Term {
+    let info = Term::Tuple(vec![anno, Term::atom("erl_parse"), Term::string(message)]);
+    Term::Tuple(vec![Term::atom("error"), info])
+}
+
+fn fun_list(x: &ProperList<NameAndArity>) -> Term {
+    Term::List(
+        x.elements
+            .iter()
+            .flat_map(|e| e.iter())
+            .map(|f| {
+                Term::Tuple(vec![
+                    Term::atom(f.name.value()),
+                    Term::from(BigInt::from(f.arity.value().clone())),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Converts a constant expression to a term (i.e., `erl_parse:normalise/1`).
+///
+/// As with attributes in `erl_parse`, `Name/Arity` is converted to `{Name, Arity}`.
+fn normalise(expr: &Expr) -> Option<Term> {
+    match *expr {
+        Expr::Literal(ref x) => Some(match *x {
+            Literal::Atom(ref t) => Term::atom(t.value()),
+            Literal::Char(ref t) => Term::from(t.value() as u32),
+            Literal::Float(ref t) => Term::from(t.value()),
+            Literal::Integer(ref t) => Term::from(BigInt::from(t.value().clone())),
+            Literal::String { .. } | Literal::TripleQuotedString(_) => {
+                Term::string(&x.string_value().unwrap_or_default())
+            }
+        }),
+        Expr::Tuple(ref x) => x
+            .iter()
+            .map(normalise)
+            .collect::<Option<_>>()
+            .map(Term::Tuple),
+        Expr::List(ref x) => {
+            let elements = x.iter().map(normalise).collect::<Option<Vec<_>>>()?;
+            Some(match x.tail().map(normalise) {
+                None => Term::List(elements),
+                Some(tail) => match tail? {
+                    Term::List(rest) => Term::List(elements.into_iter().chain(rest).collect()),
+                    Term::ImproperList(rest, t) => {
+                        Term::ImproperList(elements.into_iter().chain(rest).collect(), t)
+                    }
+                    t => Term::ImproperList(elements, Box::new(t)),
+                },
+            })
+        }
+        Expr::Map(ref x) => x
+            .iter()
+            .map(|f| {
+                if f._relation.value() != Symbol::DoubleRightArrow {
+                    return None;
+                }
+                Some((normalise(&f.key)?, normalise(&f.value)?))
+            })
+            .collect::<Option<_>>()
+            .map(Term::Map),
+        Expr::Bits(ref x) => {
+            let mut bytes = Vec::new();
+            for e in x.elements.iter().flat_map(|e| e.iter()) {
+                if e.size.is_some() || e.type_specs.is_some() {
+                    return None;
+                }
+                match e.element {
+                    Expr::Literal(Literal::String { .. })
+                    | Expr::Literal(Literal::TripleQuotedString(_)) => {
+                        let s = normalise(&e.element)?;
+                        for c in s.as_string()?.chars() {
+                            bytes.push(c as u8);
+                        }
+                    }
+                    Expr::Literal(Literal::Integer(ref t)) => {
+                        bytes.push(num::ToPrimitive::to_u8(t.value())?);
+                    }
+                    Expr::Literal(Literal::Char(ref t)) => bytes.push(t.value() as u8),
+                    _ => return None,
+                }
+            }
+            Some(Term::Binary(bytes))
+        }
+        Expr::Parenthesized(ref x) => normalise(&x.item),
+        Expr::UnaryOpCall(ref x) => {
+            let negate = match x.op.tokens()[0].text() {
+                "-" => true,
+                "+" => false,
+                _ => return None,
+            };
+            match normalise(&x.operand)? {
+                Term::Integer(i) if negate => Some(Term::Integer(-i)),
+                Term::Float(f) if negate => Some(Term::Float(-f)),
+                t @ Term::Integer(_) | t @ Term::Float(_) => Some(t),
+                _ => None,
+            }
+        }
+        Expr::BinaryOpCall(ref x) if x.op.tokens()[0].text() == "/" => match (&x.left, &x.right) {
+            (Expr::Literal(Literal::Atom(ref f)), Expr::Literal(Literal::Integer(ref a))) => {
+                Some(Term::Tuple(vec![
+                    Term::atom(f.value()),
+                    Term::from(BigInt::from(a.value().clone())),
+                ]))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
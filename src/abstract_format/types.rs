@@ -0,0 +1,249 @@
+use erl_tokenize::values::Symbol;
+
+use super::exprs::{binary_op_name, macro_call, record_name, unary_op_name};
+use super::{line, list, node, ToAbstract};
+use crate::cst::clauses::SpecClause;
+use crate::cst::commons::parts::Args;
+use crate::cst::types::parts::BitsSpec;
+use crate::cst::types::{Constraint, Fun};
+use crate::cst::Type;
+use crate::term::Term;
+
+/// Built-in types (i.e., `erl_internal:is_type/2`).
+const BUILTIN_TYPES: &[(&str, usize)] = &[
+    ("any", 0),
+    ("arity", 0),
+    ("atom", 0),
+    ("binary", 0),
+    ("bitstring", 0),
+    ("bool", 0),
+    ("boolean", 0),
+    ("byte", 0),
+    ("char", 0),
+    ("dynamic", 0),
+    ("float", 0),
+    ("function", 0),
+    ("identifier", 0),
+    ("integer", 0),
+    ("iodata", 0),
+    ("iolist", 0),
+    ("list", 0),
+    ("list", 1),
+    ("map", 0),
+    ("maybe_improper_list", 0),
+    ("maybe_improper_list", 2),
+    ("mfa", 0),
+    ("module", 0),
+    ("neg_integer", 0),
+    ("nil", 0),
+    ("no_return", 0),
+    ("node", 0),
+    ("non_neg_integer", 0),
+    ("none", 0),
+    ("nonempty_binary", 0),
+    ("nonempty_bitstring", 0),
+    ("nonempty_improper_list", 2),
+    ("nonempty_list", 0),
+    ("nonempty_list", 1),
+    ("nonempty_maybe_improper_list", 0),
+    ("nonempty_maybe_improper_list", 2),
+    ("nonempty_string", 0),
+    ("number", 0),
+    ("pid", 0),
+    ("port", 0),
+    ("pos_integer", 0),
+    ("reference", 0),
+    ("string", 0),
+    ("term", 0),
+    ("timeout", 0),
+    ("tuple", 0),
+];
+
+impl ToAbstract for Type {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Type::Literal(ref x) => x.to_abstract(),
+            Type::Variable(ref x) => x.to_abstract(),
+            Type::MacroCall(ref x) => macro_call(x),
+            Type::Annotated(ref x) => node(
+                "ann_type",
+                line(x),
+                vec![Term::List(vec![x.var.to_abstract(), x.ty.to_abstract()])],
+            ),
+            Type::Tuple(ref x) => type_node(
+                line(x),
+                "tuple",
+                list(x.elements.iter().flat_map(|e| e.iter())),
+            ),
+            Type::Map(ref x) => {
+                let fields = x.fields.iter().flat_map(|f| f.iter()).map(|f| {
+                    let tag = if f._relation.value() == Symbol::DoubleRightArrow {
+                        "map_field_assoc"
+                    } else {
+                        "map_field_exact"
+                    };
+                    let kv = Term::List(vec![f.key.to_abstract(), f.value.to_abstract()]);
+                    type_node(line(&f._relation), tag, kv)
+                });
+                type_node(line(x), "map", Term::List(fields.collect()))
+            }
+            Type::Record(ref x) => {
+                let mut args = vec![record_name(&x.name)];
+                args.extend(x.fields.iter().flat_map(|f| f.iter()).map(|f| {
+                    let kv = Term::List(vec![f.key.to_abstract(), f.value.to_abstract()]);
+                    type_node(line(f), "field_type", kv)
+                }));
+                type_node(line(x), "record", Term::List(args))
+            }
+            Type::List(ref x) => match x.element {
+                None => type_node(line(x), "nil", Term::List(Vec::new())),
+                Some(ref e) => {
+                    let name = if e.non_empty.is_some() {
+                        "nonempty_list"
+                    } else {
+                        "list"
+                    };
+                    type_node(line(x), name, list(Some(&e.element_type)))
+                }
+            },
+            Type::Bits(ref x) => {
+                let anno = line(x);
+                let (m, n) = match x.spec {
+                    None => (0u32.into(), 0u32.into()),
+                    Some(BitsSpec::Bytes(ref b)) => (b.size.value().clone(), 0u32.into()),
+                    Some(BitsSpec::Bits(ref b)) => (0u32.into(), b.size.value().clone()),
+                    Some(BitsSpec::BytesAndBits(ref b)) => {
+                        (b.byte.size.value().clone(), b.bit.size.value().clone())
+                    }
+                };
+                let integer = |i: num::BigUint| {
+                    node(
+                        "integer",
+                        anno.clone(),
+                        vec![Term::from(num::BigInt::from(i))],
+                    )
+                };
+                type_node(
+                    anno.clone(),
+                    "binary",
+                    Term::List(vec![integer(m), integer(n)]),
+                )
+            }
+            Type::Parenthesized(ref x) => node(
+                "paren_type",
+                line(&x.item),
+                vec![Term::List(vec![x.item.to_abstract()])],
+            ),
+            Type::TypeCall(ref x) => {
+                let anno = line(&x.name);
+                let name = x.name.value();
+                match x.module {
+                    Some(ref m) => node(
+                        "remote_type",
+                        line(&m._colon),
+                        vec![Term::List(vec![
+                            m.name.to_abstract(),
+                            x.name.to_abstract(),
+                            args(&x.args),
+                        ])],
+                    ),
+                    None if x.args.is_empty() && (name == "tuple" || name == "map") => {
+                        type_node(anno, name, Term::atom("any"))
+                    }
+                    None if BUILTIN_TYPES.contains(&(name, x.args.len())) => {
+                        type_node(anno, name, args(&x.args))
+                    }
+                    None => node("user_type", anno, vec![Term::atom(name), args(&x.args)]),
+                }
+            }
+            Type::UnaryOpCall(ref x) => node(
+                "op",
+                line(&x.op),
+                vec![unary_op_name(&x.op), x.operand.to_abstract()],
+            ),
+            Type::BinaryOpCall(ref x) => node(
+                "op",
+                line(&x.op),
+                vec![
+                    binary_op_name(&x.op),
+                    x.left.to_abstract(),
+                    x.right.to_abstract(),
+                ],
+            ),
+            Type::Fun(ref x) => x.to_abstract(),
+            Type::Range(ref x) => type_node(
+                line(&x._dot),
+                "range",
+                Term::List(vec![x.low.to_abstract(), x.high.to_abstract()]),
+            ),
+            Type::Union(ref x) => {
+                let mut members = Vec::new();
+                flatten_union(&x.left, &mut members);
+                flatten_union(&x.right, &mut members);
+                type_node(line(x), "union", Term::List(members))
+            }
+        }
+    }
+}
+impl ToAbstract for Fun {
+    fn to_abstract(&self) -> Term {
+        match *self {
+            Fun::Any(ref x) => type_node(line(x), "fun", Term::List(Vec::new())),
+            Fun::AnyArity(ref x) => {
+                let any = node("type", line(&x._args), vec![Term::atom("any")]);
+                let args = Term::List(vec![any, x.return_type.to_abstract()]);
+                type_node(line(x), "fun", args)
+            }
+            Fun::Normal(ref x) => fun_type(line(x), &x.args, &x.return_type),
+        }
+    }
+}
+impl ToAbstract for SpecClause {
+    fn to_abstract(&self) -> Term {
+        let anno = line(self);
+        let fun = fun_type(anno.clone(), &self.args, &self.return_type);
+        match self.constraints {
+            None => fun,
+            Some(ref c) => {
+                let constraints = c.constraints.iter().map(constraint).collect();
+                type_node(
+                    anno,
+                    "bounded_fun",
+                    Term::List(vec![fun, Term::List(constraints)]),
+                )
+            }
+        }
+    }
+}
+
+/// Makes `{type, Anno, Name, Args}`.
+fn type_node(anno: Term, name: &str, args: Term) -> Term {
+    node("type", anno, vec![Term::atom(name), args])
+}
+
+fn args(x: &Args<Type>) -> Term {
+    list(x.args.iter().flat_map(|a| a.iter()))
+}
+
+fn fun_type(anno: Term, params: &Args<Type>, return_type: &Type) -> Term {
+    let product = type_node(line(params), "product", args(params));
+    let args = Term::List(vec![product, return_type.to_abstract()]);
+    type_node(anno, "fun", args)
+}
+
+fn flatten_union(ty: &Type, members: &mut Vec<Term>) {
+    if let Type::Union(ref x) = *ty {
+        flatten_union(&x.left, members);
+        flatten_union(&x.right, members);
+    } else {
+        members.push(ty.to_abstract());
+    }
+}
+
+/// Converts `Var :: Type` (or the old style `is_subtype(Var, Type)`) in a `when` clause.
+fn constraint(x: &Constraint) -> Term {
+    let anno = line(x);
+    let is_subtype = node("atom", anno.clone(), vec![Term::atom("is_subtype")]);
+    let args = Term::List(vec![x.var().to_abstract(), x.bound().to_abstract()]);
+    type_node(anno, "constraint", Term::List(vec![is_subtype, args]))
+}
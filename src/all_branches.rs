@@ -0,0 +1,239 @@
+use erl_tokenize::values::{Keyword, Symbol};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::traits::is_symbol;
+use crate::Span;
+
+/// Branch of a conditional compilation block (`-ifdef`, `-ifndef` or `-if` ... `-endif`).
+#[derive(Debug, Clone)]
+pub struct Branch {
+    /// The directive which starts this branch (`ifdef`, `ifndef`, `if`, `elif` or `else`).
+    pub directive: String,
+
+    /// The tokens of the condition (e.g., `TEST` of `-ifdef(TEST).`), which are empty for `-else`.
+    pub condition: Vec<LexicalToken>,
+
+    /// The span from the start of the directive to the end of the directive which ends this branch.
+    pub span: Span,
+
+    /// The index of the block which contains this branch (the branches of a block share the index).
+    pub block: usize,
+}
+impl Branch {
+    /// Returns `true` if `position` is within this branch.
+    pub fn contains(&self, position: &Position) -> bool {
+        position.filepath() == self.span.start.filepath()
+            && self.span.contains_offset(position.offset())
+    }
+}
+
+/// Shared log of the branches read by `AllBranches`.
+#[derive(Debug, Clone, Default)]
+pub struct Branches(Arc<Mutex<Vec<Branch>>>);
+impl Branches {
+    /// Returns the branches in the order of their end.
+    pub fn to_vec(&self) -> Vec<Branch> {
+        self.0.lock().expect("Never fails").clone()
+    }
+
+    /// Returns the branches which contain `position` (i.e., the conditions governing the code there),
+    /// from the outermost to the innermost.
+    pub fn conditions_at(&self, position: &Position) -> Vec<Branch> {
+        let mut branches = self
+            .0
+            .lock()
+            .expect("Never fails")
+            .iter()
+            .filter(|b| b.contains(position))
+            .cloned()
+            .collect::<Vec<_>>();
+        branches.sort_by_key(|b| b.span.start.offset());
+        branches
+    }
+}
+
+/// Token stream which removes conditional compilation directives
+/// (`-ifdef`, `-ifndef`, `-if`, `-elif`, `-else` and `-endif`),
+/// so that every branch of the conditional blocks is parsed (e.g., code which is compiled out in tests).
+///
+/// This is placed between a lexer (or an `IncludeExpander`) and `erl_pp::Preprocessor`,
+/// which then expands macros in all branches.
+/// The removed directives are recorded in `Branches`, which tells the condition governing each form.
+/// Note that code of exclusive branches may conflict (e.g., a function defined in both branches),
+/// and that the macros defined in every branch are visible to the following code.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_parse::{AllBranches, Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::{Lexer, PositionRange};
+///
+/// let text = "-ifdef(TEST).\nf() -> test.\n-else.\nf() -> prod.\n-endif.";
+/// let tokens = AllBranches::new(Lexer::new(text));
+/// let branches = tokens.branches();
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+/// let module: ModuleDecl = parser.parse().unwrap();
+/// assert_eq!(module.forms.len(), 2);
+///
+/// let conditions = branches.conditions_at(&module.forms[1].start_position());
+/// assert_eq!(conditions[0].directive, "else");
+/// ```
+#[derive(Debug)]
+pub struct AllBranches<T> {
+    inner: T,
+    pending: VecDeque<LexicalToken>,
+    pending_error: Option<erl_tokenize::Error>,
+    form_start: bool,
+    last_position: Option<Position>,
+
+    /// The branches being read: their block indices, directives, conditions and start positions.
+    open: Vec<(usize, String, Vec<LexicalToken>, Position)>,
+    blocks: usize,
+    branches: Branches,
+}
+impl<T> AllBranches<T>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+{
+    pub fn new(inner: T) -> Self {
+        AllBranches {
+            inner,
+            pending: VecDeque::new(),
+            pending_error: None,
+            form_start: true,
+            last_position: None,
+            open: Vec::new(),
+            blocks: 0,
+            branches: Branches::default(),
+        }
+    }
+
+    /// Returns the log of the branches, which is filled while this stream is read.
+    pub fn branches(&self) -> Branches {
+        self.branches.clone()
+    }
+
+    fn next_raw(&mut self) -> Option<erl_tokenize::Result<LexicalToken>> {
+        if let Some(t) = self.pending.pop_front() {
+            return Some(Ok(t));
+        }
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+        self.inner.next()
+    }
+
+    /// Reads the rest of a conditional directive following `-`,
+    /// and returns the directive name and its condition tokens.
+    fn try_directive(&mut self) -> Option<(String, Vec<LexicalToken>, Position)> {
+        let name = match self.next_raw() {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => {
+                self.pending_error = Some(e);
+                return None;
+            }
+            None => return None,
+        };
+        let directive = match name {
+            LexicalToken::Keyword(ref t) if t.value() == Keyword::If => "if",
+            LexicalToken::Atom(ref t)
+                if matches!(t.value(), "ifdef" | "ifndef" | "elif" | "else" | "endif") =>
+            {
+                t.value()
+            }
+            _ => {
+                self.pending.push_back(name);
+                return None;
+            }
+        }
+        .to_owned();
+        let mut tokens = vec![name];
+        loop {
+            match self.next_raw() {
+                Some(Ok(t)) => {
+                    let is_dot = is_symbol(&t, Symbol::Dot);
+                    tokens.push(t);
+                    if is_dot {
+                        break;
+                    }
+                }
+                Some(Err(e)) => {
+                    self.pending_error = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+        let end = tokens[tokens.len() - 1].end_position();
+        let condition = if tokens.len() > 4 {
+            tokens[2..tokens.len() - 2].to_vec()
+        } else {
+            Vec::new()
+        };
+        Some((directive, condition, end))
+    }
+
+    fn close_branch(&mut self, end: Position) -> Option<usize> {
+        let (block, directive, condition, start) = self.open.pop()?;
+        self.branches.0.lock().expect("Never fails").push(Branch {
+            directive,
+            condition,
+            span: Span { start, end },
+            block,
+        });
+        Some(block)
+    }
+}
+impl<T> Iterator for AllBranches<T>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+{
+    type Item = erl_tokenize::Result<LexicalToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = match self.next_raw() {
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(t)) => t,
+                None => {
+                    while let Some(end) = self.last_position.clone() {
+                        if self.close_branch(end).is_none() {
+                            break;
+                        }
+                    }
+                    return None;
+                }
+            };
+            let form_start = self.form_start;
+            self.form_start = is_symbol(&token, Symbol::Dot);
+            if form_start && is_symbol(&token, Symbol::Hyphen) {
+                if let Some((directive, condition, end)) = self.try_directive() {
+                    let start = token.start_position();
+                    self.last_position = Some(end.clone());
+                    self.form_start = true;
+                    match directive.as_str() {
+                        "ifdef" | "ifndef" | "if" => {
+                            self.open.push((self.blocks, directive, condition, start));
+                            self.blocks += 1;
+                        }
+                        "elif" | "else" => {
+                            if let Some(block) = self.close_branch(end) {
+                                self.open.push((block, directive, condition, start));
+                            }
+                        }
+                        _ => {
+                            self.close_branch(end);
+                        }
+                    }
+                    continue;
+                }
+            }
+            self.last_position = Some(token.end_position());
+            return Some(Ok(token));
+        }
+    }
+}
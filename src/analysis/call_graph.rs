@@ -0,0 +1,185 @@
+use num::ToPrimitive;
+use std::fmt;
+
+use super::{resolve_call, ModuleIndex, Resolution};
+use crate::cst::commons::{AtomOrVariable, IntegerOrVariable};
+use crate::cst::exprs::{self, Fun};
+use crate::cst::{Expr, Form, GuardTest, Literal, ModuleDecl, Node};
+use crate::Span;
+
+/// Function identified by its module, name and arity.
+///
+/// `module` is `None` for the functions of a module without `-module` attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Mfa {
+    pub module: Option<String>,
+    pub name: String,
+    pub arity: usize,
+}
+impl fmt::Display for Mfa {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref m) = self.module {
+            write!(f, "{}:", m)?;
+        }
+        write!(f, "{}/{}", self.name, self.arity)
+    }
+}
+
+/// Target of a call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Callee {
+    Function(Mfa),
+
+    /// The target is only known at runtime
+    /// (e.g., `F(X)`, `M:f(X)` or `apply(M, F, Args)` with non-literal arguments).
+    Unknown,
+}
+
+/// Call (or `fun` reference) made in a function declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallEdge {
+    pub caller: Mfa,
+    pub callee: Callee,
+
+    /// The span of the call expression (or the `fun` reference).
+    pub span: Span,
+}
+
+/// Extracts the calls made in the function declarations of `module`, in the order of appearance.
+///
+/// Calls in anonymous functions are attributed to the enclosing declaration.
+/// Local calls are resolved to the module itself, the module given by `-import`,
+/// or `erlang` for the auto-imported BIFs (e.g., `length/1`) as `resolve_call` does.
+/// `apply/3` (and `erlang:apply/3`) calls whose module, function and argument list are literals
+/// are resolved to the applied functions, and the other `apply` calls are regarded as unknown.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::analysis::{call_graph, Callee};
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-module(foo).\nf(X) -> g(X), lists:map(fun h/1, X), X().\ng(_) -> ok.\nh(_) -> ok.";
+/// let module: ModuleDecl = Parser::new(TokenReader::new(Lexer::new(text))).parse().unwrap();
+///
+/// let callees: Vec<String> = call_graph(&module)
+///     .into_iter()
+///     .map(|e| match e.callee {
+///         Callee::Function(f) => f.to_string(),
+///         Callee::Unknown => "?".to_owned(),
+///     })
+///     .collect();
+/// assert_eq!(callees, ["foo:g/1", "lists:map/2", "foo:h/1", "?"]);
+/// ```
+pub fn call_graph(module: &ModuleDecl) -> Vec<CallEdge> {
+    let index = ModuleIndex::new(module);
+    let mut edges = Vec::new();
+    for form in &module.forms {
+        let decl = match *form {
+            Form::FunDecl(ref x) => x,
+            _ => continue,
+        };
+        let caller = Mfa {
+            module: index.module.clone(),
+            name: decl.name().to_owned(),
+            arity: decl.arity(),
+        };
+        Node::Form(form).walk(|node| {
+            let (callee, span) = match node {
+                Node::Expr(Expr::FunCall(x)) => (call(&index, x), Span::of(&**x)),
+                Node::Expr(Expr::Fun(x)) => match **x {
+                    Fun::Defined(ref x) => (fun_ref(&index, x), Span::of(x)),
+                    _ => return,
+                },
+                Node::GuardTest(GuardTest::FunCall(x)) => {
+                    let module = x.module.as_ref().map(|m| m.name.value());
+                    let callee = resolve(&index, module, x.name.value(), x.args.len());
+                    (callee, Span::of(&**x))
+                }
+                _ => return,
+            };
+            edges.push(CallEdge {
+                caller: caller.clone(),
+                callee,
+                span,
+            });
+        });
+    }
+    edges
+}
+
+fn call(index: &ModuleIndex, x: &exprs::FunCall) -> Callee {
+    let module = match x.module {
+        None => None,
+        Some(ref m) => match atom(&m.name) {
+            None => return Callee::Unknown,
+            Some(m) => Some(m),
+        },
+    };
+    let name = match atom(&x.name) {
+        None => return Callee::Unknown,
+        Some(name) => name,
+    };
+    let arity = x.args.len();
+    let callee = resolve(index, module, name, arity);
+    if !matches!(callee, Callee::Function(ref f) if f.module.as_deref() == Some("erlang") && f.name == "apply")
+    {
+        return callee;
+    }
+
+    // `apply(Module, Function, [Arg...])`
+    let args: Vec<&Expr> = x.args.iter().collect();
+    if let [module, function, Expr::List(ref list)] = args[..] {
+        if let (Some(module), Some(name), None) = (atom(module), atom(function), list.tail()) {
+            return Callee::Function(Mfa {
+                module: Some(module.to_owned()),
+                name: name.to_owned(),
+                arity: list.iter().count(),
+            });
+        }
+    }
+    Callee::Unknown
+}
+
+fn fun_ref(index: &ModuleIndex, x: &exprs::DefinedFun) -> Callee {
+    let module = match x.module {
+        None => None,
+        Some(ref m) => match m.name {
+            AtomOrVariable::Atom(ref t) => Some(t.value()),
+            AtomOrVariable::Variable(_) => return Callee::Unknown,
+        },
+    };
+    let (name, arity) = match (&x.fun.name, &x.fun.arity) {
+        (AtomOrVariable::Atom(ref n), IntegerOrVariable::Integer(ref a)) => {
+            (n.value(), a.value().to_usize().unwrap_or(usize::MAX))
+        }
+        _ => return Callee::Unknown,
+    };
+    resolve(index, module, name, arity)
+}
+
+/// Resolves the module of a call of `name/arity` (prefixed by `module` if remote).
+fn resolve(index: &ModuleIndex, module: Option<&str>, name: &str, arity: usize) -> Callee {
+    let module = match module {
+        Some(m) => Some(m.to_owned()),
+        None => match resolve_call(index, name, arity) {
+            Resolution::Imported { module } => Some(module),
+            Resolution::AutoImported => Some("erlang".to_owned()),
+            Resolution::Local | Resolution::Undefined => index.module.clone(),
+        },
+    };
+    Callee::Function(Mfa {
+        module,
+        name: name.to_owned(),
+        arity,
+    })
+}
+
+fn atom(expr: &Expr) -> Option<&str> {
+    match *expr {
+        Expr::Literal(Literal::Atom(ref t)) => Some(t.value()),
+        _ => None,
+    }
+}
@@ -0,0 +1,115 @@
+use erl_tokenize::LexicalToken;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::cst::{Expr, ModuleDecl, Node};
+use crate::traits::CollectTokens;
+use crate::Span;
+
+/// Code fragments (expressions) which are the same up to the renaming of variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneGroup {
+    /// The structural hash of the fragments (see `expr_hash`).
+    pub hash: u64,
+
+    /// The number of the tokens of each fragment.
+    pub size: usize,
+
+    /// The spans of the fragments in the order of the modules and the positions.
+    pub spans: Vec<Span>,
+}
+
+/// Returns the structural hash of `expr`.
+///
+/// The hash ignores the layout (positions, whitespaces and comments) of the expression,
+/// and its variables are alpha-renamed in the order of their first occurrences,
+/// so `{X, Y, X}` and `{A, B, A}` have the same hash while `{A, A, B}` has a different one.
+pub fn expr_hash(expr: &Expr) -> u64 {
+    hash_key(&normalize(&expr.tokens()))
+}
+
+/// Finds the expressions which are duplicated in `modules` (e.g., the modules of a workspace).
+///
+/// Expressions of fewer than `min_size` tokens are ignored.
+/// Only maximal clones are reported: a group whose fragments are all within the fragments of
+/// a larger group is omitted.
+/// The groups are sorted by their sizes in descending order.
+pub fn find_clones<'a, I>(modules: I, min_size: usize) -> Vec<CloneGroup>
+where
+    I: IntoIterator<Item = &'a ModuleDecl>,
+{
+    let mut keys = Vec::new();
+    let mut fragments: HashMap<Vec<String>, Vec<Span>> = HashMap::new();
+    for module in modules {
+        for form in Node::forms(module) {
+            form.walk(|node| {
+                if let Node::Expr(x) = node {
+                    let tokens = x.tokens();
+                    if tokens.len() >= min_size.max(1) {
+                        let key = normalize(&tokens);
+                        let spans = fragments.entry(key.clone()).or_default();
+                        if spans.is_empty() {
+                            keys.push(key);
+                        }
+                        spans.push(Span::of(x));
+                    }
+                }
+            });
+        }
+    }
+
+    let mut groups = keys
+        .into_iter()
+        .filter_map(|key| {
+            let spans = fragments.remove(&key)?;
+            if spans.len() < 2 {
+                return None;
+            }
+            Some(CloneGroup {
+                hash: hash_key(&key),
+                size: key.len(),
+                spans,
+            })
+        })
+        .collect::<Vec<_>>();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.size));
+
+    let mut reported: Vec<Span> = Vec::new();
+    groups.retain(|g| {
+        let is_subsumed = g
+            .spans
+            .iter()
+            .all(|s| reported.iter().any(|r| is_within(s, r)));
+        if !is_subsumed {
+            reported.extend(g.spans.iter().cloned());
+        }
+        !is_subsumed
+    });
+    groups
+}
+
+/// Returns the texts of `tokens` with the variables renamed to `V0`, `V1`, ...
+fn normalize(tokens: &[LexicalToken]) -> Vec<String> {
+    let mut variables: HashMap<&str, usize> = HashMap::new();
+    tokens
+        .iter()
+        .map(|t| match t.as_variable_token() {
+            Some(v) if v.value() != "_" => {
+                let n = variables.len();
+                format!("V{}", variables.entry(v.value()).or_insert(n))
+            }
+            _ => t.text().to_owned(),
+        })
+        .collect()
+}
+
+fn hash_key(key: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn is_within(inner: &Span, outer: &Span) -> bool {
+    inner.start.filepath() == outer.start.filepath() && outer.contains(inner)
+}
@@ -0,0 +1,81 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken};
+
+use crate::traits::is_symbol;
+use crate::{Error, Result};
+
+/// Build-graph dependencies of a module, as extracted by `scan_dependencies`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependencies {
+    pub module: Option<String>,
+    pub behaviours: Vec<String>,
+    pub includes: Vec<String>,
+    pub include_libs: Vec<String>,
+    pub imports: Vec<String>,
+    pub parse_transforms: Vec<String>,
+}
+
+/// Extracts the dependencies of the module `text` without preprocessing it or building a CST.
+///
+/// Only the tokens of attribute forms are inspected; function bodies are skipped
+/// token-by-token until the terminating `.`, so this is much faster than a full parse
+/// and also works on files which contain syntax errors inside functions.
+pub fn scan_dependencies(text: &str) -> Result<Dependencies> {
+    let mut deps = Dependencies::default();
+    let mut form = Vec::new();
+    for token in Lexer::new(text) {
+        let token = track!(token.map_err(Error::from))?;
+        if is_symbol(&token, Symbol::Dot) {
+            handle_form(&form, &mut deps);
+            form.clear();
+        } else if form.len() < 2 || is_attr_form(&form) {
+            form.push(token);
+        }
+    }
+    Ok(deps)
+}
+
+fn handle_form(form: &[LexicalToken], deps: &mut Dependencies) {
+    if !is_attr_form(form) {
+        return;
+    }
+    let name = form[1].as_atom_token().map_or("", |t| t.value());
+    let first_arg = form.get(2).and_then(|t| {
+        if is_symbol(t, Symbol::OpenParen) {
+            form.get(3)
+        } else {
+            Some(t)
+        }
+    });
+    let atom_arg = first_arg
+        .and_then(LexicalToken::as_atom_token)
+        .map(|t| t.value().to_owned());
+    let string_arg = first_arg
+        .and_then(LexicalToken::as_string_token)
+        .map(|t| t.value().to_owned());
+    match name {
+        "module" => deps.module = atom_arg,
+        "behaviour" | "behavior" => deps.behaviours.extend(atom_arg),
+        "include" => deps.includes.extend(string_arg),
+        "include_lib" => deps.include_libs.extend(string_arg),
+        "import" => deps.imports.extend(atom_arg),
+        "compile" => {
+            // Looks for `{parse_transform, Module}` anywhere in the options
+            for w in form.windows(4) {
+                let is_transform = is_symbol(&w[0], Symbol::OpenBrace)
+                    && w[1].as_atom_token().map(|t| t.value()) == Some("parse_transform")
+                    && is_symbol(&w[2], Symbol::Comma);
+                if is_transform {
+                    if let Some(m) = w[3].as_atom_token() {
+                        deps.parse_transforms.push(m.value().to_owned());
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_attr_form(form: &[LexicalToken]) -> bool {
+    form.len() >= 2 && is_symbol(&form[0], Symbol::Hyphen) && form[1].as_atom_token().is_some()
+}
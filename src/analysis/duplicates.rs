@@ -0,0 +1,242 @@
+use erl_pp::Directive;
+use erl_tokenize::{Position, PositionRange};
+use num::{BigInt, ToPrimitive};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use crate::cst::commons::parts::{MapField, Sequence, UnaryOp};
+use crate::cst::commons::{List, Record};
+use crate::cst::{Expr, Form, GuardTest, Literal, ModuleDecl, Node};
+use crate::messages::Message;
+use crate::term::Term;
+use crate::{Diagnostic, Span};
+
+/// Reports functions, records, types and export entries which are defined more than once in `module`.
+///
+/// Function declarations are considered duplicated when two separate `FunDecl` forms
+/// (i.e., clause groups split by other forms) have the same name and arity.
+pub fn find_duplicates(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let mut funs = Definitions::new("duplicate_function");
+    let mut records = Definitions::new("duplicate_record");
+    let mut types = Definitions::new("duplicate_type");
+    let mut exports = Definitions::new("duplicate_export");
+    for form in &module.forms {
+        match *form {
+            Form::FunDecl(ref f) => {
+                let name = format!("{}/{}", f.name(), f.arity());
+                funs.insert(name, &f.clauses.item.name);
+            }
+            Form::RecordDecl(ref r) => {
+                records.insert(r.record_name.value().to_owned(), &r.record_name);
+            }
+            Form::TypeDecl(ref t) => {
+                let name = format!("{}/{}", t.name(), t.arity());
+                types.insert(name, &t.type_name);
+            }
+            Form::ExportAttr(ref e) => {
+                for export in e.exports.elements.iter().flat_map(|s| s.iter()) {
+                    let arity = export.arity.value().to_usize().unwrap_or(usize::MAX);
+                    let name = format!("{}/{}", export.name.value(), arity);
+                    exports.insert(name, export);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut diagnostics = Vec::new();
+    diagnostics.extend(funs.diagnostics);
+    diagnostics.extend(records.diagnostics);
+    diagnostics.extend(types.diagnostics);
+    diagnostics.extend(exports.diagnostics);
+    diagnostics.sort_by(|a, b| a.start_position.cmp(&b.start_position));
+    diagnostics
+}
+
+/// Reports macros which are defined more than once with the same name and arity.
+///
+/// `directives` is typically the result of `erl_pp::Preprocessor::directives()`.
+/// Redefinitions preceded by an `-undef` of the same macro are not reported.
+pub fn find_duplicate_macros(directives: &BTreeMap<Position, Directive>) -> Vec<Diagnostic> {
+    let mut macros = Definitions::new("duplicate_macro");
+    for directive in directives.values() {
+        match *directive {
+            Directive::Define(ref d) => {
+                let name = match d.variables {
+                    None => d.name.value().to_owned(),
+                    Some(ref v) => format!("{}/{}", d.name.value(), v.len()),
+                };
+                macros.insert(name, d);
+            }
+            Directive::Undef(ref u) => {
+                let prefix = format!("{}/", u.name.value());
+                macros
+                    .first
+                    .retain(|name, _| name != u.name.value() && !name.starts_with(&prefix));
+            }
+            _ => {}
+        }
+    }
+    macros.diagnostics
+}
+
+#[derive(Debug)]
+struct Definitions {
+    code: &'static str,
+    warning: bool,
+    related: &'static str,
+    first: BTreeMap<String, Span>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl Definitions {
+    fn new(code: &'static str) -> Self {
+        Definitions {
+            code,
+            warning: false,
+            related: "first_definition",
+            first: BTreeMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+    fn warning(mut self, related: &'static str) -> Self {
+        self.warning = true;
+        self.related = related;
+        self
+    }
+    fn insert<R: PositionRange>(&mut self, key: String, range: &R) {
+        match self.first.entry(key) {
+            Entry::Vacant(e) => {
+                e.insert(Span::of(range));
+            }
+            Entry::Occupied(e) => {
+                // The message identifiers are the same as the diagnostic codes.
+                let message = Message::new(self.code).arg("name", e.key().as_str());
+                let d = if self.warning {
+                    Diagnostic::warning(self.code, message, range)
+                } else {
+                    Diagnostic::error(self.code, message, range)
+                };
+                let d = d.with_related(Message::new(self.related), e.get());
+                self.diagnostics.push(d);
+            }
+        }
+    }
+}
+
+/// Reports duplicate literal keys in map constructions and updates,
+/// and duplicate fields in record constructions and updates (erlc warns about both).
+///
+/// A key is regarded as literal if it consists only of literals, tuples and proper lists
+/// (e.g., `#{a => 1, {b, 2} => 2}`).
+pub fn find_duplicate_keys(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let mut finder = DuplicateKeyFinder {
+        diagnostics: Vec::new(),
+    };
+    for form in Node::forms(module) {
+        form.walk(|node| finder.check(node));
+    }
+    finder.diagnostics
+}
+
+struct DuplicateKeyFinder {
+    diagnostics: Vec<Diagnostic>,
+}
+impl DuplicateKeyFinder {
+    fn check(&mut self, node: Node) {
+        match node {
+            Node::Expr(Expr::Map(x)) => self.check_map(&x.fields, expr_term),
+            Node::Expr(Expr::MapUpdate(x)) => self.check_map(&x.update.fields, expr_term),
+            Node::Expr(Expr::Record(x)) => self.check_record(x),
+            Node::Expr(Expr::RecordUpdate(x)) => self.check_record(&x.update),
+            Node::GuardTest(GuardTest::Map(x)) => self.check_map(&x.fields, guard_test_term),
+            Node::GuardTest(GuardTest::Record(x)) => self.check_record(x),
+            _ => {}
+        }
+    }
+    fn check_map<T, F>(&mut self, fields: &Option<Sequence<MapField<T>>>, key_term: F)
+    where
+        T: PositionRange,
+        F: Fn(&T) -> Option<Term>,
+    {
+        let mut keys = Definitions::new("duplicate_map_key").warning("first_occurrence");
+        for field in fields.iter().flat_map(|f| f.iter()) {
+            if let Some(term) = key_term(&field.key) {
+                keys.insert(term.to_string(), &field.key);
+            }
+        }
+        self.diagnostics.extend(keys.diagnostics);
+    }
+    fn check_record<T>(&mut self, record: &Record<T>) {
+        let mut keys = Definitions::new("duplicate_record_field").warning("first_occurrence");
+        for field in record.fields.iter().flat_map(|f| f.iter()) {
+            keys.insert(field.key.value().to_owned(), &field.key);
+        }
+        self.diagnostics.extend(keys.diagnostics);
+    }
+}
+fn literal_term(literal: &Literal) -> Term {
+    match *literal {
+        Literal::Atom(ref t) => Term::atom(t.value()),
+        Literal::Char(ref t) => Term::from(t.value() as u32),
+        Literal::Float(ref t) => Term::Float(t.value()),
+        Literal::Integer(ref t) => Term::from(BigInt::from(t.value().clone())),
+        Literal::String { .. } | Literal::TripleQuotedString(_) => {
+            Term::string(&literal.string_value().unwrap_or_default())
+        }
+    }
+}
+
+fn negate(term: Term) -> Option<Term> {
+    match term {
+        Term::Integer(x) => Some(Term::Integer(-x)),
+        Term::Float(x) => Some(Term::Float(-x)),
+        _ => None,
+    }
+}
+
+fn list_terms<T, F>(list: &List<T>, f: F) -> Option<Term>
+where
+    F: Fn(&T) -> Option<Term>,
+{
+    if !list.is_proper_list() {
+        return None;
+    }
+    list.iter().map(f).collect::<Option<_>>().map(Term::List)
+}
+
+fn expr_term(expr: &Expr) -> Option<Term> {
+    match *expr {
+        Expr::Literal(ref x) => Some(literal_term(x)),
+        Expr::Tuple(ref x) => x
+            .iter()
+            .map(expr_term)
+            .collect::<Option<_>>()
+            .map(Term::Tuple),
+        Expr::List(ref x) => list_terms(x, expr_term),
+        Expr::Parenthesized(ref x) => expr_term(&x.item),
+        Expr::UnaryOpCall(ref x) => match x.op {
+            UnaryOp::Plus(_) => expr_term(&x.operand),
+            UnaryOp::Minus(_) => expr_term(&x.operand).and_then(negate),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn guard_test_term(test: &GuardTest) -> Option<Term> {
+    match *test {
+        GuardTest::Literal(ref x) => Some(literal_term(x)),
+        GuardTest::Tuple(ref x) => x
+            .iter()
+            .map(guard_test_term)
+            .collect::<Option<_>>()
+            .map(Term::Tuple),
+        GuardTest::List(ref x) => list_terms(x, guard_test_term),
+        GuardTest::Parenthesized(ref x) => guard_test_term(&x.item),
+        GuardTest::UnaryOpCall(ref x) => match x.op {
+            UnaryOp::Plus(_) => guard_test_term(&x.operand),
+            UnaryOp::Minus(_) => guard_test_term(&x.operand).and_then(negate),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::workspace::{FileIndex, Location, WorkspaceIndex};
+
+const ELIXIR_PREFIX: &str = "Elixir.";
+
+/// Returns the Elixir-style name of the module `atom` (e.g., `Enum` for `'Elixir.Enum'`).
+///
+/// Returns `None` if `atom` is not the name of an Elixir module.
+pub fn elixir_module_name(atom: &str) -> Option<&str> {
+    atom.strip_prefix(ELIXIR_PREFIX).filter(|s| !s.is_empty())
+}
+
+/// A call (or `fun` reference) from Erlang code to a function defined in an Elixir module.
+///
+/// `Display` formats it in the Elixir style (e.g., `Enum.map/2`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElixirCall {
+    /// Elixir-style module name (e.g., `Enum` or `MyApp.Repo`).
+    pub module: String,
+    pub name: String,
+    pub arity: usize,
+    pub location: Location,
+}
+impl fmt::Display for ElixirCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}/{}", self.module, self.name, self.arity)
+    }
+}
+
+/// Returns the calls into Elixir modules made in the file indexed as `index`.
+pub fn find_elixir_calls(index: &FileIndex) -> Vec<ElixirCall> {
+    index
+        .calls
+        .iter()
+        .filter_map(|c| {
+            let module = elixir_module_name(c.module.as_ref()?)?;
+            Some(ElixirCall {
+                module: module.to_owned(),
+                name: c.name.clone(),
+                arity: c.arity,
+                location: c.location,
+            })
+        })
+        .collect()
+}
+
+/// Returns the Elixir modules used by each Erlang module in `index`.
+///
+/// Files without a `-module` attribute are keyed by their paths.
+/// Modules which do not use Elixir are omitted.
+pub fn elixir_dependencies(index: &WorkspaceIndex) -> BTreeMap<String, BTreeSet<String>> {
+    let mut deps = BTreeMap::new();
+    for (path, file) in index.files() {
+        let used = find_elixir_calls(file)
+            .into_iter()
+            .map(|c| c.module)
+            .collect::<BTreeSet<_>>();
+        if used.is_empty() {
+            continue;
+        }
+        let key = file
+            .module
+            .clone()
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        deps.entry(key).or_insert_with(BTreeSet::new).extend(used);
+    }
+    deps
+}
@@ -0,0 +1,857 @@
+use num::{BigInt, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::ast::{
+    BinaryOp, Clause, Expr, ExprKind, Function, Literal, MapField, Module, Pattern, PatternKind,
+    UnaryOp,
+};
+use crate::term::Term;
+
+/// Reason why `Evaluator` could not compute a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// The fuel has run out.
+    OutOfFuel,
+
+    /// The nesting of function calls exceeds the maximum depth.
+    TooDeep,
+
+    /// The expression contains a construct which is not supported (or not side-effect-free),
+    /// such as a `receive`, a remote call or a free variable.
+    Unsupported,
+
+    /// The evaluation raised an error with the given reason (e.g., `badarith`).
+    Exception(Term),
+}
+
+type EvalResult<T> = Result<T, EvalError>;
+type Env = HashMap<String, Term>;
+
+/// Experimental evaluator of side-effect-free functions over literal arguments.
+///
+/// It supports literals, tuples, lists, maps, operators, pattern matching (`=`, `case`, `if` and
+/// function clauses), calls of the functions in the module, and a few pure BIFs.
+/// Every evaluation step consumes a unit of fuel (and building large values consumes more),
+/// so the evaluation of a non-terminating or expensive function fails with `OutOfFuel`.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::analysis::{EvalError, Evaluator};
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_parse::term::Term;
+/// use erl_parse::{ast, Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "fact(0) -> 1; fact(N) when N > 0 -> N * fact(N - 1).\nloop(X) -> loop(X).";
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+/// let module: ModuleDecl = parser.parse().unwrap();
+/// let module = ast::Module::from(&module);
+///
+/// let mut evaluator = Evaluator::new(&module);
+/// assert_eq!(evaluator.call("fact", &[Term::from(5u32)]), Ok(Term::from(120u32)));
+/// assert_eq!(
+///     evaluator.call("fact", &[Term::from(-1i64)]),
+///     Err(EvalError::Exception(Term::atom("function_clause")))
+/// );
+///
+/// let mut evaluator = Evaluator::new(&module).fuel(1000).max_depth(10_000);
+/// assert_eq!(evaluator.call("loop", &[Term::atom("a")]), Err(EvalError::OutOfFuel));
+/// ```
+#[derive(Debug)]
+pub struct Evaluator<'a> {
+    functions: HashMap<(&'a str, usize), &'a Function>,
+    fuel: usize,
+    max_depth: usize,
+    depth: usize,
+}
+impl<'a> Evaluator<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        let mut functions = HashMap::new();
+        for f in module.functions() {
+            functions.entry((f.name.as_str(), f.arity)).or_insert(f);
+        }
+        Evaluator {
+            functions,
+            fuel: 10_000,
+            max_depth: 100,
+            depth: 0,
+        }
+    }
+
+    /// Sets the amount of fuel (the default is `10_000`).
+    pub fn fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Sets the maximum depth of nested function calls (the default is `100`).
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Returns the remaining fuel.
+    pub fn remaining_fuel(&self) -> usize {
+        self.fuel
+    }
+
+    /// Calls the function `name/args.len()` of the module.
+    pub fn call(&mut self, name: &str, args: &[Term]) -> EvalResult<Term> {
+        let f = *self
+            .functions
+            .get(&(name, args.len()))
+            .ok_or(EvalError::Unsupported)?;
+        self.call_function(f, args.to_vec())
+    }
+
+    /// Evaluates a closed expression (i.e., one without free variables).
+    pub fn eval(&mut self, expr: &Expr) -> EvalResult<Term> {
+        self.eval_expr(expr, &mut Env::new())
+    }
+
+    fn consume(&mut self, amount: usize) -> EvalResult<()> {
+        if self.fuel < amount {
+            self.fuel = 0;
+            return Err(EvalError::OutOfFuel);
+        }
+        self.fuel -= amount;
+        Ok(())
+    }
+
+    fn call_function(&mut self, f: &Function, args: Vec<Term>) -> EvalResult<Term> {
+        if self.depth >= self.max_depth {
+            return Err(EvalError::TooDeep);
+        }
+        self.depth += 1;
+        let result = self.call_clauses(&f.clauses, &args);
+        self.depth -= 1;
+        result?.ok_or_else(|| exception(Term::atom("function_clause")))
+    }
+
+    /// Evaluates the first clause whose patterns match `args` (returns `None` if there is none).
+    fn call_clauses(&mut self, clauses: &[Clause], args: &[Term]) -> EvalResult<Option<Term>> {
+        for clause in clauses {
+            let mut env = Env::new();
+            if self.select(clause, args, &mut env)? {
+                return self.eval_body(&clause.body, &mut env).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn select(&mut self, clause: &Clause, values: &[Term], env: &mut Env) -> EvalResult<bool> {
+        for (pattern, value) in clause.patterns.iter().zip(values) {
+            if !self.match_pattern(pattern, value, env)? {
+                return Ok(false);
+            }
+        }
+        if clause.guards.is_empty() {
+            return Ok(true);
+        }
+        for guard in &clause.guards {
+            let mut holds = true;
+            for test in guard {
+                match self.eval_expr(test, &mut env.clone()) {
+                    Ok(Term::Atom(ref a)) if a == "true" => {}
+                    Ok(_) | Err(EvalError::Exception(_)) => {
+                        holds = false;
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if holds {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn eval_body(&mut self, body: &[Expr], env: &mut Env) -> EvalResult<Term> {
+        let mut value = Term::atom("ok");
+        for expr in body {
+            value = self.eval_expr(expr, env)?;
+        }
+        Ok(value)
+    }
+
+    fn eval_exprs(&mut self, exprs: &[Expr], env: &mut Env) -> EvalResult<Vec<Term>> {
+        exprs.iter().map(|e| self.eval_expr(e, env)).collect()
+    }
+
+    fn eval_expr(&mut self, expr: &Expr, env: &mut Env) -> EvalResult<Term> {
+        self.consume(1)?;
+        match expr.kind {
+            ExprKind::Literal(ref x) => Ok(literal(x)),
+            ExprKind::Variable(ref x) => env.get(x).cloned().ok_or(EvalError::Unsupported),
+            ExprKind::Tuple(ref x) => Ok(Term::Tuple(self.eval_exprs(x, env)?)),
+            ExprKind::List {
+                ref elements,
+                ref tail,
+            } => {
+                let elements = self.eval_exprs(elements, env)?;
+                match *tail {
+                    None => Ok(Term::List(elements)),
+                    Some(ref tail) => {
+                        let tail = self.eval_expr(tail, env)?;
+                        Ok(cons(elements, tail))
+                    }
+                }
+            }
+            ExprKind::Map {
+                ref base,
+                ref fields,
+            } => {
+                let pairs = match *base {
+                    None => Vec::new(),
+                    Some(ref base) => match self.eval_expr(base, env)? {
+                        Term::Map(pairs) => pairs,
+                        other => return Err(exception(error_tuple("badmap", other))),
+                    },
+                };
+                self.update_map(pairs, base.is_none(), fields, env)
+            }
+            ExprKind::Match {
+                ref pattern,
+                ref value,
+            } => {
+                let value = self.eval_expr(value, env)?;
+                if self.match_pattern(pattern, &value, env)? {
+                    Ok(value)
+                } else {
+                    Err(exception(error_tuple("badmatch", value)))
+                }
+            }
+            ExprKind::Call {
+                ref module,
+                ref function,
+                ref args,
+            } => {
+                let module = match *module {
+                    None => None,
+                    Some(ref m) => Some(atom_of(m)?),
+                };
+                let function = atom_of(function)?;
+                let args = self.eval_exprs(args, env)?;
+                self.eval_call(module, function, args)
+            }
+            ExprKind::UnaryOp { op, ref operand } => {
+                let operand = self.eval_expr(operand, env)?;
+                unary_op(op, operand)
+            }
+            ExprKind::BinaryOp {
+                op: BinaryOp::Andalso,
+                ref left,
+                ref right,
+            } => match as_bool(&self.eval_expr(left, env)?) {
+                Some(true) => self.eval_expr(right, env),
+                Some(false) => Ok(Term::atom("false")),
+                None => Err(exception(Term::atom("badarg"))),
+            },
+            ExprKind::BinaryOp {
+                op: BinaryOp::Orelse,
+                ref left,
+                ref right,
+            } => match as_bool(&self.eval_expr(left, env)?) {
+                Some(true) => Ok(Term::atom("true")),
+                Some(false) => self.eval_expr(right, env),
+                None => Err(exception(Term::atom("badarg"))),
+            },
+            ExprKind::BinaryOp {
+                op,
+                ref left,
+                ref right,
+            } => {
+                let left = self.eval_expr(left, env)?;
+                let right = self.eval_expr(right, env)?;
+                self.binary_op(op, left, right)
+            }
+            ExprKind::Block(ref body) => self.eval_body(body, env),
+            ExprKind::Case {
+                ref expr,
+                ref clauses,
+            } => {
+                let value = self.eval_expr(expr, env)?;
+                for clause in clauses {
+                    let mut clause_env = env.clone();
+                    if self.select(clause, std::slice::from_ref(&value), &mut clause_env)? {
+                        let result = self.eval_body(&clause.body, &mut clause_env)?;
+                        *env = clause_env;
+                        return Ok(result);
+                    }
+                }
+                Err(exception(error_tuple("case_clause", value)))
+            }
+            ExprKind::If(ref clauses) => {
+                for clause in clauses {
+                    let mut clause_env = env.clone();
+                    if self.select(clause, &[], &mut clause_env)? {
+                        let result = self.eval_body(&clause.body, &mut clause_env)?;
+                        *env = clause_env;
+                        return Ok(result);
+                    }
+                }
+                Err(exception(Term::atom("if_clause")))
+            }
+            _ => Err(EvalError::Unsupported),
+        }
+    }
+
+    fn update_map(
+        &mut self,
+        mut pairs: Vec<(Term, Term)>,
+        is_new: bool,
+        fields: &[MapField<Expr>],
+        env: &mut Env,
+    ) -> EvalResult<Term> {
+        for field in fields {
+            if field.exact && is_new {
+                return Err(EvalError::Unsupported);
+            }
+            let key = self.eval_expr(&field.key, env)?;
+            let value = self.eval_expr(&field.value, env)?;
+            match pairs.iter_mut().find(|p| p.0 == key) {
+                Some(pair) => pair.1 = value,
+                None if field.exact => return Err(exception(error_tuple("badkey", key))),
+                None => pairs.push((key, value)),
+            }
+        }
+        pairs.sort_by(|a, b| compare(&a.0, &b.0));
+        Ok(Term::Map(pairs))
+    }
+
+    fn eval_call(
+        &mut self,
+        module: Option<&str>,
+        function: &str,
+        args: Vec<Term>,
+    ) -> EvalResult<Term> {
+        if module.is_none() {
+            if let Some(f) = self.functions.get(&(function, args.len())).copied() {
+                return self.call_function(f, args);
+            }
+        }
+        let module = module.unwrap_or("erlang");
+        let badarg = || exception(Term::atom("badarg"));
+        let result = match (module, function, args.as_slice()) {
+            ("erlang", "error", [reason]) => return Err(exception(reason.clone())),
+            ("erlang", "abs", [Term::Integer(x)]) => Term::Integer(x.abs()),
+            ("erlang", "abs", [Term::Float(x)]) => Term::Float(x.abs()),
+            ("erlang", "hd", [list]) => list_parts(list)
+                .and_then(|(elements, _)| elements.first().cloned())
+                .ok_or_else(badarg)?,
+            ("erlang", "tl", [list]) => match list_parts(list) {
+                Some((elements, tail)) if !elements.is_empty() => {
+                    cons(elements[1..].to_vec(), tail)
+                }
+                _ => return Err(badarg()),
+            },
+            ("erlang", "length", [Term::List(elements)]) => Term::from(elements.len()),
+            ("erlang", "tuple_size", [Term::Tuple(elements)]) => Term::from(elements.len()),
+            ("erlang", "map_size", [Term::Map(pairs)]) => Term::from(pairs.len()),
+            ("erlang", "element", [Term::Integer(i), Term::Tuple(elements)]) => i
+                .to_usize()
+                .and_then(|i| elements.get(i.checked_sub(1)?))
+                .cloned()
+                .ok_or_else(badarg)?,
+            ("erlang", "setelement", [Term::Integer(i), Term::Tuple(elements), value]) => {
+                let mut elements = elements.clone();
+                let i = i
+                    .to_usize()
+                    .filter(|&i| 0 < i && i <= elements.len())
+                    .ok_or_else(badarg)?;
+                elements[i - 1] = value.clone();
+                Term::Tuple(elements)
+            }
+            ("erlang", "tuple_to_list", [Term::Tuple(elements)]) => Term::List(elements.clone()),
+            ("erlang", "list_to_tuple", [Term::List(elements)]) => Term::Tuple(elements.clone()),
+            ("erlang", "atom_to_list", [Term::Atom(name)]) => Term::string(name),
+            ("erlang", "integer_to_list", [Term::Integer(x)]) => Term::string(&x.to_string()),
+            ("erlang", "float", [Term::Integer(x)]) => Term::Float(to_f64(x)),
+            ("erlang", "float", [Term::Float(x)]) => Term::Float(*x),
+            ("erlang", "trunc", [Term::Float(x)]) => float_to_integer(x.trunc())?,
+            ("erlang", "round", [Term::Float(x)]) => float_to_integer(x.round())?,
+            ("erlang", "trunc", [Term::Integer(x)]) | ("erlang", "round", [Term::Integer(x)]) => {
+                Term::Integer(x.clone())
+            }
+            ("erlang", "max", [a, b]) => {
+                if compare(a, b) == Ordering::Less {
+                    b.clone()
+                } else {
+                    a.clone()
+                }
+            }
+            ("erlang", "min", [a, b]) => {
+                if compare(b, a) == Ordering::Less {
+                    b.clone()
+                } else {
+                    a.clone()
+                }
+            }
+            ("erlang", "map_get", [key, Term::Map(pairs)]) => pairs
+                .iter()
+                .find(|p| p.0 == *key)
+                .map(|p| p.1.clone())
+                .ok_or_else(|| exception(error_tuple("badkey", key.clone())))?,
+            ("erlang", "is_map_key", [key, Term::Map(pairs)]) => {
+                boolean(pairs.iter().any(|p| p.0 == *key))
+            }
+            ("erlang", name, [x]) if name.starts_with("is_") => {
+                let result = match name {
+                    "is_atom" => matches!(*x, Term::Atom(_)),
+                    "is_boolean" => as_bool(x).is_some(),
+                    "is_integer" => matches!(*x, Term::Integer(_)),
+                    "is_float" => matches!(*x, Term::Float(_)),
+                    "is_number" => matches!(*x, Term::Integer(_) | Term::Float(_)),
+                    "is_list" => matches!(*x, Term::List(_) | Term::ImproperList(..)),
+                    "is_tuple" => matches!(*x, Term::Tuple(_)),
+                    "is_map" => matches!(*x, Term::Map(_)),
+                    "is_binary" => matches!(*x, Term::Binary(_)),
+                    _ => return Err(EvalError::Unsupported),
+                };
+                boolean(result)
+            }
+            ("lists", "reverse", [Term::List(elements)]) => {
+                Term::List(elements.iter().rev().cloned().collect())
+            }
+            ("lists", "member", [x, Term::List(elements)]) => boolean(elements.contains(x)),
+            ("lists", "nth", [Term::Integer(i), Term::List(elements)]) => i
+                .to_usize()
+                .and_then(|i| elements.get(i.checked_sub(1)?))
+                .cloned()
+                .ok_or_else(|| exception(Term::atom("function_clause")))?,
+            ("lists", "append", [a, b]) => {
+                return self.binary_op(BinaryOp::PlusPlus, a.clone(), b.clone())
+            }
+            ("lists", "seq", [Term::Integer(from), Term::Integer(to)]) => {
+                let n = (to - from + 1u32).to_usize().ok_or_else(badarg)?;
+                self.consume(n)?;
+                Term::List((0..n).map(|i| Term::Integer(from + i)).collect())
+            }
+            _ => {
+                if is_pure_bif(module, function, args.len()) {
+                    return Err(badarg());
+                }
+                return Err(EvalError::Unsupported);
+            }
+        };
+        Ok(result)
+    }
+
+    fn binary_op(&mut self, op: BinaryOp, left: Term, right: Term) -> EvalResult<Term> {
+        let badarith = || exception(Term::atom("badarith"));
+        let result = match op {
+            BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Mul => match (&left, &right) {
+                (Term::Integer(a), Term::Integer(b)) => Term::Integer(match op {
+                    BinaryOp::Plus => a + b,
+                    BinaryOp::Minus => a - b,
+                    _ => a * b,
+                }),
+                _ => {
+                    let (a, b) = (
+                        number(&left).ok_or_else(badarith)?,
+                        number(&right).ok_or_else(badarith)?,
+                    );
+                    let x = match op {
+                        BinaryOp::Plus => a + b,
+                        BinaryOp::Minus => a - b,
+                        _ => a * b,
+                    };
+                    finite(x).ok_or_else(badarith)?
+                }
+            },
+            BinaryOp::FloatDiv => {
+                let (a, b) = (
+                    number(&left).ok_or_else(badarith)?,
+                    number(&right).ok_or_else(badarith)?,
+                );
+                if b == 0.0 {
+                    return Err(badarith());
+                }
+                finite(a / b).ok_or_else(badarith)?
+            }
+            BinaryOp::IntDiv
+            | BinaryOp::Rem
+            | BinaryOp::Band
+            | BinaryOp::Bor
+            | BinaryOp::Bxor
+            | BinaryOp::Bsl
+            | BinaryOp::Bsr => {
+                let (a, b) = match (&left, &right) {
+                    (Term::Integer(a), Term::Integer(b)) => (a, b),
+                    _ => return Err(badarith()),
+                };
+                Term::Integer(match op {
+                    BinaryOp::IntDiv | BinaryOp::Rem if b.is_zero() => return Err(badarith()),
+                    BinaryOp::IntDiv => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Band => a & b,
+                    BinaryOp::Bor => a | b,
+                    BinaryOp::Bxor => a ^ b,
+                    _ => {
+                        let shift = b.to_i64().ok_or(EvalError::Unsupported)?;
+                        let left_shift = (op == BinaryOp::Bsl) == (shift >= 0);
+                        let shift = shift.unsigned_abs() as usize;
+                        if left_shift {
+                            self.consume(shift / 64)?;
+                            a << shift
+                        } else {
+                            a >> shift
+                        }
+                    }
+                })
+            }
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                match (as_bool(&left), as_bool(&right)) {
+                    (Some(a), Some(b)) => boolean(match op {
+                        BinaryOp::And => a && b,
+                        BinaryOp::Or => a || b,
+                        _ => a != b,
+                    }),
+                    _ => return Err(exception(Term::atom("badarg"))),
+                }
+            }
+            BinaryOp::Andalso | BinaryOp::Orelse | BinaryOp::Send => {
+                return Err(EvalError::Unsupported)
+            }
+            BinaryOp::PlusPlus => match left {
+                Term::List(mut elements) => {
+                    self.consume(elements.len())?;
+                    if elements.is_empty() {
+                        right
+                    } else {
+                        match right {
+                            Term::List(rest) => {
+                                elements.extend(rest);
+                                Term::List(elements)
+                            }
+                            Term::ImproperList(rest, tail) => {
+                                elements.extend(rest);
+                                Term::ImproperList(elements, tail)
+                            }
+                            tail => Term::ImproperList(elements, Box::new(tail)),
+                        }
+                    }
+                }
+                _ => return Err(exception(Term::atom("badarg"))),
+            },
+            BinaryOp::MinusMinus => match (left, right) {
+                (Term::List(mut elements), Term::List(removed)) => {
+                    self.consume(elements.len().saturating_mul(removed.len()))?;
+                    for x in &removed {
+                        if let Some(i) = elements.iter().position(|e| e == x) {
+                            elements.remove(i);
+                        }
+                    }
+                    Term::List(elements)
+                }
+                _ => return Err(exception(Term::atom("badarg"))),
+            },
+            BinaryOp::Eq => boolean(compare(&left, &right) == Ordering::Equal),
+            BinaryOp::NotEq => boolean(compare(&left, &right) != Ordering::Equal),
+            BinaryOp::ExactEq => boolean(left == right),
+            BinaryOp::ExactNotEq => boolean(left != right),
+            BinaryOp::Less => boolean(compare(&left, &right) == Ordering::Less),
+            BinaryOp::LessEq => boolean(compare(&left, &right) != Ordering::Greater),
+            BinaryOp::Greater => boolean(compare(&left, &right) == Ordering::Greater),
+            BinaryOp::GreaterEq => boolean(compare(&left, &right) != Ordering::Less),
+        };
+        if let Term::Integer(ref x) = result {
+            self.consume(x.bits() as usize / 64)?;
+        }
+        Ok(result)
+    }
+
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Term,
+        env: &mut Env,
+    ) -> EvalResult<bool> {
+        self.consume(1)?;
+        match pattern.kind {
+            PatternKind::Variable(ref name) => {
+                if name == "_" {
+                    return Ok(true);
+                }
+                if let Some(bound) = env.get(name) {
+                    return Ok(bound == value);
+                }
+                env.insert(name.clone(), value.clone());
+                Ok(true)
+            }
+            PatternKind::Tuple(ref patterns) => match *value {
+                Term::Tuple(ref elements) if elements.len() == patterns.len() => {
+                    for (p, e) in patterns.iter().zip(elements) {
+                        if !self.match_pattern(p, e, env)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                _ => Ok(false),
+            },
+            PatternKind::List {
+                ref elements,
+                ref tail,
+            } => {
+                let (values, value_tail) = match list_parts(value) {
+                    Some(parts) if parts.0.len() >= elements.len() => parts,
+                    _ => return Ok(false),
+                };
+                for (p, e) in elements.iter().zip(values) {
+                    if !self.match_pattern(p, e, env)? {
+                        return Ok(false);
+                    }
+                }
+                let rest = cons(values[elements.len()..].to_vec(), value_tail);
+                match *tail {
+                    None => Ok(rest == Term::List(Vec::new())),
+                    Some(ref tail) => self.match_pattern(tail, &rest, env),
+                }
+            }
+            PatternKind::Map(ref fields) => {
+                let pairs = match *value {
+                    Term::Map(ref pairs) => pairs,
+                    _ => return Ok(false),
+                };
+                for field in fields {
+                    let key = self.pattern_value(&field.key, env)?;
+                    match pairs.iter().find(|p| p.0 == key) {
+                        Some(pair) => {
+                            if !self.match_pattern(&field.value, &pair.1, env)? {
+                                return Ok(false);
+                            }
+                        }
+                        None => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            PatternKind::Match {
+                ref left,
+                ref right,
+            } => Ok(self.match_pattern(left, value, env)? && self.match_pattern(right, value, env)?),
+            PatternKind::BinaryOp {
+                op: BinaryOp::PlusPlus,
+                ref left,
+                ref right,
+            } => {
+                let prefix = match self.pattern_value(left, env)? {
+                    Term::List(prefix) => prefix,
+                    _ => return Err(EvalError::Unsupported),
+                };
+                match list_parts(value) {
+                    Some((values, tail)) if values.starts_with(&prefix) => {
+                        let rest = cons(values[prefix.len()..].to_vec(), tail);
+                        self.match_pattern(right, &rest, env)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            _ => Ok(self.pattern_value(pattern, env)? == *value),
+        }
+    }
+
+    /// Evaluates a pattern which denotes a value (e.g., a literal or a bound variable).
+    fn pattern_value(&mut self, pattern: &Pattern, env: &mut Env) -> EvalResult<Term> {
+        match pattern.kind {
+            PatternKind::Literal(ref x) => Ok(literal(x)),
+            PatternKind::Variable(ref name) => env.get(name).cloned().ok_or(EvalError::Unsupported),
+            PatternKind::UnaryOp { op, ref operand } => {
+                let operand = self.pattern_value(operand, env)?;
+                unary_op(op, operand)
+            }
+            PatternKind::BinaryOp {
+                op,
+                ref left,
+                ref right,
+            } => {
+                let left = self.pattern_value(left, env)?;
+                let right = self.pattern_value(right, env)?;
+                self.binary_op(op, left, right)
+            }
+            _ => Err(EvalError::Unsupported),
+        }
+    }
+}
+
+fn exception(reason: Term) -> EvalError {
+    EvalError::Exception(reason)
+}
+
+fn error_tuple(tag: &str, value: Term) -> Term {
+    Term::Tuple(vec![Term::atom(tag), value])
+}
+
+fn boolean(b: bool) -> Term {
+    Term::atom(if b { "true" } else { "false" })
+}
+
+fn as_bool(term: &Term) -> Option<bool> {
+    match term.as_atom() {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    }
+}
+
+fn atom_of(expr: &Expr) -> EvalResult<&str> {
+    match expr.kind {
+        ExprKind::Literal(Literal::Atom(ref name)) => Ok(name),
+        _ => Err(EvalError::Unsupported),
+    }
+}
+
+fn literal(literal: &Literal) -> Term {
+    match *literal {
+        Literal::Atom(ref x) => Term::Atom(x.clone()),
+        Literal::Char(x) => Term::from(x as u32),
+        Literal::Float(x) => Term::Float(x),
+        Literal::Integer(ref x) => Term::Integer(BigInt::from(x.clone())),
+        Literal::String(ref x) => Term::string(x),
+    }
+}
+
+fn unary_op(op: UnaryOp, operand: Term) -> EvalResult<Term> {
+    match (op, operand) {
+        (UnaryOp::Plus, x @ Term::Integer(_)) | (UnaryOp::Plus, x @ Term::Float(_)) => Ok(x),
+        (UnaryOp::Minus, Term::Integer(x)) => Ok(Term::Integer(-x)),
+        (UnaryOp::Minus, Term::Float(x)) => Ok(Term::Float(-x)),
+        (UnaryOp::Bnot, Term::Integer(x)) => Ok(Term::Integer(!x)),
+        (UnaryOp::Not, x) => match as_bool(&x) {
+            Some(b) => Ok(boolean(!b)),
+            None => Err(exception(Term::atom("badarg"))),
+        },
+        _ => Err(exception(Term::atom("badarith"))),
+    }
+}
+
+/// Makes the list `[elements | tail]`.
+fn cons(mut elements: Vec<Term>, tail: Term) -> Term {
+    match tail {
+        Term::List(rest) => {
+            elements.extend(rest);
+            Term::List(elements)
+        }
+        Term::ImproperList(rest, tail) => {
+            elements.extend(rest);
+            Term::ImproperList(elements, tail)
+        }
+        tail if elements.is_empty() => tail,
+        tail => Term::ImproperList(elements, Box::new(tail)),
+    }
+}
+
+/// Splits a list into its elements and tail (`[]` for proper lists).
+fn list_parts(term: &Term) -> Option<(&[Term], Term)> {
+    match *term {
+        Term::List(ref elements) => Some((elements, Term::List(Vec::new()))),
+        Term::ImproperList(ref elements, ref tail) => Some((elements, (**tail).clone())),
+        _ => None,
+    }
+}
+
+fn to_f64(x: &BigInt) -> f64 {
+    x.to_f64().unwrap_or(f64::INFINITY)
+}
+
+fn number(term: &Term) -> Option<f64> {
+    match *term {
+        Term::Integer(ref x) => Some(to_f64(x)),
+        Term::Float(x) => Some(x),
+        _ => None,
+    }
+}
+
+fn finite(x: f64) -> Option<Term> {
+    if x.is_finite() {
+        Some(Term::Float(x))
+    } else {
+        None
+    }
+}
+
+fn float_to_integer(x: f64) -> EvalResult<Term> {
+    num::FromPrimitive::from_f64(x)
+        .map(Term::Integer)
+        .ok_or_else(|| exception(Term::atom("badarg")))
+}
+
+fn is_pure_bif(module: &str, function: &str, arity: usize) -> bool {
+    match (module, arity) {
+        ("erlang", 1) => [
+            "abs",
+            "hd",
+            "tl",
+            "length",
+            "tuple_size",
+            "map_size",
+            "tuple_to_list",
+            "list_to_tuple",
+            "atom_to_list",
+            "integer_to_list",
+            "float",
+            "trunc",
+            "round",
+        ]
+        .contains(&function),
+        ("erlang", 2) => ["element", "map_get", "is_map_key"].contains(&function),
+        ("erlang", 3) => function == "setelement",
+        ("lists", 1) => function == "reverse",
+        ("lists", 2) => ["member", "nth", "seq"].contains(&function),
+        _ => false,
+    }
+}
+
+/// Compares terms in the Erlang term order.
+fn compare(a: &Term, b: &Term) -> Ordering {
+    fn rank(t: &Term) -> u8 {
+        match *t {
+            Term::Integer(_) | Term::Float(_) => 0,
+            Term::Atom(_) => 1,
+            Term::Tuple(_) => 2,
+            Term::Map(_) => 3,
+            Term::List(_) | Term::ImproperList(..) => 4,
+            Term::Binary(_) => 5,
+        }
+    }
+    match (a, b) {
+        (Term::Integer(x), Term::Integer(y)) => x.cmp(y),
+        (Term::Atom(x), Term::Atom(y)) => x.cmp(y),
+        (Term::Binary(x), Term::Binary(y)) => x.cmp(y),
+        (Term::Tuple(x), Term::Tuple(y)) => x.len().cmp(&y.len()).then_with(|| compare_all(x, y)),
+        (Term::Map(x), Term::Map(y)) => x.len().cmp(&y.len()).then_with(|| {
+            let keys = |m: &[(Term, Term)]| m.iter().map(|p| p.0.clone()).collect::<Vec<_>>();
+            let values = |m: &[(Term, Term)]| m.iter().map(|p| p.1.clone()).collect::<Vec<_>>();
+            compare_all(&keys(x), &keys(y)).then_with(|| compare_all(&values(x), &values(y)))
+        }),
+        (Term::List(x), Term::List(y)) => compare_all(x, y),
+        _ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+        _ => match (number(a), number(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => {
+                // Improper lists
+                let (x, x_tail) = list_parts(a).expect("never fails");
+                let (y, y_tail) = list_parts(b).expect("never fails");
+                let n = x.len().min(y.len());
+                compare_all(&x[..n], &y[..n]).then_with(|| {
+                    let x_rest = cons(x[n..].to_vec(), x_tail);
+                    let y_rest = cons(y[n..].to_vec(), y_tail);
+                    compare(&x_rest, &y_rest)
+                })
+            }
+        },
+    }
+}
+
+fn compare_all(x: &[Term], y: &[Term]) -> Ordering {
+    x.iter()
+        .zip(y)
+        .map(|(a, b)| compare(a, b))
+        .find(|o| *o != Ordering::Equal)
+        .unwrap_or_else(|| x.len().cmp(&y.len()))
+}
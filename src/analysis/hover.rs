@@ -0,0 +1,126 @@
+use erl_tokenize::values::Whitespace;
+use erl_tokenize::{Position, PositionRange, Token};
+
+use crate::cst::forms::{FunDecl, FunSpec};
+use crate::cst::{Form, ModuleDecl, Trivia};
+use crate::traits::CollectTokens;
+
+/// Information on a function shown when hovering over it in an editor.
+#[derive(Debug, Clone)]
+pub struct HoverInfo {
+    pub name: String,
+    pub arity: usize,
+
+    /// The `-spec` of the function, rendered by `CollectTokens::to_erlang_source`.
+    pub spec: Option<String>,
+
+    /// The content of the `-doc` attribute or, if it is absent, the EDoc comment of the function.
+    pub doc: Option<String>,
+
+    /// The start position of the function declaration.
+    pub start_position: Position,
+
+    /// The end position of the function declaration.
+    pub end_position: Position,
+}
+impl HoverInfo {
+    /// Returns the contents of an LSP hover response in Markdown.
+    pub fn to_markdown(&self) -> String {
+        let signature = self
+            .spec
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", self.name, self.arity));
+        let mut markdown = format!("```erlang\n{}\n```", signature);
+        if let Some(ref doc) = self.doc {
+            markdown.push_str("\n\n");
+            markdown.push_str(doc);
+        }
+        markdown
+    }
+}
+impl PositionRange for HoverInfo {
+    fn start_position(&self) -> Position {
+        self.start_position.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end_position.clone()
+    }
+}
+
+/// Returns the hover information of the function declaration enclosing the byte offset `offset`.
+///
+/// The `-spec` of the function is searched for in the whole module.
+/// The documentation is taken from a `-doc(..)` attribute preceding the function (or its `-spec`),
+/// or else from the `%` comment block just before them, which requires `trivia` of the module text.
+pub fn hover_info(
+    module: &ModuleDecl,
+    trivia: Option<&Trivia>,
+    offset: usize,
+) -> Option<HoverInfo> {
+    let i = module.forms.iter().position(|f| {
+        f.start_position().offset() <= offset && offset < f.end_position().offset()
+    })?;
+    let decl = match module.forms[i] {
+        Form::FunDecl(ref f) => f,
+        _ => return None,
+    };
+    let spec = module.forms.iter().find_map(|f| match *f {
+        Form::FunSpec(ref s) if is_spec_of(s, decl) => Some(s.to_erlang_source()),
+        _ => None,
+    });
+
+    // Attributes directly preceding the declaration
+    let mut first = i;
+    let mut doc = None;
+    while first > 0 {
+        match module.forms[first - 1] {
+            Form::FunSpec(ref s) if is_spec_of(s, decl) => {}
+            Form::DocAttr(ref a) if !a.is_module_doc() => {
+                doc = doc.or_else(|| a.text());
+            }
+            _ => break,
+        }
+        first -= 1;
+    }
+    let doc = doc.or_else(|| {
+        let comment = comment_block(trivia?.leading(&module.forms[first]));
+        Some(comment).filter(|c| !c.is_empty())
+    });
+    Some(HoverInfo {
+        name: decl.name().to_owned(),
+        arity: decl.arity(),
+        spec,
+        doc,
+        start_position: decl.start_position(),
+        end_position: decl.end_position(),
+    })
+}
+
+fn is_spec_of(spec: &FunSpec, decl: &FunDecl) -> bool {
+    spec.module.is_none() && spec.fun_name.value() == decl.name() && spec.arity() == decl.arity()
+}
+
+/// Returns the last block of consecutive comment lines in `trivia`, without the leading `%`s.
+fn comment_block(trivia: &[Token]) -> String {
+    let mut lines = Vec::new();
+    let mut newlines = 0;
+    for token in trivia {
+        match *token {
+            Token::Comment(ref t) => {
+                if newlines > 1 {
+                    lines.clear();
+                }
+                newlines = 0;
+                let line = t.value().trim_start_matches('%');
+                let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+                lines.push(line.strip_prefix("@doc ").unwrap_or(line));
+            }
+            Token::Whitespace(ref t) if t.value() == Whitespace::Newline => newlines += 1,
+            _ => {}
+        }
+    }
+    if newlines > 1 {
+        lines.clear();
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,222 @@
+use num::ToPrimitive;
+
+use crate::cst::commons::parts::BinaryOp;
+use crate::cst::{Expr, Form, Literal, ModuleDecl};
+use crate::traits::CollectTokens;
+
+/// Summary of what a module exposes to other modules, analogous to the interface of a `.beam` file.
+///
+/// This is small and self-contained (specs and types are kept as source text),
+/// so analyses of dependent modules can keep the interfaces of their dependencies
+/// instead of the CSTs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModuleInterface {
+    pub module: Option<String>,
+
+    /// The exported functions in the order of the export entries
+    /// (or of the declarations if the module is compiled with `export_all`).
+    pub exports: Vec<ExportedFunction>,
+
+    /// The types exported by `-export_type`.
+    pub types: Vec<ExportedType>,
+
+    /// The behaviours implemented by the module.
+    pub behaviours: Vec<String>,
+
+    /// The callbacks defined by the module (i.e., the module is a behaviour).
+    pub callbacks: Vec<CallbackInfo>,
+}
+impl ModuleInterface {
+    pub fn export(&self, name: &str, arity: usize) -> Option<&ExportedFunction> {
+        self.exports
+            .iter()
+            .find(|f| f.name == name && f.arity == arity)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportedFunction {
+    pub name: String,
+    pub arity: usize,
+
+    /// The `-spec` attribute of the function rendered by `CollectTokens::to_erlang_source`.
+    pub spec: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportedType {
+    pub name: String,
+    pub arity: usize,
+    pub opaque: bool,
+
+    /// The type declaration rendered by `CollectTokens::to_erlang_source`.
+    ///
+    /// This is `None` for opaque types, whose definitions are hidden from other modules,
+    /// and for types which are not declared in the module.
+    pub definition: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallbackInfo {
+    pub name: String,
+    pub arity: usize,
+
+    /// The `-callback` attribute rendered by `CollectTokens::to_erlang_source`.
+    pub spec: String,
+
+    /// Whether the callback is listed in `-optional_callbacks`.
+    pub optional: bool,
+}
+
+/// Extracts the interface of `module`.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::analysis::interface;
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-module(foo).\n-export([f/1]).\n-spec f(integer()) -> ok.\nf(_) -> ok.\ng() -> ok.";
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+/// let module: ModuleDecl = parser.parse().unwrap();
+///
+/// let interface = interface(&module);
+/// assert_eq!(interface.module.as_deref(), Some("foo"));
+/// assert_eq!(interface.exports.len(), 1);
+/// assert_eq!(interface.exports[0].spec.as_deref(), Some("-spec f(integer()) -> ok."));
+/// ```
+pub fn interface(module: &ModuleDecl) -> ModuleInterface {
+    let mut exports = Vec::new();
+    let mut export_types = Vec::new();
+    let mut export_all = false;
+    let mut behaviours = Vec::new();
+    let mut optional_callbacks = Vec::new();
+    for form in &module.forms {
+        match *form {
+            Form::ExportAttr(ref a) => {
+                for e in a.exports.elements.iter().flat_map(|s| s.iter()) {
+                    let arity = e.arity.value().to_usize().unwrap_or(usize::MAX);
+                    exports.push((e.name.value(), arity));
+                }
+            }
+            Form::ExportTypeAttr(ref a) => {
+                for e in a.exports.elements.iter().flat_map(|s| s.iter()) {
+                    let arity = e.arity.value().to_usize().unwrap_or(usize::MAX);
+                    export_types.push((e.name.value(), arity));
+                }
+            }
+            Form::CompileAttr(ref a) => {
+                let options: Vec<&Expr> = match a.options {
+                    Expr::List(ref x) => x.iter().collect(),
+                    ref x => vec![x],
+                };
+                export_all |= options.iter().any(|o| atom(o) == Some("export_all"));
+            }
+            Form::BehaviourAttr(ref a) => behaviours.push(a.behaviour_name.value().to_owned()),
+            Form::WildAttr(ref a) if a.attr_name.value() == "optional_callbacks" => {
+                if let Some(Expr::List(ref x)) = a.parse_value() {
+                    optional_callbacks.extend(x.iter().filter_map(name_and_arity));
+                }
+            }
+            _ => {}
+        }
+    }
+    if export_all {
+        exports = module.fun_decls().map(|f| (f.name(), f.arity())).collect();
+    }
+    let mut seen = Vec::new();
+    exports.retain(|e| {
+        let is_new = !seen.contains(e);
+        seen.push(*e);
+        is_new
+    });
+
+    let spec_of = |name: &str, arity: usize| {
+        module.forms.iter().find_map(|f| match *f {
+            Form::FunSpec(ref s) if s.fun_name.value() == name && s.arity() == arity => {
+                let is_local = s
+                    .module
+                    .as_ref()
+                    .is_none_or(|m| Some(m.name.value()) == module.module_name());
+                Some(s.to_erlang_source()).filter(|_| is_local)
+            }
+            _ => None,
+        })
+    };
+    let exports = exports
+        .into_iter()
+        .map(|(name, arity)| ExportedFunction {
+            name: name.to_owned(),
+            arity,
+            spec: spec_of(name, arity),
+        })
+        .collect();
+    let types = export_types
+        .into_iter()
+        .map(|(name, arity)| {
+            let decl = module
+                .type_decls()
+                .find(|t| t.name() == name && t.arity() == arity);
+            ExportedType {
+                name: name.to_owned(),
+                arity,
+                opaque: decl.is_some_and(|t| t.is_opaque()),
+                definition: decl
+                    .filter(|t| !t.is_opaque())
+                    .map(|t| t.to_erlang_source()),
+            }
+        })
+        .collect();
+    let callbacks = module
+        .forms
+        .iter()
+        .filter_map(|f| match *f {
+            Form::CallbackSpec(ref c) => {
+                let name = c.callback_name.value().to_owned();
+                let arity = c.arity();
+                Some(CallbackInfo {
+                    optional: optional_callbacks.contains(&(name.clone(), arity)),
+                    name,
+                    arity,
+                    spec: c.to_erlang_source(),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    ModuleInterface {
+        module: module.module_name().map(str::to_owned),
+        exports,
+        types,
+        behaviours,
+        callbacks,
+    }
+}
+
+fn atom(expr: &Expr) -> Option<&str> {
+    match *expr {
+        Expr::Literal(Literal::Atom(ref t)) => Some(t.value()),
+        _ => None,
+    }
+}
+
+/// Interprets `name/arity` in an attribute value.
+fn name_and_arity(expr: &Expr) -> Option<(String, usize)> {
+    match *expr {
+        Expr::BinaryOpCall(ref x) if matches!(x.op, BinaryOp::FloatDiv(_)) => {
+            let arity = match x.right {
+                Expr::Literal(Literal::Integer(ref t)) => t.value().to_usize()?,
+                _ => return None,
+            };
+            Some((atom(&x.left)?.to_owned(), arity))
+        }
+        _ => None,
+    }
+}
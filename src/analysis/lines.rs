@@ -0,0 +1,138 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{PositionRange, Token, Tokenizer};
+
+const DIRECTIVES: &[&str] = &[
+    "define",
+    "undef",
+    "ifdef",
+    "ifndef",
+    "if",
+    "elif",
+    "else",
+    "endif",
+    "include",
+    "include_lib",
+    "error",
+    "warning",
+];
+
+/// What a source line contains.
+///
+/// A line may contain several kinds of tokens (e.g., code followed by a trailing comment),
+/// so each kind is recorded as an independent flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LineClass {
+    /// The line contains tokens other than comments and strings, outside of preprocessor directives.
+    pub code: bool,
+    pub comment: bool,
+
+    /// The line contains (a part of) a string literal.
+    pub string: bool,
+
+    /// The line is (a part of) a preprocessor directive such as `-define(..).` or `-ifdef(..).`.
+    pub directive: bool,
+}
+impl LineClass {
+    /// Returns `true` if the line consists only of whitespaces.
+    pub fn is_blank(&self) -> bool {
+        *self == LineClass::default()
+    }
+
+    /// Returns `true` if the line contains comments and nothing else.
+    ///
+    /// Changes to such lines never affect the meaning of a program.
+    pub fn is_comment_only(&self) -> bool {
+        self.comment && !(self.code || self.string || self.directive)
+    }
+}
+
+/// Classifies each line of `text`.
+///
+/// The `i`-th element of the result corresponds to the line `i + 1`.
+/// Only the tokenizer is used, so this also works on files which cannot be parsed.
+/// If tokenization fails (e.g., due to an unterminated string),
+/// the remaining lines are classified by looking at their first non-whitespace character.
+pub fn classify_lines(text: &str) -> Vec<LineClass> {
+    let line_count = text.split('\n').count();
+    let mut lines = vec![LineClass::default(); line_count];
+
+    let mut tokens = Vec::new();
+    let mut failed_at = None;
+    for token in Tokenizer::new(text) {
+        match token {
+            Ok(Token::Whitespace(_)) => {}
+            Ok(token) => tokens.push(token),
+            Err(_) => {
+                let offset = tokens.last().map_or(0, |t| t.end_position().offset());
+                failed_at = Some(offset);
+                break;
+            }
+        }
+    }
+
+    let mut form_start = true;
+    let mut in_directive = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let start = token.start_position().line();
+        let end = last_line(token);
+        for line in &mut lines[start - 1..end] {
+            match *token {
+                Token::Comment(_) => line.comment = true,
+                Token::String(_) => line.string = true,
+                _ if in_directive || is_directive_start(form_start, &tokens[i..]) => {
+                    line.directive = true
+                }
+                _ => line.code = true,
+            }
+        }
+        match *token {
+            Token::Comment(_) => continue,
+            Token::Symbol(ref t) if t.value() == Symbol::Dot => {
+                form_start = true;
+                in_directive = false;
+            }
+            _ => {
+                in_directive |= is_directive_start(form_start, &tokens[i..]);
+                form_start = false;
+            }
+        }
+    }
+
+    if let Some(offset) = failed_at {
+        let first = text[..offset].split('\n').count() - 1;
+        for (line, s) in lines[first..].iter_mut().zip(text[offset..].split('\n')) {
+            let s = s.trim_start();
+            if s.starts_with('%') {
+                line.comment = true;
+            } else if !s.is_empty() {
+                line.code = true;
+            }
+        }
+    }
+    lines
+}
+
+fn is_directive_start(form_start: bool, tokens: &[Token]) -> bool {
+    let is_hyphen = match tokens[0] {
+        Token::Symbol(ref t) => t.value() == Symbol::Hyphen,
+        _ => false,
+    };
+    let name = tokens[1..].iter().find_map(|t| match *t {
+        Token::Comment(_) => None,
+        Token::Atom(ref t) => Some(t.value()),
+        Token::Keyword(ref t) => Some(t.value().as_str()),
+        _ => Some(""),
+    });
+    form_start && is_hyphen && name.is_some_and(|n| DIRECTIVES.contains(&n))
+}
+
+/// Returns the line on which the last character of `token` is located.
+fn last_line(token: &Token) -> usize {
+    let start = token.start_position();
+    let end = token.end_position();
+    if end.line() > start.line() && end.column() == 1 {
+        end.line() - 1
+    } else {
+        end.line()
+    }
+}
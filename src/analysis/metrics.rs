@@ -0,0 +1,90 @@
+use erl_tokenize::tokens::AtomToken;
+use erl_tokenize::{Position, PositionRange};
+
+use crate::cst::exprs::Fun;
+use crate::cst::forms::FunDecl;
+use crate::cst::{Expr, Form, ModuleDecl, Node};
+
+/// Size and complexity of a function declaration.
+#[derive(Debug, Clone)]
+pub struct FunctionMetrics {
+    pub name: String,
+    pub arity: usize,
+
+    /// The function name in the head of the first clause.
+    pub head: AtomToken,
+
+    pub start_position: Position,
+    pub end_position: Position,
+
+    /// The number of lines from the first clause to the terminating `.` (inclusive).
+    pub lines: usize,
+
+    pub clauses: usize,
+
+    /// The maximum depth of nested `case`, `if`, `receive`, `try`, `maybe`, `begin`, `catch`,
+    /// anonymous functions and comprehensions (`0` if there are none).
+    pub max_nesting: usize,
+}
+impl<'a> From<&'a FunDecl> for FunctionMetrics {
+    fn from(f: &'a FunDecl) -> Self {
+        FunctionMetrics {
+            name: f.name().to_owned(),
+            arity: f.arity(),
+            head: f.clauses.item.name.clone(),
+            start_position: f.start_position(),
+            end_position: f.end_position(),
+            lines: f.end_position().line() + 1 - f.start_position().line(),
+            clauses: f.clauses.iter().count(),
+            max_nesting: f
+                .clauses
+                .iter()
+                .map(|c| max_nesting(Node::FunDeclClause(c)))
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+impl PositionRange for FunctionMetrics {
+    fn start_position(&self) -> Position {
+        self.start_position.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end_position.clone()
+    }
+}
+
+/// Computes the metrics of the functions declared in `module`.
+pub fn function_metrics(module: &ModuleDecl) -> Vec<FunctionMetrics> {
+    module
+        .forms
+        .iter()
+        .filter_map(|f| match *f {
+            Form::FunDecl(ref f) => Some(FunctionMetrics::from(f)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the maximum depth of nested constructs in `node` (including `node` itself).
+fn max_nesting(node: Node) -> usize {
+    let nests = match node {
+        Node::Expr(x) => match *x {
+            Expr::Case(_)
+            | Expr::If(_)
+            | Expr::Receive(_)
+            | Expr::Try(_)
+            | Expr::Maybe(_)
+            | Expr::Block(_)
+            | Expr::Catch(_)
+            | Expr::ListComprehension(_)
+            | Expr::BitsComprehension(_)
+            | Expr::MapComprehension(_) => true,
+            Expr::Fun(ref x) => !matches!(**x, Fun::Defined(_)),
+            _ => false,
+        },
+        _ => false,
+    };
+    let children = node.children().into_iter().map(max_nesting).max();
+    children.unwrap_or(0) + usize::from(nests)
+}
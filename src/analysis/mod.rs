@@ -0,0 +1,36 @@
+//! Analyses over parsed modules (or, for the fast ones, over tokens).
+pub use self::call_graph::{call_graph, CallEdge, Callee, Mfa};
+pub use self::clones::{expr_hash, find_clones, CloneGroup};
+pub use self::deps::{scan_dependencies, Dependencies};
+pub use self::duplicates::{find_duplicate_keys, find_duplicate_macros, find_duplicates};
+pub use self::elixir::{elixir_dependencies, elixir_module_name, find_elixir_calls, ElixirCall};
+pub use self::eval::{EvalError, Evaluator};
+pub use self::hover::{hover_info, HoverInfo};
+pub use self::interface::{
+    interface, CallbackInfo, ExportedFunction, ExportedType, ModuleInterface,
+};
+pub use self::lines::{classify_lines, LineClass};
+pub use self::metrics::{function_metrics, FunctionMetrics};
+pub use self::module_index::{ImportEntry, ModuleIndex, SymbolEntry};
+pub use self::patterns::check_patterns;
+pub use self::record_defaults::check_record_defaults;
+pub use self::resolution::{resolve_call, CallResolutions, Resolution};
+pub use self::specs::check_spec_clauses;
+pub use self::variables::check_variables;
+
+mod call_graph;
+mod clones;
+mod deps;
+mod duplicates;
+mod elixir;
+mod eval;
+mod hover;
+mod interface;
+mod lines;
+mod metrics;
+mod module_index;
+mod patterns;
+mod record_defaults;
+mod resolution;
+mod specs;
+mod variables;
@@ -0,0 +1,314 @@
+use erl_tokenize::LexicalToken;
+use num::ToPrimitive;
+
+use super::resolution::is_auto_imported_bif;
+use crate::cst::commons::parts::{BinaryOp, NameAndArity};
+use crate::cst::{Expr, Form, Literal, ModuleDecl};
+use crate::Span;
+
+/// Symbol table of a parsed module.
+///
+/// Unlike `workspace::FileIndex`, which scans tokens, this is built from a CST,
+/// so the spans cover the whole definitions.
+/// Macros are only found in CSTs parsed in no-preprocess mode (see `Parser::no_preprocess`),
+/// where `-define` directives remain as forms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleIndex {
+    pub module: Option<String>,
+
+    /// The function declarations.
+    pub functions: Vec<SymbolEntry>,
+
+    /// The entries of `-export` attributes.
+    pub exports: Vec<SymbolEntry>,
+
+    /// `true` if the module is compiled with `export_all`.
+    pub export_all: bool,
+
+    /// The functions given by `{no_auto_import, [Name/Arity]}` compile options.
+    pub no_auto_imports: Vec<SymbolEntry>,
+
+    /// `true` if the module is compiled with `no_auto_import` (i.e., without any auto-imported BIF).
+    pub no_auto_import_all: bool,
+
+    /// The entries of `-import` attributes.
+    pub imports: Vec<ImportEntry>,
+
+    /// The record declarations, whose arities are `None`.
+    pub records: Vec<SymbolEntry>,
+
+    /// The type declarations (`-type`, `-opaque` and `-nominal`).
+    pub types: Vec<SymbolEntry>,
+
+    /// The entries of `-export_type` attributes.
+    pub export_types: Vec<SymbolEntry>,
+
+    /// The `-define` directives, whose arities are `None` for macros without parameters.
+    pub macros: Vec<SymbolEntry>,
+
+    /// All attributes (including the above ones) in the order of appearance,
+    /// whose arities are `None`.
+    pub attributes: Vec<SymbolEntry>,
+}
+impl ModuleIndex {
+    /// Builds the index of `module`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erl_parse::analysis::ModuleIndex;
+    /// use erl_parse::cst::ModuleDecl;
+    /// use erl_parse::{Parser, TokenReader};
+    /// use erl_tokenize::Lexer;
+    ///
+    /// let text = "-module(foo).\n-export([f/1]).\n-record(r, {a}).\nf(X) -> X.\ng() -> ok.";
+    /// let mut parser = Parser::new(TokenReader::new(Lexer::new(text)));
+    /// let module: ModuleDecl = parser.parse().unwrap();
+    ///
+    /// let index = ModuleIndex::new(&module);
+    /// assert_eq!(index.functions.len(), 2);
+    /// assert!(index.is_exported("f", 1));
+    /// assert!(!index.is_exported("g", 0));
+    /// assert_eq!(index.records[0].name, "r");
+    /// ```
+    pub fn new(module: &ModuleDecl) -> Self {
+        let mut index = ModuleIndex {
+            module: module.module_name().map(str::to_owned),
+            ..ModuleIndex::default()
+        };
+        for form in &module.forms {
+            if let Some(name) = attribute_name(form) {
+                index
+                    .attributes
+                    .push(SymbolEntry::new(name, None, Span::of(form)));
+            }
+            match *form {
+                Form::FunDecl(ref x) => {
+                    let entry = SymbolEntry::new(x.name(), Some(x.arity()), Span::of(x));
+                    index.functions.push(entry);
+                }
+                Form::ExportAttr(ref x) => {
+                    index
+                        .exports
+                        .extend(x.exports.elements.iter().flat_map(|s| s.iter()).map(entry));
+                }
+                Form::ExportTypeAttr(ref x) => {
+                    index
+                        .export_types
+                        .extend(x.exports.elements.iter().flat_map(|s| s.iter()).map(entry));
+                }
+                Form::ImportAttr(ref x) => {
+                    let imports = x.imports.elements.iter().flat_map(|s| s.iter());
+                    index.imports.extend(imports.map(|e| ImportEntry {
+                        module: x.module_name.value().to_owned(),
+                        name: e.name.value().to_owned(),
+                        arity: arity(e),
+                        span: Span::of(e),
+                    }));
+                }
+                Form::CompileAttr(ref x) => {
+                    let options: Vec<&Expr> = match x.options {
+                        Expr::List(ref x) => x.iter().collect(),
+                        ref x => vec![x],
+                    };
+                    for option in options {
+                        match *option {
+                            Expr::Literal(Literal::Atom(ref t)) => match t.value() {
+                                "export_all" => index.export_all = true,
+                                "no_auto_import" => index.no_auto_import_all = true,
+                                _ => {}
+                            },
+                            Expr::Tuple(ref x) => {
+                                if let [Expr::Literal(Literal::Atom(ref k)), Expr::List(ref l)] =
+                                    x.iter().collect::<Vec<_>>()[..]
+                                {
+                                    if k.value() == "no_auto_import" {
+                                        index
+                                            .no_auto_imports
+                                            .extend(l.iter().filter_map(name_and_arity));
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Form::RecordDecl(ref x) => {
+                    let entry = SymbolEntry::new(x.name(), None, Span::of(x));
+                    index.records.push(entry);
+                }
+                Form::TypeDecl(ref x) => {
+                    let entry = SymbolEntry::new(x.name(), Some(x.arity()), Span::of(x));
+                    index.types.push(entry);
+                }
+                Form::Directive(ref x) if x.name.value() == "define" => {
+                    if let Some(name) = x.macro_name() {
+                        let params = x
+                            .args
+                            .as_ref()
+                            .and_then(|a| a.args.as_ref())
+                            .map(|a| &a.item.tokens[1..])
+                            .filter(|t| !t.is_empty());
+                        let arity = params.map(|t| {
+                            t.iter()
+                                .filter(|t| matches!(t, LexicalToken::Variable(_)))
+                                .count()
+                        });
+                        index
+                            .macros
+                            .push(SymbolEntry::new(name, arity, Span::of(x)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        index
+    }
+
+    /// Returns the declaration of the function `name/arity`.
+    pub fn function(&self, name: &str, arity: usize) -> Option<&SymbolEntry> {
+        find(&self.functions, name, Some(arity))
+    }
+
+    /// Returns `true` if the function `name/arity` is exported (explicitly or by `export_all`).
+    ///
+    /// With `export_all`, only the functions declared in the module are regarded as exported.
+    pub fn is_exported(&self, name: &str, arity: usize) -> bool {
+        if self.export_all && self.function(name, arity).is_some() {
+            return true;
+        }
+        find(&self.exports, name, Some(arity)).is_some()
+    }
+
+    /// Returns the `-import` entry of the function `name/arity`.
+    pub fn import(&self, name: &str, arity: usize) -> Option<&ImportEntry> {
+        self.imports
+            .iter()
+            .find(|i| i.name == name && i.arity == arity)
+    }
+
+    /// Returns `true` if the calls of `name/arity` refer to the auto-imported BIF
+    /// unless the module defines or imports `name/arity`.
+    pub fn is_auto_imported(&self, name: &str, arity: usize) -> bool {
+        !self.no_auto_import_all
+            && find(&self.no_auto_imports, name, Some(arity)).is_none()
+            && is_auto_imported_bif(name, arity)
+    }
+
+    pub fn record(&self, name: &str) -> Option<&SymbolEntry> {
+        find(&self.records, name, None)
+    }
+
+    pub fn type_decl(&self, name: &str, arity: usize) -> Option<&SymbolEntry> {
+        find(&self.types, name, Some(arity))
+    }
+
+    /// Returns `true` if the type `name/arity` is exported by `-export_type`.
+    pub fn is_type_exported(&self, name: &str, arity: usize) -> bool {
+        find(&self.export_types, name, Some(arity)).is_some()
+    }
+
+    /// Returns the last definition of the macro `name` with `arity`
+    /// (`None` for the macro without parameters).
+    pub fn macro_def(&self, name: &str, arity: Option<usize>) -> Option<&SymbolEntry> {
+        self.macros
+            .iter()
+            .rev()
+            .find(|m| m.name == name && m.arity == arity)
+    }
+
+    /// Returns the attributes named `name`.
+    pub fn attributes<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SymbolEntry> + 'a {
+        self.attributes.iter().filter(move |a| a.name == name)
+    }
+}
+
+/// Named item in a `ModuleIndex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub arity: Option<usize>,
+    pub span: Span,
+}
+impl SymbolEntry {
+    fn new(name: &str, arity: Option<usize>, span: Span) -> Self {
+        SymbolEntry {
+            name: name.to_owned(),
+            arity,
+            span,
+        }
+    }
+}
+
+/// Entry of an `-import` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportEntry {
+    pub module: String,
+    pub name: String,
+    pub arity: usize,
+    pub span: Span,
+}
+
+fn find<'a>(
+    entries: &'a [SymbolEntry],
+    name: &str,
+    arity: Option<usize>,
+) -> Option<&'a SymbolEntry> {
+    entries.iter().find(|e| e.name == name && e.arity == arity)
+}
+
+fn entry(x: &NameAndArity) -> SymbolEntry {
+    SymbolEntry::new(x.name.value(), Some(arity(x)), Span::of(x))
+}
+
+/// Converts `Name/Arity` in a term to an entry.
+fn name_and_arity(expr: &Expr) -> Option<SymbolEntry> {
+    match *expr {
+        Expr::BinaryOpCall(ref x) if matches!(x.op, BinaryOp::FloatDiv(_)) => {
+            match (&x.left, &x.right) {
+                (Expr::Literal(Literal::Atom(ref n)), Expr::Literal(Literal::Integer(ref a))) => {
+                    let arity = a.value().to_usize()?;
+                    Some(SymbolEntry::new(n.value(), Some(arity), Span::of(expr)))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn arity(x: &NameAndArity) -> usize {
+    x.arity.value().to_usize().unwrap_or(usize::MAX)
+}
+
+/// Returns the name of `form` if it is an attribute (or a preprocessor directive).
+fn attribute_name(form: &Form) -> Option<&str> {
+    Some(match *form {
+        Form::ModuleAttr(_) => "module",
+        Form::ExportAttr(_) => "export",
+        Form::ExportTypeAttr(_) => "export_type",
+        Form::ImportAttr(_) => "import",
+        Form::FileAttr(_) => "file",
+        Form::CompileAttr(_) => "compile",
+        Form::VsnAttr(_) => "vsn",
+        Form::FeatureAttr(_) => "feature",
+        Form::BehaviourAttr(ref x) => x._behaviour.value(),
+        Form::OnLoadAttr(_) => "on_load",
+        Form::DialyzerAttr(_) => "dialyzer",
+        Form::DocAttr(ref x) => {
+            if x.is_module_doc() {
+                "moduledoc"
+            } else {
+                "doc"
+            }
+        }
+        Form::WildAttr(ref x) => x.attr_name.value(),
+        Form::ExtensionAttr(ref x) => x.attr_name.value(),
+        Form::FunSpec(_) => "spec",
+        Form::CallbackSpec(_) => "callback",
+        Form::RecordDecl(_) => "record",
+        Form::TypeDecl(ref x) => x.type_kind.value(),
+        Form::Directive(ref x) => x.name.value(),
+        Form::FunDecl(_) | Form::Error(_) | Form::Unparsed(_) => return None,
+    })
+}
@@ -0,0 +1,161 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::PositionRange;
+
+use crate::cst::commons::parts::{BinaryOp, UnaryOp};
+use crate::cst::{Literal, ModuleDecl, Node, Pattern};
+use crate::messages::Message;
+use crate::Diagnostic;
+
+/// Reports patterns which are parsed but rejected by `erlc`.
+///
+/// The pattern grammar of this crate rejects function calls but accepts any operator call,
+/// so this checks that
+///
+/// - arithmetic operators are applied only to constants (e.g., `-1` or `2 * 3`),
+/// - `++` is applied only to a string (or character list) literal on the left,
+/// - no other operators (e.g., comparisons) are used,
+/// - map patterns use only `:=`, and
+/// - map keys are guard expressions (e.g., they contain neither `=` nor `_`).
+///
+/// The messages are the same as those of `erlc` (e.g., "illegal pattern").
+/// Note that the bound-ness of the variables in map keys is not checked.
+pub fn check_patterns(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let mut checker = PatternChecker {
+        diagnostics: Vec::new(),
+    };
+    for form in Node::forms(module) {
+        checker.visit(form);
+    }
+    checker.diagnostics
+}
+
+struct PatternChecker {
+    diagnostics: Vec<Diagnostic>,
+}
+impl PatternChecker {
+    fn visit(&mut self, node: Node) {
+        match node {
+            Node::Pattern(x) => self.check_pattern(x),
+            _ => {
+                for child in node.children() {
+                    self.visit(child);
+                }
+            }
+        }
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern) {
+        let legal = match *pattern {
+            Pattern::Map(ref x) => {
+                for field in x.fields.iter().flat_map(|s| s.iter()) {
+                    if field._relation.value() != Symbol::MapMatch {
+                        self.report("illegal_pattern", &field._relation);
+                    }
+                    self.check_map_key(&field.key);
+                    self.check_pattern(&field.value);
+                }
+                return;
+            }
+            Pattern::Bits(ref x) => {
+                // The sizes are guard expressions (e.g., `<<X:(N * 8)>>`)
+                for e in x.elements.iter().flat_map(|s| s.iter()) {
+                    self.check_pattern(&e.element);
+                }
+                return;
+            }
+            Pattern::UnaryOpCall(_) => is_constant(pattern),
+            Pattern::BinaryOpCall(ref x) => match x.op {
+                BinaryOp::PlusPlus(_) => is_char_list(&x.left),
+                _ => is_constant(pattern),
+            },
+            _ => true,
+        };
+        if !legal {
+            self.report("illegal_pattern", pattern);
+            return;
+        }
+        for child in Node::Pattern(pattern).children() {
+            self.visit(child);
+        }
+    }
+
+    fn check_map_key(&mut self, key: &Pattern) {
+        match *key {
+            Pattern::Variable(ref x) if x.value() == "_" => {
+                self.report("unbound_underscore", x);
+                return;
+            }
+            Pattern::Match(_) => {
+                self.report("illegal_map_key", key);
+                return;
+            }
+            Pattern::BinaryOpCall(ref x) => {
+                if let BinaryOp::PlusPlus(_) | BinaryOp::MinusMinus(_) = x.op {
+                    self.report("illegal_map_key", key);
+                    return;
+                }
+            }
+            _ => {}
+        }
+        for child in Node::Pattern(key).children() {
+            if let Node::Pattern(x) = child {
+                self.check_map_key(x);
+            }
+        }
+    }
+
+    fn report<T: PositionRange>(&mut self, code: &'static str, range: &T) {
+        self.diagnostics
+            .push(Diagnostic::error(code, Message::new(code), range));
+    }
+}
+
+/// Returns `true` if `pattern` is an arithmetic expression consisting of number literals.
+fn is_constant(pattern: &Pattern) -> bool {
+    match *pattern {
+        Pattern::Literal(Literal::Integer(_))
+        | Pattern::Literal(Literal::Float(_))
+        | Pattern::Literal(Literal::Char(_)) => true,
+        Pattern::Parenthesized(ref x) => is_constant(&x.item),
+        Pattern::UnaryOpCall(ref x) => !matches!(x.op, UnaryOp::Not(_)) && is_constant(&x.operand),
+        Pattern::BinaryOpCall(ref x) => {
+            is_arithmetic(&x.op) && is_constant(&x.left) && is_constant(&x.right)
+        }
+        _ => false,
+    }
+}
+
+fn is_arithmetic(op: &BinaryOp) -> bool {
+    matches!(
+        *op,
+        BinaryOp::Plus(_)
+            | BinaryOp::Minus(_)
+            | BinaryOp::Mul(_)
+            | BinaryOp::FloatDiv(_)
+            | BinaryOp::IntDiv(_)
+            | BinaryOp::Rem(_)
+            | BinaryOp::Bor(_)
+            | BinaryOp::Bxor(_)
+            | BinaryOp::Band(_)
+            | BinaryOp::Bsl(_)
+            | BinaryOp::Bsr(_)
+    )
+}
+
+/// Returns `true` if `pattern` is a string literal or a proper list of character (or integer) literals.
+fn is_char_list(pattern: &Pattern) -> bool {
+    match *pattern {
+        Pattern::Literal(ref x) => x.string_value().is_some(),
+        Pattern::Parenthesized(ref x) => is_char_list(&x.item),
+        Pattern::List(ref x) => {
+            x.is_proper_list()
+                && x.iter().all(|e| {
+                    matches!(
+                        *e,
+                        Pattern::Literal(Literal::Char(_)) | Pattern::Literal(Literal::Integer(_))
+                    )
+                })
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,84 @@
+use super::{EvalError, Evaluator};
+use crate::cst::{Expr, ModuleDecl, Node};
+use crate::messages::Message;
+use crate::traits::CollectTokens;
+use crate::{ast, Diagnostic};
+
+/// Reports default values of record fields which are not compile-time constants.
+///
+/// Each default value is classified by folding it with `Evaluator`,
+/// so constant expressions including calls of pure BIFs (e.g., `length([a, b])`) are accepted.
+/// Among the rest,
+///
+/// - function calls (e.g., `erlang:system_time()`) are reported, as they are evaluated
+///   each time a record is created and make the record unusable in patterns and guards,
+/// - variables, matches and `receive` expressions are reported as non-constants, and
+/// - constants whose evaluation fails (e.g., `1 / 0`) are reported with the exception reason.
+///
+/// The other expressions (e.g., records, funs and binaries) are not reported.
+pub fn check_record_defaults(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let empty = ast::Module { forms: Vec::new() };
+    let mut diagnostics = Vec::new();
+    for record in module.record_decls() {
+        for field in record.fields.elements.iter().flat_map(|s| s.iter()) {
+            let default = match field.field_default {
+                None => continue,
+                Some(ref d) => &d.value,
+            };
+            let reason = match Evaluator::new(&empty).eval(&ast::Expr::from(default)) {
+                Ok(_) => continue,
+                Err(EvalError::Exception(reason)) => Some(reason),
+                Err(_) => None,
+            };
+            let field_name = format!("#{}.{}", record.record_name.value(), field.name());
+            let diagnostic = match (non_constant(default), reason) {
+                (Some(Expr::FunCall(ref call)), _) => {
+                    let function = match call.module {
+                        None => call.name.to_erlang_source(),
+                        Some(ref m) => format!(
+                            "{}:{}",
+                            m.name.to_erlang_source(),
+                            call.name.to_erlang_source()
+                        ),
+                    };
+                    let message = Message::new("record_default_call")
+                        .arg("field", field_name)
+                        .arg("function", function);
+                    Diagnostic::warning("record_default_call", message, call)
+                }
+                (Some(x), _) => {
+                    let message = Message::new("record_default_not_constant")
+                        .arg("field", field_name)
+                        .arg("expr", x.to_erlang_source());
+                    Diagnostic::warning("record_default_not_constant", message, x)
+                }
+                (None, Some(reason)) => {
+                    let message = Message::new("record_default_exception")
+                        .arg("field", field_name)
+                        .arg("reason", reason.to_string());
+                    Diagnostic::warning("record_default_exception", message, default)
+                }
+                (None, None) => continue,
+            };
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+/// Returns the first function call, variable, match or `receive` expression in `expr`.
+fn non_constant(expr: &Expr) -> Option<&Expr> {
+    let mut found = None;
+    Node::Expr(expr).walk(|node| {
+        if let Node::Expr(x) = node {
+            let is_non_constant = matches!(
+                *x,
+                Expr::FunCall(_) | Expr::Variable(_) | Expr::Match(_) | Expr::Receive(_)
+            );
+            if is_non_constant && found.is_none() {
+                found = Some(x);
+            }
+        }
+    });
+    found
+}
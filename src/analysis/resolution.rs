@@ -0,0 +1,301 @@
+use num::ToPrimitive;
+use std::collections::HashMap;
+
+use super::ModuleIndex;
+use crate::cst::commons::{AtomOrVariable, IntegerOrVariable};
+use crate::cst::exprs::Fun;
+use crate::cst::{is_guard_bif, Expr, GuardTest, Literal, ModuleDecl, Node};
+use crate::Span;
+
+/// Target of a local call (i.e., a call without module prefix).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    /// The function is defined in the module.
+    Local,
+
+    /// The function is imported by `-import` from `module`.
+    Imported { module: String },
+
+    /// The function is a BIF auto-imported from `erlang`.
+    AutoImported,
+
+    /// The function is not defined anywhere.
+    Undefined,
+}
+
+/// Annotation layer which maps the local calls in a module to their `Resolution`s.
+///
+/// The calls are keyed by their spans, which cover the whole call expressions
+/// (e.g., `f(X)` or `fun f/1`), as in `CallEdge::span`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallResolutions {
+    entries: Vec<(Span, Resolution)>,
+    index: HashMap<Span, usize>,
+}
+impl CallResolutions {
+    /// Resolves the local calls (including local `fun` references) in `module`.
+    ///
+    /// A function defined in the module takes precedence over an imported one,
+    /// which in turn takes precedence over an auto-imported BIF (as the compiler does).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erl_parse::analysis::{CallResolutions, ModuleIndex, Resolution};
+    /// use erl_parse::cst::ModuleDecl;
+    /// use erl_parse::{Parser, Span, TokenReader};
+    /// use erl_tokenize::Lexer;
+    ///
+    /// let text = "-import(lists, [map/2]).\nf(X) -> g(map(fun h/1, X)), length(X), i().\nh(X) -> X.";
+    /// let module: ModuleDecl = Parser::new(TokenReader::new(Lexer::new(text))).parse().unwrap();
+    ///
+    /// let index = ModuleIndex::new(&module);
+    /// let resolutions = CallResolutions::new(&module, &index);
+    /// let calls: Vec<_> = resolutions
+    ///     .iter()
+    ///     .map(|(span, r)| (span.text(text).unwrap(), r.clone()))
+    ///     .collect();
+    /// assert_eq!(
+    ///     calls,
+    ///     [
+    ///         ("g(map(fun h/1, X))", Resolution::Undefined),
+    ///         ("map(fun h/1, X)", Resolution::Imported { module: "lists".to_owned() }),
+    ///         ("fun h/1", Resolution::Local),
+    ///         ("length(X)", Resolution::AutoImported),
+    ///         ("i()", Resolution::Undefined),
+    ///     ]
+    /// );
+    /// ```
+    pub fn new(module: &ModuleDecl, index: &ModuleIndex) -> Self {
+        let mut resolutions = CallResolutions::default();
+        for form in &module.forms {
+            Node::Form(form).walk(|node| {
+                let (name, arity, span) = match node {
+                    Node::Expr(Expr::FunCall(x)) if x.module.is_none() => match x.name {
+                        Expr::Literal(Literal::Atom(ref t)) => {
+                            (t.value(), x.args.len(), Span::of(&**x))
+                        }
+                        _ => return,
+                    },
+                    Node::Expr(Expr::Fun(x)) => match **x {
+                        Fun::Defined(ref x) if x.module.is_none() => {
+                            match (&x.fun.name, &x.fun.arity) {
+                                (
+                                    AtomOrVariable::Atom(ref n),
+                                    IntegerOrVariable::Integer(ref a),
+                                ) => (
+                                    n.value(),
+                                    a.value().to_usize().unwrap_or(usize::MAX),
+                                    Span::of(x),
+                                ),
+                                _ => return,
+                            }
+                        }
+                        _ => return,
+                    },
+                    Node::GuardTest(GuardTest::FunCall(x)) if x.module.is_none() => {
+                        (x.name.value(), x.args.len(), Span::of(&**x))
+                    }
+                    _ => return,
+                };
+                resolutions.insert(span, resolve_call(index, name, arity));
+            });
+        }
+        resolutions
+    }
+
+    /// Returns the resolution of the call whose span is `span`.
+    pub fn get(&self, span: &Span) -> Option<&Resolution> {
+        self.index.get(span).map(|&i| &self.entries[i].1)
+    }
+
+    /// Returns an iterator over the calls and their resolutions in the order of appearance.
+    pub fn iter(&self) -> impl Iterator<Item = (&Span, &Resolution)> {
+        self.entries.iter().map(|(s, r)| (s, r))
+    }
+
+    /// Returns an iterator over the calls of undefined functions.
+    pub fn undefined(&self) -> impl Iterator<Item = &Span> {
+        self.iter()
+            .filter(|(_, r)| **r == Resolution::Undefined)
+            .map(|(s, _)| s)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn insert(&mut self, span: Span, resolution: Resolution) {
+        if let Some(&i) = self.index.get(&span) {
+            self.entries[i].1 = resolution;
+        } else {
+            self.index.insert(span.clone(), self.entries.len());
+            self.entries.push((span, resolution));
+        }
+    }
+}
+
+/// Resolves the local call of `name/arity`.
+pub fn resolve_call(index: &ModuleIndex, name: &str, arity: usize) -> Resolution {
+    if index.function(name, arity).is_some() {
+        Resolution::Local
+    } else if let Some(i) = index.import(name, arity) {
+        Resolution::Imported {
+            module: i.module.clone(),
+        }
+    } else if index.is_auto_imported(name, arity) {
+        Resolution::AutoImported
+    } else {
+        Resolution::Undefined
+    }
+}
+
+/// Returns `true` if `name/arity` is a BIF auto-imported from `erlang`.
+///
+/// The BIFs are the ones listed by `erl_internal:bif/2` (as of OTP 27), including the guard BIFs.
+pub(crate) fn is_auto_imported_bif(name: &str, arity: usize) -> bool {
+    is_guard_bif(name, arity)
+        || matches!(
+            (name, arity),
+            ("alias", 0)
+                | ("alias", 1)
+                | ("apply", 2)
+                | ("apply", 3)
+                | ("atom_to_binary", 1)
+                | ("atom_to_binary", 2)
+                | ("atom_to_list", 1)
+                | ("binary_to_atom", 1)
+                | ("binary_to_atom", 2)
+                | ("binary_to_existing_atom", 1)
+                | ("binary_to_existing_atom", 2)
+                | ("binary_to_float", 1)
+                | ("binary_to_integer", 1)
+                | ("binary_to_integer", 2)
+                | ("binary_to_list", 1)
+                | ("binary_to_list", 3)
+                | ("binary_to_term", 1)
+                | ("binary_to_term", 2)
+                | ("bitstring_to_list", 1)
+                | ("check_old_code", 1)
+                | ("check_process_code", 2)
+                | ("check_process_code", 3)
+                | ("date", 0)
+                | ("delete_module", 1)
+                | ("demonitor", 1)
+                | ("demonitor", 2)
+                | ("disconnect_node", 1)
+                | ("erase", 0)
+                | ("erase", 1)
+                | ("error", 1)
+                | ("error", 2)
+                | ("error", 3)
+                | ("exit", 1)
+                | ("exit", 2)
+                | ("float_to_binary", 1)
+                | ("float_to_binary", 2)
+                | ("float_to_list", 1)
+                | ("float_to_list", 2)
+                | ("garbage_collect", 0)
+                | ("garbage_collect", 1)
+                | ("garbage_collect", 2)
+                | ("get", 0)
+                | ("get", 1)
+                | ("get_keys", 0)
+                | ("get_keys", 1)
+                | ("group_leader", 0)
+                | ("group_leader", 2)
+                | ("halt", 0)
+                | ("halt", 1)
+                | ("halt", 2)
+                | ("integer_to_binary", 1)
+                | ("integer_to_binary", 2)
+                | ("integer_to_list", 1)
+                | ("integer_to_list", 2)
+                | ("iolist_size", 1)
+                | ("iolist_to_binary", 1)
+                | ("is_alive", 0)
+                | ("is_process_alive", 1)
+                | ("link", 1)
+                | ("list_to_atom", 1)
+                | ("list_to_binary", 1)
+                | ("list_to_bitstring", 1)
+                | ("list_to_existing_atom", 1)
+                | ("list_to_float", 1)
+                | ("list_to_integer", 1)
+                | ("list_to_integer", 2)
+                | ("list_to_pid", 1)
+                | ("list_to_port", 1)
+                | ("list_to_ref", 1)
+                | ("list_to_tuple", 1)
+                | ("load_module", 2)
+                | ("make_ref", 0)
+                | ("module_loaded", 1)
+                | ("monitor", 2)
+                | ("monitor", 3)
+                | ("monitor_node", 2)
+                | ("monitor_node", 3)
+                | ("nodes", 0)
+                | ("nodes", 1)
+                | ("nodes", 2)
+                | ("now", 0)
+                | ("open_port", 2)
+                | ("pid_to_list", 1)
+                | ("port_close", 1)
+                | ("port_command", 2)
+                | ("port_command", 3)
+                | ("port_connect", 2)
+                | ("port_control", 3)
+                | ("port_to_list", 1)
+                | ("pre_loaded", 0)
+                | ("process_flag", 2)
+                | ("process_flag", 3)
+                | ("process_info", 1)
+                | ("process_info", 2)
+                | ("processes", 0)
+                | ("purge_module", 1)
+                | ("put", 2)
+                | ("ref_to_list", 1)
+                | ("register", 2)
+                | ("registered", 0)
+                | ("setelement", 3)
+                | ("spawn", 1)
+                | ("spawn", 2)
+                | ("spawn", 3)
+                | ("spawn", 4)
+                | ("spawn_link", 1)
+                | ("spawn_link", 2)
+                | ("spawn_link", 3)
+                | ("spawn_link", 4)
+                | ("spawn_monitor", 1)
+                | ("spawn_monitor", 2)
+                | ("spawn_monitor", 3)
+                | ("spawn_monitor", 4)
+                | ("spawn_opt", 2)
+                | ("spawn_opt", 3)
+                | ("spawn_opt", 4)
+                | ("spawn_opt", 5)
+                | ("spawn_request", 1)
+                | ("spawn_request", 2)
+                | ("spawn_request", 3)
+                | ("spawn_request", 4)
+                | ("spawn_request", 5)
+                | ("spawn_request_abandon", 1)
+                | ("split_binary", 2)
+                | ("statistics", 1)
+                | ("term_to_binary", 1)
+                | ("term_to_binary", 2)
+                | ("term_to_iovec", 1)
+                | ("term_to_iovec", 2)
+                | ("throw", 1)
+                | ("time", 0)
+                | ("tuple_to_list", 1)
+                | ("unalias", 1)
+                | ("unlink", 1)
+                | ("unregister", 1)
+                | ("whereis", 1)
+        )
+}
@@ -0,0 +1,304 @@
+use num::{BigInt, ToPrimitive};
+use std::collections::HashMap;
+
+use crate::cst::commons::parts::{Args, BinaryOp, UnaryOp};
+use crate::cst::forms::{FunSpec, TypeDecl};
+use crate::cst::{Form, Literal, ModuleDecl, Pattern, Type};
+use crate::messages::Message;
+use crate::traits::CollectTokens;
+use crate::Diagnostic;
+
+/// The maximum depth of the local type aliases followed by `check_spec_clauses`.
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Reports function clauses which contradict the specs of their functions (spec drift).
+///
+/// A clause contradicts a spec if, for every clause of the spec, some argument pattern
+/// of the function clause has a literal shape which the declared type never admits
+/// (e.g., matching `foo` where the spec says `integer()`).
+/// Such clauses are dead code as far as the spec is concerned, or the spec is outdated.
+///
+/// Only the shapes of patterns (atoms, numbers, lists, tuples, maps, binaries and records)
+/// are compared with built-in types, literal types, unions, ranges and local type aliases without parameters;
+/// the other types (e.g., remote types and type variables) are regarded as admitting anything,
+/// so the diagnostics are advisory warnings without false positives in common code.
+pub fn check_spec_clauses(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let aliases = module
+        .type_decls()
+        .filter(|t| t.arity() == 0)
+        .map(|t| (t.name(), t))
+        .collect::<HashMap<_, _>>();
+    let checker = SpecChecker { aliases };
+
+    let mut specs = HashMap::new();
+    for form in &module.forms {
+        if let Form::FunSpec(ref s) = *form {
+            let is_local = s
+                .module
+                .as_ref()
+                .is_none_or(|m| Some(m.name.value()) == module.module_name());
+            if is_local {
+                specs.insert((s.fun_name.value(), s.arity()), s);
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for f in module.fun_decls() {
+        let spec = match specs.get(&(f.name(), f.arity())) {
+            None => continue,
+            Some(spec) => spec,
+        };
+        for clause in f.clauses.iter() {
+            if let Some(d) = checker.check_clause(f.name(), &clause.patterns, spec) {
+                diagnostics.push(d);
+            }
+        }
+    }
+    diagnostics
+}
+
+struct SpecChecker<'a> {
+    aliases: HashMap<&'a str, &'a TypeDecl>,
+}
+impl<'a> SpecChecker<'a> {
+    fn check_clause(
+        &self,
+        name: &str,
+        patterns: &Args<Pattern>,
+        spec: &FunSpec,
+    ) -> Option<Diagnostic> {
+        let mut first_mismatch = None;
+        for spec_clause in spec.clauses.iter() {
+            let mismatch = patterns
+                .iter()
+                .zip(spec_clause.args.iter())
+                .find(|&(p, t)| !self.admits(t, &Shape::of(p), 0));
+            match mismatch {
+                None => return None,
+                Some(m) => {
+                    first_mismatch.get_or_insert(m);
+                }
+            }
+        }
+        let (pattern, ty) = first_mismatch?;
+        let name = format!("{}/{}", name, spec.arity());
+        let message = Message::new("spec_mismatch")
+            .arg("name", name)
+            .arg("pattern", pattern.to_erlang_source())
+            .arg("type", ty.to_erlang_source());
+        Some(
+            Diagnostic::warning("spec_mismatch", message, pattern)
+                .with_related(Message::new("declared_type"), ty),
+        )
+    }
+
+    /// Returns `false` if no value of `shape` is of the type `ty`.
+    fn admits(&self, ty: &Type, shape: &Shape, depth: usize) -> bool {
+        if *shape == Shape::Unknown {
+            return true;
+        }
+        match *ty {
+            Type::Annotated(ref x) => self.admits(&x.ty, shape, depth),
+            Type::Parenthesized(ref x) => self.admits(&x.item, shape, depth),
+            Type::Union(ref x) => {
+                self.admits(&x.left, shape, depth) || self.admits(&x.right, shape, depth)
+            }
+            Type::Literal(Literal::Atom(ref a)) => match *shape {
+                Shape::Atom(ref b) => a.value() == b,
+                _ => false,
+            },
+            Type::Literal(_) | Type::UnaryOpCall(_) | Type::BinaryOpCall(_) => {
+                match (eval_integer(ty), shape) {
+                    (Some(n), Shape::Integer(Some(m))) => n == *m,
+                    (_, Shape::Integer(_)) => true,
+                    _ => false,
+                }
+            }
+            Type::Range(ref x) => match *shape {
+                Shape::Integer(Some(ref n)) => {
+                    eval_integer(&x.low).is_none_or(|low| low <= *n)
+                        && eval_integer(&x.high).is_none_or(|high| *n <= high)
+                }
+                Shape::Integer(None) => true,
+                _ => false,
+            },
+            Type::Tuple(ref x) => match *shape {
+                Shape::Tuple(n) => x.len() == n,
+                Shape::Record(_) => true,
+                _ => false,
+            },
+            Type::Record(ref x) => match *shape {
+                Shape::Record(ref name) => x.name.as_atom().is_none_or(|a| a.value() == name),
+                Shape::Tuple(_) => true,
+                _ => false,
+            },
+            Type::Map(_) => *shape == Shape::Map,
+            Type::Bits(_) => *shape == Shape::Binary,
+            Type::List(ref x) => match *shape {
+                Shape::Nil => x.element.as_ref().is_none_or(|e| e.non_empty.is_none()),
+                Shape::List => x.element.is_some(),
+                _ => false,
+            },
+            Type::Fun(_) => false,
+            Type::TypeCall(ref x) => {
+                if x.module.is_some() {
+                    return true;
+                }
+                let name = x.name.value();
+                if x.args.is_empty() {
+                    if let Some(alias) = self.aliases.get(name) {
+                        return depth >= MAX_ALIAS_DEPTH
+                            || self.admits(&alias.ty, shape, depth + 1);
+                    }
+                }
+                admits_builtin(name, shape)
+            }
+            Type::Variable(_) | Type::MacroCall(_) => true,
+        }
+    }
+}
+
+/// Returns whether the built-in type `name` admits `shape` (`true` if the type is unknown).
+fn admits_builtin(name: &str, shape: &Shape) -> bool {
+    let integer = |f: fn(&BigInt) -> bool| match *shape {
+        Shape::Integer(Some(ref n)) => f(n),
+        Shape::Integer(None) => true,
+        _ => false,
+    };
+    let list = matches!(*shape, Shape::Nil | Shape::List);
+    match name {
+        "term" | "any" | "dynamic" => true,
+        "none" | "no_return" | "pid" | "port" | "reference" | "identifier" | "fun" | "function" => {
+            false
+        }
+        "atom" | "module" | "node" => matches!(*shape, Shape::Atom(_)),
+        "boolean" => matches!(*shape, Shape::Atom(ref a) if a == "true" || a == "false"),
+        "integer" => integer(|_| true),
+        "non_neg_integer" => integer(|n| n.to_i64().is_none_or(|n| n >= 0)),
+        "pos_integer" => integer(|n| n.to_i64().is_none_or(|n| n > 0)),
+        "neg_integer" => integer(|n| n.to_i64().is_none_or(|n| n < 0)),
+        "byte" | "arity" => integer(|n| n.to_i64().is_some_and(|n| (0..=255).contains(&n))),
+        "char" => integer(|n| n.to_i64().is_some_and(|n| (0..=0x10ffff).contains(&n))),
+        "float" => *shape == Shape::Float,
+        "number" => matches!(*shape, Shape::Integer(_) | Shape::Float),
+        "timeout" => {
+            integer(|n| n.to_i64().is_none_or(|n| n >= 0))
+                || matches!(*shape, Shape::Atom(ref a) if a == "infinity")
+        }
+        "nil" => *shape == Shape::Nil,
+        "list" | "string" | "maybe_improper_list" | "iolist" | "charlist" => list,
+        "nonempty_list"
+        | "nonempty_string"
+        | "nonempty_maybe_improper_list"
+        | "nonempty_improper_list" => *shape == Shape::List,
+        "binary" | "bitstring" | "nonempty_binary" | "nonempty_bitstring" => {
+            *shape == Shape::Binary
+        }
+        "iodata" => list || *shape == Shape::Binary,
+        "tuple" => matches!(*shape, Shape::Tuple(_) | Shape::Record(_)),
+        "mfa" => matches!(*shape, Shape::Tuple(3) | Shape::Record(_)),
+        "map" => *shape == Shape::Map,
+        _ => true,
+    }
+}
+
+/// Evaluates an integer type (e.g., `-1` or `1 bsl 8`) if it consists of integer literals.
+fn eval_integer(ty: &Type) -> Option<BigInt> {
+    match *ty {
+        Type::Literal(Literal::Integer(ref x)) => Some(BigInt::from(x.value().clone())),
+        Type::Literal(Literal::Char(ref x)) => Some(BigInt::from(x.value() as u32)),
+        Type::Parenthesized(ref x) => eval_integer(&x.item),
+        Type::UnaryOpCall(ref x) => {
+            let n = eval_integer(&x.operand)?;
+            match x.op {
+                UnaryOp::Plus(_) => Some(n),
+                UnaryOp::Minus(_) => Some(-n),
+                _ => None,
+            }
+        }
+        Type::BinaryOpCall(ref x) => {
+            let (l, r) = (eval_integer(&x.left)?, eval_integer(&x.right)?);
+            match x.op {
+                BinaryOp::Plus(_) => Some(l + r),
+                BinaryOp::Minus(_) => Some(l - r),
+                BinaryOp::Mul(_) => Some(l * r),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Literal shape of a pattern.
+#[derive(Debug, PartialEq, Eq)]
+enum Shape {
+    Atom(String),
+
+    /// An integer (`None` if the value is unknown)
+    Integer(Option<BigInt>),
+    Float,
+
+    /// `[]` (or `""`)
+    Nil,
+
+    /// A non-empty list (or string)
+    List,
+    Tuple(usize),
+    Map,
+    Binary,
+    Record(String),
+
+    /// Anything (e.g., a variable)
+    Unknown,
+}
+impl Shape {
+    fn of(pattern: &Pattern) -> Self {
+        match *pattern {
+            Pattern::Literal(ref x) => match *x {
+                Literal::Atom(ref a) => Shape::Atom(a.value().to_owned()),
+                Literal::Char(ref c) => Shape::Integer(Some(BigInt::from(c.value() as u32))),
+                Literal::Integer(ref i) => Shape::Integer(Some(BigInt::from(i.value().clone()))),
+                Literal::Float(_) => Shape::Float,
+                Literal::String { .. } | Literal::TripleQuotedString(_) => {
+                    if x.string_value().is_some_and(|s| s.is_empty()) {
+                        Shape::Nil
+                    } else {
+                        Shape::List
+                    }
+                }
+            },
+            Pattern::Tuple(ref x) => Shape::Tuple(x.len()),
+            Pattern::Map(_) => Shape::Map,
+            Pattern::Record(ref x) => match x.name.as_atom() {
+                Some(name) => Shape::Record(name.value().to_owned()),
+                None => Shape::Unknown,
+            },
+            Pattern::RecordFieldIndex(_) => Shape::Integer(None),
+            Pattern::List(ref x) => {
+                if x.elements.is_none() {
+                    Shape::Nil
+                } else {
+                    Shape::List
+                }
+            }
+            Pattern::Bits(_) => Shape::Binary,
+            Pattern::Parenthesized(ref x) => Shape::of(&x.item),
+            Pattern::Match(ref x) => match Shape::of(&x.pattern) {
+                Shape::Unknown => Shape::of(&x.value),
+                shape => shape,
+            },
+            Pattern::UnaryOpCall(ref x) => match (&x.op, Shape::of(&x.operand)) {
+                (UnaryOp::Minus(_), Shape::Integer(n)) => Shape::Integer(n.map(|n| -n)),
+                (UnaryOp::Plus(_), Shape::Integer(n)) => Shape::Integer(n),
+                (UnaryOp::Minus(_) | UnaryOp::Plus(_), Shape::Float) => Shape::Float,
+                _ => Shape::Unknown,
+            },
+            Pattern::BinaryOpCall(ref x) => match x.op {
+                BinaryOp::PlusPlus(_) => Shape::List,
+                _ => Shape::Unknown,
+            },
+            Pattern::Variable(_) | Pattern::MacroCall(_) => Shape::Unknown,
+        }
+    }
+}
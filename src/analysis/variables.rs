@@ -0,0 +1,606 @@
+use std::collections::BTreeMap;
+
+use crate::ast::{
+    self, AtomOrVariable, BinaryOp, CatchClause, Clause, Expr, ExprKind, Fun, IntegerOrVariable,
+    Pattern, PatternKind, Qualifier,
+};
+use crate::cst::ModuleDecl;
+use crate::messages::Message;
+use crate::{Diagnostic, Span};
+
+/// Reports the variable warnings and errors of `erlc` in function declarations:
+///
+/// - variables which are bound but never used (except for those starting with `_`),
+/// - variables which are used but not bound, and
+/// - variables used after a `case`, `if`, `receive`, `try`, `catch`, `maybe`, `andalso` or `orelse`
+///   expression which binds them only in some of its branches (i.e., unsafe variables).
+///
+/// The messages are the same as those of `erlc` (e.g., "variable 'X' is unused").
+/// Variables in macro arguments (in no-preprocess mode) are regarded as used if they are bound,
+/// but unbound ones are not reported.
+pub fn check_variables(module: &ModuleDecl) -> Vec<Diagnostic> {
+    let mut checker = VariableChecker {
+        bindings: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for decl in module.fun_decls() {
+        for clause in &ast::Function::from(decl).clauses {
+            checker.bindings.clear();
+            let mut env = Env::new();
+            checker.clause(&mut env, clause, true);
+            checker.report_unused();
+        }
+    }
+    checker
+        .diagnostics
+        .sort_by_key(|d| d.start_position.offset());
+    checker.diagnostics
+}
+
+/// Variables visible at a point, mapped to the indices of their bindings.
+type Env = BTreeMap<String, Var>;
+
+#[derive(Debug, Clone)]
+enum Var {
+    Bound(Vec<usize>),
+
+    /// Bound only in some branches of `construct` at `span`.
+    Unsafe {
+        bindings: Vec<usize>,
+        construct: &'static str,
+        span: Span,
+    },
+}
+impl Var {
+    fn bindings(&self) -> &[usize] {
+        match *self {
+            Var::Bound(ref x)
+            | Var::Unsafe {
+                bindings: ref x, ..
+            } => x,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Binding {
+    name: String,
+    span: Span,
+    used: bool,
+}
+
+#[derive(Debug)]
+struct VariableChecker {
+    bindings: Vec<Binding>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl VariableChecker {
+    /// Checks `clause`, whose head variables shadow the variables in `env` if `shadow` is `true`.
+    fn clause(&mut self, env: &mut Env, clause: &Clause, shadow: bool) {
+        let mut shadowed = Vec::new();
+        for p in &clause.patterns {
+            self.bind(env, p, shadow.then_some(&mut shadowed));
+        }
+        self.guards(env, &clause.guards);
+        self.exprs(env, &clause.body);
+    }
+
+    fn catch_clause(&mut self, env: &mut Env, clause: &CatchClause) {
+        if let Some(AtomOrVariable::Variable(ref v)) = clause.class {
+            self.bind_variable(env, v, &clause.span, None);
+        }
+        self.bind(env, &clause.pattern, None);
+        if let Some(ref v) = clause.stacktrace {
+            // The stacktrace variable has no span of its own
+            let i = self.bindings.len();
+            self.bind_variable(env, v, &clause.span, None);
+            if let Some(b) = self.bindings.get_mut(i) {
+                b.used = true;
+            }
+        }
+        self.guards(env, &clause.guards);
+        self.exprs(env, &clause.body);
+    }
+
+    fn guards(&mut self, env: &mut Env, guards: &[Vec<Expr>]) {
+        for test in guards.iter().flatten() {
+            self.expr(env, test);
+        }
+    }
+
+    fn exprs(&mut self, env: &mut Env, exprs: &[Expr]) {
+        for e in exprs {
+            self.expr(env, e);
+        }
+    }
+
+    fn expr(&mut self, env: &mut Env, expr: &Expr) {
+        match expr.kind {
+            ExprKind::Literal(_) | ExprKind::RecordIndex { .. } => {}
+            ExprKind::Variable(ref name) => self.use_variable(env, name, &expr.span),
+            ExprKind::Tuple(ref xs) | ExprKind::Block(ref xs) => self.exprs(env, xs),
+            ExprKind::List {
+                ref elements,
+                ref tail,
+            } => {
+                self.exprs(env, elements);
+                if let Some(ref x) = *tail {
+                    self.expr(env, x);
+                }
+            }
+            ExprKind::Map {
+                ref base,
+                ref fields,
+            } => {
+                if let Some(ref x) = *base {
+                    self.expr(env, x);
+                }
+                for f in fields {
+                    self.expr(env, &f.key);
+                    self.expr(env, &f.value);
+                }
+            }
+            ExprKind::Record {
+                ref base,
+                ref fields,
+                ..
+            } => {
+                if let Some(ref x) = *base {
+                    self.expr(env, x);
+                }
+                for f in fields {
+                    self.expr(env, &f.value);
+                }
+            }
+            ExprKind::RecordAccess { ref record, .. } => self.expr(env, record),
+            ExprKind::Bits(ref elems) => {
+                for e in elems {
+                    self.expr(env, &e.value);
+                    if let Some(ref x) = e.size {
+                        self.expr(env, x);
+                    }
+                }
+            }
+            ExprKind::ListComprehension {
+                ref element,
+                ref qualifiers,
+            }
+            | ExprKind::BitsComprehension {
+                ref element,
+                ref qualifiers,
+            } => {
+                let mut inner = env.clone();
+                self.qualifiers(&mut inner, qualifiers);
+                self.expr(&mut inner, element);
+            }
+            ExprKind::MapComprehension {
+                ref key,
+                ref value,
+                ref qualifiers,
+            } => {
+                let mut inner = env.clone();
+                self.qualifiers(&mut inner, qualifiers);
+                self.expr(&mut inner, key);
+                self.expr(&mut inner, value);
+            }
+            ExprKind::Fun(ref x) => self.fun(env, x, &expr.span),
+            ExprKind::Call {
+                ref module,
+                ref function,
+                ref args,
+            } => {
+                if let Some(ref x) = *module {
+                    self.expr(env, x);
+                }
+                self.expr(env, function);
+                self.exprs(env, args);
+            }
+            ExprKind::UnaryOp { ref operand, .. } => self.expr(env, operand),
+            ExprKind::BinaryOp {
+                op,
+                ref left,
+                ref right,
+            } => {
+                self.expr(env, left);
+                if op == BinaryOp::Andalso || op == BinaryOp::Orelse {
+                    // The right operand may not be evaluated
+                    let mut branch = env.clone();
+                    self.expr(&mut branch, right);
+                    self.merge(env, vec![branch, env.clone()], op.as_str(), &expr.span);
+                } else {
+                    self.expr(env, right);
+                }
+            }
+            ExprKind::Match {
+                ref pattern,
+                ref value,
+            }
+            | ExprKind::MaybeMatch {
+                ref pattern,
+                ref value,
+            } => {
+                self.expr(env, value);
+                self.bind(env, pattern, None);
+            }
+            ExprKind::Catch(ref xs) => {
+                // The evaluation may be aborted by an exception
+                let mut branch = env.clone();
+                self.exprs(&mut branch, xs);
+                self.merge(env, vec![branch, env.clone()], "catch", &expr.span);
+            }
+            ExprKind::If(ref clauses) => {
+                let branches = self.branches(env, clauses);
+                self.merge(env, branches, "if", &expr.span);
+            }
+            ExprKind::Case {
+                expr: ref x,
+                ref clauses,
+            } => {
+                self.expr(env, x);
+                let branches = self.branches(env, clauses);
+                self.merge(env, branches, "case", &expr.span);
+            }
+            ExprKind::Receive {
+                ref clauses,
+                ref timeout,
+            } => {
+                let mut branches = self.branches(env, clauses);
+                if let Some(ref t) = *timeout {
+                    self.expr(env, &t.duration);
+                    let mut branch = env.clone();
+                    self.exprs(&mut branch, &t.body);
+                    branches.push(branch);
+                }
+                self.merge(env, branches, "receive", &expr.span);
+            }
+            ExprKind::Try {
+                ref body,
+                ref clauses,
+                ref catch_clauses,
+                ref after,
+            } => {
+                let mut branches = Vec::new();
+                let mut body_env = env.clone();
+                self.exprs(&mut body_env, body);
+                branches.extend(self.branches(&body_env, clauses));
+                branches.push(body_env);
+                for c in catch_clauses {
+                    let mut branch = env.clone();
+                    self.catch_clause(&mut branch, c);
+                    branches.push(branch);
+                }
+                let mut branch = env.clone();
+                self.exprs(&mut branch, after);
+                branches.push(branch);
+
+                // Variables bound in `try` expressions are never safe to use afterwards
+                self.merge(env, branches, "try", &expr.span);
+                self.make_unsafe(env, "try", &expr.span);
+            }
+            ExprKind::Maybe {
+                ref body,
+                ref else_clauses,
+            } => {
+                let mut branch = env.clone();
+                self.exprs(&mut branch, body);
+                let mut branches = self.branches(env, else_clauses);
+                branches.push(branch);
+                self.merge(env, branches, "maybe", &expr.span);
+                self.make_unsafe(env, "maybe", &expr.span);
+            }
+            ExprKind::MacroCall(ref x) => {
+                self.use_in_text(env, x.args.iter().flatten());
+            }
+            ExprKind::Extension(ref x) => self.use_in_text(env, Some(&x.body)),
+        }
+    }
+
+    fn fun(&mut self, env: &mut Env, fun: &Fun, span: &Span) {
+        match *fun {
+            Fun::Defined {
+                ref module,
+                ref name,
+                ref arity,
+            } => {
+                let module = module.iter().chain(Some(name));
+                for x in module {
+                    if let AtomOrVariable::Variable(ref v) = *x {
+                        self.use_variable(env, v, span);
+                    }
+                }
+                if let IntegerOrVariable::Variable(ref v) = *arity {
+                    self.use_variable(env, v, span);
+                }
+            }
+            Fun::Anonymous(ref clauses) => {
+                for c in clauses {
+                    self.clause(&mut env.clone(), c, true);
+                }
+            }
+            Fun::Named {
+                ref name,
+                ref clauses,
+            } => {
+                for c in clauses {
+                    let mut inner = env.clone();
+                    self.bind_variable(&mut inner, name, span, Some(&mut Vec::new()));
+                    if let Some(b) = self.bindings.last_mut() {
+                        b.used = true;
+                    }
+                    self.clause(&mut inner, c, true);
+                }
+            }
+        }
+    }
+
+    fn qualifiers(&mut self, env: &mut Env, qualifiers: &[Qualifier]) {
+        for q in qualifiers {
+            match *q {
+                Qualifier::Generator {
+                    ref pattern,
+                    ref source,
+                    ..
+                } => {
+                    self.expr(env, source);
+                    self.bind(env, pattern, Some(&mut Vec::new()));
+                }
+                Qualifier::MapGenerator {
+                    ref key,
+                    ref value,
+                    ref source,
+                    ..
+                } => {
+                    self.expr(env, source);
+                    let mut shadowed = Vec::new();
+                    self.bind(env, key, Some(&mut shadowed));
+                    self.bind(env, value, Some(&mut shadowed));
+                }
+                Qualifier::Filter(ref x) => self.expr(env, x),
+            }
+        }
+    }
+
+    /// Checks `clauses` as alternative branches starting from `env`.
+    fn branches(&mut self, env: &Env, clauses: &[Clause]) -> Vec<Env> {
+        clauses
+            .iter()
+            .map(|c| {
+                let mut branch = env.clone();
+                self.clause(&mut branch, c, false);
+                branch
+            })
+            .collect()
+    }
+
+    /// Adds the variables bound in `branches` to `env`,
+    /// where those which are not bound in all the branches are unsafe.
+    fn merge(&mut self, env: &mut Env, branches: Vec<Env>, construct: &'static str, span: &Span) {
+        let mut new: BTreeMap<String, (Vec<usize>, usize)> = BTreeMap::new();
+        for branch in &branches {
+            for (name, var) in branch {
+                if env.contains_key(name) {
+                    continue;
+                }
+                let entry = new.entry(name.clone()).or_default();
+                entry.0.extend(var.bindings());
+                entry.1 += 1;
+            }
+        }
+        for (name, (bindings, count)) in new {
+            let var = if count == branches.len() {
+                Var::Bound(bindings)
+            } else {
+                Var::Unsafe {
+                    bindings,
+                    construct,
+                    span: span.clone(),
+                }
+            };
+            env.insert(name, var);
+        }
+    }
+
+    /// Makes the variables bound within `span` unsafe.
+    fn make_unsafe(&mut self, env: &mut Env, construct: &'static str, span: &Span) {
+        for var in env.values_mut() {
+            let bindings = var.bindings();
+            let is_new = !bindings.is_empty()
+                && bindings
+                    .iter()
+                    .all(|&i| self.bindings[i].span.start.offset() >= span.start.offset());
+            if is_new && matches!(*var, Var::Bound(_)) {
+                *var = Var::Unsafe {
+                    bindings: var.bindings().to_vec(),
+                    construct,
+                    span: span.clone(),
+                };
+            }
+        }
+    }
+
+    /// Binds the variables in `pattern`.
+    ///
+    /// If `shadowed` is `Some`, the variables in `env` are shadowed by new bindings
+    /// (`shadowed` collects the names already bound in the same head).
+    fn bind(&mut self, env: &mut Env, pattern: &Pattern, mut shadowed: Option<&mut Vec<String>>) {
+        match pattern.kind {
+            PatternKind::Literal(_) | PatternKind::RecordIndex { .. } => {}
+            PatternKind::Variable(ref name) => {
+                self.bind_variable(env, name, &pattern.span, shadowed)
+            }
+            PatternKind::Tuple(ref xs) => {
+                for x in xs {
+                    self.bind(env, x, shadowed.as_deref_mut());
+                }
+            }
+            PatternKind::List {
+                ref elements,
+                ref tail,
+            } => {
+                for x in elements.iter().chain(tail.as_deref()) {
+                    self.bind(env, x, shadowed.as_deref_mut());
+                }
+            }
+            PatternKind::Map(ref fields) => {
+                for f in fields {
+                    self.use_in_pattern(env, &f.key);
+                    self.bind(env, &f.value, shadowed.as_deref_mut());
+                }
+            }
+            PatternKind::Record { ref fields, .. } => {
+                for f in fields {
+                    self.bind(env, &f.value, shadowed.as_deref_mut());
+                }
+            }
+            PatternKind::Bits(ref elems) => {
+                for e in elems {
+                    self.bind(env, &e.value, shadowed.as_deref_mut());
+                    if let Some(ref x) = e.size {
+                        self.use_in_pattern(env, x);
+                    }
+                }
+            }
+            PatternKind::UnaryOp { ref operand, .. } => {
+                self.bind(env, operand, shadowed);
+            }
+            PatternKind::BinaryOp {
+                ref left,
+                ref right,
+                ..
+            }
+            | PatternKind::Match {
+                ref left,
+                ref right,
+            } => {
+                self.bind(env, left, shadowed.as_deref_mut());
+                self.bind(env, right, shadowed);
+            }
+            PatternKind::MacroCall(ref x) => {
+                self.use_in_text(env, x.args.iter().flatten());
+            }
+        }
+    }
+
+    fn bind_variable(
+        &mut self,
+        env: &mut Env,
+        name: &str,
+        span: &Span,
+        shadowed: Option<&mut Vec<String>>,
+    ) {
+        if name == "_" {
+            return;
+        }
+        let is_fresh = match shadowed {
+            Some(names) if !names.iter().any(|n| n == name) => {
+                names.push(name.to_owned());
+                true
+            }
+            _ => !env.contains_key(name),
+        };
+        if !is_fresh {
+            // Matching against the bound value
+            self.use_variable(env, name, span);
+            return;
+        }
+        env.insert(name.to_owned(), Var::Bound(vec![self.bindings.len()]));
+        self.bindings.push(Binding {
+            name: name.to_owned(),
+            span: span.clone(),
+            used: false,
+        });
+    }
+
+    /// Uses the variables in `pattern`, which is a map key or a binary element size.
+    fn use_in_pattern(&mut self, env: &mut Env, pattern: &Pattern) {
+        match pattern.kind {
+            PatternKind::Variable(ref name) => self.use_variable(env, name, &pattern.span),
+            PatternKind::Tuple(ref xs) => {
+                for x in xs {
+                    self.use_in_pattern(env, x);
+                }
+            }
+            PatternKind::List {
+                ref elements,
+                ref tail,
+            } => {
+                for x in elements.iter().chain(tail.as_deref()) {
+                    self.use_in_pattern(env, x);
+                }
+            }
+            PatternKind::UnaryOp { ref operand, .. } => self.use_in_pattern(env, operand),
+            PatternKind::BinaryOp {
+                ref left,
+                ref right,
+                ..
+            } => {
+                self.use_in_pattern(env, left);
+                self.use_in_pattern(env, right);
+            }
+            _ => {}
+        }
+    }
+
+    /// Uses the bound variables appearing in source `texts` (e.g., macro arguments).
+    fn use_in_text<'a, I>(&mut self, env: &mut Env, texts: I)
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        for text in texts {
+            let words = text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '@'));
+            for word in words {
+                if let Some(var) = env.get(word) {
+                    for &i in var.bindings() {
+                        self.bindings[i].used = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn use_variable(&mut self, env: &mut Env, name: &str, span: &Span) {
+        if name == "_" {
+            return;
+        }
+        match env.get(name) {
+            None => {
+                let message = Message::new("unbound_var").arg("name", name);
+                self.diagnostics
+                    .push(Diagnostic::error("unbound_var", message, span));
+                // Reported only once
+                env.insert(name.to_owned(), Var::Bound(Vec::new()));
+            }
+            Some(var) => {
+                for &i in var.bindings() {
+                    self.bindings[i].used = true;
+                }
+                if let Var::Unsafe {
+                    construct,
+                    span: ref construct_span,
+                    ..
+                } = *var
+                {
+                    let (line, column) = construct_span.start_line_column();
+                    let message = Message::new("unsafe_var")
+                        .arg("name", name)
+                        .arg("construct", construct)
+                        .arg("line", line.to_string())
+                        .arg("column", column.to_string());
+                    self.diagnostics
+                        .push(Diagnostic::error("unsafe_var", message, span));
+                }
+            }
+        }
+    }
+
+    fn report_unused(&mut self) {
+        for b in &self.bindings {
+            if b.used || b.name.starts_with('_') {
+                continue;
+            }
+            let message = Message::new("unused_var").arg("name", b.name.clone());
+            self.diagnostics
+                .push(Diagnostic::warning("unused_var", message, &b.span));
+        }
+    }
+}
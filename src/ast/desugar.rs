@@ -0,0 +1,356 @@
+use num::BigUint;
+
+use super::{
+    BitsElem, CatchClause, Clause, Expr, ExprKind, Form, FormKind, Fun, Literal, Module, Pattern,
+    PatternKind, Qualifier, Span,
+};
+
+impl Module {
+    /// Replaces the string literals in this module by lists of character codes (see `Expr::strings_to_lists`).
+    pub fn strings_to_lists(&mut self) {
+        for form in &mut self.forms {
+            form.strings_to_lists();
+        }
+    }
+}
+impl Form {
+    /// Replaces the string literals in this form by lists of character codes (see `Expr::strings_to_lists`).
+    pub fn strings_to_lists(&mut self) {
+        match self.kind {
+            FormKind::Compile(ref mut x)
+            | FormKind::Vsn(ref mut x)
+            | FormKind::Dialyzer(ref mut x)
+            | FormKind::Doc {
+                value: ref mut x, ..
+            } => x.strings_to_lists(),
+            FormKind::Attribute {
+                value: Some(ref mut x),
+                ..
+            } => x.strings_to_lists(),
+            FormKind::Function(ref mut f) => clauses(&mut f.clauses),
+            FormKind::Record { ref mut fields, .. } => {
+                for x in fields.iter_mut().filter_map(|f| f.default.as_mut()) {
+                    x.strings_to_lists();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+impl Expr {
+    /// Replaces the string literals in this expression by lists of character codes
+    /// (e.g., `"ab"` by `[97, 98]`), as the Erlang compiler does.
+    ///
+    /// Strings which are elements of bit strings (e.g., `<<"ab">>`) are kept as they are.
+    /// Each of the resulting elements has the span of the whole string literal.
+    pub fn strings_to_lists(&mut self) {
+        match self.kind {
+            ExprKind::Literal(Literal::String(ref s)) => {
+                let elements = chars(s, &self.span)
+                    .map(|(span, kind)| Expr { span, kind })
+                    .collect();
+                self.kind = ExprKind::List {
+                    elements,
+                    tail: None,
+                };
+            }
+            ExprKind::Literal(_)
+            | ExprKind::Variable(_)
+            | ExprKind::RecordIndex { .. }
+            | ExprKind::MacroCall(_)
+            | ExprKind::Extension(_) => {}
+            ExprKind::Tuple(ref mut xs)
+            | ExprKind::Block(ref mut xs)
+            | ExprKind::Catch(ref mut xs) => exprs(xs),
+            ExprKind::List {
+                ref mut elements,
+                ref mut tail,
+            } => {
+                exprs(elements);
+                if let Some(x) = tail {
+                    x.strings_to_lists();
+                }
+            }
+            ExprKind::Map {
+                ref mut base,
+                ref mut fields,
+            } => {
+                if let Some(x) = base {
+                    x.strings_to_lists();
+                }
+                for f in fields {
+                    f.key.strings_to_lists();
+                    f.value.strings_to_lists();
+                }
+            }
+            ExprKind::Record {
+                ref mut base,
+                ref mut fields,
+                ..
+            } => {
+                if let Some(x) = base {
+                    x.strings_to_lists();
+                }
+                for f in fields {
+                    f.value.strings_to_lists();
+                }
+            }
+            ExprKind::RecordAccess { ref mut record, .. } => record.strings_to_lists(),
+            ExprKind::Bits(ref mut elems) => {
+                for e in elems {
+                    bits_elem(e, Expr::strings_to_lists, |x| {
+                        matches!(x.kind, ExprKind::Literal(Literal::String(_)))
+                    });
+                }
+            }
+            ExprKind::ListComprehension {
+                ref mut element,
+                qualifiers: ref mut qs,
+            }
+            | ExprKind::BitsComprehension {
+                ref mut element,
+                qualifiers: ref mut qs,
+            } => {
+                element.strings_to_lists();
+                qualifiers(qs);
+            }
+            ExprKind::MapComprehension {
+                ref mut key,
+                ref mut value,
+                qualifiers: ref mut qs,
+            } => {
+                key.strings_to_lists();
+                value.strings_to_lists();
+                qualifiers(qs);
+            }
+            ExprKind::Fun(ref mut f) => match **f {
+                Fun::Defined { .. } => {}
+                Fun::Anonymous(ref mut xs)
+                | Fun::Named {
+                    clauses: ref mut xs,
+                    ..
+                } => clauses(xs),
+            },
+            ExprKind::Call {
+                ref mut module,
+                ref mut function,
+                ref mut args,
+            } => {
+                if let Some(x) = module {
+                    x.strings_to_lists();
+                }
+                function.strings_to_lists();
+                exprs(args);
+            }
+            ExprKind::UnaryOp {
+                ref mut operand, ..
+            } => operand.strings_to_lists(),
+            ExprKind::BinaryOp {
+                ref mut left,
+                ref mut right,
+                ..
+            } => {
+                left.strings_to_lists();
+                right.strings_to_lists();
+            }
+            ExprKind::Match {
+                ref mut pattern,
+                ref mut value,
+            }
+            | ExprKind::MaybeMatch {
+                ref mut pattern,
+                ref mut value,
+            } => {
+                pattern.strings_to_lists();
+                value.strings_to_lists();
+            }
+            ExprKind::If(ref mut xs) => clauses(xs),
+            ExprKind::Case {
+                ref mut expr,
+                clauses: ref mut xs,
+            } => {
+                expr.strings_to_lists();
+                clauses(xs);
+            }
+            ExprKind::Receive {
+                clauses: ref mut xs,
+                ref mut timeout,
+            } => {
+                clauses(xs);
+                if let Some(t) = timeout {
+                    t.duration.strings_to_lists();
+                    exprs(&mut t.body);
+                }
+            }
+            ExprKind::Try {
+                ref mut body,
+                clauses: ref mut xs,
+                ref mut catch_clauses,
+                ref mut after,
+            } => {
+                exprs(body);
+                clauses(xs);
+                for c in catch_clauses {
+                    catch_clause(c);
+                }
+                exprs(after);
+            }
+            ExprKind::Maybe {
+                ref mut body,
+                ref mut else_clauses,
+            } => {
+                exprs(body);
+                clauses(else_clauses);
+            }
+        }
+    }
+}
+impl Pattern {
+    /// Replaces the string literals in this pattern by lists of character codes (see `Expr::strings_to_lists`).
+    pub fn strings_to_lists(&mut self) {
+        match self.kind {
+            PatternKind::Literal(Literal::String(ref s)) => {
+                let elements = chars(s, &self.span)
+                    .map(|(span, kind)| Pattern { span, kind })
+                    .collect();
+                self.kind = PatternKind::List {
+                    elements,
+                    tail: None,
+                };
+            }
+            PatternKind::Literal(_)
+            | PatternKind::Variable(_)
+            | PatternKind::RecordIndex { .. }
+            | PatternKind::MacroCall(_) => {}
+            PatternKind::Tuple(ref mut xs) => patterns(xs),
+            PatternKind::List {
+                ref mut elements,
+                ref mut tail,
+            } => {
+                patterns(elements);
+                if let Some(x) = tail {
+                    x.strings_to_lists();
+                }
+            }
+            PatternKind::Map(ref mut fields) => {
+                for f in fields {
+                    f.key.strings_to_lists();
+                    f.value.strings_to_lists();
+                }
+            }
+            PatternKind::Record { ref mut fields, .. } => {
+                for f in fields {
+                    f.value.strings_to_lists();
+                }
+            }
+            PatternKind::Bits(ref mut elems) => {
+                for e in elems {
+                    bits_elem(e, Pattern::strings_to_lists, |x| {
+                        matches!(x.kind, PatternKind::Literal(Literal::String(_)))
+                    });
+                }
+            }
+            PatternKind::UnaryOp {
+                ref mut operand, ..
+            } => operand.strings_to_lists(),
+            PatternKind::BinaryOp {
+                ref mut left,
+                ref mut right,
+                ..
+            }
+            | PatternKind::Match {
+                ref mut left,
+                ref mut right,
+            } => {
+                left.strings_to_lists();
+                right.strings_to_lists();
+            }
+        }
+    }
+}
+
+fn chars<'a, K>(s: &'a str, span: &'a Span) -> impl Iterator<Item = (Span, K)> + 'a
+where
+    K: From<Literal>,
+{
+    s.chars().map(move |c| {
+        let code = Literal::Integer(BigUint::from(c as u32));
+        (span.clone(), K::from(code))
+    })
+}
+impl From<Literal> for ExprKind {
+    fn from(f: Literal) -> Self {
+        ExprKind::Literal(f)
+    }
+}
+impl From<Literal> for PatternKind {
+    fn from(f: Literal) -> Self {
+        PatternKind::Literal(f)
+    }
+}
+
+fn bits_elem<T, F, P>(e: &mut BitsElem<T>, f: F, is_string: P)
+where
+    F: Fn(&mut T),
+    P: Fn(&T) -> bool,
+{
+    if !is_string(&e.value) {
+        f(&mut e.value);
+    }
+    if let Some(ref mut x) = e.size {
+        f(x);
+    }
+}
+
+fn exprs(xs: &mut [Expr]) {
+    for x in xs {
+        x.strings_to_lists();
+    }
+}
+
+fn patterns(xs: &mut [Pattern]) {
+    for x in xs {
+        x.strings_to_lists();
+    }
+}
+
+fn guards(xs: &mut [Vec<Expr>]) {
+    for x in xs {
+        exprs(x);
+    }
+}
+
+fn qualifiers(xs: &mut [Qualifier]) {
+    for q in xs {
+        match q {
+            Qualifier::Generator {
+                pattern, source, ..
+            } => {
+                pattern.strings_to_lists();
+                source.strings_to_lists();
+            }
+            Qualifier::MapGenerator {
+                key, value, source, ..
+            } => {
+                key.strings_to_lists();
+                value.strings_to_lists();
+                source.strings_to_lists();
+            }
+            Qualifier::Filter(x) => x.strings_to_lists(),
+        }
+    }
+}
+
+fn clauses(xs: &mut [Clause]) {
+    for c in xs {
+        patterns(&mut c.patterns);
+        guards(&mut c.guards);
+        exprs(&mut c.body);
+    }
+}
+
+fn catch_clause(c: &mut CatchClause) {
+    c.pattern.strings_to_lists();
+    guards(&mut c.guards);
+    exprs(&mut c.body);
+}
@@ -0,0 +1,608 @@
+use erl_tokenize::tokens::AtomToken;
+use erl_tokenize::PositionRange;
+
+use super::{
+    atom, items, record_name, seq_items, variable, AtomOrVariable, BinaryOp, BitsElem, Extension,
+    IntegerOrVariable, Literal, MacroCall, MapField, RecordField, Span, UnaryOp,
+};
+use crate::cst::clauses::parts::WhenGuard;
+use crate::cst::clauses::{CaseClause, CatchClause as CstCatchClause, FunClause, IfClause};
+use crate::cst::clauses::{FunDeclClause, NamedFunClause};
+use crate::cst::commons::parts::Sequence;
+use crate::cst::commons::{self, parts::Clauses};
+use crate::cst::exprs::parts::{Body, Generator, Qualifier as CstQualifier};
+use crate::cst::exprs::{self, Fun as CstFun};
+use crate::cst::{self as cst};
+
+/// Expression (also used for guard tests).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub span: Span,
+    pub kind: ExprKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Literal),
+    Variable(String),
+    Tuple(Vec<Expr>),
+
+    /// `[Elements | Tail]` (`tail` is `None` for proper lists).
+    List {
+        elements: Vec<Expr>,
+        tail: Option<Box<Expr>>,
+    },
+
+    /// `#{...}` or `Base#{...}`.
+    Map {
+        base: Option<Box<Expr>>,
+        fields: Vec<MapField<Expr>>,
+    },
+
+    /// `#name{...}` or `Base#name{...}`.
+    Record {
+        base: Option<Box<Expr>>,
+        name: String,
+        fields: Vec<RecordField<Expr>>,
+    },
+
+    /// `#name.field`
+    RecordIndex {
+        name: String,
+        field: String,
+    },
+
+    /// `Record#name.field`
+    RecordAccess {
+        record: Box<Expr>,
+        name: String,
+        field: String,
+    },
+    Bits(Vec<BitsElem<Expr>>),
+    ListComprehension {
+        element: Box<Expr>,
+        qualifiers: Vec<Qualifier>,
+    },
+    BitsComprehension {
+        element: Box<Expr>,
+        qualifiers: Vec<Qualifier>,
+    },
+    /// `#{Key => Value || Qualifiers}`
+    MapComprehension {
+        key: Box<Expr>,
+        value: Box<Expr>,
+        qualifiers: Vec<Qualifier>,
+    },
+    Fun(Box<Fun>),
+
+    /// `Module:Function(Args)` or `Function(Args)`.
+    Call {
+        module: Option<Box<Expr>>,
+        function: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    UnaryOp {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    BinaryOp {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Match {
+        pattern: Box<Pattern>,
+        value: Box<Expr>,
+    },
+    Block(Vec<Expr>),
+    Catch(Vec<Expr>),
+    If(Vec<Clause>),
+    Case {
+        expr: Box<Expr>,
+        clauses: Vec<Clause>,
+    },
+    Receive {
+        clauses: Vec<Clause>,
+        timeout: Option<Box<Timeout>>,
+    },
+    Try {
+        body: Vec<Expr>,
+
+        /// The clauses of the `of` section (empty if absent).
+        clauses: Vec<Clause>,
+        catch_clauses: Vec<CatchClause>,
+
+        /// The body of the `after` section (empty if absent).
+        after: Vec<Expr>,
+    },
+
+    /// `maybe Body else Clauses end` (`else_clauses` is empty if the `else` section is absent).
+    Maybe {
+        body: Vec<Expr>,
+        else_clauses: Vec<Clause>,
+    },
+
+    /// `Pattern ?= Value` (only in the body of `Maybe`).
+    MaybeMatch {
+        pattern: Box<Pattern>,
+        value: Box<Expr>,
+    },
+
+    /// `?NAME` or `?NAME(Args)` (only in no-preprocess mode).
+    MacroCall(MacroCall),
+
+    /// Expression parsed by a syntax extension registered for its prefix atom.
+    Extension(Extension),
+}
+impl<'a> From<&'a cst::Expr> for Expr {
+    fn from(f: &'a cst::Expr) -> Self {
+        let kind = match *f {
+            cst::Expr::Literal(ref x) => ExprKind::Literal(Literal::from(x)),
+            cst::Expr::Variable(ref x) => ExprKind::Variable(variable(x)),
+            cst::Expr::Tuple(ref x) => ExprKind::Tuple(items(&x.elements)),
+            cst::Expr::Map(ref x) => map(None, x),
+            cst::Expr::MapUpdate(ref x) => map(Some(&x.map), &x.update),
+            cst::Expr::Record(ref x) => record(None, x),
+            cst::Expr::RecordUpdate(ref x) => record(Some(&x.record), &x.update),
+            cst::Expr::RecordFieldIndex(ref x) => record_index(x),
+            cst::Expr::RecordFieldAccess(ref x) => record_access(x),
+            cst::Expr::List(ref x) => {
+                let (elements, tail) = list(x);
+                ExprKind::List { elements, tail }
+            }
+            cst::Expr::ListComprehension(ref x) => ExprKind::ListComprehension {
+                element: Box::new(Expr::from(&x.element)),
+                qualifiers: seq_items(&x.qualifiers),
+            },
+            cst::Expr::Bits(ref x) => ExprKind::Bits(items(&x.elements)),
+            cst::Expr::BitsComprehension(ref x) => ExprKind::BitsComprehension {
+                element: Box::new(Expr::from(&x.element)),
+                qualifiers: seq_items(&x.qualifiers),
+            },
+            cst::Expr::MapComprehension(ref x) => ExprKind::MapComprehension {
+                key: Box::new(Expr::from(&x.key)),
+                value: Box::new(Expr::from(&x.value)),
+                qualifiers: seq_items(&x.qualifiers),
+            },
+            cst::Expr::Fun(ref x) => ExprKind::Fun(Box::new(Fun::from(&**x))),
+            cst::Expr::Parenthesized(ref x) => Expr::from(&x.item).kind,
+            cst::Expr::FunCall(ref x) => call(x),
+            cst::Expr::UnaryOpCall(ref x) => unary_op(x),
+            cst::Expr::BinaryOpCall(ref x) => binary_op(x),
+            cst::Expr::Match(ref x) => ExprKind::Match {
+                pattern: Box::new(Pattern::from(&x.pattern)),
+                value: Box::new(Expr::from(&x.value)),
+            },
+            cst::Expr::Block(ref x) => ExprKind::Block(body(&x.body)),
+            cst::Expr::Catch(ref x) => ExprKind::Catch(body(&x.expr)),
+            cst::Expr::If(ref x) => ExprKind::If(clauses(&x.clauses)),
+            cst::Expr::Case(ref x) => ExprKind::Case {
+                expr: Box::new(Expr::from(&x.expr)),
+                clauses: clauses(&x.clauses),
+            },
+            cst::Expr::Receive(ref x) => ExprKind::Receive {
+                clauses: clauses(&x.clauses),
+                timeout: x.timeout.as_ref().map(|t| {
+                    Box::new(Timeout {
+                        span: Span::of(t),
+                        duration: Expr::from(&t.duration),
+                        body: body(&t.body),
+                    })
+                }),
+            },
+            cst::Expr::Try(ref x) => ExprKind::Try {
+                body: body(&x.body),
+                clauses: x.branch.iter().flat_map(|b| clauses(&b.clauses)).collect(),
+                catch_clauses: x.catch.iter().flat_map(|c| clauses(&c.clauses)).collect(),
+                after: x.after.iter().flat_map(|a| body(&a.body)).collect(),
+            },
+            cst::Expr::Maybe(ref x) => ExprKind::Maybe {
+                body: body(&x.body),
+                else_clauses: x
+                    .else_clauses
+                    .iter()
+                    .flat_map(|e| clauses(&e.clauses))
+                    .collect(),
+            },
+            cst::Expr::MaybeMatch(ref x) => ExprKind::MaybeMatch {
+                pattern: Box::new(Pattern::from(&x.pattern)),
+                value: Box::new(Expr::from(&x.value)),
+            },
+            cst::Expr::MacroCall(ref x) => ExprKind::MacroCall(MacroCall::from(&**x)),
+            cst::Expr::Extension(ref x) => ExprKind::Extension(Extension::from(&**x)),
+        };
+        Expr {
+            span: Span::of(f),
+            kind,
+        }
+    }
+}
+impl<'a> From<&'a cst::GuardTest> for Expr {
+    fn from(f: &'a cst::GuardTest) -> Self {
+        let kind = match *f {
+            cst::GuardTest::Literal(ref x) => ExprKind::Literal(Literal::from(x)),
+            cst::GuardTest::Variable(ref x) => ExprKind::Variable(variable(x)),
+            cst::GuardTest::Tuple(ref x) => ExprKind::Tuple(items(&x.elements)),
+            cst::GuardTest::Map(ref x) => map(None::<&cst::GuardTest>, x),
+            cst::GuardTest::Record(ref x) => record(None::<&cst::GuardTest>, x),
+            cst::GuardTest::RecordFieldIndex(ref x) => record_index(x),
+            cst::GuardTest::RecordFieldAccess(ref x) => record_access(x),
+            cst::GuardTest::List(ref x) => {
+                let (elements, tail) = list(x);
+                ExprKind::List { elements, tail }
+            }
+            cst::GuardTest::Bits(ref x) => ExprKind::Bits(items(&x.elements)),
+            cst::GuardTest::Parenthesized(ref x) => Expr::from(&x.item).kind,
+            cst::GuardTest::FunCall(ref x) => call(x),
+            cst::GuardTest::UnaryOpCall(ref x) => unary_op(x),
+            cst::GuardTest::BinaryOpCall(ref x) => binary_op(x),
+            cst::GuardTest::MacroCall(ref x) => ExprKind::MacroCall(MacroCall::from(&**x)),
+        };
+        Expr {
+            span: Span::of(f),
+            kind,
+        }
+    }
+}
+impl<'a> From<&'a AtomToken> for Expr {
+    fn from(f: &'a AtomToken) -> Self {
+        Expr {
+            span: Span::of(f),
+            kind: ExprKind::Literal(Literal::Atom(atom(f))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub span: Span,
+    pub kind: PatternKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    Literal(Literal),
+    Variable(String),
+    Tuple(Vec<Pattern>),
+    List {
+        elements: Vec<Pattern>,
+        tail: Option<Box<Pattern>>,
+    },
+    Map(Vec<MapField<Pattern>>),
+    Record {
+        name: String,
+        fields: Vec<RecordField<Pattern>>,
+    },
+    RecordIndex {
+        name: String,
+        field: String,
+    },
+    Bits(Vec<BitsElem<Pattern>>),
+    UnaryOp {
+        op: UnaryOp,
+        operand: Box<Pattern>,
+    },
+    BinaryOp {
+        op: BinaryOp,
+        left: Box<Pattern>,
+        right: Box<Pattern>,
+    },
+    Match {
+        left: Box<Pattern>,
+        right: Box<Pattern>,
+    },
+    MacroCall(MacroCall),
+}
+impl<'a> From<&'a cst::Pattern> for Pattern {
+    fn from(f: &'a cst::Pattern) -> Self {
+        let kind = match *f {
+            cst::Pattern::Literal(ref x) => PatternKind::Literal(Literal::from(x)),
+            cst::Pattern::Variable(ref x) => PatternKind::Variable(variable(x)),
+            cst::Pattern::Tuple(ref x) => PatternKind::Tuple(items(&x.elements)),
+            cst::Pattern::Map(ref x) => PatternKind::Map(items(&x.fields)),
+            cst::Pattern::Record(ref x) => PatternKind::Record {
+                name: record_name(&x.name),
+                fields: items(&x.fields),
+            },
+            cst::Pattern::RecordFieldIndex(ref x) => PatternKind::RecordIndex {
+                name: record_name(&x.name),
+                field: atom(&x.field),
+            },
+            cst::Pattern::List(ref x) => {
+                let (elements, tail) = list(x);
+                PatternKind::List { elements, tail }
+            }
+            cst::Pattern::Bits(ref x) => PatternKind::Bits(items(&x.elements)),
+            cst::Pattern::Parenthesized(ref x) => Pattern::from(&x.item).kind,
+            cst::Pattern::UnaryOpCall(ref x) => PatternKind::UnaryOp {
+                op: UnaryOp::from(&x.op),
+                operand: Box::new(Pattern::from(&x.operand)),
+            },
+            cst::Pattern::BinaryOpCall(ref x) => PatternKind::BinaryOp {
+                op: BinaryOp::from(&x.op),
+                left: Box::new(Pattern::from(&x.left)),
+                right: Box::new(Pattern::from(&x.right)),
+            },
+            cst::Pattern::Match(ref x) => PatternKind::Match {
+                left: Box::new(Pattern::from(&x.pattern)),
+                right: Box::new(Pattern::from(&x.value)),
+            },
+            cst::Pattern::MacroCall(ref x) => PatternKind::MacroCall(MacroCall::from(&**x)),
+        };
+        Pattern {
+            span: Span::of(f),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fun {
+    /// `fun Module:Name/Arity` or `fun Name/Arity`.
+    Defined {
+        module: Option<AtomOrVariable>,
+        name: AtomOrVariable,
+        arity: IntegerOrVariable,
+    },
+    Anonymous(Vec<Clause>),
+    Named {
+        name: String,
+        clauses: Vec<Clause>,
+    },
+}
+impl<'a> From<&'a CstFun> for Fun {
+    fn from(f: &'a CstFun) -> Self {
+        match *f {
+            CstFun::Defined(ref x) => Fun::Defined {
+                module: x.module.as_ref().map(|m| AtomOrVariable::from(&m.name)),
+                name: AtomOrVariable::from(&x.fun.name),
+                arity: IntegerOrVariable::from(&x.fun.arity),
+            },
+            CstFun::Anonymous(ref x) => Fun::Anonymous(clauses(&x.clauses)),
+            CstFun::Named(ref x) => Fun::Named {
+                name: variable(&x.clauses.item.name),
+                clauses: clauses(&x.clauses),
+            },
+        }
+    }
+}
+
+/// Clause of a function, `fun`, `case`, `if`, `receive` or `try ... of`.
+///
+/// `if` clauses have no patterns, and `case` clauses have exactly one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub span: Span,
+    pub patterns: Vec<Pattern>,
+
+    /// Guard sequence: the clause is selected if any of the guards (all of whose tests are true) holds.
+    pub guards: Vec<Vec<Expr>>,
+    pub body: Vec<Expr>,
+}
+impl Clause {
+    fn new<T: PositionRange>(
+        span: &T,
+        patterns: Vec<Pattern>,
+        guard: Option<&WhenGuard>,
+        body: &Body,
+    ) -> Self {
+        Clause {
+            span: Span::of(span),
+            patterns,
+            guards: guard.map_or_else(Vec::new, |g| guards(&g.seq)),
+            body: self::body(body),
+        }
+    }
+}
+impl<'a> From<&'a CaseClause> for Clause {
+    fn from(f: &'a CaseClause) -> Self {
+        let patterns = vec![Pattern::from(&f.pattern)];
+        Clause::new(f, patterns, f.guard.as_ref(), &f.body)
+    }
+}
+impl<'a> From<&'a IfClause> for Clause {
+    fn from(f: &'a IfClause) -> Self {
+        Clause {
+            span: Span::of(f),
+            patterns: Vec::new(),
+            guards: guards(&f.guard),
+            body: body(&f.body),
+        }
+    }
+}
+impl<'a> From<&'a FunClause> for Clause {
+    fn from(f: &'a FunClause) -> Self {
+        let patterns = items(&f.patterns.args);
+        Clause::new(f, patterns, f.guard.as_ref(), &f.body)
+    }
+}
+impl<'a> From<&'a NamedFunClause> for Clause {
+    fn from(f: &'a NamedFunClause) -> Self {
+        let patterns = items(&f.patterns.args);
+        Clause::new(f, patterns, f.guard.as_ref(), &f.body)
+    }
+}
+impl<'a> From<&'a FunDeclClause> for Clause {
+    fn from(f: &'a FunDeclClause) -> Self {
+        let patterns = items(&f.patterns.args);
+        Clause::new(f, patterns, f.guard.as_ref(), &f.body)
+    }
+}
+
+/// `Class:Pattern:Stacktrace when Guards -> Body` in a `try ... catch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatchClause {
+    pub span: Span,
+
+    /// `None` means `throw`.
+    pub class: Option<AtomOrVariable>,
+    pub pattern: Pattern,
+    pub stacktrace: Option<String>,
+    pub guards: Vec<Vec<Expr>>,
+    pub body: Vec<Expr>,
+}
+impl<'a> From<&'a CstCatchClause> for CatchClause {
+    fn from(f: &'a CstCatchClause) -> Self {
+        CatchClause {
+            span: Span::of(f),
+            class: f.class.as_ref().map(|c| AtomOrVariable::from(&c.class)),
+            pattern: Pattern::from(&f.pattern),
+            stacktrace: f.stacktrace.as_ref().map(|s| variable(&s.variable)),
+            guards: f.guard.as_ref().map_or_else(Vec::new, |g| guards(&g.seq)),
+            body: body(&f.body),
+        }
+    }
+}
+
+/// `after Duration -> Body` in a `receive`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeout {
+    pub span: Span,
+    pub duration: Expr,
+    pub body: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Qualifier {
+    /// `Pattern <- List` or `Pattern <= Binary` (`binary` is `true` for the latter).
+    ///
+    /// `strict` is `true` for the strict generators `<:-` and `<:=`.
+    Generator {
+        span: Span,
+        pattern: Pattern,
+        source: Expr,
+        binary: bool,
+        strict: bool,
+    },
+    /// `Key := Value <- Map` (or `<:-` if `strict` is `true`)
+    MapGenerator {
+        span: Span,
+        key: Pattern,
+        value: Pattern,
+        source: Expr,
+        strict: bool,
+    },
+    Filter(Expr),
+}
+impl<'a> From<&'a CstQualifier> for Qualifier {
+    fn from(f: &'a CstQualifier) -> Self {
+        match *f {
+            CstQualifier::Generator(ref x) => generator(x),
+            CstQualifier::MapGenerator(ref x) => Qualifier::MapGenerator {
+                span: Span::of(x),
+                key: Pattern::from(&x.key),
+                value: Pattern::from(&x.value),
+                source: Expr::from(&x.source),
+                strict: x.arrow.is_strict(),
+            },
+            CstQualifier::Filter(ref x) => Qualifier::Filter(Expr::from(x)),
+        }
+    }
+}
+
+fn generator(x: &Generator) -> Qualifier {
+    Qualifier::Generator {
+        span: Span::of(x),
+        pattern: Pattern::from(&x.pattern),
+        source: Expr::from(&x.source),
+        binary: x.arrow.is_binary(),
+        strict: x.arrow.is_strict(),
+    }
+}
+
+fn body(x: &Body) -> Vec<Expr> {
+    seq_items(&x.exprs)
+}
+
+fn clauses<'a, T, U>(x: &'a Clauses<T>) -> Vec<U>
+where
+    U: From<&'a T>,
+{
+    seq_items(x)
+}
+
+fn guards(x: &Clauses<Sequence<cst::GuardTest>>) -> Vec<Vec<Expr>> {
+    x.iter().map(seq_items).collect()
+}
+
+fn map<'a, T>(base: Option<&'a T>, x: &'a commons::Map<T>) -> ExprKind
+where
+    T: PositionRange,
+    Expr: From<&'a T>,
+{
+    ExprKind::Map {
+        base: base.map(|b| Box::new(Expr::from(b))),
+        fields: items(&x.fields),
+    }
+}
+
+fn record<'a, T>(base: Option<&'a T>, x: &'a commons::Record<T>) -> ExprKind
+where
+    T: PositionRange,
+    Expr: From<&'a T>,
+{
+    ExprKind::Record {
+        base: base.map(|b| Box::new(Expr::from(b))),
+        name: record_name(&x.name),
+        fields: items(&x.fields),
+    }
+}
+
+fn record_index(x: &commons::RecordFieldIndex) -> ExprKind {
+    ExprKind::RecordIndex {
+        name: record_name(&x.name),
+        field: atom(&x.field),
+    }
+}
+
+fn record_access<'a, T>(x: &'a exprs::RecordFieldAccess<T>) -> ExprKind
+where
+    Expr: From<&'a T>,
+{
+    ExprKind::RecordAccess {
+        record: Box::new(Expr::from(&x.record)),
+        name: record_name(&x.index.name),
+        field: atom(&x.index.field),
+    }
+}
+
+fn list<'a, T, U>(x: &'a commons::List<T>) -> (Vec<U>, Option<Box<U>>)
+where
+    U: From<&'a T>,
+{
+    let elements = x.iter().map(U::from).collect();
+    let tail = x.tail().map(|t| Box::new(U::from(t)));
+    (elements, tail)
+}
+
+fn call<'a, T, A>(x: &'a commons::Call<T, A>) -> ExprKind
+where
+    Expr: From<&'a T> + From<&'a A>,
+{
+    ExprKind::Call {
+        module: x.module.as_ref().map(|m| Box::new(Expr::from(&m.name))),
+        function: Box::new(Expr::from(&x.name)),
+        args: items(&x.args.args),
+    }
+}
+
+fn unary_op<'a, T>(x: &'a commons::UnaryOpCall<T>) -> ExprKind
+where
+    Expr: From<&'a T>,
+{
+    ExprKind::UnaryOp {
+        op: UnaryOp::from(&x.op),
+        operand: Box::new(Expr::from(&x.operand)),
+    }
+}
+
+fn binary_op<'a, T>(x: &'a commons::BinaryOpCall<T>) -> ExprKind
+where
+    Expr: From<&'a T>,
+{
+    ExprKind::BinaryOp {
+        op: BinaryOp::from(&x.op),
+        left: Box::new(Expr::from(&x.left)),
+        right: Box::new(Expr::from(&x.right)),
+    }
+}
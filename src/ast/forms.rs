@@ -0,0 +1,267 @@
+use num::ToPrimitive;
+
+use super::types::{constraints, Constraint};
+use super::{
+    atom, items, seq_items, token_groups, variable, Clause, Expr, Extension, NameAndArity, Span,
+    Type,
+};
+use crate::cst::clauses::SpecClause as CstSpecClause;
+use crate::cst::forms;
+use crate::cst::forms::parts::RecordFieldDecl as CstRecordFieldDecl;
+use crate::cst::{self as cst};
+
+/// Module (i.e., the forms of a source file).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub forms: Vec<Form>,
+}
+impl Module {
+    /// Returns the name given by the `-module` attribute.
+    pub fn name(&self) -> Option<&str> {
+        self.forms.iter().find_map(|f| match f.kind {
+            FormKind::Module(ref name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the function declarations in this module.
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.forms.iter().filter_map(|f| match f.kind {
+            FormKind::Function(ref x) => Some(x),
+            _ => None,
+        })
+    }
+}
+impl<'a> From<&'a cst::ModuleDecl> for Module {
+    fn from(f: &'a cst::ModuleDecl) -> Self {
+        Module {
+            forms: f.forms.iter().map(Form::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+    pub span: Span,
+    pub kind: FormKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormKind {
+    Module(String),
+    Export(Vec<NameAndArity>),
+    ExportType(Vec<NameAndArity>),
+    Import {
+        module: String,
+        functions: Vec<NameAndArity>,
+    },
+    File {
+        name: String,
+        line: usize,
+    },
+    Compile(Expr),
+    Vsn(Expr),
+
+    /// `-feature(Name, enable | disable).`
+    Feature {
+        name: String,
+        enabled: bool,
+    },
+
+    /// `-behaviour(Name).` (or `-behavior(Name).`)
+    Behaviour(String),
+
+    /// `-on_load(Name/Arity).`
+    OnLoad(NameAndArity),
+    Dialyzer(Expr),
+
+    /// `-doc Value.` or `-moduledoc Value.` (if `module` is `true`).
+    Doc {
+        module: bool,
+        value: Expr,
+    },
+
+    /// Other attribute.
+    ///
+    /// `value` is `None` if the value is not an expression (see `cst::forms::WildAttr::parse_value`).
+    Attribute {
+        name: String,
+        value: Option<Expr>,
+    },
+    Spec {
+        module: Option<String>,
+        name: String,
+        clauses: Vec<SpecClause>,
+    },
+    Callback {
+        name: String,
+        clauses: Vec<SpecClause>,
+    },
+    Function(Function),
+    Record {
+        name: String,
+        fields: Vec<RecordFieldDecl>,
+    },
+    Type(TypeDecl),
+
+    /// Preprocessor directive (only in no-preprocess mode).
+    ///
+    /// The arguments are kept as source text (see `ast::MacroCall`).
+    Directive {
+        name: String,
+        args: Option<Vec<String>>,
+    },
+
+    /// Attribute parsed by a syntax extension registered for its name.
+    Extension(Extension),
+
+    /// Form which could not be parsed.
+    Error,
+
+    /// Form which seems to use unsupported syntax (see `cst::forms::UnparsedForm`).
+    Unparsed {
+        reason: String,
+    },
+}
+impl<'a> From<&'a cst::Form> for Form {
+    fn from(f: &'a cst::Form) -> Self {
+        let kind = match *f {
+            cst::Form::ModuleAttr(ref x) => FormKind::Module(atom(&x.module_name)),
+            cst::Form::ExportAttr(ref x) => FormKind::Export(items(&x.exports.elements)),
+            cst::Form::ExportTypeAttr(ref x) => FormKind::ExportType(items(&x.exports.elements)),
+            cst::Form::ImportAttr(ref x) => FormKind::Import {
+                module: atom(&x.module_name),
+                functions: items(&x.imports.elements),
+            },
+            cst::Form::FileAttr(ref x) => FormKind::File {
+                name: x.file_name.value().to_owned(),
+                line: x.line_num.value().to_usize().unwrap_or(usize::MAX),
+            },
+            cst::Form::CompileAttr(ref x) => FormKind::Compile(Expr::from(&x.options)),
+            cst::Form::VsnAttr(ref x) => FormKind::Vsn(Expr::from(&x.vsn)),
+            cst::Form::FeatureAttr(ref x) => FormKind::Feature {
+                name: atom(&x.feature_name),
+                enabled: x.is_enabled(),
+            },
+            cst::Form::BehaviourAttr(ref x) => FormKind::Behaviour(atom(&x.behaviour_name)),
+            cst::Form::OnLoadAttr(ref x) => FormKind::OnLoad(NameAndArity::from(&x.fun)),
+            cst::Form::DialyzerAttr(ref x) => FormKind::Dialyzer(Expr::from(&x.options)),
+            cst::Form::DocAttr(ref x) => FormKind::Doc {
+                module: x.is_module_doc(),
+                value: Expr::from(&x.value),
+            },
+            cst::Form::WildAttr(ref x) => FormKind::Attribute {
+                name: atom(&x.attr_name),
+                value: x.parse_value().as_ref().map(Expr::from),
+            },
+            cst::Form::FunSpec(ref x) => FormKind::Spec {
+                module: x.module.as_ref().map(|m| atom(&m.name)),
+                name: atom(&x.fun_name),
+                clauses: seq_items(&x.clauses),
+            },
+            cst::Form::CallbackSpec(ref x) => FormKind::Callback {
+                name: atom(&x.callback_name),
+                clauses: seq_items(&x.clauses),
+            },
+            cst::Form::FunDecl(ref x) => FormKind::Function(Function::from(x)),
+            cst::Form::RecordDecl(ref x) => FormKind::Record {
+                name: atom(&x.record_name),
+                fields: items(&x.fields.elements),
+            },
+            cst::Form::TypeDecl(ref x) => FormKind::Type(TypeDecl::from(x)),
+            cst::Form::Directive(ref x) => FormKind::Directive {
+                name: x.name.value().to_owned(),
+                args: token_groups(&x.args),
+            },
+            cst::Form::ExtensionAttr(ref x) => FormKind::Extension(Extension::from(x)),
+            cst::Form::Error(_) => FormKind::Error,
+            cst::Form::Unparsed(ref x) => FormKind::Unparsed {
+                reason: x.reason.clone(),
+            },
+        };
+        Form {
+            span: Span::of(f),
+            kind,
+        }
+    }
+}
+
+/// Function declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub clauses: Vec<Clause>,
+}
+impl<'a> From<&'a forms::FunDecl> for Function {
+    fn from(f: &'a forms::FunDecl) -> Self {
+        Function {
+            name: f.name().to_owned(),
+            arity: f.arity(),
+            clauses: seq_items(&f.clauses),
+        }
+    }
+}
+
+/// `-type` or `-opaque` declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDecl {
+    pub name: String,
+    pub opaque: bool,
+    pub params: Vec<String>,
+    pub ty: Type,
+}
+impl<'a> From<&'a forms::TypeDecl> for TypeDecl {
+    fn from(f: &'a forms::TypeDecl) -> Self {
+        TypeDecl {
+            name: atom(&f.type_name),
+            opaque: f.is_opaque(),
+            params: f
+                .variables
+                .args
+                .iter()
+                .flat_map(|a| a.iter())
+                .map(variable)
+                .collect(),
+            ty: Type::from(&f.ty),
+        }
+    }
+}
+
+/// `(Args) -> ReturnType when Constraints` in a `-spec` or `-callback`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecClause {
+    pub span: Span,
+    pub args: Vec<Type>,
+    pub return_type: Type,
+    pub constraints: Vec<Constraint>,
+}
+impl<'a> From<&'a CstSpecClause> for SpecClause {
+    fn from(f: &'a CstSpecClause) -> Self {
+        SpecClause {
+            span: Span::of(f),
+            args: items(&f.args.args),
+            return_type: Type::from(&f.return_type),
+            constraints: constraints(&f.constraints),
+        }
+    }
+}
+
+/// Field of a `-record` declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordFieldDecl {
+    pub span: Span,
+    pub name: String,
+    pub default: Option<Expr>,
+    pub ty: Option<Type>,
+}
+impl<'a> From<&'a CstRecordFieldDecl> for RecordFieldDecl {
+    fn from(f: &'a CstRecordFieldDecl) -> Self {
+        RecordFieldDecl {
+            span: Span::of(f),
+            name: atom(&f.field_name),
+            default: f.field_default.as_ref().map(|d| Expr::from(&d.value)),
+            ty: f.field_type.as_ref().map(|t| Type::from(&t.field_type)),
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+
+use super::{CatchClause, Clause, Expr, ExprKind, Form, FormKind, Fun, FunType, Module};
+use super::{Pattern, PatternKind, Qualifier, Span, SpecClause, Type, TypeKind};
+use crate::cst::{self, Node, NodeId, NodeIndex, NodeKind};
+
+/// Reference to a node of an AST.
+#[derive(Debug, Clone, Copy)]
+pub enum AstNode<'a> {
+    Form(&'a Form),
+    Clause(&'a Clause),
+    CatchClause(&'a CatchClause),
+    SpecClause(&'a SpecClause),
+    Expr(&'a Expr),
+    Pattern(&'a Pattern),
+    Type(&'a Type),
+}
+impl<'a> AstNode<'a> {
+    pub fn span(&self) -> &'a Span {
+        match *self {
+            AstNode::Form(x) => &x.span,
+            AstNode::Clause(x) => &x.span,
+            AstNode::CatchClause(x) => &x.span,
+            AstNode::SpecClause(x) => &x.span,
+            AstNode::Expr(x) => &x.span,
+            AstNode::Pattern(x) => &x.span,
+            AstNode::Type(x) => &x.span,
+        }
+    }
+
+    fn kinds(&self) -> &'static [NodeKind] {
+        match *self {
+            AstNode::Form(_) => &[NodeKind::Form],
+            AstNode::Clause(_) | AstNode::CatchClause(_) | AstNode::SpecClause(_) => {
+                &[NodeKind::Clause]
+            }
+            AstNode::Expr(_) => &[NodeKind::Expr, NodeKind::GuardTest],
+            AstNode::Pattern(_) => &[NodeKind::Pattern],
+            AstNode::Type(_) => &[NodeKind::Type],
+        }
+    }
+
+    fn children(&self) -> Vec<AstNode<'a>> {
+        let mut c = Vec::new();
+        match *self {
+            AstNode::Form(x) => form(x, &mut c),
+            AstNode::Clause(x) => {
+                c.extend(x.patterns.iter().map(AstNode::Pattern));
+                c.extend(x.guards.iter().flatten().map(AstNode::Expr));
+                c.extend(x.body.iter().map(AstNode::Expr));
+            }
+            AstNode::CatchClause(x) => {
+                c.push(AstNode::Pattern(&x.pattern));
+                c.extend(x.guards.iter().flatten().map(AstNode::Expr));
+                c.extend(x.body.iter().map(AstNode::Expr));
+            }
+            AstNode::SpecClause(x) => {
+                c.extend(x.args.iter().map(AstNode::Type));
+                c.push(AstNode::Type(&x.return_type));
+                c.extend(x.constraints.iter().map(|c| AstNode::Type(&c.bound)));
+            }
+            AstNode::Expr(x) => expr(x, &mut c),
+            AstNode::Pattern(x) => pattern(x, &mut c),
+            AstNode::Type(x) => ty(x, &mut c),
+        }
+        c
+    }
+}
+
+/// Links between the nodes of an AST and the CST nodes from which they were converted.
+///
+/// Nodes are matched by their kinds and spans, so analyses written against the AST
+/// can report the precise spans of (and edit) the concrete syntax.
+/// An AST node made from a parenthesized CST node is linked to the parenthesized node.
+/// Nodes which were introduced by desugaring (e.g., `Module::strings_to_lists`) are linked to
+/// the CST node of the outermost AST node that has the same span.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::ast::{self, FormKind, Links};
+/// use erl_parse::cst::{ModuleDecl, Node};
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::{Lexer, PositionRange};
+///
+/// let text = "-module(foo).\nf(X) -> (X + 1).";
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+/// let cst: ModuleDecl = parser.parse().unwrap();
+/// let ast = ast::Module::from(&cst);
+/// let links = Links::new(&cst, &ast);
+///
+/// let body = match ast.forms[1].kind {
+///     FormKind::Function(ref f) => &f.clauses[0].body[0],
+///     _ => unreachable!(),
+/// };
+/// let id = links.cst_expr(body).unwrap();
+/// match links.cst_node(id) {
+///     Some(Node::Expr(e)) => assert_eq!(e.start_position().offset(), 22),
+///     _ => unreachable!(),
+/// }
+/// assert!(links.ast_node(id).is_some());
+/// ```
+#[derive(Debug)]
+pub struct Links<'a> {
+    cst_index: NodeIndex<'a>,
+    ast_nodes: HashMap<NodeId, AstNode<'a>>,
+}
+impl<'a> Links<'a> {
+    /// Makes the links between `cst` and `ast` (which should be converted from `cst`).
+    pub fn new(cst: &'a cst::ModuleDecl, ast: &'a Module) -> Self {
+        let mut links = Links {
+            cst_index: NodeIndex::new(cst),
+            ast_nodes: HashMap::new(),
+        };
+
+        let mut stack = ast
+            .forms
+            .iter()
+            .rev()
+            .map(AstNode::Form)
+            .collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if let Some(id) = links.cst_id(node) {
+                links.ast_nodes.entry(id).or_insert(node);
+            }
+            stack.extend(node.children().into_iter().rev());
+        }
+        links
+    }
+
+    /// Returns the CST node identified by `id`.
+    pub fn cst_node(&self, id: NodeId) -> Option<Node<'a>> {
+        self.cst_index.node(id)
+    }
+
+    /// Returns the AST node linked to the CST node identified by `id`.
+    pub fn ast_node(&self, id: NodeId) -> Option<AstNode<'a>> {
+        self.ast_nodes.get(&id).copied()
+    }
+
+    /// Returns the identifier of the CST node linked to `node`.
+    pub fn cst_id(&self, node: AstNode) -> Option<NodeId> {
+        node.kinds()
+            .iter()
+            .find_map(|&kind| self.find(kind, node.span()))
+    }
+
+    /// Returns the identifier of the outermost CST node of `kind` whose span is `span`.
+    pub fn find(&self, kind: NodeKind, span: &Span) -> Option<NodeId> {
+        self.cst_index.find(kind, span)
+    }
+
+    pub fn cst_form(&self, x: &Form) -> Option<NodeId> {
+        self.cst_id(AstNode::Form(x))
+    }
+
+    /// Returns the identifier of the CST expression or guard test linked to `x`.
+    pub fn cst_expr(&self, x: &Expr) -> Option<NodeId> {
+        self.cst_id(AstNode::Expr(x))
+    }
+
+    pub fn cst_pattern(&self, x: &Pattern) -> Option<NodeId> {
+        self.cst_id(AstNode::Pattern(x))
+    }
+
+    pub fn cst_type(&self, x: &Type) -> Option<NodeId> {
+        self.cst_id(AstNode::Type(x))
+    }
+
+    pub fn cst_clause(&self, x: &Clause) -> Option<NodeId> {
+        self.cst_id(AstNode::Clause(x))
+    }
+}
+
+fn form<'a>(x: &'a Form, c: &mut Vec<AstNode<'a>>) {
+    match x.kind {
+        FormKind::Compile(ref e)
+        | FormKind::Vsn(ref e)
+        | FormKind::Dialyzer(ref e)
+        | FormKind::Doc { value: ref e, .. }
+        | FormKind::Attribute {
+            value: Some(ref e), ..
+        } => c.push(AstNode::Expr(e)),
+        FormKind::Spec { ref clauses, .. } | FormKind::Callback { ref clauses, .. } => {
+            c.extend(clauses.iter().map(AstNode::SpecClause))
+        }
+        FormKind::Function(ref f) => c.extend(f.clauses.iter().map(AstNode::Clause)),
+        FormKind::Record { ref fields, .. } => {
+            for f in fields {
+                c.extend(f.default.iter().map(AstNode::Expr));
+                c.extend(f.ty.iter().map(AstNode::Type));
+            }
+        }
+        FormKind::Type(ref t) => c.push(AstNode::Type(&t.ty)),
+        _ => {}
+    }
+}
+
+fn expr<'a>(x: &'a Expr, c: &mut Vec<AstNode<'a>>) {
+    let e = AstNode::Expr;
+    match x.kind {
+        ExprKind::Literal(_)
+        | ExprKind::Variable(_)
+        | ExprKind::RecordIndex { .. }
+        | ExprKind::MacroCall(_)
+        | ExprKind::Extension(_) => {}
+        ExprKind::Tuple(ref xs) | ExprKind::Block(ref xs) | ExprKind::Catch(ref xs) => {
+            c.extend(xs.iter().map(e))
+        }
+        ExprKind::List {
+            ref elements,
+            ref tail,
+        } => {
+            c.extend(elements.iter().map(e));
+            c.extend(tail.iter().map(|x| e(x)));
+        }
+        ExprKind::Map {
+            ref base,
+            ref fields,
+        } => {
+            c.extend(base.iter().map(|x| e(x)));
+            for f in fields {
+                c.push(e(&f.key));
+                c.push(e(&f.value));
+            }
+        }
+        ExprKind::Record {
+            ref base,
+            ref fields,
+            ..
+        } => {
+            c.extend(base.iter().map(|x| e(x)));
+            c.extend(fields.iter().map(|f| e(&f.value)));
+        }
+        ExprKind::RecordAccess { ref record, .. } => c.push(e(record)),
+        ExprKind::Bits(ref elems) => {
+            for x in elems {
+                c.push(e(&x.value));
+                c.extend(x.size.iter().map(e));
+            }
+        }
+        ExprKind::ListComprehension {
+            ref element,
+            ref qualifiers,
+        }
+        | ExprKind::BitsComprehension {
+            ref element,
+            ref qualifiers,
+        } => {
+            c.push(e(element));
+            qualifiers_children(qualifiers, c);
+        }
+        ExprKind::MapComprehension {
+            ref key,
+            ref value,
+            ref qualifiers,
+        } => {
+            c.push(e(key));
+            c.push(e(value));
+            qualifiers_children(qualifiers, c);
+        }
+        ExprKind::Fun(ref f) => match **f {
+            Fun::Defined { .. } => {}
+            Fun::Anonymous(ref xs)
+            | Fun::Named {
+                clauses: ref xs, ..
+            } => c.extend(xs.iter().map(AstNode::Clause)),
+        },
+        ExprKind::Call {
+            ref module,
+            ref function,
+            ref args,
+        } => {
+            c.extend(module.iter().map(|x| e(x)));
+            c.push(e(function));
+            c.extend(args.iter().map(e));
+        }
+        ExprKind::UnaryOp { ref operand, .. } => c.push(e(operand)),
+        ExprKind::BinaryOp {
+            ref left,
+            ref right,
+            ..
+        } => {
+            c.push(e(left));
+            c.push(e(right));
+        }
+        ExprKind::Match {
+            ref pattern,
+            ref value,
+        }
+        | ExprKind::MaybeMatch {
+            ref pattern,
+            ref value,
+        } => {
+            c.push(AstNode::Pattern(pattern));
+            c.push(e(value));
+        }
+        ExprKind::If(ref xs) => c.extend(xs.iter().map(AstNode::Clause)),
+        ExprKind::Case {
+            expr: ref x,
+            ref clauses,
+        } => {
+            c.push(e(x));
+            c.extend(clauses.iter().map(AstNode::Clause));
+        }
+        ExprKind::Receive {
+            ref clauses,
+            ref timeout,
+        } => {
+            c.extend(clauses.iter().map(AstNode::Clause));
+            if let Some(t) = timeout {
+                c.push(e(&t.duration));
+                c.extend(t.body.iter().map(e));
+            }
+        }
+        ExprKind::Try {
+            ref body,
+            ref clauses,
+            ref catch_clauses,
+            ref after,
+        } => {
+            c.extend(body.iter().map(e));
+            c.extend(clauses.iter().map(AstNode::Clause));
+            c.extend(catch_clauses.iter().map(AstNode::CatchClause));
+            c.extend(after.iter().map(e));
+        }
+        ExprKind::Maybe {
+            ref body,
+            ref else_clauses,
+        } => {
+            c.extend(body.iter().map(e));
+            c.extend(else_clauses.iter().map(AstNode::Clause));
+        }
+    }
+}
+
+fn qualifiers_children<'a>(xs: &'a [Qualifier], c: &mut Vec<AstNode<'a>>) {
+    for q in xs {
+        match *q {
+            Qualifier::Generator {
+                ref pattern,
+                ref source,
+                ..
+            } => {
+                c.push(AstNode::Pattern(pattern));
+                c.push(AstNode::Expr(source));
+            }
+            Qualifier::MapGenerator {
+                ref key,
+                ref value,
+                ref source,
+                ..
+            } => {
+                c.push(AstNode::Pattern(key));
+                c.push(AstNode::Pattern(value));
+                c.push(AstNode::Expr(source));
+            }
+            Qualifier::Filter(ref x) => c.push(AstNode::Expr(x)),
+        }
+    }
+}
+
+fn pattern<'a>(x: &'a Pattern, c: &mut Vec<AstNode<'a>>) {
+    let p = AstNode::Pattern;
+    match x.kind {
+        PatternKind::Literal(_)
+        | PatternKind::Variable(_)
+        | PatternKind::RecordIndex { .. }
+        | PatternKind::MacroCall(_) => {}
+        PatternKind::Tuple(ref xs) => c.extend(xs.iter().map(p)),
+        PatternKind::List {
+            ref elements,
+            ref tail,
+        } => {
+            c.extend(elements.iter().map(p));
+            c.extend(tail.iter().map(|x| p(x)));
+        }
+        PatternKind::Map(ref fields) => {
+            for f in fields {
+                c.push(p(&f.key));
+                c.push(p(&f.value));
+            }
+        }
+        PatternKind::Record { ref fields, .. } => c.extend(fields.iter().map(|f| p(&f.value))),
+        PatternKind::Bits(ref elems) => {
+            for x in elems {
+                c.push(p(&x.value));
+                c.extend(x.size.iter().map(p));
+            }
+        }
+        PatternKind::UnaryOp { ref operand, .. } => c.push(p(operand)),
+        PatternKind::BinaryOp {
+            ref left,
+            ref right,
+            ..
+        }
+        | PatternKind::Match {
+            ref left,
+            ref right,
+        } => {
+            c.push(p(left));
+            c.push(p(right));
+        }
+    }
+}
+
+fn ty<'a>(x: &'a Type, c: &mut Vec<AstNode<'a>>) {
+    let t = AstNode::Type;
+    match x.kind {
+        TypeKind::Literal(_)
+        | TypeKind::Variable(_)
+        | TypeKind::Bits { .. }
+        | TypeKind::MacroCall(_) => {}
+        TypeKind::Annotated { ty: ref x, .. } => c.push(t(x)),
+        TypeKind::Tuple(ref xs) | TypeKind::Union(ref xs) | TypeKind::Call { args: ref xs, .. } => {
+            c.extend(xs.iter().map(t))
+        }
+        TypeKind::Map(ref fields) => {
+            for f in fields {
+                c.push(t(&f.key));
+                c.push(t(&f.value));
+            }
+        }
+        TypeKind::Record { ref fields, .. } => c.extend(fields.iter().map(|f| t(&f.value))),
+        TypeKind::List { ref element, .. } => c.extend(element.iter().map(|x| t(x))),
+        TypeKind::UnaryOp { ref operand, .. } => c.push(t(operand)),
+        TypeKind::BinaryOp {
+            ref left,
+            ref right,
+            ..
+        } => {
+            c.push(t(left));
+            c.push(t(right));
+        }
+        TypeKind::Fun(ref f) => match *f {
+            FunType::Any => {}
+            FunType::AnyArity(ref r) => c.push(t(r)),
+            FunType::Normal {
+                ref args,
+                ref return_type,
+            } => {
+                c.extend(args.iter().map(t));
+                c.push(t(return_type));
+            }
+        },
+        TypeKind::Range { ref low, ref high } => {
+            c.push(t(low));
+            c.push(t(high));
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! Simplified abstract syntax trees.
+//!
+//! Unlike the CST (the `cst` module), the nodes defined here own plain values instead of tokens:
+//! punctuation and keywords are dropped, operators are enums, sequences are `Vec`s and
+//! parentheses are removed (the tree structure already expresses grouping; the span of a
+//! parenthesized node still covers the parentheses).
+//! Guard tests are represented as `Expr`s.
+//! Every node keeps the `Span` of the CST node from which it was converted.
+//!
+//! ASTs are made by the `From` conversions from the corresponding CST nodes.
+//! String literals are kept as strings unless desugared by `strings_to_lists`.
+//! `Links` maps AST nodes back to the CST nodes from which they were converted (and vice versa).
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::ast::{self, BinaryOp, ExprKind};
+//! use erl_parse::cst::Expr;
+//! use erl_parse::{Parser, TokenReader};
+//! use erl_pp::Preprocessor;
+//! use erl_tokenize::Lexer;
+//!
+//! let text = "(1 + 2) * X";
+//! let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+//! let expr: Expr = parser.parse().unwrap();
+//!
+//! let expr = ast::Expr::from(&expr);
+//! match expr.kind {
+//!     ExprKind::BinaryOp { op, ref left, .. } => {
+//!         assert_eq!(op, BinaryOp::Mul);
+//!         assert_eq!(left.span.start.offset(), 0);
+//!         assert_eq!(left.span.end.offset(), 7);
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+use erl_tokenize::tokens::{AtomToken, VariableToken};
+use erl_tokenize::{Position, PositionRange};
+use num::{BigUint, ToPrimitive};
+
+use crate::cst::commons::parts::{Args as CstArgs, BinaryOp as CstBinaryOp, UnaryOp as CstUnaryOp};
+use crate::cst::commons::parts::{BitsElem as CstBitsElem, BitsElemSpec, Sequence};
+use crate::cst::commons::parts::{MapField as CstMapField, RecordField as CstRecordField};
+use crate::cst::commons::{self, parts::NameAndArity as CstNameAndArity};
+use crate::cst::exprs::TokenGroup;
+use crate::cst::{self as cst};
+use crate::traits::CollectTokens;
+
+pub use self::exprs::{
+    CatchClause, Clause, Expr, ExprKind, Fun, Pattern, PatternKind, Qualifier, Timeout,
+};
+pub use self::forms::{Form, FormKind, Function, Module, RecordFieldDecl, SpecClause, TypeDecl};
+pub use self::links::{AstNode, Links};
+pub use self::types::{Constraint, FunType, Type, TypeKind};
+
+pub use crate::span::Span;
+
+mod desugar;
+mod exprs;
+mod forms;
+mod links;
+mod types;
+
+macro_rules! impl_position_range {
+    ($($t:ty),*) => {
+        $(impl PositionRange for $t {
+            fn start_position(&self) -> Position {
+                self.span.start.clone()
+            }
+            fn end_position(&self) -> Position {
+                self.span.end.clone()
+            }
+        })*
+    };
+}
+impl_position_range!(
+    Expr,
+    Pattern,
+    Clause,
+    CatchClause,
+    Timeout,
+    Form,
+    SpecClause,
+    RecordFieldDecl,
+    NameAndArity,
+    Type
+);
+impl<T> PositionRange for MapField<T> {
+    fn start_position(&self) -> Position {
+        self.span.start.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.span.end.clone()
+    }
+}
+impl<T> PositionRange for RecordField<T> {
+    fn start_position(&self) -> Position {
+        self.span.start.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.span.end.clone()
+    }
+}
+impl<T> PositionRange for BitsElem<T> {
+    fn start_position(&self) -> Position {
+        self.span.start.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.span.end.clone()
+    }
+}
+
+/// Literal value.
+///
+/// Integer literals are non-negative; negative numbers are `UnaryOp` calls as in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Atom(String),
+    Char(char),
+    Float(f64),
+    Integer(BigUint),
+
+    /// String literal (adjacent literals are concatenated).
+    String(String),
+}
+impl<'a> From<&'a cst::Literal> for Literal {
+    fn from(f: &'a cst::Literal) -> Self {
+        match *f {
+            cst::Literal::Atom(ref x) => Literal::Atom(x.value().to_owned()),
+            cst::Literal::Char(ref x) => Literal::Char(x.value()),
+            cst::Literal::Float(ref x) => Literal::Float(x.value()),
+            cst::Literal::Integer(ref x) => Literal::Integer(x.value().clone()),
+            cst::Literal::String { .. } | cst::Literal::TripleQuotedString(_) => {
+                Literal::String(f.string_value().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Macro call, which appears only in the CSTs parsed in no-preprocess mode.
+///
+/// The arguments are kept as source text since they may be any token sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroCall {
+    pub name: String,
+    pub args: Option<Vec<String>>,
+}
+impl<'a> From<&'a cst::exprs::MacroCall> for MacroCall {
+    fn from(f: &'a cst::exprs::MacroCall) -> Self {
+        MacroCall {
+            name: f.name.value().to_owned(),
+            args: token_groups(&f.args),
+        }
+    }
+}
+
+/// Construct parsed by a syntax extension (see the `extension` module).
+///
+/// The body following the attribute name or the prefix atom is kept as source text
+/// since its structure is defined by the extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub name: String,
+    pub body: String,
+}
+impl<'a> From<&'a cst::forms::ExtensionAttr> for Extension {
+    fn from(f: &'a cst::forms::ExtensionAttr) -> Self {
+        Extension {
+            name: atom(&f.attr_name),
+            body: f.parts.to_erlang_source(),
+        }
+    }
+}
+impl<'a> From<&'a cst::exprs::ExtensionExpr> for Extension {
+    fn from(f: &'a cst::exprs::ExtensionExpr) -> Self {
+        Extension {
+            name: atom(&f.prefix),
+            body: f.parts.to_erlang_source(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AtomOrVariable {
+    Atom(String),
+    Variable(String),
+}
+impl<'a> From<&'a commons::AtomOrVariable> for AtomOrVariable {
+    fn from(f: &'a commons::AtomOrVariable) -> Self {
+        match *f {
+            commons::AtomOrVariable::Atom(ref x) => AtomOrVariable::Atom(x.value().to_owned()),
+            commons::AtomOrVariable::Variable(ref x) => {
+                AtomOrVariable::Variable(x.value().to_owned())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IntegerOrVariable {
+    Integer(BigUint),
+    Variable(String),
+}
+impl<'a> From<&'a commons::IntegerOrVariable> for IntegerOrVariable {
+    fn from(f: &'a commons::IntegerOrVariable) -> Self {
+        match *f {
+            commons::IntegerOrVariable::Integer(ref x) => {
+                IntegerOrVariable::Integer(x.value().clone())
+            }
+            commons::IntegerOrVariable::Variable(ref x) => {
+                IntegerOrVariable::Variable(x.value().to_owned())
+            }
+        }
+    }
+}
+
+/// `Name/Arity` (e.g., an entry of an `-export` list).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NameAndArity {
+    pub span: Span,
+    pub name: String,
+    pub arity: usize,
+}
+impl<'a> From<&'a CstNameAndArity> for NameAndArity {
+    fn from(f: &'a CstNameAndArity) -> Self {
+        NameAndArity {
+            span: Span::of(f),
+            name: f.name.value().to_owned(),
+            arity: f.arity.value().to_usize().unwrap_or(usize::MAX),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+    Not,
+    Bnot,
+}
+impl UnaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOp::Plus => "+",
+            UnaryOp::Minus => "-",
+            UnaryOp::Not => "not",
+            UnaryOp::Bnot => "bnot",
+        }
+    }
+}
+impl<'a> From<&'a CstUnaryOp> for UnaryOp {
+    fn from(f: &'a CstUnaryOp) -> Self {
+        match *f {
+            CstUnaryOp::Plus(_) => UnaryOp::Plus,
+            CstUnaryOp::Minus(_) => UnaryOp::Minus,
+            CstUnaryOp::Not(_) => UnaryOp::Not,
+            CstUnaryOp::Bnot(_) => UnaryOp::Bnot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Plus,
+    Minus,
+    Mul,
+    FloatDiv,
+    IntDiv,
+    Rem,
+    Bor,
+    Bxor,
+    Band,
+    Bsl,
+    Bsr,
+    And,
+    Or,
+    Xor,
+    PlusPlus,
+    MinusMinus,
+    Eq,
+    ExactEq,
+    NotEq,
+    ExactNotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Andalso,
+    Orelse,
+    Send,
+}
+impl BinaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::FloatDiv => "/",
+            BinaryOp::IntDiv => "div",
+            BinaryOp::Rem => "rem",
+            BinaryOp::Bor => "bor",
+            BinaryOp::Bxor => "bxor",
+            BinaryOp::Band => "band",
+            BinaryOp::Bsl => "bsl",
+            BinaryOp::Bsr => "bsr",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Xor => "xor",
+            BinaryOp::PlusPlus => "++",
+            BinaryOp::MinusMinus => "--",
+            BinaryOp::Eq => "==",
+            BinaryOp::ExactEq => "=:=",
+            BinaryOp::NotEq => "/=",
+            BinaryOp::ExactNotEq => "=/=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEq => "=<",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEq => ">=",
+            BinaryOp::Andalso => "andalso",
+            BinaryOp::Orelse => "orelse",
+            BinaryOp::Send => "!",
+        }
+    }
+}
+impl<'a> From<&'a CstBinaryOp> for BinaryOp {
+    fn from(f: &'a CstBinaryOp) -> Self {
+        match *f {
+            CstBinaryOp::Plus(_) => BinaryOp::Plus,
+            CstBinaryOp::Minus(_) => BinaryOp::Minus,
+            CstBinaryOp::Mul(_) => BinaryOp::Mul,
+            CstBinaryOp::FloatDiv(_) => BinaryOp::FloatDiv,
+            CstBinaryOp::IntDiv(_) => BinaryOp::IntDiv,
+            CstBinaryOp::Rem(_) => BinaryOp::Rem,
+            CstBinaryOp::Bor(_) => BinaryOp::Bor,
+            CstBinaryOp::Bxor(_) => BinaryOp::Bxor,
+            CstBinaryOp::Band(_) => BinaryOp::Band,
+            CstBinaryOp::Bsl(_) => BinaryOp::Bsl,
+            CstBinaryOp::Bsr(_) => BinaryOp::Bsr,
+            CstBinaryOp::And(_) => BinaryOp::And,
+            CstBinaryOp::Or(_) => BinaryOp::Or,
+            CstBinaryOp::Xor(_) => BinaryOp::Xor,
+            CstBinaryOp::PlusPlus(_) => BinaryOp::PlusPlus,
+            CstBinaryOp::MinusMinus(_) => BinaryOp::MinusMinus,
+            CstBinaryOp::Eq(_) => BinaryOp::Eq,
+            CstBinaryOp::ExactEq(_) => BinaryOp::ExactEq,
+            CstBinaryOp::NotEq(_) => BinaryOp::NotEq,
+            CstBinaryOp::ExactNotEq(_) => BinaryOp::ExactNotEq,
+            CstBinaryOp::Less(_) => BinaryOp::Less,
+            CstBinaryOp::LessEq(_) => BinaryOp::LessEq,
+            CstBinaryOp::Greater(_) => BinaryOp::Greater,
+            CstBinaryOp::GreaterEq(_) => BinaryOp::GreaterEq,
+            CstBinaryOp::Andalso(_) => BinaryOp::Andalso,
+            CstBinaryOp::Orelse(_) => BinaryOp::Orelse,
+            CstBinaryOp::Send(_) => BinaryOp::Send,
+        }
+    }
+}
+
+/// `Key => Value` or `Key := Value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapField<T> {
+    pub span: Span,
+    pub key: T,
+    pub value: T,
+
+    /// `true` if the field is `Key := Value`.
+    pub exact: bool,
+}
+impl<'a, T, U> From<&'a CstMapField<T>> for MapField<U>
+where
+    T: PositionRange,
+    U: From<&'a T>,
+{
+    fn from(f: &'a CstMapField<T>) -> Self {
+        MapField {
+            span: Span::of(f),
+            key: U::from(&f.key),
+            value: U::from(&f.value),
+            exact: f._relation.text() == ":=",
+        }
+    }
+}
+
+/// `Name = Value` in a record expression or pattern (`Name` can be `_`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordField<T> {
+    pub span: Span,
+    pub name: AtomOrVariable,
+    pub value: T,
+}
+impl<'a, T, U> From<&'a CstRecordField<T>> for RecordField<U>
+where
+    T: PositionRange,
+    U: From<&'a T>,
+{
+    fn from(f: &'a CstRecordField<T>) -> Self {
+        RecordField {
+            span: Span::of(f),
+            name: AtomOrVariable::from(&f.key),
+            value: U::from(&f.value),
+        }
+    }
+}
+
+/// Element of a bit string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitsElem<T> {
+    pub span: Span,
+    pub value: T,
+    pub size: Option<T>,
+    pub specs: Vec<BitsSpec>,
+}
+impl<'a, T, U> From<&'a CstBitsElem<T>> for BitsElem<U>
+where
+    T: PositionRange,
+    U: From<&'a T>,
+{
+    fn from(f: &'a CstBitsElem<T>) -> Self {
+        BitsElem {
+            span: Span::of(f),
+            value: U::from(&f.element),
+            size: f.size.as_ref().map(|s| U::from(&s.size)),
+            specs: f
+                .type_specs
+                .iter()
+                .flat_map(|s| s.specs.iter())
+                .map(BitsSpec::from)
+                .collect(),
+        }
+    }
+}
+
+/// Type specifier of a bit string element (e.g., `binary` or `unit:8`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BitsSpec {
+    Type(String),
+    Unit(BigUint),
+}
+impl<'a> From<&'a BitsElemSpec> for BitsSpec {
+    fn from(f: &'a BitsElemSpec) -> Self {
+        match *f {
+            BitsElemSpec::Type(ref t) => BitsSpec::Type(t.value().to_owned()),
+            BitsElemSpec::Unit { ref unit, .. } => BitsSpec::Unit(unit.value().clone()),
+        }
+    }
+}
+
+/// Returns the source text of each argument of a macro call or a preprocessor directive.
+fn token_groups(args: &Option<CstArgs<TokenGroup>>) -> Option<Vec<String>> {
+    args.as_ref().map(|a| {
+        a.args
+            .iter()
+            .flat_map(|s| s.iter())
+            .map(|g| g.to_erlang_source())
+            .collect()
+    })
+}
+
+fn atom(token: &AtomToken) -> String {
+    token.value().to_owned()
+}
+
+/// Returns the name of a record (`?NAME` if it is an unexpanded macro call).
+fn record_name(name: &commons::RecordName) -> String {
+    match *name {
+        commons::RecordName::Atom(ref x) => atom(x),
+        commons::RecordName::MacroCall(ref x) => format!("?{}", x.name.text()),
+    }
+}
+
+fn variable(token: &VariableToken) -> String {
+    token.value().to_owned()
+}
+
+/// Converts the items of an optional sequence.
+fn items<'a, T, U, D>(seq: &'a Option<Sequence<T, D>>) -> Vec<U>
+where
+    U: From<&'a T>,
+{
+    seq.iter().flat_map(|s| s.iter()).map(U::from).collect()
+}
+
+/// Converts the items of a sequence.
+fn seq_items<'a, T, U, D>(seq: &'a Sequence<T, D>) -> Vec<U>
+where
+    U: From<&'a T>,
+{
+    seq.iter().map(U::from).collect()
+}
+
+/// Implements `From<T>` by converting `&T`.
+macro_rules! impl_from_owned {
+    ($($cst:ty => $ast:ty),* $(,)*) => {
+        $(
+            impl From<$cst> for $ast {
+                fn from(f: $cst) -> Self {
+                    Self::from(&f)
+                }
+            }
+        )*
+    };
+}
+impl_from_owned! {
+    cst::Literal => Literal,
+    cst::Expr => Expr,
+    cst::Pattern => Pattern,
+    cst::GuardTest => Expr,
+    cst::Type => Type,
+    cst::Form => Form,
+    cst::ModuleDecl => Module,
+}
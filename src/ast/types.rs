@@ -0,0 +1,202 @@
+use num::BigUint;
+
+use super::UnaryOp;
+use super::{
+    atom, items, record_name, seq_items, variable, BinaryOp, Literal, MacroCall, MapField,
+    RecordField, Span,
+};
+use crate::cst::types::parts::BitsSpec;
+use crate::cst::types::Fun;
+use crate::cst::{self as cst};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {
+    pub span: Span,
+    pub kind: TypeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Literal(Literal),
+    Variable(String),
+
+    /// `Var :: Type`
+    Annotated {
+        var: String,
+        ty: Box<Type>,
+    },
+    Tuple(Vec<Type>),
+    Map(Vec<MapField<Type>>),
+    Record {
+        name: String,
+        fields: Vec<RecordField<Type>>,
+    },
+
+    /// `[]` (if `element` is `None`), `[T]` or `[T, ...]` (if `non_empty` is `true`).
+    List {
+        element: Option<Box<Type>>,
+        non_empty: bool,
+    },
+
+    /// `<<_:M, _:_*N>>`
+    Bits {
+        bytes: Option<BigUint>,
+        bits: Option<BigUint>,
+    },
+
+    /// `Module:Name(Args)` or `Name(Args)`.
+    Call {
+        module: Option<String>,
+        name: String,
+        args: Vec<Type>,
+    },
+    UnaryOp {
+        op: UnaryOp,
+        operand: Box<Type>,
+    },
+    BinaryOp {
+        op: BinaryOp,
+        left: Box<Type>,
+        right: Box<Type>,
+    },
+    Fun(FunType),
+
+    /// `Low..High`
+    Range {
+        low: Box<Type>,
+        high: Box<Type>,
+    },
+
+    /// `T1 | T2 | ...` (nested unions are flattened).
+    Union(Vec<Type>),
+
+    /// `?NAME` or `?NAME(Args)` (only in no-preprocess mode).
+    MacroCall(MacroCall),
+}
+impl<'a> From<&'a cst::Type> for Type {
+    fn from(f: &'a cst::Type) -> Self {
+        let kind = match *f {
+            cst::Type::Literal(ref x) => TypeKind::Literal(Literal::from(x)),
+            cst::Type::Variable(ref x) => TypeKind::Variable(variable(x)),
+            cst::Type::Annotated(ref x) => TypeKind::Annotated {
+                var: variable(&x.var),
+                ty: Box::new(Type::from(&x.ty)),
+            },
+            cst::Type::Tuple(ref x) => TypeKind::Tuple(items(&x.elements)),
+            cst::Type::Map(ref x) => TypeKind::Map(items(&x.fields)),
+            cst::Type::Record(ref x) => TypeKind::Record {
+                name: record_name(&x.name),
+                fields: items(&x.fields),
+            },
+            cst::Type::List(ref x) => TypeKind::List {
+                element: x
+                    .element
+                    .as_ref()
+                    .map(|e| Box::new(Type::from(&e.element_type))),
+                non_empty: x.element.as_ref().is_some_and(|e| e.non_empty.is_some()),
+            },
+            cst::Type::Bits(ref x) => {
+                let (bytes, bits) = match x.spec {
+                    None => (None, None),
+                    Some(BitsSpec::Bytes(ref b)) => (Some(b.size.value().clone()), None),
+                    Some(BitsSpec::Bits(ref b)) => (None, Some(b.size.value().clone())),
+                    Some(BitsSpec::BytesAndBits(ref b)) => (
+                        Some(b.byte.size.value().clone()),
+                        Some(b.bit.size.value().clone()),
+                    ),
+                };
+                TypeKind::Bits { bytes, bits }
+            }
+            cst::Type::Parenthesized(ref x) => Type::from(&x.item).kind,
+            cst::Type::TypeCall(ref x) => TypeKind::Call {
+                module: x.module.as_ref().map(|m| atom(&m.name)),
+                name: atom(&x.name),
+                args: items(&x.args.args),
+            },
+            cst::Type::UnaryOpCall(ref x) => TypeKind::UnaryOp {
+                op: UnaryOp::from(&x.op),
+                operand: Box::new(Type::from(&x.operand)),
+            },
+            cst::Type::BinaryOpCall(ref x) => TypeKind::BinaryOp {
+                op: BinaryOp::from(&x.op),
+                left: Box::new(Type::from(&x.left)),
+                right: Box::new(Type::from(&x.right)),
+            },
+            cst::Type::Fun(ref x) => TypeKind::Fun(FunType::from(&**x)),
+            cst::Type::Range(ref x) => TypeKind::Range {
+                low: Box::new(Type::from(&x.low)),
+                high: Box::new(Type::from(&x.high)),
+            },
+            cst::Type::Union(_) => {
+                let mut members = Vec::new();
+                flatten_union(f, &mut members);
+                TypeKind::Union(members)
+            }
+            cst::Type::MacroCall(ref x) => TypeKind::MacroCall(MacroCall::from(&**x)),
+        };
+        Type {
+            span: Span::of(f),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunType {
+    /// `fun()`
+    Any,
+
+    /// `fun((...) -> ReturnType)`
+    AnyArity(Box<Type>),
+
+    /// `fun((Args) -> ReturnType)`
+    Normal {
+        args: Vec<Type>,
+        return_type: Box<Type>,
+    },
+}
+impl<'a> From<&'a Fun> for FunType {
+    fn from(f: &'a Fun) -> Self {
+        match *f {
+            Fun::Any(_) => FunType::Any,
+            Fun::AnyArity(ref x) => FunType::AnyArity(Box::new(Type::from(&x.return_type))),
+            Fun::Normal(ref x) => FunType::Normal {
+                args: items(&x.args.args),
+                return_type: Box::new(Type::from(&x.return_type)),
+            },
+        }
+    }
+}
+
+/// `Var :: Bound` (or `is_subtype(Var, Bound)`) in the `when` part of a spec clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub span: Span,
+    pub var: String,
+    pub bound: Type,
+}
+impl<'a> From<&'a cst::types::Constraint> for Constraint {
+    fn from(f: &'a cst::types::Constraint) -> Self {
+        Constraint {
+            span: Span::of(f),
+            var: variable(f.var()),
+            bound: Type::from(f.bound()),
+        }
+    }
+}
+
+fn flatten_union(ty: &cst::Type, members: &mut Vec<Type>) {
+    if let cst::Type::Union(ref x) = *ty {
+        flatten_union(&x.left, members);
+        flatten_union(&x.right, members);
+    } else {
+        members.push(Type::from(ty));
+    }
+}
+
+/// Converts the constraints (`when ...`) of a spec clause.
+pub(super) fn constraints(x: &Option<cst::types::Constraints>) -> Vec<Constraint> {
+    x.iter()
+        .flat_map(|c| seq_items::<_, Constraint, _>(&c.constraints))
+        .collect()
+}
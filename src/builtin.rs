@@ -1,9 +1,11 @@
 use erl_pp::{self, Preprocessor};
+use erl_tokenize::values::Symbol;
 use erl_tokenize::{Lexer, LexicalToken};
 
-use crate::cst::ModuleDecl;
-use crate::traits::TokenRead;
-use crate::{Parser, Result, TokenReader};
+use crate::cst::commons::parts::{Clauses, Sequence};
+use crate::cst::{Expr, Form, GuardTest, ModuleDecl, Pattern, Type};
+use crate::traits::{Parse, TokenRead};
+use crate::{ErrorKind, MaybeMatchTokens, Parser, Result, TokenReader};
 
 #[derive(Debug)]
 pub struct ModuleParser<'a>(Parser<TokenReader<Preprocessor<Lexer<&'a str>>, erl_pp::Error>>);
@@ -25,3 +27,59 @@ impl<'a> ModuleParser<'a> {
 pub fn parse_module(tokens: &mut dyn TokenRead) -> Result<ModuleDecl> {
     Parser::new(tokens).parse()
 }
+
+/// Parses `text` as an expression (e.g., a config snippet or REPL input).
+///
+/// `text` may end with a `.`, and the macros defined by `-define` can be used as usual.
+/// The other tokens following the expression are rejected.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::builtin::parse_expr;
+/// use erl_parse::cst::Expr;
+///
+/// let expr = parse_expr("lists:sum([1, 2, 3]).").unwrap();
+/// assert!(matches!(expr, Expr::FunCall(_)));
+/// assert!(parse_expr("1 2").is_err());
+/// ```
+pub fn parse_expr(text: &str) -> Result<Expr> {
+    track!(parse_fragment(text, true))
+}
+
+/// Parses `text` as a pattern in the manner of `parse_expr`.
+pub fn parse_pattern(text: &str) -> Result<Pattern> {
+    track!(parse_fragment(text, true))
+}
+
+/// Parses `text` as a type in the manner of `parse_expr`.
+pub fn parse_type(text: &str) -> Result<Type> {
+    track!(parse_fragment(text, true))
+}
+
+/// Parses `text` as a guard (i.e., `;`-separated guard sequences without `when`)
+/// in the manner of `parse_expr`.
+pub fn parse_guard(text: &str) -> Result<Clauses<Sequence<GuardTest>>> {
+    track!(parse_fragment(text, true))
+}
+
+/// Parses `text` as a form, which ends with a `.`.
+pub fn parse_form(text: &str) -> Result<Form> {
+    track!(parse_fragment(text, false))
+}
+
+fn parse_fragment<T: Parse>(text: &str, optional_dot: bool) -> Result<T> {
+    let tokens = MaybeMatchTokens::new(Lexer::new(text));
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let fragment = track!(parser.parse())?;
+    let mut dot = !optional_dot;
+    while !track!(parser.eos())? {
+        let token = track!(parser.parse::<LexicalToken>())?;
+        let is_dot = token.as_symbol_token().map(|t| t.value()) == Some(Symbol::Dot);
+        if dot || !is_dot {
+            track_panic!(ErrorKind::UnexpectedToken(token));
+        }
+        dot = true;
+    }
+    Ok(fragment)
+}
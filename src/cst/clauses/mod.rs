@@ -1,19 +1,21 @@
 use erl_tokenize::tokens::{AtomToken, SymbolToken, VariableToken};
 use erl_tokenize::values::Symbol;
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use self::parts::{ExceptionClass, StackTrace, WhenGuard};
 use crate::cst::commons::parts::{Args, Clauses, Sequence};
 use crate::cst::exprs::parts::Body;
 use crate::cst::types;
 use crate::cst::{GuardTest, Pattern, Type};
-use crate::traits::{Parse, TokenRead};
+use crate::messages::Message;
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{Parser, Result};
 
 pub mod parts;
 
 /// `Option<ExceptionClass>` `Pattern` `Option<WhenGuard>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CatchClause {
     pub class: Option<ExceptionClass>,
     pub pattern: Pattern,
@@ -48,9 +50,32 @@ impl PositionRange for CatchClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for CatchClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.class.collect_tokens(tokens);
+        self.pattern.collect_tokens(tokens);
+        self.stacktrace.collect_tokens(tokens);
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for CatchClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        CatchClause {
+            class: self.class.fold_with(folder),
+            pattern: self.pattern.fold_with(folder),
+            stacktrace: self.stacktrace.fold_with(folder),
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `Args<Type>` `->` `Type` `Option<Constraints>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpecClause {
     pub args: Args<Type>,
     pub _arrow: SymbolToken,
@@ -81,9 +106,28 @@ impl PositionRange for SpecClause {
             .unwrap_or_else(|| self.return_type.end_position())
     }
 }
+impl CollectTokens for SpecClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.args.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.return_type.collect_tokens(tokens);
+        self.constraints.collect_tokens(tokens);
+    }
+}
+impl Foldable for SpecClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        SpecClause {
+            args: self.args.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            return_type: self.return_type.fold_with(folder),
+            constraints: self.constraints.fold_with(folder),
+        }
+    }
+}
 
 /// `Pattern` `Option<WhenGuard>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CaseClause {
     pub pattern: Pattern,
     pub guard: Option<WhenGuard>,
@@ -111,9 +155,28 @@ impl PositionRange for CaseClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for CaseClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.pattern.collect_tokens(tokens);
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for CaseClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        CaseClause {
+            pattern: self.pattern.fold_with(folder),
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `Clauses<Sequence<GuardTest>>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfClause {
     pub guard: Clauses<Sequence<GuardTest>>,
     pub _arrow: SymbolToken,
@@ -139,9 +202,26 @@ impl PositionRange for IfClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for IfClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for IfClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        IfClause {
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `Args<Pattern>` `Option<WhenGuard>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunClause {
     pub patterns: Args<Pattern>,
     pub guard: Option<WhenGuard>,
@@ -169,9 +249,28 @@ impl PositionRange for FunClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for FunClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.patterns.collect_tokens(tokens);
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for FunClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FunClause {
+            patterns: self.patterns.fold_with(folder),
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `VariableToken` `Args<Pattern>` `Option<WhenGuard>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NamedFunClause {
     pub name: VariableToken,
     pub patterns: Args<Pattern>,
@@ -201,9 +300,30 @@ impl PositionRange for NamedFunClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for NamedFunClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.name.collect_tokens(tokens);
+        self.patterns.collect_tokens(tokens);
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for NamedFunClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        NamedFunClause {
+            name: self.name.fold_with(folder),
+            patterns: self.patterns.fold_with(folder),
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `AtomToken` `Args<Pattern>` `Option<WhenGuard>` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunDeclClause {
     pub name: AtomToken,
     pub patterns: Args<Pattern>,
@@ -211,18 +331,36 @@ pub struct FunDeclClause {
     pub _arrow: SymbolToken,
     pub body: Body,
 }
+impl FunDeclClause {
+    pub fn arity(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Returns the name and arity of this clause (e.g., `foo/1`).
+    pub(crate) fn head(&self) -> String {
+        format!("{}/{}", self.name.value(), self.arity())
+    }
+}
 impl Parse for FunDeclClause {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
         // TODO: handle predefined macros
-        Ok(FunDeclClause {
-            name: track!(parser.parse())?,
-            patterns: track!(parser.parse())?,
-            guard: track!(parser.parse())?,
-            _arrow: track!(parser.expect(&Symbol::RightArrow))?,
-            body: track!(parser.parse())?,
+        let name: AtomToken = track!(parser.parse())?;
+        let patterns: Args<Pattern> = track!(parser.parse())?;
+        let description = Message::new("function_context")
+            .arg("name", name.value())
+            .arg("arity", patterns.len().to_string());
+        let start = name.start_position();
+        parser.with_context_at(description, start, |parser| {
+            Ok(FunDeclClause {
+                name,
+                patterns,
+                guard: track!(parser.parse())?,
+                _arrow: track!(parser.expect(&Symbol::RightArrow))?,
+                body: track!(parser.parse())?,
+            })
         })
     }
 }
@@ -234,3 +372,23 @@ impl PositionRange for FunDeclClause {
         self.body.end_position()
     }
 }
+impl CollectTokens for FunDeclClause {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.name.collect_tokens(tokens);
+        self.patterns.collect_tokens(tokens);
+        self.guard.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for FunDeclClause {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FunDeclClause {
+            name: self.name.fold_with(folder),
+            patterns: self.patterns.fold_with(folder),
+            guard: self.guard.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
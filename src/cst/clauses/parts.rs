@@ -1,15 +1,16 @@
 use erl_tokenize::tokens::{KeywordToken, SymbolToken, VariableToken};
 use erl_tokenize::values::{Keyword, Symbol};
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use super::super::commons::parts::{Clauses, Sequence};
 use super::super::commons::AtomOrVariable;
 use super::super::GuardTest;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{Parser, Result};
 
 /// `AtomOrVariable` `:`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExceptionClass {
     pub class: AtomOrVariable,
     pub _colon: SymbolToken,
@@ -30,8 +31,23 @@ impl PositionRange for ExceptionClass {
         self._colon.end_position()
     }
 }
+impl CollectTokens for ExceptionClass {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.class.collect_tokens(tokens);
+        self._colon.collect_tokens(tokens);
+    }
+}
+impl Foldable for ExceptionClass {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ExceptionClass {
+            class: self.class.fold_with(folder),
+            _colon: self._colon.fold_with(folder),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StackTrace {
     pub _colon: SymbolToken,
     pub variable: VariableToken,
@@ -52,9 +68,24 @@ impl PositionRange for StackTrace {
         self.variable.end_position()
     }
 }
+impl CollectTokens for StackTrace {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._colon.collect_tokens(tokens);
+        self.variable.collect_tokens(tokens);
+    }
+}
+impl Foldable for StackTrace {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        StackTrace {
+            _colon: self._colon.fold_with(folder),
+            variable: self.variable.fold_with(folder),
+        }
+    }
+}
 
 /// `when` `Clauses<Sequence<GuardTest>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WhenGuard {
     pub _when: KeywordToken,
     pub seq: Clauses<Sequence<GuardTest>>,
@@ -75,3 +106,17 @@ impl PositionRange for WhenGuard {
         self.seq.end_position()
     }
 }
+impl CollectTokens for WhenGuard {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._when.collect_tokens(tokens);
+        self.seq.collect_tokens(tokens);
+    }
+}
+impl Foldable for WhenGuard {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        WhenGuard {
+            _when: self._when.fold_with(folder),
+            seq: self.seq.fold_with(folder),
+        }
+    }
+}
@@ -26,25 +26,23 @@ impl<'a, T: 'a> Iterator for ConsCellIterInner<'a, T> {
     type Item = (Option<Symbol>, &'a T);
     fn next(&mut self) -> Option<Self::Item> {
         match *self {
-            ConsCellIterInner::Head(&ConsCell { ref item, ref tail }) => {
-                if let Some(ref tail) = *tail {
+            ConsCellIterInner::Head(ConsCell { item, tail }) => {
+                if let Some(tail) = tail {
                     *self = ConsCellIterInner::Tail(tail);
                 } else {
                     *self = ConsCellIterInner::Eos
                 }
                 Some((None, item))
             }
-            ConsCellIterInner::Tail(&ConsCellTail::Proper {
-                ref item, ref tail, ..
-            }) => {
-                if let Some(ref tail) = *tail {
+            ConsCellIterInner::Tail(ConsCellTail::Proper { item, tail, .. }) => {
+                if let Some(tail) = tail {
                     *self = ConsCellIterInner::Tail(tail);
                 } else {
                     *self = ConsCellIterInner::Eos
                 }
                 Some((Some(Symbol::Comma), item))
             }
-            ConsCellIterInner::Tail(&ConsCellTail::Improper { ref item, .. }) => {
+            ConsCellIterInner::Tail(ConsCellTail::Improper { item, .. }) => {
                 *self = ConsCellIterInner::Eos;
                 Some((Some(Symbol::VerticalBar), item))
             }
@@ -78,18 +76,16 @@ impl<'a, T: 'a, D: 'a> Iterator for SequenceIterInner<'a, T, D> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
         match *self {
-            SequenceIterInner::Head(&Sequence { ref item, ref tail }) => {
-                if let Some(ref tail) = *tail {
+            SequenceIterInner::Head(Sequence { item, tail }) => {
+                if let Some(tail) = tail {
                     *self = SequenceIterInner::Tail(tail);
                 } else {
                     *self = SequenceIterInner::Eos
                 }
                 Some(item)
             }
-            SequenceIterInner::Tail(&SequenceTail {
-                ref item, ref tail, ..
-            }) => {
-                if let Some(ref tail) = *tail {
+            SequenceIterInner::Tail(SequenceTail { item, tail, .. }) => {
+                if let Some(tail) = tail {
                     *self = SequenceIterInner::Tail(tail);
                 } else {
                     *self = SequenceIterInner::Eos
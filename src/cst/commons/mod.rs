@@ -5,8 +5,10 @@ use erl_tokenize::{LexicalToken, Position, PositionRange};
 use self::parts::{
     Args, BinaryOp, BitsElem, ConsCell, MapField, ModulePrefix, RecordField, Sequence, UnaryOp,
 };
+use super::exprs::MacroCall;
 use super::Pattern;
-use crate::traits::{Parse, ParseTail, TokenRead};
+use crate::messages::Message;
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, ParseTail, TokenRead};
 use crate::{ErrorKind, Parser, Result};
 
 pub mod iterators;
@@ -14,11 +16,26 @@ pub mod parts;
 
 /// `{` `Option<Sequence<T>>` `}`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tuple<T> {
     pub _open: SymbolToken,
     pub elements: Option<Sequence<T>>,
     pub _close: SymbolToken,
 }
+impl<T> Tuple<T> {
+    /// Returns the number of the elements.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_none()
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elements.iter().flat_map(|s| s.iter())
+    }
+}
 impl<T: Parse> Parse for Tuple<T> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
         Ok(Tuple {
@@ -36,14 +53,65 @@ impl<T> PositionRange for Tuple<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Tuple<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.elements.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Tuple<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Tuple {
+            _open: self._open.fold_with(folder),
+            elements: self.elements.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `[` `Option<ConsCell<T>>` `]`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct List<T> {
     pub _open: SymbolToken,
     pub elements: Option<ConsCell<T>>,
     pub _close: SymbolToken,
 }
+impl<T> List<T> {
+    /// Returns the number of the elements (excluding the tail after `|`).
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_none()
+    }
+
+    /// Returns an iterator over the elements (excluding the tail after `|`).
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells()
+            .filter(|&(d, _)| d != Some(Symbol::VerticalBar))
+            .map(|(_, x)| x)
+    }
+
+    /// Returns the tail after `|` (e.g., `T` of `[H | T]`).
+    pub fn tail(&self) -> Option<&T> {
+        self.cells()
+            .find(|&(d, _)| d == Some(Symbol::VerticalBar))
+            .map(|(_, x)| x)
+    }
+
+    /// Returns `true` if this list has no tail after `|`.
+    ///
+    /// Note that `[a | []]` is regarded as improper since the tail is not inspected.
+    pub fn is_proper_list(&self) -> bool {
+        self.tail().is_none()
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (Option<Symbol>, &T)> {
+        self.elements.iter().flat_map(|c| c.iter())
+    }
+}
 impl<T: Parse> Parse for List<T> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
         Ok(List {
@@ -61,9 +129,26 @@ impl<T> PositionRange for List<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for List<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.elements.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for List<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        List {
+            _open: self._open.fold_with(folder),
+            elements: self.elements.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `[` `Option<Sequence<T>>` `]`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProperList<T> {
     pub _open: SymbolToken,
     pub elements: Option<Sequence<T>>,
@@ -86,9 +171,26 @@ impl<T> PositionRange for ProperList<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for ProperList<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.elements.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for ProperList<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ProperList {
+            _open: self._open.fold_with(folder),
+            elements: self.elements.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `<<` `Option<Sequence<BitsElem<T>>>` `>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bits<T> {
     pub _open: SymbolToken,
     pub elements: Option<Sequence<BitsElem<T>>>,
@@ -111,12 +213,29 @@ impl<T> PositionRange for Bits<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Bits<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.elements.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Bits<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Bits {
+            _open: self._open.fold_with(folder),
+            elements: self.elements.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
-/// `#` `AtomToken` `{` `Option<Sequence<RecordField<T>>>` `}`
+/// `#` `RecordName` `{` `Option<Sequence<RecordField<T>>>` `}`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Record<T> {
     pub _sharp: SymbolToken,
-    pub name: AtomToken,
+    pub name: RecordName,
     pub _open: SymbolToken,
     pub fields: Option<Sequence<RecordField<T>>>,
     pub _close: SymbolToken,
@@ -140,12 +259,33 @@ impl<T> PositionRange for Record<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Record<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._sharp.collect_tokens(tokens);
+        self.name.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.fields.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Record<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Record {
+            _sharp: self._sharp.fold_with(folder),
+            name: self.name.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            fields: self.fields.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
-/// `#` `AtomToken` `.` `AtomToken`
+/// `#` `RecordName` `.` `AtomToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordFieldIndex {
     pub _sharp: SymbolToken,
-    pub name: AtomToken,
+    pub name: RecordName,
     pub _dot: SymbolToken,
     pub field: AtomToken,
 }
@@ -167,15 +307,48 @@ impl PositionRange for RecordFieldIndex {
         self.field.end_position()
     }
 }
+impl CollectTokens for RecordFieldIndex {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._sharp.collect_tokens(tokens);
+        self.name.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+        self.field.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordFieldIndex {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordFieldIndex {
+            _sharp: self._sharp.fold_with(folder),
+            name: self.name.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+            field: self.field.fold_with(folder),
+        }
+    }
+}
 
 /// `#` `{` `Option<Sequence<MapField<T>>>` `}`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Map<T> {
     pub _sharp: SymbolToken,
     pub _open: SymbolToken,
     pub fields: Option<Sequence<MapField<T>>>,
     pub _close: SymbolToken,
 }
+impl<T> Map<T> {
+    /// Returns the number of the fields.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_none()
+    }
+
+    /// Returns an iterator over the fields.
+    pub fn iter(&self) -> impl Iterator<Item = &MapField<T>> {
+        self.fields.iter().flat_map(|s| s.iter())
+    }
+}
 impl<T: Parse> Parse for Map<T> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
         Ok(Map {
@@ -194,9 +367,28 @@ impl<T> PositionRange for Map<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Map<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._sharp.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.fields.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Map<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Map {
+            _sharp: self._sharp.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            fields: self.fields.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `Pattern` `=` `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Match<T> {
     pub pattern: Pattern,
     pub _match: SymbolToken,
@@ -229,9 +421,26 @@ impl<T: PositionRange> PositionRange for Match<T> {
         self.value.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Match<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.pattern.collect_tokens(tokens);
+        self._match.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Match<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Match {
+            pattern: self.pattern.fold_with(folder),
+            _match: self._match.fold_with(folder),
+            value: self.value.fold_with(folder),
+        }
+    }
+}
 
 /// `T` `BinaryOp` `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryOpCall<T> {
     pub left: T,
     pub op: BinaryOp,
@@ -255,9 +464,26 @@ impl<T: PositionRange> PositionRange for BinaryOpCall<T> {
         self.right.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for BinaryOpCall<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.left.collect_tokens(tokens);
+        self.op.collect_tokens(tokens);
+        self.right.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for BinaryOpCall<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BinaryOpCall {
+            left: self.left.fold_with(folder),
+            op: self.op.fold_with(folder),
+            right: self.right.fold_with(folder),
+        }
+    }
+}
 
 /// `UnaryOp` `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnaryOpCall<T> {
     pub op: UnaryOp,
     pub operand: T,
@@ -278,37 +504,56 @@ impl<T: PositionRange> PositionRange for UnaryOpCall<T> {
         self.operand.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for UnaryOpCall<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.op.collect_tokens(tokens);
+        self.operand.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for UnaryOpCall<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        UnaryOpCall {
+            op: self.op.fold_with(folder),
+            operand: self.operand.fold_with(folder),
+        }
+    }
+}
 
 /// `Option<ModulePrefix<T>>` `T` `Args<A>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Call<T, A = T> {
     pub module: Option<ModulePrefix<T>>,
     pub name: T,
     pub args: Args<A>,
 }
-impl<T: Parse, A: Parse> Parse for Call<T, A> {
+impl<T: Parse + PositionRange, A: Parse> Parse for Call<T, A> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
-        Ok(Call {
-            module: track!(parser.parse())?,
-            name: track!(T::parse_non_left_recor(parser))?,
-            args: track!(parser.parse())?,
-        })
+        let module: Option<ModulePrefix<T>> = track!(parser.parse())?;
+        let name = track!(T::parse_non_left_recor(parser))?;
+        let start = module
+            .as_ref()
+            .map(PositionRange::start_position)
+            .unwrap_or_else(|| name.start_position());
+        let args = track!(parse_call_args(parser, start))?;
+        Ok(Call { module, name, args })
     }
 }
-impl<T: Parse, A: Parse> ParseTail for Call<T, A> {
+impl<T: Parse + PositionRange, A: Parse> ParseTail for Call<T, A> {
     type Head = T;
     fn parse_tail<U: TokenRead>(parser: &mut Parser<U>, head: Self::Head) -> Result<Self> {
+        let start = head.start_position();
         if let Ok(_colon) = parser.transaction(|parser| parser.expect(&Symbol::Colon)) {
             Ok(Call {
                 module: Some(ModulePrefix { name: head, _colon }),
                 name: track!(T::parse_non_left_recor(parser))?,
-                args: track!(parser.parse())?,
+                args: track!(parse_call_args(parser, start))?,
             })
         } else {
             Ok(Call {
                 module: None,
                 name: head,
-                args: track!(parser.parse())?,
+                args: track!(parse_call_args(parser, start))?,
             })
         }
     }
@@ -324,9 +569,26 @@ impl<T: PositionRange, A> PositionRange for Call<T, A> {
         self.args.end_position()
     }
 }
+impl<T: CollectTokens, A: CollectTokens> CollectTokens for Call<T, A> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.module.collect_tokens(tokens);
+        self.name.collect_tokens(tokens);
+        self.args.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable, A: Foldable> Foldable for Call<T, A> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Call {
+            module: self.module.fold_with(folder),
+            name: self.name.fold_with(folder),
+            args: self.args.fold_with(folder),
+        }
+    }
+}
 
 /// `(` `T` `)`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parenthesized<T> {
     pub _open: SymbolToken,
     pub item: T,
@@ -349,9 +611,26 @@ impl<T> PositionRange for Parenthesized<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Parenthesized<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.item.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Parenthesized<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Parenthesized {
+            _open: self._open.fold_with(folder),
+            item: self.item.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `AtomToken` | `VariableToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AtomOrVariable {
     Atom(AtomToken),
     Variable(VariableToken),
@@ -393,9 +672,89 @@ impl PositionRange for AtomOrVariable {
         }
     }
 }
+impl CollectTokens for AtomOrVariable {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            AtomOrVariable::Atom(ref x) => x.collect_tokens(tokens),
+            AtomOrVariable::Variable(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for AtomOrVariable {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            AtomOrVariable::Atom(x) => AtomOrVariable::Atom(x.fold_with(folder)),
+            AtomOrVariable::Variable(x) => AtomOrVariable::Variable(x.fold_with(folder)),
+        }
+    }
+}
+
+/// `AtomToken` | `MacroCall`
+///
+/// Macro calls (e.g., `#?MODULE{}`) are accepted only in the no-preprocess mode
+/// (see `ParserBuilder::no_preprocess`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordName {
+    Atom(AtomToken),
+    MacroCall(Box<MacroCall>),
+}
+impl RecordName {
+    /// Returns the atom of this name (`None` if it is an unexpanded macro call).
+    pub fn as_atom(&self) -> Option<&AtomToken> {
+        match *self {
+            RecordName::Atom(ref t) => Some(t),
+            RecordName::MacroCall(_) => None,
+        }
+    }
+}
+impl Parse for RecordName {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let is_macro_call = parser.is_no_preprocess()
+            && parser
+                .peek(|parser| parser.expect::<SymbolToken>(&Symbol::Question))
+                .is_ok();
+        if is_macro_call {
+            Ok(RecordName::MacroCall(track!(parser.parse())?))
+        } else {
+            Ok(RecordName::Atom(track!(parser.parse())?))
+        }
+    }
+}
+impl PositionRange for RecordName {
+    fn start_position(&self) -> Position {
+        match *self {
+            RecordName::Atom(ref t) => t.start_position(),
+            RecordName::MacroCall(ref t) => t.start_position(),
+        }
+    }
+    fn end_position(&self) -> Position {
+        match *self {
+            RecordName::Atom(ref t) => t.end_position(),
+            RecordName::MacroCall(ref t) => t.end_position(),
+        }
+    }
+}
+impl CollectTokens for RecordName {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            RecordName::Atom(ref x) => x.collect_tokens(tokens),
+            RecordName::MacroCall(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for RecordName {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            RecordName::Atom(x) => RecordName::Atom(x.fold_with(folder)),
+            RecordName::MacroCall(x) => RecordName::MacroCall(x.fold_with(folder)),
+        }
+    }
+}
 
 /// `IntegerToken` | `VariableToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IntegerOrVariable {
     Integer(IntegerToken),
     Variable(VariableToken),
@@ -431,3 +790,26 @@ impl PositionRange for IntegerOrVariable {
         }
     }
 }
+impl CollectTokens for IntegerOrVariable {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            IntegerOrVariable::Integer(ref x) => x.collect_tokens(tokens),
+            IntegerOrVariable::Variable(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for IntegerOrVariable {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            IntegerOrVariable::Integer(x) => IntegerOrVariable::Integer(x.fold_with(folder)),
+            IntegerOrVariable::Variable(x) => IntegerOrVariable::Variable(x.fold_with(folder)),
+        }
+    }
+}
+
+fn parse_call_args<T: TokenRead, A: Parse>(
+    parser: &mut Parser<T>,
+    start: Position,
+) -> Result<Args<A>> {
+    track!(parser.with_context_at(Message::new("call_context"), start, Parser::parse))
+}
@@ -5,13 +5,16 @@ use std::marker::PhantomData;
 
 use super::iterators::{ConsCellIter, SequenceIter};
 use super::AtomOrVariable;
-use crate::traits::{Delimiter, Parse, TokenRead};
+use crate::cst::{Expr, Pattern, Type};
+use crate::messages::Message;
+use crate::traits::{CollectTokens, Delimiter, Fold, Foldable, Parse, TokenRead};
 use crate::{ErrorKind, Parser, Result};
 
 pub type Clauses<T> = Sequence<T, Semicolon>;
 
 /// `T` `Option<BitsElemSize<T>>` `Option<BitsElemSpecs>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitsElem<T> {
     pub element: T,
     pub size: Option<BitsElemSize<T>>,
@@ -38,9 +41,26 @@ impl<T: PositionRange> PositionRange for BitsElem<T> {
             .unwrap_or_else(|| self.element.end_position())
     }
 }
+impl<T: CollectTokens> CollectTokens for BitsElem<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.element.collect_tokens(tokens);
+        self.size.collect_tokens(tokens);
+        self.type_specs.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for BitsElem<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BitsElem {
+            element: self.element.fold_with(folder),
+            size: self.size.fold_with(folder),
+            type_specs: self.type_specs.fold_with(folder),
+        }
+    }
+}
 
 /// `:` `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitsElemSize<T> {
     pub _colon: SymbolToken,
     pub size: T,
@@ -61,9 +81,24 @@ impl<T: PositionRange> PositionRange for BitsElemSize<T> {
         self.size.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for BitsElemSize<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._colon.collect_tokens(tokens);
+        self.size.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for BitsElemSize<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BitsElemSize {
+            _colon: self._colon.fold_with(folder),
+            size: self.size.fold_with(folder),
+        }
+    }
+}
 
 /// `/` `Sequence<BitsElemSpec, Hyphen>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitsElemSpecs {
     pub _slash: SymbolToken,
     pub specs: Sequence<BitsElemSpec, Hyphen>,
@@ -84,9 +119,24 @@ impl PositionRange for BitsElemSpecs {
         self.specs.end_position()
     }
 }
+impl CollectTokens for BitsElemSpecs {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._slash.collect_tokens(tokens);
+        self.specs.collect_tokens(tokens);
+    }
+}
+impl Foldable for BitsElemSpecs {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BitsElemSpecs {
+            _slash: self._slash.fold_with(folder),
+            specs: self.specs.fold_with(folder),
+        }
+    }
+}
 
 /// `AtomToken` | (`unit` `:` `IntegerToken`)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitsElemSpec {
     Type(AtomToken),
     Unit {
@@ -123,16 +173,68 @@ impl PositionRange for BitsElemSpec {
         }
     }
 }
+impl CollectTokens for BitsElemSpec {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            BitsElemSpec::Type(ref x) => x.collect_tokens(tokens),
+            BitsElemSpec::Unit {
+                ref _unit,
+                ref _colon,
+                ref unit,
+            } => {
+                _unit.collect_tokens(tokens);
+                _colon.collect_tokens(tokens);
+                unit.collect_tokens(tokens);
+            }
+        }
+    }
+}
+impl Foldable for BitsElemSpec {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            BitsElemSpec::Type(x) => BitsElemSpec::Type(x.fold_with(folder)),
+            BitsElemSpec::Unit {
+                _unit,
+                _colon,
+                unit,
+            } => BitsElemSpec::Unit {
+                _unit: _unit.fold_with(folder),
+                _colon: _colon.fold_with(folder),
+                unit: unit.fold_with(folder),
+            },
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConsCell<T> {
     pub item: T,
     pub tail: Option<ConsCellTail<T>>,
 }
 impl<T> ConsCell<T> {
-    pub fn iter(&self) -> ConsCellIter<T> {
+    pub fn iter(&self) -> ConsCellIter<'_, T> {
         ConsCellIter::new(self)
     }
+
+    /// Makes a cons cell from `item`, the following `(comma, item)` pairs and the improper tail `(bar, item)`.
+    ///
+    /// Unlike the parsed ones, the nested tails are built without recursion.
+    pub(crate) fn from_items(
+        item: T,
+        rest: Vec<(SymbolToken, T)>,
+        improper: Option<(SymbolToken, T)>,
+    ) -> Self {
+        let mut tail = improper.map(|(_bar, item)| ConsCellTail::Improper { _bar, item });
+        for (_comma, item) in rest.into_iter().rev() {
+            tail = Some(ConsCellTail::Proper {
+                _comma,
+                item,
+                tail: tail.map(Box::new),
+            });
+        }
+        ConsCell { item, tail }
+    }
 }
 impl<T: Parse> Parse for ConsCell<T> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
@@ -153,8 +255,23 @@ impl<T: PositionRange> PositionRange for ConsCell<T> {
             .unwrap_or_else(|| self.item.end_position())
     }
 }
+impl<T: CollectTokens> CollectTokens for ConsCell<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.item.collect_tokens(tokens);
+        self.tail.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for ConsCell<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ConsCell {
+            item: self.item.fold_with(folder),
+            tail: self.tail.fold_with(folder),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConsCellTail<T> {
     Proper {
         _comma: SymbolToken,
@@ -168,12 +285,36 @@ pub enum ConsCellTail<T> {
 }
 impl<T: Parse> Parse for ConsCellTail<T> {
     fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
+        // The cells are parsed in the loop (instead of recursively) to handle long lists
+        let mut cells = vec![track!(Self::parse_cell(parser))?];
+        while let Some(&ConsCellTail::Proper { .. }) = cells.last() {
+            match parser.transaction(Self::parse_cell) {
+                Ok(cell) => cells.push(cell),
+                Err(_) => break,
+            }
+        }
+        let mut tail = cells.pop().expect("Never fails");
+        while let Some(mut cell) = cells.pop() {
+            if let ConsCellTail::Proper {
+                tail: ref mut t, ..
+            } = cell
+            {
+                *t = Some(Box::new(tail));
+            }
+            tail = cell;
+        }
+        Ok(tail)
+    }
+}
+impl<T: Parse> ConsCellTail<T> {
+    /// Parses `,` `T` (without the following cells) or `|` `T`.
+    fn parse_cell<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
         let symbol: SymbolToken = track!(parser.parse())?;
         match symbol.value() {
             Symbol::Comma => Ok(ConsCellTail::Proper {
                 _comma: symbol,
                 item: track!(parser.parse())?,
-                tail: track!(parser.parse())?,
+                tail: None,
             }),
             Symbol::VerticalBar => Ok(ConsCellTail::Improper {
                 _bar: symbol,
@@ -204,8 +345,43 @@ impl<T: PositionRange> PositionRange for ConsCellTail<T> {
         }
     }
 }
+impl<T: CollectTokens> CollectTokens for ConsCellTail<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            ConsCellTail::Proper {
+                ref _comma,
+                ref item,
+                ref tail,
+            } => {
+                _comma.collect_tokens(tokens);
+                item.collect_tokens(tokens);
+                tail.collect_tokens(tokens);
+            }
+            ConsCellTail::Improper { ref _bar, ref item } => {
+                _bar.collect_tokens(tokens);
+                item.collect_tokens(tokens);
+            }
+        }
+    }
+}
+impl<T: Foldable> Foldable for ConsCellTail<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            ConsCellTail::Proper { _comma, item, tail } => ConsCellTail::Proper {
+                _comma: _comma.fold_with(folder),
+                item: item.fold_with(folder),
+                tail: tail.fold_with(folder),
+            },
+            ConsCellTail::Improper { _bar, item } => ConsCellTail::Improper {
+                _bar: _bar.fold_with(folder),
+                item: item.fold_with(folder),
+            },
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     Plus(SymbolToken),
     Minus(SymbolToken),
@@ -218,6 +394,7 @@ pub enum BinaryOp {
     Band(KeywordToken),
     Bsl(KeywordToken),
     Bsr(KeywordToken),
+    And(KeywordToken),
     Or(KeywordToken),
     Xor(KeywordToken),
     PlusPlus(SymbolToken),
@@ -263,6 +440,7 @@ impl BinaryOp {
                 Keyword::Band => Ok(BinaryOp::Band(k)),
                 Keyword::Bsl => Ok(BinaryOp::Bsl(k)),
                 Keyword::Bsr => Ok(BinaryOp::Bsr(k)),
+                Keyword::And => Ok(BinaryOp::And(k)),
                 Keyword::Or => Ok(BinaryOp::Or(k)),
                 Keyword::Xor => Ok(BinaryOp::Xor(k)),
                 Keyword::Andalso => Ok(BinaryOp::Andalso(k)),
@@ -272,6 +450,57 @@ impl BinaryOp {
             _ => Err(token),
         }
     }
+
+    /// Returns the precedence of this operator.
+    ///
+    /// Operators with greater values bind more tightly (see `erl_parse.yrl` in OTP).
+    pub fn precedence(&self) -> u8 {
+        match *self {
+            BinaryOp::Send(_) => 1,
+            BinaryOp::Orelse(_) => 2,
+            BinaryOp::Andalso(_) => 3,
+            BinaryOp::Eq(_)
+            | BinaryOp::ExactEq(_)
+            | BinaryOp::NotEq(_)
+            | BinaryOp::ExactNotEq(_)
+            | BinaryOp::Less(_)
+            | BinaryOp::LessEq(_)
+            | BinaryOp::Greater(_)
+            | BinaryOp::GreaterEq(_) => 4,
+            BinaryOp::PlusPlus(_) | BinaryOp::MinusMinus(_) => 5,
+            BinaryOp::Plus(_)
+            | BinaryOp::Minus(_)
+            | BinaryOp::Bor(_)
+            | BinaryOp::Bxor(_)
+            | BinaryOp::Bsl(_)
+            | BinaryOp::Bsr(_)
+            | BinaryOp::Or(_)
+            | BinaryOp::Xor(_) => 6,
+            BinaryOp::Mul(_)
+            | BinaryOp::FloatDiv(_)
+            | BinaryOp::IntDiv(_)
+            | BinaryOp::Rem(_)
+            | BinaryOp::Band(_)
+            | BinaryOp::And(_) => 7,
+        }
+    }
+
+    /// Returns `true` if this operator is right associative.
+    pub fn is_right_associative(&self) -> bool {
+        matches!(
+            *self,
+            BinaryOp::Send(_)
+                | BinaryOp::Orelse(_)
+                | BinaryOp::Andalso(_)
+                | BinaryOp::PlusPlus(_)
+                | BinaryOp::MinusMinus(_)
+        )
+    }
+
+    /// Returns `true` if this operator is non-associative (i.e., a comparison operator).
+    pub fn is_non_associative(&self) -> bool {
+        self.precedence() == 4
+    }
 }
 impl Parse for BinaryOp {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
@@ -292,6 +521,7 @@ impl PositionRange for BinaryOp {
             | BinaryOp::Band(ref t)
             | BinaryOp::Bsl(ref t)
             | BinaryOp::Bsr(ref t)
+            | BinaryOp::And(ref t)
             | BinaryOp::Or(ref t)
             | BinaryOp::Xor(ref t)
             | BinaryOp::Andalso(ref t)
@@ -322,6 +552,7 @@ impl PositionRange for BinaryOp {
             | BinaryOp::Band(ref t)
             | BinaryOp::Bsl(ref t)
             | BinaryOp::Bsr(ref t)
+            | BinaryOp::And(ref t)
             | BinaryOp::Or(ref t)
             | BinaryOp::Xor(ref t)
             | BinaryOp::Andalso(ref t)
@@ -344,8 +575,75 @@ impl PositionRange for BinaryOp {
         }
     }
 }
+impl CollectTokens for BinaryOp {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            BinaryOp::Plus(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Minus(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Mul(ref x) => x.collect_tokens(tokens),
+            BinaryOp::FloatDiv(ref x) => x.collect_tokens(tokens),
+            BinaryOp::IntDiv(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Rem(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Bor(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Bxor(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Band(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Bsl(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Bsr(ref x) => x.collect_tokens(tokens),
+            BinaryOp::And(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Or(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Xor(ref x) => x.collect_tokens(tokens),
+            BinaryOp::PlusPlus(ref x) => x.collect_tokens(tokens),
+            BinaryOp::MinusMinus(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Eq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::ExactEq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::NotEq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::ExactNotEq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Less(ref x) => x.collect_tokens(tokens),
+            BinaryOp::LessEq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Greater(ref x) => x.collect_tokens(tokens),
+            BinaryOp::GreaterEq(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Andalso(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Orelse(ref x) => x.collect_tokens(tokens),
+            BinaryOp::Send(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for BinaryOp {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            BinaryOp::Plus(x) => BinaryOp::Plus(x.fold_with(folder)),
+            BinaryOp::Minus(x) => BinaryOp::Minus(x.fold_with(folder)),
+            BinaryOp::Mul(x) => BinaryOp::Mul(x.fold_with(folder)),
+            BinaryOp::FloatDiv(x) => BinaryOp::FloatDiv(x.fold_with(folder)),
+            BinaryOp::IntDiv(x) => BinaryOp::IntDiv(x.fold_with(folder)),
+            BinaryOp::Rem(x) => BinaryOp::Rem(x.fold_with(folder)),
+            BinaryOp::Bor(x) => BinaryOp::Bor(x.fold_with(folder)),
+            BinaryOp::Bxor(x) => BinaryOp::Bxor(x.fold_with(folder)),
+            BinaryOp::Band(x) => BinaryOp::Band(x.fold_with(folder)),
+            BinaryOp::Bsl(x) => BinaryOp::Bsl(x.fold_with(folder)),
+            BinaryOp::Bsr(x) => BinaryOp::Bsr(x.fold_with(folder)),
+            BinaryOp::And(x) => BinaryOp::And(x.fold_with(folder)),
+            BinaryOp::Or(x) => BinaryOp::Or(x.fold_with(folder)),
+            BinaryOp::Xor(x) => BinaryOp::Xor(x.fold_with(folder)),
+            BinaryOp::PlusPlus(x) => BinaryOp::PlusPlus(x.fold_with(folder)),
+            BinaryOp::MinusMinus(x) => BinaryOp::MinusMinus(x.fold_with(folder)),
+            BinaryOp::Eq(x) => BinaryOp::Eq(x.fold_with(folder)),
+            BinaryOp::ExactEq(x) => BinaryOp::ExactEq(x.fold_with(folder)),
+            BinaryOp::NotEq(x) => BinaryOp::NotEq(x.fold_with(folder)),
+            BinaryOp::ExactNotEq(x) => BinaryOp::ExactNotEq(x.fold_with(folder)),
+            BinaryOp::Less(x) => BinaryOp::Less(x.fold_with(folder)),
+            BinaryOp::LessEq(x) => BinaryOp::LessEq(x.fold_with(folder)),
+            BinaryOp::Greater(x) => BinaryOp::Greater(x.fold_with(folder)),
+            BinaryOp::GreaterEq(x) => BinaryOp::GreaterEq(x.fold_with(folder)),
+            BinaryOp::Andalso(x) => BinaryOp::Andalso(x.fold_with(folder)),
+            BinaryOp::Orelse(x) => BinaryOp::Orelse(x.fold_with(folder)),
+            BinaryOp::Send(x) => BinaryOp::Send(x.fold_with(folder)),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     Plus(SymbolToken),
     Minus(SymbolToken),
@@ -392,22 +690,62 @@ impl PositionRange for UnaryOp {
         }
     }
 }
+impl CollectTokens for UnaryOp {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            UnaryOp::Plus(ref x) => x.collect_tokens(tokens),
+            UnaryOp::Minus(ref x) => x.collect_tokens(tokens),
+            UnaryOp::Not(ref x) => x.collect_tokens(tokens),
+            UnaryOp::Bnot(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for UnaryOp {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            UnaryOp::Plus(x) => UnaryOp::Plus(x.fold_with(folder)),
+            UnaryOp::Minus(x) => UnaryOp::Minus(x.fold_with(folder)),
+            UnaryOp::Not(x) => UnaryOp::Not(x.fold_with(folder)),
+            UnaryOp::Bnot(x) => UnaryOp::Bnot(x.fold_with(folder)),
+        }
+    }
+}
 
 /// `(` `Option<Sequence<T>>` `)`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Args<T> {
     pub _open: SymbolToken,
     pub args: Option<Sequence<T>>,
     pub _close: SymbolToken,
 }
+impl<T> Args<T> {
+    /// Returns the number of the arguments.
+    pub fn len(&self) -> usize {
+        self.args.as_ref().map_or(0, |a| a.iter().count())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.args.is_none()
+    }
+
+    /// Returns an iterator over the arguments.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.args.iter().flat_map(|s| s.iter())
+    }
+}
 impl<T: Parse> Parse for Args<T> {
-    fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self>
-    where
-        U: TokenRead,
-    {
+    fn parse<U: TokenRead>(parser: &mut Parser<U>) -> Result<Self> {
         Ok(Args {
             _open: track!(parser.expect(&Symbol::OpenParen))?,
-            args: track!(parser.parse())?,
+            args: parser
+                .transaction(|parser| {
+                    Sequence::parse_with_context(parser, &|i| {
+                        Message::new("argument_context")
+                            .arg("ordinal", ordinal(i + 1))
+                            .arg("index", (i + 1).to_string())
+                    })
+                })
+                .ok(),
             _close: track!(parser.expect(&Symbol::CloseParen))?,
         })
     }
@@ -420,9 +758,26 @@ impl<T> PositionRange for Args<T> {
         self._close.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for Args<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.args.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for Args<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Args {
+            _open: self._open.fold_with(folder),
+            args: self.args.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `T` `:`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModulePrefix<T> {
     pub name: T,
     pub _colon: SymbolToken,
@@ -443,9 +798,24 @@ impl<T: PositionRange> PositionRange for ModulePrefix<T> {
         self._colon.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for ModulePrefix<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.name.collect_tokens(tokens);
+        self._colon.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for ModulePrefix<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ModulePrefix {
+            name: self.name.fold_with(folder),
+            _colon: self._colon.fold_with(folder),
+        }
+    }
+}
 
 /// `T` (`:=`|`=>`) `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MapField<T> {
     pub key: T,
     pub _relation: SymbolToken,
@@ -468,9 +838,26 @@ impl<T: PositionRange> PositionRange for MapField<T> {
         self.value.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for MapField<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.key.collect_tokens(tokens);
+        self._relation.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for MapField<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MapField {
+            key: self.key.fold_with(folder),
+            _relation: self._relation.fold_with(folder),
+            value: self.value.fold_with(folder),
+        }
+    }
+}
 
 /// `AtomOrVariable` `=` `T`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordField<T> {
     pub key: AtomOrVariable,
     pub _bind: SymbolToken,
@@ -493,9 +880,26 @@ impl<T: PositionRange> PositionRange for RecordField<T> {
         self.value.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for RecordField<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.key.collect_tokens(tokens);
+        self._bind.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for RecordField<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordField {
+            key: self.key.fold_with(folder),
+            _bind: self._bind.fold_with(folder),
+            value: self.value.fold_with(folder),
+        }
+    }
+}
 
 /// `AtomToken` `/` `IntegerToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NameAndArity<N = AtomToken, A = IntegerToken> {
     pub name: N,
     pub _slash: SymbolToken,
@@ -518,17 +922,48 @@ impl<N: PositionRange, A: PositionRange> PositionRange for NameAndArity<N, A> {
         self.arity.end_position()
     }
 }
+impl<N: CollectTokens, A: CollectTokens> CollectTokens for NameAndArity<N, A> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.name.collect_tokens(tokens);
+        self._slash.collect_tokens(tokens);
+        self.arity.collect_tokens(tokens);
+    }
+}
+impl<N: Foldable, A: Foldable> Foldable for NameAndArity<N, A> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        NameAndArity {
+            name: self.name.fold_with(folder),
+            _slash: self._slash.fold_with(folder),
+            arity: self.arity.fold_with(folder),
+        }
+    }
+}
 
 /// `item` `Option<SequenceTail<T>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sequence<T, D = Comma> {
     pub item: T,
     pub tail: Option<SequenceTail<T, D>>,
 }
 impl<T, D> Sequence<T, D> {
-    pub fn iter(&self) -> SequenceIter<T, D> {
+    pub fn iter(&self) -> SequenceIter<'_, T, D> {
         SequenceIter::new(self)
     }
+
+    /// Makes a sequence from `item` and the following `(delimiter, item)` pairs.
+    pub(crate) fn from_items(item: T, rest: Vec<(SymbolToken, T)>) -> Self {
+        let mut tail = None;
+        for (_comma, item) in rest.into_iter().rev() {
+            tail = Some(SequenceTail {
+                _comma,
+                item,
+                tail: tail.map(Box::new),
+                _phantom: PhantomData,
+            });
+        }
+        Sequence { item, tail }
+    }
 }
 impl<T: Parse, D: Delimiter> Parse for Sequence<T, D> {
     fn parse<U>(parser: &mut Parser<U>) -> Result<Self>
@@ -541,6 +976,21 @@ impl<T: Parse, D: Delimiter> Parse for Sequence<T, D> {
         })
     }
 }
+impl<T: Parse, D: Delimiter> Sequence<T, D> {
+    /// Parses a sequence in which the `i`-th (0-origin) item is parsed in the context `describe(i)`.
+    pub(crate) fn parse_with_context<U, F>(parser: &mut Parser<U>, describe: &F) -> Result<Self>
+    where
+        U: TokenRead,
+        F: Fn(usize) -> Message,
+    {
+        Ok(Sequence {
+            item: track!(parser.with_context(describe(0), Parser::parse))?,
+            tail: parser
+                .transaction(|parser| SequenceTail::parse_with_context(parser, 1, describe))
+                .ok(),
+        })
+    }
+}
 impl<T: PositionRange, D> PositionRange for Sequence<T, D> {
     fn start_position(&self) -> Position {
         self.item.start_position()
@@ -552,9 +1002,24 @@ impl<T: PositionRange, D> PositionRange for Sequence<T, D> {
             .unwrap_or_else(|| self.item.end_position())
     }
 }
+impl<T: CollectTokens, D> CollectTokens for Sequence<T, D> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.item.collect_tokens(tokens);
+        self.tail.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable, D> Foldable for Sequence<T, D> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Sequence {
+            item: self.item.fold_with(folder),
+            tail: self.tail.fold_with(folder),
+        }
+    }
+}
 
 /// `,` `item` `Option<SequenceTail<T>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SequenceTail<T, D> {
     pub _comma: SymbolToken,
     pub item: T,
@@ -566,12 +1031,49 @@ impl<T: Parse, D: Delimiter> Parse for SequenceTail<T, D> {
     where
         U: TokenRead,
     {
-        Ok(SequenceTail {
-            _comma: track!(parser.expect(&D::delimiter()))?,
-            item: track!(parser.parse())?,
-            tail: track!(parser.parse())?,
-            _phantom: PhantomData,
-        })
+        track!(Self::parse_items(parser, |parser, _| parser.parse()))
+    }
+}
+impl<T: Parse, D: Delimiter> SequenceTail<T, D> {
+    fn parse_with_context<U, F>(parser: &mut Parser<U>, index: usize, describe: &F) -> Result<Self>
+    where
+        U: TokenRead,
+        F: Fn(usize) -> Message,
+    {
+        track!(Self::parse_items(parser, |parser, i| {
+            parser.with_context(describe(index + i), Parser::parse)
+        }))
+    }
+
+    /// Parses the items (the `i`-th one by `parse_item(parser, i)`) in the loop
+    /// (instead of recursively) to handle long sequences.
+    fn parse_items<U, F>(parser: &mut Parser<U>, parse_item: F) -> Result<Self>
+    where
+        U: TokenRead,
+        F: Fn(&mut Parser<U>, usize) -> Result<T>,
+    {
+        let parse_one = |parser: &mut Parser<U>, i| -> Result<(SymbolToken, T)> {
+            let delimiter = track!(parser.expect(&D::delimiter()))?;
+            Ok((delimiter, track!(parse_item(parser, i))?))
+        };
+        let mut items = vec![track!(parse_one(parser, 0))?];
+        loop {
+            let i = items.len();
+            match parser.transaction(|parser| parse_one(parser, i)) {
+                Ok(x) => items.push(x),
+                Err(_) => break,
+            }
+        }
+        let mut tail = None;
+        while let Some((_comma, item)) = items.pop() {
+            tail = Some(Box::new(SequenceTail {
+                _comma,
+                item,
+                tail,
+                _phantom: PhantomData,
+            }));
+        }
+        Ok(*tail.expect("Never fails"))
     }
 }
 impl<T: PositionRange, D> PositionRange for SequenceTail<T, D> {
@@ -585,8 +1087,77 @@ impl<T: PositionRange, D> PositionRange for SequenceTail<T, D> {
             .unwrap_or_else(|| self.item.end_position())
     }
 }
+impl<T: CollectTokens, D> CollectTokens for SequenceTail<T, D> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._comma.collect_tokens(tokens);
+        self.item.collect_tokens(tokens);
+        self.tail.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable, D> Foldable for SequenceTail<T, D> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        SequenceTail {
+            _comma: self._comma.fold_with(folder),
+            item: self.item.fold_with(folder),
+            tail: self.tail.fold_with(folder),
+            _phantom: self._phantom,
+        }
+    }
+}
 
+/// Part of a construct read by an `extension::SyntaxExtension`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExtensionPart {
+    /// Consecutive tokens read by `extension::ExtensionInput::read_token`.
+    Tokens(
+        #[cfg_attr(feature = "serde", serde(with = "crate::cst::serde_tokens"))] Vec<LexicalToken>,
+    ),
+    Expr(Expr),
+    Pattern(Pattern),
+    Type(Type),
+}
+impl PositionRange for ExtensionPart {
+    fn start_position(&self) -> Position {
+        match *self {
+            ExtensionPart::Tokens(ref x) => x[0].start_position(),
+            ExtensionPart::Expr(ref x) => x.start_position(),
+            ExtensionPart::Pattern(ref x) => x.start_position(),
+            ExtensionPart::Type(ref x) => x.start_position(),
+        }
+    }
+    fn end_position(&self) -> Position {
+        match *self {
+            ExtensionPart::Tokens(ref x) => x[x.len() - 1].end_position(),
+            ExtensionPart::Expr(ref x) => x.end_position(),
+            ExtensionPart::Pattern(ref x) => x.end_position(),
+            ExtensionPart::Type(ref x) => x.end_position(),
+        }
+    }
+}
+impl CollectTokens for ExtensionPart {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            ExtensionPart::Tokens(ref x) => x.collect_tokens(tokens),
+            ExtensionPart::Expr(ref x) => x.collect_tokens(tokens),
+            ExtensionPart::Pattern(ref x) => x.collect_tokens(tokens),
+            ExtensionPart::Type(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for ExtensionPart {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            ExtensionPart::Tokens(x) => ExtensionPart::Tokens(x.fold_with(folder)),
+            ExtensionPart::Expr(x) => ExtensionPart::Expr(x.fold_with(folder)),
+            ExtensionPart::Pattern(x) => ExtensionPart::Pattern(x.fold_with(folder)),
+            ExtensionPart::Type(x) => ExtensionPart::Type(x.fold_with(folder)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Comma;
 impl Delimiter for Comma {
     fn delimiter() -> Symbol {
@@ -595,6 +1166,7 @@ impl Delimiter for Comma {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hyphen;
 impl Delimiter for Hyphen {
     fn delimiter() -> Symbol {
@@ -603,9 +1175,22 @@ impl Delimiter for Hyphen {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Semicolon;
 impl Delimiter for Semicolon {
     fn delimiter() -> Symbol {
         Symbol::Semicolon
     }
 }
+
+/// Makes an English ordinal number (e.g., "1st", "12th", "22nd").
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
@@ -0,0 +1,262 @@
+use erl_tokenize::{LexicalToken, PositionRange};
+
+use crate::cst::{Form, ModuleDecl, Node, NodeKind};
+use crate::traits::CollectTokens;
+use crate::Span;
+
+/// Kind of a `Change`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The node exists only in the new tree.
+    Added,
+
+    /// The node exists only in the old tree.
+    Removed,
+
+    /// The node exists in both trees, but its tokens differ.
+    Modified,
+}
+
+/// Difference between two CSTs reported by `diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub node_kind: NodeKind,
+
+    /// The span of the node in the old tree (`None` if the node is added).
+    pub old: Option<Span>,
+
+    /// The span of the node in the new tree (`None` if the node is removed).
+    pub new: Option<Span>,
+}
+
+/// Compares two modules modulo whitespaces, comments and token positions.
+///
+/// Nodes are compared by the texts of their tokens.
+/// Unchanged forms are matched in order, and the others are paired by their names
+/// (e.g., a function declaration with the one of the same name and arity).
+/// For each pair of differing nodes, the smallest differing descendants are reported:
+/// if two nodes are the same except for their children, the children are compared,
+/// and if they differ only in the number of their children (e.g., an expression is added to a body),
+/// the added and removed children are reported.
+/// Otherwise (e.g., an operator is replaced), the nodes are reported as modified.
+///
+/// The changes are reported in the source order.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::{diff, ChangeKind, ModuleDecl};
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_tokenize::Lexer;
+///
+/// let parse = |text| -> ModuleDecl {
+///     Parser::new(TokenReader::new(Lexer::new(text))).parse().unwrap()
+/// };
+/// let old_text = "-module(foo).\nf(X) -> X + 1.\ng() -> ok.\n";
+/// let new_text = "-module(foo).\n\n%% Comment\nf(X) ->\n    X + 2.\nh() -> ok.\n";
+///
+/// let changes: Vec<_> = diff(&parse(old_text), &parse(new_text))
+///     .into_iter()
+///     .map(|c| {
+///         let old = c.old.map(|s| s.text(old_text).unwrap());
+///         let new = c.new.map(|s| s.text(new_text).unwrap());
+///         (c.kind, old, new)
+///     })
+///     .collect();
+/// assert_eq!(
+///     changes,
+///     [
+///         (ChangeKind::Modified, Some("1"), Some("2")),
+///         (ChangeKind::Removed, Some("g() -> ok."), None),
+///         (ChangeKind::Added, None, Some("h() -> ok.")),
+///     ]
+/// );
+/// ```
+pub fn diff(old: &ModuleDecl, new: &ModuleDecl) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_nodes(
+        Node::forms(old).collect(),
+        Node::forms(new).collect(),
+        &mut changes,
+    );
+    changes
+}
+
+/// Compares the sequences of nodes.
+fn diff_nodes(old: Vec<Node>, new: Vec<Node>, changes: &mut Vec<Change>) {
+    let old: Vec<_> = old.into_iter().map(|n| (n, n.tokens())).collect();
+    let new: Vec<_> = new.into_iter().map(|n| (n, n.tokens())).collect();
+    let same = |a: &(Node, Vec<LexicalToken>), b: &(Node, Vec<LexicalToken>)| {
+        a.0.kind() == b.0.kind() && same_texts(&a.1, &b.1)
+    };
+
+    // Longest common subsequence
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut gap_i, mut gap_j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same(&old[i], &new[j]) {
+            diff_gap(&old[gap_i..i], &new[gap_j..j], changes);
+            i += 1;
+            j += 1;
+            gap_i = i;
+            gap_j = j;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    diff_gap(&old[gap_i..], &new[gap_j..], changes);
+}
+
+/// Compares the nodes between the unchanged ones.
+fn diff_gap(
+    old: &[(Node, Vec<LexicalToken>)],
+    new: &[(Node, Vec<LexicalToken>)],
+    changes: &mut Vec<Change>,
+) {
+    let (mut i, mut j) = (0, 0);
+    for (k, o) in old.iter().enumerate() {
+        let key = pairing_key(&o.0);
+        let paired = new[j..].iter().position(|n| pairing_key(&n.0) == key);
+        if let Some(l) = paired.map(|l| j + l) {
+            changes.extend(old[i..k].iter().map(|o| removed(&o.0)));
+            changes.extend(new[j..l].iter().map(|n| added(&n.0)));
+            diff_node(o, &new[l], changes);
+            i = k + 1;
+            j = l + 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|o| removed(&o.0)));
+    changes.extend(new[j..].iter().map(|n| added(&n.0)));
+}
+
+/// Compares the paired nodes.
+fn diff_node(
+    old: &(Node, Vec<LexicalToken>),
+    new: &(Node, Vec<LexicalToken>),
+    changes: &mut Vec<Change>,
+) {
+    if same_texts(&old.1, &new.1) {
+        return;
+    }
+    let old_children = old.0.children();
+    let new_children = new.0.children();
+    let old_skeleton = skeleton(&old.1, &old_children);
+    let new_skeleton = skeleton(&new.1, &new_children);
+    if old_skeleton == new_skeleton && old_children.len() == new_children.len() {
+        for (o, n) in old_children.into_iter().zip(new_children) {
+            diff_node(&(o, o.tokens()), &(n, n.tokens()), changes);
+        }
+    } else if strip_children(&old_skeleton) == strip_children(&new_skeleton)
+        && !(old_children.is_empty() && new_children.is_empty())
+    {
+        diff_nodes(old_children, new_children, changes);
+    } else {
+        changes.push(Change {
+            kind: ChangeKind::Modified,
+            node_kind: old.0.kind(),
+            old: Some(Span::of(&old.0)),
+            new: Some(Span::of(&new.0)),
+        });
+    }
+}
+
+/// Returns the key by which nodes which are not the same can be paired.
+fn pairing_key(node: &Node) -> (NodeKind, Option<(String, String, usize)>) {
+    let key = match *node {
+        Node::Form(form) => Some(match *form {
+            Form::FunDecl(ref x) => ("fun".to_owned(), x.name().to_owned(), x.arity()),
+            Form::FunSpec(ref x) => ("spec".to_owned(), x.fun_name.value().to_owned(), x.arity()),
+            Form::CallbackSpec(ref x) => (
+                "callback".to_owned(),
+                x.callback_name.value().to_owned(),
+                x.arity(),
+            ),
+            Form::RecordDecl(ref x) => ("record".to_owned(), x.name().to_owned(), 0),
+            Form::TypeDecl(ref x) => (
+                x.type_kind.value().to_owned(),
+                x.name().to_owned(),
+                x.arity(),
+            ),
+            _ => {
+                // Other attributes are paired by their names (e.g., `-export`)
+                let tokens = form.tokens();
+                let name = tokens.get(1).map_or("", |t| t.text());
+                (name.to_owned(), String::new(), 0)
+            }
+        }),
+        _ => None,
+    };
+    (node.kind(), key)
+}
+
+/// Returns the texts of `tokens` except for those of `children`, each of which is replaced by `""`.
+fn skeleton<'a>(tokens: &'a [LexicalToken], children: &[Node]) -> Vec<&'a str> {
+    let mut skeleton = Vec::new();
+    let mut children = children.iter().peekable();
+    let mut in_child = false;
+    for token in tokens {
+        let offset = token.start_position().offset();
+        while children
+            .peek()
+            .is_some_and(|c| c.end_position().offset() <= offset)
+        {
+            children.next();
+            in_child = false;
+        }
+        match children.peek() {
+            Some(c) if c.start_position().offset() <= offset => {
+                if !in_child {
+                    skeleton.push("");
+                    in_child = true;
+                }
+            }
+            _ => skeleton.push(token.text()),
+        }
+    }
+    skeleton
+}
+
+/// Removes the children and their separators from `skeleton`.
+fn strip_children<'a>(skeleton: &[&'a str]) -> Vec<&'a str> {
+    skeleton
+        .iter()
+        .cloned()
+        .filter(|t| !matches!(*t, "" | "," | ";"))
+        .collect()
+}
+
+fn same_texts(a: &[LexicalToken], b: &[LexicalToken]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.text() == b.text())
+}
+
+fn added(node: &Node) -> Change {
+    Change {
+        kind: ChangeKind::Added,
+        node_kind: node.kind(),
+        old: None,
+        new: Some(Span::of(node)),
+    }
+}
+
+fn removed(node: &Node) -> Change {
+    Change {
+        kind: ChangeKind::Removed,
+        node_kind: node.kind(),
+        old: Some(Span::of(node)),
+        new: None,
+    }
+}
@@ -0,0 +1,175 @@
+use erl_tokenize::tokens::VariableToken;
+use erl_tokenize::Position;
+
+use crate::cst::commons::parts::{ConsCell, ConsCellTail, Sequence};
+use crate::cst::{Expr, GuardTest, Pattern, Type};
+use crate::traits::{Fold, Foldable};
+
+/// Drops `node` without recursion.
+///
+/// A CST node is dropped recursively as usual,
+/// so deeply nested trees (e.g., the ones parsed with `Limits::max_depth` raised or removed)
+/// may exhaust the stack when dropped. This function tears such trees down iteratively instead.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::{self, Expr};
+/// use erl_parse::{Limits, ParserBuilder, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = format!("{}1{}", "f(".repeat(1500), ")".repeat(1500));
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new(text.as_str())));
+/// let limits = Limits {
+///     max_depth: None,
+///     ..Limits::new()
+/// };
+/// let mut parser = ParserBuilder::new().limits(limits).build(reader);
+/// let expr: Expr = parser.parse().unwrap();
+/// cst::dispose(expr);
+/// ```
+pub fn dispose<T: Foldable>(node: T) {
+    let mut detach = Detach::new();
+    drop(node.fold_with(&mut detach));
+    while !detach.is_empty() {
+        if let Some(x) = detach.exprs.pop() {
+            match x {
+                Expr::Tuple(x) => detach.push_sequence(x.elements, Detach::push_expr),
+                Expr::List(x) => detach.push_cells(x.elements, Detach::push_expr),
+                x => drop(x.map_children(&mut detach)),
+            }
+        } else if let Some(x) = detach.patterns.pop() {
+            match x {
+                Pattern::Tuple(x) => detach.push_sequence(x.elements, Detach::push_pattern),
+                Pattern::List(x) => detach.push_cells(x.elements, Detach::push_pattern),
+                x => drop(x.map_children(&mut detach)),
+            }
+        } else if let Some(x) = detach.tests.pop() {
+            match x {
+                GuardTest::Tuple(x) => detach.push_sequence(x.elements, Detach::push_test),
+                GuardTest::List(x) => detach.push_cells(x.elements, Detach::push_test),
+                x => drop(x.map_children(&mut detach)),
+            }
+        } else if let Some(x) = detach.types.pop() {
+            match x {
+                Type::Tuple(x) => detach.push_sequence(x.elements, Detach::push_type),
+                x => drop(x.map_children(&mut detach)),
+            }
+        }
+    }
+}
+
+/// Detaches the nested expressions, patterns, guard tests and types from their parents
+/// (replacing them with placeholders) to be dropped later.
+struct Detach {
+    exprs: Vec<Expr>,
+    patterns: Vec<Pattern>,
+    tests: Vec<GuardTest>,
+    types: Vec<Type>,
+    placeholder: VariableToken,
+}
+impl Detach {
+    fn new() -> Self {
+        Detach {
+            exprs: Vec::new(),
+            patterns: Vec::new(),
+            tests: Vec::new(),
+            types: Vec::new(),
+            placeholder: VariableToken::from_value("_", Position::new()).expect("Never fails"),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+            && self.patterns.is_empty()
+            && self.tests.is_empty()
+            && self.types.is_empty()
+    }
+    fn push_expr(&mut self, x: Expr) {
+        self.exprs.push(x);
+    }
+    fn push_pattern(&mut self, x: Pattern) {
+        self.patterns.push(x);
+    }
+    fn push_test(&mut self, x: GuardTest) {
+        self.tests.push(x);
+    }
+    fn push_type(&mut self, x: Type) {
+        self.types.push(x);
+    }
+
+    // The elements of long sequences and lists are unchained here
+    // since `map_children` and `drop` follow the chains recursively.
+    fn push_sequence<T, D, F>(&mut self, seq: Option<Sequence<T, D>>, push: F)
+    where
+        F: Fn(&mut Self, T),
+    {
+        if let Some(seq) = seq {
+            push(self, seq.item);
+            let mut tail = seq.tail;
+            while let Some(x) = tail {
+                push(self, x.item);
+                tail = x.tail.map(|x| *x);
+            }
+        }
+    }
+    fn push_cells<T, F>(&mut self, cell: Option<ConsCell<T>>, push: F)
+    where
+        F: Fn(&mut Self, T),
+    {
+        if let Some(cell) = cell {
+            push(self, cell.item);
+            let mut tail = cell.tail;
+            while let Some(x) = tail {
+                tail = match x {
+                    ConsCellTail::Proper { item, tail, .. } => {
+                        push(self, item);
+                        tail.map(|x| *x)
+                    }
+                    ConsCellTail::Improper { item, .. } => {
+                        push(self, item);
+                        None
+                    }
+                };
+            }
+        }
+    }
+}
+impl Fold for Detach {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Literal(_) | Expr::Variable(_) => expr,
+            _ => {
+                self.exprs.push(expr);
+                Expr::Variable(self.placeholder.clone())
+            }
+        }
+    }
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        match pattern {
+            Pattern::Literal(_) | Pattern::Variable(_) => pattern,
+            _ => {
+                self.patterns.push(pattern);
+                Pattern::Variable(self.placeholder.clone())
+            }
+        }
+    }
+    fn fold_guard_test(&mut self, test: GuardTest) -> GuardTest {
+        match test {
+            GuardTest::Literal(_) | GuardTest::Variable(_) => test,
+            _ => {
+                self.tests.push(test);
+                GuardTest::Variable(self.placeholder.clone())
+            }
+        }
+    }
+    fn fold_type(&mut self, ty: Type) -> Type {
+        match ty {
+            Type::Literal(_) | Type::Variable(_) => ty,
+            _ => {
+                self.types.push(ty);
+                Type::Variable(self.placeholder.clone())
+            }
+        }
+    }
+}
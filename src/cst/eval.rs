@@ -0,0 +1,234 @@
+//! Evaluation of constant expressions.
+//!
+//! This folds literals, tuples, lists, maps, binaries of literals and operator calls over them
+//! into `Term`s without converting the CST to an AST,
+//! so that tools can read the values of `-define`d constants or record defaults.
+//! For the evaluation of side-effect-free functions, see `analysis::Evaluator`.
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::builtin::parse_expr;
+//! use erl_parse::cst::eval::eval;
+//! use erl_parse::term::Term;
+//!
+//! let expr = parse_expr("{timeout, 60 * 1000, \"a\" ++ \"b\", [1 | [2.0 / 4]]}").unwrap();
+//! assert_eq!(eval(&expr).unwrap().to_string(), "{timeout,60000,[97,98],[1,0.5]}");
+//!
+//! assert_eq!(eval(&parse_expr("1 div 0").unwrap()), None);
+//! assert_eq!(eval(&parse_expr("[X, 1]").unwrap()), None);
+//! ```
+use erl_tokenize::values::Symbol;
+use num::{BigInt, ToPrimitive, Zero};
+
+use crate::cst::commons::parts::{BinaryOp, UnaryOp};
+use crate::cst::{Expr, Literal};
+use crate::term::Term;
+
+/// The maximum shift amount of `bsl` and `bsr`, which keeps folded integers reasonably small.
+const MAX_SHIFT: i64 = 1 << 16;
+
+/// Evaluates the constant expression `expr`.
+///
+/// Supported are literals (adjacent strings are concatenated), tuples, lists, maps without bases,
+/// binaries of integer, character and string literals without sizes nor types, parentheses,
+/// the arithmetic and bitwise operators over numbers, and `++` over lists.
+///
+/// Returns `None` if `expr` contains any other construct (e.g., a variable or a function call)
+/// or if the evaluation raises an exception (e.g., `badarith`).
+pub fn eval(expr: &Expr) -> Option<Term> {
+    match *expr {
+        Expr::Literal(ref x) => Some(literal(x)),
+        Expr::Tuple(ref x) => x.iter().map(eval).collect::<Option<_>>().map(Term::Tuple),
+        Expr::List(ref x) => {
+            let elements = x.iter().map(eval).collect::<Option<Vec<_>>>()?;
+            match x.tail() {
+                None => Some(Term::List(elements)),
+                Some(tail) => Some(cons(elements, eval(tail)?)),
+            }
+        }
+        Expr::Map(ref x) => x
+            .iter()
+            .map(|f| {
+                if f._relation.value() != Symbol::DoubleRightArrow {
+                    return None;
+                }
+                Some((eval(&f.key)?, eval(&f.value)?))
+            })
+            .collect::<Option<_>>()
+            .map(Term::Map),
+        Expr::Bits(ref x) => {
+            let mut bytes = Vec::new();
+            for e in x.elements.iter().flat_map(|e| e.iter()) {
+                if e.size.is_some() || e.type_specs.is_some() {
+                    return None;
+                }
+                match e.element {
+                    Expr::Literal(ref l @ Literal::String { .. })
+                    | Expr::Literal(ref l @ Literal::TripleQuotedString(_)) => {
+                        bytes.extend(l.string_value()?.chars().map(|c| c as u8));
+                    }
+                    Expr::Literal(Literal::Integer(ref t)) => {
+                        bytes.push((t.value() % 256u32).to_u8()?);
+                    }
+                    Expr::Literal(Literal::Char(ref t)) => bytes.push(t.value() as u8),
+                    _ => return None,
+                }
+            }
+            Some(Term::Binary(bytes))
+        }
+        Expr::Parenthesized(ref x) => eval(&x.item),
+        Expr::UnaryOpCall(_) | Expr::BinaryOpCall(_) => {
+            let mut operands = Vec::new();
+            let mut ops = Vec::new();
+            flatten(expr, Vec::new(), &mut operands, &mut ops);
+            let values = operands
+                .into_iter()
+                .map(|(unary_ops, operand)| {
+                    unary_ops
+                        .into_iter()
+                        .rev()
+                        .try_fold(eval(operand)?, |value, op| unary_op(op, value))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            climb(&values, &ops, &mut 0, 0)
+        }
+        _ => None,
+    }
+}
+
+/// Flattens a chain of operator calls into its operands (with the unary operators applied to them)
+/// and binary operators.
+///
+/// The parser nests binary operator calls to the right regardless of their precedence,
+/// and the operand of a unary operator call spans the rest of the chain,
+/// so the precedence and associativity are resolved by `climb` afterwards.
+fn flatten<'a>(
+    expr: &'a Expr,
+    mut unary_ops: Vec<&'a UnaryOp>,
+    operands: &mut Vec<(Vec<&'a UnaryOp>, &'a Expr)>,
+    ops: &mut Vec<&'a BinaryOp>,
+) {
+    match *expr {
+        Expr::UnaryOpCall(ref x) => {
+            unary_ops.push(&x.op);
+            flatten(&x.operand, unary_ops, operands, ops);
+        }
+        Expr::BinaryOpCall(ref x) => {
+            flatten(&x.left, unary_ops, operands, ops);
+            ops.push(&x.op);
+            flatten(&x.right, Vec::new(), operands, ops);
+        }
+        _ => operands.push((unary_ops, expr)),
+    }
+}
+
+/// Evaluates `values[*i] ops[*i] values[*i + 1] ...` by precedence climbing.
+fn climb(values: &[Term], ops: &[&BinaryOp], i: &mut usize, min_precedence: u8) -> Option<Term> {
+    let mut left = values[*i].clone();
+    while let Some(&op) = ops.get(*i).filter(|op| op.precedence() >= min_precedence) {
+        *i += 1;
+        let is_right_assoc = matches!(*op, BinaryOp::PlusPlus(_) | BinaryOp::MinusMinus(_));
+        let next_precedence = op.precedence() + if is_right_assoc { 0 } else { 1 };
+        let right = climb(values, ops, i, next_precedence)?;
+        left = binary_op(op, left, right)?;
+    }
+    Some(left)
+}
+
+fn literal(x: &Literal) -> Term {
+    match *x {
+        Literal::Atom(ref t) => Term::atom(t.value()),
+        Literal::Char(ref t) => Term::from(t.value() as u32),
+        Literal::Float(ref t) => Term::from(t.value()),
+        Literal::Integer(ref t) => Term::from(BigInt::from(t.value().clone())),
+        Literal::String { .. } | Literal::TripleQuotedString(_) => {
+            Term::string(&x.string_value().unwrap_or_default())
+        }
+    }
+}
+
+fn unary_op(op: &UnaryOp, operand: Term) -> Option<Term> {
+    match (op, operand) {
+        (UnaryOp::Plus(_), t @ Term::Integer(_)) | (UnaryOp::Plus(_), t @ Term::Float(_)) => {
+            Some(t)
+        }
+        (UnaryOp::Minus(_), Term::Integer(i)) => Some(Term::Integer(-i)),
+        (UnaryOp::Minus(_), Term::Float(f)) => Some(Term::Float(-f)),
+        (UnaryOp::Bnot(_), Term::Integer(i)) => Some(Term::Integer(!i)),
+        _ => None,
+    }
+}
+
+fn binary_op(op: &BinaryOp, left: Term, right: Term) -> Option<Term> {
+    if let BinaryOp::PlusPlus(_) = *op {
+        let elements = match left {
+            Term::List(elements) => elements,
+            _ => return None,
+        };
+        return Some(cons(elements, right));
+    }
+    if let (Term::Integer(ref x), Term::Integer(ref y)) = (&left, &right) {
+        let z = match *op {
+            BinaryOp::Plus(_) => x + y,
+            BinaryOp::Minus(_) => x - y,
+            BinaryOp::Mul(_) => x * y,
+            BinaryOp::IntDiv(_) if !y.is_zero() => x / y,
+            BinaryOp::Rem(_) if !y.is_zero() => x % y,
+            BinaryOp::Band(_) => x & y,
+            BinaryOp::Bor(_) => x | y,
+            BinaryOp::Bxor(_) => x ^ y,
+            BinaryOp::Bsl(_) | BinaryOp::Bsr(_) => {
+                let shift = y.to_i64().filter(|n| n.abs() <= MAX_SHIFT)?;
+                let shift = if let BinaryOp::Bsr(_) = *op {
+                    -shift
+                } else {
+                    shift
+                };
+                if shift >= 0 {
+                    x << shift as usize
+                } else {
+                    x >> (-shift) as usize
+                }
+            }
+            BinaryOp::FloatDiv(_) => return float_op(op, number(&left)?, number(&right)?),
+            _ => return None,
+        };
+        return Some(Term::Integer(z));
+    }
+    float_op(op, number(&left)?, number(&right)?)
+}
+
+fn float_op(op: &BinaryOp, x: f64, y: f64) -> Option<Term> {
+    let z = match *op {
+        BinaryOp::Plus(_) => x + y,
+        BinaryOp::Minus(_) => x - y,
+        BinaryOp::Mul(_) => x * y,
+        BinaryOp::FloatDiv(_) if y != 0.0 => x / y,
+        _ => return None,
+    };
+    Some(Term::Float(z)).filter(|_| z.is_finite())
+}
+
+fn number(term: &Term) -> Option<f64> {
+    match *term {
+        Term::Integer(ref i) => i.to_f64(),
+        Term::Float(f) => Some(f),
+        _ => None,
+    }
+}
+
+fn cons(mut elements: Vec<Term>, tail: Term) -> Term {
+    match tail {
+        Term::List(rest) => {
+            elements.extend(rest);
+            Term::List(elements)
+        }
+        Term::ImproperList(rest, t) => {
+            elements.extend(rest);
+            Term::ImproperList(elements, t)
+        }
+        t if elements.is_empty() => t,
+        t => Term::ImproperList(elements, Box::new(t)),
+    }
+}
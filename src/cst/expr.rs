@@ -1,14 +1,17 @@
-use erl_tokenize::tokens::{AtomToken, SymbolToken, VariableToken};
+use erl_tokenize::tokens::{SymbolToken, VariableToken};
 use erl_tokenize::values::{Keyword, Symbol};
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 
 use super::commons::parts::BinaryOp;
+use super::commons::RecordName;
 use super::exprs;
 use super::Literal;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{ErrorKind, Parser, Result};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Literal(Literal),
     Variable(VariableToken),
@@ -23,6 +26,7 @@ pub enum Expr {
     ListComprehension(Box<exprs::ListComprehension>),
     Bits(Box<exprs::Bits>),
     BitsComprehension(Box<exprs::BitsComprehension>),
+    MapComprehension(Box<exprs::MapComprehension>),
     Fun(Box<exprs::Fun>),
     Parenthesized(Box<exprs::Parenthesized>),
     FunCall(Box<exprs::FunCall>),
@@ -35,6 +39,10 @@ pub enum Expr {
     Case(Box<exprs::Case>),
     Receive(Box<exprs::Receive>),
     Try(Box<exprs::Try>),
+    Maybe(Box<exprs::Maybe>),
+    MaybeMatch(Box<exprs::MaybeMatch>),
+    MacroCall(Box<exprs::MacroCall>),
+    Extension(Box<exprs::ExtensionExpr>),
 }
 impl Parse for Expr {
     fn parse_non_left_recor<T>(parser: &mut Parser<T>) -> Result<Self>
@@ -53,6 +61,7 @@ impl Parse for Expr {
             HeadKind::ListComprehension => Expr::ListComprehension(track!(parser.parse())?),
             HeadKind::Bits => Expr::Bits(track!(parser.parse())?),
             HeadKind::BitsComprehension => Expr::BitsComprehension(track!(parser.parse())?),
+            HeadKind::MapComprehension => Expr::MapComprehension(track!(parser.parse())?),
             HeadKind::Fun => Expr::Fun(track!(parser.parse())?),
             HeadKind::UnaryOpCall => Expr::UnaryOpCall(track!(parser.parse())?),
             HeadKind::Parenthesized => Expr::Parenthesized(track!(parser.parse())?),
@@ -62,6 +71,12 @@ impl Parse for Expr {
             HeadKind::Case => Expr::Case(track!(parser.parse())?),
             HeadKind::Receive => Expr::Receive(track!(parser.parse())?),
             HeadKind::Try => Expr::Try(track!(parser.parse())?),
+            HeadKind::Maybe => Expr::Maybe(track!(parser.parse())?),
+            HeadKind::MacroCall => Expr::MacroCall(track!(parser.parse())?),
+            HeadKind::Extension => match parser.transaction(Parser::parse) {
+                Ok(x) => Expr::Extension(x),
+                Err(_) => Expr::Literal(track!(parser.parse())?),
+            },
             _ => track_panic!(ErrorKind::InvalidInput, "unreachable"),
         };
         Ok(expr)
@@ -70,25 +85,58 @@ impl Parse for Expr {
     where
         T: TokenRead,
     {
-        if let Ok(expr) = parser.transaction(Parser::parse) {
-            return Ok(Expr::Match(expr));
+        // Deeply nested data would exhaust the stack if parsed recursively
+        if parser.lookahead(exprs::flat::is_candidate) {
+            if let Some(expr) = parser.shortcut(exprs::flat::parse) {
+                return Ok(expr);
+            }
         }
+        parser.nested(|parser| {
+            parser.memoize(|parser| {
+                // The operands of binary operators are parsed in the loop (instead of recursively)
+                // to handle long operator chains (e.g., `A ++ B ++ ...`).
+                // Since the operators are right-associative in the CST, they are combined at the end.
+                let mut operands = Vec::new();
+                let mut expr = 'operand: loop {
+                    track!(parser.check_depth(operands.len()))?;
+                    if parser.is_match_lhs() {
+                        if let Ok(expr) = parser.transaction(Parser::parse) {
+                            break Expr::Match(expr);
+                        }
+                    }
 
-        let mut head = track!(Self::parse_non_left_recor(parser))?;
-        loop {
-            let kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
-            head = match kind {
-                TailKind::FunCall => Expr::FunCall(track!(parser.parse_tail(head))?),
-                TailKind::MapUpdate => Expr::MapUpdate(track!(parser.parse_tail(head))?),
-                TailKind::RecordUpdate => Expr::RecordUpdate(track!(parser.parse_tail(head))?),
-                TailKind::RecordFieldAccess => {
-                    Expr::RecordFieldAccess(track!(parser.parse_tail(head))?)
+                    let mut head = track!(Self::parse_non_left_recor(parser))?;
+                    loop {
+                        let kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
+                        head = match kind {
+                            TailKind::FunCall => Expr::FunCall(track!(parser.parse_tail(head))?),
+                            TailKind::MapUpdate => {
+                                Expr::MapUpdate(track!(parser.parse_tail(head))?)
+                            }
+                            TailKind::RecordUpdate => {
+                                Expr::RecordUpdate(track!(parser.parse_tail(head))?)
+                            }
+                            TailKind::RecordFieldAccess => {
+                                Expr::RecordFieldAccess(track!(parser.parse_tail(head))?)
+                            }
+                            TailKind::BinaryOpCall => {
+                                operands.push((head, track!(parser.parse())?));
+                                continue 'operand;
+                            }
+                            TailKind::None => break 'operand head,
+                        };
+                    }
+                };
+                while let Some((left, op)) = operands.pop() {
+                    expr = Expr::BinaryOpCall(Box::new(exprs::BinaryOpCall {
+                        left,
+                        op,
+                        right: expr,
+                    }));
                 }
-                TailKind::BinaryOpCall => Expr::BinaryOpCall(track!(parser.parse_tail(head))?),
-                TailKind::None => break,
-            };
-        }
-        Ok(head)
+                Ok(expr)
+            })
+        })
     }
 }
 impl PositionRange for Expr {
@@ -107,6 +155,7 @@ impl PositionRange for Expr {
             Expr::ListComprehension(ref x) => x.start_position(),
             Expr::Bits(ref x) => x.start_position(),
             Expr::BitsComprehension(ref x) => x.start_position(),
+            Expr::MapComprehension(ref x) => x.start_position(),
             Expr::Parenthesized(ref x) => x.start_position(),
             Expr::Fun(ref x) => x.start_position(),
             Expr::FunCall(ref x) => x.start_position(),
@@ -119,6 +168,10 @@ impl PositionRange for Expr {
             Expr::Case(ref x) => x.start_position(),
             Expr::Receive(ref x) => x.start_position(),
             Expr::Try(ref x) => x.start_position(),
+            Expr::Maybe(ref x) => x.start_position(),
+            Expr::MaybeMatch(ref x) => x.start_position(),
+            Expr::MacroCall(ref x) => x.start_position(),
+            Expr::Extension(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -136,6 +189,7 @@ impl PositionRange for Expr {
             Expr::ListComprehension(ref x) => x.end_position(),
             Expr::Bits(ref x) => x.end_position(),
             Expr::BitsComprehension(ref x) => x.end_position(),
+            Expr::MapComprehension(ref x) => x.end_position(),
             Expr::Parenthesized(ref x) => x.end_position(),
             Expr::Fun(ref x) => x.end_position(),
             Expr::FunCall(ref x) => x.end_position(),
@@ -148,8 +202,92 @@ impl PositionRange for Expr {
             Expr::Case(ref x) => x.end_position(),
             Expr::Receive(ref x) => x.end_position(),
             Expr::Try(ref x) => x.end_position(),
+            Expr::Maybe(ref x) => x.end_position(),
+            Expr::MaybeMatch(ref x) => x.end_position(),
+            Expr::MacroCall(ref x) => x.end_position(),
+            Expr::Extension(ref x) => x.end_position(),
+        }
+    }
+}
+impl CollectTokens for Expr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Expr::Literal(ref x) => x.collect_tokens(tokens),
+            Expr::Variable(ref x) => x.collect_tokens(tokens),
+            Expr::Tuple(ref x) => x.collect_tokens(tokens),
+            Expr::Map(ref x) => x.collect_tokens(tokens),
+            Expr::MapUpdate(ref x) => x.collect_tokens(tokens),
+            Expr::Record(ref x) => x.collect_tokens(tokens),
+            Expr::RecordUpdate(ref x) => x.collect_tokens(tokens),
+            Expr::RecordFieldIndex(ref x) => x.collect_tokens(tokens),
+            Expr::RecordFieldAccess(ref x) => x.collect_tokens(tokens),
+            Expr::List(ref x) => x.collect_tokens(tokens),
+            Expr::ListComprehension(ref x) => x.collect_tokens(tokens),
+            Expr::Bits(ref x) => x.collect_tokens(tokens),
+            Expr::BitsComprehension(ref x) => x.collect_tokens(tokens),
+            Expr::MapComprehension(ref x) => x.collect_tokens(tokens),
+            Expr::Fun(ref x) => x.collect_tokens(tokens),
+            Expr::Parenthesized(ref x) => x.collect_tokens(tokens),
+            Expr::FunCall(ref x) => x.collect_tokens(tokens),
+            Expr::UnaryOpCall(ref x) => x.collect_tokens(tokens),
+            Expr::BinaryOpCall(ref x) => x.collect_tokens(tokens),
+            Expr::Match(ref x) => x.collect_tokens(tokens),
+            Expr::Block(ref x) => x.collect_tokens(tokens),
+            Expr::Catch(ref x) => x.collect_tokens(tokens),
+            Expr::If(ref x) => x.collect_tokens(tokens),
+            Expr::Case(ref x) => x.collect_tokens(tokens),
+            Expr::Receive(ref x) => x.collect_tokens(tokens),
+            Expr::Try(ref x) => x.collect_tokens(tokens),
+            Expr::Maybe(ref x) => x.collect_tokens(tokens),
+            Expr::MaybeMatch(ref x) => x.collect_tokens(tokens),
+            Expr::MacroCall(ref x) => x.collect_tokens(tokens),
+            Expr::Extension(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Expr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Expr::Literal(x) => Expr::Literal(x.fold_with(folder)),
+            Expr::Variable(x) => Expr::Variable(x.fold_with(folder)),
+            Expr::Tuple(x) => Expr::Tuple(x.fold_with(folder)),
+            Expr::Map(x) => Expr::Map(x.fold_with(folder)),
+            Expr::MapUpdate(x) => Expr::MapUpdate(x.fold_with(folder)),
+            Expr::Record(x) => Expr::Record(x.fold_with(folder)),
+            Expr::RecordUpdate(x) => Expr::RecordUpdate(x.fold_with(folder)),
+            Expr::RecordFieldIndex(x) => Expr::RecordFieldIndex(x.fold_with(folder)),
+            Expr::RecordFieldAccess(x) => Expr::RecordFieldAccess(x.fold_with(folder)),
+            Expr::List(x) => Expr::List(x.fold_with(folder)),
+            Expr::ListComprehension(x) => Expr::ListComprehension(x.fold_with(folder)),
+            Expr::Bits(x) => Expr::Bits(x.fold_with(folder)),
+            Expr::BitsComprehension(x) => Expr::BitsComprehension(x.fold_with(folder)),
+            Expr::MapComprehension(x) => Expr::MapComprehension(x.fold_with(folder)),
+            Expr::Fun(x) => Expr::Fun(x.fold_with(folder)),
+            Expr::Parenthesized(x) => Expr::Parenthesized(x.fold_with(folder)),
+            Expr::FunCall(x) => Expr::FunCall(x.fold_with(folder)),
+            Expr::UnaryOpCall(x) => Expr::UnaryOpCall(x.fold_with(folder)),
+            Expr::BinaryOpCall(x) => Expr::BinaryOpCall(x.fold_with(folder)),
+            Expr::Match(x) => Expr::Match(x.fold_with(folder)),
+            Expr::Block(x) => Expr::Block(x.fold_with(folder)),
+            Expr::Catch(x) => Expr::Catch(x.fold_with(folder)),
+            Expr::If(x) => Expr::If(x.fold_with(folder)),
+            Expr::Case(x) => Expr::Case(x.fold_with(folder)),
+            Expr::Receive(x) => Expr::Receive(x.fold_with(folder)),
+            Expr::Try(x) => Expr::Try(x.fold_with(folder)),
+            Expr::Maybe(x) => Expr::Maybe(x.fold_with(folder)),
+            Expr::MaybeMatch(x) => Expr::MaybeMatch(x.fold_with(folder)),
+            Expr::MacroCall(x) => Expr::MacroCall(x.fold_with(folder)),
+            Expr::Extension(x) => Expr::Extension(x.fold_with(folder)),
         }
     }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_expr(self)
+    }
+}
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
 }
 
 #[derive(Debug)]
@@ -164,6 +302,7 @@ enum HeadKind {
     ListComprehension,
     Bits,
     BitsComprehension,
+    MapComprehension,
     Fun,
     UnaryOpCall,
     Parenthesized,
@@ -173,6 +312,9 @@ enum HeadKind {
     Case,
     Receive,
     Try,
+    Maybe,
+    MacroCall,
+    Extension,
     Annotated,
 }
 impl HeadKind {
@@ -181,11 +323,7 @@ impl HeadKind {
             LexicalToken::Symbol(t) => match t.value() {
                 Symbol::OpenBrace => HeadKind::Tuple,
                 Symbol::DoubleLeftAngle => {
-                    let maybe_comprehension = parser.parse::<Expr>().is_ok()
-                        && parser
-                            .expect::<SymbolToken>(&Symbol::DoubleVerticalBar)
-                            .is_ok();
-                    if maybe_comprehension {
+                    if parser.lookahead(is_comprehension) {
                         HeadKind::BitsComprehension
                     } else {
                         HeadKind::Bits
@@ -193,29 +331,28 @@ impl HeadKind {
                 }
                 Symbol::OpenParen => HeadKind::Parenthesized,
                 Symbol::OpenSquare => {
-                    let maybe_comprehension = parser.parse::<Expr>().is_ok()
-                        && parser
-                            .expect::<SymbolToken>(&Symbol::DoubleVerticalBar)
-                            .is_ok();
-                    if maybe_comprehension {
+                    if parser.lookahead(is_comprehension) {
                         HeadKind::ListComprehension
                     } else {
                         HeadKind::List
                     }
                 }
                 Symbol::Sharp => {
-                    if parser.parse::<AtomToken>().is_ok() {
+                    if parser.parse::<RecordName>().is_ok() {
                         let next = parser.parse::<SymbolToken>().map(|t| t.value()).ok();
                         if next == Some(Symbol::Dot) {
                             HeadKind::RecordFieldIndex
                         } else {
                             HeadKind::Record
                         }
+                    } else if parser.lookahead(is_comprehension) {
+                        HeadKind::MapComprehension
                     } else {
                         HeadKind::Map
                     }
                 }
                 Symbol::Plus | Symbol::Hyphen => HeadKind::UnaryOpCall,
+                Symbol::Question if parser.is_no_preprocess() => HeadKind::MacroCall,
                 _ => track_panic!(ErrorKind::UnexpectedToken(t.into())),
             },
             LexicalToken::Keyword(t) => match t.value() {
@@ -236,11 +373,60 @@ impl HeadKind {
                     HeadKind::Variable
                 }
             }
+            LexicalToken::Atom(ref t)
+                if t.text() == "maybe" && parser.lookahead(exprs::Maybe::follows_keyword) =>
+            {
+                HeadKind::Maybe
+            }
+            LexicalToken::Atom(ref t)
+                if parser.extensions().get_expr_prefix(t.value()).is_some() =>
+            {
+                HeadKind::Extension
+            }
             _ => HeadKind::Literal,
         })
     }
 }
 
+/// Scans the tokens following an opening `[` or `<<` for `||` at the same bracket depth.
+///
+/// The scan stops at the corresponding closing bracket, a form-terminating `.` or an error.
+fn is_comprehension<T: TokenRead>(parser: &mut Parser<T>) -> bool {
+    let mut depth = 0usize;
+    let mut after_sharp = false;
+    let mut after_sharp_question = false;
+    let mut after_record_name = false;
+    while let Ok(t) = parser.parse::<LexicalToken>() {
+        let symbol = t.as_symbol_token().map(SymbolToken::value);
+        match symbol {
+            Some(Symbol::OpenParen)
+            | Some(Symbol::OpenSquare)
+            | Some(Symbol::OpenBrace)
+            | Some(Symbol::DoubleLeftAngle) => depth += 1,
+            Some(Symbol::CloseParen)
+            | Some(Symbol::CloseSquare)
+            | Some(Symbol::CloseBrace)
+            | Some(Symbol::DoubleRightAngle) => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            }
+            Some(Symbol::DoubleVerticalBar) if depth == 0 => return true,
+
+            // `#name.field` (or `#?NAME.field`) is a record field access (or index)
+            Some(Symbol::Dot) if !after_record_name => return false,
+            _ => {}
+        }
+        after_record_name = (after_sharp && t.as_atom_token().is_some())
+            || (after_sharp_question
+                && (t.as_atom_token().is_some() || t.as_variable_token().is_some()));
+        after_sharp_question = after_sharp && symbol == Some(Symbol::Question);
+        after_sharp = symbol == Some(Symbol::Sharp);
+    }
+    false
+}
+
 #[derive(Debug)]
 enum TailKind {
     FunCall,
@@ -261,12 +447,7 @@ impl TailKind {
         Ok(match token.as_symbol_token().map(SymbolToken::value) {
             Some(Symbol::OpenParen) | Some(Symbol::Colon) => TailKind::FunCall,
             Some(Symbol::Sharp) => {
-                if parser
-                    .parse::<LexicalToken>()
-                    .ok()
-                    .and_then(|t| t.as_atom_token().map(|_| ()))
-                    .is_some()
-                {
+                if parser.parse::<RecordName>().is_ok() {
                     let is_record_update = parser
                         .parse::<LexicalToken>()
                         .ok()
@@ -0,0 +1,228 @@
+//! Non-recursive parsing of expressions which consist only of literals, variables, tuples,
+//! lists, parentheses and operators.
+//!
+//! Deeply nested data (e.g., machine-generated terms) would exhaust the stack
+//! if parsed by the recursive `Expr::parse`.
+use erl_tokenize::tokens::SymbolToken;
+use erl_tokenize::values::{Keyword, Symbol};
+use erl_tokenize::LexicalToken;
+
+use super::{BinaryOpCall, List, Parenthesized, Tuple, UnaryOpCall};
+use crate::cst::commons::parts::{BinaryOp, ConsCell, Sequence, UnaryOp};
+use crate::cst::Expr;
+use crate::traits::TokenRead;
+use crate::{ErrorKind, Parser, Result};
+
+/// Returns `true` if the following expression is worth trying `parse`.
+pub(crate) fn is_candidate<T: TokenRead>(parser: &mut Parser<T>) -> bool {
+    match parser.parse::<LexicalToken>() {
+        Ok(LexicalToken::Symbol(t)) => matches!(
+            t.value(),
+            Symbol::OpenSquare
+                | Symbol::OpenBrace
+                | Symbol::OpenParen
+                | Symbol::Plus
+                | Symbol::Hyphen
+        ),
+        Ok(LexicalToken::Keyword(_)) | Err(_) => false,
+        Ok(_) => parser
+            .parse::<LexicalToken>()
+            .is_ok_and(|t| BinaryOp::from_token(t).is_ok()),
+    }
+}
+
+/// Parses an expression without recursion.
+///
+/// The resulting CST is the same as the one made by `Expr::parse`.
+/// If any other construct (including match expressions and function calls) appears,
+/// this fails and the expression should be parsed by `Expr::parse` instead.
+pub(crate) fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Expr> {
+    let mut frames = Vec::new();
+    'operand: loop {
+        track!(parser.check_depth(frames.len() + 1))?;
+        let mut expr = match track!(parser.peek(Parser::parse))? {
+            LexicalToken::Symbol(t) => match t.value() {
+                Symbol::OpenBrace | Symbol::OpenSquare | Symbol::OpenParen => {
+                    let open: SymbolToken = track!(parser.parse())?;
+                    let next = track!(parser.peek(Parser::parse::<LexicalToken>))?;
+                    let next = next.as_symbol_token().map(SymbolToken::value);
+                    match (open.value(), next) {
+                        (Symbol::OpenBrace, Some(Symbol::CloseBrace)) => {
+                            Expr::Tuple(Box::new(Tuple {
+                                _open: open,
+                                elements: None,
+                                _close: track!(parser.parse())?,
+                            }))
+                        }
+                        (Symbol::OpenSquare, Some(Symbol::CloseSquare)) => {
+                            Expr::List(Box::new(List {
+                                _open: open,
+                                elements: None,
+                                _close: track!(parser.parse())?,
+                            }))
+                        }
+                        _ => {
+                            frames.push(Frame::open(open));
+                            continue 'operand;
+                        }
+                    }
+                }
+                Symbol::Plus | Symbol::Hyphen => {
+                    frames.push(Frame::UnaryOp(track!(parser.parse())?));
+                    continue 'operand;
+                }
+                _ => track_panic!(ErrorKind::UnexpectedToken(t.into())),
+            },
+            LexicalToken::Keyword(t) => match t.value() {
+                Keyword::Not | Keyword::Bnot => {
+                    frames.push(Frame::UnaryOp(track!(parser.parse())?));
+                    continue 'operand;
+                }
+                _ => track_panic!(ErrorKind::UnexpectedToken(t.into())),
+            },
+            LexicalToken::Variable(_) => Expr::Variable(track!(parser.parse())?),
+            _ => Expr::Literal(track!(parser.parse())?),
+        };
+
+        loop {
+            let next = if track!(parser.eos())? {
+                None
+            } else {
+                Some(track!(parser.peek(Parser::parse::<LexicalToken>))?)
+            };
+            if let Some(ref t) = next {
+                if BinaryOp::from_token(t.clone()).is_ok() {
+                    frames.push(Frame::BinaryOp(expr, track!(parser.parse())?));
+                    continue 'operand;
+                }
+            }
+            let next = next
+                .as_ref()
+                .and_then(LexicalToken::as_symbol_token)
+                .map(SymbolToken::value);
+            match next {
+                Some(Symbol::Match)
+                | Some(Symbol::OpenParen)
+                | Some(Symbol::Colon)
+                | Some(Symbol::Sharp)
+                | Some(Symbol::DoubleColon) => {
+                    track_panic!(ErrorKind::InvalidInput, "Unsupported tail: {:?}", next)
+                }
+                _ => {}
+            }
+
+            expr = match frames.pop() {
+                None => return Ok(expr),
+                Some(Frame::UnaryOp(op)) => {
+                    Expr::UnaryOpCall(Box::new(UnaryOpCall { op, operand: expr }))
+                }
+                Some(Frame::BinaryOp(left, op)) => Expr::BinaryOpCall(Box::new(BinaryOpCall {
+                    left,
+                    op,
+                    right: expr,
+                })),
+                Some(Frame::Parenthesized(open)) => {
+                    track_assert_eq!(next, Some(Symbol::CloseParen), ErrorKind::InvalidInput);
+                    Expr::Parenthesized(Box::new(Parenthesized {
+                        _open: open,
+                        item: expr,
+                        _close: track!(parser.parse())?,
+                    }))
+                }
+                Some(Frame::Tuple(open, mut elements)) => {
+                    elements.push(expr);
+                    match next {
+                        Some(Symbol::Comma) => {
+                            let comma = track!(parser.parse())?;
+                            frames.push(Frame::Tuple(open, elements.with_delimiter(comma)));
+                            continue 'operand;
+                        }
+                        Some(Symbol::CloseBrace) => Expr::Tuple(Box::new(Tuple {
+                            _open: open,
+                            elements: Some(elements.into_sequence()),
+                            _close: track!(parser.parse())?,
+                        })),
+                        _ => track_panic!(ErrorKind::InvalidInput, "Unexpected: {:?}", next),
+                    }
+                }
+                Some(Frame::List(open, mut elements)) => {
+                    elements.push(expr);
+                    match next {
+                        Some(Symbol::Comma) => {
+                            let comma = track!(parser.parse())?;
+                            frames.push(Frame::List(open, elements.with_delimiter(comma)));
+                            continue 'operand;
+                        }
+                        Some(Symbol::VerticalBar) => {
+                            let bar = track!(parser.parse())?;
+                            frames.push(Frame::ImproperList(open, elements, bar));
+                            continue 'operand;
+                        }
+                        Some(Symbol::CloseSquare) => Expr::List(Box::new(List {
+                            _open: open,
+                            elements: Some(elements.into_cons_cell(None)),
+                            _close: track!(parser.parse())?,
+                        })),
+                        _ => track_panic!(ErrorKind::InvalidInput, "Unexpected: {:?}", next),
+                    }
+                }
+                Some(Frame::ImproperList(open, elements, bar)) => {
+                    track_assert_eq!(next, Some(Symbol::CloseSquare), ErrorKind::InvalidInput);
+                    Expr::List(Box::new(List {
+                        _open: open,
+                        elements: Some(elements.into_cons_cell(Some((bar, expr)))),
+                        _close: track!(parser.parse())?,
+                    }))
+                }
+            };
+        }
+    }
+}
+
+/// Pending construct waiting for its next operand.
+enum Frame {
+    UnaryOp(UnaryOp),
+    BinaryOp(Expr, BinaryOp),
+    Parenthesized(SymbolToken),
+    Tuple(SymbolToken, Elements),
+    List(SymbolToken, Elements),
+    ImproperList(SymbolToken, Elements, SymbolToken),
+}
+impl Frame {
+    fn open(open: SymbolToken) -> Self {
+        match open.value() {
+            Symbol::OpenBrace => Frame::Tuple(open, Elements::default()),
+            Symbol::OpenSquare => Frame::List(open, Elements::default()),
+            _ => Frame::Parenthesized(open),
+        }
+    }
+}
+
+/// Comma-separated elements of a tuple or a list.
+#[derive(Default)]
+struct Elements {
+    items: Vec<Expr>,
+    delimiters: Vec<SymbolToken>,
+}
+impl Elements {
+    fn push(&mut self, item: Expr) {
+        self.items.push(item);
+    }
+    fn with_delimiter(mut self, delimiter: SymbolToken) -> Self {
+        self.delimiters.push(delimiter);
+        self
+    }
+    fn split(self) -> (Expr, Vec<(SymbolToken, Expr)>) {
+        let mut items = self.items.into_iter();
+        let first = items.next().expect("Never fails");
+        (first, self.delimiters.into_iter().zip(items).collect())
+    }
+    fn into_sequence(self) -> Sequence<Expr> {
+        let (first, rest) = self.split();
+        Sequence::from_items(first, rest)
+    }
+    fn into_cons_cell(self, improper: Option<(SymbolToken, Expr)>) -> ConsCell<Expr> {
+        let (first, rest) = self.split();
+        ConsCell::from_items(first, rest, improper)
+    }
+}
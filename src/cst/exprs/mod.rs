@@ -1,15 +1,20 @@
-use erl_tokenize::tokens::{KeywordToken, SymbolToken};
+use erl_tokenize::tokens::{AtomToken, KeywordToken, SymbolToken, VariableToken};
 use erl_tokenize::values::{Keyword, Symbol};
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
-use self::parts::{Body, Qualifier, Timeout, TryAfter, TryCatch, TryOf};
+use self::parts::{Body, MaybeElse, Qualifier, Timeout, TryAfter, TryCatch, TryOf};
 use crate::cst::clauses::{CaseClause, FunClause, IfClause, NamedFunClause};
-use crate::cst::commons::parts::{Clauses, ModulePrefix, NameAndArity, Sequence};
+use crate::cst::commons::parts::{
+    Args, Clauses, ExtensionPart, ModulePrefix, NameAndArity, Sequence,
+};
 use crate::cst::commons::{self, AtomOrVariable, IntegerOrVariable};
-use crate::cst::Expr;
-use crate::traits::{Parse, ParseTail, TokenRead};
-use crate::{Parser, Result};
+use crate::cst::{Expr, Pattern};
+use crate::extension;
+use crate::messages::Message;
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, ParseTail, TokenRead};
+use crate::{ErrorKind, Parser, Result};
 
+pub(crate) mod flat;
 pub mod parts;
 
 pub type Tuple = commons::Tuple<Expr>;
@@ -26,6 +31,7 @@ pub type Match = commons::Match<Expr>;
 
 /// `Expr` `Map`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MapUpdate {
     pub map: Expr,
     pub update: Map,
@@ -47,9 +53,24 @@ impl PositionRange for MapUpdate {
         self.update.end_position()
     }
 }
+impl CollectTokens for MapUpdate {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.map.collect_tokens(tokens);
+        self.update.collect_tokens(tokens);
+    }
+}
+impl Foldable for MapUpdate {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MapUpdate {
+            map: self.map.fold_with(folder),
+            update: self.update.fold_with(folder),
+        }
+    }
+}
 
 /// `Expr` `Record`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordUpdate {
     pub record: Expr,
     pub update: Record,
@@ -71,9 +92,24 @@ impl PositionRange for RecordUpdate {
         self.update.end_position()
     }
 }
+impl CollectTokens for RecordUpdate {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.record.collect_tokens(tokens);
+        self.update.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordUpdate {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordUpdate {
+            record: self.record.fold_with(folder),
+            update: self.update.fold_with(folder),
+        }
+    }
+}
 
 /// `try` `Body` `Option<TryOf>` `Option<TryCatch>` `Option<TryAfter>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Try {
     pub _try: KeywordToken,
     pub body: Body,
@@ -84,13 +120,16 @@ pub struct Try {
 }
 impl Parse for Try {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(Try {
-            _try: track!(parser.expect(&Keyword::Try))?,
-            body: track!(parser.parse())?,
-            branch: track!(parser.parse())?,
-            catch: track!(parser.parse())?,
-            after: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "try");
+        parser.with_context(description, |parser| {
+            Ok(Try {
+                _try: track!(parser.expect(&Keyword::Try))?,
+                body: track!(parser.parse())?,
+                branch: track!(parser.parse())?,
+                catch: track!(parser.parse())?,
+                after: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -102,9 +141,188 @@ impl PositionRange for Try {
         self._end.end_position()
     }
 }
+impl CollectTokens for Try {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._try.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+        self.branch.collect_tokens(tokens);
+        self.catch.collect_tokens(tokens);
+        self.after.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for Try {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Try {
+            _try: self._try.fold_with(folder),
+            body: self.body.fold_with(folder),
+            branch: self.branch.fold_with(folder),
+            catch: self.catch.fold_with(folder),
+            after: self.after.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
+
+/// `maybe` `Body` `Option<MaybeElse>` `end`
+///
+/// The elements of the body may be `MaybeMatch` expressions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Maybe {
+    pub _maybe: AtomToken,
+    pub body: Body,
+    pub else_clauses: Option<MaybeElse>,
+    pub _end: KeywordToken,
+}
+impl Maybe {
+    /// Returns `true` if the token following a `maybe` atom starts an expression,
+    /// which means the atom is the keyword of a `maybe` expression (since OTP 25)
+    /// because no expression can follow an atom.
+    pub(crate) fn follows_keyword<T: TokenRead>(parser: &mut Parser<T>) -> bool {
+        match parser.parse::<LexicalToken>() {
+            Ok(LexicalToken::Symbol(t)) => matches!(
+                t.value(),
+                Symbol::OpenBrace | Symbol::OpenSquare | Symbol::DoubleLeftAngle | Symbol::Sharp
+            ),
+            Ok(LexicalToken::Keyword(t)) => matches!(
+                t.value(),
+                Keyword::Begin
+                    | Keyword::Case
+                    | Keyword::Catch
+                    | Keyword::Fun
+                    | Keyword::If
+                    | Keyword::Not
+                    | Keyword::Bnot
+                    | Keyword::Receive
+                    | Keyword::Try
+            ),
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
+
+    fn parse_body<T: TokenRead>(parser: &mut Parser<T>) -> Result<Body> {
+        let first = track!(Self::parse_body_expr(parser))?;
+        let mut rest = Vec::new();
+        while let Ok(comma) = parser.expect(&Symbol::Comma) {
+            rest.push((comma, track!(Self::parse_body_expr(parser))?));
+        }
+        Ok(Body {
+            exprs: Sequence::from_items(first, rest),
+        })
+    }
+
+    fn parse_body_expr<T: TokenRead>(parser: &mut Parser<T>) -> Result<Expr> {
+        if let Ok(x) = parser.transaction(Parser::parse) {
+            Ok(Expr::MaybeMatch(x))
+        } else {
+            track!(parser.parse())
+        }
+    }
+}
+impl Parse for Maybe {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let description = Message::new("expression_context").arg("keyword", "maybe");
+        parser.with_context(description, |parser| {
+            Ok(Maybe {
+                _maybe: track!(parser.expect("maybe"))?,
+                body: track!(Self::parse_body(parser))?,
+                else_clauses: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
+        })
+    }
+}
+impl PositionRange for Maybe {
+    fn start_position(&self) -> Position {
+        self._maybe.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._end.end_position()
+    }
+}
+impl CollectTokens for Maybe {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._maybe.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+        self.else_clauses.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for Maybe {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Maybe {
+            _maybe: self._maybe.fold_with(folder),
+            body: self.body.fold_with(folder),
+            else_clauses: self.else_clauses.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
+
+/// `Pattern` `?=` `Expr`
+///
+/// Conditional match, which appears only in the body of `Maybe`.
+/// Since `?=` is not a token of `erl_tokenize`, this consists of adjacent `?` and `=` symbols.
+/// Note that `erl_pp` drops the `?` (as a broken macro call),
+/// so the tokens to be preprocessed by `erl_pp` should be read via `MaybeMatchTokens` (or `IncludeExpander`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaybeMatch {
+    pub pattern: Pattern,
+    pub _question: SymbolToken,
+    pub _match: SymbolToken,
+    pub value: Expr,
+}
+impl Parse for MaybeMatch {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let pattern = track!(parser.parse())?;
+        let _question: SymbolToken = track!(parser.expect(&Symbol::Question))?;
+        let _match: SymbolToken = track!(parser.expect(&Symbol::Match))?;
+        track_assert_eq!(
+            _question.end_position().offset(),
+            _match.start_position().offset(),
+            ErrorKind::UnexpectedToken(_match.into())
+        );
+        Ok(MaybeMatch {
+            pattern,
+            _question,
+            _match,
+            value: track!(parser.parse())?,
+        })
+    }
+}
+impl PositionRange for MaybeMatch {
+    fn start_position(&self) -> Position {
+        self.pattern.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.value.end_position()
+    }
+}
+impl CollectTokens for MaybeMatch {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.pattern.collect_tokens(tokens);
+        self._question.collect_tokens(tokens);
+        self._match.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+    }
+}
+impl Foldable for MaybeMatch {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MaybeMatch {
+            pattern: self.pattern.fold_with(folder),
+            _question: self._question.fold_with(folder),
+            _match: self._match.fold_with(folder),
+            value: self.value.fold_with(folder),
+        }
+    }
+}
 
 /// `receive` `Clauses<CaseClause>` `Option<Timeout>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Receive {
     pub _receive: KeywordToken,
     pub clauses: Clauses<CaseClause>,
@@ -113,11 +331,14 @@ pub struct Receive {
 }
 impl Parse for Receive {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(Receive {
-            _receive: track!(parser.expect(&Keyword::Receive))?,
-            clauses: track!(parser.parse())?,
-            timeout: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "receive");
+        parser.with_context(description, |parser| {
+            Ok(Receive {
+                _receive: track!(parser.expect(&Keyword::Receive))?,
+                clauses: track!(parser.parse())?,
+                timeout: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -129,9 +350,28 @@ impl PositionRange for Receive {
         self._end.end_position()
     }
 }
+impl CollectTokens for Receive {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._receive.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self.timeout.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for Receive {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Receive {
+            _receive: self._receive.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            timeout: self.timeout.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `if` `Clauses<IfClause>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct If {
     pub _if: KeywordToken,
     pub clauses: Clauses<IfClause>,
@@ -139,10 +379,13 @@ pub struct If {
 }
 impl Parse for If {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(If {
-            _if: track!(parser.expect(&Keyword::If))?,
-            clauses: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "if");
+        parser.with_context(description, |parser| {
+            Ok(If {
+                _if: track!(parser.expect(&Keyword::If))?,
+                clauses: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -154,9 +397,26 @@ impl PositionRange for If {
         self._end.end_position()
     }
 }
+impl CollectTokens for If {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._if.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for If {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        If {
+            _if: self._if.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `case` `Expr` `of` `Clauses<CaseClause>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Case {
     pub _case: KeywordToken,
     pub expr: Expr,
@@ -166,12 +426,15 @@ pub struct Case {
 }
 impl Parse for Case {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(Case {
-            _case: track!(parser.expect(&Keyword::Case))?,
-            expr: track!(parser.parse())?,
-            _of: track!(parser.expect(&Keyword::Of))?,
-            clauses: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "case");
+        parser.with_context(description, |parser| {
+            Ok(Case {
+                _case: track!(parser.expect(&Keyword::Case))?,
+                expr: track!(parser.parse())?,
+                _of: track!(parser.expect(&Keyword::Of))?,
+                clauses: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -183,9 +446,30 @@ impl PositionRange for Case {
         self._end.end_position()
     }
 }
+impl CollectTokens for Case {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._case.collect_tokens(tokens);
+        self.expr.collect_tokens(tokens);
+        self._of.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for Case {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Case {
+            _case: self._case.fold_with(folder),
+            expr: self.expr.fold_with(folder),
+            _of: self._of.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `DefinedFun | AnonymousFun | NamedFun`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum Fun {
     Defined(DefinedFun),
@@ -220,9 +504,28 @@ impl PositionRange for Fun {
         }
     }
 }
+impl CollectTokens for Fun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Fun::Defined(ref x) => x.collect_tokens(tokens),
+            Fun::Anonymous(ref x) => x.collect_tokens(tokens),
+            Fun::Named(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Fun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Fun::Defined(x) => Fun::Defined(x.fold_with(folder)),
+            Fun::Anonymous(x) => Fun::Anonymous(x.fold_with(folder)),
+            Fun::Named(x) => Fun::Named(x.fold_with(folder)),
+        }
+    }
+}
 
 /// `fun` `Option<ModulePrefix>` `NameAndArity`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DefinedFun {
     pub _fun: KeywordToken,
     pub module: Option<ModulePrefix<AtomOrVariable>>,
@@ -245,9 +548,26 @@ impl PositionRange for DefinedFun {
         self.fun.end_position()
     }
 }
+impl CollectTokens for DefinedFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self.module.collect_tokens(tokens);
+        self.fun.collect_tokens(tokens);
+    }
+}
+impl Foldable for DefinedFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        DefinedFun {
+            _fun: self._fun.fold_with(folder),
+            module: self.module.fold_with(folder),
+            fun: self.fun.fold_with(folder),
+        }
+    }
+}
 
 /// `fun` `Clauses<FunClause>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnonymousFun {
     pub _fun: KeywordToken,
     pub clauses: Clauses<FunClause>,
@@ -255,10 +575,13 @@ pub struct AnonymousFun {
 }
 impl Parse for AnonymousFun {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(AnonymousFun {
-            _fun: track!(parser.expect(&Keyword::Fun))?,
-            clauses: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "fun");
+        parser.with_context(description, |parser| {
+            Ok(AnonymousFun {
+                _fun: track!(parser.expect(&Keyword::Fun))?,
+                clauses: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -270,9 +593,26 @@ impl PositionRange for AnonymousFun {
         self._end.end_position()
     }
 }
+impl CollectTokens for AnonymousFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for AnonymousFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        AnonymousFun {
+            _fun: self._fun.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `fun` `Clauses<NamedFunClause>` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NamedFun {
     pub _fun: KeywordToken,
     pub clauses: Clauses<NamedFunClause>,
@@ -280,10 +620,13 @@ pub struct NamedFun {
 }
 impl Parse for NamedFun {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(NamedFun {
-            _fun: track!(parser.expect(&Keyword::Fun))?,
-            clauses: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("expression_context").arg("keyword", "fun");
+        parser.with_context(description, |parser| {
+            Ok(NamedFun {
+                _fun: track!(parser.expect(&Keyword::Fun))?,
+                clauses: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -295,9 +638,26 @@ impl PositionRange for NamedFun {
         self._end.end_position()
     }
 }
+impl CollectTokens for NamedFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for NamedFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        NamedFun {
+            _fun: self._fun.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `[` `Expr` `||` `Sequence<Qualifier>` `]`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ListComprehension {
     pub _open: SymbolToken,
     pub element: Expr,
@@ -307,12 +667,15 @@ pub struct ListComprehension {
 }
 impl Parse for ListComprehension {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(ListComprehension {
-            _open: track!(parser.expect(&Symbol::OpenSquare))?,
-            element: track!(parser.parse())?,
-            _bar: track!(parser.expect(&Symbol::DoubleVerticalBar))?,
-            qualifiers: track!(parser.parse())?,
-            _close: track!(parser.expect(&Symbol::CloseSquare))?,
+        let description = Message::new("list_comprehension_context");
+        parser.with_context(description, |parser| {
+            Ok(ListComprehension {
+                _open: track!(parser.expect(&Symbol::OpenSquare))?,
+                element: track!(parser.parse())?,
+                _bar: track!(parser.expect(&Symbol::DoubleVerticalBar))?,
+                qualifiers: track!(parser.parse())?,
+                _close: track!(parser.expect(&Symbol::CloseSquare))?,
+            })
         })
     }
 }
@@ -324,9 +687,30 @@ impl PositionRange for ListComprehension {
         self._close.end_position()
     }
 }
+impl CollectTokens for ListComprehension {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.element.collect_tokens(tokens);
+        self._bar.collect_tokens(tokens);
+        self.qualifiers.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for ListComprehension {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ListComprehension {
+            _open: self._open.fold_with(folder),
+            element: self.element.fold_with(folder),
+            _bar: self._bar.fold_with(folder),
+            qualifiers: self.qualifiers.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `<<` `Expr` `||` `Sequence<Qualifiers>` `>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitsComprehension {
     pub _open: SymbolToken,
     pub element: Expr,
@@ -336,12 +720,15 @@ pub struct BitsComprehension {
 }
 impl Parse for BitsComprehension {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(BitsComprehension {
-            _open: track!(parser.expect(&Symbol::DoubleLeftAngle))?,
-            element: track!(parser.parse())?,
-            _bar: track!(parser.expect(&Symbol::DoubleVerticalBar))?,
-            qualifiers: track!(parser.parse())?,
-            _close: track!(parser.expect(&Symbol::DoubleRightAngle))?,
+        let description = Message::new("binary_comprehension_context");
+        parser.with_context(description, |parser| {
+            Ok(BitsComprehension {
+                _open: track!(parser.expect(&Symbol::DoubleLeftAngle))?,
+                element: track!(parser.parse())?,
+                _bar: track!(parser.expect(&Symbol::DoubleVerticalBar))?,
+                qualifiers: track!(parser.parse())?,
+                _close: track!(parser.expect(&Symbol::DoubleRightAngle))?,
+            })
         })
     }
 }
@@ -353,9 +740,95 @@ impl PositionRange for BitsComprehension {
         self._close.end_position()
     }
 }
+impl CollectTokens for BitsComprehension {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.element.collect_tokens(tokens);
+        self._bar.collect_tokens(tokens);
+        self.qualifiers.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for BitsComprehension {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BitsComprehension {
+            _open: self._open.fold_with(folder),
+            element: self.element.fold_with(folder),
+            _bar: self._bar.fold_with(folder),
+            qualifiers: self.qualifiers.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
+
+/// `#` `{` `Expr` `=>` `Expr` `||` `Sequence<Qualifier>` `}`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapComprehension {
+    pub _sharp: SymbolToken,
+    pub _open: SymbolToken,
+    pub key: Expr,
+    pub _relation: SymbolToken,
+    pub value: Expr,
+    pub _bar: SymbolToken,
+    pub qualifiers: Sequence<Qualifier>,
+    pub _close: SymbolToken,
+}
+impl Parse for MapComprehension {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let description = Message::new("map_comprehension_context");
+        parser.with_context(description, |parser| {
+            Ok(MapComprehension {
+                _sharp: track!(parser.expect(&Symbol::Sharp))?,
+                _open: track!(parser.expect(&Symbol::OpenBrace))?,
+                key: track!(parser.parse())?,
+                _relation: track!(parser.expect(&Symbol::DoubleRightArrow))?,
+                value: track!(parser.parse())?,
+                _bar: track!(parser.expect(&Symbol::DoubleVerticalBar))?,
+                qualifiers: track!(parser.parse())?,
+                _close: track!(parser.expect(&Symbol::CloseBrace))?,
+            })
+        })
+    }
+}
+impl PositionRange for MapComprehension {
+    fn start_position(&self) -> Position {
+        self._sharp.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._close.end_position()
+    }
+}
+impl CollectTokens for MapComprehension {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._sharp.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.key.collect_tokens(tokens);
+        self._relation.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+        self._bar.collect_tokens(tokens);
+        self.qualifiers.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for MapComprehension {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MapComprehension {
+            _sharp: self._sharp.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            key: self.key.fold_with(folder),
+            _relation: self._relation.fold_with(folder),
+            value: self.value.fold_with(folder),
+            _bar: self._bar.fold_with(folder),
+            qualifiers: self.qualifiers.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `catch` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Catch {
     pub _catch: KeywordToken,
     pub expr: Body,
@@ -376,9 +849,24 @@ impl PositionRange for Catch {
         self.expr.end_position()
     }
 }
+impl CollectTokens for Catch {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._catch.collect_tokens(tokens);
+        self.expr.collect_tokens(tokens);
+    }
+}
+impl Foldable for Catch {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Catch {
+            _catch: self._catch.fold_with(folder),
+            expr: self.expr.fold_with(folder),
+        }
+    }
+}
 
 /// `begin` `Body` `end`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub _begin: KeywordToken,
     pub body: Body,
@@ -386,10 +874,13 @@ pub struct Block {
 }
 impl Parse for Block {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        Ok(Block {
-            _begin: track!(parser.expect(&Keyword::Begin))?,
-            body: track!(parser.parse())?,
-            _end: track!(parser.expect(&Keyword::End))?,
+        let description = Message::new("block_context");
+        parser.with_context(description, |parser| {
+            Ok(Block {
+                _begin: track!(parser.expect(&Keyword::Begin))?,
+                body: track!(parser.parse())?,
+                _end: track!(parser.expect(&Keyword::End))?,
+            })
         })
     }
 }
@@ -401,9 +892,26 @@ impl PositionRange for Block {
         self._end.end_position()
     }
 }
+impl CollectTokens for Block {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._begin.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+        self._end.collect_tokens(tokens);
+    }
+}
+impl Foldable for Block {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Block {
+            _begin: self._begin.fold_with(folder),
+            body: self.body.fold_with(folder),
+            _end: self._end.fold_with(folder),
+        }
+    }
+}
 
 /// `Expr` `RecordFieldIndex`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordFieldAccess<T = Expr> {
     pub record: T,
     pub index: RecordFieldIndex,
@@ -425,3 +933,204 @@ impl<T: PositionRange> PositionRange for RecordFieldAccess<T> {
         self.index.end_position()
     }
 }
+impl<T: CollectTokens> CollectTokens for RecordFieldAccess<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.record.collect_tokens(tokens);
+        self.index.collect_tokens(tokens);
+    }
+}
+impl<T: Foldable> Foldable for RecordFieldAccess<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordFieldAccess {
+            record: self.record.fold_with(folder),
+            index: self.index.fold_with(folder),
+        }
+    }
+}
+
+/// `?` `AtomOrVariable` `Option<Args<TokenGroup>>`
+///
+/// This is produced only in no-preprocess mode (see `Parser::no_preprocess`).
+/// If a macro call is followed by `(`, the parenthesized tokens are regarded as its arguments.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacroCall {
+    pub _question: SymbolToken,
+    pub name: AtomOrVariable,
+    pub args: Option<Args<TokenGroup>>,
+}
+impl Parse for MacroCall {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        Ok(MacroCall {
+            _question: track!(parser.expect(&Symbol::Question))?,
+            name: track!(parser.parse())?,
+            args: parser.transaction(Parser::parse).ok(),
+        })
+    }
+}
+impl PositionRange for MacroCall {
+    fn start_position(&self) -> Position {
+        self._question.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.args
+            .as_ref()
+            .map(PositionRange::end_position)
+            .unwrap_or_else(|| self.name.end_position())
+    }
+}
+impl CollectTokens for MacroCall {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._question.collect_tokens(tokens);
+        self.name.collect_tokens(tokens);
+        self.args.collect_tokens(tokens);
+    }
+}
+impl Foldable for MacroCall {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MacroCall {
+            _question: self._question.fold_with(folder),
+            name: self.name.fold_with(folder),
+            args: self.args.fold_with(folder),
+        }
+    }
+}
+
+/// `AtomToken` `Vec<ExtensionPart>`
+///
+/// Expression parsed by the `extension::SyntaxExtension` registered for its prefix atom
+/// (see `extension::Extensions::expr_prefix`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionExpr {
+    pub prefix: AtomToken,
+    pub parts: Vec<ExtensionPart>,
+}
+impl Parse for ExtensionExpr {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let prefix: AtomToken = track!(parser.parse())?;
+        let extension = track_assert_some!(
+            parser.extensions().get_expr_prefix(prefix.value()),
+            ErrorKind::UnexpectedToken(prefix.clone().into())
+        );
+        Ok(ExtensionExpr {
+            parts: track!(extension::run(parser, &*extension))?,
+            prefix,
+        })
+    }
+}
+impl PositionRange for ExtensionExpr {
+    fn start_position(&self) -> Position {
+        self.prefix.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.parts
+            .last()
+            .map(PositionRange::end_position)
+            .unwrap_or_else(|| self.prefix.end_position())
+    }
+}
+impl CollectTokens for ExtensionExpr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.prefix.collect_tokens(tokens);
+        self.parts.collect_tokens(tokens);
+    }
+}
+impl Foldable for ExtensionExpr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ExtensionExpr {
+            prefix: self.prefix.fold_with(folder),
+            parts: self.parts.fold_with(folder),
+        }
+    }
+}
+
+/// Non-empty tokens up to a `,` or `)` which is not enclosed in brackets or `... end` blocks.
+///
+/// This is an argument of a macro call (or a preprocessor directive), which is not parsed further
+/// since it may be any token sequence (e.g., `?M(a, b)` where `-define(M(X, Y), X:Y()).`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenGroup {
+    #[cfg_attr(feature = "serde", serde(with = "crate::cst::serde_tokens"))]
+    pub tokens: Vec<LexicalToken>,
+}
+impl Parse for TokenGroup {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+        loop {
+            let token = track!(parser.peek(|parser| parser.parse::<LexicalToken>()))?;
+            let opens = match token {
+                LexicalToken::Symbol(ref t) => match t.value() {
+                    Symbol::Comma | Symbol::CloseParen if depth == 0 => break,
+                    Symbol::OpenParen
+                    | Symbol::OpenBrace
+                    | Symbol::OpenSquare
+                    | Symbol::DoubleLeftAngle => Some(true),
+                    Symbol::CloseParen
+                    | Symbol::CloseBrace
+                    | Symbol::CloseSquare
+                    | Symbol::DoubleRightAngle => Some(false),
+                    Symbol::Dot => track_panic!(ErrorKind::UnexpectedToken(token)),
+                    _ => None,
+                },
+                LexicalToken::Keyword(ref t) => match t.value() {
+                    Keyword::Begin
+                    | Keyword::Case
+                    | Keyword::If
+                    | Keyword::Receive
+                    | Keyword::Try => Some(true),
+                    Keyword::Fun => parser.peek(TokenGroup::is_fun_expr).ok().map(|_| true),
+                    Keyword::End => Some(false),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match opens {
+                Some(true) => depth += 1,
+                Some(false) if depth == 0 => track_panic!(ErrorKind::UnexpectedToken(token)),
+                Some(false) => depth -= 1,
+                None => {}
+            }
+            tokens.push(track!(parser.parse())?);
+        }
+        if tokens.is_empty() {
+            let token = track!(parser.parse())?;
+            track_panic!(ErrorKind::UnexpectedToken(token));
+        }
+        Ok(TokenGroup { tokens })
+    }
+}
+impl TokenGroup {
+    /// Succeeds if the following `fun` starts an anonymous or named fun (terminated by `end`).
+    fn is_fun_expr<T: TokenRead>(parser: &mut Parser<T>) -> Result<()> {
+        track!(parser.expect::<KeywordToken>(&Keyword::Fun))?;
+        if parser.expect::<SymbolToken>(&Symbol::OpenParen).is_ok() {
+            return Ok(());
+        }
+        track!(parser.parse::<VariableToken>())?;
+        track!(parser.expect::<SymbolToken>(&Symbol::OpenParen))?;
+        Ok(())
+    }
+}
+impl PositionRange for TokenGroup {
+    fn start_position(&self) -> Position {
+        self.tokens[0].start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.tokens[self.tokens.len() - 1].end_position()
+    }
+}
+impl CollectTokens for TokenGroup {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.tokens.collect_tokens(tokens);
+    }
+}
+impl Foldable for TokenGroup {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TokenGroup {
+            tokens: self.tokens.fold_with(folder),
+        }
+    }
+}
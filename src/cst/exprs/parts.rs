@@ -1,16 +1,17 @@
-use erl_tokenize::tokens::{KeywordToken, SymbolToken};
+use erl_tokenize::tokens::{AtomToken, KeywordToken, SymbolToken};
 use erl_tokenize::values::{Keyword, Symbol};
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use super::super::clauses::{CaseClause, CatchClause};
 use super::super::commons::parts::{Clauses, Sequence};
 use super::super::Pattern;
 use super::Expr;
-use crate::traits::{Parse, TokenRead};
-use crate::{Parser, Result};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
+use crate::{ErrorKind, Parser, Result};
 
 /// `Sequence<Expr>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Body {
     pub exprs: Sequence<Expr>,
 }
@@ -28,17 +29,33 @@ impl PositionRange for Body {
         self.exprs.end_position()
     }
 }
+impl CollectTokens for Body {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.exprs.collect_tokens(tokens);
+    }
+}
+impl Foldable for Body {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Body {
+            exprs: self.exprs.fold_with(folder),
+        }
+    }
+}
 
-/// `Generator` | `Filter`
+/// `Generator` | `MapGenerator` | `Filter`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Qualifier {
     Generator(Generator),
+    MapGenerator(MapGenerator),
     Filter(Expr),
 }
 impl Parse for Qualifier {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
         if let Ok(generator) = parser.transaction(Parser::parse) {
             Ok(Qualifier::Generator(generator))
+        } else if let Ok(generator) = parser.transaction(Parser::parse) {
+            Ok(Qualifier::MapGenerator(generator))
         } else {
             Ok(Qualifier::Filter(track!(parser.parse())?))
         }
@@ -48,29 +65,50 @@ impl PositionRange for Qualifier {
     fn start_position(&self) -> Position {
         match *self {
             Qualifier::Generator(ref x) => x.start_position(),
+            Qualifier::MapGenerator(ref x) => x.start_position(),
             Qualifier::Filter(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
         match *self {
             Qualifier::Generator(ref x) => x.end_position(),
+            Qualifier::MapGenerator(ref x) => x.end_position(),
             Qualifier::Filter(ref x) => x.end_position(),
         }
     }
 }
+impl CollectTokens for Qualifier {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Qualifier::Generator(ref x) => x.collect_tokens(tokens),
+            Qualifier::MapGenerator(ref x) => x.collect_tokens(tokens),
+            Qualifier::Filter(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Qualifier {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Qualifier::Generator(x) => Qualifier::Generator(x.fold_with(folder)),
+            Qualifier::MapGenerator(x) => Qualifier::MapGenerator(x.fold_with(folder)),
+            Qualifier::Filter(x) => Qualifier::Filter(x.fold_with(folder)),
+        }
+    }
+}
 
-/// `Pattern` (`<-`|`<=`) `Expr`
+/// `Pattern` `GeneratorArrow` `Expr`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Generator {
     pub pattern: Pattern,
-    pub _arrow: SymbolToken,
+    pub arrow: GeneratorArrow,
     pub source: Expr,
 }
 impl Parse for Generator {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
         Ok(Generator {
             pattern: track!(parser.parse())?,
-            _arrow: track!(parser.expect_any(&[&Symbol::LeftArrow, &Symbol::DoubleLeftArrow],))?,
+            arrow: track!(parser.parse())?,
             source: track!(parser.parse())?,
         })
     }
@@ -83,9 +121,212 @@ impl PositionRange for Generator {
         self.source.end_position()
     }
 }
+impl CollectTokens for Generator {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.pattern.collect_tokens(tokens);
+        self.arrow.collect_tokens(tokens);
+        self.source.collect_tokens(tokens);
+    }
+}
+impl Foldable for Generator {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Generator {
+            pattern: self.pattern.fold_with(folder),
+            arrow: self.arrow.fold_with(folder),
+            source: self.source.fold_with(folder),
+        }
+    }
+}
+
+/// `Pattern` `:=` `Pattern` (`<-`|`<:-`) `Expr`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapGenerator {
+    pub key: Pattern,
+    pub _relation: SymbolToken,
+    pub value: Pattern,
+    pub arrow: GeneratorArrow,
+    pub source: Expr,
+}
+impl Parse for MapGenerator {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let key = track!(parser.parse())?;
+        let _relation = track!(parser.expect(&Symbol::MapMatch))?;
+        let value = track!(parser.parse())?;
+        let arrow: GeneratorArrow = track!(parser.parse())?;
+        if arrow.is_binary() {
+            let token = arrow.last_token().clone();
+            track_panic!(ErrorKind::UnexpectedToken(token.into()));
+        }
+        Ok(MapGenerator {
+            key,
+            _relation,
+            value,
+            arrow,
+            source: track!(parser.parse())?,
+        })
+    }
+}
+impl PositionRange for MapGenerator {
+    fn start_position(&self) -> Position {
+        self.key.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.source.end_position()
+    }
+}
+impl CollectTokens for MapGenerator {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.key.collect_tokens(tokens);
+        self._relation.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+        self.arrow.collect_tokens(tokens);
+        self.source.collect_tokens(tokens);
+    }
+}
+impl Foldable for MapGenerator {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MapGenerator {
+            key: self.key.fold_with(folder),
+            _relation: self._relation.fold_with(folder),
+            value: self.value.fold_with(folder),
+            arrow: self.arrow.fold_with(folder),
+            source: self.source.fold_with(folder),
+        }
+    }
+}
+
+/// `<-` | `<=` | `<:-` | `<:=`
+///
+/// Strict generators (`<:-` and `<:=`) raise an error on elements which do not match the pattern,
+/// whereas the others skip such elements.
+/// Since the strict arrows are not tokens of `erl_tokenize`, they consist of adjacent symbols.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GeneratorArrow {
+    /// `<-`
+    List(SymbolToken),
+
+    /// `<=`
+    Binary(SymbolToken),
+
+    /// `<` `:` `-`
+    StrictList(SymbolToken, SymbolToken, SymbolToken),
+
+    /// `<` `:=`
+    StrictBinary(SymbolToken, SymbolToken),
+}
+impl GeneratorArrow {
+    /// Returns `true` if this is `<:-` or `<:=`.
+    pub fn is_strict(&self) -> bool {
+        matches!(
+            *self,
+            GeneratorArrow::StrictList(..) | GeneratorArrow::StrictBinary(..)
+        )
+    }
+
+    /// Returns `true` if this is `<=` or `<:=`.
+    pub fn is_binary(&self) -> bool {
+        matches!(
+            *self,
+            GeneratorArrow::Binary(_) | GeneratorArrow::StrictBinary(..)
+        )
+    }
+
+    pub fn first_token(&self) -> &SymbolToken {
+        match *self {
+            GeneratorArrow::List(ref t)
+            | GeneratorArrow::Binary(ref t)
+            | GeneratorArrow::StrictList(ref t, _, _)
+            | GeneratorArrow::StrictBinary(ref t, _) => t,
+        }
+    }
+
+    pub fn last_token(&self) -> &SymbolToken {
+        match *self {
+            GeneratorArrow::List(ref t)
+            | GeneratorArrow::Binary(ref t)
+            | GeneratorArrow::StrictList(_, _, ref t)
+            | GeneratorArrow::StrictBinary(_, ref t) => t,
+        }
+    }
+}
+impl Parse for GeneratorArrow {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let first: SymbolToken = track!(parser.parse())?;
+        match first.value() {
+            Symbol::LeftArrow => Ok(GeneratorArrow::List(first)),
+            Symbol::DoubleLeftArrow => Ok(GeneratorArrow::Binary(first)),
+            Symbol::Less => {
+                let second: SymbolToken =
+                    track!(parser.expect_any(&[&Symbol::Colon, &Symbol::MapMatch]))?;
+                track!(expect_adjacent(&first, &second))?;
+                if second.value() == Symbol::MapMatch {
+                    return Ok(GeneratorArrow::StrictBinary(first, second));
+                }
+                let third: SymbolToken = track!(parser.expect(&Symbol::Hyphen))?;
+                track!(expect_adjacent(&second, &third))?;
+                Ok(GeneratorArrow::StrictList(first, second, third))
+            }
+            _ => track_panic!(ErrorKind::UnexpectedToken(first.into())),
+        }
+    }
+}
+impl PositionRange for GeneratorArrow {
+    fn start_position(&self) -> Position {
+        self.first_token().start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.last_token().end_position()
+    }
+}
+impl CollectTokens for GeneratorArrow {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            GeneratorArrow::List(ref a) | GeneratorArrow::Binary(ref a) => {
+                a.collect_tokens(tokens);
+            }
+            GeneratorArrow::StrictList(ref a, ref b, ref c) => {
+                a.collect_tokens(tokens);
+                b.collect_tokens(tokens);
+                c.collect_tokens(tokens);
+            }
+            GeneratorArrow::StrictBinary(ref a, ref b) => {
+                a.collect_tokens(tokens);
+                b.collect_tokens(tokens);
+            }
+        }
+    }
+}
+impl Foldable for GeneratorArrow {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            GeneratorArrow::List(a) => GeneratorArrow::List(a.fold_with(folder)),
+            GeneratorArrow::Binary(a) => GeneratorArrow::Binary(a.fold_with(folder)),
+            GeneratorArrow::StrictList(a, b, c) => GeneratorArrow::StrictList(
+                a.fold_with(folder),
+                b.fold_with(folder),
+                c.fold_with(folder),
+            ),
+            GeneratorArrow::StrictBinary(a, b) => {
+                GeneratorArrow::StrictBinary(a.fold_with(folder), b.fold_with(folder))
+            }
+        }
+    }
+}
+
+fn expect_adjacent(left: &SymbolToken, right: &SymbolToken) -> Result<()> {
+    track_assert_eq!(
+        left.end_position().offset(),
+        right.start_position().offset(),
+        ErrorKind::UnexpectedToken(right.clone().into())
+    );
+    Ok(())
+}
 
 /// `after` `Expr` `->` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Timeout {
     pub _after: KeywordToken,
     pub duration: Expr,
@@ -110,9 +351,28 @@ impl PositionRange for Timeout {
         self.body.end_position()
     }
 }
+impl CollectTokens for Timeout {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._after.collect_tokens(tokens);
+        self.duration.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for Timeout {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Timeout {
+            _after: self._after.fold_with(folder),
+            duration: self.duration.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
 
 /// `of` `Clauses<CaseClause>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TryOf {
     pub _of: KeywordToken,
     pub clauses: Clauses<CaseClause>,
@@ -133,9 +393,24 @@ impl PositionRange for TryOf {
         self.clauses.end_position()
     }
 }
+impl CollectTokens for TryOf {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._of.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+    }
+}
+impl Foldable for TryOf {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TryOf {
+            _of: self._of.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+        }
+    }
+}
 
 /// `catch` `Clauses<CatchClause>`a
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TryCatch {
     pub _catch: KeywordToken,
     pub clauses: Clauses<CatchClause>,
@@ -156,9 +431,62 @@ impl PositionRange for TryCatch {
         self.clauses.end_position()
     }
 }
+impl CollectTokens for TryCatch {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._catch.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+    }
+}
+impl Foldable for TryCatch {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TryCatch {
+            _catch: self._catch.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+        }
+    }
+}
+
+/// `else` `Clauses<CaseClause>`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaybeElse {
+    pub _else: AtomToken,
+    pub clauses: Clauses<CaseClause>,
+}
+impl Parse for MaybeElse {
+    fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        Ok(MaybeElse {
+            _else: track!(parser.expect("else"))?,
+            clauses: track!(parser.parse())?,
+        })
+    }
+}
+impl PositionRange for MaybeElse {
+    fn start_position(&self) -> Position {
+        self._else.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.clauses.end_position()
+    }
+}
+impl CollectTokens for MaybeElse {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._else.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+    }
+}
+impl Foldable for MaybeElse {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        MaybeElse {
+            _else: self._else.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+        }
+    }
+}
 
 /// `after` `Body`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TryAfter {
     pub _after: KeywordToken,
     pub body: Body,
@@ -179,3 +507,17 @@ impl PositionRange for TryAfter {
         self.body.end_position()
     }
 }
+impl CollectTokens for TryAfter {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._after.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+    }
+}
+impl Foldable for TryAfter {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TryAfter {
+            _after: self._after.fold_with(folder),
+            body: self.body.fold_with(folder),
+        }
+    }
+}
@@ -1,12 +1,21 @@
-use erl_tokenize::tokens::AtomToken;
+use erl_tokenize::tokens::{AtomToken, SymbolToken};
 use erl_tokenize::values::Symbol;
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 
+use super::exprs::parts::Qualifier;
 use super::forms;
-use crate::traits::{Parse, TokenRead};
-use crate::{ErrorKind, Parser, Result};
+use super::node::Node;
+use super::{Expr, GuardTest, Literal, Pattern};
+use crate::messages::Message;
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
+use crate::{
+    ErrorKind, IllegalGuardExpr, OtpRelease, Parser, Result, Span, SyntaxFeature,
+    UnsupportedFeature,
+};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum Form {
     ModuleAttr(forms::ModuleAttr),
@@ -14,31 +23,121 @@ pub enum Form {
     ExportTypeAttr(forms::ExportTypeAttr),
     ImportAttr(forms::ImportAttr),
     FileAttr(forms::FileAttr),
+    CompileAttr(forms::CompileAttr),
+    VsnAttr(forms::VsnAttr),
+    FeatureAttr(forms::FeatureAttr),
+    BehaviourAttr(forms::BehaviourAttr),
+    OnLoadAttr(forms::OnLoadAttr),
+    DialyzerAttr(forms::DialyzerAttr),
+    DocAttr(forms::DocAttr),
     WildAttr(forms::WildAttr),
+    ExtensionAttr(forms::ExtensionAttr),
     FunSpec(forms::FunSpec),
     CallbackSpec(forms::CallbackSpec),
     FunDecl(forms::FunDecl),
     RecordDecl(forms::RecordDecl),
     TypeDecl(forms::TypeDecl),
+    Directive(forms::Directive),
+    Error(forms::ErrorForm),
+    Unparsed(forms::UnparsedForm),
 }
 impl Parse for Form {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
+        track!(parser.start_form())?;
         let kind = track!(FormKind::guess(parser))?;
+        let form = if let Some(description) = kind.description() {
+            track!(parser.with_context(description, |parser| Form::parse_kind(parser, kind)))?
+        } else {
+            track!(Form::parse_kind(parser, kind))?
+        };
+        if parser.is_strict() {
+            if let Some(e) = form.illegal_guard_expr() {
+                track_panic!(ErrorKind::IllegalGuardExpr(Box::new(e)));
+            }
+        }
+        if let Form::FeatureAttr(ref x) = form {
+            parser.set_feature(x.feature_name.value(), x.is_enabled());
+        }
+        if let Some(release) = parser.otp_release() {
+            if let Some(e) =
+                form.unsupported_feature(release, |name| parser.is_feature_enabled(name))
+            {
+                track_panic!(ErrorKind::UnsupportedFeature(Box::new(e)));
+            }
+        }
+        Ok(form)
+    }
+}
+impl Form {
+    /// Returns the first expression in the guards of this form which is not allowed in guards.
+    fn illegal_guard_expr(&self) -> Option<IllegalGuardExpr> {
+        let mut stack = vec![Node::Form(self)];
+        while let Some(node) = stack.pop() {
+            if let Node::GuardTest(x) = node {
+                if let Some(e) = x.illegal_expr() {
+                    return Some(e);
+                }
+            }
+            stack.extend(node.children().into_iter().rev());
+        }
+        None
+    }
+
+    /// Returns the first syntax in this form which is not accepted by `release`
+    /// (with the features for which `is_enabled` returns `true`).
+    fn unsupported_feature<F>(
+        &self,
+        release: OtpRelease,
+        is_enabled: F,
+    ) -> Option<UnsupportedFeature>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut stack = vec![Node::Form(self)];
+        while let Some(node) = stack.pop() {
+            for (feature, span) in syntax_features(node) {
+                let feature_attr = feature
+                    .feature_attr(release)
+                    .filter(|name| !is_enabled(name));
+                if feature.required_release() > release || feature_attr.is_some() {
+                    return Some(UnsupportedFeature {
+                        feature,
+                        required_release: feature.required_release(),
+                        feature_attr: feature_attr.map(str::to_owned),
+                        span,
+                    });
+                }
+            }
+            stack.extend(node.children().into_iter().rev());
+        }
+        None
+    }
+
+    fn parse_kind<T: TokenRead>(parser: &mut Parser<T>, kind: FormKind) -> Result<Self> {
         Ok(match kind {
             FormKind::ModuleAttr => Form::ModuleAttr(track!(parser.parse())?),
             FormKind::ExportAttr => Form::ExportAttr(track!(parser.parse())?),
             FormKind::ExportTypeAttr => Form::ExportTypeAttr(track!(parser.parse())?),
             FormKind::ImportAttr => Form::ImportAttr(track!(parser.parse())?),
             FormKind::FileAttr => Form::FileAttr(track!(parser.parse())?),
+            FormKind::CompileAttr => Form::CompileAttr(track!(parser.parse())?),
+            FormKind::VsnAttr => Form::VsnAttr(track!(parser.parse())?),
+            FormKind::FeatureAttr => Form::FeatureAttr(track!(parser.parse())?),
+            FormKind::BehaviourAttr => Form::BehaviourAttr(track!(parser.parse())?),
+            FormKind::OnLoadAttr => Form::OnLoadAttr(track!(parser.parse())?),
+            FormKind::DialyzerAttr => Form::DialyzerAttr(track!(parser.parse())?),
+            FormKind::DocAttr | FormKind::ModuleDocAttr => Form::DocAttr(track!(parser.parse())?),
             FormKind::WildAttr => Form::WildAttr(track!(parser.parse())?),
+            FormKind::ExtensionAttr => Form::ExtensionAttr(track!(parser.parse())?),
             FormKind::FunSpec => Form::FunSpec(track!(parser.parse())?),
             FormKind::CallbackSpec => Form::CallbackSpec(track!(parser.parse())?),
             FormKind::FunDecl => Form::FunDecl(track!(parser.parse())?),
             FormKind::RecordDecl => Form::RecordDecl(track!(parser.parse())?),
             FormKind::TypeDecl => Form::TypeDecl(track!(parser.parse())?),
+            FormKind::Directive => Form::Directive(track!(parser.parse())?),
         })
     }
 }
@@ -50,12 +149,23 @@ impl PositionRange for Form {
             Form::ExportTypeAttr(ref t) => t.start_position(),
             Form::ImportAttr(ref t) => t.start_position(),
             Form::FileAttr(ref t) => t.start_position(),
+            Form::CompileAttr(ref t) => t.start_position(),
+            Form::VsnAttr(ref t) => t.start_position(),
+            Form::FeatureAttr(ref t) => t.start_position(),
+            Form::BehaviourAttr(ref t) => t.start_position(),
+            Form::OnLoadAttr(ref t) => t.start_position(),
+            Form::DialyzerAttr(ref t) => t.start_position(),
+            Form::DocAttr(ref t) => t.start_position(),
             Form::WildAttr(ref t) => t.start_position(),
+            Form::ExtensionAttr(ref t) => t.start_position(),
             Form::FunSpec(ref t) => t.start_position(),
             Form::CallbackSpec(ref t) => t.start_position(),
             Form::FunDecl(ref t) => t.start_position(),
             Form::RecordDecl(ref t) => t.start_position(),
             Form::TypeDecl(ref t) => t.start_position(),
+            Form::Directive(ref t) => t.start_position(),
+            Form::Error(ref t) => t.start_position(),
+            Form::Unparsed(ref t) => t.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -65,36 +175,162 @@ impl PositionRange for Form {
             Form::ExportTypeAttr(ref t) => t.end_position(),
             Form::ImportAttr(ref t) => t.end_position(),
             Form::FileAttr(ref t) => t.end_position(),
+            Form::CompileAttr(ref t) => t.end_position(),
+            Form::VsnAttr(ref t) => t.end_position(),
+            Form::FeatureAttr(ref t) => t.end_position(),
+            Form::BehaviourAttr(ref t) => t.end_position(),
+            Form::OnLoadAttr(ref t) => t.end_position(),
+            Form::DialyzerAttr(ref t) => t.end_position(),
+            Form::DocAttr(ref t) => t.end_position(),
             Form::WildAttr(ref t) => t.end_position(),
+            Form::ExtensionAttr(ref t) => t.end_position(),
             Form::FunSpec(ref t) => t.end_position(),
             Form::CallbackSpec(ref t) => t.end_position(),
             Form::FunDecl(ref t) => t.end_position(),
             Form::RecordDecl(ref t) => t.end_position(),
             Form::TypeDecl(ref t) => t.end_position(),
+            Form::Directive(ref t) => t.end_position(),
+            Form::Error(ref t) => t.end_position(),
+            Form::Unparsed(ref t) => t.end_position(),
+        }
+    }
+}
+impl CollectTokens for Form {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Form::ModuleAttr(ref x) => x.collect_tokens(tokens),
+            Form::ExportAttr(ref x) => x.collect_tokens(tokens),
+            Form::ExportTypeAttr(ref x) => x.collect_tokens(tokens),
+            Form::ImportAttr(ref x) => x.collect_tokens(tokens),
+            Form::FileAttr(ref x) => x.collect_tokens(tokens),
+            Form::CompileAttr(ref x) => x.collect_tokens(tokens),
+            Form::VsnAttr(ref x) => x.collect_tokens(tokens),
+            Form::FeatureAttr(ref x) => x.collect_tokens(tokens),
+            Form::BehaviourAttr(ref x) => x.collect_tokens(tokens),
+            Form::OnLoadAttr(ref x) => x.collect_tokens(tokens),
+            Form::DialyzerAttr(ref x) => x.collect_tokens(tokens),
+            Form::DocAttr(ref x) => x.collect_tokens(tokens),
+            Form::WildAttr(ref x) => x.collect_tokens(tokens),
+            Form::ExtensionAttr(ref x) => x.collect_tokens(tokens),
+            Form::FunSpec(ref x) => x.collect_tokens(tokens),
+            Form::CallbackSpec(ref x) => x.collect_tokens(tokens),
+            Form::FunDecl(ref x) => x.collect_tokens(tokens),
+            Form::RecordDecl(ref x) => x.collect_tokens(tokens),
+            Form::TypeDecl(ref x) => x.collect_tokens(tokens),
+            Form::Directive(ref x) => x.collect_tokens(tokens),
+            Form::Error(ref x) => x.collect_tokens(tokens),
+            Form::Unparsed(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Form {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Form::ModuleAttr(x) => Form::ModuleAttr(x.fold_with(folder)),
+            Form::ExportAttr(x) => Form::ExportAttr(x.fold_with(folder)),
+            Form::ExportTypeAttr(x) => Form::ExportTypeAttr(x.fold_with(folder)),
+            Form::ImportAttr(x) => Form::ImportAttr(x.fold_with(folder)),
+            Form::FileAttr(x) => Form::FileAttr(x.fold_with(folder)),
+            Form::CompileAttr(x) => Form::CompileAttr(x.fold_with(folder)),
+            Form::VsnAttr(x) => Form::VsnAttr(x.fold_with(folder)),
+            Form::FeatureAttr(x) => Form::FeatureAttr(x.fold_with(folder)),
+            Form::BehaviourAttr(x) => Form::BehaviourAttr(x.fold_with(folder)),
+            Form::OnLoadAttr(x) => Form::OnLoadAttr(x.fold_with(folder)),
+            Form::DialyzerAttr(x) => Form::DialyzerAttr(x.fold_with(folder)),
+            Form::DocAttr(x) => Form::DocAttr(x.fold_with(folder)),
+            Form::WildAttr(x) => Form::WildAttr(x.fold_with(folder)),
+            Form::ExtensionAttr(x) => Form::ExtensionAttr(x.fold_with(folder)),
+            Form::FunSpec(x) => Form::FunSpec(x.fold_with(folder)),
+            Form::CallbackSpec(x) => Form::CallbackSpec(x.fold_with(folder)),
+            Form::FunDecl(x) => Form::FunDecl(x.fold_with(folder)),
+            Form::RecordDecl(x) => Form::RecordDecl(x.fold_with(folder)),
+            Form::TypeDecl(x) => Form::TypeDecl(x.fold_with(folder)),
+            Form::Directive(x) => Form::Directive(x.fold_with(folder)),
+            Form::Error(x) => Form::Error(x.fold_with(folder)),
+            Form::Unparsed(x) => Form::Unparsed(x.fold_with(folder)),
         }
     }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_form(self)
+    }
+}
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FormKind {
     ModuleAttr,
     ExportAttr,
     ExportTypeAttr,
     ImportAttr,
     FileAttr,
+    CompileAttr,
+    VsnAttr,
+    FeatureAttr,
+    BehaviourAttr,
+    OnLoadAttr,
+    DialyzerAttr,
+    DocAttr,
+    ModuleDocAttr,
     WildAttr,
+    ExtensionAttr,
     FunSpec,
     CallbackSpec,
     FunDecl,
     RecordDecl,
     TypeDecl,
+    Directive,
 }
 impl FormKind {
+    /// Returns the description of the form used in syntax error messages.
+    ///
+    /// Function declarations have no description since their clauses describe themselves.
+    fn description(&self) -> Option<Message> {
+        let attr = |name| Some(Message::new("attribute_context").arg("name", name));
+        match *self {
+            FormKind::ModuleAttr => attr("module"),
+            FormKind::ExportAttr => attr("export"),
+            FormKind::ExportTypeAttr => attr("export_type"),
+            FormKind::ImportAttr => attr("import"),
+            FormKind::FileAttr => attr("file"),
+            FormKind::CompileAttr => attr("compile"),
+            FormKind::VsnAttr => attr("vsn"),
+            FormKind::FeatureAttr => attr("feature"),
+            FormKind::BehaviourAttr => attr("behaviour"),
+            FormKind::OnLoadAttr => attr("on_load"),
+            FormKind::DialyzerAttr => attr("dialyzer"),
+            FormKind::DocAttr => attr("doc"),
+            FormKind::ModuleDocAttr => attr("moduledoc"),
+            FormKind::WildAttr | FormKind::ExtensionAttr => {
+                Some(Message::new("wild_attribute_context"))
+            }
+            FormKind::FunSpec => attr("spec"),
+            FormKind::CallbackSpec => attr("callback"),
+            FormKind::FunDecl => None,
+            FormKind::RecordDecl => attr("record"),
+            FormKind::TypeDecl => Some(Message::new("type_decl_context")),
+            FormKind::Directive => Some(Message::new("directive_context")),
+        }
+    }
     pub fn guess<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
-        parser.peek(|parser| {
+        let is_directive = parser.is_no_preprocess()
+            && parser
+                .peek(|parser| {
+                    track!(parser.expect::<SymbolToken>(&Symbol::Hyphen))?;
+                    track!(parser.parse::<forms::DirectiveName>())
+                })
+                .is_ok();
+        if is_directive {
+            return Ok(FormKind::Directive);
+        }
+        let kind = track!(parser.peek(|parser| {
             Ok(match track!(parser.parse())? {
                 LexicalToken::Symbol(ref t) if t.value() == Symbol::Hyphen => {
                     match track!(parser.parse::<AtomToken>())?.value() {
@@ -103,16 +339,85 @@ impl FormKind {
                         "export_type" => FormKind::ExportTypeAttr,
                         "import" => FormKind::ImportAttr,
                         "file" => FormKind::FileAttr,
+                        "compile" => FormKind::CompileAttr,
+                        "vsn" => FormKind::VsnAttr,
+                        "feature" => FormKind::FeatureAttr,
+                        "behaviour" | "behavior" => FormKind::BehaviourAttr,
+                        "on_load" => FormKind::OnLoadAttr,
+                        "dialyzer" => FormKind::DialyzerAttr,
+                        "doc" => FormKind::DocAttr,
+                        "moduledoc" => FormKind::ModuleDocAttr,
                         "spec" => FormKind::FunSpec,
                         "callback" => FormKind::CallbackSpec,
                         "record" => FormKind::RecordDecl,
-                        "type" | "opaque" => FormKind::TypeDecl,
+                        "type" | "opaque" | "nominal" => FormKind::TypeDecl,
+                        name if parser.extensions().get_attribute(name).is_some() => {
+                            FormKind::ExtensionAttr
+                        }
                         _ => FormKind::WildAttr,
                     }
                 }
                 LexicalToken::Atom(_) => FormKind::FunDecl,
                 token => track_panic!(ErrorKind::UnexpectedToken(token)),
             })
-        })
+        }))?;
+
+        // In no-preprocess mode, attributes such as `-behaviour(?MODULE).` are kept as is
+        if parser.is_no_preprocess() {
+            let is_typed = match kind {
+                FormKind::BehaviourAttr => parser.peek(forms::BehaviourAttr::parse).is_ok(),
+                FormKind::OnLoadAttr => parser.peek(forms::OnLoadAttr::parse).is_ok(),
+                _ => true,
+            };
+            if !is_typed {
+                return Ok(FormKind::WildAttr);
+            }
+        }
+        Ok(kind)
+    }
+}
+
+/// Returns the syntax features used by `node` (excluding its children).
+fn syntax_features(node: Node) -> Vec<(SyntaxFeature, Span)> {
+    let mut features = Vec::new();
+    let literal = match node {
+        Node::Expr(Expr::Literal(x))
+        | Node::Pattern(Pattern::Literal(x))
+        | Node::GuardTest(GuardTest::Literal(x)) => Some(x),
+        _ => None,
+    };
+    if let Some(x @ Literal::TripleQuotedString(_)) = literal {
+        features.push((SyntaxFeature::TripleQuotedString, Span::of(x)));
+    }
+    let qualifiers = match node {
+        Node::Expr(Expr::Maybe(x)) => {
+            features.push((SyntaxFeature::MaybeExpr, Span::of(&**x)));
+            return features;
+        }
+        Node::Expr(Expr::MaybeMatch(x)) => {
+            features.push((SyntaxFeature::MaybeExpr, Span::of(&**x)));
+            return features;
+        }
+        Node::Expr(Expr::ListComprehension(x)) => &x.qualifiers,
+        Node::Expr(Expr::BitsComprehension(x)) => &x.qualifiers,
+        Node::Expr(Expr::MapComprehension(x)) => {
+            features.push((SyntaxFeature::MapComprehension, Span::of(&**x)));
+            &x.qualifiers
+        }
+        _ => return features,
+    };
+    for q in qualifiers.iter() {
+        let arrow = match *q {
+            Qualifier::Generator(ref g) => &g.arrow,
+            Qualifier::MapGenerator(ref g) => {
+                features.push((SyntaxFeature::MapComprehension, Span::of(g)));
+                &g.arrow
+            }
+            Qualifier::Filter(_) => continue,
+        };
+        if arrow.is_strict() {
+            features.push((SyntaxFeature::StrictGenerator, Span::of(arrow)));
+        }
     }
+    features
 }
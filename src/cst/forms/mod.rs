@@ -1,19 +1,27 @@
-use erl_tokenize::tokens::{AtomToken, IntegerToken, StringToken, SymbolToken, VariableToken};
-use erl_tokenize::values::Symbol;
+use erl_tokenize::tokens::{
+    AtomToken, IntegerToken, KeywordToken, StringToken, SymbolToken, VariableToken,
+};
+use erl_tokenize::values::{Keyword, Symbol};
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use num::ToPrimitive;
 
 use self::parts::RecordFieldDecl;
 use crate::cst::clauses::{FunDeclClause, SpecClause};
-use crate::cst::commons::parts::{Args, Clauses, ModulePrefix, NameAndArity};
+use crate::cst::commons::parts::{
+    Args, BinaryOp, Clauses, ExtensionPart, ModulePrefix, NameAndArity,
+};
 use crate::cst::commons::{ProperList, Tuple};
-use crate::cst::Type;
-use crate::traits::{Parse, TokenRead};
-use crate::{Parser, Result};
+use crate::cst::exprs::TokenGroup;
+use crate::cst::{Expr, Literal, Type};
+use crate::extension;
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, Preprocessor, TokenRead};
+use crate::{ErrorKind, HeadMismatch, Parser, Result, Span};
 
 pub mod parts;
 
 /// `-` `module` `(` `AtomToken` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleAttr {
     pub _hyphen: SymbolToken,
     pub _module: AtomToken,
@@ -37,10 +45,9 @@ impl Parse for ModuleAttr {
         };
         {
             let module = &this.module_name;
-            let reader = parser.reader_mut();
             let module_string = StringToken::from_value(module.value(), module.start_position());
-            reader.define_macro("MODULE", vec![module.clone().into()]);
-            reader.define_macro("MODULE_STRING", vec![module_string.into()]);
+            parser.define_macro("MODULE", vec![module.clone().into()]);
+            parser.define_macro("MODULE_STRING", vec![module_string.into()]);
         }
         Ok(this)
     }
@@ -53,9 +60,32 @@ impl PositionRange for ModuleAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for ModuleAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._module.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.module_name.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for ModuleAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ModuleAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _module: self._module.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            module_name: self.module_name.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `export` `(` `ProperList<NameAndArity>` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExportAttr {
     pub _hyphen: SymbolToken,
     pub _export: AtomToken,
@@ -87,9 +117,32 @@ impl PositionRange for ExportAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for ExportAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._export.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.exports.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for ExportAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ExportAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _export: self._export.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            exports: self.exports.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `export_type` `(` `ProperList<NameAndArity>` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExportTypeAttr {
     pub _hyphen: SymbolToken,
     pub _export_type: AtomToken,
@@ -121,9 +174,32 @@ impl PositionRange for ExportTypeAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for ExportTypeAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._export_type.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.exports.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for ExportTypeAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ExportTypeAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _export_type: self._export_type.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            exports: self.exports.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `import` `(` `AtomToken` `,` `ProperList<NameAndArity>` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportAttr {
     pub _hyphen: SymbolToken,
     pub _import: AtomToken,
@@ -159,9 +235,36 @@ impl PositionRange for ImportAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for ImportAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._import.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.module_name.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.imports.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for ImportAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ImportAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _import: self._import.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            module_name: self.module_name.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            imports: self.imports.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `file` `(` `StringToken` `,` `IntegerToken` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileAttr {
     pub _hyphen: SymbolToken,
     pub _file: AtomToken,
@@ -197,17 +300,647 @@ impl PositionRange for FileAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for FileAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._file.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.file_name.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.line_num.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for FileAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FileAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _file: self._file.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            file_name: self.file_name.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            line_num: self.line_num.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `compile` `(` `Expr` `)` `.`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompileAttr {
+    pub _hyphen: SymbolToken,
+    pub _compile: AtomToken,
+    pub _open: SymbolToken,
+    pub options: Expr,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl CompileAttr {
+    /// Returns the modules given by the `{parse_transform, Module}` options in the source order.
+    pub fn parse_transforms(&self) -> Vec<&str> {
+        let options: Vec<&Expr> = match self.options {
+            Expr::List(ref x) => x.iter().collect(),
+            ref x => vec![x],
+        };
+        options
+            .into_iter()
+            .filter_map(|option| match *option {
+                Expr::Tuple(ref x) => match x.iter().collect::<Vec<_>>()[..] {
+                    [Expr::Literal(Literal::Atom(ref k)), Expr::Literal(Literal::Atom(ref m))]
+                        if k.value() == "parse_transform" =>
+                    {
+                        Some(m.value())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+}
+impl Parse for CompileAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(CompileAttr {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            _compile: track!(parser.expect("compile"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            options: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for CompileAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for CompileAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._compile.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.options.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for CompileAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        CompileAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _compile: self._compile.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            options: self.options.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `vsn` `(` `Expr` `)` `.`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VsnAttr {
+    pub _hyphen: SymbolToken,
+    pub _vsn: AtomToken,
+    pub _open: SymbolToken,
+    pub vsn: Expr,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl Parse for VsnAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(VsnAttr {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            _vsn: track!(parser.expect("vsn"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            vsn: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for VsnAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for VsnAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._vsn.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.vsn.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for VsnAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        VsnAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _vsn: self._vsn.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            vsn: self.vsn.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `feature` `(` `AtomToken` `,` `AtomToken` `)` `.`
+///
+/// For example, `-feature(maybe_expr, enable).`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeatureAttr {
+    pub _hyphen: SymbolToken,
+    pub _feature: AtomToken,
+    pub _open: SymbolToken,
+    pub feature_name: AtomToken,
+    pub _comma: SymbolToken,
+    pub action: AtomToken,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl FeatureAttr {
+    /// Returns `true` if the action is `enable` (i.e., not `disable`).
+    pub fn is_enabled(&self) -> bool {
+        self.action.value() == "enable"
+    }
+}
+impl Parse for FeatureAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(FeatureAttr {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            _feature: track!(parser.expect("feature"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            feature_name: track!(parser.parse())?,
+            _comma: track!(parser.expect(&Symbol::Comma))?,
+            action: track!(parser.expect_any(&["enable", "disable"]))?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for FeatureAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for FeatureAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._feature.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.feature_name.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.action.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for FeatureAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FeatureAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _feature: self._feature.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            feature_name: self.feature_name.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            action: self.action.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `behaviour` `(` `AtomToken` `)` `.`
+///
+/// The American spelling `-behavior` is also accepted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BehaviourAttr {
+    pub _hyphen: SymbolToken,
+    pub _behaviour: AtomToken,
+    pub _open: SymbolToken,
+    pub behaviour_name: AtomToken,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl Parse for BehaviourAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let _behaviour: AtomToken = track!(parser.parse())?;
+        if !matches!(_behaviour.value(), "behaviour" | "behavior") {
+            track_panic!(ErrorKind::UnexpectedToken(_behaviour.into()));
+        }
+        Ok(BehaviourAttr {
+            _hyphen,
+            _behaviour,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            behaviour_name: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for BehaviourAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for BehaviourAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._behaviour.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.behaviour_name.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for BehaviourAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BehaviourAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _behaviour: self._behaviour.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            behaviour_name: self.behaviour_name.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `on_load` `(` `NameAndArity` `)` `.`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnLoadAttr {
+    pub _hyphen: SymbolToken,
+    pub _on_load: AtomToken,
+    pub _open: SymbolToken,
+    pub fun: NameAndArity,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl OnLoadAttr {
+    pub fn fun_name(&self) -> &str {
+        self.fun.name.value()
+    }
+    pub fn arity(&self) -> usize {
+        self.fun.arity.value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+impl Parse for OnLoadAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(OnLoadAttr {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            _on_load: track!(parser.expect("on_load"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            fun: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for OnLoadAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for OnLoadAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._on_load.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.fun.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for OnLoadAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        OnLoadAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _on_load: self._on_load.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            fun: self.fun.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `dialyzer` `(` `Expr` `)` `.`
+///
+/// For example, `-dialyzer({nowarn_function, f/1}).` and `-dialyzer([no_return, no_match]).`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialyzerAttr {
+    pub _hyphen: SymbolToken,
+    pub _dialyzer: AtomToken,
+    pub _open: SymbolToken,
+    pub options: Expr,
+    pub _close: SymbolToken,
+    pub _dot: SymbolToken,
+}
+impl DialyzerAttr {
+    /// Returns the options in the source order.
+    ///
+    /// An option is either a warning option (e.g., `no_return`) applied to the whole module,
+    /// or a tuple of warning options and the functions to which they are applied
+    /// (e.g., `{[no_return, no_match], [f/1, g/2]}`).
+    /// Malformed options are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erl_parse::cst::Form;
+    /// use erl_parse::cst::forms::DialyzerOption;
+    /// use erl_parse::{Parser, TokenReader};
+    /// use erl_tokenize::Lexer;
+    ///
+    /// let text = "-dialyzer([no_return, {nowarn_function, f/1}, {[no_match], [g/0, h/2]}]).";
+    /// let form: Form = Parser::new(TokenReader::new(Lexer::new(text))).parse().unwrap();
+    /// let attr = match form {
+    ///     Form::DialyzerAttr(ref x) => x,
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!(
+    ///     attr.options(),
+    ///     [
+    ///         DialyzerOption { warnings: vec!["no_return"], functions: vec![] },
+    ///         DialyzerOption { warnings: vec!["nowarn_function"], functions: vec![("f", 1)] },
+    ///         DialyzerOption { warnings: vec!["no_match"], functions: vec![("g", 0), ("h", 2)] },
+    ///     ]
+    /// );
+    /// ```
+    pub fn options(&self) -> Vec<DialyzerOption<'_>> {
+        let options: Vec<&Expr> = match self.options {
+            Expr::List(ref x) => x.iter().collect(),
+            ref x => vec![x],
+        };
+        options
+            .into_iter()
+            .filter_map(|option| match *option {
+                Expr::Tuple(ref x) => match x.iter().collect::<Vec<_>>()[..] {
+                    [warnings, functions] => Some(DialyzerOption {
+                        warnings: list_or_single(warnings, literal_atom)?,
+                        functions: list_or_single(functions, fun_name_and_arity)?,
+                    }),
+                    _ => None,
+                },
+                ref x => Some(DialyzerOption {
+                    warnings: vec![literal_atom(x)?],
+                    functions: Vec::new(),
+                }),
+            })
+            .collect()
+    }
+}
+impl Parse for DialyzerAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(DialyzerAttr {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            _dialyzer: track!(parser.expect("dialyzer"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            options: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for DialyzerAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for DialyzerAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._dialyzer.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.options.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for DialyzerAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        DialyzerAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            _dialyzer: self._dialyzer.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            options: self.options.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// Option of a `-dialyzer` attribute (see `DialyzerAttr::options`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialyzerOption<'a> {
+    /// The warning options (e.g., `nowarn_function` or `no_return`).
+    pub warnings: Vec<&'a str>,
+
+    /// The functions to which the warning options are applied (empty for the whole module).
+    pub functions: Vec<(&'a str, usize)>,
+}
+
+fn list_or_single<'a, T, F>(expr: &'a Expr, f: F) -> Option<Vec<T>>
+where
+    F: Fn(&'a Expr) -> Option<T>,
+{
+    match *expr {
+        Expr::List(ref x) => x.iter().map(f).collect(),
+        ref x => Some(vec![f(x)?]),
+    }
+}
+
+fn literal_atom(expr: &Expr) -> Option<&str> {
+    match *expr {
+        Expr::Literal(Literal::Atom(ref t)) => Some(t.value()),
+        _ => None,
+    }
+}
+
+fn fun_name_and_arity(expr: &Expr) -> Option<(&str, usize)> {
+    match *expr {
+        Expr::BinaryOpCall(ref x) => match (&x.left, &x.op, &x.right) {
+            (
+                Expr::Literal(Literal::Atom(ref f)),
+                BinaryOp::FloatDiv(_),
+                Expr::Literal(Literal::Integer(ref a)),
+            ) => Some((f.value(), a.value().to_usize()?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `-` `doc`|`moduledoc` `Option<(>` `Expr` `Option<)>` `.`
+///
+/// The value is a string (e.g., `-doc "Returns the foo.".`), a metadata map (e.g., `-doc #{since => "1.0"}.`),
+/// `false`, `hidden` or `{file, Path}`.
+/// Unlike the other attributes, the parentheses around the value are usually omitted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocAttr {
+    pub _hyphen: SymbolToken,
+    pub attr_name: AtomToken,
+    pub _open: Option<SymbolToken>,
+    pub value: Expr,
+    pub _close: Option<SymbolToken>,
+    pub _dot: SymbolToken,
+}
+impl DocAttr {
+    /// Returns `true` if this is a `-moduledoc` attribute.
+    pub fn is_module_doc(&self) -> bool {
+        self.attr_name.value() == "moduledoc"
+    }
+
+    /// Returns the documentation text if the value is a string.
+    pub fn text(&self) -> Option<String> {
+        match self.value {
+            Expr::Literal(ref x) => x.string_value(),
+            _ => None,
+        }
+    }
+}
+impl Parse for DocAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let attr_name = track!(parser.expect_any(&["doc", "moduledoc"]))?;
+        let parenthesized = parser
+            .transaction(|parser| {
+                let _open = track!(parser.expect(&Symbol::OpenParen))?;
+                let value = track!(parser.parse())?;
+                let _close = track!(parser.expect(&Symbol::CloseParen))?;
+                let _dot = track!(parser.expect(&Symbol::Dot))?;
+                Ok((_open, value, _close, _dot))
+            })
+            .ok();
+        if let Some((_open, value, _close, _dot)) = parenthesized {
+            return Ok(DocAttr {
+                _hyphen,
+                attr_name,
+                _open: Some(_open),
+                value,
+                _close: Some(_close),
+                _dot,
+            });
+        }
+        Ok(DocAttr {
+            _hyphen,
+            attr_name,
+            _open: None,
+            value: track!(parser.parse())?,
+            _close: None,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for DocAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for DocAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self.attr_name.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for DocAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        DocAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            attr_name: self.attr_name.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            value: self.value.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `AtomToken` `(` `Vec<LexicalToken>` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WildAttr {
     pub _hyphen: SymbolToken,
     pub attr_name: AtomToken,
     pub _open: SymbolToken,
+    #[cfg_attr(feature = "serde", serde(with = "crate::cst::serde_tokens"))]
     pub attr_value: Vec<LexicalToken>,
     pub _close: SymbolToken,
     pub _dot: SymbolToken,
 }
+impl WildAttr {
+    /// Parses the value of this attribute as an expression.
+    ///
+    /// Returns `None` if the value is not a single expression.
+    pub fn parse_value(&self) -> Option<Expr> {
+        let mut parser = Parser::new(Tokens(self.attr_value.iter().rev().cloned().collect()));
+        let expr: Expr = parser.parse().ok()?;
+        if !parser.eos().ok()? {
+            return None;
+        }
+        Some(expr)
+    }
+}
 impl Parse for WildAttr {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
@@ -250,28 +983,124 @@ impl PositionRange for WildAttr {
         self._dot.end_position()
     }
 }
+impl CollectTokens for WildAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self.attr_name.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.attr_value.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for WildAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        WildAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            attr_name: self.attr_name.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            attr_value: self.attr_value.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `AtomToken` `Vec<ExtensionPart>` `.`
+///
+/// Attribute parsed by the `extension::SyntaxExtension` registered for its name
+/// (see `extension::Extensions::attribute`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionAttr {
+    pub _hyphen: SymbolToken,
+    pub attr_name: AtomToken,
+    pub parts: Vec<ExtensionPart>,
+    pub _dot: SymbolToken,
+}
+impl Parse for ExtensionAttr {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let attr_name: AtomToken = track!(parser.parse())?;
+        let extension = track_assert_some!(
+            parser.extensions().get_attribute(attr_name.value()),
+            ErrorKind::UnexpectedToken(attr_name.clone().into())
+        );
+        Ok(ExtensionAttr {
+            _hyphen,
+            parts: track!(extension::run(parser, &*extension))?,
+            attr_name,
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for ExtensionAttr {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for ExtensionAttr {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self.attr_name.collect_tokens(tokens);
+        self.parts.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for ExtensionAttr {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ExtensionAttr {
+            _hyphen: self._hyphen.fold_with(folder),
+            attr_name: self.attr_name.fold_with(folder),
+            parts: self.parts.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `spec` `Option<ModulePrefix>` `AtomToken` `Clauses<SpecClause>` `.`
+///
+/// The part following `spec` may be enclosed in parentheses (e.g., `-spec(foo() -> ok).`).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunSpec {
     pub _hyphen: SymbolToken,
     pub _spec: AtomToken,
+    pub _open: Option<SymbolToken>,
     pub module: Option<ModulePrefix<AtomToken>>,
     pub fun_name: AtomToken,
     pub clauses: Clauses<SpecClause>,
+    pub _close: Option<SymbolToken>,
     pub _dot: SymbolToken,
 }
+impl FunSpec {
+    /// Returns the arity of the function (taken from the first clause).
+    pub fn arity(&self) -> usize {
+        self.clauses.item.args.len()
+    }
+}
 impl Parse for FunSpec {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let _spec = track!(parser.expect("spec"))?;
+        let _open = parse_open_paren(parser);
         Ok(FunSpec {
-            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
-            _spec: track!(parser.expect("spec"))?,
+            _hyphen,
+            _spec,
             module: track!(parser.parse())?,
             fun_name: track!(parser.parse())?,
             clauses: track!(parser.parse())?,
+            _close: track!(parse_close_paren(parser, &_open))?,
+            _open,
             _dot: track!(parser.expect(&Symbol::Dot))?,
         })
     }
@@ -284,26 +1113,68 @@ impl PositionRange for FunSpec {
         self._dot.end_position()
     }
 }
+impl CollectTokens for FunSpec {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._spec.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.module.collect_tokens(tokens);
+        self.fun_name.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for FunSpec {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FunSpec {
+            _hyphen: self._hyphen.fold_with(folder),
+            _spec: self._spec.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            module: self.module.fold_with(folder),
+            fun_name: self.fun_name.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `-` `callback` `AtomToken` `Clauses<SpecClause>` `.`
+///
+/// As with `FunSpec`, the part following `callback` may be enclosed in parentheses.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallbackSpec {
     pub _hyphen: SymbolToken,
     pub _spec: AtomToken,
+    pub _open: Option<SymbolToken>,
     pub callback_name: AtomToken,
     pub clauses: Clauses<SpecClause>,
+    pub _close: Option<SymbolToken>,
     pub _dot: SymbolToken,
 }
+impl CallbackSpec {
+    /// Returns the arity of the callback (taken from the first clause).
+    pub fn arity(&self) -> usize {
+        self.clauses.item.args.len()
+    }
+}
 impl Parse for CallbackSpec {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let _spec = track!(parser.expect("callback"))?;
+        let _open = parse_open_paren(parser);
         Ok(CallbackSpec {
-            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
-            _spec: track!(parser.expect("callback"))?,
+            _hyphen,
+            _spec,
             callback_name: track!(parser.parse())?,
             clauses: track!(parser.parse())?,
+            _close: track!(parse_close_paren(parser, &_open))?,
+            _open,
             _dot: track!(parser.expect(&Symbol::Dot))?,
         })
     }
@@ -316,22 +1187,74 @@ impl PositionRange for CallbackSpec {
         self._dot.end_position()
     }
 }
+impl CollectTokens for CallbackSpec {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._spec.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.callback_name.collect_tokens(tokens);
+        self.clauses.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for CallbackSpec {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        CallbackSpec {
+            _hyphen: self._hyphen.fold_with(folder),
+            _spec: self._spec.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            callback_name: self.callback_name.fold_with(folder),
+            clauses: self.clauses.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
 /// `Clauses<FunDeclClause>` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunDecl {
     pub clauses: Clauses<FunDeclClause>,
     pub _dot: SymbolToken,
 }
+impl FunDecl {
+    /// Returns the name of the function (taken from the first clause).
+    pub fn name(&self) -> &str {
+        self.clauses.item.name.value()
+    }
+
+    /// Returns the arity of the function (taken from the first clause).
+    pub fn arity(&self) -> usize {
+        self.clauses.item.arity()
+    }
+}
 impl Parse for FunDecl {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
-        Ok(FunDecl {
-            clauses: track!(parser.parse())?,
-            _dot: track!(parser.expect(&Symbol::Dot))?,
-        })
+        let clauses: Clauses<FunDeclClause> = track!(parser.parse())?;
+        let _dot = track!(parser.expect(&Symbol::Dot))?;
+        let items = clauses.iter().collect::<Vec<_>>();
+        for pair in items.windows(2) {
+            let (previous, clause) = (pair[0], pair[1]);
+            if previous.head() != clause.head() {
+                let head = |c: &FunDeclClause| Span {
+                    start: c.start_position(),
+                    end: c.patterns.end_position(),
+                };
+                let mismatch = HeadMismatch {
+                    previous: previous.head(),
+                    name: clause.head(),
+                    previous_head: head(previous),
+                    head: head(clause),
+                };
+                track_panic!(ErrorKind::HeadMismatch(Box::new(mismatch)));
+            }
+        }
+        Ok(FunDecl { clauses, _dot })
     }
 }
 impl PositionRange for FunDecl {
@@ -342,9 +1265,27 @@ impl PositionRange for FunDecl {
         self._dot.end_position()
     }
 }
+impl CollectTokens for FunDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.clauses.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for FunDecl {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        FunDecl {
+            clauses: self.clauses.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_fun_decl(self)
+    }
+}
 
 /// `-` `record` `(` `AtomToken` `,` `Tuple<RecordFieldDecl>` `)` `.`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordDecl {
     pub _hyphen: SymbolToken,
     pub _record: AtomToken,
@@ -355,6 +1296,16 @@ pub struct RecordDecl {
     pub _close: SymbolToken,
     pub _dot: SymbolToken,
 }
+impl RecordDecl {
+    pub fn name(&self) -> &str {
+        self.record_name.value()
+    }
+
+    /// Returns an iterator over the field declarations of this record.
+    pub fn fields(&self) -> impl Iterator<Item = &RecordFieldDecl> {
+        self.fields.elements.iter().flat_map(|s| s.iter())
+    }
+}
 impl Parse for RecordDecl {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
@@ -380,30 +1331,80 @@ impl PositionRange for RecordDecl {
         self._dot.end_position()
     }
 }
+impl CollectTokens for RecordDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self._record.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.record_name.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.fields.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordDecl {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordDecl {
+            _hyphen: self._hyphen.fold_with(folder),
+            _record: self._record.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            record_name: self.record_name.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            fields: self.fields.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
 
-/// `-` `type|opaque` `AtomToken` `Args<VariableToken>` `::` `Type` `.`
+/// `-` `type|opaque|nominal` `AtomToken` `Args<VariableToken>` `::` `Type` `.`
+///
+/// The part following the type kind may be enclosed in parentheses (e.g., `-type(foo() :: atom()).`).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeDecl {
     pub _hyphen: SymbolToken,
     pub type_kind: AtomToken,
+    pub _open: Option<SymbolToken>,
     pub type_name: AtomToken,
     pub variables: Args<VariableToken>,
     pub _double_colon: SymbolToken,
     pub ty: Type,
+    pub _close: Option<SymbolToken>,
     pub _dot: SymbolToken,
 }
+impl TypeDecl {
+    pub fn name(&self) -> &str {
+        self.type_name.value()
+    }
+    pub fn arity(&self) -> usize {
+        self.variables.len()
+    }
+    pub fn is_opaque(&self) -> bool {
+        self.type_kind.value() == "opaque"
+    }
+    pub fn is_nominal(&self) -> bool {
+        self.type_kind.value() == "nominal"
+    }
+}
 impl Parse for TypeDecl {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
+        let _hyphen = track!(parser.expect(&Symbol::Hyphen))?;
+        let type_kind = track!(parser.expect_any(&["type", "opaque", "nominal"]))?;
+        let _open = parse_open_paren(parser);
         Ok(TypeDecl {
-            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
-            type_kind: track!(parser.expect_any(&["type", "opaque"]))?,
+            _hyphen,
+            type_kind,
             type_name: track!(parser.parse())?,
             variables: track!(parser.parse())?,
             _double_colon: track!(parser.expect(&Symbol::DoubleColon))?,
             ty: track!(parser.parse())?,
+            _close: track!(parse_close_paren(parser, &_open))?,
+            _open,
             _dot: track!(parser.expect(&Symbol::Dot))?,
         })
     }
@@ -416,3 +1417,343 @@ impl PositionRange for TypeDecl {
         self._dot.end_position()
     }
 }
+impl CollectTokens for TypeDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self.type_kind.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.type_name.collect_tokens(tokens);
+        self.variables.collect_tokens(tokens);
+        self._double_colon.collect_tokens(tokens);
+        self.ty.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for TypeDecl {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TypeDecl {
+            _hyphen: self._hyphen.fold_with(folder),
+            type_kind: self.type_kind.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            type_name: self.type_name.fold_with(folder),
+            variables: self.variables.fold_with(folder),
+            _double_colon: self._double_colon.fold_with(folder),
+            ty: self.ty.fold_with(folder),
+            _close: self._close.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// `-` `DirectiveName` `Option<Args<TokenGroup>>` `.`
+///
+/// Preprocessor directive (e.g., `-define(A, 1).`, `-ifdef(A).` and `-endif.`).
+/// This is produced only in no-preprocess mode (see `Parser::no_preprocess`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Directive {
+    pub _hyphen: SymbolToken,
+    pub name: DirectiveName,
+    pub args: Option<Args<TokenGroup>>,
+    pub _dot: SymbolToken,
+}
+impl Directive {
+    /// Returns the name of the macro defined, undefined or tested by this directive
+    /// (`-define`, `-undef`, `-ifdef` or `-ifndef`).
+    pub fn macro_name(&self) -> Option<&str> {
+        if !matches!(self.name.value(), "define" | "undef" | "ifdef" | "ifndef") {
+            return None;
+        }
+        let group = self.args.as_ref()?.args.as_ref()?.iter().next()?;
+        match group.tokens[0] {
+            LexicalToken::Atom(ref t) => Some(t.value()),
+            LexicalToken::Variable(ref t) => Some(t.value()),
+            _ => None,
+        }
+    }
+
+    /// Returns the replacement tokens of a `-define` directive (i.e., the tokens after the first `,`).
+    pub fn replacement(&self) -> Option<Vec<LexicalToken>> {
+        if self.name.value() != "define" {
+            return None;
+        }
+        let args = self.args.as_ref()?.args.as_ref()?;
+        let mut tokens = Vec::new();
+        args.tail.as_ref()?.item.collect_tokens(&mut tokens);
+        let mut tail = args.tail.as_ref()?.tail.as_ref();
+        while let Some(t) = tail {
+            t._comma.collect_tokens(&mut tokens);
+            t.item.collect_tokens(&mut tokens);
+            tail = t.tail.as_ref();
+        }
+        Some(tokens)
+    }
+}
+impl Parse for Directive {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(Directive {
+            _hyphen: track!(parser.expect(&Symbol::Hyphen))?,
+            name: track!(parser.parse())?,
+            args: parser.transaction(Parser::parse).ok(),
+            _dot: track!(parser.expect(&Symbol::Dot))?,
+        })
+    }
+}
+impl PositionRange for Directive {
+    fn start_position(&self) -> Position {
+        self._hyphen.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._dot.end_position()
+    }
+}
+impl CollectTokens for Directive {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._hyphen.collect_tokens(tokens);
+        self.name.collect_tokens(tokens);
+        self.args.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for Directive {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Directive {
+            _hyphen: self._hyphen.fold_with(folder),
+            name: self.name.fold_with(folder),
+            args: self.args.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+        }
+    }
+}
+
+/// Name of a preprocessor directive (`if` is a keyword).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DirectiveName {
+    Atom(AtomToken),
+    If(KeywordToken),
+}
+impl DirectiveName {
+    pub fn value(&self) -> &str {
+        match *self {
+            DirectiveName::Atom(ref t) => t.value(),
+            DirectiveName::If(_) => "if",
+        }
+    }
+}
+impl Parse for DirectiveName {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        match track!(parser.parse())? {
+            LexicalToken::Keyword(t) if t.value() == Keyword::If => Ok(DirectiveName::If(t)),
+            LexicalToken::Atom(t)
+                if matches!(
+                    t.value(),
+                    "define"
+                        | "undef"
+                        | "ifdef"
+                        | "ifndef"
+                        | "else"
+                        | "elif"
+                        | "endif"
+                        | "include"
+                        | "include_lib"
+                        | "error"
+                        | "warning"
+                ) =>
+            {
+                Ok(DirectiveName::Atom(t))
+            }
+            token => track_panic!(ErrorKind::UnexpectedToken(token)),
+        }
+    }
+}
+impl PositionRange for DirectiveName {
+    fn start_position(&self) -> Position {
+        match *self {
+            DirectiveName::Atom(ref t) => t.start_position(),
+            DirectiveName::If(ref t) => t.start_position(),
+        }
+    }
+    fn end_position(&self) -> Position {
+        match *self {
+            DirectiveName::Atom(ref t) => t.end_position(),
+            DirectiveName::If(ref t) => t.end_position(),
+        }
+    }
+}
+impl CollectTokens for DirectiveName {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            DirectiveName::Atom(ref t) => t.collect_tokens(tokens),
+            DirectiveName::If(ref t) => t.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for DirectiveName {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            DirectiveName::Atom(t) => DirectiveName::Atom(t.fold_with(folder)),
+            DirectiveName::If(t) => DirectiveName::If(t.fold_with(folder)),
+        }
+    }
+}
+
+/// Tokens of a form which could not be parsed, up to and including the terminating `.`.
+///
+/// This is produced only by `Parser::parse_module_resilient`.
+/// Parsing an `ErrorForm` never fails unless the input is empty;
+/// a `.` is regarded as a form terminator only if it is not immediately followed by another token
+/// (so that record field accesses like `X#r.f` are skipped).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorForm {
+    #[cfg_attr(feature = "serde", serde(with = "crate::cst::serde_tokens"))]
+    pub tokens: Vec<LexicalToken>,
+}
+impl Parse for ErrorForm {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let mut tokens = vec![track!(parser.parse::<LexicalToken>())?];
+        loop {
+            let last = &tokens[tokens.len() - 1];
+            let is_dot = last.as_symbol_token().map(SymbolToken::value) == Some(Symbol::Dot);
+            if track!(parser.eos())? {
+                break;
+            }
+            let next = track!(parser.peek(|parser| parser.parse::<LexicalToken>()))?;
+            if is_dot && next.start_position() != last.end_position() {
+                break;
+            }
+            tokens.push(track!(parser.parse())?);
+        }
+        Ok(ErrorForm { tokens })
+    }
+}
+impl PositionRange for ErrorForm {
+    fn start_position(&self) -> Position {
+        self.tokens[0].start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.tokens[self.tokens.len() - 1].end_position()
+    }
+}
+impl CollectTokens for ErrorForm {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.tokens.collect_tokens(tokens);
+    }
+}
+impl Foldable for ErrorForm {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ErrorForm {
+            tokens: self.tokens.fold_with(folder),
+        }
+    }
+}
+
+/// Tokens of a form which seems to use syntax not supported by this crate
+/// (e.g., syntax introduced by a newer OTP release).
+///
+/// This is produced by `Parser::parse_module_resilient` instead of `ErrorForm`
+/// if the form is well-formed as a token sequence (i.e., its brackets are balanced)
+/// and the parsing failed before its terminating `.`.
+/// Since such forms cannot be told from syntax errors in general, this is only a heuristic guess;
+/// the error is reported in either case.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnparsedForm {
+    #[cfg_attr(feature = "serde", serde(with = "crate::cst::serde_tokens"))]
+    pub tokens: Vec<LexicalToken>,
+
+    /// The message of the parse error.
+    pub reason: String,
+}
+impl UnparsedForm {
+    /// Returns `true` if the brackets in `tokens` are balanced.
+    pub(crate) fn is_well_formed(tokens: &[LexicalToken]) -> bool {
+        let mut stack = Vec::new();
+        for t in tokens {
+            let close = match t.as_symbol_token().map(SymbolToken::value) {
+                Some(Symbol::OpenParen) => Symbol::CloseParen,
+                Some(Symbol::OpenSquare) => Symbol::CloseSquare,
+                Some(Symbol::OpenBrace) => Symbol::CloseBrace,
+                Some(Symbol::DoubleLeftAngle) => Symbol::DoubleRightAngle,
+                Some(
+                    s @ (Symbol::CloseParen
+                    | Symbol::CloseSquare
+                    | Symbol::CloseBrace
+                    | Symbol::DoubleRightAngle),
+                ) => {
+                    if stack.pop() != Some(s) {
+                        return false;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            stack.push(close);
+        }
+        stack.is_empty()
+    }
+}
+impl PositionRange for UnparsedForm {
+    fn start_position(&self) -> Position {
+        self.tokens[0].start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.tokens[self.tokens.len() - 1].end_position()
+    }
+}
+impl CollectTokens for UnparsedForm {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.tokens.collect_tokens(tokens);
+    }
+}
+impl Foldable for UnparsedForm {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        UnparsedForm {
+            tokens: self.tokens.fold_with(folder),
+            reason: self.reason,
+        }
+    }
+}
+
+fn parse_open_paren<T: TokenRead>(parser: &mut Parser<T>) -> Option<SymbolToken> {
+    parser
+        .transaction(|parser| parser.expect(&Symbol::OpenParen))
+        .ok()
+}
+
+fn parse_close_paren<T: TokenRead>(
+    parser: &mut Parser<T>,
+    open: &Option<SymbolToken>,
+) -> Result<Option<SymbolToken>> {
+    if open.is_some() {
+        Ok(Some(track!(parser.expect(&Symbol::CloseParen))?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Token reader over the tokens of an attribute value (in reverse order).
+struct Tokens(Vec<LexicalToken>);
+impl Preprocessor for Tokens {
+    fn define_macro(&mut self, _name: &str, _replacement: Vec<LexicalToken>) {}
+    fn undef_macro(&mut self, _name: &str) {}
+}
+impl TokenRead for Tokens {
+    fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
+        Ok(self.0.pop())
+    }
+    fn unread_token(&mut self, token: LexicalToken) {
+        self.0.push(token);
+    }
+}
@@ -1,19 +1,25 @@
 use erl_tokenize::tokens::{AtomToken, SymbolToken};
 use erl_tokenize::values::Symbol;
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use super::super::Expr;
 use super::Type;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{Parser, Result};
 
 /// `AtomToken` `Option<RecordFieldDefault>` `Option<RecordFieldType>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordFieldDecl {
     pub field_name: AtomToken,
     pub field_default: Option<RecordFieldDefault>,
     pub field_type: Option<RecordFieldType>,
 }
+impl RecordFieldDecl {
+    pub fn name(&self) -> &str {
+        self.field_name.value()
+    }
+}
 impl Parse for RecordFieldDecl {
     fn parse<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
         Ok(RecordFieldDecl {
@@ -35,9 +41,26 @@ impl PositionRange for RecordFieldDecl {
             .unwrap_or_else(|| self.field_name.end_position())
     }
 }
+impl CollectTokens for RecordFieldDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.field_name.collect_tokens(tokens);
+        self.field_default.collect_tokens(tokens);
+        self.field_type.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordFieldDecl {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordFieldDecl {
+            field_name: self.field_name.fold_with(folder),
+            field_default: self.field_default.fold_with(folder),
+            field_type: self.field_type.fold_with(folder),
+        }
+    }
+}
 
 /// `=` `Expr`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordFieldDefault {
     pub _match: SymbolToken,
     pub value: Expr,
@@ -58,9 +81,24 @@ impl PositionRange for RecordFieldDefault {
         self.value.end_position()
     }
 }
+impl CollectTokens for RecordFieldDefault {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._match.collect_tokens(tokens);
+        self.value.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordFieldDefault {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordFieldDefault {
+            _match: self._match.fold_with(folder),
+            value: self.value.fold_with(folder),
+        }
+    }
+}
 
 /// `::` `Type`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordFieldType {
     pub _double_colon: SymbolToken,
     pub field_type: Type,
@@ -81,3 +119,17 @@ impl PositionRange for RecordFieldType {
         self.field_type.end_position()
     }
 }
+impl CollectTokens for RecordFieldType {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._double_colon.collect_tokens(tokens);
+        self.field_type.collect_tokens(tokens);
+    }
+}
+impl Foldable for RecordFieldType {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        RecordFieldType {
+            _double_colon: self._double_colon.fold_with(folder),
+            field_type: self.field_type.fold_with(folder),
+        }
+    }
+}
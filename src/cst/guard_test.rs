@@ -1,15 +1,19 @@
-use erl_tokenize::tokens::{AtomToken, SymbolToken, VariableToken};
+use erl_tokenize::tokens::{SymbolToken, VariableToken};
 use erl_tokenize::values::Symbol;
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 use trackable::error::ErrorKindExt;
 
 use super::commons::parts::{BinaryOp, UnaryOp};
+use super::commons::RecordName;
+use super::exprs;
 use super::guard_tests;
 use super::Literal;
-use crate::traits::{Parse, TokenRead};
-use crate::{ErrorKind, Parser, Result};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
+use crate::{ErrorKind, IllegalGuardExpr, Parser, Result, Span};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GuardTest {
     Literal(Literal),
     Variable(VariableToken),
@@ -24,6 +28,7 @@ pub enum GuardTest {
     FunCall(Box<guard_tests::FunCall>),
     UnaryOpCall(Box<guard_tests::UnaryOpCall>),
     BinaryOpCall(Box<guard_tests::BinaryOpCall>),
+    MacroCall(Box<exprs::MacroCall>),
 }
 impl Parse for GuardTest {
     fn parse_non_left_recor<T>(parser: &mut Parser<T>) -> Result<Self>
@@ -41,8 +46,13 @@ impl Parse for GuardTest {
             HeadKind::List => GuardTest::List(track!(parser.parse())?),
             HeadKind::Bits => GuardTest::Bits(track!(parser.parse())?),
             HeadKind::FunCall => GuardTest::FunCall(track!(parser.parse())?),
-            HeadKind::UnaryOpCall => GuardTest::UnaryOpCall(track!(parser.parse())?),
+            HeadKind::UnaryOpCall => {
+                let op = track!(parser.parse())?;
+                let operand = track!(parser.nested(Self::parse_operand))?;
+                GuardTest::UnaryOpCall(Box::new(guard_tests::UnaryOpCall { op, operand }))
+            }
             HeadKind::Parenthesized => GuardTest::Parenthesized(track!(parser.parse())?),
+            HeadKind::MacroCall => GuardTest::MacroCall(track!(parser.parse())?),
         };
         Ok(test)
     }
@@ -50,6 +60,44 @@ impl Parse for GuardTest {
     where
         T: TokenRead,
     {
+        parser
+            .nested(|parser| parser.memoize(|parser| track!(Self::parse_binary_op_call(parser, 0))))
+    }
+}
+impl GuardTest {
+    /// Returns the reason if this guard test (excluding its children) is not allowed in guards,
+    /// i.e., it calls a function other than the guard BIFs or calls `++` or `--`.
+    pub(crate) fn illegal_expr(&self) -> Option<IllegalGuardExpr> {
+        match *self {
+            GuardTest::FunCall(ref x) => {
+                let module = x.module.as_ref().map(|m| m.name.value());
+                let name = x.name.value();
+                let arity = x.args.len();
+                if module.unwrap_or("erlang") == "erlang" && is_guard_bif(name, arity) {
+                    return None;
+                }
+                Some(IllegalGuardExpr::Call {
+                    module: module.map(str::to_owned),
+                    name: name.to_owned(),
+                    arity,
+                    span: Span::of(&**x),
+                })
+            }
+            GuardTest::BinaryOpCall(ref x) => match x.op {
+                BinaryOp::PlusPlus(ref t) | BinaryOp::MinusMinus(ref t) => {
+                    Some(IllegalGuardExpr::Operator {
+                        op: t.text().to_owned(),
+                        span: Span::of(t),
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Parses a guard test without binary operators (but with trailing record field accesses).
+    fn parse_operand<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
         let mut head = track!(Self::parse_non_left_recor(parser))?;
         loop {
             let kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
@@ -57,12 +105,50 @@ impl Parse for GuardTest {
                 TailKind::RecordFieldAccess => {
                     GuardTest::RecordFieldAccess(track!(parser.parse_tail(head))?)
                 }
-                TailKind::BinaryOpCall => GuardTest::BinaryOpCall(track!(parser.parse_tail(head))?),
                 TailKind::None => break,
             };
         }
         Ok(head)
     }
+
+    /// Parses binary operator calls by precedence climbing.
+    ///
+    /// Only operators whose precedence is greater than or equal to `min_precedence` are consumed.
+    /// Non-associative operators cannot be chained (e.g., `A < B < C`).
+    fn parse_binary_op_call<T: TokenRead>(
+        parser: &mut Parser<T>,
+        min_precedence: u8,
+    ) -> Result<Self> {
+        let mut left = track!(Self::parse_operand(parser))?;
+        let mut non_associative = None;
+        loop {
+            let op = match parser.peek(|parser| parser.parse::<BinaryOp>()) {
+                Ok(BinaryOp::Send(_)) | Err(_) => break,
+                Ok(op) => op,
+            };
+            if op.precedence() < min_precedence {
+                break;
+            }
+            if non_associative == Some(op.precedence()) {
+                let token = track!(parser.parse::<LexicalToken>())?;
+                track_panic!(ErrorKind::UnexpectedToken(token));
+            }
+            let op: BinaryOp = track!(parser.parse())?;
+            if op.is_non_associative() {
+                non_associative = Some(op.precedence());
+            }
+            let next_precedence = if op.is_right_associative() {
+                op.precedence()
+            } else {
+                op.precedence() + 1
+            };
+            let right = track!(
+                parser.nested(|parser| Self::parse_binary_op_call(parser, next_precedence))
+            )?;
+            left = GuardTest::BinaryOpCall(Box::new(guard_tests::BinaryOpCall { left, op, right }));
+        }
+        Ok(left)
+    }
 }
 impl PositionRange for GuardTest {
     fn start_position(&self) -> Position {
@@ -80,6 +166,7 @@ impl PositionRange for GuardTest {
             GuardTest::FunCall(ref x) => x.start_position(),
             GuardTest::UnaryOpCall(ref x) => x.start_position(),
             GuardTest::BinaryOpCall(ref x) => x.start_position(),
+            GuardTest::MacroCall(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -97,9 +184,58 @@ impl PositionRange for GuardTest {
             GuardTest::FunCall(ref x) => x.end_position(),
             GuardTest::UnaryOpCall(ref x) => x.end_position(),
             GuardTest::BinaryOpCall(ref x) => x.end_position(),
+            GuardTest::MacroCall(ref x) => x.end_position(),
         }
     }
 }
+impl CollectTokens for GuardTest {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            GuardTest::Literal(ref x) => x.collect_tokens(tokens),
+            GuardTest::Variable(ref x) => x.collect_tokens(tokens),
+            GuardTest::Tuple(ref x) => x.collect_tokens(tokens),
+            GuardTest::Map(ref x) => x.collect_tokens(tokens),
+            GuardTest::Record(ref x) => x.collect_tokens(tokens),
+            GuardTest::RecordFieldIndex(ref x) => x.collect_tokens(tokens),
+            GuardTest::RecordFieldAccess(ref x) => x.collect_tokens(tokens),
+            GuardTest::List(ref x) => x.collect_tokens(tokens),
+            GuardTest::Bits(ref x) => x.collect_tokens(tokens),
+            GuardTest::Parenthesized(ref x) => x.collect_tokens(tokens),
+            GuardTest::FunCall(ref x) => x.collect_tokens(tokens),
+            GuardTest::UnaryOpCall(ref x) => x.collect_tokens(tokens),
+            GuardTest::BinaryOpCall(ref x) => x.collect_tokens(tokens),
+            GuardTest::MacroCall(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for GuardTest {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            GuardTest::Literal(x) => GuardTest::Literal(x.fold_with(folder)),
+            GuardTest::Variable(x) => GuardTest::Variable(x.fold_with(folder)),
+            GuardTest::Tuple(x) => GuardTest::Tuple(x.fold_with(folder)),
+            GuardTest::Map(x) => GuardTest::Map(x.fold_with(folder)),
+            GuardTest::Record(x) => GuardTest::Record(x.fold_with(folder)),
+            GuardTest::RecordFieldIndex(x) => GuardTest::RecordFieldIndex(x.fold_with(folder)),
+            GuardTest::RecordFieldAccess(x) => GuardTest::RecordFieldAccess(x.fold_with(folder)),
+            GuardTest::List(x) => GuardTest::List(x.fold_with(folder)),
+            GuardTest::Bits(x) => GuardTest::Bits(x.fold_with(folder)),
+            GuardTest::Parenthesized(x) => GuardTest::Parenthesized(x.fold_with(folder)),
+            GuardTest::FunCall(x) => GuardTest::FunCall(x.fold_with(folder)),
+            GuardTest::UnaryOpCall(x) => GuardTest::UnaryOpCall(x.fold_with(folder)),
+            GuardTest::BinaryOpCall(x) => GuardTest::BinaryOpCall(x.fold_with(folder)),
+            GuardTest::MacroCall(x) => GuardTest::MacroCall(x.fold_with(folder)),
+        }
+    }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_guard_test(self)
+    }
+}
+impl fmt::Display for GuardTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
 
 #[derive(Debug)]
 enum HeadKind {
@@ -114,6 +250,7 @@ enum HeadKind {
     FunCall,
     UnaryOpCall,
     Parenthesized,
+    MacroCall,
 }
 impl HeadKind {
     fn guess<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
@@ -124,7 +261,7 @@ impl HeadKind {
                 Symbol::OpenParen => HeadKind::Parenthesized,
                 Symbol::OpenSquare => HeadKind::List,
                 Symbol::Sharp => {
-                    if parser.parse::<AtomToken>().is_ok() {
+                    if parser.parse::<RecordName>().is_ok() {
                         let next = parser.parse::<SymbolToken>().map(|t| t.value()).ok();
                         if next == Some(Symbol::Dot) {
                             HeadKind::RecordFieldIndex
@@ -135,6 +272,7 @@ impl HeadKind {
                         HeadKind::Map
                     }
                 }
+                Symbol::Question if parser.is_no_preprocess() => HeadKind::MacroCall,
                 _ => track!(UnaryOp::from_token(t.into())
                     .map(|_| HeadKind::UnaryOpCall)
                     .map_err(|e| ErrorKind::UnexpectedToken(e).error()))?,
@@ -158,7 +296,6 @@ impl HeadKind {
 #[derive(Debug)]
 enum TailKind {
     RecordFieldAccess,
-    BinaryOpCall,
     None,
 }
 impl TailKind {
@@ -171,13 +308,55 @@ impl TailKind {
             LexicalToken::Symbol(ref t) if t.value() == Symbol::Sharp => {
                 TailKind::RecordFieldAccess
             }
-            token => {
-                if BinaryOp::from_token(token).is_ok() {
-                    TailKind::BinaryOpCall
-                } else {
-                    TailKind::None
-                }
-            }
+            _ => TailKind::None,
         })
     }
 }
+
+/// Returns `true` if `name/arity` is a BIF allowed in guards (in the `erlang` module).
+pub(crate) fn is_guard_bif(name: &str, arity: usize) -> bool {
+    matches!(
+        (name, arity),
+        ("abs", 1)
+            | ("binary_part", 2)
+            | ("binary_part", 3)
+            | ("bit_size", 1)
+            | ("byte_size", 1)
+            | ("ceil", 1)
+            | ("element", 2)
+            | ("float", 1)
+            | ("floor", 1)
+            | ("hd", 1)
+            | ("is_atom", 1)
+            | ("is_binary", 1)
+            | ("is_bitstring", 1)
+            | ("is_boolean", 1)
+            | ("is_float", 1)
+            | ("is_function", 1)
+            | ("is_function", 2)
+            | ("is_integer", 1)
+            | ("is_list", 1)
+            | ("is_map", 1)
+            | ("is_map_key", 2)
+            | ("is_number", 1)
+            | ("is_pid", 1)
+            | ("is_port", 1)
+            | ("is_record", 2)
+            | ("is_record", 3)
+            | ("is_reference", 1)
+            | ("is_tuple", 1)
+            | ("length", 1)
+            | ("map_get", 2)
+            | ("map_size", 1)
+            | ("max", 2)
+            | ("min", 2)
+            | ("node", 0)
+            | ("node", 1)
+            | ("round", 1)
+            | ("self", 0)
+            | ("size", 1)
+            | ("tl", 1)
+            | ("trunc", 1)
+            | ("tuple_size", 1)
+    )
+}
@@ -1,19 +1,41 @@
 use erl_tokenize::tokens::{AtomToken, CharToken, FloatToken, IntegerToken, StringToken};
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 
-use crate::traits::{Parse, TokenRead};
-use crate::{ErrorKind, Parser, Result};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
+use crate::{ErrorKind, Parser, Result, SyntaxFeature};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Atom(AtomToken),
     Char(CharToken),
     Float(FloatToken),
     Integer(IntegerToken),
+    /// One or more adjacent string tokens (e.g., `"foo" "bar"`), which make a single string.
     String {
         head: StringToken,
         tail: Vec<StringToken>,
     },
+    TripleQuotedString(Box<TripleQuotedString>),
+}
+impl Literal {
+    /// Returns the value of a string literal (adjacent string tokens are concatenated).
+    ///
+    /// Returns `None` if this is not a string literal.
+    pub fn string_value(&self) -> Option<String> {
+        match *self {
+            Literal::String { ref head, ref tail } => {
+                let mut s = head.value().to_owned();
+                for t in tail {
+                    s.push_str(t.value());
+                }
+                Some(s)
+            }
+            Literal::TripleQuotedString(ref x) => Some(x.value()),
+            _ => None,
+        }
+    }
 }
 impl Parse for Literal {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
@@ -26,6 +48,15 @@ impl Parse for Literal {
             LexicalToken::Float(t) => Ok(Literal::Float(t)),
             LexicalToken::Integer(t) => Ok(Literal::Integer(t)),
             LexicalToken::String(head) => {
+                // Before OTP 27, `"""` is just an empty string followed by another string.
+                let release = SyntaxFeature::TripleQuotedString.required_release();
+                if head.text() == "\"\"" && parser.otp_release().is_none_or(|r| r >= release) {
+                    if let Ok(x) =
+                        parser.transaction(|parser| TripleQuotedString::parse_rest(parser, &head))
+                    {
+                        return Ok(Literal::TripleQuotedString(Box::new(x)));
+                    }
+                }
                 let mut tail = Vec::new();
                 while let Ok(t) = parser.transaction(Parser::parse) {
                     tail.push(t);
@@ -44,6 +75,7 @@ impl PositionRange for Literal {
             Literal::Float(ref x) => x.start_position(),
             Literal::Integer(ref x) => x.start_position(),
             Literal::String { ref head, .. } => head.start_position(),
+            Literal::TripleQuotedString(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -56,6 +88,148 @@ impl PositionRange for Literal {
                 .last()
                 .map(PositionRange::end_position)
                 .unwrap_or_else(|| head.end_position()),
+            Literal::TripleQuotedString(ref x) => x.end_position(),
+        }
+    }
+}
+impl CollectTokens for Literal {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Literal::Atom(ref x) => x.collect_tokens(tokens),
+            Literal::Char(ref x) => x.collect_tokens(tokens),
+            Literal::Float(ref x) => x.collect_tokens(tokens),
+            Literal::Integer(ref x) => x.collect_tokens(tokens),
+            Literal::String { ref head, ref tail } => {
+                head.collect_tokens(tokens);
+                tail.collect_tokens(tokens);
+            }
+            Literal::TripleQuotedString(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Literal {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Literal::Atom(x) => Literal::Atom(x.fold_with(folder)),
+            Literal::Char(x) => Literal::Char(x.fold_with(folder)),
+            Literal::Float(x) => Literal::Float(x.fold_with(folder)),
+            Literal::Integer(x) => Literal::Integer(x.fold_with(folder)),
+            Literal::String { head, tail } => Literal::String {
+                head: head.fold_with(folder),
+                tail: tail.fold_with(folder),
+            },
+            Literal::TripleQuotedString(x) => Literal::TripleQuotedString(x.fold_with(folder)),
+        }
+    }
+}
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
+
+/// Triple-quoted string (OTP 27).
+///
+/// Since `erl_tokenize` does not know triple-quoted strings, `"""` `CONTENT` `"""` is tokenized as
+/// three adjacent string tokens (`""`, `"CONTENT"` and `""`) and is recognized as such.
+/// Hence the content cannot contain `"`.
+/// Sigils (e.g., `~"..."` and `~b"..."`) are rejected by `erl_tokenize`, so they are not supported.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TripleQuotedString {
+    pub _open: StringToken,
+    pub body: StringToken,
+    pub _close: StringToken,
+}
+impl TripleQuotedString {
+    /// Returns the value of this string.
+    ///
+    /// It consists of the lines between the delimiters,
+    /// without the indentation of the closing delimiter.
+    /// Escape sequences are not interpreted.
+    pub fn value(&self) -> String {
+        let (lines, indent) = self.lines();
+        lines
+            .iter()
+            .map(|l| l.strip_prefix(indent).unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn content(&self) -> &str {
+        let text = self.body.text();
+        &text[1..text.len() - 1]
+    }
+
+    /// Returns the content lines and the indentation of the closing delimiter.
+    fn lines(&self) -> (Vec<&str>, &str) {
+        let content = self.content();
+        let first_newline = content.find('\n').unwrap_or(content.len());
+        let last_newline = content.rfind('\n').unwrap_or(0);
+        let indent = &content[last_newline + 1..];
+        let lines = if first_newline < last_newline {
+            content[first_newline + 1..last_newline]
+                .split('\n')
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (lines, indent)
+    }
+
+    fn is_valid(&self) -> bool {
+        let content = self.content();
+        let is_blank = |s: &str| s.chars().all(|c| c == ' ' || c == '\t');
+        let (first, last) = match (content.find('\n'), content.rfind('\n')) {
+            (Some(first), Some(last)) => (&content[..first], &content[last + 1..]),
+            _ => return false,
+        };
+        let (lines, indent) = self.lines();
+        is_blank(first)
+            && is_blank(last)
+            && lines.iter().all(|l| is_blank(l) || l.starts_with(indent))
+    }
+
+    /// Parses the rest of a triple-quoted string after the first token (`""`).
+    fn parse_rest<T: TokenRead>(parser: &mut Parser<T>, open: &StringToken) -> Result<Self> {
+        let body: StringToken = track!(parser.parse())?;
+        let close: StringToken = track!(parser.parse())?;
+        let x = TripleQuotedString {
+            _open: open.clone(),
+            body,
+            _close: close,
+        };
+        track_assert!(
+            x._open.end_position() == x.body.start_position()
+                && x.body.end_position() == x._close.start_position()
+                && x._close.text() == "\"\""
+                && x.is_valid(),
+            ErrorKind::InvalidInput
+        );
+        Ok(x)
+    }
+}
+impl PositionRange for TripleQuotedString {
+    fn start_position(&self) -> Position {
+        self._open.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._close.end_position()
+    }
+}
+impl CollectTokens for TripleQuotedString {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.body.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for TripleQuotedString {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        TripleQuotedString {
+            _open: self._open.fold_with(folder),
+            body: self.body.fold_with(folder),
+            _close: self._close.fold_with(folder),
         }
     }
 }
@@ -1,33 +1,112 @@
-use crate::traits::{Parse, TokenRead};
-use crate::{Parser, Result};
+use erl_tokenize::LexicalToken;
+use std::fmt;
 
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
+use crate::{ErrorKind, Parser, Result};
+
+pub use self::diff::{diff, Change, ChangeKind};
+pub use self::dispose::dispose;
 pub use self::expr::Expr;
 pub use self::form::Form;
 pub use self::guard_test::GuardTest;
-pub use self::literal::Literal;
+pub use self::literal::{Literal, TripleQuotedString};
+pub use self::node::{Node, NodeId, NodeIndex, NodeKind};
 pub use self::pattern::Pattern;
+pub use self::trivia::Trivia;
 pub use self::ty::Type;
 
+pub(crate) use self::guard_test::is_guard_bif;
+
 pub mod clauses;
 pub mod commons;
+pub mod eval;
 pub mod exprs;
 pub mod forms;
 pub mod guard_tests;
 pub mod patterns;
 pub mod types;
 
+mod diff;
+mod dispose;
 mod expr;
 mod form;
 mod guard_test;
 mod literal;
+mod node;
 mod pattern;
+#[cfg(feature = "serde")]
+mod serde_tokens;
+mod trivia;
 mod ty;
 
 /// `Vec<Form>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleDecl {
     pub forms: Vec<Form>,
 }
+impl ModuleDecl {
+    /// Returns the name given by the `-module` attribute.
+    pub fn module_name(&self) -> Option<&str> {
+        self.forms.iter().find_map(|f| match *f {
+            Form::ModuleAttr(ref a) => Some(a.module_name.value()),
+            _ => None,
+        })
+    }
+
+    /// Returns the parse transforms specified by the `-compile` attributes in the source order.
+    pub fn parse_transforms(&self) -> Vec<&str> {
+        self.forms
+            .iter()
+            .filter_map(|f| match *f {
+                Form::CompileAttr(ref a) => Some(a.parse_transforms()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn fun_decls(&self) -> impl Iterator<Item = &forms::FunDecl> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::FunDecl(ref x) => Some(x),
+            _ => None,
+        })
+    }
+
+    pub fn record_decls(&self) -> impl Iterator<Item = &forms::RecordDecl> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::RecordDecl(ref x) => Some(x),
+            _ => None,
+        })
+    }
+
+    pub fn type_decls(&self) -> impl Iterator<Item = &forms::TypeDecl> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::TypeDecl(ref x) => Some(x),
+            _ => None,
+        })
+    }
+}
+impl CollectTokens for ModuleDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.forms.collect_tokens(tokens);
+    }
+}
+impl Foldable for ModuleDecl {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ModuleDecl {
+            forms: self.forms.fold_with(folder),
+        }
+    }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_module_decl(self)
+    }
+}
+impl fmt::Display for ModuleDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
 impl Parse for ModuleDecl {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
@@ -41,3 +120,88 @@ impl Parse for ModuleDecl {
         Ok(ModuleDecl { forms })
     }
 }
+
+/// Root of a header (`.hrl`) file: `Vec<Form>` without `-module`.
+///
+/// A header typically consists of record, type and macro definitions and other attributes,
+/// so it is meant to be parsed in no-preprocess mode (see `Parser::no_preprocess`),
+/// which keeps the `-define` directives as `forms::Directive`s.
+/// A `-module` attribute is rejected, since it belongs to the including module.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::HeaderDecl;
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-record(r, {a :: integer()}).\n-type t() :: #r{}.\n-define(A(X), {X}).\n";
+/// let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+/// let header: HeaderDecl = parser.parse().unwrap();
+/// assert_eq!(header.record_decls().count(), 1);
+/// assert_eq!(header.type_decls().count(), 1);
+/// let macros: Vec<_> = header.macro_defs().filter_map(|d| d.macro_name()).collect();
+/// assert_eq!(macros, ["A"]);
+///
+/// let mut parser = Parser::new(TokenReader::new(Lexer::new("-module(foo).")));
+/// assert!(parser.parse::<HeaderDecl>().is_err());
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderDecl {
+    pub forms: Vec<Form>,
+}
+impl HeaderDecl {
+    pub fn record_decls(&self) -> impl Iterator<Item = &forms::RecordDecl> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::RecordDecl(ref x) => Some(x),
+            _ => None,
+        })
+    }
+
+    pub fn type_decls(&self) -> impl Iterator<Item = &forms::TypeDecl> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::TypeDecl(ref x) => Some(x),
+            _ => None,
+        })
+    }
+
+    /// Returns the `-define` directives (which appear only if parsed in no-preprocess mode).
+    pub fn macro_defs(&self) -> impl Iterator<Item = &forms::Directive> {
+        self.forms.iter().filter_map(|f| match *f {
+            Form::Directive(ref x) if x.name.value() == "define" => Some(x),
+            _ => None,
+        })
+    }
+
+    /// Converts this into a `ModuleDecl` (e.g., to analyze the header by `analysis::ModuleIndex`).
+    pub fn into_module(self) -> ModuleDecl {
+        ModuleDecl { forms: self.forms }
+    }
+}
+impl CollectTokens for HeaderDecl {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.forms.collect_tokens(tokens);
+    }
+}
+impl fmt::Display for HeaderDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
+impl Parse for HeaderDecl {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let mut forms = Vec::new();
+        while !track!(parser.eos())? {
+            let form = track!(parser.parse())?;
+            if let Form::ModuleAttr(ref x) = form {
+                track_panic!(ErrorKind::UnexpectedToken(x._module.clone().into()));
+            }
+            forms.push(form);
+        }
+        Ok(HeaderDecl { forms })
+    }
+}
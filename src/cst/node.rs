@@ -0,0 +1,643 @@
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::collections::HashMap;
+
+use crate::cst::clauses::parts::WhenGuard;
+use crate::cst::clauses::{
+    CaseClause, CatchClause, FunClause, FunDeclClause, IfClause, NamedFunClause, SpecClause,
+};
+use crate::cst::commons::parts::{Args, BitsElem, Clauses, ExtensionPart, Sequence};
+use crate::cst::commons::{Bits, List, Map, Record, Tuple};
+use crate::cst::exprs::parts::{Body, Qualifier};
+use crate::cst::exprs::{self, Fun};
+use crate::cst::types;
+use crate::cst::{Expr, Form, GuardTest, ModuleDecl, Pattern, Type};
+use crate::traits::CollectTokens;
+use crate::Span;
+
+/// Reference to a form, a clause, an expression, a pattern, a guard test or a type in a CST.
+///
+/// Unlike `Fold`, this allows for walking a CST without cloning it
+/// (e.g., to find the nodes at a position).
+/// The other nodes (e.g., map fields) are not visited; their children are regarded as
+/// the children of the enclosing node.
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Form(&'a Form),
+    FunDeclClause(&'a FunDeclClause),
+    CaseClause(&'a CaseClause),
+    IfClause(&'a IfClause),
+    FunClause(&'a FunClause),
+    NamedFunClause(&'a NamedFunClause),
+    CatchClause(&'a CatchClause),
+    SpecClause(&'a SpecClause),
+    Expr(&'a Expr),
+    Pattern(&'a Pattern),
+    GuardTest(&'a GuardTest),
+    Type(&'a Type),
+}
+impl<'a> Node<'a> {
+    /// Returns the forms of `module` as nodes.
+    pub fn forms(module: &'a ModuleDecl) -> impl Iterator<Item = Node<'a>> {
+        module.forms.iter().map(Node::Form)
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        match *self {
+            Node::Form(_) => NodeKind::Form,
+            Node::FunDeclClause(_)
+            | Node::CaseClause(_)
+            | Node::IfClause(_)
+            | Node::FunClause(_)
+            | Node::NamedFunClause(_)
+            | Node::CatchClause(_)
+            | Node::SpecClause(_) => NodeKind::Clause,
+            Node::Expr(_) => NodeKind::Expr,
+            Node::Pattern(_) => NodeKind::Pattern,
+            Node::GuardTest(_) => NodeKind::GuardTest,
+            Node::Type(_) => NodeKind::Type,
+        }
+    }
+
+    /// Returns the child nodes in the source order.
+    pub fn children(&self) -> Vec<Node<'a>> {
+        let mut c = Children(Vec::new());
+        match *self {
+            Node::Form(x) => c.form(x),
+            Node::FunDeclClause(x) => {
+                c.args(&x.patterns, Node::Pattern);
+                c.when_guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::CaseClause(x) => {
+                c.push(Node::Pattern(&x.pattern));
+                c.when_guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::IfClause(x) => {
+                c.guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::FunClause(x) => {
+                c.args(&x.patterns, Node::Pattern);
+                c.when_guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::NamedFunClause(x) => {
+                c.args(&x.patterns, Node::Pattern);
+                c.when_guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::CatchClause(x) => {
+                c.push(Node::Pattern(&x.pattern));
+                c.when_guard(&x.guard);
+                c.body(&x.body);
+            }
+            Node::SpecClause(x) => {
+                c.args(&x.args, Node::Type);
+                c.push(Node::Type(&x.return_type));
+                if let Some(ref x) = x.constraints {
+                    for constraint in x.constraints.iter() {
+                        c.push(Node::Type(constraint.bound()));
+                    }
+                }
+            }
+            Node::Expr(x) => c.expr(x),
+            Node::Pattern(x) => c.pattern(x),
+            Node::GuardTest(x) => c.guard_test(x),
+            Node::Type(x) => c.ty(x),
+        }
+        c.0
+    }
+
+    /// Calls `f` for this node and its descendants in pre-order.
+    ///
+    /// The traversal is not recursive, so this can be used for deeply nested trees.
+    pub fn walk<F: FnMut(Node<'a>)>(&self, mut f: F) {
+        let mut stack = vec![*self];
+        while let Some(node) = stack.pop() {
+            f(node);
+            stack.extend(node.children().into_iter().rev());
+        }
+    }
+}
+impl<'a> PositionRange for Node<'a> {
+    fn start_position(&self) -> Position {
+        match *self {
+            Node::Form(x) => x.start_position(),
+            Node::FunDeclClause(x) => x.start_position(),
+            Node::CaseClause(x) => x.start_position(),
+            Node::IfClause(x) => x.start_position(),
+            Node::FunClause(x) => x.start_position(),
+            Node::NamedFunClause(x) => x.start_position(),
+            Node::CatchClause(x) => x.start_position(),
+            Node::SpecClause(x) => x.start_position(),
+            Node::Expr(x) => x.start_position(),
+            Node::Pattern(x) => x.start_position(),
+            Node::GuardTest(x) => x.start_position(),
+            Node::Type(x) => x.start_position(),
+        }
+    }
+    fn end_position(&self) -> Position {
+        match *self {
+            Node::Form(x) => x.end_position(),
+            Node::FunDeclClause(x) => x.end_position(),
+            Node::CaseClause(x) => x.end_position(),
+            Node::IfClause(x) => x.end_position(),
+            Node::FunClause(x) => x.end_position(),
+            Node::NamedFunClause(x) => x.end_position(),
+            Node::CatchClause(x) => x.end_position(),
+            Node::SpecClause(x) => x.end_position(),
+            Node::Expr(x) => x.end_position(),
+            Node::Pattern(x) => x.end_position(),
+            Node::GuardTest(x) => x.end_position(),
+            Node::Type(x) => x.end_position(),
+        }
+    }
+}
+impl<'a> CollectTokens for Node<'a> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Node::Form(x) => x.collect_tokens(tokens),
+            Node::FunDeclClause(x) => x.collect_tokens(tokens),
+            Node::CaseClause(x) => x.collect_tokens(tokens),
+            Node::IfClause(x) => x.collect_tokens(tokens),
+            Node::FunClause(x) => x.collect_tokens(tokens),
+            Node::NamedFunClause(x) => x.collect_tokens(tokens),
+            Node::CatchClause(x) => x.collect_tokens(tokens),
+            Node::SpecClause(x) => x.collect_tokens(tokens),
+            Node::Expr(x) => x.collect_tokens(tokens),
+            Node::Pattern(x) => x.collect_tokens(tokens),
+            Node::GuardTest(x) => x.collect_tokens(tokens),
+            Node::Type(x) => x.collect_tokens(tokens),
+        }
+    }
+}
+
+impl ModuleDecl {
+    /// Returns the path of the nodes covering the byte `offset`,
+    /// from the outermost (form) to the innermost node.
+    ///
+    /// The result is empty if no form covers `offset`.
+    pub fn find_node_at(&self, offset: usize) -> Vec<Node<'_>> {
+        self.find_node_by(&offset, Position::offset)
+    }
+
+    /// Same as `find_node_at` except that the position is specified by
+    /// a line and a column (both are 1-origin).
+    pub fn find_node_at_line_column(&self, line: usize, column: usize) -> Vec<Node<'_>> {
+        self.find_node_by(&(line, column), |p| (p.line(), p.column()))
+    }
+
+    fn find_node_by<K, F>(&self, key: &K, f: F) -> Vec<Node<'_>>
+    where
+        K: Ord,
+        F: Fn(&Position) -> K,
+    {
+        let covers =
+            |node: &Node| f(&node.start_position()) <= *key && *key < f(&node.end_position());
+        let i = self
+            .forms
+            .partition_point(|form| f(&form.end_position()) <= *key);
+        let mut path = Vec::new();
+        let mut next = self.forms.get(i).map(Node::Form).filter(|n| covers(n));
+        while let Some(node) = next {
+            path.push(node);
+            next = node.children().into_iter().find(|n| covers(n));
+        }
+        path
+    }
+}
+
+/// Identifier of a `Node` in a module: the index of the node in the pre-order traversal
+/// of the forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(pub usize);
+
+/// Index of the nodes in a module, which maps `NodeId`s to the nodes, their parents and spans.
+///
+/// Since `NodeId`s are assigned in the pre-order traversal, they are stable as long as the CST is
+/// unchanged, and analyses can attach external metadata to the nodes (e.g., `HashMap<NodeId, T>`)
+/// without modifying the tree types.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::{ModuleDecl, NodeIndex, NodeKind};
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_tokenize::Lexer;
+///
+/// let text = "f(X) -> g(X + 1).";
+/// let module: ModuleDecl = Parser::new(TokenReader::new(Lexer::new(text))).parse().unwrap();
+/// let index = NodeIndex::new(&module);
+///
+/// let id = index.find_innermost_at(10).unwrap();
+/// assert_eq!(index.span(id).unwrap().text(text), Some("X"));
+/// let ancestors: Vec<_> = index
+///     .ancestors(id)
+///     .map(|id| index.span(id).unwrap().text(text).unwrap())
+///     .collect();
+/// assert_eq!(ancestors, ["X + 1", "g(X + 1)", "f(X) -> g(X + 1)", "f(X) -> g(X + 1)."]);
+/// assert_eq!(index.node(index.root_of(id)).unwrap().kind(), NodeKind::Form);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NodeIndex<'a> {
+    nodes: Vec<Node<'a>>,
+    parents: Vec<Option<NodeId>>,
+    spans: Vec<Span>,
+    ids: HashMap<(NodeKind, Span), NodeId>,
+}
+impl<'a> NodeIndex<'a> {
+    /// Assigns `NodeId`s to the nodes in `module`.
+    pub fn new(module: &'a ModuleDecl) -> Self {
+        let mut index = NodeIndex {
+            nodes: Vec::new(),
+            parents: Vec::new(),
+            spans: Vec::new(),
+            ids: HashMap::new(),
+        };
+        let mut stack: Vec<_> = module
+            .forms
+            .iter()
+            .rev()
+            .map(|f| (Node::Form(f), None))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            let id = NodeId(index.nodes.len());
+            let span = Span::of(&node);
+            index.ids.entry((node.kind(), span.clone())).or_insert(id);
+            index.nodes.push(node);
+            index.parents.push(parent);
+            index.spans.push(span);
+            stack.extend(node.children().into_iter().rev().map(|n| (n, Some(id))));
+        }
+        index
+    }
+
+    /// Returns the node identified by `id`.
+    pub fn node(&self, id: NodeId) -> Option<Node<'a>> {
+        self.nodes.get(id.0).copied()
+    }
+
+    /// Returns the parent of the node identified by `id` (`None` for forms).
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.parents.get(id.0).copied().flatten()
+    }
+
+    pub fn span(&self, id: NodeId) -> Option<&Span> {
+        self.spans.get(id.0)
+    }
+
+    /// Returns an iterator over the ancestors of the node identified by `id`,
+    /// from the parent to the form.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut next = self.parent(id);
+        std::iter::from_fn(move || {
+            let id = next?;
+            next = self.parent(id);
+            Some(id)
+        })
+    }
+
+    /// Returns the form which contains the node identified by `id`.
+    pub fn root_of(&self, id: NodeId) -> NodeId {
+        self.ancestors(id).last().unwrap_or(id)
+    }
+
+    /// Returns the identifier of the outermost node of `kind` whose span is `span`.
+    pub fn find(&self, kind: NodeKind, span: &Span) -> Option<NodeId> {
+        self.ids.get(&(kind, span.clone())).copied()
+    }
+
+    /// Returns the identifier of the innermost node covering the byte `offset`.
+    pub fn find_innermost_at(&self, offset: usize) -> Option<NodeId> {
+        let i = self.spans.iter().rposition(|s| s.contains_offset(offset))?;
+        Some(NodeId(i))
+    }
+
+    /// Returns an iterator over the identifiers and nodes in the pre-order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, Node<'a>)> + '_ {
+        self.nodes.iter().enumerate().map(|(i, n)| (NodeId(i), *n))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Kind of a `Node`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    Form,
+    Clause,
+    Expr,
+    Pattern,
+    GuardTest,
+    Type,
+}
+
+struct Children<'a>(Vec<Node<'a>>);
+impl<'a> Children<'a> {
+    fn push(&mut self, node: Node<'a>) {
+        self.0.push(node);
+    }
+    fn seq<T, D>(&mut self, x: &'a Sequence<T, D>, f: fn(&'a T) -> Node<'a>) {
+        self.0.extend(x.iter().map(f));
+    }
+    fn opt_seq<T, D>(&mut self, x: &'a Option<Sequence<T, D>>, f: fn(&'a T) -> Node<'a>) {
+        self.0.extend(x.iter().flat_map(|s| s.iter()).map(f));
+    }
+    fn args<T>(&mut self, x: &'a Args<T>, f: fn(&'a T) -> Node<'a>) {
+        self.opt_seq(&x.args, f);
+    }
+    fn body(&mut self, x: &'a Body) {
+        self.seq(&x.exprs, Node::Expr);
+    }
+    fn guard(&mut self, x: &'a Clauses<Sequence<GuardTest>>) {
+        for tests in x.iter() {
+            self.seq(tests, Node::GuardTest);
+        }
+    }
+    fn when_guard(&mut self, x: &'a Option<WhenGuard>) {
+        if let Some(ref x) = *x {
+            self.guard(&x.seq);
+        }
+    }
+    fn clauses<T, D>(&mut self, x: &'a Sequence<T, D>, f: fn(&'a T) -> Node<'a>) {
+        self.seq(x, f);
+    }
+    fn extension_parts(&mut self, x: &'a [ExtensionPart]) {
+        for part in x {
+            match *part {
+                ExtensionPart::Tokens(_) => {}
+                ExtensionPart::Expr(ref x) => self.push(Node::Expr(x)),
+                ExtensionPart::Pattern(ref x) => self.push(Node::Pattern(x)),
+                ExtensionPart::Type(ref x) => self.push(Node::Type(x)),
+            }
+        }
+    }
+    fn tuple<T>(&mut self, x: &'a Tuple<T>, f: fn(&'a T) -> Node<'a>) {
+        self.opt_seq(&x.elements, f);
+    }
+    fn list<T>(&mut self, x: &'a List<T>, f: fn(&'a T) -> Node<'a>) {
+        self.0
+            .extend(x.elements.iter().flat_map(|c| c.iter()).map(|(_, x)| f(x)));
+    }
+    fn map<T>(&mut self, x: &'a Map<T>, f: fn(&'a T) -> Node<'a>) {
+        for field in x.iter() {
+            self.push(f(&field.key));
+            self.push(f(&field.value));
+        }
+    }
+    fn record<T>(&mut self, x: &'a Record<T>, f: fn(&'a T) -> Node<'a>) {
+        for field in x.fields.iter().flat_map(|s| s.iter()) {
+            self.push(f(&field.value));
+        }
+    }
+    fn bits<T>(&mut self, x: &'a Bits<T>, f: fn(&'a T) -> Node<'a>) {
+        for e in x.elements.iter().flat_map(|s| s.iter()) {
+            self.bits_elem(e, f);
+        }
+    }
+    fn bits_elem<T>(&mut self, x: &'a BitsElem<T>, f: fn(&'a T) -> Node<'a>) {
+        self.push(f(&x.element));
+        if let Some(ref s) = x.size {
+            self.push(f(&s.size));
+        }
+    }
+    fn qualifiers(&mut self, x: &'a Sequence<Qualifier>) {
+        for q in x.iter() {
+            match *q {
+                Qualifier::Generator(ref g) => {
+                    self.push(Node::Pattern(&g.pattern));
+                    self.push(Node::Expr(&g.source));
+                }
+                Qualifier::MapGenerator(ref g) => {
+                    self.push(Node::Pattern(&g.key));
+                    self.push(Node::Pattern(&g.value));
+                    self.push(Node::Expr(&g.source));
+                }
+                Qualifier::Filter(ref e) => self.push(Node::Expr(e)),
+            }
+        }
+    }
+
+    fn form(&mut self, x: &'a Form) {
+        match *x {
+            Form::CompileAttr(ref x) => self.push(Node::Expr(&x.options)),
+            Form::VsnAttr(ref x) => self.push(Node::Expr(&x.vsn)),
+            Form::DocAttr(ref x) => self.push(Node::Expr(&x.value)),
+            Form::DialyzerAttr(ref x) => self.push(Node::Expr(&x.options)),
+            Form::FunSpec(ref x) => self.clauses(&x.clauses, Node::SpecClause),
+            Form::CallbackSpec(ref x) => self.clauses(&x.clauses, Node::SpecClause),
+            Form::FunDecl(ref x) => self.clauses(&x.clauses, Node::FunDeclClause),
+            Form::RecordDecl(ref x) => {
+                for field in x.fields() {
+                    if let Some(ref d) = field.field_default {
+                        self.push(Node::Expr(&d.value));
+                    }
+                    if let Some(ref t) = field.field_type {
+                        self.push(Node::Type(&t.field_type));
+                    }
+                }
+            }
+            Form::TypeDecl(ref x) => self.push(Node::Type(&x.ty)),
+            Form::ExtensionAttr(ref x) => self.extension_parts(&x.parts),
+            Form::ModuleAttr(_)
+            | Form::ExportAttr(_)
+            | Form::ExportTypeAttr(_)
+            | Form::ImportAttr(_)
+            | Form::FileAttr(_)
+            | Form::FeatureAttr(_)
+            | Form::BehaviourAttr(_)
+            | Form::OnLoadAttr(_)
+            | Form::WildAttr(_)
+            | Form::Directive(_)
+            | Form::Error(_)
+            | Form::Unparsed(_) => {}
+        }
+    }
+    fn expr(&mut self, x: &'a Expr) {
+        let e = Node::Expr;
+        match *x {
+            Expr::Literal(_)
+            | Expr::Variable(_)
+            | Expr::RecordFieldIndex(_)
+            | Expr::MacroCall(_) => {}
+            Expr::Extension(ref x) => self.extension_parts(&x.parts),
+            Expr::Tuple(ref x) => self.tuple(x, e),
+            Expr::Map(ref x) => self.map(x, e),
+            Expr::MapUpdate(ref x) => {
+                self.push(e(&x.map));
+                self.map(&x.update, e);
+            }
+            Expr::Record(ref x) => self.record(x, e),
+            Expr::RecordUpdate(ref x) => {
+                self.push(e(&x.record));
+                self.record(&x.update, e);
+            }
+            Expr::RecordFieldAccess(ref x) => self.push(e(&x.record)),
+            Expr::List(ref x) => self.list(x, e),
+            Expr::ListComprehension(ref x) => {
+                self.push(e(&x.element));
+                self.qualifiers(&x.qualifiers);
+            }
+            Expr::Bits(ref x) => self.bits(x, e),
+            Expr::BitsComprehension(ref x) => {
+                self.push(e(&x.element));
+                self.qualifiers(&x.qualifiers);
+            }
+            Expr::MapComprehension(ref x) => {
+                self.push(e(&x.key));
+                self.push(e(&x.value));
+                self.qualifiers(&x.qualifiers);
+            }
+            Expr::Parenthesized(ref x) => self.push(e(&x.item)),
+            Expr::Fun(ref x) => match **x {
+                Fun::Defined(_) => {}
+                Fun::Anonymous(ref x) => self.clauses(&x.clauses, Node::FunClause),
+                Fun::Named(ref x) => self.clauses(&x.clauses, Node::NamedFunClause),
+            },
+            Expr::FunCall(ref x) => {
+                if let Some(ref m) = x.module {
+                    self.push(e(&m.name));
+                }
+                self.push(e(&x.name));
+                self.args(&x.args, e);
+            }
+            Expr::UnaryOpCall(ref x) => self.push(e(&x.operand)),
+            Expr::BinaryOpCall(ref x) => {
+                self.push(e(&x.left));
+                self.push(e(&x.right));
+            }
+            Expr::Match(ref x) => {
+                self.push(Node::Pattern(&x.pattern));
+                self.push(e(&x.value));
+            }
+            Expr::Block(ref x) => self.body(&x.body),
+            Expr::Catch(ref x) => self.body(&x.expr),
+            Expr::If(ref x) => self.clauses(&x.clauses, Node::IfClause),
+            Expr::Case(ref x) => {
+                self.push(e(&x.expr));
+                self.clauses(&x.clauses, Node::CaseClause);
+            }
+            Expr::Receive(ref x) => {
+                self.clauses(&x.clauses, Node::CaseClause);
+                if let Some(ref t) = x.timeout {
+                    self.push(e(&t.duration));
+                    self.body(&t.body);
+                }
+            }
+            Expr::Try(ref x) => self.try_expr(x),
+            Expr::Maybe(ref x) => {
+                self.body(&x.body);
+                if let Some(ref e) = x.else_clauses {
+                    self.clauses(&e.clauses, Node::CaseClause);
+                }
+            }
+            Expr::MaybeMatch(ref x) => {
+                self.push(Node::Pattern(&x.pattern));
+                self.push(e(&x.value));
+            }
+        }
+    }
+    fn try_expr(&mut self, x: &'a exprs::Try) {
+        self.body(&x.body);
+        if let Some(ref b) = x.branch {
+            self.clauses(&b.clauses, Node::CaseClause);
+        }
+        if let Some(ref c) = x.catch {
+            self.clauses(&c.clauses, Node::CatchClause);
+        }
+        if let Some(ref a) = x.after {
+            self.body(&a.body);
+        }
+    }
+    fn pattern(&mut self, x: &'a Pattern) {
+        let p = Node::Pattern;
+        match *x {
+            Pattern::Literal(_)
+            | Pattern::Variable(_)
+            | Pattern::RecordFieldIndex(_)
+            | Pattern::MacroCall(_) => {}
+            Pattern::Tuple(ref x) => self.tuple(x, p),
+            Pattern::Map(ref x) => self.map(x, p),
+            Pattern::Record(ref x) => self.record(x, p),
+            Pattern::List(ref x) => self.list(x, p),
+            Pattern::Bits(ref x) => self.bits(x, p),
+            Pattern::Parenthesized(ref x) => self.push(p(&x.item)),
+            Pattern::UnaryOpCall(ref x) => self.push(p(&x.operand)),
+            Pattern::BinaryOpCall(ref x) => {
+                self.push(p(&x.left));
+                self.push(p(&x.right));
+            }
+            Pattern::Match(ref x) => {
+                self.push(p(&x.pattern));
+                self.push(p(&x.value));
+            }
+        }
+    }
+    fn guard_test(&mut self, x: &'a GuardTest) {
+        let g = Node::GuardTest;
+        match *x {
+            GuardTest::Literal(_)
+            | GuardTest::Variable(_)
+            | GuardTest::RecordFieldIndex(_)
+            | GuardTest::MacroCall(_) => {}
+            GuardTest::Tuple(ref x) => self.tuple(x, g),
+            GuardTest::Map(ref x) => self.map(x, g),
+            GuardTest::Record(ref x) => self.record(x, g),
+            GuardTest::RecordFieldAccess(ref x) => self.push(g(&x.record)),
+            GuardTest::List(ref x) => self.list(x, g),
+            GuardTest::Bits(ref x) => self.bits(x, g),
+            GuardTest::Parenthesized(ref x) => self.push(g(&x.item)),
+            GuardTest::FunCall(ref x) => self.args(&x.args, g),
+            GuardTest::UnaryOpCall(ref x) => self.push(g(&x.operand)),
+            GuardTest::BinaryOpCall(ref x) => {
+                self.push(g(&x.left));
+                self.push(g(&x.right));
+            }
+        }
+    }
+    fn ty(&mut self, x: &'a Type) {
+        let t = Node::Type;
+        match *x {
+            Type::Literal(_) | Type::Variable(_) | Type::Bits(_) | Type::MacroCall(_) => {}
+            Type::Annotated(ref x) => self.push(t(&x.ty)),
+            Type::Tuple(ref x) => self.tuple(x, t),
+            Type::Map(ref x) => self.map(x, t),
+            Type::Record(ref x) => self.record(x, t),
+            Type::List(ref x) => {
+                if let Some(ref e) = x.element {
+                    self.push(t(&e.element_type));
+                }
+            }
+            Type::Parenthesized(ref x) => self.push(t(&x.item)),
+            Type::TypeCall(ref x) => self.args(&x.args, t),
+            Type::UnaryOpCall(ref x) => self.push(t(&x.operand)),
+            Type::BinaryOpCall(ref x) => {
+                self.push(t(&x.left));
+                self.push(t(&x.right));
+            }
+            Type::Fun(ref x) => {
+                if let types::Fun::Normal(ref x) = **x {
+                    self.args(&x.args, t);
+                    self.push(t(&x.return_type));
+                }
+            }
+            Type::Range(ref x) => {
+                self.push(t(&x.low));
+                self.push(t(&x.high));
+            }
+            Type::Union(ref x) => {
+                self.push(t(&x.left));
+                self.push(t(&x.right));
+            }
+        }
+    }
+}
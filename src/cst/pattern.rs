@@ -1,15 +1,19 @@
-use erl_tokenize::tokens::{AtomToken, SymbolToken, VariableToken};
+use erl_tokenize::tokens::{SymbolToken, VariableToken};
 use erl_tokenize::values::Symbol;
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 use trackable::error::ErrorKindExt;
 
 use super::commons::parts::{BinaryOp, UnaryOp};
+use super::commons::RecordName;
+use super::exprs;
 use super::patterns;
 use super::Literal;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{ErrorKind, Parser, Result};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pattern {
     Literal(Literal),
     Variable(VariableToken),
@@ -23,12 +27,14 @@ pub enum Pattern {
     UnaryOpCall(Box<patterns::UnaryOpCall>),
     BinaryOpCall(Box<patterns::BinaryOpCall>),
     Match(Box<patterns::Match>),
+    MacroCall(Box<exprs::MacroCall>),
 }
 impl Parse for Pattern {
     fn parse_non_left_recor<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
+        let start = parser.token_index();
         let kind = track!(parser.peek(|parser| HeadKind::guess(parser)))?;
         let pattern = match kind {
             HeadKind::Literal => Pattern::Literal(track!(parser.parse())?),
@@ -41,20 +47,28 @@ impl Parse for Pattern {
             HeadKind::Bits => Pattern::Bits(track!(parser.parse())?),
             HeadKind::UnaryOpCall => Pattern::UnaryOpCall(track!(parser.parse())?),
             HeadKind::Parenthesized => Pattern::Parenthesized(track!(parser.parse())?),
+            HeadKind::MacroCall => Pattern::MacroCall(track!(parser.parse())?),
         };
+        parser.record_match_lhs(start);
         Ok(pattern)
     }
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
         T: TokenRead,
     {
-        let head = track!(Pattern::parse_non_left_recor(parser))?;
-        let tail_kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
-        match tail_kind {
-            TailKind::BinaryOpCall => Ok(Pattern::BinaryOpCall(track!(parser.parse_tail(head))?)),
-            TailKind::Match => Ok(Pattern::Match(track!(parser.parse_tail(head))?)),
-            TailKind::None => Ok(head),
-        }
+        parser.nested(|parser| {
+            parser.memoize(|parser| {
+                let head = track!(Pattern::parse_non_left_recor(parser))?;
+                let tail_kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
+                match tail_kind {
+                    TailKind::BinaryOpCall => {
+                        Ok(Pattern::BinaryOpCall(track!(parser.parse_tail(head))?))
+                    }
+                    TailKind::Match => Ok(Pattern::Match(track!(parser.parse_tail(head))?)),
+                    TailKind::None => Ok(head),
+                }
+            })
+        })
     }
 }
 impl PositionRange for Pattern {
@@ -72,6 +86,7 @@ impl PositionRange for Pattern {
             Pattern::UnaryOpCall(ref x) => x.start_position(),
             Pattern::BinaryOpCall(ref x) => x.start_position(),
             Pattern::Match(ref x) => x.start_position(),
+            Pattern::MacroCall(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -88,9 +103,56 @@ impl PositionRange for Pattern {
             Pattern::UnaryOpCall(ref x) => x.end_position(),
             Pattern::BinaryOpCall(ref x) => x.end_position(),
             Pattern::Match(ref x) => x.end_position(),
+            Pattern::MacroCall(ref x) => x.end_position(),
         }
     }
 }
+impl CollectTokens for Pattern {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Pattern::Literal(ref x) => x.collect_tokens(tokens),
+            Pattern::Variable(ref x) => x.collect_tokens(tokens),
+            Pattern::Tuple(ref x) => x.collect_tokens(tokens),
+            Pattern::Map(ref x) => x.collect_tokens(tokens),
+            Pattern::Record(ref x) => x.collect_tokens(tokens),
+            Pattern::RecordFieldIndex(ref x) => x.collect_tokens(tokens),
+            Pattern::List(ref x) => x.collect_tokens(tokens),
+            Pattern::Bits(ref x) => x.collect_tokens(tokens),
+            Pattern::Parenthesized(ref x) => x.collect_tokens(tokens),
+            Pattern::UnaryOpCall(ref x) => x.collect_tokens(tokens),
+            Pattern::BinaryOpCall(ref x) => x.collect_tokens(tokens),
+            Pattern::Match(ref x) => x.collect_tokens(tokens),
+            Pattern::MacroCall(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Pattern {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Pattern::Literal(x) => Pattern::Literal(x.fold_with(folder)),
+            Pattern::Variable(x) => Pattern::Variable(x.fold_with(folder)),
+            Pattern::Tuple(x) => Pattern::Tuple(x.fold_with(folder)),
+            Pattern::Map(x) => Pattern::Map(x.fold_with(folder)),
+            Pattern::Record(x) => Pattern::Record(x.fold_with(folder)),
+            Pattern::RecordFieldIndex(x) => Pattern::RecordFieldIndex(x.fold_with(folder)),
+            Pattern::List(x) => Pattern::List(x.fold_with(folder)),
+            Pattern::Bits(x) => Pattern::Bits(x.fold_with(folder)),
+            Pattern::Parenthesized(x) => Pattern::Parenthesized(x.fold_with(folder)),
+            Pattern::UnaryOpCall(x) => Pattern::UnaryOpCall(x.fold_with(folder)),
+            Pattern::BinaryOpCall(x) => Pattern::BinaryOpCall(x.fold_with(folder)),
+            Pattern::Match(x) => Pattern::Match(x.fold_with(folder)),
+            Pattern::MacroCall(x) => Pattern::MacroCall(x.fold_with(folder)),
+        }
+    }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_pattern(self)
+    }
+}
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
 
 #[derive(Debug)]
 enum HeadKind {
@@ -104,6 +166,7 @@ enum HeadKind {
     Bits,
     UnaryOpCall,
     Parenthesized,
+    MacroCall,
 }
 impl HeadKind {
     fn guess<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
@@ -114,7 +177,7 @@ impl HeadKind {
                 Symbol::OpenParen => HeadKind::Parenthesized,
                 Symbol::OpenSquare => HeadKind::List,
                 Symbol::Sharp => {
-                    if parser.parse::<AtomToken>().is_ok() {
+                    if parser.parse::<RecordName>().is_ok() {
                         let next = parser.parse::<SymbolToken>().map(|t| t.value()).ok();
                         if next == Some(Symbol::Dot) {
                             HeadKind::RecordFieldIndex
@@ -125,6 +188,7 @@ impl HeadKind {
                         HeadKind::Map
                     }
                 }
+                Symbol::Question if parser.is_no_preprocess() => HeadKind::MacroCall,
                 _ => track!(UnaryOp::from_token(t.into())
                     .map(|_| HeadKind::UnaryOpCall)
                     .map_err(|e| ErrorKind::UnexpectedToken(e).error()))?,
@@ -138,6 +202,14 @@ impl HeadKind {
     }
 }
 
+/// Returns `true` if `less` is the beginning of a strict generator arrow (`<:-` or `<:=`).
+fn follows_strict_arrow<T: TokenRead>(parser: &mut Parser<T>, less: &SymbolToken) -> bool {
+    parser.parse::<SymbolToken>().is_ok_and(|t| {
+        matches!(t.value(), Symbol::Colon | Symbol::MapMatch)
+            && t.start_position().offset() == less.end_position().offset()
+    })
+}
+
 #[derive(Debug)]
 enum TailKind {
     BinaryOpCall,
@@ -152,6 +224,11 @@ impl TailKind {
         }
         Ok(match track!(parser.parse())? {
             LexicalToken::Symbol(ref t) if t.value() == Symbol::Match => TailKind::Match,
+            LexicalToken::Symbol(ref t)
+                if t.value() == Symbol::Less && follows_strict_arrow(parser, t) =>
+            {
+                TailKind::None
+            }
             token => {
                 if BinaryOp::from_token(token).is_ok() {
                     TailKind::BinaryOpCall
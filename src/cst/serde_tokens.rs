@@ -0,0 +1,43 @@
+//! (De)serialization of `Vec<LexicalToken>` fields.
+//!
+//! `LexicalToken` itself does not implement serde traits, but all of its variants do.
+use erl_tokenize::tokens::{
+    AtomToken, CharToken, FloatToken, IntegerToken, KeywordToken, StringToken, SymbolToken,
+    VariableToken,
+};
+use erl_tokenize::LexicalToken;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "LexicalToken")]
+enum LexicalTokenDef {
+    Atom(AtomToken),
+    Char(CharToken),
+    Float(FloatToken),
+    Integer(IntegerToken),
+    Keyword(KeywordToken),
+    String(StringToken),
+    Symbol(SymbolToken),
+    Variable(VariableToken),
+}
+
+struct TokenRef<'a>(&'a LexicalToken);
+impl Serialize for TokenRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LexicalTokenDef::serialize(self.0, serializer)
+    }
+}
+
+#[derive(Deserialize)]
+struct Token(#[serde(with = "LexicalTokenDef")] LexicalToken);
+
+pub fn serialize<S: Serializer>(tokens: &[LexicalToken], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(tokens.iter().map(TokenRef))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<LexicalToken>, D::Error> {
+    let tokens = Vec::<Token>::deserialize(deserializer)?;
+    Ok(tokens.into_iter().map(|t| t.0).collect())
+}
@@ -0,0 +1,109 @@
+use erl_tokenize::values::Whitespace;
+use erl_tokenize::{LexicalToken, PositionRange, Token, Tokenizer};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{Error, Result};
+
+/// Side-table of the comments and whitespaces (trivia) of a source text.
+///
+/// CST nodes hold only lexical tokens.
+/// This table keeps the rest, keyed by the offsets of the lexical tokens,
+/// so that the original text can be reproduced from a CST built from the same text.
+///
+/// Trivia following a token up to and including the next newline are the trailing trivia of the token.
+/// The others are the leading trivia of the next token
+/// (or, after the last token, the trivia at the end of the file).
+///
+/// Tokens produced by macro expansion or file inclusion have no trivia in this table.
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    tokens: Vec<Token>,
+    leading: HashMap<usize, Range<usize>>,
+    trailing: HashMap<usize, Range<usize>>,
+    end_of_file: Range<usize>,
+}
+impl Trivia {
+    /// Collects the trivia of `text`.
+    pub fn build(text: &str) -> Result<Self> {
+        let mut trivia = Trivia {
+            tokens: Vec::new(),
+            leading: HashMap::new(),
+            trailing: HashMap::new(),
+            end_of_file: 0..0,
+        };
+        let mut start = 0;
+        let mut last_end = None;
+        for token in Tokenizer::new(text) {
+            let token = track!(token.map_err(Error::from))?;
+            match token {
+                Token::Comment(_) | Token::Whitespace(_) => {
+                    let is_newline = match token {
+                        Token::Whitespace(ref t) => t.value() == Whitespace::Newline,
+                        _ => false,
+                    };
+                    trivia.tokens.push(token);
+                    if is_newline {
+                        if let Some(end) = last_end.take() {
+                            trivia.trailing.insert(end, start..trivia.tokens.len());
+                            start = trivia.tokens.len();
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(end) = last_end {
+                        trivia.trailing.insert(end, start..start);
+                    }
+                    trivia
+                        .leading
+                        .insert(token.start_position().offset(), start..trivia.tokens.len());
+                    start = trivia.tokens.len();
+                    last_end = Some(token.end_position().offset());
+                }
+            }
+        }
+        if let Some(end) = last_end {
+            trivia.trailing.insert(end, start..trivia.tokens.len());
+            start = trivia.tokens.len();
+        }
+        trivia.end_of_file = start..trivia.tokens.len();
+        Ok(trivia)
+    }
+
+    /// Returns the trivia preceding the first token of `node`.
+    pub fn leading<R: PositionRange>(&self, node: &R) -> &[Token] {
+        let range = self.leading.get(&node.start_position().offset());
+        range.map_or(&[], |r| &self.tokens[r.clone()])
+    }
+
+    /// Returns the trivia following the last token of `node`.
+    pub fn trailing<R: PositionRange>(&self, node: &R) -> &[Token] {
+        let range = self.trailing.get(&node.end_position().offset());
+        range.map_or(&[], |r| &self.tokens[r.clone()])
+    }
+
+    /// Returns the trivia following the last token of the text.
+    ///
+    /// If the text contains no tokens, this returns all the trivia.
+    pub fn end_of_file(&self) -> &[Token] {
+        &self.tokens[self.end_of_file.clone()]
+    }
+
+    /// Concatenates `tokens` with their trivia.
+    ///
+    /// If `tokens` are all the lexical tokens of the text (e.g., `ModuleDecl::tokens()`),
+    /// the result equals the text.
+    pub fn reproduce<'a, I>(&self, tokens: I) -> String
+    where
+        I: IntoIterator<Item = &'a LexicalToken>,
+    {
+        let mut text = String::new();
+        for token in tokens {
+            text.extend(self.leading(token).iter().map(Token::text));
+            text.push_str(token.text());
+            text.extend(self.trailing(token).iter().map(Token::text));
+        }
+        text.extend(self.end_of_file().iter().map(Token::text));
+        text
+    }
+}
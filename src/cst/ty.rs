@@ -1,15 +1,26 @@
-use erl_tokenize::tokens::{AtomToken, SymbolToken, VariableToken};
+use erl_tokenize::tokens::{SymbolToken, VariableToken};
 use erl_tokenize::values::{Keyword, Symbol};
 use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::fmt;
 use trackable::error::ErrorKindExt;
 
 use super::commons::parts::{BinaryOp, UnaryOp};
+use super::commons::RecordName;
+use super::exprs;
 use super::types;
 use super::Literal;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{ErrorKind, Parser, Result};
 
+/// Precedence of additive operators, the loosest binding operators allowed in integer types.
+const ARITH_PRECEDENCE: u8 = 6;
+
+/// Type expression.
+///
+/// From the loosest to the tightest binding, `::` (`Annotated`), `|` (`Union`), `..` (`Range`),
+/// additive operators, multiplicative operators and prefix operators are recognized.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Literal(Literal),
     Variable(VariableToken),
@@ -26,6 +37,7 @@ pub enum Type {
     Fun(Box<types::Fun>),
     Range(Box<types::Range>),
     Union(Box<types::Union>),
+    MacroCall(Box<exprs::MacroCall>),
 }
 impl Parse for Type {
     fn parse_non_left_recor<T>(parser: &mut Parser<T>) -> Result<Self>
@@ -43,9 +55,14 @@ impl Parse for Type {
             HeadKind::Map => Type::Map(track!(parser.parse())?),
             HeadKind::Record => Type::Record(track!(parser.parse())?),
             HeadKind::TypeCall => Type::TypeCall(track!(parser.parse())?),
-            HeadKind::UnaryOpCall => Type::UnaryOpCall(track!(parser.parse())?),
+            HeadKind::UnaryOpCall => {
+                let op = track!(parser.parse())?;
+                let operand = track!(parser.nested(Self::parse_non_left_recor))?;
+                Type::UnaryOpCall(Box::new(types::UnaryOpCall { op, operand }))
+            }
             HeadKind::Parenthesized => Type::Parenthesized(track!(parser.parse())?),
             HeadKind::Fun => Type::Fun(track!(parser.parse())?),
+            HeadKind::MacroCall => Type::MacroCall(track!(parser.parse())?),
         };
         Ok(ty)
     }
@@ -53,14 +70,58 @@ impl Parse for Type {
     where
         T: TokenRead,
     {
-        let head = track!(Type::parse_non_left_recor(parser))?;
-        let tail_kind = track!(parser.peek(|parser| TailKind::guess(parser)))?;
-        match tail_kind {
-            TailKind::BinaryOpCall => Ok(Type::BinaryOpCall(track!(parser.parse_tail(head))?)),
-            TailKind::Union => Ok(Type::Union(track!(parser.parse_tail(head))?)),
-            TailKind::Range => Ok(Type::Range(track!(parser.parse_tail(head))?)),
-            TailKind::None => Ok(head),
+        parser.nested(|parser| {
+            parser.memoize(|parser| {
+                let left = track!(Self::parse_range(parser))?;
+                if track!(Self::peek_symbol(parser))? != Some(Symbol::VerticalBar) {
+                    return Ok(left);
+                }
+                let _or = track!(parser.expect(&Symbol::VerticalBar))?;
+                let right = track!(Self::parse(parser))?;
+                Ok(Type::Union(Box::new(types::Union { left, _or, right })))
+            })
+        })
+    }
+}
+impl Type {
+    /// Parses a range type (`Type` `..` `Type`) or a type which binds more tightly than it.
+    fn parse_range<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
+        let low = track!(Self::parse_binary_op_call(parser, ARITH_PRECEDENCE))?;
+        if track!(Self::peek_symbol(parser))? != Some(Symbol::DoubleDot) {
+            return Ok(low);
+        }
+        let _dot = track!(parser.expect(&Symbol::DoubleDot))?;
+        let high = track!(Self::parse_binary_op_call(parser, ARITH_PRECEDENCE))?;
+        Ok(Type::Range(Box::new(types::Range { low, _dot, high })))
+    }
+
+    /// Parses arithmetic operator calls (used in integer types) by precedence climbing.
+    fn parse_binary_op_call<T: TokenRead>(
+        parser: &mut Parser<T>,
+        min_precedence: u8,
+    ) -> Result<Self> {
+        let mut left = track!(Self::parse_non_left_recor(parser))?;
+        loop {
+            let op = match parser.peek(|parser| parser.parse::<BinaryOp>()) {
+                Ok(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            let next_precedence = op.precedence() + 1;
+            let op: BinaryOp = track!(parser.parse())?;
+            let right = track!(
+                parser.nested(|parser| Self::parse_binary_op_call(parser, next_precedence))
+            )?;
+            left = Type::BinaryOpCall(Box::new(types::BinaryOpCall { left, op, right }));
         }
+        Ok(left)
+    }
+
+    fn peek_symbol<T: TokenRead>(parser: &mut Parser<T>) -> Result<Option<Symbol>> {
+        if track!(parser.eos())? {
+            return Ok(None);
+        }
+        let token = track!(parser.peek(|parser| parser.parse::<LexicalToken>()))?;
+        Ok(token.as_symbol_token().map(SymbolToken::value))
     }
 }
 impl PositionRange for Type {
@@ -81,6 +142,7 @@ impl PositionRange for Type {
             Type::BinaryOpCall(ref x) => x.start_position(),
             Type::Range(ref x) => x.start_position(),
             Type::Union(ref x) => x.start_position(),
+            Type::MacroCall(ref x) => x.start_position(),
         }
     }
     fn end_position(&self) -> Position {
@@ -100,9 +162,62 @@ impl PositionRange for Type {
             Type::BinaryOpCall(ref x) => x.end_position(),
             Type::Range(ref x) => x.end_position(),
             Type::Union(ref x) => x.end_position(),
+            Type::MacroCall(ref x) => x.end_position(),
+        }
+    }
+}
+impl CollectTokens for Type {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Type::Literal(ref x) => x.collect_tokens(tokens),
+            Type::Variable(ref x) => x.collect_tokens(tokens),
+            Type::Annotated(ref x) => x.collect_tokens(tokens),
+            Type::Tuple(ref x) => x.collect_tokens(tokens),
+            Type::Map(ref x) => x.collect_tokens(tokens),
+            Type::Record(ref x) => x.collect_tokens(tokens),
+            Type::List(ref x) => x.collect_tokens(tokens),
+            Type::Bits(ref x) => x.collect_tokens(tokens),
+            Type::Parenthesized(ref x) => x.collect_tokens(tokens),
+            Type::TypeCall(ref x) => x.collect_tokens(tokens),
+            Type::UnaryOpCall(ref x) => x.collect_tokens(tokens),
+            Type::BinaryOpCall(ref x) => x.collect_tokens(tokens),
+            Type::Fun(ref x) => x.collect_tokens(tokens),
+            Type::Range(ref x) => x.collect_tokens(tokens),
+            Type::Union(ref x) => x.collect_tokens(tokens),
+            Type::MacroCall(ref x) => x.collect_tokens(tokens),
         }
     }
 }
+impl Foldable for Type {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Type::Literal(x) => Type::Literal(x.fold_with(folder)),
+            Type::Variable(x) => Type::Variable(x.fold_with(folder)),
+            Type::Annotated(x) => Type::Annotated(x.fold_with(folder)),
+            Type::Tuple(x) => Type::Tuple(x.fold_with(folder)),
+            Type::Map(x) => Type::Map(x.fold_with(folder)),
+            Type::Record(x) => Type::Record(x.fold_with(folder)),
+            Type::List(x) => Type::List(x.fold_with(folder)),
+            Type::Bits(x) => Type::Bits(x.fold_with(folder)),
+            Type::Parenthesized(x) => Type::Parenthesized(x.fold_with(folder)),
+            Type::TypeCall(x) => Type::TypeCall(x.fold_with(folder)),
+            Type::UnaryOpCall(x) => Type::UnaryOpCall(x.fold_with(folder)),
+            Type::BinaryOpCall(x) => Type::BinaryOpCall(x.fold_with(folder)),
+            Type::Fun(x) => Type::Fun(x.fold_with(folder)),
+            Type::Range(x) => Type::Range(x.fold_with(folder)),
+            Type::Union(x) => Type::Union(x.fold_with(folder)),
+            Type::MacroCall(x) => Type::MacroCall(x.fold_with(folder)),
+        }
+    }
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        folder.fold_type(self)
+    }
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_erlang_source())
+    }
+}
 
 #[derive(Debug)]
 enum HeadKind {
@@ -118,6 +233,7 @@ enum HeadKind {
     TypeCall,
     UnaryOpCall,
     Parenthesized,
+    MacroCall,
 }
 impl HeadKind {
     fn guess<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
@@ -128,12 +244,13 @@ impl HeadKind {
                 Symbol::OpenParen => HeadKind::Parenthesized,
                 Symbol::OpenSquare => HeadKind::List,
                 Symbol::Sharp => {
-                    if parser.parse::<AtomToken>().is_ok() {
+                    if parser.parse::<RecordName>().is_ok() {
                         HeadKind::Record
                     } else {
                         HeadKind::Map
                     }
                 }
+                Symbol::Question if parser.is_no_preprocess() => HeadKind::MacroCall,
                 _ => track!(UnaryOp::from_token(t.into())
                     .map(|_| HeadKind::UnaryOpCall)
                     .map_err(|e| ErrorKind::UnexpectedToken(e).error()))?,
@@ -165,31 +282,3 @@ impl HeadKind {
         })
     }
 }
-
-#[derive(Debug)]
-enum TailKind {
-    BinaryOpCall,
-    Union,
-    Range,
-    None,
-}
-impl TailKind {
-    fn guess<T: TokenRead>(parser: &mut Parser<T>) -> Result<Self> {
-        let is_eos = track!(parser.eos())?;
-        if is_eos {
-            return Ok(TailKind::None);
-        }
-        let token = track!(parser.parse::<LexicalToken>())?;
-        Ok(match token.as_symbol_token().map(SymbolToken::value) {
-            Some(Symbol::VerticalBar) => TailKind::Union,
-            Some(Symbol::DoubleDot) => TailKind::Range,
-            _ => {
-                if BinaryOp::from_token(token).is_ok() {
-                    TailKind::BinaryOpCall
-                } else {
-                    TailKind::None
-                }
-            }
-        })
-    }
-}
@@ -1,12 +1,12 @@
 use erl_tokenize::tokens::{AtomToken, KeywordToken, SymbolToken, VariableToken};
 use erl_tokenize::values::{Keyword, Symbol};
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use self::parts::{BitsSpec, ListElement};
 use crate::cst::commons;
 use crate::cst::commons::parts::{Args, Sequence};
 use crate::cst::Type;
-use crate::traits::{Parse, ParseTail, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, ParseTail, TokenRead};
 use crate::{Parser, Result};
 
 pub mod parts;
@@ -21,6 +21,7 @@ pub type BinaryOpCall = commons::BinaryOpCall<Type>;
 
 /// `AnyFun | AnyArityFun | NormalFun`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum Fun {
     Any(AnyFun),
@@ -58,9 +59,28 @@ impl PositionRange for Fun {
         }
     }
 }
+impl CollectTokens for Fun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Fun::Any(ref x) => x.collect_tokens(tokens),
+            Fun::AnyArity(ref x) => x.collect_tokens(tokens),
+            Fun::Normal(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Fun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Fun::Any(x) => Fun::Any(x.fold_with(folder)),
+            Fun::AnyArity(x) => Fun::AnyArity(x.fold_with(folder)),
+            Fun::Normal(x) => Fun::Normal(x.fold_with(folder)),
+        }
+    }
+}
 
 /// `fun` `(` `)`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnyFun {
     pub _fun: KeywordToken,
     pub _open: SymbolToken,
@@ -86,9 +106,26 @@ impl PositionRange for AnyFun {
         self._close.end_position()
     }
 }
+impl CollectTokens for AnyFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for AnyFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        AnyFun {
+            _fun: self._fun.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `fun` `(` `(` `...` `)` `)` `->` `Type` `)`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnyArityFun {
     pub _fun: KeywordToken,
     pub _open: SymbolToken,
@@ -124,9 +161,36 @@ impl PositionRange for AnyArityFun {
         self._close.end_position()
     }
 }
+impl CollectTokens for AnyArityFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self._args_open.collect_tokens(tokens);
+        self._args.collect_tokens(tokens);
+        self._args_close.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.return_type.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for AnyArityFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        AnyArityFun {
+            _fun: self._fun.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            _args_open: self._args_open.fold_with(folder),
+            _args: self._args.fold_with(folder),
+            _args_close: self._args_close.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            return_type: self.return_type.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `fun` `(` `Args<Type>` `->` `Type` `)`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NormalFun {
     pub _fun: KeywordToken,
     pub _open: SymbolToken,
@@ -158,12 +222,35 @@ impl PositionRange for NormalFun {
         self._close.end_position()
     }
 }
+impl CollectTokens for NormalFun {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._fun.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.args.collect_tokens(tokens);
+        self._arrow.collect_tokens(tokens);
+        self.return_type.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for NormalFun {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        NormalFun {
+            _fun: self._fun.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            args: self.args.fold_with(folder),
+            _arrow: self._arrow.fold_with(folder),
+            return_type: self.return_type.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
-/// `when` `Sequence<Type>`
+/// `when` `Sequence<Constraint>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Constraints {
     pub _when: KeywordToken,
-    pub constraints: Sequence<Type>,
+    pub constraints: Sequence<Constraint>,
 }
 impl Parse for Constraints {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
@@ -184,9 +271,195 @@ impl PositionRange for Constraints {
         self.constraints.end_position()
     }
 }
+impl CollectTokens for Constraints {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._when.collect_tokens(tokens);
+        self.constraints.collect_tokens(tokens);
+    }
+}
+impl Foldable for Constraints {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Constraints {
+            _when: self._when.fold_with(folder),
+            constraints: self.constraints.fold_with(folder),
+        }
+    }
+}
+
+/// `SubtypeConstraint | IsSubtypeConstraint`
+///
+/// Both bound the type variable `var` by the type `bound`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constraint {
+    Subtype(SubtypeConstraint),
+    IsSubtype(IsSubtypeConstraint),
+}
+impl Constraint {
+    pub fn var(&self) -> &VariableToken {
+        match *self {
+            Constraint::Subtype(ref x) => &x.var,
+            Constraint::IsSubtype(ref x) => &x.var,
+        }
+    }
+    pub fn bound(&self) -> &Type {
+        match *self {
+            Constraint::Subtype(ref x) => &x.bound,
+            Constraint::IsSubtype(ref x) => &x.bound,
+        }
+    }
+}
+impl Parse for Constraint {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        let token = track!(parser.peek(|parser| parser.parse::<LexicalToken>()))?;
+        if let LexicalToken::Variable(_) = token {
+            Ok(Constraint::Subtype(track!(parser.parse())?))
+        } else {
+            Ok(Constraint::IsSubtype(track!(parser.parse())?))
+        }
+    }
+}
+impl PositionRange for Constraint {
+    fn start_position(&self) -> Position {
+        match *self {
+            Constraint::Subtype(ref x) => x.start_position(),
+            Constraint::IsSubtype(ref x) => x.start_position(),
+        }
+    }
+    fn end_position(&self) -> Position {
+        match *self {
+            Constraint::Subtype(ref x) => x.end_position(),
+            Constraint::IsSubtype(ref x) => x.end_position(),
+        }
+    }
+}
+impl CollectTokens for Constraint {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            Constraint::Subtype(ref x) => x.collect_tokens(tokens),
+            Constraint::IsSubtype(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for Constraint {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            Constraint::Subtype(x) => Constraint::Subtype(x.fold_with(folder)),
+            Constraint::IsSubtype(x) => Constraint::IsSubtype(x.fold_with(folder)),
+        }
+    }
+}
+
+/// `VariableToken` `::` `Type`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubtypeConstraint {
+    pub var: VariableToken,
+    pub _double_colon: SymbolToken,
+    pub bound: Type,
+}
+impl Parse for SubtypeConstraint {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(SubtypeConstraint {
+            var: track!(parser.parse())?,
+            _double_colon: track!(parser.expect(&Symbol::DoubleColon))?,
+            bound: track!(parser.parse())?,
+        })
+    }
+}
+impl PositionRange for SubtypeConstraint {
+    fn start_position(&self) -> Position {
+        self.var.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self.bound.end_position()
+    }
+}
+impl CollectTokens for SubtypeConstraint {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.var.collect_tokens(tokens);
+        self._double_colon.collect_tokens(tokens);
+        self.bound.collect_tokens(tokens);
+    }
+}
+impl Foldable for SubtypeConstraint {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        SubtypeConstraint {
+            var: self.var.fold_with(folder),
+            _double_colon: self._double_colon.fold_with(folder),
+            bound: self.bound.fold_with(folder),
+        }
+    }
+}
+
+/// `is_subtype` `(` `VariableToken` `,` `Type` `)`
+///
+/// The old style of `SubtypeConstraint`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IsSubtypeConstraint {
+    pub _is_subtype: AtomToken,
+    pub _open: SymbolToken,
+    pub var: VariableToken,
+    pub _comma: SymbolToken,
+    pub bound: Type,
+    pub _close: SymbolToken,
+}
+impl Parse for IsSubtypeConstraint {
+    fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
+    where
+        T: TokenRead,
+    {
+        Ok(IsSubtypeConstraint {
+            _is_subtype: track!(parser.expect("is_subtype"))?,
+            _open: track!(parser.expect(&Symbol::OpenParen))?,
+            var: track!(parser.parse())?,
+            _comma: track!(parser.expect(&Symbol::Comma))?,
+            bound: track!(parser.parse())?,
+            _close: track!(parser.expect(&Symbol::CloseParen))?,
+        })
+    }
+}
+impl PositionRange for IsSubtypeConstraint {
+    fn start_position(&self) -> Position {
+        self._is_subtype.start_position()
+    }
+    fn end_position(&self) -> Position {
+        self._close.end_position()
+    }
+}
+impl CollectTokens for IsSubtypeConstraint {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._is_subtype.collect_tokens(tokens);
+        self._open.collect_tokens(tokens);
+        self.var.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.bound.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for IsSubtypeConstraint {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        IsSubtypeConstraint {
+            _is_subtype: self._is_subtype.fold_with(folder),
+            _open: self._open.fold_with(folder),
+            var: self.var.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            bound: self.bound.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `Type` `..` `Type`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Range {
     pub low: Type,
     pub _dot: SymbolToken,
@@ -213,9 +486,26 @@ impl PositionRange for Range {
         self.high.end_position()
     }
 }
+impl CollectTokens for Range {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.low.collect_tokens(tokens);
+        self._dot.collect_tokens(tokens);
+        self.high.collect_tokens(tokens);
+    }
+}
+impl Foldable for Range {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Range {
+            low: self.low.fold_with(folder),
+            _dot: self._dot.fold_with(folder),
+            high: self.high.fold_with(folder),
+        }
+    }
+}
 
 /// `Type` `|` `Type`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Union {
     pub left: Type,
     pub _or: SymbolToken,
@@ -242,9 +532,26 @@ impl PositionRange for Union {
         self.right.end_position()
     }
 }
+impl CollectTokens for Union {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.left.collect_tokens(tokens);
+        self._or.collect_tokens(tokens);
+        self.right.collect_tokens(tokens);
+    }
+}
+impl Foldable for Union {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Union {
+            left: self.left.fold_with(folder),
+            _or: self._or.fold_with(folder),
+            right: self.right.fold_with(folder),
+        }
+    }
+}
 
 /// `VariableToken` `::` `Type`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Annotated {
     pub var: VariableToken,
     pub _colon: SymbolToken,
@@ -270,9 +577,26 @@ impl PositionRange for Annotated {
         self.ty.end_position()
     }
 }
+impl CollectTokens for Annotated {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.var.collect_tokens(tokens);
+        self._colon.collect_tokens(tokens);
+        self.ty.collect_tokens(tokens);
+    }
+}
+impl Foldable for Annotated {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Annotated {
+            var: self.var.fold_with(folder),
+            _colon: self._colon.fold_with(folder),
+            ty: self.ty.fold_with(folder),
+        }
+    }
+}
 
 /// `[` `Option<ListElement>` `]`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct List {
     pub _open: SymbolToken,
     pub element: Option<ListElement>,
@@ -298,9 +622,26 @@ impl PositionRange for List {
         self._close.end_position()
     }
 }
+impl CollectTokens for List {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.element.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for List {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        List {
+            _open: self._open.fold_with(folder),
+            element: self.element.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
 
 /// `<<` `Option<BitsSpec>` `>>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bits {
     pub _open: SymbolToken,
     pub spec: Option<BitsSpec>,
@@ -326,3 +667,19 @@ impl PositionRange for Bits {
         self._close.end_position()
     }
 }
+impl CollectTokens for Bits {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._open.collect_tokens(tokens);
+        self.spec.collect_tokens(tokens);
+        self._close.collect_tokens(tokens);
+    }
+}
+impl Foldable for Bits {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Bits {
+            _open: self._open.fold_with(folder),
+            spec: self.spec.fold_with(folder),
+            _close: self._close.fold_with(folder),
+        }
+    }
+}
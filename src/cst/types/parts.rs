@@ -1,13 +1,14 @@
 use erl_tokenize::tokens::{IntegerToken, SymbolToken, VariableToken};
 use erl_tokenize::values::Symbol;
-use erl_tokenize::{Position, PositionRange};
+use erl_tokenize::{LexicalToken, Position, PositionRange};
 
 use super::Type;
-use crate::traits::{Parse, TokenRead};
+use crate::traits::{CollectTokens, Fold, Foldable, Parse, TokenRead};
 use crate::{Parser, Result};
 
 /// `Type` `Option<NonEmpty>`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ListElement {
     pub element_type: Type,
     pub non_empty: Option<NonEmpty>,
@@ -31,9 +32,24 @@ impl PositionRange for ListElement {
             .unwrap_or_else(|| self.element_type.end_position())
     }
 }
+impl CollectTokens for ListElement {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.element_type.collect_tokens(tokens);
+        self.non_empty.collect_tokens(tokens);
+    }
+}
+impl Foldable for ListElement {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ListElement {
+            element_type: self.element_type.fold_with(folder),
+            non_empty: self.non_empty.fold_with(folder),
+        }
+    }
+}
 
 /// `,` `...`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NonEmpty {
     pub _comma: SymbolToken,
     pub _triple_dot: SymbolToken,
@@ -54,9 +70,24 @@ impl PositionRange for NonEmpty {
         self._triple_dot.end_position()
     }
 }
+impl CollectTokens for NonEmpty {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._comma.collect_tokens(tokens);
+        self._triple_dot.collect_tokens(tokens);
+    }
+}
+impl Foldable for NonEmpty {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        NonEmpty {
+            _comma: self._comma.fold_with(folder),
+            _triple_dot: self._triple_dot.fold_with(folder),
+        }
+    }
+}
 
 /// `ByteSize` `,` `BitSize`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ByteAndBitSize {
     pub byte: ByteSize,
     pub _comma: SymbolToken,
@@ -79,9 +110,26 @@ impl PositionRange for ByteAndBitSize {
         self.bit.end_position()
     }
 }
+impl CollectTokens for ByteAndBitSize {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self.byte.collect_tokens(tokens);
+        self._comma.collect_tokens(tokens);
+        self.bit.collect_tokens(tokens);
+    }
+}
+impl Foldable for ByteAndBitSize {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ByteAndBitSize {
+            byte: self.byte.fold_with(folder),
+            _comma: self._comma.fold_with(folder),
+            bit: self.bit.fold_with(folder),
+        }
+    }
+}
 
 /// `_` `:` `IntegerToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ByteSize {
     pub _underscore: VariableToken,
     pub _colon: SymbolToken,
@@ -104,9 +152,26 @@ impl PositionRange for ByteSize {
         self.size.end_position()
     }
 }
+impl CollectTokens for ByteSize {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._underscore.collect_tokens(tokens);
+        self._colon.collect_tokens(tokens);
+        self.size.collect_tokens(tokens);
+    }
+}
+impl Foldable for ByteSize {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        ByteSize {
+            _underscore: self._underscore.fold_with(folder),
+            _colon: self._colon.fold_with(folder),
+            size: self.size.fold_with(folder),
+        }
+    }
+}
 
 /// `_` `:` `_` `*` `IntegerToken`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitSize {
     pub _underscore0: VariableToken,
     pub _colon: SymbolToken,
@@ -133,9 +198,30 @@ impl PositionRange for BitSize {
         self.size.end_position()
     }
 }
+impl CollectTokens for BitSize {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        self._underscore0.collect_tokens(tokens);
+        self._colon.collect_tokens(tokens);
+        self._underscore1.collect_tokens(tokens);
+        self._asterisk.collect_tokens(tokens);
+        self.size.collect_tokens(tokens);
+    }
+}
+impl Foldable for BitSize {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        BitSize {
+            _underscore0: self._underscore0.fold_with(folder),
+            _colon: self._colon.fold_with(folder),
+            _underscore1: self._underscore1.fold_with(folder),
+            _asterisk: self._asterisk.fold_with(folder),
+            size: self.size.fold_with(folder),
+        }
+    }
+}
 
 /// `ByteAndBitSize` | `ByteSize` | `BitSize`
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum BitsSpec {
     BytesAndBits(ByteAndBitSize),
@@ -169,3 +255,21 @@ impl PositionRange for BitsSpec {
         }
     }
 }
+impl CollectTokens for BitsSpec {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        match *self {
+            BitsSpec::BytesAndBits(ref x) => x.collect_tokens(tokens),
+            BitsSpec::Bytes(ref x) => x.collect_tokens(tokens),
+            BitsSpec::Bits(ref x) => x.collect_tokens(tokens),
+        }
+    }
+}
+impl Foldable for BitsSpec {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            BitsSpec::BytesAndBits(x) => BitsSpec::BytesAndBits(x.fold_with(folder)),
+            BitsSpec::Bytes(x) => BitsSpec::Bytes(x.fold_with(folder)),
+            BitsSpec::Bits(x) => BitsSpec::Bits(x.fold_with(folder)),
+        }
+    }
+}
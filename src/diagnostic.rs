@@ -0,0 +1,223 @@
+use erl_tokenize::{Position, PositionRange};
+use std::fmt;
+
+use crate::messages::{English, Message, MessageCatalog};
+
+/// Severity of a `Diagnostic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in source code, located by a position range.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: Message,
+    pub start_position: Position,
+    pub end_position: Position,
+    pub related: Vec<RelatedInfo>,
+
+    /// Suggested changes which resolve the problem.
+    pub fixes: Vec<Fix>,
+
+    /// The source texts of the tokens which would have been accepted at the start position
+    /// (set to syntax errors by `Parser::error_diagnostic`).
+    pub expected: Vec<String>,
+}
+impl Diagnostic {
+    pub fn new<R: PositionRange>(
+        severity: Severity,
+        code: &'static str,
+        message: Message,
+        range: &R,
+    ) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            message,
+            start_position: range.start_position(),
+            end_position: range.end_position(),
+            related: Vec::new(),
+            fixes: Vec::new(),
+            expected: Vec::new(),
+        }
+    }
+    pub fn error<R: PositionRange>(code: &'static str, message: Message, range: &R) -> Self {
+        Self::new(Severity::Error, code, message, range)
+    }
+    pub fn warning<R: PositionRange>(code: &'static str, message: Message, range: &R) -> Self {
+        Self::new(Severity::Warning, code, message, range)
+    }
+    pub fn with_related<R: PositionRange>(mut self, message: Message, range: &R) -> Self {
+        self.related.push(RelatedInfo {
+            message,
+            start_position: range.start_position(),
+            end_position: range.end_position(),
+        });
+        self
+    }
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+
+    /// Formats this diagnostic in the same way as `Display` but with the messages in `catalog`.
+    pub fn format<C: MessageCatalog + ?Sized>(&self, catalog: &C) -> String {
+        format!(
+            "{}:{}: {}[{}]: {}",
+            self.start_position.line(),
+            self.start_position.column(),
+            self.severity,
+            self.code,
+            self.message.format(catalog)
+        )
+    }
+
+    /// Renders this diagnostic for printing by CLI tools.
+    ///
+    /// In addition to the line formatted by `format`, the line of `text` (the source text)
+    /// containing the start position is shown with carets under the range (up to the end of the line),
+    /// followed by the expected tokens (if any).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erl_parse::cst::ModuleDecl;
+    /// use erl_parse::messages::English;
+    /// use erl_parse::{Parser, TokenReader};
+    /// use erl_pp::Preprocessor;
+    /// use erl_tokenize::Lexer;
+    ///
+    /// let text = "f() -> ok.\ng(X -> X.";
+    /// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    /// let error = parser.parse::<ModuleDecl>().unwrap_err();
+    /// let diagnostic = parser.error_diagnostic(&error);
+    /// let rendered = diagnostic.render(text, &English);
+    /// assert!(rendered.ends_with("\n  |\n2 | g(X -> X.\n  |     ^^\n  = expected one of `,`, `)`"));
+    /// ```
+    pub fn render<C: MessageCatalog + ?Sized>(&self, text: &str, catalog: &C) -> String {
+        let mut lines = vec![self.format(catalog)];
+        let start = self.start_position.offset();
+        if let Some(before) = text.get(..start) {
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+            let end = self.end_position.offset().clamp(start, line_end);
+            let indent = text[line_start..start]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let width = text.get(start..end).map_or(0, |s| s.chars().count()).max(1);
+            let number = self.start_position.line().to_string();
+            let margin = " ".repeat(number.len());
+            lines.push(format!("{} |", margin));
+            lines.push(format!(
+                "{} | {}",
+                number,
+                text[line_start..line_end].trim_end_matches('\r')
+            ));
+            lines.push(format!("{} | {}{}", margin, indent, "^".repeat(width)));
+        }
+        if !self.expected.is_empty() {
+            let tokens = self
+                .expected
+                .iter()
+                .map(|t| format!("`{}`", t))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = Message::new("expected_tokens").arg("tokens", tokens);
+            let margin = " ".repeat(self.start_position.line().to_string().len());
+            lines.push(format!("{} = {}", margin, message.format(catalog)));
+        }
+        lines.join("\n")
+    }
+}
+impl PositionRange for Diagnostic {
+    fn start_position(&self) -> Position {
+        self.start_position.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end_position.clone()
+    }
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(&English))
+    }
+}
+
+/// Secondary location attached to a `Diagnostic` (e.g., the first definition of a duplicate).
+#[derive(Debug, Clone)]
+pub struct RelatedInfo {
+    pub message: Message,
+    pub start_position: Position,
+    pub end_position: Position,
+}
+impl PositionRange for RelatedInfo {
+    fn start_position(&self) -> Position {
+        self.start_position.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end_position.clone()
+    }
+}
+
+/// A suggested change to source code, consisting of one or more text edits.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub title: Message,
+    pub edits: Vec<TextEdit>,
+}
+impl Fix {
+    /// Applies the edits of this fix to `text`.
+    ///
+    /// The edits must not overlap each other.
+    pub fn apply(&self, text: &str) -> String {
+        let mut edits = self.edits.iter().collect::<Vec<_>>();
+        edits.sort_by_key(|e| e.start_position.offset());
+        let mut result = String::with_capacity(text.len());
+        let mut offset = 0;
+        for edit in edits {
+            result.push_str(&text[offset..edit.start_position.offset()]);
+            result.push_str(&edit.new_text);
+            offset = edit.end_position.offset();
+        }
+        result.push_str(&text[offset..]);
+        result
+    }
+}
+
+/// Replacement of the text in a position range.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub start_position: Position,
+    pub end_position: Position,
+    pub new_text: String,
+}
+impl TextEdit {
+    pub fn replace<R: PositionRange>(range: &R, new_text: String) -> Self {
+        TextEdit {
+            start_position: range.start_position(),
+            end_position: range.end_position(),
+            new_text,
+        }
+    }
+}
+impl PositionRange for TextEdit {
+    fn start_position(&self) -> Position {
+        self.start_position.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end_position.clone()
+    }
+}
@@ -1,7 +1,9 @@
-use erl_pp;
+use erl_pp::MacroDef;
 use erl_tokenize::{self, LexicalToken};
-use trackable::error::TrackableError;
-use trackable::error::{ErrorKind as TrackableErrorKind};
+use trackable::error::ErrorKind as TrackableErrorKind;
+use trackable::error::{ErrorKindExt, TrackableError};
+
+use crate::{OtpRelease, Span, SyntaxFeature};
 
 /// This crate specific error type.
 #[derive(Debug, Clone, TrackableError)]
@@ -20,6 +22,20 @@ impl From<erl_tokenize::Error> for Error {
 
 impl From<erl_pp::Error> for Error {
     fn from(f: erl_pp::Error) -> Self {
+        if let erl_pp::Error::MacroArgsMismatched {
+            ref macro_call,
+            macro_def: MacroDef::Static(ref definition),
+        } = f
+        {
+            let mismatch = MacroArgsMismatch {
+                name: macro_call.name.value().to_owned(),
+                params: definition.variables.as_ref().map(|v| v.len()),
+                args: macro_call.args.as_ref().map(|a| a.len()),
+                call: Span::of(macro_call),
+                definition: Span::of(definition),
+            };
+            return ErrorKind::MacroArgsMismatched(Box::new(mismatch)).into();
+        }
         ErrorKind::PreprocessorError(format!("{:?}", f)).into()
         // match f.kind().clone() {
         //     erl_pp::ErrorKind::InvalidInput => ErrorKind::InvalidInput.takes_over(f).into(),
@@ -31,17 +47,105 @@ impl From<erl_pp::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(f: std::io::Error) -> Self {
+        ErrorKind::Other.cause(f).into()
+    }
+}
+
 /// The list of the possible error kinds
 #[derive(Clone, Debug)]
 pub enum ErrorKind {
     InvalidInput,
     UnexpectedToken(LexicalToken),
     UnexpectedEos,
+
+    /// The input exceeds `InputLimits` or `Limits`
+    LimitExceeded,
     Other,
     /// Wrap tokenizer error without processing
     TokenizeError(String), // erl_tokenize::Error
     /// Wrap preprocessor error without processing
     PreprocessorError(String), // erl_pp::Error, but cloning io and glob error is not implemented
+
+    /// A macro is invoked with a different number of arguments from its definition
+    MacroArgsMismatched(Box<MacroArgsMismatch>),
+
+    /// A guard contains an expression which is not allowed in guards (checked in strict mode)
+    IllegalGuardExpr(Box<IllegalGuardExpr>),
+
+    /// A clause of a function declaration has a different name or arity from the previous one
+    HeadMismatch(Box<HeadMismatch>),
+
+    /// A form uses syntax which is not accepted by the target OTP release (see `ParserBuilder::otp_release`)
+    UnsupportedFeature(Box<UnsupportedFeature>),
 }
 
 impl TrackableErrorKind for ErrorKind {}
+
+/// Details of `ErrorKind::MacroArgsMismatched`.
+#[derive(Clone, Debug)]
+pub struct MacroArgsMismatch {
+    pub name: String,
+
+    /// The number of the parameters (`None` if the macro is defined without parentheses)
+    pub params: Option<usize>,
+
+    /// The number of the arguments (`None` if the macro is invoked without parentheses)
+    pub args: Option<usize>,
+
+    pub call: Span,
+    pub definition: Span,
+}
+
+/// Details of `ErrorKind::HeadMismatch`.
+#[derive(Clone, Debug)]
+pub struct HeadMismatch {
+    /// The name and arity of the previous clause (e.g., `foo/1`)
+    pub previous: String,
+
+    /// The name and arity of the mismatched clause
+    pub name: String,
+
+    /// The heads (i.e., the names and the arguments) of the clauses
+    pub previous_head: Span,
+    pub head: Span,
+}
+
+/// Details of `ErrorKind::UnsupportedFeature`.
+#[derive(Clone, Debug)]
+pub struct UnsupportedFeature {
+    pub feature: SyntaxFeature,
+
+    /// The first release which accepts the feature
+    pub required_release: OtpRelease,
+
+    /// The feature which must be enabled by `-feature(Name, enable).` to use the syntax
+    /// (`None` if the release does not accept it at all)
+    pub feature_attr: Option<String>,
+
+    pub span: Span,
+}
+
+/// Details of `ErrorKind::IllegalGuardExpr`.
+#[derive(Clone, Debug)]
+pub enum IllegalGuardExpr {
+    /// Call to a function other than the guard BIFs
+    Call {
+        module: Option<String>,
+        name: String,
+        arity: usize,
+        span: Span,
+    },
+
+    /// Call to a list operator (`++` or `--`)
+    Operator { op: String, span: Span },
+}
+impl IllegalGuardExpr {
+    pub fn span(&self) -> &Span {
+        match *self {
+            IllegalGuardExpr::Call { ref span, .. }
+            | IllegalGuardExpr::Operator { ref span, .. } => span,
+        }
+    }
+}
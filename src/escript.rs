@@ -0,0 +1,146 @@
+//! Escript support.
+//!
+//! An escript starts with a `#!` line, optionally followed by a comment line
+//! (e.g., `%% -*- erlang -*-`) and a `%%!` line giving the arguments of the emulator.
+//! The rest is a module body, which does not need a `-module` attribute
+//! and whose `main/1` is the entry point.
+use erl_pp::Preprocessor;
+use erl_tokenize::Lexer;
+use std::borrow::Cow;
+
+use crate::cst::forms::FunDecl;
+use crate::cst::ModuleDecl;
+use crate::{MaybeMatchTokens, Parser, Result, TokenReader};
+
+/// Header of an escript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscriptHeader {
+    /// The interpreter given by the `#!` line (e.g., `/usr/bin/env escript`).
+    pub shebang: String,
+
+    /// The emulator arguments given by the `%%!` line (e.g., `["-smp", "enable"]`).
+    ///
+    /// The `%%!` line is recognized on the second line,
+    /// or on the third line if the second one is a comment.
+    pub emulator_args: Vec<String>,
+}
+impl EscriptHeader {
+    /// Parses the header of `text`.
+    ///
+    /// Returns `None` if `text` does not start with `#!`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let shebang = lines.next()?.strip_prefix("#!")?.trim().to_owned();
+        let mut emulator_args = Vec::new();
+        for line in lines.take(2) {
+            if let Some(args) = line.strip_prefix("%%!") {
+                emulator_args = args.split_whitespace().map(str::to_owned).collect();
+                break;
+            }
+            if !line.starts_with('%') {
+                break;
+            }
+        }
+        Some(EscriptHeader {
+            shebang,
+            emulator_args,
+        })
+    }
+
+    /// Returns the emulator flags (the arguments starting with `-` or `+`) with their values.
+    ///
+    /// Values preceding the first flag are associated with `""`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erl_parse::escript::EscriptHeader;
+    ///
+    /// let header = EscriptHeader::parse("#!/usr/bin/env escript\n%%! -sname foo -pa a b +A 8\n").unwrap();
+    /// assert_eq!(
+    ///     header.emulator_flags(),
+    ///     [("-sname", vec!["foo"]), ("-pa", vec!["a", "b"]), ("+A", vec!["8"])]
+    /// );
+    /// ```
+    pub fn emulator_flags(&self) -> Vec<(&str, Vec<&str>)> {
+        let mut flags: Vec<(&str, Vec<&str>)> = Vec::new();
+        for arg in &self.emulator_args {
+            if arg.starts_with('-') || arg.starts_with('+') {
+                flags.push((arg, Vec::new()));
+            } else if let Some(flag) = flags.last_mut() {
+                flag.1.push(arg);
+            } else {
+                flags.push(("", vec![arg]));
+            }
+        }
+        flags
+    }
+}
+
+/// Replaces the `#!` line of `text` (if any) with spaces,
+/// so that the rest can be tokenized as Erlang source code at the original positions.
+pub fn mask_shebang(text: &str) -> Cow<'_, str> {
+    if !text.starts_with("#!") {
+        return Cow::Borrowed(text);
+    }
+    let end = text.find('\n').unwrap_or(text.len());
+    let mut masked = " ".repeat(text[..end].trim_end_matches('\r').len());
+    masked.push_str(&text[masked.len()..]);
+    Cow::Owned(masked)
+}
+
+/// Parsed escript.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::escript::Escript;
+///
+/// let text = r#"#!/usr/bin/env escript
+/// %% -*- erlang -*-
+/// %%! -smp enable -sname factorial
+/// main([String]) ->
+///     N = list_to_integer(String),
+///     io:format("factorial ~w = ~w~n", [N, fac(N)]).
+///
+/// fac(0) -> 1;
+/// fac(N) -> N * fac(N - 1).
+/// "#;
+/// let escript = Escript::parse(text).unwrap();
+/// let header = escript.header.as_ref().unwrap();
+/// assert_eq!(header.shebang, "/usr/bin/env escript");
+/// assert_eq!(header.emulator_args, ["-smp", "enable", "-sname", "factorial"]);
+/// assert_eq!(escript.module.forms.len(), 2);
+/// assert!(escript.main().is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Escript {
+    /// The header (`None` if the text does not start with `#!`).
+    pub header: Option<EscriptHeader>,
+
+    /// The module body.
+    ///
+    /// The positions of the tokens are those in the original text (including the header).
+    pub module: ModuleDecl,
+}
+impl Escript {
+    /// Parses `text` as an escript (with preprocessing).
+    ///
+    /// To parse it in other ways (e.g., in no-preprocess mode),
+    /// tokenize `mask_shebang(text)` and use `EscriptHeader::parse` for the header.
+    pub fn parse(text: &str) -> Result<Self> {
+        let header = EscriptHeader::parse(text);
+        let masked = mask_shebang(text);
+        let tokens = MaybeMatchTokens::new(Lexer::new(&*masked));
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+        let module = track!(parser.parse())?;
+        Ok(Escript { header, module })
+    }
+
+    /// Returns the entry point `main/1`.
+    pub fn main(&self) -> Option<&FunDecl> {
+        self.module
+            .fun_decls()
+            .find(|f| f.name() == "main" && f.arity() == 1)
+    }
+}
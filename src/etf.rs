@@ -0,0 +1,166 @@
+//! Encoder of the Erlang External Term Format.
+//!
+//! The output is the same as `erlang:term_to_binary/1`, so the abstract format made by
+//! the `abstract_format` module can be passed to `compile:forms/2` through a port
+//! (or written to a file and read by `binary_to_term/1`).
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::etf;
+//! use erl_parse::term::Term;
+//!
+//! let term = Term::Tuple(vec![Term::atom("ok"), Term::from(1u32)]);
+//! assert_eq!(etf::encode(&term), [131, 104, 2, 119, 2, b'o', b'k', 97, 1]);
+//! ```
+use num::bigint::Sign;
+use num::ToPrimitive;
+use std::io::Write;
+
+use crate::term::Term;
+use crate::{Error, Result};
+
+const VERSION: u8 = 131;
+const NEW_FLOAT_EXT: u8 = 70;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const MAP_EXT: u8 = 116;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+/// Encodes `term` (including the version header).
+pub fn encode(term: &Term) -> Vec<u8> {
+    let mut buf = vec![VERSION];
+    encode_term(term, &mut buf);
+    buf
+}
+
+/// Writes the encoded `term` to `writer`.
+///
+/// If `packet` is `true`, the term is preceded by its size as a 4-byte big-endian integer,
+/// as expected by a port opened with the `{packet, 4}` option.
+pub fn write_term<W: Write>(mut writer: W, term: &Term, packet: bool) -> Result<()> {
+    let bytes = encode(term);
+    if packet {
+        let size = bytes.len() as u32;
+        track!(writer.write_all(&size.to_be_bytes()).map_err(Error::from))?;
+    }
+    track!(writer.write_all(&bytes).map_err(Error::from))?;
+    Ok(())
+}
+
+fn encode_term(term: &Term, buf: &mut Vec<u8>) {
+    match *term {
+        Term::Atom(ref name) => {
+            if name.len() <= 255 {
+                buf.push(SMALL_ATOM_UTF8_EXT);
+                buf.push(name.len() as u8);
+            } else {
+                buf.push(ATOM_UTF8_EXT);
+                buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            }
+            buf.extend_from_slice(name.as_bytes());
+        }
+        Term::Integer(ref i) => {
+            if let Some(n) = i.to_u8() {
+                buf.push(SMALL_INTEGER_EXT);
+                buf.push(n);
+            } else if let Some(n) = i.to_i32() {
+                buf.push(INTEGER_EXT);
+                buf.extend_from_slice(&n.to_be_bytes());
+            } else {
+                let (sign, digits) = i.to_bytes_le();
+                if digits.len() <= 255 {
+                    buf.push(SMALL_BIG_EXT);
+                    buf.push(digits.len() as u8);
+                } else {
+                    buf.push(LARGE_BIG_EXT);
+                    buf.extend_from_slice(&(digits.len() as u32).to_be_bytes());
+                }
+                buf.push(if sign == Sign::Minus { 1 } else { 0 });
+                buf.extend_from_slice(&digits);
+            }
+        }
+        Term::Float(x) => {
+            buf.push(NEW_FLOAT_EXT);
+            buf.extend_from_slice(&x.to_bits().to_be_bytes());
+        }
+        Term::Tuple(ref elements) => {
+            if elements.len() <= 255 {
+                buf.push(SMALL_TUPLE_EXT);
+                buf.push(elements.len() as u8);
+            } else {
+                buf.push(LARGE_TUPLE_EXT);
+                buf.extend_from_slice(&(elements.len() as u32).to_be_bytes());
+            }
+            for e in elements {
+                encode_term(e, buf);
+            }
+        }
+        Term::List(ref elements) => {
+            if elements.is_empty() {
+                buf.push(NIL_EXT);
+            } else if let Some(bytes) = as_bytes(elements) {
+                buf.push(STRING_EXT);
+                buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+                buf.extend_from_slice(&bytes);
+            } else {
+                encode_list(elements, None, buf);
+            }
+        }
+        Term::ImproperList(ref elements, ref tail) => {
+            if elements.is_empty() {
+                encode_term(tail, buf);
+            } else {
+                encode_list(elements, Some(tail), buf);
+            }
+        }
+        Term::Binary(ref bytes) => {
+            buf.push(BINARY_EXT);
+            buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+            buf.extend_from_slice(bytes);
+        }
+        Term::Map(ref pairs) => {
+            buf.push(MAP_EXT);
+            buf.extend_from_slice(&(pairs.len() as u32).to_be_bytes());
+            for (k, v) in pairs {
+                encode_term(k, buf);
+                encode_term(v, buf);
+            }
+        }
+    }
+}
+
+fn encode_list(elements: &[Term], tail: Option<&Term>, buf: &mut Vec<u8>) {
+    buf.push(LIST_EXT);
+    buf.extend_from_slice(&(elements.len() as u32).to_be_bytes());
+    for e in elements {
+        encode_term(e, buf);
+    }
+    match tail {
+        None => buf.push(NIL_EXT),
+        Some(tail) => encode_term(tail, buf),
+    }
+}
+
+/// Returns the bytes if `elements` can be encoded as `STRING_EXT` (as `term_to_binary/1` does).
+fn as_bytes(elements: &[Term]) -> Option<Vec<u8>> {
+    if elements.len() > usize::from(u16::MAX) {
+        return None;
+    }
+    elements
+        .iter()
+        .map(|e| match *e {
+            Term::Integer(ref i) => i.to_u8(),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,190 @@
+//! Extension points for syntax which is not supported by this crate.
+//!
+//! Embedders can register `SyntaxExtension`s for attribute names (e.g., `-route ...`)
+//! and expression prefixes (e.g., `sql "SELECT ..."`) to a parser by `ParserBuilder::extensions`.
+//! The constructs are parsed into `cst::forms::ExtensionAttr` and `cst::exprs::ExtensionExpr`,
+//! which keep all tokens read by the extensions and the Erlang terms parsed on their behalf.
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::cst::{Expr, Form};
+//! use erl_parse::extension::{Extensions, ExtensionInput, SyntaxExtension};
+//! use erl_parse::traits::CollectTokens;
+//! use erl_parse::{ParserBuilder, Result, TokenReader};
+//! use erl_tokenize::Lexer;
+//!
+//! /// `-route Method Path -> Handler.`
+//! struct Route;
+//! impl SyntaxExtension for Route {
+//!     fn parse(&self, input: &mut dyn ExtensionInput) -> Result<()> {
+//!         input.read_token()?;
+//!         input.parse_expr()?;
+//!         input.read_token()?;
+//!         input.parse_expr()?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let text = "-route get \"/users\" -> users_handler.";
+//! let extensions = Extensions::new().attribute("route", Route);
+//! let mut parser = ParserBuilder::new()
+//!     .extensions(extensions)
+//!     .build(TokenReader::new(Lexer::new(text)));
+//! let form: Form = parser.parse().unwrap();
+//! if let Form::ExtensionAttr(ref x) = form {
+//!     assert_eq!(x.attr_name.value(), "route");
+//!     assert_eq!(x.parts.len(), 4);
+//! } else {
+//!     panic!("{:?}", form);
+//! }
+//! assert_eq!(form.to_erlang_source(), text);
+//! ```
+use erl_tokenize::LexicalToken;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cst::commons::parts::ExtensionPart;
+use crate::cst::{Expr, Pattern, Type};
+use crate::traits::TokenRead;
+use crate::{Parser, Result};
+
+/// Parser of an embedder-specific construct.
+pub trait SyntaxExtension: Send + Sync {
+    /// Reads the construct from `input`.
+    ///
+    /// For attributes, `input` is positioned after the attribute name,
+    /// and the terminating `.` is read by the parser after this returns.
+    /// For expressions, `input` is positioned after the prefix atom.
+    fn parse(&self, input: &mut dyn ExtensionInput) -> Result<()>;
+}
+
+/// Tokens given to `SyntaxExtension::parse`.
+///
+/// Everything read through this is recorded in the `ExtensionPart`s of the resulting node.
+pub trait ExtensionInput {
+    /// Returns the next token without consuming it (`None` at the end of the input).
+    fn peek_token(&mut self) -> Result<Option<LexicalToken>>;
+
+    fn read_token(&mut self) -> Result<LexicalToken>;
+    fn parse_expr(&mut self) -> Result<&Expr>;
+    fn parse_pattern(&mut self) -> Result<&Pattern>;
+    fn parse_type(&mut self) -> Result<&Type>;
+}
+
+/// Registry of `SyntaxExtension`s.
+///
+/// Only the names which are not recognized by this crate can be registered in effect:
+/// the built-in attributes (e.g., `-spec`) are not passed to extensions.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    attributes: HashMap<String, Arc<dyn SyntaxExtension>>,
+    expr_prefixes: HashMap<String, Arc<dyn SyntaxExtension>>,
+}
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `extension` for the attributes named `name`.
+    pub fn attribute<E: SyntaxExtension + 'static>(mut self, name: &str, extension: E) -> Self {
+        self.attributes.insert(name.to_owned(), Arc::new(extension));
+        self
+    }
+
+    /// Registers `extension` for the expressions starting with the atom `prefix`.
+    ///
+    /// If the extension fails, the expression is parsed as usual
+    /// (i.e., `prefix` remains available as an ordinary atom, e.g., in `prefix:f()`).
+    pub fn expr_prefix<E: SyntaxExtension + 'static>(mut self, prefix: &str, extension: E) -> Self {
+        self.expr_prefixes
+            .insert(prefix.to_owned(), Arc::new(extension));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.expr_prefixes.is_empty()
+    }
+
+    pub(crate) fn get_attribute(&self, name: &str) -> Option<Arc<dyn SyntaxExtension>> {
+        self.attributes.get(name).cloned()
+    }
+
+    pub(crate) fn get_expr_prefix(&self, prefix: &str) -> Option<Arc<dyn SyntaxExtension>> {
+        self.expr_prefixes.get(prefix).cloned()
+    }
+}
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field(
+                "attributes",
+                &self.attributes.keys().collect::<BTreeSet<_>>(),
+            )
+            .field(
+                "expr_prefixes",
+                &self.expr_prefixes.keys().collect::<BTreeSet<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Runs `extension` on `parser`, returning the parts it read.
+pub(crate) fn run<T: TokenRead>(
+    parser: &mut Parser<T>,
+    extension: &dyn SyntaxExtension,
+) -> Result<Vec<ExtensionPart>> {
+    let mut input = Input {
+        parser,
+        parts: Vec::new(),
+    };
+    track!(extension.parse(&mut input))?;
+    Ok(input.parts)
+}
+
+struct Input<'a, T> {
+    parser: &'a mut Parser<T>,
+    parts: Vec<ExtensionPart>,
+}
+impl<T: TokenRead> ExtensionInput for Input<'_, T> {
+    fn peek_token(&mut self) -> Result<Option<LexicalToken>> {
+        if track!(self.parser.eos())? {
+            return Ok(None);
+        }
+        track!(self.parser.peek(Parser::parse)).map(Some)
+    }
+    fn read_token(&mut self) -> Result<LexicalToken> {
+        let token: LexicalToken = track!(self.parser.parse())?;
+        if let Some(ExtensionPart::Tokens(ref mut tokens)) = self.parts.last_mut() {
+            tokens.push(token.clone());
+        } else {
+            self.parts.push(ExtensionPart::Tokens(vec![token.clone()]));
+        }
+        Ok(token)
+    }
+    fn parse_expr(&mut self) -> Result<&Expr> {
+        self.parts
+            .push(ExtensionPart::Expr(track!(self.parser.parse())?));
+        match self.parts.last() {
+            Some(ExtensionPart::Expr(x)) => Ok(x),
+            _ => unreachable!(),
+        }
+    }
+    fn parse_pattern(&mut self) -> Result<&Pattern> {
+        self.parts
+            .push(ExtensionPart::Pattern(track!(self.parser.parse())?));
+        match self.parts.last() {
+            Some(ExtensionPart::Pattern(x)) => Ok(x),
+            _ => unreachable!(),
+        }
+    }
+    fn parse_type(&mut self) -> Result<&Type> {
+        self.parts
+            .push(ExtensionPart::Type(track!(self.parser.parse())?));
+        match self.parts.last() {
+            Some(ExtensionPart::Type(x)) => Ok(x),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Layout documents and their renderer (a variant of Wadler's pretty printer).
+
+/// Layout document.
+#[derive(Debug, Clone)]
+pub(crate) enum Doc {
+    Text(String),
+
+    /// A space in flat mode, or a newline in break mode.
+    Line,
+
+    /// Nothing in flat mode, or a newline in break mode.
+    SoftLine,
+
+    /// A newline, which breaks the enclosing groups.
+    ///
+    /// Like the other line breaks, this does not make an empty line.
+    HardLine,
+
+    /// An empty line, unless the output is at the start or already after an empty line.
+    BlankLine,
+
+    /// Text which is emitted only in break mode.
+    IfBreak(&'static str),
+
+    /// A comment on its own line.
+    LeadingComment(String),
+
+    /// A comment which is put at the end of the current line.
+    TrailingComment(String),
+
+    /// Indents the lines of the documents by the given width.
+    Nest(usize, Vec<Doc>),
+
+    /// Indents the lines of the documents to the column where they start.
+    Align(Vec<Doc>),
+
+    /// Documents which are laid out in flat mode if they fit in the line, or in break mode otherwise.
+    Group {
+        docs: Vec<Doc>,
+        broken: bool,
+    },
+
+    /// Documents which are always laid out in flat mode.
+    Flat(Vec<Doc>),
+
+    /// The group (the second) if it fits in flat mode, the first documents
+    /// if their first line fits (in break mode), or the group in break mode otherwise.
+    Choice(Vec<Doc>, Box<Doc>),
+}
+impl Doc {
+    pub fn text<S: Into<String>>(s: S) -> Self {
+        Doc::Text(s.into())
+    }
+
+    /// Makes a group, which is always broken if `docs` contain hard line breaks.
+    pub fn group(docs: Vec<Doc>) -> Self {
+        let broken = docs.iter().any(Doc::has_hard_break);
+        Doc::Group { docs, broken }
+    }
+
+    pub fn has_hard_break(&self) -> bool {
+        match *self {
+            Doc::HardLine | Doc::BlankLine | Doc::LeadingComment(_) | Doc::TrailingComment(_) => {
+                true
+            }
+            Doc::Text(ref s) => s.contains('\n'),
+            Doc::Line | Doc::SoftLine | Doc::IfBreak(_) => false,
+            Doc::Nest(_, ref docs) | Doc::Align(ref docs) => docs.iter().any(Doc::has_hard_break),
+            Doc::Group { broken, .. } => broken,
+            Doc::Flat(ref docs) => docs.iter().any(Doc::has_hard_break),
+            Doc::Choice(_, ref group) => group.has_hard_break(),
+        }
+    }
+}
+
+/// Renders `docs` within `max_width` columns as far as possible.
+pub(crate) fn render(docs: &[Doc], max_width: usize) -> String {
+    let mut r = Renderer {
+        out: String::new(),
+        column: 0,
+        line_start: true,
+        indent: 0,
+        suffixes: Vec::new(),
+    };
+    let mut stack: Vec<(usize, bool, &Doc)> = docs.iter().rev().map(|d| (0, false, d)).collect();
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match *doc {
+            Doc::Text(ref s) => r.text(s),
+            Doc::Line if flat => r.text(" "),
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => r.newline(indent),
+            Doc::BlankLine => {
+                if !r.out.is_empty() {
+                    if !r.line_start {
+                        r.newline(indent);
+                    }
+                    if !r.out.ends_with("\n\n") {
+                        r.out.push('\n');
+                    }
+                }
+            }
+            Doc::IfBreak(s) => {
+                if !flat {
+                    r.text(s);
+                }
+            }
+            Doc::LeadingComment(ref s) => {
+                if !r.line_start {
+                    r.newline(indent);
+                }
+                r.text(s);
+            }
+            Doc::TrailingComment(ref s) => r.suffixes.push(s.clone()),
+            Doc::Nest(n, ref docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent + n, flat, d)));
+            }
+            Doc::Align(ref docs) => {
+                let column = if r.line_start { r.indent } else { r.column };
+                stack.extend(docs.iter().rev().map(|d| (column, flat, d)));
+            }
+            Doc::Group { ref docs, broken } => {
+                let width = max_width as isize - r.column as isize;
+                let flat = flat || (!broken && fits(width, docs, true, &stack));
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)));
+            }
+            Doc::Flat(ref docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent, true, d)));
+            }
+            Doc::Choice(ref first, ref group) => {
+                let width = max_width as isize - r.column as isize;
+                let group_fits = match **group {
+                    Doc::Group { ref docs, broken } => !broken && fits(width, docs, true, &stack),
+                    _ => false,
+                };
+                if flat || group_fits || !fits(width, first, false, &stack) {
+                    stack.push((indent, flat, group));
+                } else {
+                    stack.extend(first.iter().rev().map(|d| (indent, false, d)));
+                }
+            }
+        }
+    }
+    r.flush_suffixes();
+    if !r.out.ends_with('\n') {
+        r.out.push('\n');
+    }
+    r.out
+}
+
+/// Returns `true` if `docs` (in flat mode if `flat` is `true`), followed by the `rest`,
+/// fit in `width` columns up to the next line break.
+fn fits(mut width: isize, docs: &[Doc], flat: bool, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut stack: Vec<(bool, &Doc)> = docs.iter().rev().map(|d| (flat, d)).collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        if width < 0 {
+            return false;
+        }
+        let (flat, doc) = match stack.pop() {
+            Some(x) => x,
+            None => match rest.next() {
+                Some(&(_, flat, doc)) => (flat, doc),
+                None => return true,
+            },
+        };
+        match *doc {
+            Doc::Text(ref s) => match s.find('\n') {
+                Some(i) => return width >= s[..i].chars().count() as isize,
+                None => width -= s.chars().count() as isize,
+            },
+            Doc::Line if flat => width -= 1,
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::BlankLine => return true,
+            Doc::LeadingComment(_) => return true,
+            Doc::TrailingComment(_) => {}
+            Doc::IfBreak(s) => {
+                if !flat {
+                    width -= s.len() as isize;
+                }
+            }
+            Doc::Nest(_, ref docs) | Doc::Align(ref docs) => {
+                stack.extend(docs.iter().rev().map(|d| (flat, d)));
+            }
+            Doc::Group { ref docs, broken } => {
+                stack.extend(docs.iter().rev().map(|d| (flat && !broken, d)));
+            }
+            Doc::Flat(ref docs) => stack.extend(docs.iter().rev().map(|d| (true, d))),
+            Doc::Choice(_, ref group) => stack.push((flat, group)),
+        }
+    }
+}
+
+struct Renderer {
+    out: String,
+    column: usize,
+
+    /// `true` if nothing but the (pending) indentation is on the current line.
+    line_start: bool,
+    indent: usize,
+    suffixes: Vec<String>,
+}
+impl Renderer {
+    fn text(&mut self, s: &str) {
+        if self.line_start {
+            self.out.push_str(&" ".repeat(self.indent));
+            self.column = self.indent;
+            self.line_start = false;
+        }
+        self.out.push_str(s);
+        match s.rfind('\n') {
+            Some(i) => self.column = s[i + 1..].chars().count(),
+            None => self.column += s.chars().count(),
+        }
+    }
+
+    /// Starts a new line (unless the current line is empty).
+    fn newline(&mut self, indent: usize) {
+        self.flush_suffixes();
+        if !self.line_start {
+            self.out.push('\n');
+        }
+        self.line_start = true;
+        self.indent = indent;
+        self.column = indent;
+    }
+
+    fn flush_suffixes(&mut self) {
+        for s in std::mem::take(&mut self.suffixes) {
+            if !self.line_start {
+                self.out.push(' ');
+            }
+            self.text(&s);
+        }
+    }
+}
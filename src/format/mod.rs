@@ -0,0 +1,837 @@
+//! Pretty-printer which lays out a CST according to a configurable style.
+//!
+//! Unlike `CollectTokens::to_erlang_source` (which puts the tokens in a single line per form)
+//! and `cst::Trivia::reproduce` (which keeps the original layout),
+//! this re-indents the source code and breaks the lines which exceed `FormatOptions::max_width`:
+//!
+//! - Bracketed elements (arguments, lists, tuples, maps, records and binaries)
+//!   are put in a line, or one per line if they do not fit.
+//! - The clauses of `case`, `if`, `receive`, `try` and multi-clause `fun`s are put one per line,
+//!   and a clause body is put after `->` if it consists of a single expression which fits.
+//! - Comments are kept (if the trivia of the source text are given),
+//!   and consecutive empty lines are squashed into one.
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::format::{format_text, CommaPlacement, FormatOptions};
+//!
+//! let text = "-module(foo).\nf(X)->case X of a->1;  b->{ok,[1,2,  3]}end.";
+//! let formatted = format_text(text, &FormatOptions::new()).unwrap();
+//! assert_eq!(
+//!     formatted,
+//!     "-module(foo).\nf(X) ->\n    case X of\n        a -> 1;\n        b -> {ok, [1, 2, 3]}\n    end.\n"
+//! );
+//!
+//! let options = FormatOptions::new()
+//!     .max_width(20)
+//!     .comma_placement(CommaPlacement::Leading);
+//! let formatted = format_text("f() -> [alpha, beta, gamma, delta].", &options).unwrap();
+//! assert_eq!(
+//!     formatted,
+//!     "f() ->\n    [ alpha\n    , beta\n    , gamma\n    , delta\n    ].\n"
+//! );
+//! ```
+use erl_tokenize::tokens::KeywordToken;
+use erl_tokenize::values::{Keyword, Symbol, Whitespace};
+use erl_tokenize::{Lexer, LexicalToken, Position, PositionRange, Token};
+use std::collections::HashMap;
+
+use self::doc::Doc;
+use crate::cst::exprs::Fun;
+use crate::cst::{Expr, Form, ModuleDecl, Node, Trivia};
+use crate::traits::{is_form_end, is_symbol, needs_space, CollectTokens};
+use crate::{Parser, Result, TokenReader};
+
+mod doc;
+
+/// Formatting style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The preferred maximum number of columns of a line (default: `100`).
+    ///
+    /// Lines may exceed this if there is no way to break them
+    /// (e.g., long operator expressions or string literals).
+    pub max_width: usize,
+
+    /// The number of spaces of an indentation level (default: `4`).
+    pub indent: usize,
+
+    /// The placement of the commas between bracketed elements broken into lines
+    /// (default: `CommaPlacement::Trailing`).
+    pub comma_placement: CommaPlacement,
+}
+impl FormatOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn max_width(mut self, n: usize) -> Self {
+        self.max_width = n;
+        self
+    }
+    pub fn indent(mut self, n: usize) -> Self {
+        self.indent = n;
+        self
+    }
+    pub fn comma_placement(mut self, placement: CommaPlacement) -> Self {
+        self.comma_placement = placement;
+        self
+    }
+}
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            max_width: 100,
+            indent: 4,
+            comma_placement: CommaPlacement::Trailing,
+        }
+    }
+}
+
+/// Placement of the commas between bracketed elements broken into lines.
+///
+/// The expressions in clause bodies are always followed by commas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommaPlacement {
+    /// ```text
+    /// [
+    ///     a,
+    ///     b
+    /// ]
+    /// ```
+    Trailing,
+
+    /// ```text
+    /// [ a
+    /// , b
+    /// ]
+    /// ```
+    Leading,
+}
+
+/// Formats `text` (Erlang source code) with its comments.
+///
+/// The text is parsed without preprocessing (see `Parser::no_preprocess`),
+/// so macro calls and directives are formatted as they are.
+pub fn format_text(text: &str, options: &FormatOptions) -> Result<String> {
+    let trivia = track!(Trivia::build(text))?;
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let module: ModuleDecl = track!(parser.parse())?;
+    Ok(format_module(&module, Some(&trivia), options))
+}
+
+/// Formats `module`.
+///
+/// If `trivia` (of the text which `module` is parsed from) is given, the comments are kept.
+pub fn format_module(
+    module: &ModuleDecl,
+    trivia: Option<&Trivia>,
+    options: &FormatOptions,
+) -> String {
+    let mut docs = Vec::new();
+    let mut prev_fun = false;
+    for form in &module.forms {
+        let is_fun = matches!(*form, Form::FunDecl(_));
+        if is_fun && prev_fun {
+            docs.push(Doc::BlankLine);
+        }
+        prev_fun = is_fun;
+
+        let mut builder = Builder::new(form, trivia, options);
+        builder.form(&mut docs, form);
+        docs.push(Doc::HardLine);
+    }
+    if let Some(trivia) = trivia {
+        comments(&mut docs, trivia.end_of_file(), true);
+    }
+    doc::render(&docs, options.max_width)
+}
+
+/// Role of a token which is not determined by the token itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// `fun` of an anonymous function or `maybe` of a `maybe` expression.
+    BlockStart,
+
+    /// `else` of a `maybe` expression.
+    BlockMiddle,
+
+    /// `catch` of a catch expression (not of a `try` expression).
+    Prefix,
+
+    /// `/` of `fun Name/Arity`, which is not surrounded by spaces.
+    Tight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Case,
+    If,
+    Receive,
+    Try,
+    Begin,
+    Fun,
+    Maybe,
+}
+
+struct Builder<'a> {
+    tokens: Vec<LexicalToken>,
+    pos: usize,
+    roles: HashMap<Position, Role>,
+    trivia: Option<&'a Trivia>,
+    options: &'a FormatOptions,
+
+    /// `true` if a line break (or a space) has been emitted after the last token.
+    at_break: bool,
+
+    /// The position of the token whose leading comments have been emitted.
+    comments_done: Option<usize>,
+
+    /// Whether the brackets enclosing the current token are of a binary (before `||`).
+    bits: Vec<bool>,
+    failed: bool,
+}
+impl<'a> Builder<'a> {
+    fn new(form: &Form, trivia: Option<&'a Trivia>, options: &'a FormatOptions) -> Self {
+        let mut roles = HashMap::new();
+        Node::Form(form).walk(|node| {
+            let (token, role) = match node {
+                Node::Expr(Expr::Fun(x)) => match **x {
+                    Fun::Anonymous(ref x) => (x._fun.start_position(), Role::BlockStart),
+                    Fun::Named(ref x) => (x._fun.start_position(), Role::BlockStart),
+                    Fun::Defined(ref x) => (x.fun._slash.start_position(), Role::Tight),
+                },
+                Node::Expr(Expr::Maybe(x)) => {
+                    if let Some(ref e) = x.else_clauses {
+                        roles.insert(e._else.start_position(), Role::BlockMiddle);
+                    }
+                    (x._maybe.start_position(), Role::BlockStart)
+                }
+                Node::Expr(Expr::Catch(x)) => (x._catch.start_position(), Role::Prefix),
+                _ => return,
+            };
+            roles.insert(token, role);
+        });
+        Builder {
+            tokens: form.tokens(),
+            pos: 0,
+            roles,
+            trivia,
+            options,
+            at_break: true,
+            comments_done: None,
+            bits: Vec::new(),
+            failed: false,
+        }
+    }
+
+    fn form(&mut self, out: &mut Vec<Doc>, form: &Form) {
+        let is_fun = matches!(*form, Form::FunDecl(_));
+        self.leading_comments(out, true);
+        let mut docs = Vec::new();
+        if is_fun {
+            let clauses = self.clauses();
+            self.join_clauses(&mut docs, clauses);
+        } else {
+            let types = matches!(
+                *form,
+                Form::FunSpec(_) | Form::CallbackSpec(_) | Form::TypeDecl(_)
+            );
+            self.attribute(&mut docs, types);
+        }
+        if !self.failed && self.is_form_end() {
+            self.token(&mut docs);
+        }
+        if self.failed || self.pos != self.tokens.len() {
+            // Unbalanced tokens (e.g., in a `-define` directive) are laid out as they are
+            docs.clear();
+            self.pos = 0;
+            self.at_break = true;
+            while self.pos < self.tokens.len() {
+                self.token(&mut docs);
+            }
+        }
+        if is_fun {
+            out.extend(docs);
+        } else {
+            out.push(Doc::group(docs));
+        }
+    }
+
+    /// Emits the tokens of an attribute (or a directive) except the terminating `.`.
+    ///
+    /// The attribute can be broken after the top-level `,` and `;` (e.g., of `-spec`),
+    /// and also before the top-level `|` if `types` is `true`.
+    /// The lines following the first break are indented.
+    fn attribute(&mut self, out: &mut Vec<Doc>, types: bool) {
+        let mut rest = Vec::new();
+        while !self.failed && self.pos < self.tokens.len() && !self.is_form_end() {
+            let docs = if rest.is_empty() {
+                &mut *out
+            } else {
+                &mut rest
+            };
+            if self.is_symbol(Symbol::Comma) || self.is_symbol(Symbol::Semicolon) {
+                self.token(docs);
+                self.line(&mut rest, Doc::Line);
+            } else if types && self.is_symbol(Symbol::VerticalBar) {
+                self.line(&mut rest, Doc::Line);
+                self.token(&mut rest);
+            } else if self.opener().is_some() {
+                self.brackets(docs);
+            } else if let Some(block) = self.block() {
+                self.block_expr(docs, block);
+            } else if self.at_stop(false) {
+                self.failed = true;
+            } else {
+                self.token(docs);
+            }
+        }
+        out.push(Doc::Nest(self.options.indent, rest));
+    }
+
+    /// Emits the tokens up to the next token which terminates an expression at this level.
+    ///
+    /// `->` is a terminator only if `arrow` is `true`.
+    fn expr(&mut self, out: &mut Vec<Doc>, arrow: bool) {
+        while !self.failed && !self.at_stop(arrow) {
+            if self.opener().is_some() {
+                self.brackets(out);
+            } else if let Some(block) = self.block() {
+                self.block_expr(out, block);
+            } else {
+                self.token(out);
+            }
+        }
+    }
+
+    fn brackets(&mut self, out: &mut Vec<Doc>) {
+        let close = self.opener().map(closer_of);
+        let mut open = Vec::new();
+        self.bits.push(self.is_symbol(Symbol::DoubleLeftAngle));
+        self.token(&mut open);
+
+        let mut elements = Vec::new();
+        while !self.failed {
+            if self.symbol() == close {
+                break;
+            }
+            let start = self.pos;
+            let mut element = Vec::new();
+            self.leading_comments(&mut element, false);
+            self.expr(&mut element, false);
+            let sep = if self.is_symbol(Symbol::Comma) || self.is_symbol(Symbol::Semicolon) {
+                let mut sep = Vec::new();
+                self.token(&mut sep);
+                self.at_break = true;
+                Some(sep)
+            } else if self.symbol() == close {
+                None
+            } else {
+                self.failed = true;
+                return;
+            };
+            if self.pos == start {
+                self.failed = true;
+                return;
+            }
+            elements.push((element, sep));
+        }
+        self.bits.pop();
+        let mut close_docs = Vec::new();
+        self.token(&mut close_docs);
+
+        let mut docs = open;
+        let is_hug = |element: &[Doc]| matches!(element, [Doc::Group { .. }]);
+        if elements.is_empty() || (elements.len() == 1 && is_hug(&elements[0].0)) {
+            // `f()` or `f([...])`
+            for (element, _) in elements {
+                docs.extend(element);
+            }
+            docs.extend(close_docs);
+            out.push(Doc::group(docs));
+            return;
+        }
+        if self.options.comma_placement == CommaPlacement::Leading {
+            if matches!(docs.first(), Some(Doc::Text(ref s)) if s == " ") {
+                // The elements are aligned to the opening bracket, not to the preceding space
+                out.push(docs.remove(0));
+            }
+            docs.push(Doc::IfBreak(" "));
+            let mut sep_docs: Option<Vec<Doc>> = None;
+            for (element, sep) in elements {
+                let mut element = element.into_iter().peekable();
+                if let Some(sep) = sep_docs.take() {
+                    // The comments around a separator are put before it
+                    let (comments, sep): (Vec<_>, Vec<_>) = sep
+                        .into_iter()
+                        .partition(|d| matches!(d, Doc::TrailingComment(_)));
+                    docs.extend(comments);
+                    while let Some(Doc::BlankLine | Doc::LeadingComment(_) | Doc::HardLine) =
+                        element.peek()
+                    {
+                        docs.extend(element.next());
+                    }
+                    docs.push(Doc::SoftLine);
+                    docs.extend(sep);
+                    docs.push(Doc::text(" "));
+                }
+                docs.push(Doc::Nest(2, element.collect()));
+                sep_docs = sep;
+            }
+            docs.push(Doc::SoftLine);
+            docs.extend(close_docs);
+            out.push(Doc::group(vec![Doc::Align(docs)]));
+            return;
+        }
+
+        // `f(A, B, fun() -> ... end)` or `-record(r, {...})` can be laid out
+        // with the last element broken into lines while the rest are kept in a line.
+        let (last, _) = &elements[elements.len() - 1];
+        let hug = if is_hug(last)
+            && elements[..elements.len() - 1].iter().all(|(e, sep)| {
+                !e.iter()
+                    .chain(sep.iter().flatten())
+                    .any(Doc::has_hard_break)
+            }) {
+            let mut prefix = docs.clone();
+            for (element, sep) in &elements[..elements.len() - 1] {
+                prefix.extend(element.iter().cloned());
+                prefix.extend(sep.iter().flatten().cloned());
+                prefix.push(Doc::Line);
+            }
+            let mut hug = vec![Doc::Flat(prefix)];
+            hug.extend(last.iter().cloned());
+            hug.extend(close_docs.iter().cloned());
+            Some(hug)
+        } else {
+            None
+        };
+
+        let mut inner = vec![Doc::SoftLine];
+        for (element, sep) in elements {
+            inner.extend(element);
+            if let Some(sep) = sep {
+                inner.extend(sep);
+                inner.push(Doc::Line);
+            }
+        }
+        docs.push(Doc::Nest(self.options.indent, inner));
+        docs.push(Doc::SoftLine);
+        docs.extend(close_docs);
+        let group = Doc::group(docs);
+        out.push(match hug {
+            Some(hug) => Doc::Choice(hug, Box::new(group)),
+            None => group,
+        });
+    }
+
+    fn block_expr(&mut self, out: &mut Vec<Doc>, block: Block) {
+        let indent = self.options.indent;
+        let mut docs = Vec::new();
+        self.token(&mut docs);
+        match block {
+            Block::Case => {
+                self.expr(&mut docs, false);
+                self.expect_keyword(&mut docs, Keyword::Of);
+                self.indented_clauses(&mut docs);
+            }
+            Block::If => self.indented_clauses(&mut docs),
+            Block::Receive => {
+                if !self.is_keyword(Keyword::After) {
+                    self.indented_clauses(&mut docs);
+                }
+                if self.is_keyword(Keyword::After) {
+                    self.hard_line(&mut docs);
+                    self.token(&mut docs);
+                    self.indented_clauses(&mut docs);
+                }
+            }
+            Block::Try => {
+                self.indented_body(&mut docs);
+                if self.is_keyword(Keyword::Of) {
+                    self.hard_line(&mut docs);
+                    self.token(&mut docs);
+                    self.indented_clauses(&mut docs);
+                }
+                if self.is_keyword(Keyword::Catch) {
+                    self.hard_line(&mut docs);
+                    self.token(&mut docs);
+                    self.indented_clauses(&mut docs);
+                }
+                if self.is_keyword(Keyword::After) {
+                    self.hard_line(&mut docs);
+                    self.token(&mut docs);
+                    self.indented_body(&mut docs);
+                }
+            }
+            Block::Begin => self.indented_body(&mut docs),
+            Block::Maybe => {
+                self.indented_body(&mut docs);
+                if self.role() == Some(Role::BlockMiddle) {
+                    self.hard_line(&mut docs);
+                    self.token(&mut docs);
+                    self.indented_clauses(&mut docs);
+                }
+            }
+            Block::Fun => {
+                let mut clauses = self.clauses();
+                if clauses.len() == 1 {
+                    let (head, body) = clauses.remove(0);
+                    docs.extend(head);
+                    docs.push(Doc::Nest(indent, body));
+                    self.line(&mut docs, Doc::Line);
+                    self.expect_keyword(&mut docs, Keyword::End);
+                    out.push(Doc::group(docs));
+                    return;
+                }
+                if let Some((head, _)) = clauses.first_mut() {
+                    // `fun Name(...)` of a named function
+                    if matches!(head.first(), Some(Doc::Text(ref s)) if s == " ") {
+                        head.remove(0);
+                    }
+                }
+                let mut inner = vec![Doc::HardLine];
+                self.join_clauses(&mut inner, clauses);
+                docs.push(Doc::Nest(indent, inner));
+            }
+        }
+        self.hard_line(&mut docs);
+        self.expect_keyword(&mut docs, Keyword::End);
+        out.push(Doc::group(docs));
+    }
+
+    fn indented_clauses(&mut self, out: &mut Vec<Doc>) {
+        let mut inner = Vec::new();
+        self.hard_line(&mut inner);
+        let clauses = self.clauses();
+        self.join_clauses(&mut inner, clauses);
+        out.push(Doc::Nest(self.options.indent, inner));
+    }
+
+    fn indented_body(&mut self, out: &mut Vec<Doc>) {
+        let mut inner = Vec::new();
+        self.hard_line(&mut inner);
+        for (i, expr) in self.body().into_iter().enumerate() {
+            if i > 0 {
+                inner.push(Doc::HardLine);
+            }
+            inner.extend(expr);
+        }
+        out.push(Doc::Nest(self.options.indent, inner));
+    }
+
+    fn join_clauses(&mut self, out: &mut Vec<Doc>, clauses: Vec<(Vec<Doc>, Vec<Doc>)>) {
+        for (i, (head, body)) in clauses.into_iter().enumerate() {
+            if i > 0 {
+                out.push(Doc::HardLine);
+            }
+            let mut docs = Vec::new();
+            let mut head = head.into_iter().peekable();
+            while let Some(Doc::BlankLine | Doc::LeadingComment(_) | Doc::HardLine) = head.peek() {
+                out.extend(head.next());
+            }
+            docs.extend(head);
+            docs.push(Doc::Nest(self.options.indent, body));
+            out.push(Doc::group(docs));
+        }
+    }
+
+    /// Reads `;`-separated clauses, each of which is split into the head (up to `->`) and the body.
+    fn clauses(&mut self) -> Vec<(Vec<Doc>, Vec<Doc>)> {
+        let mut clauses = Vec::new();
+        while !self.failed {
+            let mut head = Vec::new();
+            self.leading_comments(&mut head, !clauses.is_empty());
+            loop {
+                self.expr(&mut head, true);
+                if self.is_symbol(Symbol::Comma) || self.is_symbol(Symbol::Semicolon) {
+                    self.token(&mut head);
+                } else if self.is_symbol(Symbol::RightArrow) {
+                    self.token(&mut head);
+                    self.at_break = true;
+                    break;
+                } else {
+                    self.failed = true;
+                    return clauses;
+                }
+            }
+
+            let exprs = self.body();
+            let multiline = exprs.len() > 1;
+            let mut body = Vec::new();
+            for expr in exprs {
+                body.push(if multiline { Doc::HardLine } else { Doc::Line });
+                body.extend(expr);
+            }
+            let is_last = !self.is_symbol(Symbol::Semicolon);
+            if !is_last {
+                self.token(&mut body);
+                self.at_break = true;
+            }
+            clauses.push((head, body));
+            if is_last {
+                break;
+            }
+        }
+        clauses
+    }
+
+    /// Reads `,`-separated expressions (with their trailing `,`).
+    fn body(&mut self) -> Vec<Vec<Doc>> {
+        let mut exprs = Vec::new();
+        while !self.failed {
+            let start = self.pos;
+            let mut expr = Vec::new();
+            self.leading_comments(&mut expr, !exprs.is_empty());
+            self.expr(&mut expr, false);
+            if self.pos == start {
+                self.failed = true;
+                break;
+            }
+            let is_last = !self.is_symbol(Symbol::Comma);
+            if !is_last {
+                self.token(&mut expr);
+                self.at_break = true;
+            }
+            exprs.push(expr);
+            if is_last {
+                break;
+            }
+        }
+        exprs
+    }
+
+    fn expect_keyword(&mut self, out: &mut Vec<Doc>, keyword: Keyword) {
+        if self.is_keyword(keyword) {
+            self.token(out);
+        } else {
+            self.failed = true;
+        }
+    }
+
+    fn line(&mut self, out: &mut Vec<Doc>, line: Doc) {
+        out.push(line);
+        self.at_break = true;
+    }
+
+    fn hard_line(&mut self, out: &mut Vec<Doc>) {
+        self.line(out, Doc::HardLine);
+    }
+
+    /// Emits the comments (and an empty line if `blank` is `true`) preceding the current token.
+    fn leading_comments(&mut self, out: &mut Vec<Doc>, blank: bool) {
+        if self.comments_done == Some(self.pos) {
+            return;
+        }
+        if let (Some(trivia), Some(token)) = (self.trivia, self.tokens.get(self.pos)) {
+            if comments(out, trivia.leading(token), blank) {
+                self.at_break = true;
+            }
+        }
+        self.comments_done = Some(self.pos);
+    }
+
+    /// Emits the current token with the preceding space and the surrounding comments.
+    fn token(&mut self, out: &mut Vec<Doc>) {
+        self.leading_comments(out, false);
+        let token = &self.tokens[self.pos];
+        if !self.at_break && self.pos > 0 && self.needs_space() {
+            out.push(Doc::text(" "));
+        }
+        out.push(Doc::text(token.text()));
+        if self.is_symbol(Symbol::DoubleVerticalBar) {
+            if let Some(bits) = self.bits.last_mut() {
+                *bits = false;
+            }
+        }
+        if let Some(trivia) = self.trivia {
+            for t in trivia.trailing(token) {
+                if let Token::Comment(ref c) = *t {
+                    out.push(Doc::TrailingComment(c.text().to_owned()));
+                }
+            }
+        }
+        self.at_break = false;
+        self.pos += 1;
+    }
+
+    fn needs_space(&self) -> bool {
+        let i = self.pos;
+        let prev = &self.tokens[i - 1];
+        if let Some(s) = prev.as_symbol_token().map(|t| t.value()) {
+            if matches!(s, Symbol::Hyphen | Symbol::Plus)
+                && (i < 2 || !ends_expr(&self.tokens[i - 2]))
+            {
+                // Unary operators
+                return false;
+            }
+        }
+        let next = &self.tokens[i];
+        if keyword(prev) == Some(Keyword::Fun) && self.is_symbol(Symbol::OpenParen) {
+            return false;
+        }
+        if self.is_symbol(Symbol::OpenBrace) {
+            // `#name{` or `#?MODULE{`
+            let sharp = i >= 2
+                && is_symbol(&self.tokens[i - 2], Symbol::Sharp)
+                && prev.as_atom_token().is_some();
+            let macro_sharp = i >= 3
+                && (prev.as_atom_token().is_some() || prev.as_variable_token().is_some())
+                && is_symbol(&self.tokens[i - 2], Symbol::Question)
+                && is_symbol(&self.tokens[i - 3], Symbol::Sharp);
+            if sharp || macro_sharp {
+                return false;
+            }
+        }
+        let in_bits = self.bits.last() == Some(&true);
+        for (slash, j) in [(prev, i - 1), (next, i)] {
+            if !is_symbol(slash, Symbol::Slash) {
+                continue;
+            }
+            let name_and_arity = j >= 1
+                && self.tokens[j - 1].as_atom_token().is_some()
+                && self
+                    .tokens
+                    .get(j + 1)
+                    .is_some_and(|t| t.as_integer_token().is_some());
+            if in_bits
+                || name_and_arity
+                || self.roles.get(&slash.start_position()) == Some(&Role::Tight)
+            {
+                // `<<X/binary>>`, `f/1` or `fun M:F/A`
+                return false;
+            }
+        }
+        if in_bits
+            && ((is_symbol(prev, Symbol::Hyphen) && next.as_atom_token().is_some())
+                || (is_symbol(next, Symbol::Hyphen) && prev.as_atom_token().is_some()))
+        {
+            // `<<X:8/integer-unit:8>>`
+            return false;
+        }
+        needs_space(&self.tokens, i)
+    }
+
+    fn at_stop(&self, arrow: bool) -> bool {
+        let token = match self.tokens.get(self.pos) {
+            None => return true,
+            Some(t) => t,
+        };
+        if let Some(s) = token.as_symbol_token().map(|t| t.value()) {
+            return match s {
+                Symbol::Comma | Symbol::Semicolon => true,
+                Symbol::RightArrow => arrow,
+                Symbol::Dot => self.is_form_end(),
+                _ => closer(s),
+            };
+        }
+        match keyword(token) {
+            Some(Keyword::End) | Some(Keyword::Of) | Some(Keyword::After) => true,
+            Some(Keyword::Catch) => self.role() != Some(Role::Prefix),
+            _ => self.role() == Some(Role::BlockMiddle),
+        }
+    }
+
+    fn block(&self) -> Option<Block> {
+        let token = self.tokens.get(self.pos)?;
+        match keyword(token) {
+            Some(Keyword::Case) => Some(Block::Case),
+            Some(Keyword::If) => Some(Block::If),
+            Some(Keyword::Receive) => Some(Block::Receive),
+            Some(Keyword::Try) => Some(Block::Try),
+            Some(Keyword::Begin) => Some(Block::Begin),
+            Some(Keyword::Fun) if self.role() == Some(Role::BlockStart) => Some(Block::Fun),
+            None if token.as_atom_token().is_some() && self.role() == Some(Role::BlockStart) => {
+                Some(Block::Maybe)
+            }
+            _ => None,
+        }
+    }
+
+    fn role(&self) -> Option<Role> {
+        let token = self.tokens.get(self.pos)?;
+        self.roles.get(&token.start_position()).copied()
+    }
+
+    fn symbol(&self) -> Option<Symbol> {
+        self.tokens
+            .get(self.pos)
+            .and_then(|t| t.as_symbol_token())
+            .map(|t| t.value())
+    }
+
+    fn opener(&self) -> Option<Symbol> {
+        self.symbol().filter(|&s| closer_of(s) != s)
+    }
+
+    fn is_symbol(&self, symbol: Symbol) -> bool {
+        self.symbol() == Some(symbol)
+    }
+
+    fn is_keyword(&self, k: Keyword) -> bool {
+        self.tokens.get(self.pos).and_then(keyword) == Some(k)
+    }
+
+    fn is_form_end(&self) -> bool {
+        self.pos < self.tokens.len() && is_form_end(&self.tokens, self.pos)
+    }
+}
+
+/// Emits the comments in `trivia` on their own lines, returning `true` if there are any.
+///
+/// If `blank` is `true`, the empty lines in `trivia` are also emitted (consecutive ones are squashed).
+fn comments(out: &mut Vec<Doc>, trivia: &[Token], blank: bool) -> bool {
+    let mut emitted = false;
+    let mut line_empty = true;
+    for t in trivia {
+        match *t {
+            Token::Comment(ref c) => {
+                out.push(Doc::LeadingComment(c.text().to_owned()));
+                out.push(Doc::HardLine);
+                line_empty = false;
+                emitted = true;
+            }
+            Token::Whitespace(ref w) if w.value() == Whitespace::Newline => {
+                if line_empty && blank {
+                    out.push(Doc::BlankLine);
+                    emitted = true;
+                }
+                line_empty = true;
+            }
+            _ => {}
+        }
+    }
+    emitted
+}
+
+fn keyword(token: &LexicalToken) -> Option<Keyword> {
+    token.as_keyword_token().map(KeywordToken::value)
+}
+
+/// Returns the closing symbol of `s` if it is an opening bracket, or `s` itself otherwise.
+fn closer_of(s: Symbol) -> Symbol {
+    match s {
+        Symbol::OpenParen => Symbol::CloseParen,
+        Symbol::OpenSquare => Symbol::CloseSquare,
+        Symbol::OpenBrace => Symbol::CloseBrace,
+        Symbol::DoubleLeftAngle => Symbol::DoubleRightAngle,
+        _ => s,
+    }
+}
+
+fn closer(s: Symbol) -> bool {
+    matches!(
+        s,
+        Symbol::CloseParen | Symbol::CloseSquare | Symbol::CloseBrace | Symbol::DoubleRightAngle
+    )
+}
+
+/// Returns `true` if `token` can be the last token of an expression.
+fn ends_expr(token: &LexicalToken) -> bool {
+    match *token {
+        LexicalToken::Symbol(ref t) => closer(t.value()),
+        LexicalToken::Keyword(ref t) => t.value() == Keyword::End,
+        _ => true,
+    }
+}
@@ -0,0 +1,72 @@
+//! Golden tests of the printer (`CollectTokens::to_erlang_source`).
+//!
+//! A golden test compares the printed form of an input file with an expected output file,
+//! so downstream repositories (e.g., of formatter configurations) can maintain their own suites:
+//!
+//! ```no_run
+//! erl_parse::golden::check("tests/golden/foo.erl", "tests/golden/foo.expected").unwrap();
+//! ```
+//!
+//! If the environment variable `ERL_PARSE_UPDATE_GOLDEN` is set,
+//! `check` (re)writes the expected output files instead of comparing them.
+use erl_tokenize::Lexer;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::cst::ModuleDecl;
+use crate::traits::CollectTokens;
+use crate::{Error, ErrorKind, Parser, Result, TokenReader};
+
+/// The environment variable which makes `check` update the expected output files.
+pub const UPDATE_ENV: &str = "ERL_PARSE_UPDATE_GOLDEN";
+
+/// Prints `text` as the golden tests do.
+///
+/// The text is parsed without preprocessing (see `Parser::no_preprocess`),
+/// so macro calls and directives are printed as they are and no include files are needed.
+pub fn print(text: &str) -> Result<String> {
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let module: ModuleDecl = track!(parser.parse())?;
+    let mut printed = module.to_erlang_source();
+    printed.push('\n');
+    Ok(printed)
+}
+
+/// Checks that the printed form of the file `input_path` equals the content of the file `expected_output_path`.
+///
+/// The error of a mismatch tells the first differing line.
+pub fn check<P, Q>(input_path: P, expected_output_path: Q) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let input_path = input_path.as_ref();
+    let expected_output_path = expected_output_path.as_ref();
+    let text = track!(fs::read_to_string(input_path).map_err(Error::from); input_path)?;
+    let actual = track!(print(&text); input_path)?;
+    if env::var_os(UPDATE_ENV).is_some() {
+        track!(fs::write(expected_output_path, actual).map_err(Error::from); expected_output_path)?;
+        return Ok(());
+    }
+    let expected = track!(fs::read_to_string(expected_output_path).map_err(Error::from); expected_output_path)?;
+    if actual != expected {
+        let expected_lines = expected.lines().collect::<Vec<_>>();
+        let actual_lines = actual.lines().collect::<Vec<_>>();
+        let line = (0..)
+            .find(|&i| expected_lines.get(i) != actual_lines.get(i) || i >= expected_lines.len())
+            .unwrap_or(0);
+        let expected_line = expected_lines.get(line).copied().unwrap_or("");
+        let actual_line = actual_lines.get(line).copied().unwrap_or("");
+        track_panic!(
+            ErrorKind::Other,
+            "Golden mismatch: input={:?}, expected={:?}, line={}: expected {:?}, actual {:?}",
+            input_path,
+            expected_output_path,
+            line + 1,
+            expected_line,
+            actual_line
+        );
+    }
+    Ok(())
+}
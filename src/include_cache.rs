@@ -0,0 +1,300 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken, PositionRange};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::include_resolver::{FileSystemResolver, IncludeResolver};
+use crate::token_reader::hide_question;
+use crate::traits::is_symbol;
+use crate::{Error, Result};
+
+const SHARDS: usize = 16;
+
+/// Maximum nesting depth of included files (to stop cyclic inclusions).
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// The entries keyed by the path and the source (see `IncludeResolver::source_id`) of each file.
+type Shard = Mutex<HashMap<(PathBuf, u64), Arc<Vec<LexicalToken>>>>;
+
+/// Thread-safe cache of the tokens of included files.
+///
+/// Clones share the same cache, so parsers running in parallel (e.g., over a workspace)
+/// tokenize each header (e.g., `kernel/include/logger.hrl`) only once.
+/// The entries are sharded by path to reduce lock contention.
+///
+/// Use `IncludeExpander` to make a preprocessor read included files via this cache.
+#[derive(Debug, Clone)]
+pub struct IncludeCache {
+    shards: Arc<Vec<Shard>>,
+}
+impl IncludeCache {
+    pub fn new() -> Self {
+        IncludeCache {
+            shards: Arc::new((0..SHARDS).map(|_| Mutex::default()).collect()),
+        }
+    }
+
+    /// Returns the tokens of the file `path`, reading and tokenizing it if it is not cached.
+    ///
+    /// If the file is requested concurrently before cached, it may be tokenized more than once
+    /// (the locks are not held while reading files).
+    pub fn tokens<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Vec<LexicalToken>>> {
+        track!(self.tokens_with(path, &FileSystemResolver::default()))
+    }
+
+    /// Same as `tokens` except that the file is read by `resolver` if it is not cached.
+    pub fn tokens_with<P, R>(&self, path: P, resolver: &R) -> Result<Arc<Vec<LexicalToken>>>
+    where
+        P: AsRef<Path>,
+        R: IncludeResolver + ?Sized,
+    {
+        let path = path.as_ref();
+        let key = (path.to_path_buf(), resolver.source_id(path));
+        if let Some(tokens) = self.shard(path).lock().expect("Never fails").get(&key) {
+            return Ok(Arc::clone(tokens));
+        }
+        let text = track!(resolver.read(path))?;
+        let mut lexer = Lexer::new(&text);
+        lexer.set_filepath(path);
+        let tokens =
+            track!(lexer.collect::<erl_tokenize::Result<Vec<_>>>().map_err(Error::from); path)?;
+        let mut shard = self.shard(path).lock().expect("Never fails");
+        let tokens = shard.entry(key).or_insert_with(|| Arc::new(tokens));
+        Ok(Arc::clone(tokens))
+    }
+
+    /// Removes the entries of `path` (e.g., after the file or the buffer of a `MemoryResolver` is modified).
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        let mut shard = self.shard(path).lock().expect("Never fails");
+        let len = shard.len();
+        shard.retain(|(p, _), _| p != path);
+        shard.len() != len
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().expect("Never fails").clear();
+        }
+    }
+
+    /// Returns the number of the cached files.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| s.lock().expect("Never fails").len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard(&self, path: &Path) -> &Shard {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+}
+impl Default for IncludeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Token stream which replaces `-include` and `-include_lib` directives
+/// with the tokens of the included files taken from an `IncludeCache`.
+///
+/// This is placed between a lexer and `erl_pp::Preprocessor`,
+/// which then handles the rest of the directives (e.g., conditional compilation) as usual.
+/// Directives whose files cannot be found (or which use path variables like `$HOME`)
+/// are passed through to the preprocessor.
+/// Since the replaced directives do not reach the preprocessor,
+/// they do not appear in `erl_pp::Preprocessor::directives`.
+///
+/// The included files are located by an `IncludeResolver`
+/// (`FileSystemResolver::new()` unless specified by `with_resolver`).
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::{IncludeCache, IncludeExpander, Parser, TokenReader};
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join(format!("erl_parse_include_doc_{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+/// fs::write(dir.join("a.hrl"), "-define(A, 1).").unwrap();
+///
+/// let cache = IncludeCache::new();
+/// for _ in 0..2 {
+///     let text = "-module(foo).\n-include(\"a.hrl\").\nf() -> ?A.";
+///     let tokens = IncludeExpander::new(Lexer::new(text), cache.clone()).include_dir(&dir);
+///     let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+///     let module: ModuleDecl = parser.parse().unwrap();
+///     assert_eq!(module.forms.len(), 2);
+/// }
+/// assert_eq!(cache.len(), 1);
+/// fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct IncludeExpander<T, R = FileSystemResolver> {
+    inner: T,
+    cache: IncludeCache,
+    resolver: R,
+    included: Vec<(Arc<Vec<LexicalToken>>, usize)>,
+    pending: VecDeque<LexicalToken>,
+    pending_error: Option<erl_tokenize::Error>,
+    form_start: bool,
+}
+impl<T> IncludeExpander<T>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+{
+    pub fn new(inner: T, cache: IncludeCache) -> Self {
+        Self::with_resolver(inner, cache, FileSystemResolver::new())
+    }
+
+    /// Adds a directory to search for `-include` files (see `FileSystemResolver::include_dir`).
+    pub fn include_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.resolver = self.resolver.include_dir(dir);
+        self
+    }
+
+    /// Adds a directory to search for the applications of `-include_lib` files
+    /// (see `FileSystemResolver::code_path`).
+    pub fn code_path<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.resolver = self.resolver.code_path(dir);
+        self
+    }
+}
+impl<T, R> IncludeExpander<T, R>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+    R: IncludeResolver,
+{
+    pub fn with_resolver(inner: T, cache: IncludeCache, resolver: R) -> Self {
+        IncludeExpander {
+            inner,
+            cache,
+            resolver,
+            included: Vec::new(),
+            pending: VecDeque::new(),
+            pending_error: None,
+            form_start: true,
+        }
+    }
+
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    fn next_raw(&mut self) -> Option<erl_tokenize::Result<LexicalToken>> {
+        if let Some(t) = self.pending.pop_front() {
+            return Some(Ok(t));
+        }
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+        while let Some((tokens, i)) = self.included.last_mut() {
+            if let Some(t) = tokens.get(*i) {
+                *i += 1;
+                return Some(Ok(t.clone()));
+            }
+            self.included.pop();
+        }
+        self.inner.next()
+    }
+
+    /// Reads the rest of an `-include` directive following `-`,
+    /// and returns the included tokens if it is found.
+    fn try_include(&mut self, hyphen: &LexicalToken) -> Option<Arc<Vec<LexicalToken>>> {
+        let mut tokens = Vec::new();
+        while tokens.len() < 5 {
+            match self.next_raw() {
+                Some(Ok(t)) => tokens.push(t),
+                Some(Err(e)) => {
+                    self.pending.extend(tokens);
+                    self.pending_error = Some(e);
+                    return None;
+                }
+                None => break,
+            }
+        }
+        let path = directive_path(&tokens);
+        let found = path.and_then(|(lib, path)| {
+            let position = hyphen.start_position();
+            let base = position.filepath().and_then(|p| p.parent());
+            let path = self.resolver.locate(&path, lib, base)?;
+            self.cache.tokens_with(path, &self.resolver).ok()
+        });
+        match found {
+            Some(tokens) if self.included.len() < MAX_INCLUDE_DEPTH => Some(tokens),
+            _ => {
+                self.pending.extend(tokens);
+                None
+            }
+        }
+    }
+}
+impl<T, R> Iterator for IncludeExpander<T, R>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+    R: IncludeResolver,
+{
+    type Item = erl_tokenize::Result<LexicalToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = match self.next_raw()? {
+                Err(e) => return Some(Err(e)),
+                Ok(t) => t,
+            };
+            let form_start = self.form_start;
+            self.form_start = is_symbol(&token, Symbol::Dot);
+            if form_start && is_symbol(&token, Symbol::Hyphen) {
+                if let Some(tokens) = self.try_include(&token) {
+                    self.included.push((tokens, 0));
+                    self.form_start = true;
+                    continue;
+                }
+            }
+            if is_symbol(&token, Symbol::Question) {
+                // Keeps `?` of `?=` from being dropped by `erl_pp` (see `MaybeMatchTokens`)
+                match self.next_raw() {
+                    Some(Ok(next)) => {
+                        let token = hide_question(token, &next);
+                        self.pending.push_front(next);
+                        return Some(Ok(token));
+                    }
+                    Some(Err(e)) => self.pending_error = Some(e),
+                    None => {}
+                }
+            }
+            return Some(Ok(token));
+        }
+    }
+}
+
+/// Returns `Some((is_include_lib, path))` if `tokens` are `include("path").` or `include_lib("path").`.
+fn directive_path(tokens: &[LexicalToken]) -> Option<(bool, String)> {
+    if tokens.len() != 5
+        || !is_symbol(&tokens[1], Symbol::OpenParen)
+        || !is_symbol(&tokens[3], Symbol::CloseParen)
+        || !is_symbol(&tokens[4], Symbol::Dot)
+    {
+        return None;
+    }
+    let lib = match tokens[0].as_atom_token().map(|t| t.value()) {
+        Some("include") => false,
+        Some("include_lib") => true,
+        _ => return None,
+    };
+    let path = tokens[2].as_string_token()?.value().to_owned();
+    Some((lib, path))
+}
@@ -0,0 +1,245 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, ErrorKind, Result};
+
+/// Locator of the files included by `-include` and `-include_lib` directives.
+///
+/// `IncludeExpander` asks this to locate and read the included files,
+/// so that they can come from other places than the file system
+/// (e.g., the unsaved buffers of an editor; see `MemoryResolver`).
+pub trait IncludeResolver {
+    /// Returns the path of the file included by `-include(path)` (or by `-include_lib(path)` if `lib` is `true`).
+    ///
+    /// `base` is the directory of the including file if it is known.
+    fn locate(&self, path: &str, lib: bool, base: Option<&Path>) -> Option<PathBuf>;
+
+    /// Reads the text of the file located by `locate`.
+    fn read(&self, path: &Path) -> Result<String>;
+
+    /// Returns the identifier of the source which `read` reads `path` from.
+    ///
+    /// `IncludeCache` keys its entries by this as well as by the path,
+    /// so that the files read from different sources (e.g., the file system and the buffers of an editor)
+    /// do not share the entries. `0` means the file system.
+    fn source_id(&self, path: &Path) -> u64 {
+        let _ = path;
+        0
+    }
+}
+
+/// `IncludeResolver` which reads files from the file system as `erlc` does.
+///
+/// Relative `-include` paths are resolved against the directory of the including file,
+/// the directories added by `include_dir`, and the current directory, in that order.
+/// `-include_lib("app/...")` paths are resolved against the `app` or `app-*` directories
+/// (the latest version first) in the code path directories, and then as `-include` paths.
+/// Paths starting with a path variable (e.g., `$HOME`) are not resolved.
+#[derive(Debug, Clone, Default)]
+pub struct FileSystemResolver {
+    include_dirs: Vec<PathBuf>,
+    code_paths: Vec<PathBuf>,
+}
+impl FileSystemResolver {
+    /// Makes a resolver whose code path consists of the directories listed in the `ERL_LIBS` environment variable.
+    pub fn new() -> Self {
+        let code_paths = env::var_os("ERL_LIBS")
+            .map(|v| env::split_paths(&v).collect())
+            .unwrap_or_default();
+        FileSystemResolver {
+            include_dirs: Vec::new(),
+            code_paths,
+        }
+    }
+
+    /// Adds a directory to search for `-include` files.
+    pub fn include_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds a directory to search for the applications of `-include_lib` files.
+    pub fn code_path<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.code_paths.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    fn lib_candidates(&self, path: &Path) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        let mut components = path.components();
+        let app = match components.next().and_then(|c| c.as_os_str().to_str()) {
+            None => return candidates,
+            Some(app) => app.to_owned(),
+        };
+        let rest = components.as_path();
+        for root in &self.code_paths {
+            let mut dirs = fs::read_dir(root)
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                        n == app || n.strip_prefix(&app).is_some_and(|v| v.starts_with('-'))
+                    })
+                })
+                .collect::<Vec<_>>();
+            dirs.sort_by(|a, b| compare_versions(&app, a, b).then_with(|| a.cmp(b)));
+            candidates.extend(dirs.into_iter().rev().map(|d| d.join(rest)));
+        }
+        candidates
+    }
+}
+impl IncludeResolver for FileSystemResolver {
+    fn locate(&self, path: &str, lib: bool, base: Option<&Path>) -> Option<PathBuf> {
+        if path.starts_with('$') {
+            return None;
+        }
+        let path = Path::new(path);
+        let mut candidates = if lib {
+            self.lib_candidates(path)
+        } else {
+            Vec::new()
+        };
+        if path.is_absolute() {
+            candidates.push(path.to_path_buf());
+        } else {
+            candidates.extend(base.map(|b| b.join(path)));
+            candidates.extend(self.include_dirs.iter().map(|d| d.join(path)));
+            candidates.push(path.to_path_buf());
+        }
+        candidates.into_iter().find(|p| p.is_file())
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        track!(fs::read_to_string(path).map_err(Error::from); path)
+    }
+}
+
+/// Compares the versions of application directories (e.g., `app-1.10` > `app-1.9` > `app`).
+///
+/// The numeric components of the versions are compared as numbers.
+fn compare_versions(app: &str, a: &Path, b: &Path) -> Ordering {
+    let version = |dir: &Path| -> Option<Vec<std::result::Result<u64, String>>> {
+        let name = dir.file_name()?.to_str()?;
+        let version = name.strip_prefix(app)?.strip_prefix('-')?;
+        Some(
+            version
+                .split(['.', '-'])
+                .map(|c| c.parse().map_err(|_| c.to_owned()))
+                .collect(),
+        )
+    };
+    version(a).cmp(&version(b))
+}
+
+/// `IncludeResolver` which reads files from memory.
+///
+/// Relative `-include` paths are resolved against the directory of the including file,
+/// and then used as they are.
+/// `-include_lib` paths are used as they are (e.g., `kernel/include/logger.hrl`),
+/// and then resolved as `-include` paths.
+///
+/// If a fallback resolver is given, the files which are not in memory are located by it;
+/// this can be used to overlay the unsaved buffers of an editor on the file system.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::{FileSystemResolver, IncludeCache, IncludeExpander, MemoryResolver, Parser, TokenReader};
+/// use erl_parse::cst::ModuleDecl;
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let mut resolver = MemoryResolver::new().fallback(FileSystemResolver::new());
+/// resolver.insert("/src/a.hrl", "-define(A, 1).");
+///
+/// let mut lexer = Lexer::new("-include(\"a.hrl\").\nf() -> ?A.");
+/// lexer.set_filepath("/src/foo.erl");
+/// let tokens = IncludeExpander::with_resolver(lexer, IncludeCache::new(), resolver);
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+/// let module: ModuleDecl = parser.parse().unwrap();
+/// assert_eq!(module.forms.len(), 1);
+/// ```
+#[derive(Default)]
+pub struct MemoryResolver {
+    files: HashMap<PathBuf, String>,
+    fallback: Option<Box<dyn IncludeResolver + Send + Sync>>,
+}
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the resolver used for the files which are not in memory.
+    pub fn fallback<R>(mut self, resolver: R) -> Self
+    where
+        R: IncludeResolver + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(resolver));
+        self
+    }
+
+    /// Adds (or replaces) the file `path` whose content is `text`.
+    pub fn insert<P: AsRef<Path>, S: Into<String>>(&mut self, path: P, text: S) {
+        self.files.insert(path.as_ref().to_path_buf(), text.into());
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        self.files.remove(path.as_ref())
+    }
+}
+impl IncludeResolver for MemoryResolver {
+    fn locate(&self, path: &str, lib: bool, base: Option<&Path>) -> Option<PathBuf> {
+        let relative = Path::new(path);
+        let mut candidates = Vec::new();
+        if lib {
+            candidates.push(relative.to_path_buf());
+        }
+        candidates.extend(base.map(|b| b.join(relative)));
+        candidates.push(relative.to_path_buf());
+        candidates
+            .into_iter()
+            .find(|p| self.files.contains_key(p))
+            .or_else(|| self.fallback.as_ref()?.locate(path, lib, base))
+    }
+
+    fn read(&self, path: &Path) -> Result<String> {
+        if let Some(text) = self.files.get(path) {
+            return Ok(text.clone());
+        }
+        let fallback = track_assert_some!(
+            self.fallback.as_ref(),
+            ErrorKind::InvalidInput,
+            "No such file: {:?}",
+            path
+        );
+        track!(fallback.read(path))
+    }
+
+    /// Returns the hash of the buffer of `path` (which is never `0`),
+    /// or the identifier given by the fallback resolver if the file is not in memory.
+    fn source_id(&self, path: &Path) -> u64 {
+        match self.files.get(path) {
+            Some(text) => {
+                let mut hasher = DefaultHasher::new();
+                text.hash(&mut hasher);
+                hasher.finish().max(1)
+            }
+            None => self.fallback.as_ref().map_or(0, |r| r.source_id(path)),
+        }
+    }
+}
+impl std::fmt::Debug for MemoryResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MemoryResolver")
+            .field("files", &self.files.keys().collect::<Vec<_>>())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
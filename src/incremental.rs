@@ -0,0 +1,366 @@
+use erl_pp::{MacroDef, Preprocessor};
+use erl_tokenize::tokens::{
+    AtomToken, CharToken, FloatToken, IntegerToken, KeywordToken, StringToken, SymbolToken,
+    VariableToken,
+};
+use erl_tokenize::{LexicalToken, Position, PositionRange, Tokenizer};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+
+use crate::cst::{Form, ModuleDecl};
+use crate::traits::{is_form_end_in, Fold, Foldable};
+use crate::{Error, ErrorKind, MaybeMatchTokens, Parser, Result, TextEdit, TokenReader};
+
+/// Parsed module which can be reparsed incrementally after text edits (e.g., in an editor).
+///
+/// The source text is split into segments, each of which is a form or a directive terminated by `.`
+/// (a conditional compilation block, from `-ifdef` to the matching `-endif`, makes a single segment).
+/// `reparse` reparses only the segments affected by an edit and reuses the other ones.
+///
+/// Like `Parser::parse_module_resilient`, forms which cannot be parsed become `Form::Error`.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::{ParsedModule, TextEdit};
+/// use erl_tokenize::Position;
+///
+/// let mut module = ParsedModule::parse("-module(foo).\nf() -> 1.\ng() -> 2.\n".to_owned());
+/// assert_eq!(module.forms().count(), 3);
+///
+/// // Replaces `1` with `10`
+/// let edit = TextEdit {
+///     start_position: Position::new() + 21,
+///     end_position: Position::new() + 22,
+///     new_text: "10".to_owned(),
+/// };
+/// let reparsed = module.reparse(&edit).unwrap();
+/// assert_eq!(reparsed, 1..2);
+/// assert_eq!(module.text(), "-module(foo).\nf() -> 10.\ng() -> 2.\n");
+/// ```
+#[derive(Debug)]
+pub struct ParsedModule {
+    text: String,
+    segments: Vec<Segment>,
+}
+impl ParsedModule {
+    /// Parses `text` as a module.
+    pub fn parse(text: String) -> Self {
+        let mut segments = Vec::new();
+        let mut macros = HashMap::new();
+        let mut start = Some(Position::new());
+        while let Some(segment) = start
+            .take()
+            .and_then(|s| next_segment(&text, s, &mut macros))
+        {
+            start = segment.1;
+            segments.push(segment.0);
+        }
+        ParsedModule { text, segments }
+    }
+
+    /// Applies `edit` to the text and reparses the affected part.
+    ///
+    /// Only the offsets of the positions of `edit` are used.
+    /// The forms following the edited region are reused with their positions updated,
+    /// but if a directive is inserted, removed or edited, everything after the edit is reparsed.
+    ///
+    /// Returns the index range of the forms which have been reparsed.
+    pub fn reparse(&mut self, edit: &TextEdit) -> Result<Range<usize>> {
+        let (start, end) = (edit.start_position.offset(), edit.end_position.offset());
+        track_assert!(
+            start <= end
+                && end <= self.text.len()
+                && self.text.is_char_boundary(start)
+                && self.text.is_char_boundary(end),
+            ErrorKind::InvalidInput,
+            "Invalid edit range: {}..{}",
+            start,
+            end
+        );
+        let text = format!(
+            "{}{}{}",
+            &self.text[..start],
+            edit.new_text,
+            &self.text[end..]
+        );
+        let delta = edit.new_text.len() as isize - (end - start) as isize;
+        let edited_end = start + edit.new_text.len();
+
+        let first = self
+            .segments
+            .iter()
+            .position(|s| s.range.end >= start)
+            .unwrap_or(self.segments.len())
+            .min(self.segments.len().saturating_sub(1));
+        let mut macros = self.macros_before(first);
+        let mut next = Some(
+            self.segments
+                .get(first)
+                .map_or_else(Position::new, |s| s.start.clone()),
+        );
+        let mut has_directives = false;
+        let mut new_segments = Vec::new();
+        let mut reused = self.segments.len();
+        while let Some(segment) = next
+            .take()
+            .and_then(|s| next_segment(&text, s, &mut macros))
+        {
+            let (segment, following) = segment;
+            has_directives |= segment.has_directives;
+            let new_end = segment.range.end;
+            new_segments.push(segment);
+            next = following;
+            if new_end < edited_end {
+                continue;
+            }
+            let old_end = (new_end as isize - delta) as usize;
+            let last = match self.segments[first..].binary_search_by_key(&old_end, |s| s.range.end)
+            {
+                Ok(i) => first + i,
+                Err(_) => continue,
+            };
+            if !has_directives && !self.segments[first..=last].iter().any(|s| s.has_directives) {
+                reused = last + 1;
+                break;
+            }
+        }
+
+        let mut tail = self.segments.split_off(reused);
+        if let (Some(next), Some(old)) = (next, tail.first()) {
+            if next != old.start {
+                relocate(&text, next, delta, &mut tail);
+            }
+        }
+        let reparsed_start = self.segments[..first]
+            .iter()
+            .map(|s| s.forms.len())
+            .sum::<usize>();
+        let reparsed_end =
+            reparsed_start + new_segments.iter().map(|s| s.forms.len()).sum::<usize>();
+        self.segments.truncate(first);
+        self.segments.extend(new_segments);
+        self.segments.extend(tail);
+        self.text = text;
+        Ok(reparsed_start..reparsed_end)
+    }
+
+    /// Returns the source text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the forms of the module.
+    pub fn forms(&self) -> impl Iterator<Item = &Form> {
+        self.segments.iter().flat_map(|s| s.forms.iter())
+    }
+
+    /// Returns the errors raised while parsing the module.
+    ///
+    /// The errors of reused segments are kept as they were reported.
+    pub fn errors(&self) -> impl Iterator<Item = &Error> {
+        self.segments.iter().flat_map(|s| s.errors.iter())
+    }
+
+    /// Returns the byte ranges of the segments (i.e., the form boundaries).
+    pub fn segment_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.segments.iter().map(|s| s.range.clone())
+    }
+
+    pub fn to_module_decl(&self) -> ModuleDecl {
+        ModuleDecl {
+            forms: self.forms().cloned().collect(),
+        }
+    }
+
+    /// Returns the macros defined by the directives preceding the `index`-th segment.
+    fn macros_before(&self, index: usize) -> HashMap<String, MacroDef> {
+        let mut macros = HashMap::new();
+        for segment in self.segments[..index].iter().filter(|s| s.has_directives) {
+            let tokens = SegmentTokens::new(&self.text, segment.start.clone(), segment.range.end);
+            let mut pp = Preprocessor::new(tokens);
+            mem::swap(pp.macros_mut(), &mut macros);
+            while let Some(Ok(_)) = pp.next() {}
+            mem::swap(pp.macros_mut(), &mut macros);
+        }
+        macros
+    }
+}
+
+#[derive(Debug)]
+struct Segment {
+    range: Range<usize>,
+    start: Position,
+    forms: Vec<Form>,
+    errors: Vec<Error>,
+    has_directives: bool,
+}
+
+/// Parses the segment starting at `start`.
+///
+/// Returns the segment and the start position of the next one (`None` at the end of `text`).
+fn next_segment(
+    text: &str,
+    start: Position,
+    macros: &mut HashMap<String, MacroDef>,
+) -> Option<(Segment, Option<Position>)> {
+    let mut tokenizer = Tokenizer::new(text);
+    tokenizer.set_position(start.clone());
+    let mut has_tokens = false;
+    let mut depth = 0usize;
+    let mut form_tokens = Vec::new();
+    let mut next = None;
+    while let Some(token) = tokenizer.next() {
+        let token = match token.map(|t| t.into_lexical_token()) {
+            Err(_) => break,
+            Ok(Err(_)) => continue,
+            Ok(Ok(token)) => token,
+        };
+        has_tokens = true;
+        if form_tokens.len() < 2 {
+            form_tokens.push(token.text().to_owned());
+            if form_tokens.len() == 2 && form_tokens[0] == "-" {
+                match form_tokens[1].as_str() {
+                    "if" | "ifdef" | "ifndef" => depth += 1,
+                    "endif" => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+        if is_form_end_in(text, &token) {
+            form_tokens.clear();
+            if depth == 0 {
+                next = Some(tokenizer.next_position());
+                break;
+            }
+        }
+    }
+    if !has_tokens {
+        return None;
+    }
+    let end = next.as_ref().map_or(text.len(), Position::offset);
+
+    let tokens = MaybeMatchTokens::new(SegmentTokens::new(text, start.clone(), end));
+    let mut pp = Preprocessor::new(tokens);
+    mem::swap(pp.macros_mut(), macros);
+    let mut parser = Parser::new(TokenReader::new(pp));
+    let (module, errors) = parser.parse_module_resilient();
+    let mut pp = parser.into_reader().into_inner();
+    mem::swap(pp.macros_mut(), macros);
+    let segment = Segment {
+        range: start.offset()..end,
+        start,
+        forms: module.forms,
+        errors,
+        has_directives: !pp.directives().is_empty(),
+    };
+    let next = next.filter(|n| n.offset() < text.len());
+    Some((segment, next))
+}
+
+/// Lexical tokens from `start` up to (but not including) the offset `end`.
+#[derive(Debug)]
+struct SegmentTokens<'a> {
+    tokenizer: Tokenizer<&'a str>,
+    end: usize,
+    failed: bool,
+}
+impl<'a> SegmentTokens<'a> {
+    fn new(text: &'a str, start: Position, end: usize) -> Self {
+        let mut tokenizer = Tokenizer::new(text);
+        tokenizer.set_position(start);
+        SegmentTokens {
+            tokenizer,
+            end,
+            failed: false,
+        }
+    }
+}
+impl<'a> Iterator for SegmentTokens<'a> {
+    type Item = erl_tokenize::Result<LexicalToken>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed && self.tokenizer.next_position().offset() < self.end {
+            match self.tokenizer.next()? {
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+                Ok(token) => {
+                    if let Ok(token) = token.into_lexical_token() {
+                        return Some(Ok(token));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Updates the positions of the tokens in `segments` which have been moved by `delta` bytes.
+///
+/// `start` is the new start position of the first segment.
+fn relocate(text: &str, start: Position, delta: isize, segments: &mut [Segment]) {
+    let mut positions = HashMap::new();
+    let mut tokenizer = Tokenizer::new(text);
+    tokenizer.set_position(start);
+    loop {
+        let position = tokenizer.next_position();
+        match tokenizer.next() {
+            Some(Ok(_)) => {
+                let old_offset = (position.offset() as isize - delta) as usize;
+                positions.insert(old_offset, position);
+            }
+            _ => break,
+        }
+    }
+
+    let mut relocate = Relocate { positions };
+    for segment in segments {
+        if let Some(start) = relocate.positions.get(&segment.start.offset()) {
+            segment.start = start.clone();
+        }
+        segment.range = shift(&segment.range, delta);
+        let forms = mem::take(&mut segment.forms);
+        segment.forms = forms.fold_with(&mut relocate);
+    }
+}
+
+fn shift(range: &Range<usize>, delta: isize) -> Range<usize> {
+    let f = |x: usize| (x as isize + delta) as usize;
+    f(range.start)..f(range.end)
+}
+
+/// Rewrites the positions of tokens by the map from old offsets to new positions.
+///
+/// Tokens which are not in the map (e.g., ones expanded from macros defined before the edit)
+/// and tokens of included files are kept as they are.
+struct Relocate {
+    positions: HashMap<usize, Position>,
+}
+macro_rules! relocate_token {
+    ($($method:ident($t:ty)),*) => {
+        $(fn $method(&mut self, token: $t) -> $t {
+            let position = token.start_position();
+            if position.filepath().is_some() {
+                return token;
+            }
+            match self.positions.get(&position.offset()) {
+                None => token,
+                Some(p) => <$t>::from_text(token.text(), p.clone()).unwrap_or(token),
+            }
+        })*
+    };
+}
+impl Fold for Relocate {
+    relocate_token!(
+        fold_atom_token(AtomToken),
+        fold_char_token(CharToken),
+        fold_float_token(FloatToken),
+        fold_integer_token(IntegerToken),
+        fold_keyword_token(KeywordToken),
+        fold_string_token(StringToken),
+        fold_symbol_token(SymbolToken),
+        fold_variable_token(VariableToken)
+    );
+}
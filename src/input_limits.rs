@@ -0,0 +1,274 @@
+use erl_pp::MacroCall;
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::traits::{Preprocessor, TokenRead};
+use crate::{Error, ErrorKind, Result};
+
+/// Upper bounds of the input accepted by `Guarded` (`None` means unlimited).
+///
+/// These are meant for services which parse untrusted source code (e.g., user uploads).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputLimits {
+    /// The maximum number of bytes of the source text.
+    ///
+    /// `Guarded` counts the bytes of each source file (including included files) up to
+    /// the end of the last token read from it, so whitespaces and comments between tokens are counted.
+    /// Use `read_source` to also bound the trailing ones (and to avoid loading an oversized input).
+    pub max_bytes: Option<usize>,
+
+    /// The maximum number of tokens.
+    pub max_tokens: Option<usize>,
+}
+impl InputLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn max_bytes(mut self, n: usize) -> Self {
+        self.max_bytes = Some(n);
+        self
+    }
+    pub fn max_tokens(mut self, n: usize) -> Self {
+        self.max_tokens = Some(n);
+        self
+    }
+
+    /// Reads source text from `reader`, failing as soon as it exceeds `max_bytes`.
+    ///
+    /// At most `max_bytes + 1` bytes are read, so an oversized input is never loaded entirely.
+    pub fn read_source<R: Read>(&self, reader: R) -> Result<String> {
+        let mut text = String::new();
+        match self.max_bytes {
+            None => {
+                let mut reader = reader;
+                track!(reader.read_to_string(&mut text).map_err(Error::from))?;
+            }
+            Some(max) => {
+                let mut reader = reader.take(max as u64 + 1);
+                track!(reader.read_to_string(&mut text).map_err(Error::from))?;
+                track_assert!(
+                    text.len() <= max,
+                    ErrorKind::LimitExceeded,
+                    "The input exceeds the limit of {} bytes",
+                    max
+                );
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Upper bounds of the syntactic structure accepted by `Parser` (`None` means unlimited).
+///
+/// Only `max_depth` is set by default.
+///
+/// Unlike `InputLimits`, these are enforced by the parser itself (see `Parser::limits`).
+/// Once a limit is exceeded, the parser fails with `ErrorKind::LimitExceeded`
+/// and every subsequent parse fails with the same error.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::Expr;
+/// use erl_parse::{ErrorKind, Limits, Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new("[[[[1]]]]")));
+/// let mut parser = Parser::new(reader).limits(Limits::new().max_depth(3));
+/// let e = parser.parse::<Expr>().err().unwrap();
+/// assert!(matches!(e.kind(), ErrorKind::LimitExceeded));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum nesting depth of expressions, patterns, guard tests and types
+    /// (`Limits::DEFAULT_MAX_DEPTH` by default).
+    ///
+    /// The operands of a chain of binary operators (e.g., `A ++ B ++ C`) are regarded as nested.
+    ///
+    /// Parsing itself never exhausts the stack, but dropping and traversing the resulting CST is recursive.
+    /// Use `cst::dispose` to drop the CST parsed with this limit raised or removed.
+    pub max_depth: Option<usize>,
+
+    /// The maximum number of tokens in a form (including its terminating `.`).
+    pub max_tokens_per_form: Option<usize>,
+
+    /// The maximum number of forms.
+    pub max_forms: Option<usize>,
+}
+impl Limits {
+    /// The default value of `max_depth`.
+    ///
+    /// Cloning a CST takes up to about 4 KiB of the stack per level of nesting
+    /// (the most among the recursive operations of this crate, e.g., dropping one takes about 300 bytes),
+    /// so with this depth it fits in 1 MiB, i.e., half the stack of a thread spawned by default.
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn max_depth(mut self, n: usize) -> Self {
+        self.max_depth = Some(n);
+        self
+    }
+    pub fn max_tokens_per_form(mut self, n: usize) -> Self {
+        self.max_tokens_per_form = Some(n);
+        self
+    }
+    pub fn max_forms(mut self, n: usize) -> Self {
+        self.max_forms = Some(n);
+        self
+    }
+}
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: Some(Self::DEFAULT_MAX_DEPTH),
+            max_tokens_per_form: None,
+            max_forms: None,
+        }
+    }
+}
+
+/// Token reader which fails when the tokens read from the inner reader exceed `InputLimits`.
+///
+/// Tokens are counted as they are read, so the input beyond the limits is never tokenized
+/// (nor preprocessed). Once a limit is exceeded, every subsequent read fails with the same error.
+/// Tokens unread by backtracking are not counted twice.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::Expr;
+/// use erl_parse::{Guarded, InputLimits, Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let limits = InputLimits::new().max_tokens(5);
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new("[1, 2]")));
+/// let mut parser = Parser::new(Guarded::new(reader, limits.clone()));
+/// assert!(parser.parse::<Expr>().is_ok());
+///
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new("[1, 2, 3]")));
+/// let mut parser = Parser::new(Guarded::new(reader, limits));
+/// assert!(parser.parse::<Expr>().is_err());
+/// ```
+#[derive(Debug)]
+pub struct Guarded<T> {
+    inner: T,
+    limits: InputLimits,
+    tokens: usize,
+    bytes: usize,
+    offsets: Vec<(Option<PathBuf>, usize)>,
+    exceeded: Option<Error>,
+}
+impl<T: TokenRead> Guarded<T> {
+    pub fn new(inner: T, limits: InputLimits) -> Self {
+        Guarded {
+            inner,
+            limits,
+            tokens: 0,
+            bytes: 0,
+            offsets: Vec::new(),
+            exceeded: None,
+        }
+    }
+
+    pub fn limits(&self) -> &InputLimits {
+        &self.limits
+    }
+
+    /// Returns the number of tokens consumed so far.
+    pub fn token_count(&self) -> usize {
+        self.tokens
+    }
+
+    /// Returns the number of bytes of the source text consumed so far (see `InputLimits::max_bytes`).
+    pub fn byte_count(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns `true` if a limit has been exceeded.
+    pub fn is_exceeded(&self) -> bool {
+        self.exceeded.is_some()
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn advance(&mut self, end: Position) {
+        let offset = end.offset();
+        let path = end.filepath();
+        if let Some(x) = self.offsets.iter_mut().find(|x| x.0.as_ref() == path) {
+            // Backtracked (or macro-expanded) tokens do not advance the offset
+            if offset > x.1 {
+                self.bytes += offset - x.1;
+                x.1 = offset;
+            }
+            return;
+        }
+        self.bytes += offset;
+        self.offsets.push((path.cloned(), offset));
+    }
+
+    fn check(&self) -> Result<()> {
+        if let Some(max) = self.limits.max_tokens {
+            track_assert!(
+                self.tokens <= max,
+                ErrorKind::LimitExceeded,
+                "The input exceeds the limit of {} tokens",
+                max
+            );
+        }
+        if let Some(max) = self.limits.max_bytes {
+            track_assert!(
+                self.bytes <= max,
+                ErrorKind::LimitExceeded,
+                "The input exceeds the limit of {} bytes",
+                max
+            );
+        }
+        Ok(())
+    }
+}
+impl<T: Preprocessor> Preprocessor for Guarded<T> {
+    fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
+        self.inner.define_macro(name, replacement);
+    }
+    fn undef_macro(&mut self, name: &str) {
+        self.inner.undef_macro(name);
+    }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        self.inner.macro_calls()
+    }
+}
+impl<T: TokenRead> TokenRead for Guarded<T> {
+    fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
+        if let Some(ref e) = self.exceeded {
+            return Err(e.clone());
+        }
+        let token = track!(self.inner.try_read_token())?;
+        if let Some(ref token) = token {
+            self.tokens += 1;
+            self.advance(token.end_position());
+            if let Err(e) = track!(self.check()) {
+                self.exceeded = Some(e.clone());
+                return Err(e);
+            }
+        }
+        Ok(token)
+    }
+    fn unread_token(&mut self, token: LexicalToken) {
+        self.tokens -= 1;
+        self.inner.unread_token(token);
+    }
+}
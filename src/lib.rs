@@ -18,19 +18,63 @@
 //! parser.parse::<Expr>().unwrap();
 //! # }
 //! ```
+//!
+//! # Features
+//!
+//! - `serde`: implements `Serialize` and `Deserialize` for all CST nodes (the `cst` module).
 #[macro_use]
 extern crate trackable;
 
-pub use crate::error::{Error, ErrorKind};
-pub use crate::parser::Parser;
-pub use crate::token_reader::TokenReader;
+pub use crate::all_branches::{AllBranches, Branch, Branches};
+pub use crate::diagnostic::{Diagnostic, Fix, RelatedInfo, Severity, TextEdit};
+pub use crate::error::{
+    Error, ErrorKind, HeadMismatch, IllegalGuardExpr, MacroArgsMismatch, UnsupportedFeature,
+};
+pub use crate::include_cache::{IncludeCache, IncludeExpander};
+pub use crate::include_resolver::{FileSystemResolver, IncludeResolver, MemoryResolver};
+pub use crate::incremental::ParsedModule;
+pub use crate::input_limits::{Guarded, InputLimits, Limits};
+pub use crate::module_parser::ModuleParser;
+pub use crate::otp_release::{OtpRelease, SyntaxFeature};
+pub use crate::parser::{MacroMode, ParseContext, Parser, ParserBuilder, Snapshot};
+pub use crate::recording::Recording;
+pub use crate::span::{Span, Spanned};
+pub use crate::token_reader::{MacroExpansion, MaybeMatchTokens, TokenReader};
 
+pub mod abstract_format;
+pub mod analysis;
+pub mod ast;
 pub mod builtin;
 pub mod cst;
+pub mod escript;
+pub mod etf;
+pub mod extension;
+pub mod format;
+pub mod golden;
+pub mod lint;
+pub mod messages;
+pub mod parsetools;
+pub mod project;
+pub mod render;
+pub mod template;
+pub mod term;
 pub mod traits;
+pub mod transform;
+pub mod workspace;
 
+mod all_branches;
+mod diagnostic;
 mod error;
+mod include_cache;
+mod include_resolver;
+mod incremental;
+mod input_limits;
+mod module_parser;
+mod otp_release;
 mod parser;
+mod recording;
+mod span;
+mod stack;
 mod token_reader;
 
 /// This crate specific `Result` type.
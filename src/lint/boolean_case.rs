@@ -0,0 +1,244 @@
+use crate::cst::clauses::CaseClause;
+use crate::cst::commons::parts::BinaryOp;
+use crate::cst::exprs::parts::Body;
+use crate::cst::exprs::Case;
+use crate::cst::{is_guard_bif, Expr, Literal, ModuleDecl, Node, NodeKind, Pattern};
+use crate::messages::Message;
+use crate::traits::CollectTokens;
+use crate::{Diagnostic, Fix, TextEdit};
+
+use super::{BooleanCaseConfig, BooleanCaseStyle};
+
+/// Reports `case Expr of true -> A; false -> B end` expressions (in either clause order).
+///
+/// The fix-it replaces the whole `case` expression by the rewrite preferred by `config.style`
+/// (or the other one if it is not applicable).
+/// Since the replacement is generated from the CST, the layout and comments inside the `case`
+/// expression are not preserved.
+pub fn check_boolean_case(module: &ModuleDecl, config: &BooleanCaseConfig) -> Vec<Diagnostic> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let mut finder = BooleanCaseFinder {
+        style: config.style,
+        diagnostics: Vec::new(),
+    };
+    let mut stack = Node::forms(module)
+        .map(|form| (form, None))
+        .collect::<Vec<_>>();
+    stack.reverse();
+    while let Some((node, parent)) = stack.pop() {
+        if let Node::Expr(Expr::Case(x)) = node {
+            finder.check(x, parent.is_some_and(is_body));
+        }
+        stack.extend(node.children().into_iter().rev().map(|c| (c, Some(node))));
+    }
+    finder.diagnostics
+}
+
+struct BooleanCaseFinder {
+    style: BooleanCaseStyle,
+    diagnostics: Vec<Diagnostic>,
+}
+impl BooleanCaseFinder {
+    fn check(&mut self, case: &Case, in_body: bool) {
+        let clauses = case.clauses.iter().collect::<Vec<_>>();
+        let (if_true, if_false) = match clauses[..] {
+            [a, b] => match (boolean_pattern(a), boolean_pattern(b)) {
+                (Some(true), Some(false)) => (&a.body, &b.body),
+                (Some(false), Some(true)) => (&b.body, &a.body),
+                _ => return,
+            },
+            _ => return,
+        };
+
+        let short_circuit = || short_circuit(&case.expr, if_true, if_false, in_body);
+        let if_expr = || {
+            if is_guard_expr(&case.expr) {
+                Some((
+                    Message::new("rewrite_with").arg("construct", "if"),
+                    format!(
+                        "if {} -> {}; true -> {} end",
+                        case.expr.to_erlang_source(),
+                        if_true.to_erlang_source(),
+                        if_false.to_erlang_source()
+                    ),
+                ))
+            } else {
+                None
+            }
+        };
+        let rewrite = match self.style {
+            BooleanCaseStyle::ShortCircuit => short_circuit().or_else(if_expr),
+            BooleanCaseStyle::If => if_expr().or_else(short_circuit),
+        };
+
+        let message = Message::new("boolean_case");
+        let mut diagnostic = Diagnostic::warning("boolean_case", message, case);
+        if let Some((title, new_text)) = rewrite {
+            diagnostic = diagnostic.with_fix(Fix {
+                title,
+                edits: vec![TextEdit::replace(case, new_text)],
+            });
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+/// Returns `true` if the expressions which are children of `parent` are the expressions of a body
+/// (e.g., of a clause), where a replacement need not be enclosed in parentheses.
+fn is_body(parent: Node) -> bool {
+    match parent {
+        Node::Expr(x) => matches!(*x, Expr::Block(_) | Expr::Try(_) | Expr::Maybe(_)),
+        _ => parent.kind() == NodeKind::Clause,
+    }
+}
+
+/// Returns `Some(b)` if the clause is `b -> ...` without guards.
+fn boolean_pattern(clause: &CaseClause) -> Option<bool> {
+    if clause.guard.is_some() {
+        return None;
+    }
+    match clause.pattern {
+        Pattern::Literal(Literal::Atom(ref a)) => match a.value() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns `Some(b)` if the body consists only of the atom `b`.
+fn boolean_body(body: &Body) -> Option<bool> {
+    if body.exprs.tail.is_some() {
+        return None;
+    }
+    match body.exprs.item {
+        Expr::Literal(Literal::Atom(ref a)) => match a.value() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Rewrites the `case` expression with `andalso`, `orelse` or `not`.
+///
+/// Unless the `case` expression is in a body (`in_body`), the replacement is enclosed in parentheses
+/// since it may be an operand or an argument.
+fn short_circuit(
+    cond: &Expr,
+    if_true: &Body,
+    if_false: &Body,
+    in_body: bool,
+) -> Option<(Message, String)> {
+    let rewrite_with = |construct| Message::new("rewrite_with").arg("construct", construct);
+    let enclose = |text: String| {
+        if in_body {
+            text
+        } else {
+            format!("({})", text)
+        }
+    };
+    Some(match (boolean_body(if_true), boolean_body(if_false)) {
+        (Some(true), Some(false)) => {
+            let text = if in_body {
+                cond.to_erlang_source()
+            } else {
+                operand(cond, true)
+            };
+            (Message::new("remove_case"), text)
+        }
+        (Some(false), Some(true)) => (
+            rewrite_with("not"),
+            enclose(format!("not {}", operand(cond, true))),
+        ),
+        (None, Some(false)) if if_true.exprs.tail.is_none() => (
+            rewrite_with("andalso"),
+            enclose(format!(
+                "{} andalso {}",
+                operand(cond, false),
+                operand(&if_true.exprs.item, false)
+            )),
+        ),
+        (Some(true), None) if if_false.exprs.tail.is_none() => (
+            rewrite_with("orelse"),
+            enclose(format!(
+                "{} orelse {}",
+                operand(cond, false),
+                operand(&if_false.exprs.item, false)
+            )),
+        ),
+        _ => return None,
+    })
+}
+
+/// Generates the source of `expr` as an operand of `not` (if `unary` is `true`) or a short-circuit operator,
+/// enclosing it in parentheses if needed.
+fn operand(expr: &Expr, unary: bool) -> String {
+    let needs_parens = match *expr {
+        Expr::Match(_) | Expr::Catch(_) => true,
+        Expr::BinaryOpCall(ref x) => {
+            unary
+                || matches!(
+                    x.op,
+                    BinaryOp::Andalso(_) | BinaryOp::Orelse(_) | BinaryOp::Send(_)
+                )
+        }
+        _ => false,
+    };
+    if needs_parens {
+        format!("({})", expr.to_erlang_source())
+    } else {
+        expr.to_erlang_source()
+    }
+}
+
+/// Returns `true` if `expr` can be used as a guard test (e.g., as the condition of an `if` clause).
+fn is_guard_expr(expr: &Expr) -> bool {
+    let mut stack = vec![Node::Expr(expr)];
+    while let Some(node) = stack.pop() {
+        match node {
+            Node::Expr(x) if is_guard_node(x) => stack.extend(node.children()),
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Returns `true` if `expr` (excluding its children) can be a part of a guard test.
+fn is_guard_node(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Literal(_)
+        | Expr::Variable(_)
+        | Expr::Tuple(_)
+        | Expr::Map(_)
+        | Expr::MapUpdate(_)
+        | Expr::Record(_)
+        | Expr::RecordUpdate(_)
+        | Expr::RecordFieldIndex(_)
+        | Expr::RecordFieldAccess(_)
+        | Expr::List(_)
+        | Expr::Bits(_)
+        | Expr::Parenthesized(_)
+        | Expr::UnaryOpCall(_) => true,
+        Expr::BinaryOpCall(ref x) => !matches!(x.op, BinaryOp::Send(_)),
+        Expr::FunCall(ref x) => {
+            let module = match x.module {
+                None => Some("erlang"),
+                Some(ref m) => atom_value(&m.name),
+            };
+            let name = atom_value(&x.name);
+            module == Some("erlang") && name.is_some_and(|n| is_guard_bif(n, x.args.len()))
+        }
+        _ => false,
+    }
+}
+
+fn atom_value(expr: &Expr) -> Option<&str> {
+    match *expr {
+        Expr::Literal(Literal::Atom(ref a)) => Some(a.value()),
+        _ => None,
+    }
+}
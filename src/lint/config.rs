@@ -0,0 +1,321 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{ErrorKind, Result};
+
+/// Configuration of the lints.
+///
+/// The configuration file is written in a subset of TOML:
+/// `[section]` headers followed by `key = value` lines,
+/// where a value is a boolean, an integer, a string or a single-line array of strings.
+/// Unknown sections and keys are rejected.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::lint::{BooleanCaseStyle, LintConfig};
+///
+/// let config = LintConfig::parse(r#"
+/// [naming]
+/// min_variable_length = 2
+/// module_prefixes = ["foo_", "bar_"]  # comment
+///
+/// [boolean_case]
+/// style = "if"
+/// "#).unwrap();
+/// assert_eq!(config.naming.min_variable_length, 2);
+/// assert_eq!(config.naming.module_prefixes, ["foo_", "bar_"]);
+/// assert_eq!(config.boolean_case.style, BooleanCaseStyle::If);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintConfig {
+    /// `[naming]` section.
+    pub naming: NamingConfig,
+
+    /// `[boolean_case]` section.
+    pub boolean_case: BooleanCaseConfig,
+
+    /// `[function_size]` section.
+    pub function_size: FunctionSizeConfig,
+
+    /// `[missing_spec]` section.
+    pub missing_spec: MissingSpecConfig,
+}
+impl LintConfig {
+    /// The name of the configuration file.
+    pub const FILE_NAME: &'static str = ".erlparse.toml";
+
+    /// Parses the configuration file content `text`.
+    ///
+    /// Keys which do not appear in `text` have their default values.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = LintConfig::default();
+        let mut section = String::new();
+        for (line_num, line) in (1..).zip(text.lines()) {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                track_assert!(
+                    line.ends_with(']'),
+                    ErrorKind::InvalidInput,
+                    "line={}",
+                    line_num
+                );
+                section = line[1..line.len() - 1].trim().to_owned();
+                track_assert!(
+                    ["naming", "boolean_case", "function_size", "missing_spec"]
+                        .contains(&section.as_str()),
+                    ErrorKind::InvalidInput,
+                    "Unknown section: line={}, section={:?}",
+                    line_num,
+                    section
+                );
+                continue;
+            }
+            let i =
+                track_assert_some!(line.find('='), ErrorKind::InvalidInput, "line={}", line_num);
+            let key = line[..i].trim();
+            let value = track!(Value::parse(line[i + 1..].trim()); line_num)?;
+            track!(config.set(&section, key, value); line_num)?;
+        }
+        Ok(config)
+    }
+
+    /// Loads the configuration file `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = track!(fs::read_to_string(path).map_err(crate::Error::from); path)?;
+        track!(Self::parse(&text); path)
+    }
+
+    /// Searches `dir` and its ancestors for the configuration file and loads the nearest one.
+    ///
+    /// Returns the path and the content of the file, or `None` if there is no such file.
+    pub fn discover<P: AsRef<Path>>(dir: P) -> Result<Option<(PathBuf, Self)>> {
+        for dir in dir.as_ref().ancestors() {
+            let path = dir.join(Self::FILE_NAME);
+            if path.is_file() {
+                let config = track!(Self::load(&path))?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: Value) -> Result<()> {
+        let naming = &mut self.naming;
+        let boolean_case = &mut self.boolean_case;
+        let function_size = &mut self.function_size;
+        let missing_spec = &mut self.missing_spec;
+        match (section, key) {
+            ("naming", "snake_case_functions") => {
+                naming.snake_case_functions = track!(value.into_bool())?
+            }
+            ("naming", "camel_case_variables") => {
+                naming.camel_case_variables = track!(value.into_bool())?
+            }
+            ("naming", "min_variable_length") => {
+                naming.min_variable_length = track!(value.into_integer())?
+            }
+            ("naming", "module_prefixes") => {
+                naming.module_prefixes = track!(value.into_string_array())?
+            }
+            ("boolean_case", "enabled") => boolean_case.enabled = track!(value.into_bool())?,
+            ("boolean_case", "style") => {
+                boolean_case.style = match track!(value.into_string())?.as_str() {
+                    "short_circuit" => BooleanCaseStyle::ShortCircuit,
+                    "if" => BooleanCaseStyle::If,
+                    s => track_panic!(ErrorKind::InvalidInput, "Unknown style: {:?}", s),
+                }
+            }
+            ("function_size", "max_lines") => {
+                function_size.max_lines = track!(value.into_integer())?
+            }
+            ("function_size", "max_clauses") => {
+                function_size.max_clauses = track!(value.into_integer())?
+            }
+            ("function_size", "max_nesting") => {
+                function_size.max_nesting = track!(value.into_integer())?
+            }
+            ("missing_spec", "enabled") => missing_spec.enabled = track!(value.into_bool())?,
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown key: {:?}", key),
+        }
+        Ok(())
+    }
+}
+
+/// `[naming]` section of `LintConfig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingConfig {
+    /// Requires function names to be `snake_case` (default: `true`).
+    pub snake_case_functions: bool,
+
+    /// Requires variable names to be `CamelCase` (default: `true`).
+    pub camel_case_variables: bool,
+
+    /// The minimum length of variable names, excluding `_`-prefixed ones (default: `1`).
+    pub min_variable_length: usize,
+
+    /// If not empty, module names must start with one of these prefixes (default: empty).
+    pub module_prefixes: Vec<String>,
+}
+impl Default for NamingConfig {
+    fn default() -> Self {
+        NamingConfig {
+            snake_case_functions: true,
+            camel_case_variables: true,
+            min_variable_length: 1,
+            module_prefixes: Vec::new(),
+        }
+    }
+}
+
+/// `[boolean_case]` section of `LintConfig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BooleanCaseConfig {
+    /// Reports `case` expressions which only match `true` and `false` (default: `true`).
+    pub enabled: bool,
+
+    /// The preferred rewrite offered as a fix-it (default: `"short_circuit"`).
+    pub style: BooleanCaseStyle,
+}
+impl Default for BooleanCaseConfig {
+    fn default() -> Self {
+        BooleanCaseConfig {
+            enabled: true,
+            style: BooleanCaseStyle::ShortCircuit,
+        }
+    }
+}
+
+/// Rewrite of a `case` expression on a boolean.
+///
+/// If the preferred one is not applicable, the other one is offered instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanCaseStyle {
+    /// `Expr andalso A`, `Expr orelse B`, `not Expr` or `Expr`
+    /// (applicable if either branch is `true` or `false`).
+    ShortCircuit,
+
+    /// `if Expr -> A; true -> B end` (applicable if `Expr` is a valid guard expression).
+    If,
+}
+
+/// `[function_size]` section of `LintConfig`.
+///
+/// A threshold of `0` disables the corresponding check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSizeConfig {
+    /// The maximum number of lines of a function (default: `100`).
+    pub max_lines: usize,
+
+    /// The maximum number of clauses of a function (default: `20`).
+    pub max_clauses: usize,
+
+    /// The maximum nesting depth of expressions in a function (default: `4`).
+    ///
+    /// See `analysis::FunctionMetrics::max_nesting` for the counted expressions.
+    pub max_nesting: usize,
+}
+impl Default for FunctionSizeConfig {
+    fn default() -> Self {
+        FunctionSizeConfig {
+            max_lines: 100,
+            max_clauses: 20,
+            max_nesting: 4,
+        }
+    }
+}
+
+/// `[missing_spec]` section of `LintConfig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingSpecConfig {
+    /// Reports exported functions which have no `-spec` (default: `true`).
+    pub enabled: bool,
+}
+impl Default for MissingSpecConfig {
+    fn default() -> Self {
+        MissingSpecConfig { enabled: true }
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Bool(bool),
+    Integer(usize),
+    String(String),
+    Array(Vec<String>),
+}
+impl Value {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        if let Ok(n) = s.parse() {
+            Ok(Value::Integer(n))
+        } else if s.starts_with('"') {
+            track!(parse_string(s)).map(Value::String)
+        } else if s.starts_with('[') && s.ends_with(']') {
+            let items = s[1..s.len() - 1]
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| track!(parse_string(item)))
+                .collect::<Result<_>>()?;
+            Ok(Value::Array(items))
+        } else {
+            track_panic!(ErrorKind::InvalidInput, "Unsupported value: {:?}", s)
+        }
+    }
+    fn into_bool(self) -> Result<bool> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            _ => track_panic!(ErrorKind::InvalidInput, "Not a boolean: {:?}", self),
+        }
+    }
+    fn into_integer(self) -> Result<usize> {
+        match self {
+            Value::Integer(n) => Ok(n),
+            _ => track_panic!(ErrorKind::InvalidInput, "Not an integer: {:?}", self),
+        }
+    }
+    fn into_string(self) -> Result<String> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => track_panic!(ErrorKind::InvalidInput, "Not a string: {:?}", self),
+        }
+    }
+    fn into_string_array(self) -> Result<Vec<String>> {
+        match self {
+            Value::Array(a) => Ok(a),
+            _ => track_panic!(ErrorKind::InvalidInput, "Not an array: {:?}", self),
+        }
+    }
+}
+
+/// Parses a double-quoted string without escape sequences.
+fn parse_string(s: &str) -> Result<String> {
+    track_assert!(
+        s.len() >= 2 && s.starts_with('"') && s.ends_with('"') && !s[1..s.len() - 1].contains('"'),
+        ErrorKind::InvalidInput,
+        "Not a string: {:?}",
+        s
+    );
+    Ok(s[1..s.len() - 1].to_owned())
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
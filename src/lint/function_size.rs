@@ -0,0 +1,33 @@
+use super::FunctionSizeConfig;
+use crate::analysis::{function_metrics, FunctionMetrics};
+use crate::cst::ModuleDecl;
+use crate::messages::Message;
+use crate::Diagnostic;
+
+/// Reports functions exceeding the thresholds configured by `config`.
+///
+/// The diagnostics point at the function name in the head of the first clause.
+pub fn check_function_size(module: &ModuleDecl, config: &FunctionSizeConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for m in function_metrics(module) {
+        let checks = [
+            ("long_function", m.lines, config.max_lines),
+            ("too_many_clauses", m.clauses, config.max_clauses),
+            ("deep_nesting", m.max_nesting, config.max_nesting),
+        ];
+        for &(code, count, max) in &checks {
+            if max != 0 && count > max {
+                diagnostics.push(diagnostic(code, &m, count, max));
+            }
+        }
+    }
+    diagnostics
+}
+
+fn diagnostic(code: &'static str, m: &FunctionMetrics, count: usize, max: usize) -> Diagnostic {
+    let message = Message::new(code)
+        .arg("name", format!("{}/{}", m.name, m.arity))
+        .arg("count", count.to_string())
+        .arg("max", max.to_string());
+    Diagnostic::warning(code, message, &m.head)
+}
@@ -0,0 +1,63 @@
+use erl_tokenize::PositionRange;
+use num::ToPrimitive;
+use std::collections::HashSet;
+
+use super::MissingSpecConfig;
+use crate::cst::{Form, ModuleDecl};
+use crate::messages::Message;
+use crate::{Diagnostic, Fix, Span, TextEdit};
+
+/// Reports exported functions which have no `-spec`.
+///
+/// The fix-it inserts a `-spec` skeleton whose parameters and return type are `term()`
+/// (e.g., `-spec foo(term(), term()) -> term().`) above the function.
+/// Functions exported by `-compile(export_all)` are not reported.
+pub fn check_missing_specs(module: &ModuleDecl, config: &MissingSpecConfig) -> Vec<Diagnostic> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let mut exports = HashSet::new();
+    let mut specs = HashSet::new();
+    for form in &module.forms {
+        match *form {
+            Form::ExportAttr(ref e) => {
+                for export in e.exports.elements.iter().flat_map(|s| s.iter()) {
+                    let arity = export.arity.value().to_usize().unwrap_or(usize::MAX);
+                    exports.insert((export.name.value(), arity));
+                }
+            }
+            Form::FunSpec(ref s) if s.module.is_none() => {
+                specs.insert((s.fun_name.value(), s.arity()));
+            }
+            _ => {}
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for f in module.fun_decls() {
+        let key = (f.name(), f.arity());
+        if !exports.contains(&key) || specs.contains(&key) {
+            continue;
+        }
+        let name = &f.clauses.item.name;
+        let skeleton = format!(
+            "-spec {}({}) -> term().\n",
+            name.text(),
+            vec!["term()"; f.arity()].join(", ")
+        );
+        let start = f.start_position();
+        let insertion = Span {
+            start: start.clone(),
+            end: start,
+        };
+        let message =
+            Message::new("missing_spec").arg("name", format!("{}/{}", f.name(), f.arity()));
+        diagnostics.push(
+            Diagnostic::warning("missing_spec", message, name).with_fix(Fix {
+                title: Message::new("add_spec_skeleton"),
+                edits: vec![TextEdit::replace(&insertion, skeleton)],
+            }),
+        );
+    }
+    diagnostics
+}
@@ -0,0 +1,32 @@
+//! Configurable style checks.
+//!
+//! Lints are configured by a `.erlparse.toml` file (see `LintConfig`).
+pub use self::boolean_case::check_boolean_case;
+pub use self::config::{
+    BooleanCaseConfig, BooleanCaseStyle, FunctionSizeConfig, LintConfig, MissingSpecConfig,
+    NamingConfig,
+};
+pub use self::function_size::check_function_size;
+pub use self::missing_spec::check_missing_specs;
+pub use self::naming::check_naming;
+
+mod boolean_case;
+mod config;
+mod function_size;
+mod missing_spec;
+mod naming;
+
+use crate::cst::ModuleDecl;
+use crate::{Diagnostic, Result};
+
+/// Runs all the lints configured by `config` against `module` (parsed from `text`).
+///
+/// The diagnostics are sorted by their positions.
+pub fn check_all(text: &str, module: &ModuleDecl, config: &LintConfig) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = track!(check_naming(text, &config.naming))?;
+    diagnostics.extend(check_boolean_case(module, &config.boolean_case));
+    diagnostics.extend(check_function_size(module, &config.function_size));
+    diagnostics.extend(check_missing_specs(module, &config.missing_spec));
+    diagnostics.sort_by_key(|d| d.start_position.offset());
+    Ok(diagnostics)
+}
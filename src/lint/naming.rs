@@ -0,0 +1,164 @@
+use erl_tokenize::tokens::VariableToken;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken, PositionRange};
+use std::collections::HashSet;
+
+use super::NamingConfig;
+use crate::messages::Message;
+use crate::traits::{is_form_end_in, is_symbol};
+use crate::{Diagnostic, Error, Fix, Result, TextEdit};
+
+/// Checks the naming conventions configured by `config`.
+///
+/// Like `analysis::scan_dependencies`, this works on the token level without preprocessing,
+/// so the fix-its edit `text` itself.
+///
+/// Macro names (e.g., `?MAX_SIZE`) are not regarded as variables.
+///
+/// Only `variable_name_case` diagnostics have fix-its,
+/// because renaming a variable affects nothing but the form in which it appears
+/// (a fix-it is omitted if the new name is already used in the form).
+/// Renaming functions or modules may break other code, so it is left to the user.
+pub fn check_naming(text: &str, config: &NamingConfig) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut form = Vec::new();
+    for token in Lexer::new(text) {
+        let token = track!(token.map_err(Error::from))?;
+        let is_end = is_form_end_in(text, &token);
+        form.push(token);
+        if is_end {
+            check_form(&form, config, &mut diagnostics);
+            form.clear();
+        }
+    }
+    check_form(&form, config, &mut diagnostics);
+    Ok(diagnostics)
+}
+
+fn check_form(form: &[LexicalToken], config: &NamingConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let is_call_head = form.get(1).is_some_and(|t| is_symbol(t, Symbol::OpenParen));
+    if form.first().is_some_and(|t| is_symbol(t, Symbol::Hyphen)) {
+        let is_module_attr = form[1..]
+            .first()
+            .and_then(LexicalToken::as_atom_token)
+            .map(|t| t.value())
+            == Some("module");
+        if is_module_attr {
+            let name = form[2..].iter().find_map(LexicalToken::as_atom_token);
+            if let Some(name) = name {
+                check_module_name(name.value(), name, config, diagnostics);
+            }
+        }
+    } else if let Some(name) = form.first().and_then(LexicalToken::as_atom_token) {
+        if config.snake_case_functions && is_call_head && !is_snake_case(name.value()) {
+            let message = Message::new("function_name_case").arg("name", name.text());
+            diagnostics.push(Diagnostic::warning("function_name_case", message, name));
+        }
+    }
+    check_variables(form, config, diagnostics);
+}
+
+fn check_module_name<R: PositionRange>(
+    name: &str,
+    range: &R,
+    config: &NamingConfig,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let prefixes = &config.module_prefixes;
+    if prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p.as_str())) {
+        return;
+    }
+    let message = Message::new("module_prefix")
+        .arg("name", name)
+        .arg("prefixes", prefixes.join(", "));
+    diagnostics.push(Diagnostic::warning("module_prefix", message, range));
+}
+
+fn check_variables(
+    form: &[LexicalToken],
+    config: &NamingConfig,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let is_macro_directive = form.first().is_some_and(|t| is_symbol(t, Symbol::Hyphen))
+        && form
+            .get(1)
+            .and_then(LexicalToken::as_atom_token)
+            .is_some_and(|t| ["define", "undef", "ifdef", "ifndef"].contains(&t.value()));
+    let mut variables: Vec<(&str, Vec<&VariableToken>)> = Vec::new();
+    for (i, token) in form.iter().enumerate() {
+        let token = match token.as_variable_token() {
+            Some(token) => token,
+            None => continue,
+        };
+        let is_macro_name = (is_macro_directive && i == 3)
+            || (i > 0
+                && (is_symbol(&form[i - 1], Symbol::Question)
+                    || is_symbol(&form[i - 1], Symbol::DoubleQuestion)));
+        if is_macro_name {
+            continue;
+        }
+        match variables.iter_mut().find(|v| v.0 == token.value()) {
+            Some(v) => v.1.push(token),
+            None => variables.push((token.value(), vec![token])),
+        }
+    }
+    let names = variables.iter().map(|v| v.0).collect::<HashSet<_>>();
+    for &(name, ref occurrences) in &variables {
+        let stem = name.trim_start_matches('_');
+        let underscores = &name[..name.len() - stem.len()];
+        if stem.is_empty() {
+            continue;
+        }
+        if config.camel_case_variables && !is_camel_case(stem) {
+            let message = Message::new("variable_name_case").arg("name", name);
+            let mut diagnostic = Diagnostic::warning("variable_name_case", message, occurrences[0]);
+            let new_name = format!("{}{}", underscores, to_camel_case(stem));
+            if is_camel_case(&new_name[underscores.len()..]) && !names.contains(new_name.as_str()) {
+                diagnostic = diagnostic.with_fix(Fix {
+                    title: Message::new("rename_variable")
+                        .arg("name", name)
+                        .arg("new_name", new_name.as_str()),
+                    edits: occurrences
+                        .iter()
+                        .map(|t| TextEdit::replace(*t, new_name.clone()))
+                        .collect(),
+                });
+            }
+            diagnostics.push(diagnostic);
+        } else if underscores.is_empty() && stem.chars().count() < config.min_variable_length {
+            let message = Message::new("short_variable_name")
+                .arg("name", name)
+                .arg("length", config.min_variable_length.to_string());
+            diagnostics.push(Diagnostic::warning(
+                "short_variable_name",
+                message,
+                occurrences[0],
+            ));
+        }
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_camel_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
@@ -0,0 +1,416 @@
+//! Message catalog for localizing diagnostics.
+//!
+//! Every message of a `Diagnostic` (including related information and fix titles)
+//! is a `Message`: an identifier plus named arguments.
+//! The English text is produced by the `English` catalog;
+//! embedders can provide translations by implementing `MessageCatalog`
+//! (a `HashMap<String, String>` from identifiers to templates already does),
+//! and `write_catalog` exports the default templates as a starting point.
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::messages::{English, Message};
+//! use std::collections::HashMap;
+//!
+//! let message = Message::new("duplicate_function").arg("name", "foo/1");
+//! assert_eq!(message.to_string(), "function foo/1 is already defined");
+//!
+//! let mut ja = HashMap::new();
+//! ja.insert("duplicate_function".to_owned(), "関数 {name} は定義済みです".to_owned());
+//! assert_eq!(message.format(&ja), "関数 foo/1 は定義済みです");
+//! assert_eq!(message.format(&English), "function foo/1 is already defined");
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+
+use crate::{Error, Result};
+
+/// Localizable message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub id: &'static str,
+    pub args: Vec<(&'static str, MessageArg)>,
+}
+impl Message {
+    pub fn new(id: &'static str) -> Self {
+        Message {
+            id,
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds the argument which replaces `{name}` in the template.
+    pub fn arg<V: Into<MessageArg>>(mut self, name: &'static str, value: V) -> Self {
+        self.args.push((name, value.into()));
+        self
+    }
+
+    /// Formats this message with the template in `catalog`.
+    ///
+    /// If `catalog` does not have the template, the English one is used.
+    /// Nested messages are formatted with `catalog` as well.
+    pub fn format<C: MessageCatalog + ?Sized>(&self, catalog: &C) -> String {
+        let template = catalog
+            .template(self.id)
+            .or_else(|| English.template(self.id))
+            .unwrap_or(self.id);
+        let mut result = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let arg = rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+                self.args.iter().find(|a| a.0 == name).map(|a| (end, &a.1))
+            });
+            match arg {
+                Some((end, MessageArg::Text(text))) => {
+                    result.push_str(text);
+                    rest = &rest[end + 1..];
+                }
+                Some((end, MessageArg::Message(message))) => {
+                    result.push_str(&message.format(catalog));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(&English))
+    }
+}
+
+/// Argument of a `Message`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageArg {
+    /// Text inserted as is (e.g., a name in source code).
+    Text(String),
+
+    /// Message which is also localized.
+    Message(Message),
+}
+impl From<String> for MessageArg {
+    fn from(f: String) -> Self {
+        MessageArg::Text(f)
+    }
+}
+impl<'a> From<&'a str> for MessageArg {
+    fn from(f: &'a str) -> Self {
+        MessageArg::Text(f.to_owned())
+    }
+}
+impl From<Message> for MessageArg {
+    fn from(f: Message) -> Self {
+        MessageArg::Message(f)
+    }
+}
+
+/// Source of message templates.
+///
+/// A template is a text in which `{name}` is replaced by the argument `name` of a `Message`.
+pub trait MessageCatalog {
+    /// Returns the template of the message identified by `id`, or `None` if unknown.
+    fn template(&self, id: &str) -> Option<&str>;
+}
+impl MessageCatalog for HashMap<String, String> {
+    fn template(&self, id: &str) -> Option<&str> {
+        self.get(id).map(String::as_str)
+    }
+}
+
+/// The default (English) catalog.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct English;
+impl MessageCatalog for English {
+    fn template(&self, id: &str) -> Option<&str> {
+        TEMPLATES.iter().find(|t| t.id == id).map(|t| t.template)
+    }
+}
+
+/// Default template of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageTemplate {
+    pub id: &'static str,
+    pub template: &'static str,
+    pub params: &'static [&'static str],
+}
+
+/// All messages used by this crate, with their English templates.
+pub const TEMPLATES: &[MessageTemplate] = &[
+    // Syntax errors
+    template("unexpected_token", "unexpected token `{token}`", &["token"]),
+    template("unexpected_eos", "unexpected end of input", &[]),
+    template("syntax_error", "syntax error", &[]),
+    template("limit_exceeded", "the input exceeds the parser limits", &[]),
+    template("tokenize_error", "{message}", &["message"]),
+    template("preprocess_error", "{message}", &["message"]),
+    template(
+        "macro_args_mismatched",
+        "macro `{name}` is defined with {params}, but invoked with {args}",
+        &["name", "params", "args"],
+    ),
+    template("macro_arity", "{count} argument(s)", &["count"]),
+    template("macro_without_args", "no argument list", &[]),
+    template(
+        "macro_definition",
+        "macro `{name}` is defined here",
+        &["name"],
+    ),
+    template(
+        "head_mismatch",
+        "head mismatch: previous function {previous} is distinct from {name}. Is the semicolon in {previous} unwanted?",
+        &["previous", "name"],
+    ),
+    template("previous_head", "previous clause of {name}", &["name"]),
+    template(
+        "illegal_guard_local_call",
+        "call to local/imported function {name} is illegal in guard",
+        &["name"],
+    ),
+    template(
+        "illegal_guard_remote_call",
+        "call to remote function {name} is illegal in guard",
+        &["name"],
+    ),
+    template(
+        "illegal_guard_operator",
+        "operator `{op}` is illegal in guard",
+        &["op"],
+    ),
+    template(
+        "unsupported_feature",
+        "{feature} require {release} or later",
+        &["feature", "release"],
+    ),
+    template(
+        "feature_not_enabled",
+        "{feature} require `-feature({name}, enable).`",
+        &["feature", "name"],
+    ),
+    template(
+        "error_in_context",
+        "{error} while parsing {context}",
+        &["error", "context"],
+    ),
+    template("nested_context", "{inner}, in {outer}", &["inner", "outer"]),
+    template(
+        "context_position",
+        "{context} starting at {line}:{column}",
+        &["context", "line", "column"],
+    ),
+    template("related_context", "while parsing {context}", &["context"]),
+    template("expected_tokens", "expected one of {tokens}", &["tokens"]),
+    // Parse contexts
+    template(
+        "argument_context",
+        "the {ordinal} argument",
+        &["ordinal", "index"],
+    ),
+    template("call_context", "the call", &[]),
+    template(
+        "function_context",
+        "function `{name}/{arity}`",
+        &["name", "arity"],
+    ),
+    template("attribute_context", "the `-{name}` attribute", &["name"]),
+    template("wild_attribute_context", "the attribute", &[]),
+    template("directive_context", "the preprocessor directive", &[]),
+    template("type_decl_context", "the type declaration", &[]),
+    template(
+        "expression_context",
+        "the `{keyword}` expression",
+        &["keyword"],
+    ),
+    template("list_comprehension_context", "the list comprehension", &[]),
+    template(
+        "binary_comprehension_context",
+        "the binary comprehension",
+        &[],
+    ),
+    template("map_comprehension_context", "the map comprehension", &[]),
+    template("block_context", "the `begin` block", &[]),
+    // Duplicates
+    template(
+        "duplicate_function",
+        "function {name} is already defined",
+        &["name"],
+    ),
+    template(
+        "duplicate_record",
+        "record {name} is already defined",
+        &["name"],
+    ),
+    template(
+        "duplicate_type",
+        "type {name} is already defined",
+        &["name"],
+    ),
+    template(
+        "duplicate_export",
+        "export entry {name} is already defined",
+        &["name"],
+    ),
+    template(
+        "duplicate_macro",
+        "macro {name} is already defined",
+        &["name"],
+    ),
+    template("first_definition", "first defined here", &[]),
+    template("duplicate_map_key", "map key {name} is repeated", &["name"]),
+    template(
+        "duplicate_record_field",
+        "record field {name} is repeated",
+        &["name"],
+    ),
+    template("first_occurrence", "first occurrence here", &[]),
+    // Patterns
+    template("illegal_pattern", "illegal pattern", &[]),
+    template("illegal_map_key", "illegal map key in pattern", &[]),
+    template("unbound_underscore", "variable '_' is unbound", &[]),
+    // Specs
+    template(
+        "spec_mismatch",
+        "this clause never matches the spec of {name}: `{pattern}` is not of type `{type}`",
+        &["name", "pattern", "type"],
+    ),
+    template("declared_type", "the type declared in the spec", &[]),
+    // Record defaults
+    template(
+        "record_default_call",
+        "default value of {field} calls {function}, which is not a compile-time constant",
+        &["field", "function"],
+    ),
+    template(
+        "record_default_not_constant",
+        "default value of {field} is not a compile-time constant: `{expr}`",
+        &["field", "expr"],
+    ),
+    template(
+        "record_default_exception",
+        "evaluation of the default value of {field} will fail with a '{reason}' exception",
+        &["field", "reason"],
+    ),
+    // Variables
+    template("unused_var", "variable '{name}' is unused", &["name"]),
+    template("unbound_var", "variable '{name}' is unbound", &["name"]),
+    template(
+        "unsafe_var",
+        "variable '{name}' unsafe in '{construct}' (line {line}, column {column})",
+        &["name", "construct", "line", "column"],
+    ),
+    // Naming
+    template(
+        "function_name_case",
+        "function name {name} is not snake_case",
+        &["name"],
+    ),
+    template(
+        "module_prefix",
+        "module name {name} does not start with any of the allowed prefixes: {prefixes}",
+        &["name", "prefixes"],
+    ),
+    template(
+        "variable_name_case",
+        "variable {name} is not CamelCase",
+        &["name"],
+    ),
+    template(
+        "short_variable_name",
+        "variable {name} is shorter than {length} characters",
+        &["name", "length"],
+    ),
+    template(
+        "rename_variable",
+        "rename {name} to {new_name}",
+        &["name", "new_name"],
+    ),
+    // Boolean case
+    template("boolean_case", "`case` on a boolean can be simplified", &[]),
+    template("rewrite_with", "rewrite with `{construct}`", &["construct"]),
+    template("remove_case", "remove the `case` expression", &[]),
+    // Function size
+    template(
+        "long_function",
+        "function {name} has {count} lines (max: {max})",
+        &["name", "count", "max"],
+    ),
+    template(
+        "too_many_clauses",
+        "function {name} has {count} clauses (max: {max})",
+        &["name", "count", "max"],
+    ),
+    template(
+        "deep_nesting",
+        "function {name} has expressions nested {count} levels deep (max: {max})",
+        &["name", "count", "max"],
+    ),
+    // Missing specs
+    template(
+        "missing_spec",
+        "exported function {name} has no spec",
+        &["name"],
+    ),
+    template("add_spec_skeleton", "add a `-spec` skeleton", &[]),
+];
+
+const fn template(
+    id: &'static str,
+    template: &'static str,
+    params: &'static [&'static str],
+) -> MessageTemplate {
+    MessageTemplate {
+        id,
+        template,
+        params,
+    }
+}
+
+/// Writes `TEMPLATES` as a JSON object which maps each identifier to its template and parameters.
+///
+/// For example, `{"syntax_error": {"template": "syntax error", "params": []}, ...}`.
+pub fn write_catalog<W: Write>(mut writer: W) -> Result<()> {
+    let mut out = "{\n".to_owned();
+    for (i, t) in TEMPLATES.iter().enumerate() {
+        let params = t
+            .params
+            .iter()
+            .map(|p| json_string(p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out += &format!(
+            "  {}: {{\"template\": {}, \"params\": [{}]}}{}\n",
+            json_string(t.id),
+            json_string(t.template),
+            params,
+            if i + 1 < TEMPLATES.len() { "," } else { "" }
+        );
+    }
+    out += "}\n";
+    track!(writer.write_all(out.as_bytes()).map_err(Error::from))?;
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = "\"".to_owned();
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
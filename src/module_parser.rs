@@ -0,0 +1,82 @@
+use crate::cst::Form;
+use crate::traits::TokenRead;
+use crate::{Error, Parser, Result};
+
+/// Iterator which parses a module form by form.
+///
+/// Unlike parsing a whole `ModuleDecl`, this does not hold the CST of the module in memory,
+/// and the buffers of the parser are reset between forms.
+///
+/// A form which cannot be parsed yields its error and is skipped up to its terminating `.`
+/// (as in `Parser::parse_module_resilient`), so the iteration can continue with the next form.
+/// A form which seems to use unsupported syntax is yielded as `Form::Unparsed` instead of the error.
+/// After an error raised while reading tokens (e.g., a tokenize error), the iteration ends.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::Form;
+/// use erl_parse::{ModuleParser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-module(foo).\nf() -> ok.\ng( -> ok.\nh() -> ok.";
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+/// let forms = ModuleParser::new(reader).collect::<Vec<_>>();
+/// assert_eq!(forms.len(), 4);
+/// assert!(matches!(forms[1], Ok(Form::FunDecl(_))));
+/// assert!(forms[2].is_err());
+/// assert!(matches!(forms[3], Ok(Form::FunDecl(_))));
+/// ```
+#[derive(Debug)]
+pub struct ModuleParser<T> {
+    parser: Parser<T>,
+    finished: bool,
+}
+impl<T: TokenRead> ModuleParser<T> {
+    pub fn new(reader: T) -> Self {
+        Self::from_parser(Parser::new(reader))
+    }
+    pub fn from_parser(parser: Parser<T>) -> Self {
+        ModuleParser {
+            parser,
+            finished: false,
+        }
+    }
+
+    /// Returns the underlying parser.
+    ///
+    /// Until the next form is parsed, `Parser::error_diagnostic` can be used
+    /// to describe the error yielded last.
+    pub fn parser(&self) -> &Parser<T> {
+        &self.parser
+    }
+    pub fn parser_mut(&mut self) -> &mut Parser<T> {
+        &mut self.parser
+    }
+    pub fn into_parser(self) -> Parser<T> {
+        self.parser
+    }
+}
+impl<T: TokenRead> Iterator for ModuleParser<T> {
+    type Item = Result<Form>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        self.parser.reset_buffers();
+        let mut errors: Vec<Error> = Vec::new();
+        match self.parser.parse_form_resilient(&mut errors) {
+            Some(Form::Error(_)) => Some(Err(errors.swap_remove(0))),
+            Some(form) => Some(Ok(form)),
+            None => {
+                self.finished = true;
+                if errors.is_empty() {
+                    None
+                } else {
+                    Some(Err(errors.swap_remove(0)))
+                }
+            }
+        }
+    }
+}
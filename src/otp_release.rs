@@ -0,0 +1,102 @@
+use std::fmt;
+
+/// OTP release targeted by source code, which determines the syntax accepted by the parser
+/// (see `ParserBuilder::otp_release`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OtpRelease {
+    Otp21,
+    Otp22,
+    Otp23,
+    Otp24,
+    Otp25,
+    Otp26,
+    Otp27,
+    Otp28,
+}
+impl OtpRelease {
+    /// The latest release known to this crate.
+    pub const LATEST: OtpRelease = OtpRelease::Otp28;
+
+    /// Returns the release of the major version `number` (e.g., `26`).
+    pub fn from_number(number: u32) -> Option<Self> {
+        Some(match number {
+            21 => OtpRelease::Otp21,
+            22 => OtpRelease::Otp22,
+            23 => OtpRelease::Otp23,
+            24 => OtpRelease::Otp24,
+            25 => OtpRelease::Otp25,
+            26 => OtpRelease::Otp26,
+            27 => OtpRelease::Otp27,
+            28 => OtpRelease::Otp28,
+            _ => return None,
+        })
+    }
+
+    /// Returns the major version number (e.g., `26`).
+    pub fn number(self) -> u32 {
+        21 + self as u32
+    }
+}
+impl fmt::Display for OtpRelease {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OTP {}", self.number())
+    }
+}
+
+/// Syntax introduced after OTP 21.
+///
+/// Sigils (OTP 27) are not listed since `erl_tokenize` rejects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SyntaxFeature {
+    /// `maybe ... end` (OTP 25 and 26 also require `-feature(maybe_expr, enable).`)
+    MaybeExpr,
+
+    /// `#{K => V || ...}` and `K := V <- Map` generators
+    MapComprehension,
+
+    /// `"""..."""`
+    TripleQuotedString,
+
+    /// `<:-` and `<:=`
+    StrictGenerator,
+}
+impl SyntaxFeature {
+    /// Returns the first release which accepts this syntax.
+    pub fn required_release(self) -> OtpRelease {
+        match self {
+            SyntaxFeature::MaybeExpr => OtpRelease::Otp25,
+            SyntaxFeature::MapComprehension => OtpRelease::Otp26,
+            SyntaxFeature::TripleQuotedString => OtpRelease::Otp27,
+            SyntaxFeature::StrictGenerator => OtpRelease::Otp28,
+        }
+    }
+
+    /// Returns the name of the feature which `release` requires to be enabled
+    /// by `-feature(Name, enable).` for this syntax (`None` if no attribute is required).
+    pub fn feature_attr(self, release: OtpRelease) -> Option<&'static str> {
+        match self {
+            SyntaxFeature::MaybeExpr
+                if matches!(release, OtpRelease::Otp25 | OtpRelease::Otp26) =>
+            {
+                Some("maybe_expr")
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SyntaxFeature::MaybeExpr => "maybe expressions",
+            SyntaxFeature::MapComprehension => "map comprehensions",
+            SyntaxFeature::TripleQuotedString => "triple-quoted strings",
+            SyntaxFeature::StrictGenerator => "strict generators",
+        }
+    }
+}
+impl fmt::Display for SyntaxFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
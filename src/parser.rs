@@ -1,16 +1,86 @@
-use erl_tokenize::LexicalToken;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
+use crate::cst::forms::{ErrorForm, UnparsedForm};
+use crate::cst::{Form, ModuleDecl};
+use crate::extension::Extensions;
+use crate::messages::Message;
+use crate::stack;
 use crate::traits::{Expect, Parse, ParseTail, TokenRead};
-use crate::{Error, ErrorKind, Result};
+use crate::{Diagnostic, Error, ErrorKind, IllegalGuardExpr, Limits, OtpRelease, Result};
 
 #[derive(Debug)]
 pub struct Parser<T> {
     reader: T,
-    // TODO: optimize
-    // (単一のバッファとトランザクション開始位置配列、に分離)
-    transactions: Vec<Vec<LexicalToken>>,
+
+    /// Tokens read in the outermost transaction (empty if there is no transaction).
+    buffer: Vec<LexicalToken>,
+
+    /// The index of the next token in `buffer`.
+    cursor: usize,
+
+    /// The values of `cursor` at the start of the ongoing transactions (outermost first).
+    checkpoints: Vec<usize>,
+
+    /// The number of tokens consumed before `buffer[0]`.
+    offset: usize,
+
+    /// Results of productions keyed by their start token index (`None` if memoization is disabled).
+    memo: Option<HashMap<(usize, TypeId), MemoEntry>>,
+
+    /// The token index at which the last failed `shortcut` stopped.
+    shortcut_failure: Option<usize>,
+
+    /// The token indices at which the patterns (parsed by `Pattern::parse_non_left_recor`)
+    /// not followed by `=` start (see `is_match_lhs`).
+    non_match_lhs: HashSet<usize>,
+
+    limits: Limits,
+    strict: bool,
+    no_preprocess: bool,
+    recover: bool,
+    otp_release: Option<OtpRelease>,
+    extensions: Extensions,
+
+    /// The features enabled by the `-feature(Name, enable).` attributes parsed so far.
+    features: HashSet<String>,
+
+    /// The nesting depth of the productions being parsed (see `nested`).
+    depth: usize,
+
+    /// The token index at which the latest form starts, and the number of forms started so far.
+    form_start: usize,
+    forms: usize,
     last_read_error: Option<Error>,
+
+    /// Macros defined (`Some`) or undefined (`None`) through `define_macro` and `undef_macro`.
+    macro_log: Vec<(String, Option<Vec<LexicalToken>>)>,
+
+    contexts: Vec<ParseContext>,
+
+    /// The furthest token read so far, the contexts in which it was read, and its token index.
+    furthest: Option<(LexicalToken, Vec<ParseContext>, usize)>,
+
+    /// The diagnostics sink (see `diagnostics`).
+    diagnostics: Vec<Diagnostic>,
+
+    /// The furthest token index at which `expect` (or `expect_any`) failed,
+    /// and the tokens expected there.
+    expected: Option<(usize, Vec<String>)>,
 }
+// A parser can be moved to a worker thread (if its reader can)
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<
+        Parser<
+            crate::TokenReader<erl_pp::Preprocessor<erl_tokenize::Lexer<String>>, erl_pp::Error>,
+        >,
+    >();
+};
+
 impl<T> Parser<T>
 where
     T: TokenRead,
@@ -18,10 +88,110 @@ where
     pub fn new(reader: T) -> Self {
         Parser {
             reader,
-            transactions: Vec::new(),
+            buffer: Vec::new(),
+            cursor: 0,
+            checkpoints: Vec::new(),
+            offset: 0,
+            memo: None,
+            shortcut_failure: None,
+            non_match_lhs: HashSet::new(),
+            limits: Limits::default(),
+            strict: false,
+            no_preprocess: false,
+            recover: false,
+            otp_release: None,
+            extensions: Extensions::default(),
+            features: HashSet::new(),
+            depth: 0,
+            form_start: 0,
+            forms: 0,
             last_read_error: None,
+            macro_log: Vec::new(),
+            contexts: Vec::new(),
+            furthest: None,
+            diagnostics: Vec::new(),
+            expected: None,
         }
     }
+    /// Enables or disables memoization (disabled by default).
+    ///
+    /// If enabled, the results of parsing expressions, patterns, guard tests and types
+    /// are cached by their start positions, so repeated speculative parses of them
+    /// (e.g., the pattern of a match expression which turns out to be a plain expression)
+    /// are answered from the cache.
+    /// This speeds up the parsing of deeply nested terms at the expense of memory.
+    pub fn memo(mut self, enabled: bool) -> Self {
+        self.memo = if enabled { Some(HashMap::new()) } else { None };
+        self
+    }
+
+    /// Sets the limits of the input accepted by this parser (unlimited by default).
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Enables or disables strict mode (disabled by default).
+    ///
+    /// In strict mode, forms are also checked for the errors reported by `erlc`
+    /// which are not syntax errors.
+    /// Currently, it rejects guards containing calls to functions other than the guard BIFs
+    /// (e.g., "call to local/imported function foo/1 is illegal in guard")
+    /// or to the list operators `++` and `--` (see `ErrorKind::IllegalGuardExpr`).
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Enables or disables no-preprocess mode (disabled by default).
+    ///
+    /// This mode is for parsing source code before preprocessing
+    /// (e.g., the tokens of `erl_tokenize::Lexer` without `erl_pp::Preprocessor`),
+    /// which formatters and linters want to see.
+    /// Macro calls in expressions, patterns, guards and types are parsed as `cst::exprs::MacroCall`,
+    /// and preprocessor directives (e.g., `-define(A, 1).` and `-endif.`) as `cst::forms::Directive`.
+    pub fn no_preprocess(mut self, enabled: bool) -> Self {
+        self.no_preprocess = enabled;
+        self
+    }
+
+    pub fn is_no_preprocess(&self) -> bool {
+        self.no_preprocess
+    }
+
+    /// Returns `true` if `parse_module` continues after forms which cannot be parsed.
+    pub fn is_recovering(&self) -> bool {
+        self.recover
+    }
+
+    /// Returns the target OTP release set by `ParserBuilder::otp_release` (`None` means the latest).
+    pub fn otp_release(&self) -> Option<OtpRelease> {
+        self.otp_release
+    }
+
+    /// Returns `true` if the feature `name` (e.g., `maybe_expr`) is enabled
+    /// by a `-feature(Name, enable).` attribute parsed so far.
+    pub fn is_feature_enabled(&self, name: &str) -> bool {
+        self.features.contains(name)
+    }
+
+    pub(crate) fn set_feature(&mut self, name: &str, enabled: bool) {
+        if enabled {
+            self.features.insert(name.to_owned());
+        } else {
+            self.features.remove(name);
+        }
+    }
+
+    /// Returns the syntax extensions registered by `ParserBuilder::extensions`.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
     pub fn parse<P: Parse>(&mut self) -> Result<P> {
         track!(P::parse(self))
     }
@@ -29,28 +199,41 @@ where
         track!(P::parse_tail(self, head))
     }
     pub fn expect<P: Parse + Expect>(&mut self, expected: &P::Value) -> Result<P> {
-        self.transaction(|parser| {
+        let index = self.offset + self.cursor;
+        let result = self.transaction(|parser| {
             let actual = track!(parser.parse::<P>(), "expected={:?}", expected)?;
             track!(actual.expect(expected))?;
             Ok(actual)
-        })
+        });
+        if result.is_err() {
+            self.record_expected(index, P::describe(expected));
+        }
+        result
     }
     pub fn expect_any<P: Parse + Expect>(&mut self, expected: &[&P::Value]) -> Result<P> {
-        let actual = track!(self.parse::<P>())?;
-        let mut last_error = None;
-        for e in expected.iter() {
-            if let Err(e) = track!(actual.expect(e)) {
-                last_error = Some(e);
+        let index = self.offset + self.cursor;
+        let result = self.parse::<P>().and_then(|actual| {
+            let mut last_error = None;
+            for e in expected.iter() {
+                if let Err(e) = track!(actual.expect(e)) {
+                    last_error = Some(e);
+                } else {
+                    last_error = None;
+                    break;
+                }
+            }
+            if let Some(e) = last_error {
+                Err(e)
             } else {
-                last_error = None;
-                break;
+                Ok(actual)
+            }
+        });
+        if result.is_err() {
+            for e in expected.iter() {
+                self.record_expected(index, P::describe(e));
             }
         }
-        if let Some(e) = last_error {
-            Err(e)
-        } else {
-            Ok(actual)
-        }
+        track!(result)
     }
     pub fn peek<F, P>(&mut self, f: F) -> Result<P>
     where
@@ -74,7 +257,532 @@ where
         }
         result
     }
+
+    /// Records that `token` (the source text of a token) was expected at the token index `index`.
+    ///
+    /// Only the expectations at the furthest index are kept, which are reported by `error_diagnostic`.
+    fn record_expected(&mut self, index: usize, token: String) {
+        match self.expected {
+            Some((i, ref mut tokens)) if i == index => {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+            Some((i, _)) if i > index => {}
+            _ => self.expected = Some((index, vec![token])),
+        }
+    }
+
+    /// Executes `f`, or replays its result cached by a previous call at the same position.
+    ///
+    /// `P` identifies the production, so `f` must be the same for the same `P`.
+    pub(crate) fn memoize<F, P>(&mut self, f: F) -> Result<P>
+    where
+        F: FnOnce(&mut Self) -> Result<P>,
+        P: Clone + Send + Sync + 'static,
+    {
+        if self.memo.is_none() {
+            return f(self);
+        }
+        let key = (self.offset + self.cursor, TypeId::of::<P>());
+        let cached = self.memo.as_ref().and_then(|m| m.get(&key)).and_then(|e| {
+            let result = e.result.downcast_ref::<Result<P>>()?;
+            Some((e.end, result.clone()))
+        });
+        if let Some((end, result)) = cached {
+            while self.offset + self.cursor < end && self.next_token().is_ok() {}
+            return result;
+        }
+        let result = f(self);
+        if let Some(ref mut memo) = self.memo {
+            let entry = MemoEntry {
+                end: self.offset + self.cursor,
+                result: Box::new(result.clone()),
+            };
+            memo.insert(key, entry);
+        }
+        result
+    }
+
+    /// Executes `f` as a production nested in the current one, checking `Limits::max_depth`.
+    ///
+    /// If the stack is running low, `f` is executed on a new thread (see `crate::stack`).
+    pub(crate) fn nested<F, P>(&mut self, f: F) -> Result<P>
+    where
+        F: FnOnce(&mut Self) -> Result<P> + Send,
+        P: Send,
+    {
+        track!(self.check_depth(1))?;
+        let outermost = self.depth == 0;
+        self.depth += 1;
+        let result = stack::maybe_grow(outermost, || f(self));
+        self.depth -= 1;
+        track!(result.map_err(Error::from))?
+    }
+
+    /// Checks `Limits::max_depth` for `extra` levels of nesting below the current production.
+    pub(crate) fn check_depth(&mut self, extra: usize) -> Result<()> {
+        if let Some(max) = self.limits.max_depth {
+            if self.depth + extra > max {
+                let e = track!(Error::from(ErrorKind::LimitExceeded), "max_depth={}", max);
+                self.last_read_error = Some(e.clone());
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the start of a form, checking `Limits::max_forms`.
+    pub(crate) fn start_form(&mut self) -> Result<()> {
+        let start = self.offset + self.cursor;
+        if self.forms > 0 && start <= self.form_start {
+            // Retrying the same form
+            self.form_start = start;
+            return Ok(());
+        }
+        self.form_start = start;
+        self.forms += 1;
+        if let Some(max) = self.limits.max_forms {
+            if self.forms > max {
+                let e = track!(Error::from(ErrorKind::LimitExceeded), "max_forms={}", max);
+                self.last_read_error = Some(e.clone());
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tries `f` as a shortcut for a parse which can also be done in the usual way.
+    ///
+    /// If `f` fails, this returns `None` without consuming any tokens,
+    /// and the tokens read by `f` are not regarded as read in terms of error reporting.
+    /// Shortcuts starting before the point at which the last one failed are skipped,
+    /// since they would likely fail at the same point (e.g., in nested tuples containing a function call).
+    pub(crate) fn shortcut<F, P>(&mut self, f: F) -> Option<P>
+    where
+        F: FnOnce(&mut Self) -> Result<P>,
+    {
+        let start = self.offset + self.cursor;
+        if self.shortcut_failure.is_some_and(|end| start < end) {
+            return None;
+        }
+        let furthest = self.furthest.clone();
+        self.start_transaction();
+        match f(self) {
+            Ok(x) => {
+                self.commit_transaction();
+                Some(x)
+            }
+            Err(_) => {
+                self.shortcut_failure = Some(self.offset + self.cursor);
+                self.abort_transaction();
+                self.furthest = furthest;
+                None
+            }
+        }
+    }
+
+    /// Records whether the pattern which starts at the token index `start` (and has just been parsed)
+    /// is followed by `=`.
+    pub(crate) fn record_match_lhs(&mut self, start: usize) {
+        let is_match_lhs = self.lookahead(|parser| {
+            parser
+                .parse::<LexicalToken>()
+                .is_ok_and(|t| t.as_symbol_token().map(|t| t.value()) == Some(Symbol::Match))
+        });
+        if !is_match_lhs {
+            self.non_match_lhs.insert(start);
+        }
+    }
+
+    /// Returns `false` if a pattern which starts at the next token is known not to be followed by `=`.
+    ///
+    /// This prevents an expression from being parsed as a pattern (to try a match expression)
+    /// again and again, which takes quadratic time for deeply nested expressions.
+    pub(crate) fn is_match_lhs(&self) -> bool {
+        !self.non_match_lhs.contains(&(self.offset + self.cursor))
+    }
+
+    /// Returns the index of the next token.
+    pub(crate) fn token_index(&self) -> usize {
+        self.offset + self.cursor
+    }
+
+    /// Same as `peek` except that the tokens read by `f` are not regarded as read
+    /// in terms of error reporting (see `error_contexts`).
+    ///
+    /// This is intended for scanning tokens ahead to decide how to parse them.
+    pub(crate) fn lookahead<F, P>(&mut self, f: F) -> P
+    where
+        F: FnOnce(&mut Self) -> P,
+    {
+        let furthest = self.furthest.clone();
+        self.start_transaction();
+        let result = f(self);
+        self.abort_transaction();
+        self.furthest = furthest;
+        result
+    }
+
+    /// Executes `f` in the context described by `description` (e.g., "the 2nd argument").
+    ///
+    /// The context starts at the first token read by `f`.
+    /// Contexts are reported by `error_contexts` and `error_diagnostic`.
+    pub fn with_context<F, P>(&mut self, description: Message, f: F) -> Result<P>
+    where
+        F: FnOnce(&mut Self) -> Result<P>,
+    {
+        self.contexts.push(ParseContext {
+            description,
+            start_position: None,
+        });
+        let result = track!(f(self));
+        self.contexts.pop();
+        result
+    }
+
+    /// Same as `with_context` except that the context starts at `start_position`.
+    pub fn with_context_at<F, P>(
+        &mut self,
+        description: Message,
+        start_position: Position,
+        f: F,
+    ) -> Result<P>
+    where
+        F: FnOnce(&mut Self) -> Result<P>,
+    {
+        self.contexts.push(ParseContext {
+            description,
+            start_position: Some(start_position),
+        });
+        let result = track!(f(self));
+        self.contexts.pop();
+        result
+    }
+
+    /// Returns the contexts (innermost first) in which the furthest token read so far was read.
+    ///
+    /// After a failed parse, the furthest token is usually the one which caused the failure.
+    pub fn error_contexts(&self) -> impl Iterator<Item = &ParseContext> {
+        self.furthest.iter().flat_map(|f| f.1.iter().rev())
+    }
+
+    /// Makes a diagnostic describing `error` which was returned by this parser.
+    ///
+    /// The diagnostic is located at the furthest token read
+    /// and its message ends with the contexts in which the token was read, as in
+    /// "unexpected token `3` while parsing the 2nd argument starting at 1:9, in the call starting at 1:5".
+    /// The contexts are also attached as related information.
+    pub fn error_diagnostic(&self, error: &Error) -> Diagnostic {
+        let token = self.furthest.as_ref().map(|f| &f.0);
+        let mut message = match (error.kind(), token) {
+            (ErrorKind::TokenizeError(e), _) => {
+                Message::new("tokenize_error").arg("message", e.as_str())
+            }
+            (ErrorKind::PreprocessorError(e), _) => {
+                Message::new("preprocess_error").arg("message", e.as_str())
+            }
+            (ErrorKind::MacroArgsMismatched(m), _) => Message::new("macro_args_mismatched")
+                .arg("name", m.name.as_str())
+                .arg("params", macro_arity(m.params))
+                .arg("args", macro_arity(m.args)),
+            (ErrorKind::HeadMismatch(m), _) => Message::new("head_mismatch")
+                .arg("previous", m.previous.as_str())
+                .arg("name", m.name.as_str()),
+            (ErrorKind::IllegalGuardExpr(e), _) => match **e {
+                IllegalGuardExpr::Call {
+                    module: None,
+                    ref name,
+                    arity,
+                    ..
+                } => Message::new("illegal_guard_local_call")
+                    .arg("name", format!("{}/{}", name, arity)),
+                IllegalGuardExpr::Call {
+                    module: Some(ref module),
+                    ref name,
+                    arity,
+                    ..
+                } => Message::new("illegal_guard_remote_call")
+                    .arg("name", format!("{}:{}/{}", module, name, arity)),
+                IllegalGuardExpr::Operator { ref op, .. } => {
+                    Message::new("illegal_guard_operator").arg("op", op.as_str())
+                }
+            },
+            (ErrorKind::UnsupportedFeature(f), _) => match f.feature_attr {
+                Some(ref name) => Message::new("feature_not_enabled")
+                    .arg("feature", f.feature.name())
+                    .arg("name", name.clone()),
+                None => Message::new("unsupported_feature")
+                    .arg("feature", f.feature.name())
+                    .arg("release", f.required_release.to_string()),
+            },
+            (ErrorKind::UnexpectedEos, _) => Message::new("unexpected_eos"),
+            (ErrorKind::LimitExceeded, _) => Message::new("limit_exceeded"),
+            (ErrorKind::UnexpectedToken(t), _) | (_, Some(t)) => {
+                Message::new("unexpected_token").arg("token", t.text())
+            }
+            (_, None) => Message::new("syntax_error"),
+        };
+        // The parse contexts are irrelevant to the errors detected after parsing
+        match *error.kind() {
+            ErrorKind::IllegalGuardExpr(ref e) => {
+                return Diagnostic::error("illegal_guard_expr", message, e.span());
+            }
+            ErrorKind::UnsupportedFeature(ref f) => {
+                return Diagnostic::error("unsupported_feature", message, &f.span);
+            }
+            ErrorKind::HeadMismatch(ref m) => {
+                let related = Message::new("previous_head").arg("name", m.previous.as_str());
+                return Diagnostic::error("head_mismatch", message, &m.head)
+                    .with_related(related, &m.previous_head);
+            }
+            _ => {}
+        }
+        let context = self
+            .error_contexts()
+            .map(ParseContext::message)
+            .reduce(|inner, outer| {
+                Message::new("nested_context")
+                    .arg("inner", inner)
+                    .arg("outer", outer)
+            });
+        if let Some(context) = context {
+            message = Message::new("error_in_context")
+                .arg("error", message)
+                .arg("context", context);
+        }
+        let mut diagnostic = match (error.kind(), token) {
+            (ErrorKind::MacroArgsMismatched(m), _) => {
+                Diagnostic::error("macro_args_mismatched", message, &m.call).with_related(
+                    Message::new("macro_definition").arg("name", m.name.as_str()),
+                    &m.definition,
+                )
+            }
+            (ErrorKind::UnexpectedToken(t), _) => Diagnostic::error("syntax_error", message, t),
+            (_, Some(t)) => Diagnostic::error("syntax_error", message, t),
+            (_, None) => Diagnostic::error("syntax_error", message, &Point(Position::new())),
+        };
+        if let (Some(f), Some((index, tokens))) = (self.furthest.as_ref(), self.expected.as_ref()) {
+            if f.2 == *index {
+                diagnostic.expected = tokens.clone();
+            }
+        }
+        for c in self.error_contexts() {
+            if let Some(ref start) = c.start_position {
+                let message = Message::new("related_context").arg("context", c.description.clone());
+                diagnostic = diagnostic.with_related(message, &Point(start.clone()));
+            }
+        }
+        diagnostic
+    }
+
+    /// Saves the current state of this parser.
+    ///
+    /// Tokens read after this call are buffered by the parser,
+    /// so `restore` brings it back to this point without re-tokenizing (or re-preprocessing) anything.
+    /// The macros defined by `define_macro` (e.g., `MODULE` by the `-module` attribute) are restored as well.
+    ///
+    /// Snapshots are nested: restoring or releasing a snapshot invalidates the ones taken after it.
+    /// A snapshot can be restored any number of times until it is released.
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.start_transaction();
+        Snapshot {
+            depth: self.checkpoints.len(),
+            macro_log_len: self.macro_log.len(),
+            last_read_error: self.last_read_error.clone(),
+        }
+    }
+
+    /// Brings this parser back to the state saved by `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        assert!(snapshot.depth <= self.checkpoints.len(), "Invalid snapshot");
+        while self.checkpoints.len() >= snapshot.depth {
+            self.abort_transaction();
+        }
+        self.start_transaction();
+
+        let changed = self
+            .macro_log
+            .drain(snapshot.macro_log_len..)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        for name in changed {
+            let replacement = self
+                .macro_log
+                .iter()
+                .rev()
+                .find(|m| m.0 == name)
+                .map(|m| m.1.clone());
+            match replacement {
+                Some(Some(replacement)) => self.reader.define_macro(&name, replacement),
+                _ => self.reader.undef_macro(&name),
+            }
+        }
+        self.last_read_error = snapshot.last_read_error.clone();
+        self.clear_memo();
+    }
+
+    /// Discards `snapshot`, keeping the current state.
+    pub fn release(&mut self, snapshot: Snapshot) {
+        assert!(snapshot.depth <= self.checkpoints.len(), "Invalid snapshot");
+        while self.checkpoints.len() >= snapshot.depth {
+            self.commit_transaction();
+        }
+    }
+
+    /// Defines the macro `name` in the underlying preprocessor.
+    ///
+    /// Unlike calling `TokenRead::define_macro` directly, the definition is undone by `restore`.
+    pub fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
+        self.macro_log
+            .push((name.to_owned(), Some(replacement.clone())));
+        self.reader.define_macro(name, replacement);
+        self.clear_memo();
+    }
+
+    /// Undefines the macro `name` in the underlying preprocessor.
+    pub fn undef_macro(&mut self, name: &str) {
+        self.macro_log.push((name.to_owned(), None));
+        self.reader.undef_macro(name);
+        self.clear_memo();
+    }
+
+    /// Parses a module, continuing after forms which cannot be parsed.
+    ///
+    /// Each such form is skipped up to its terminating `.` and recorded as `Form::Error`
+    /// (or `Form::Unparsed` if it seems to use unsupported syntax),
+    /// and the error is appended to the returned list.
+    /// Errors raised while reading tokens (e.g., tokenize or preprocess errors) abort the parsing.
+    pub fn parse_module_resilient(&mut self) -> (ModuleDecl, Vec<Error>) {
+        let mut forms = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(form) = self.parse_form_resilient(&mut errors) {
+            forms.push(form);
+        }
+        (ModuleDecl { forms }, errors)
+    }
+
+    /// Parses a module, recovering from errors if this parser is built with `ParserBuilder::recover`.
+    ///
+    /// Without recovery, the parsing stops at the first error,
+    /// and the forms parsed before it are returned with the error.
+    pub fn parse_module(&mut self) -> (ModuleDecl, Vec<Error>) {
+        if self.recover {
+            return self.parse_module_resilient();
+        }
+        let mut forms = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match track!(self.eos()) {
+                Ok(false) => {}
+                Ok(true) => break,
+                Err(e) => {
+                    self.push_error(&mut errors, e);
+                    break;
+                }
+            }
+            match track!(self.parse()) {
+                Ok(form) => forms.push(form),
+                Err(e) => {
+                    self.push_error(&mut errors, e);
+                    break;
+                }
+            }
+        }
+        (ModuleDecl { forms }, errors)
+    }
+
+    /// Returns the diagnostics of the errors raised by `parse_module`, `parse_module_resilient`
+    /// and `ModuleParser` so far, in the same order as the errors.
+    ///
+    /// With recovery enabled (see `ParserBuilder::recover`), these are all the syntax errors in a module,
+    /// as `erlc` reports several errors per run.
+    /// Each diagnostic is made by `error_diagnostic` when the error is raised,
+    /// so it has the parse contexts of the error.
+    /// The diagnostics are kept until `take_diagnostics` is called.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Takes the diagnostics collected so far (see `diagnostics`), emptying the sink.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Appends `error` to `errors` and its diagnostic to the diagnostics sink.
+    fn push_error(&mut self, errors: &mut Vec<Error>, error: Error) {
+        self.diagnostics.push(self.error_diagnostic(&error));
+        errors.push(error);
+    }
+
+    /// Parses the next form in the manner of `parse_module_resilient`.
+    ///
+    /// Returns `None` at the end of the stream or if the parsing cannot be continued.
+    pub(crate) fn parse_form_resilient(&mut self, errors: &mut Vec<Error>) -> Option<Form> {
+        match track!(self.eos()) {
+            Ok(false) => {}
+            Ok(true) => return None,
+            Err(e) => {
+                self.push_error(errors, e);
+                return None;
+            }
+        }
+        let e = match track!(self.transaction(Parser::parse)) {
+            Ok(form) => return Some(form),
+            Err(e) => e,
+        };
+        self.push_error(errors, e);
+        if self.last_read_error.is_some() {
+            return None;
+        }
+
+        // Keeps the furthest token of the failed parse for `error_diagnostic`
+        let furthest = self.furthest.take();
+        let form = track!(self.parse::<ErrorForm>());
+        self.furthest = furthest;
+        match form {
+            Ok(form) => {
+                let failed_before_end = self.furthest.as_ref().is_some_and(|f| {
+                    f.0.start_position().offset()
+                        < form.tokens[form.tokens.len() - 1].start_position().offset()
+                });
+                if failed_before_end && UnparsedForm::is_well_formed(&form.tokens) {
+                    let reason = self
+                        .error_diagnostic(&errors[errors.len() - 1])
+                        .message
+                        .to_string();
+                    Some(Form::Unparsed(UnparsedForm {
+                        tokens: form.tokens,
+                        reason,
+                    }))
+                } else {
+                    Some(Form::Error(form))
+                }
+            }
+            Err(e) => {
+                self.push_error(errors, e);
+                None
+            }
+        }
+    }
+
+    /// Drops the information kept for the last parse (e.g., the furthest token read).
+    ///
+    /// The macro log is also dropped unless there are snapshots in use.
+    pub(crate) fn reset_buffers(&mut self) {
+        self.furthest = None;
+        self.expected = None;
+        self.contexts.clear();
+        self.shortcut_failure = None;
+        if self.checkpoints.is_empty() {
+            self.macro_log.clear();
+        }
+        self.clear_memo();
+    }
     pub fn eos(&mut self) -> Result<bool> {
+        if self.cursor < self.buffer.len() {
+            return Ok(false);
+        }
         if let Some(t) = track!(self.reader.try_read_token())? {
             self.reader.unread_token(t);
             Ok(false)
@@ -87,36 +795,92 @@ where
         if let Some(ref e) = self.last_read_error {
             return Err(e.clone());
         }
-        match self.reader.read_token() {
-            Err(e) => {
-                if let ErrorKind::UnexpectedEos = *e.kind() {
-                } else {
-                    self.last_read_error = Some(e.clone());
-                }
-                Err(e)
+        if let Some(max) = self.limits.max_tokens_per_form {
+            if (self.offset + self.cursor).saturating_sub(self.form_start) >= max {
+                let e = track!(
+                    Error::from(ErrorKind::LimitExceeded),
+                    "max_tokens_per_form={}",
+                    max
+                );
+                self.last_read_error = Some(e.clone());
+                return Err(e);
             }
-            Ok(t) => {
-                if let Some(tail) = self.transactions.last_mut() {
-                    tail.push(t.clone());
+        }
+        let t = if let Some(t) = self.buffer.get(self.cursor) {
+            self.cursor += 1;
+            t.clone()
+        } else {
+            match self.reader.read_token() {
+                Err(e) => {
+                    if let ErrorKind::UnexpectedEos = *e.kind() {
+                    } else {
+                        self.last_read_error = Some(e.clone());
+                    }
+                    return Err(e);
                 }
-                Ok(t)
+                Ok(t) => {
+                    if self.checkpoints.is_empty() {
+                        self.offset += 1;
+                    } else {
+                        self.buffer.push(t.clone());
+                        self.cursor += 1;
+                    }
+                    t
+                }
+            }
+        };
+        for c in self.contexts.iter_mut().rev() {
+            if c.start_position.is_some() {
+                break;
             }
+            c.start_position = Some(t.start_position());
+        }
+        let is_furthest = self
+            .furthest
+            .as_ref()
+            .is_none_or(|f| f.0.start_position().offset() < t.start_position().offset());
+        if is_furthest {
+            let index = self.offset + self.cursor - 1;
+            self.furthest = Some((t.clone(), self.contexts.clone(), index));
         }
+        Ok(t)
     }
     fn start_transaction(&mut self) {
-        self.transactions.push(Vec::new());
+        self.checkpoints.push(self.cursor);
     }
     fn commit_transaction(&mut self) {
-        let last = self.transactions.pop().unwrap();
-        if let Some(tail) = self.transactions.last_mut() {
-            tail.extend(last);
-        }
+        self.checkpoints.pop().unwrap();
+        self.flush_buffer();
     }
     fn abort_transaction(&mut self) {
-        let last = self.transactions.pop().unwrap();
-        for t in last.into_iter().rev() {
+        self.cursor = self.checkpoints.pop().unwrap();
+        self.flush_buffer();
+    }
+
+    /// Returns the unconsumed tokens in the buffer to the reader when the outermost transaction ends.
+    fn flush_buffer(&mut self) {
+        if !self.checkpoints.is_empty() {
+            return;
+        }
+        for t in self.buffer.drain(self.cursor..).rev() {
             self.reader.unread_token(t);
         }
+        self.buffer.clear();
+        self.offset += self.cursor;
+        self.cursor = 0;
+
+        // The positions before `offset` will never be visited again
+        let offset = self.offset;
+        if let Some(ref mut memo) = self.memo {
+            memo.retain(|k, _| k.0 >= offset);
+        }
+        self.non_match_lhs.retain(|&i| i >= offset);
+    }
+    fn clear_memo(&mut self) {
+        if let Some(ref mut memo) = self.memo {
+            memo.clear();
+        }
+        self.non_match_lhs.clear();
     }
 }
 impl<T> Parser<T> {
@@ -130,6 +894,177 @@ impl<T> Parser<T> {
         self.reader
     }
 }
+/// How macros are handled by a parser built by `ParserBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MacroMode {
+    /// Macros are expected to be expanded by the token reader (e.g., `erl_pp::Preprocessor`).
+    #[default]
+    Expand,
+
+    /// Macro calls and preprocessor directives are parsed as they are (see `Parser::no_preprocess`).
+    Preserve,
+}
+
+/// Builder of `Parser`, which configures the parse behavior in one place.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::{Limits, MacroMode, OtpRelease, ParserBuilder};
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-define(A, 1).\nf() -> ?A.\ng( -> ok.\nh() -> ok.";
+/// let mut parser = ParserBuilder::new()
+///     .recover(true)
+///     .otp_release(OtpRelease::Otp26)
+///     .limits(Limits::new().max_depth(100))
+///     .macros(MacroMode::Preserve)
+///     .build(erl_parse::TokenReader::new(Lexer::new(text)));
+/// let (module, errors) = parser.parse_module();
+/// assert_eq!(module.forms.len(), 4);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(parser.otp_release(), Some(OtpRelease::Otp26));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParserBuilder {
+    recover: bool,
+    otp_release: Option<OtpRelease>,
+    limits: Limits,
+    macros: MacroMode,
+    memo: bool,
+    strict: bool,
+    extensions: Extensions,
+}
+impl ParserBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables error recovery of `Parser::parse_module` (disabled by default).
+    ///
+    /// If enabled, forms which cannot be parsed are skipped as `Parser::parse_module_resilient` does.
+    pub fn recover(mut self, enabled: bool) -> Self {
+        self.recover = enabled;
+        self
+    }
+
+    /// Sets the OTP release targeted by the source code (the latest by default).
+    ///
+    /// Forms using syntax newer than the release (e.g., `maybe` expressions before OTP 25)
+    /// are rejected with `ErrorKind::UnsupportedFeature`,
+    /// as well as `maybe` expressions in OTP 25 and 26 unless `-feature(maybe_expr, enable).` precedes them.
+    pub fn otp_release(mut self, release: OtpRelease) -> Self {
+        self.otp_release = Some(release);
+        self
+    }
+
+    /// Sets the limits of the input (see `Parser::limits`).
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how macros are handled (`MacroMode::Expand` by default).
+    pub fn macros(mut self, mode: MacroMode) -> Self {
+        self.macros = mode;
+        self
+    }
+
+    /// Enables or disables memoization (see `Parser::memo`).
+    pub fn memo(mut self, enabled: bool) -> Self {
+        self.memo = enabled;
+        self
+    }
+
+    /// Enables or disables strict mode (see `Parser::strict`).
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Registers syntax extensions for attributes and expressions unknown to this crate
+    /// (see the `extension` module).
+    pub fn extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Makes a parser configured by this builder.
+    pub fn build<T: TokenRead>(&self, reader: T) -> Parser<T> {
+        let mut parser = Parser::new(reader)
+            .memo(self.memo)
+            .limits(self.limits.clone())
+            .strict(self.strict)
+            .no_preprocess(self.macros == MacroMode::Preserve);
+        parser.recover = self.recover;
+        parser.otp_release = self.otp_release;
+        parser.extensions = self.extensions.clone();
+        parser
+    }
+}
+
+/// Syntactic construct being parsed, reported along with parse errors.
+#[derive(Debug, Clone)]
+pub struct ParseContext {
+    pub description: Message,
+
+    /// The start position of the construct (`None` if no token of it has been read).
+    pub start_position: Option<Position>,
+}
+impl ParseContext {
+    /// Makes the message describing this context and its start position.
+    pub fn message(&self) -> Message {
+        match self.start_position {
+            None => self.description.clone(),
+            Some(ref p) => Message::new("context_position")
+                .arg("context", self.description.clone())
+                .arg("line", p.line().to_string())
+                .arg("column", p.column().to_string()),
+        }
+    }
+}
+impl fmt::Display for ParseContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// Empty range at a position.
+struct Point(Position);
+impl PositionRange for Point {
+    fn start_position(&self) -> Position {
+        self.0.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.0.clone()
+    }
+}
+
+/// `{count} argument(s)`, or `no argument list` if `count` is `None`.
+fn macro_arity(count: Option<usize>) -> Message {
+    match count {
+        Some(n) => Message::new("macro_arity").arg("count", n.to_string()),
+        None => Message::new("macro_without_args"),
+    }
+}
+
+#[derive(Debug)]
+struct MemoEntry {
+    /// The token index at which the production ended.
+    end: usize,
+
+    /// `Result<P>` where `P` is the production.
+    result: Box<dyn Any + Send + Sync>,
+}
+
+/// State of a `Parser` saved by `Parser::snapshot`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    depth: usize,
+    macro_log_len: usize,
+    last_read_error: Option<Error>,
+}
+
 impl Parse for LexicalToken {
     fn parse<T>(parser: &mut Parser<T>) -> Result<Self>
     where
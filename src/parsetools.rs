@@ -0,0 +1,277 @@
+//! Parsing of the Erlang code embedded in leex (`.xrl`) and yecc (`.yrl`) files.
+//!
+//! Only the Erlang parts (rule actions and the `Erlang code.` section) are parsed into CSTs.
+//! The other parts are exposed as they are.
+//!
+//! Positions in the resulting CSTs are relative to the beginning of the whole file.
+use erl_pp::Preprocessor;
+use erl_tokenize::tokens::{AtomToken, SymbolToken};
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken, PositionRange, Token, Tokenizer};
+use std::ops::Range;
+
+use crate::cst::exprs::parts::Body;
+use crate::cst::ModuleDecl;
+use crate::traits::is_symbol;
+use crate::{Error, ErrorKind, MaybeMatchTokens, Parser, Result, TokenReader};
+
+type MaskedParser =
+    Parser<TokenReader<Preprocessor<MaybeMatchTokens<Lexer<String>>>, erl_pp::Error>>;
+
+/// Contents of a leex file.
+#[derive(Debug, Clone)]
+pub struct LeexFile {
+    pub definitions: Vec<LeexDefinition>,
+    pub rules: Vec<LeexRule>,
+
+    /// Forms in the `Erlang code.` section.
+    pub code: ModuleDecl,
+}
+
+/// `NAME = VALUE` in the `Definitions.` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeexDefinition {
+    pub name: String,
+    pub value: String,
+    pub line: usize,
+}
+
+/// `REGEX : ACTION.` in the `Rules.` section.
+#[derive(Debug, Clone)]
+pub struct LeexRule {
+    pub regex: String,
+    pub line: usize,
+    pub action: Body,
+}
+
+/// Contents of a yecc file.
+#[derive(Debug, Clone)]
+pub struct YeccFile {
+    pub declarations: Vec<YeccDeclaration>,
+    pub rules: Vec<YeccRule>,
+
+    /// Forms in the `Erlang code.` section.
+    pub code: ModuleDecl,
+}
+
+/// Declaration such as `Nonterminals list elem.` or `Left 100 '+'.`
+#[derive(Debug, Clone)]
+pub struct YeccDeclaration {
+    pub name: LexicalToken,
+    pub values: Vec<LexicalToken>,
+}
+
+/// `LHS -> SYMBOLS : ACTION.` (the action is optional)
+#[derive(Debug, Clone)]
+pub struct YeccRule {
+    pub lhs: AtomToken,
+    pub symbols: Vec<LexicalToken>,
+    pub action: Option<Body>,
+}
+
+/// Parses the leex file `text`.
+pub fn parse_leex(text: &str) -> Result<LeexFile> {
+    let (header_end, code_start) = find_erlang_code(text);
+    let rules_start = find_section(&text[..header_end], "Rules.");
+    let definitions = find_section(&text[..header_end], "Definitions.")
+        .map(|start| {
+            let end = rules_start.map_or(header_end, |r| line_start(text, r));
+            parse_leex_definitions(text, start..end)
+        })
+        .unwrap_or_default();
+
+    let mut regexes = Vec::new();
+    let mut keep = Vec::new();
+    if let Some(start) = rules_start {
+        let mut offset = start;
+        while offset < header_end {
+            let line_end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+            let line = &text[offset..line_end];
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('%') {
+                offset = line_end + 1;
+                continue;
+            }
+            let regex_len = line
+                .find(char::is_whitespace)
+                .or_else(|| line.find(':'))
+                .unwrap_or(line.len());
+            let colon = offset
+                + regex_len
+                + track_assert_some!(
+                    text[offset + regex_len..].find(':'),
+                    ErrorKind::InvalidInput,
+                    "Missing `:` in a leex rule: line={}",
+                    line_number(text, offset)
+                );
+            let action_end = track!(find_dot(text, colon + 1))?;
+            regexes.push((line[..regex_len].to_owned(), line_number(text, offset)));
+            keep.push(colon + 1..action_end);
+            offset = action_end;
+        }
+    }
+    keep.push(code_start..text.len());
+
+    let mut parser = masked_parser(text, &keep);
+    let mut rules = Vec::new();
+    for (regex, line) in regexes {
+        let action = track!(parser.parse(); regex, line)?;
+        track!(parser.expect::<SymbolToken>(&Symbol::Dot))?;
+        rules.push(LeexRule {
+            regex,
+            line,
+            action,
+        });
+    }
+    let code = track!(parser.parse())?;
+    Ok(LeexFile {
+        definitions,
+        rules,
+        code,
+    })
+}
+
+/// Parses the yecc file `text`.
+pub fn parse_yecc(text: &str) -> Result<YeccFile> {
+    let (header_end, code_start) = find_erlang_code(text);
+    let mut declarations = Vec::new();
+    let mut rules = Vec::new();
+    let mut actions = Vec::new();
+    let mut keep = Vec::new();
+
+    let mut form: Vec<LexicalToken> = Vec::new();
+    for token in Lexer::new(&text[..header_end]) {
+        let token = track!(token.map_err(Error::from))?;
+        if !is_symbol(&token, Symbol::Dot) {
+            form.push(token);
+            continue;
+        }
+        let dot = token;
+        let mut tokens = form.drain(..);
+        let name = track_assert_some!(tokens.next(), ErrorKind::UnexpectedToken(dot));
+        let rest = tokens.collect::<Vec<_>>();
+        if !rest
+            .first()
+            .is_some_and(|t| is_symbol(t, Symbol::RightArrow))
+        {
+            declarations.push(YeccDeclaration { name, values: rest });
+            continue;
+        }
+        let lhs = track!(name
+            .into_atom_token()
+            .map_err(ErrorKind::UnexpectedToken)
+            .map_err(Error::from))?;
+        let colon = rest.iter().position(|t| is_symbol(t, Symbol::Colon));
+        let symbols = rest[1..colon.unwrap_or(rest.len())].to_vec();
+        if let Some(action) = colon.and_then(|i| rest.get(i + 1)) {
+            keep.push(action.start_position().offset()..dot.end_position().offset());
+            actions.push(rules.len());
+        }
+        rules.push(YeccRule {
+            lhs,
+            symbols,
+            action: None,
+        });
+    }
+    keep.push(code_start..text.len());
+
+    let mut parser = masked_parser(text, &keep);
+    for i in actions {
+        rules[i].action = Some(track!(parser.parse(); rules[i].lhs)?);
+        track!(parser.expect::<SymbolToken>(&Symbol::Dot))?;
+    }
+    let code = track!(parser.parse())?;
+    Ok(YeccFile {
+        declarations,
+        rules,
+        code,
+    })
+}
+
+/// Returns a parser over `text` in which everything except `keep` is blanked out.
+///
+/// Blanking (instead of slicing) keeps the positions of the remaining tokens intact.
+fn masked_parser(text: &str, keep: &[Range<usize>]) -> MaskedParser {
+    let mut masked = text
+        .bytes()
+        .map(|b| if b == b'\n' { b'\n' } else { b' ' })
+        .collect::<Vec<_>>();
+    for range in keep {
+        masked[range.clone()].copy_from_slice(&text.as_bytes()[range.clone()]);
+    }
+    let masked = String::from_utf8(masked).expect("Never fails");
+    let tokens = MaybeMatchTokens::new(Lexer::new(masked));
+    Parser::new(TokenReader::new(Preprocessor::new(tokens)))
+}
+
+/// Returns the end of the header part and the start of the `Erlang code.` section.
+fn find_erlang_code(text: &str) -> (usize, usize) {
+    match find_section(text, "Erlang code.") {
+        None => (text.len(), text.len()),
+        Some(start) => (line_start(text, start), start),
+    }
+}
+
+/// Returns the offset just after the line consisting of `header`.
+fn find_section(text: &str, header: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == header {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .trim_end_matches('\n')
+        .rfind('\n')
+        .map_or(0, |i| i + 1)
+}
+
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+fn parse_leex_definitions(text: &str, range: Range<usize>) -> Vec<LeexDefinition> {
+    let first_line = line_number(text, range.start);
+    let mut definitions = Vec::new();
+    for (line_num, line) in (first_line..).zip(text[range].lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+        if let Some(i) = line.find('=') {
+            definitions.push(LeexDefinition {
+                name: line[..i].trim().to_owned(),
+                value: line[i + 1..].trim().to_owned(),
+                line: line_num,
+            });
+        }
+    }
+    definitions
+}
+
+/// Returns the offset just after the `.` which terminates the Erlang code starting at `start`.
+fn find_dot(text: &str, start: usize) -> Result<usize> {
+    for token in Tokenizer::new(&text[start..]) {
+        let token = track!(token.map_err(Error::from))?;
+        if let Token::Symbol(ref t) = token {
+            let end = start + t.end_position().offset();
+            let is_end = text[end..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || c == '%');
+            if t.value() == Symbol::Dot && is_end {
+                return Ok(end);
+            }
+        }
+    }
+    track_panic!(
+        ErrorKind::UnexpectedEos,
+        "Unterminated action: line={}",
+        line_number(text, start)
+    );
+}
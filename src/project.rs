@@ -0,0 +1,221 @@
+//! Parsing of all the source files in directories.
+use erl_pp::Preprocessor;
+use erl_tokenize::Lexer;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cst::ModuleDecl;
+use crate::{Diagnostic, Error, IncludeCache, IncludeExpander, Parser, Result, TokenReader};
+
+/// Options of `parse_directory`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectOptions {
+    /// The number of the threads to parse files (`None` means the available parallelism).
+    pub threads: Option<usize>,
+
+    /// The directories to search for `-include` files
+    /// (in addition to the directory of the including file).
+    pub include_dirs: Vec<PathBuf>,
+
+    /// The directories to search for the applications of `-include_lib` files
+    /// (in addition to the `ERL_LIBS` environment variable).
+    pub code_paths: Vec<PathBuf>,
+}
+impl ProjectOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn threads(mut self, n: usize) -> Self {
+        self.threads = Some(n);
+        self
+    }
+    pub fn include_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+    pub fn code_path<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.code_paths.push(dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// Result of parsing a source (`.erl`) or header (`.hrl`) file.
+#[derive(Debug)]
+pub struct ProjectFile {
+    pub path: PathBuf,
+
+    /// The parsed forms (`None` if the file cannot be read).
+    ///
+    /// As `Parser::parse_module_resilient`, forms which cannot be parsed are `Form::Error`s.
+    pub module: Option<ModuleDecl>,
+
+    /// The errors raised while reading or parsing the file.
+    pub errors: Vec<Error>,
+
+    /// The diagnostics of the parse errors (see `Parser::diagnostics`).
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Source and header files parsed by `parse_directory`.
+#[derive(Debug, Default)]
+pub struct Project {
+    files: Vec<ProjectFile>,
+}
+impl Project {
+    /// Returns the parsed files, ordered by path.
+    pub fn files(&self) -> &[ProjectFile] {
+        &self.files
+    }
+
+    pub fn file<P: AsRef<Path>>(&self, path: P) -> Option<&ProjectFile> {
+        let path = path.as_ref();
+        self.files
+            .binary_search_by(|f| f.path.as_path().cmp(path))
+            .ok()
+            .map(|i| &self.files[i])
+    }
+
+    /// Returns the diagnostics of all the files with their paths, ordered by path.
+    pub fn diagnostics(&self) -> Vec<(&Path, &Diagnostic)> {
+        self.files
+            .iter()
+            .flat_map(|f| f.diagnostics.iter().map(move |d| (f.path.as_path(), d)))
+            .collect()
+    }
+
+    /// Returns `true` if any file has errors.
+    pub fn has_errors(&self) -> bool {
+        self.files.iter().any(|f| !f.errors.is_empty())
+    }
+
+    pub fn into_files(self) -> Vec<ProjectFile> {
+        self.files
+    }
+}
+
+/// Parses the `.erl` and `.hrl` files in `paths` in parallel.
+///
+/// Directories in `paths` are searched recursively
+/// (except for hidden ones, e.g., `.git`, and symbolic links to directories).
+/// Included files are tokenized only once and shared among the threads (see `IncludeCache`).
+///
+/// Errors in the files are reported per file (see `ProjectFile`);
+/// this fails only if a directory cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::project::{parse_directory, ProjectOptions};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join(format!("erl_parse_project_doc_{}", std::process::id()));
+/// fs::create_dir_all(dir.join("src")).unwrap();
+/// fs::create_dir_all(dir.join("include")).unwrap();
+/// fs::write(dir.join("include/a.hrl"), "-define(A, 1).\n").unwrap();
+/// fs::write(dir.join("src/foo.erl"), "-module(foo).\n-include(\"a.hrl\").\nf() -> ?A.\n").unwrap();
+/// fs::write(dir.join("src/bar.erl"), "-module(bar).\ng( -> ok.\n").unwrap();
+///
+/// let options = ProjectOptions::new().include_dir(dir.join("include")).threads(2);
+/// let project = parse_directory(&[&dir], &options).unwrap();
+/// assert_eq!(project.files().len(), 3);
+/// assert!(project.file(dir.join("src/foo.erl")).unwrap().errors.is_empty());
+///
+/// let diagnostics = project.diagnostics();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].0, dir.join("src/bar.erl"));
+/// fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn parse_directory<P: AsRef<Path>>(paths: &[P], options: &ProjectOptions) -> Result<Project> {
+    let mut files = Vec::new();
+    for path in paths {
+        track!(discover(path.as_ref(), &mut files))?;
+    }
+    files.sort();
+    files.dedup();
+
+    let threads = options
+        .threads
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, files.len().max(1));
+    let cache = IncludeCache::new();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(files.len()));
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let file = match files.get(i) {
+                    Some(path) => parse_file(path, &cache, options),
+                    None => break,
+                };
+                results.lock().expect("Never fails").push(file);
+            });
+        }
+    });
+
+    let mut files = results.into_inner().expect("Never fails");
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Project { files })
+}
+
+fn discover(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in track!(fs::read_dir(path).map_err(Error::from); path)? {
+            let entry = track!(entry.map_err(Error::from))?;
+            let is_symlink = track!(entry.file_type().map_err(Error::from))?.is_symlink();
+            let entry = entry.path();
+            let hidden = entry
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'));
+
+            // Linked directories may form cycles or contain the files found elsewhere
+            let linked_dir = is_symlink && entry.is_dir();
+            if !(hidden || linked_dir) {
+                track!(discover(&entry, files))?;
+            }
+        }
+    } else if matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("erl") | Some("hrl")
+    ) {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn parse_file(path: &Path, cache: &IncludeCache, options: &ProjectOptions) -> ProjectFile {
+    let mut file = ProjectFile {
+        path: path.to_path_buf(),
+        module: None,
+        errors: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let text = match track!(fs::read_to_string(path).map_err(Error::from); path) {
+        Ok(text) => text,
+        Err(e) => {
+            file.errors.push(e);
+            return file;
+        }
+    };
+
+    let mut lexer = Lexer::new(text);
+    lexer.set_filepath(path);
+    let mut tokens = IncludeExpander::new(lexer, cache.clone());
+    for dir in &options.include_dirs {
+        tokens = tokens.include_dir(dir);
+    }
+    for dir in &options.code_paths {
+        tokens = tokens.code_path(dir);
+    }
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let (module, errors) = parser.parse_module_resilient();
+    file.module = Some(module);
+    file.errors = errors;
+    file.diagnostics = parser.take_diagnostics();
+    file
+}
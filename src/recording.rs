@@ -0,0 +1,96 @@
+use erl_pp::MacroCall;
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::collections::BTreeMap;
+
+use crate::traits::{Preprocessor, TokenRead};
+use crate::Result;
+
+/// Token reader which records the tokens consumed from the inner reader.
+///
+/// Tokens which are read and then unread (e.g., by backtracking of `Parser`) are not recorded.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::Expr;
+/// use erl_parse::{Parser, Recording, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "foo(1, 2) + 3";
+/// let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+/// let mut parser = Parser::new(Recording::new(reader));
+/// parser.parse::<Expr>().unwrap();
+///
+/// let recording = parser.reader();
+/// assert_eq!(recording.tokens().len(), 8);
+/// assert_eq!(recording.source(text), Some(text));
+/// ```
+#[derive(Debug)]
+pub struct Recording<T> {
+    inner: T,
+    tokens: Vec<LexicalToken>,
+}
+impl<T: TokenRead> Recording<T> {
+    pub fn new(inner: T) -> Self {
+        Recording {
+            inner,
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Returns the tokens consumed so far.
+    pub fn tokens(&self) -> &[LexicalToken] {
+        &self.tokens
+    }
+
+    /// Returns the recorded tokens and clears the recording.
+    pub fn take_tokens(&mut self) -> Vec<LexicalToken> {
+        std::mem::take(&mut self.tokens)
+    }
+
+    /// Returns the slice of `text` spanning from the first recorded token to the last one.
+    ///
+    /// `text` must be the source text from which the tokens were read.
+    /// Whitespaces and comments between the tokens are included as they are.
+    /// Returns `None` if no tokens are recorded.
+    pub fn source<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let first = self.tokens.first()?;
+        let last = self.tokens.last()?;
+        text.get(first.start_position().offset()..last.end_position().offset())
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+impl<T: Preprocessor> Preprocessor for Recording<T> {
+    fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
+        self.inner.define_macro(name, replacement);
+    }
+    fn undef_macro(&mut self, name: &str) {
+        self.inner.undef_macro(name);
+    }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        self.inner.macro_calls()
+    }
+}
+impl<T: TokenRead> TokenRead for Recording<T> {
+    fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
+        let token = track!(self.inner.try_read_token())?;
+        if let Some(ref token) = token {
+            self.tokens.push(token.clone());
+        }
+        Ok(token)
+    }
+    fn unread_token(&mut self, token: LexicalToken) {
+        self.tokens.pop();
+        self.inner.unread_token(token);
+    }
+}
@@ -0,0 +1,110 @@
+//! Rendering of self-contained modules and minified fixtures.
+//!
+//! A self-contained module has no `-include` directives nor macro calls,
+//! so it can be attached to bug reports or fed to tools which handle a single file.
+use erl_pp::Preprocessor;
+use erl_tokenize::{Lexer, PositionRange};
+use std::path::Path;
+
+use crate::cst::ModuleDecl;
+use crate::traits::{self, CollectTokens};
+use crate::{
+    Error, IncludeCache, IncludeExpander, IncludeResolver, MacroExpansion, Parser, Result,
+    TokenReader,
+};
+
+/// Renders `module` as Erlang source code with provenance comments.
+///
+/// `module` is expected to be parsed with its includes inlined and its macros expanded
+/// (see `render_file`), and `expansions` are the macro expansions made while parsing it
+/// (see `TokenReader::macro_expansions`).
+///
+/// Forms which come from included files are preceded by `%% from PATH:LINE` comments,
+/// and forms which contain macro calls are preceded by `%% expanded ?NAME (line LINE)` comments.
+/// The tokens are laid out as `CollectTokens::to_erlang_source` does.
+pub fn render_module(module: &ModuleDecl, expansions: &[MacroExpansion]) -> String {
+    let main_file = module
+        .forms
+        .last()
+        .and_then(|f| f.end_position().filepath().map(|p| p.to_path_buf()));
+    let mut text = String::new();
+    for (i, form) in module.forms.iter().enumerate() {
+        let start = form.start_position();
+        if let Some(path) = start.filepath() {
+            if main_file.as_deref() != Some(path) {
+                text.push_str(&format!("%% from {}:{}\n", path.display(), start.line()));
+            }
+        }
+        for e in expansions.iter().filter(|e| e.form == i) {
+            text.push_str(&format!(
+                "%% expanded ?{} (line {})\n",
+                e.name,
+                e.call.start.line()
+            ));
+        }
+        text.push_str(&form.to_erlang_source());
+        text.push('\n');
+    }
+    text
+}
+
+/// Parses `text` (the content of the file `path`) with its includes inlined and its macros expanded,
+/// and renders it by `render_module`.
+///
+/// The included files are located by `resolver`.
+pub fn render_file<P, R>(path: P, text: &str, resolver: R) -> Result<String>
+where
+    P: AsRef<Path>,
+    R: IncludeResolver + Send,
+{
+    let mut lexer = Lexer::new(text.to_owned());
+    lexer.set_filepath(path);
+    let tokens = IncludeExpander::with_resolver(lexer, IncludeCache::new(), resolver);
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let module: ModuleDecl = track!(parser.parse())?;
+    Ok(render_module(&module, parser.reader().macro_expansions()))
+}
+
+/// Strips the comments of `text` and collapses its whitespace,
+/// keeping each form on its original starting line.
+///
+/// The tokens of a form are laid out on a line as `CollectTokens::to_erlang_source` does,
+/// and the forms are padded with blank lines,
+/// so the diagnostics reported for the minified text have the same line numbers as the original ones.
+/// Forms starting on the same line stay on the same line.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::render::minify;
+///
+/// let text = "%% comment\n-module(foo).\n\nf(X) ->\n    %% comment\n    X + 1.\ng() -> ok.";
+/// assert_eq!(minify(text).unwrap(), "\n-module(foo).\n\nf(X) -> X + 1.\n\n\ng() -> ok.\n");
+/// ```
+pub fn minify(text: &str) -> Result<String> {
+    let tokens = Lexer::new(text)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(Error::from);
+    let tokens = track!(tokens)?;
+    let mut minified = String::new();
+    let mut line = 1;
+    for (i, token) in tokens.iter().enumerate() {
+        if i == 0 || traits::is_form_end(&tokens, i - 1) {
+            let start = token.start_position().line();
+            if start > line {
+                minified.extend(std::iter::repeat_n('\n', start - line));
+                line = start;
+            } else if i > 0 {
+                minified.push(' ');
+            }
+        } else if traits::needs_space(&tokens, i) {
+            minified.push(' ');
+        }
+        minified.push_str(token.text());
+        line += token.text().matches('\n').count();
+    }
+    if !minified.is_empty() {
+        minified.push('\n');
+    }
+    Ok(minified)
+}
@@ -0,0 +1,68 @@
+use erl_tokenize::{Position, PositionRange};
+use std::ops::Range;
+
+/// Position range of a node.
+///
+/// This is the common representation of the ranges of CST nodes, AST nodes and tokens
+/// (i.e., anything which implements `PositionRange`; see `Spanned`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+impl Span {
+    pub fn of<T: PositionRange + ?Sized>(x: &T) -> Self {
+        Span {
+            start: x.start_position(),
+            end: x.end_position(),
+        }
+    }
+
+    /// Returns the byte offset range.
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start.offset()..self.end.offset()
+    }
+
+    /// Returns the (1-origin) line and column of the start position.
+    pub fn start_line_column(&self) -> (usize, usize) {
+        (self.start.line(), self.start.column())
+    }
+
+    /// Returns the (1-origin) line and column of the end position.
+    pub fn end_line_column(&self) -> (usize, usize) {
+        (self.end.line(), self.end.column())
+    }
+
+    /// Returns `true` if the byte `offset` is within this span (the end is exclusive).
+    pub fn contains_offset(&self, offset: usize) -> bool {
+        self.byte_range().contains(&offset)
+    }
+
+    /// Returns `true` if `other` is within this span.
+    pub fn contains(&self, other: &Span) -> bool {
+        self.start.offset() <= other.start.offset() && other.end.offset() <= self.end.offset()
+    }
+
+    /// Returns the part of `text` (the source text) covered by this span.
+    ///
+    /// Returns `None` if the span is out of `text` (e.g., it is in an included file).
+    pub fn text<'a>(&self, text: &'a str) -> Option<&'a str> {
+        text.get(self.byte_range())
+    }
+}
+impl PositionRange for Span {
+    fn start_position(&self) -> Position {
+        self.start.clone()
+    }
+    fn end_position(&self) -> Position {
+        self.end.clone()
+    }
+}
+
+/// Provides `span` for anything which has a position range.
+pub trait Spanned: PositionRange {
+    fn span(&self) -> Span {
+        Span::of(self)
+    }
+}
+impl<T: PositionRange + ?Sized> Spanned for T {}
@@ -0,0 +1,66 @@
+//! Explicit stack growth for the recursive parser.
+//!
+//! Deeply nested input (e.g., machine-generated code) would exhaust the stack of the calling thread.
+//! Instead, once a thread has used `BUDGET` bytes of its stack,
+//! the parse continues on a new thread (a "segment") which has `SEGMENT_SIZE` bytes of stack,
+//! while the calling thread waits for it.
+use std::cell::Cell;
+use std::io;
+use std::panic;
+use std::thread;
+
+/// The stack usage of the calling thread allowed for parsing.
+const BUDGET: usize = 1024 * 1024;
+
+/// The stack size of a segment.
+const SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// The stack usage between consecutive calls of `maybe_grow` must be below this.
+const RED_ZONE: usize = 1024 * 1024;
+
+thread_local! {
+    /// The lowest stack address which `maybe_grow` may run `f` at on this thread.
+    static LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Executes `f`, on a new segment if the stack of the current thread is running low.
+///
+/// If `outermost` is `true` (i.e., `f` is not nested in another call),
+/// the budget of the current thread is measured from here.
+pub(crate) fn maybe_grow<F, R>(outermost: bool, f: F) -> io::Result<R>
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    let sp = stack_pointer();
+    let limit = LIMIT.with(|limit| match limit.get() {
+        Some(x) if !outermost => x,
+        _ => {
+            let x = sp.saturating_sub(BUDGET);
+            limit.set(Some(x));
+            x
+        }
+    });
+    if sp > limit {
+        return Ok(f());
+    }
+    thread::scope(|scope| {
+        let segment = thread::Builder::new()
+            .name("erl_parse-stack".to_owned())
+            .stack_size(SEGMENT_SIZE)
+            .spawn_scoped(scope, || {
+                let limit = stack_pointer().saturating_sub(SEGMENT_SIZE - RED_ZONE);
+                LIMIT.with(|x| x.set(Some(limit)));
+                f()
+            })?;
+        match segment.join() {
+            Ok(x) => Ok(x),
+            Err(e) => panic::resume_unwind(e),
+        }
+    })
+}
+
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
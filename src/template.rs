@@ -0,0 +1,128 @@
+//! Parsing of Erlang expressions embedded in templates.
+//!
+//! # Examples
+//!
+//! ```
+//! use erl_parse::template;
+//!
+//! let text = "{{ lists:sum([X || {X} <- Xs]) }} items";
+//! let (body, end) = template::parse_template_expr(&text[2..], &["}}"]).unwrap();
+//! assert_eq!(body.exprs.iter().count(), 1);
+//! assert_eq!(&text[2 + end..], "}} items");
+//! ```
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{LexicalToken, Tokenizer};
+
+use crate::cst::exprs::parts::Body;
+use crate::traits::{Preprocessor, TokenRead};
+use crate::{Error, ErrorKind, Parser, Result};
+
+/// Token reader which stops at the first terminator appearing outside of brackets.
+///
+/// Terminators are matched against the source text (not against tokens),
+/// so they need not be valid Erlang tokens (e.g., `%>` or `-->` can be used).
+/// Terminators inside string literals, comments or brackets are ignored.
+#[derive(Debug)]
+pub struct TemplateReader<'a> {
+    text: &'a str,
+    tokenizer: Tokenizer<&'a str>,
+    terminators: Vec<String>,
+    unread: Vec<LexicalToken>,
+    depth: usize,
+    end_offset: Option<usize>,
+}
+impl<'a> TemplateReader<'a> {
+    pub fn new(text: &'a str, terminators: &[&str]) -> Self {
+        TemplateReader {
+            text,
+            tokenizer: Tokenizer::new(text),
+            terminators: terminators.iter().map(|t| (*t).to_owned()).collect(),
+            unread: Vec::new(),
+            depth: 0,
+            end_offset: None,
+        }
+    }
+
+    /// Returns the offset of the terminator which stopped this reader.
+    ///
+    /// `None` means that no terminator has been reached yet.
+    pub fn end_offset(&self) -> Option<usize> {
+        self.end_offset
+    }
+
+    fn is_at_terminator(&self, offset: usize) -> bool {
+        let rest = &self.text[offset..];
+        self.depth == 0
+            && self
+                .terminators
+                .iter()
+                .any(|t| rest.starts_with(t.as_str()))
+    }
+
+    fn update_depth(&mut self, token: &LexicalToken, unread: bool) {
+        let delta: isize = match token.as_symbol_token().map(|t| t.value()) {
+            Some(Symbol::OpenParen)
+            | Some(Symbol::OpenSquare)
+            | Some(Symbol::OpenBrace)
+            | Some(Symbol::DoubleLeftAngle) => 1,
+            Some(Symbol::CloseParen)
+            | Some(Symbol::CloseSquare)
+            | Some(Symbol::CloseBrace)
+            | Some(Symbol::DoubleRightAngle) => -1,
+            _ => 0,
+        };
+        let delta = if unread { -delta } else { delta };
+        self.depth = (self.depth as isize + delta).max(0) as usize;
+    }
+}
+impl<'a> Preprocessor for TemplateReader<'a> {
+    fn define_macro(&mut self, _name: &str, _replacement: Vec<LexicalToken>) {}
+    fn undef_macro(&mut self, _name: &str) {}
+}
+impl<'a> TokenRead for TemplateReader<'a> {
+    fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
+        if let Some(token) = self.unread.pop() {
+            self.update_depth(&token, false);
+            return Ok(Some(token));
+        }
+        if self.end_offset.is_some() {
+            return Ok(None);
+        }
+        loop {
+            let offset = self.tokenizer.next_position().offset();
+            if self.is_at_terminator(offset) {
+                self.end_offset = Some(offset);
+                return Ok(None);
+            }
+            match self.tokenizer.next() {
+                None => return Ok(None),
+                Some(Err(e)) => return Err(track!(Error::from(e))),
+                Some(Ok(token)) => {
+                    if let Ok(token) = token.into_lexical_token() {
+                        self.update_depth(&token, false);
+                        return Ok(Some(token));
+                    }
+                }
+            }
+        }
+    }
+    fn unread_token(&mut self, token: LexicalToken) {
+        self.update_depth(&token, true);
+        self.unread.push(token);
+    }
+}
+
+/// Parses comma-separated expressions at the beginning of `text`, stopping at one of `terminators`.
+///
+/// Returns the parsed expressions and the offset of the terminator
+/// (or `text.len()` if no terminator appears).
+pub fn parse_template_expr(text: &str, terminators: &[&str]) -> Result<(Body, usize)> {
+    let mut parser = Parser::new(TemplateReader::new(text, terminators));
+    let body = track!(parser.parse::<Body>())?;
+    if !track!(parser.eos())? {
+        let token = track!(parser.parse::<LexicalToken>())?;
+        track_panic!(ErrorKind::UnexpectedToken(token));
+    }
+    let end = parser.reader().end_offset().unwrap_or(text.len());
+    Ok((body, end))
+}
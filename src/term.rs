@@ -0,0 +1,221 @@
+//! Erlang term data model, and reading of Erlang data files.
+use erl_tokenize::tokens::SymbolToken;
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, PositionRange};
+use num::{BigInt, ToPrimitive};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::cst::{eval, Expr};
+use crate::traits::CollectTokens;
+use crate::{Error, ErrorKind, Parser, Result, TokenReader};
+
+/// Erlang term.
+///
+/// Strings are represented as lists of integers, as in Erlang.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Atom(String),
+    Integer(BigInt),
+    Float(f64),
+    Tuple(Vec<Term>),
+    List(Vec<Term>),
+    ImproperList(Vec<Term>, Box<Term>),
+    Binary(Vec<u8>),
+    Map(Vec<(Term, Term)>),
+}
+impl Term {
+    pub fn atom(name: &str) -> Self {
+        Term::Atom(name.to_owned())
+    }
+
+    /// Makes a list of the code points of `s`.
+    pub fn string(s: &str) -> Self {
+        Term::List(s.chars().map(|c| Term::from(c as u32)).collect())
+    }
+
+    pub fn as_atom(&self) -> Option<&str> {
+        if let Term::Atom(ref name) = *self {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the string represented by this term if it is a list of code points.
+    pub fn as_string(&self) -> Option<String> {
+        if let Term::List(ref elements) = *self {
+            elements
+                .iter()
+                .map(|e| match *e {
+                    Term::Integer(ref i) => i.to_u32().and_then(char::from_u32),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            None
+        }
+    }
+}
+impl From<BigInt> for Term {
+    fn from(f: BigInt) -> Self {
+        Term::Integer(f)
+    }
+}
+impl From<u32> for Term {
+    fn from(f: u32) -> Self {
+        Term::Integer(BigInt::from(f))
+    }
+}
+impl From<usize> for Term {
+    fn from(f: usize) -> Self {
+        Term::Integer(BigInt::from(f))
+    }
+}
+impl From<i64> for Term {
+    fn from(f: i64) -> Self {
+        Term::Integer(BigInt::from(f))
+    }
+}
+impl From<f64> for Term {
+    fn from(f: f64) -> Self {
+        Term::Float(f)
+    }
+}
+
+/// Parses `text` as a sequence of dot-terminated terms, as `file:consult/1` does.
+///
+/// This is the format of data files such as `.app.src`, `sys.config` and `rebar.config`.
+/// Each term is parsed as an expression and folded by `cst::eval::eval`,
+/// so constant operator calls (e.g., `60 * 1000`) are accepted as well.
+/// Macros are not expanded.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::term::{consult, Term};
+///
+/// let text = "%% comment\n{application, foo, [{vsn, \"1.0\"}, {env, [{timeout, -1}]}]}.\n[].";
+/// let terms = consult(text).unwrap();
+/// assert_eq!(terms.len(), 2);
+/// assert_eq!(terms[0].to_string(), "{application,foo,[{vsn,[49,46,48]},{env,[{timeout,-1}]}]}");
+/// assert_eq!(terms[1], Term::List(vec![]));
+///
+/// assert!(consult("{a, X}.").is_err());
+/// assert!(consult("{a}").is_err());
+/// ```
+pub fn consult(text: &str) -> Result<Vec<Term>> {
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text)));
+    let mut terms = Vec::new();
+    while !track!(parser.eos())? {
+        let expr: Expr = track!(parser.parse())?;
+        let term = track_assert_some!(
+            eval::eval(&expr),
+            ErrorKind::InvalidInput,
+            "Not a constant term: line={}, term={}",
+            expr.start_position().line(),
+            expr.to_erlang_source()
+        );
+        track!(parser.expect::<SymbolToken>(&Symbol::Dot))?;
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+/// Reads the file `path` and parses it by `consult`.
+pub fn consult_file<P: AsRef<Path>>(path: P) -> Result<Vec<Term>> {
+    let path = path.as_ref();
+    let text = track!(fs::read_to_string(path).map_err(Error::from); path)?;
+    track!(consult(&text); path)
+}
+
+/// Formats the term in the same way as `io:format("~w", [Term])`.
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Term::Atom(ref name) => write_atom(f, name),
+            Term::Integer(ref i) => write!(f, "{}", i),
+            Term::Float(x) => {
+                let s = format!("{:?}", x);
+                match s.find('e') {
+                    Some(i) if !s[..i].contains('.') => write!(f, "{}.0{}", &s[..i], &s[i..]),
+                    _ => write!(f, "{}", s),
+                }
+            }
+            Term::Tuple(ref elements) => {
+                write!(f, "{{")?;
+                write_elements(f, elements)?;
+                write!(f, "}}")
+            }
+            Term::List(ref elements) => {
+                write!(f, "[")?;
+                write_elements(f, elements)?;
+                write!(f, "]")
+            }
+            Term::ImproperList(ref elements, ref tail) => {
+                write!(f, "[")?;
+                write_elements(f, elements)?;
+                write!(f, "|{}]", tail)
+            }
+            Term::Binary(ref bytes) => {
+                write!(f, "<<")?;
+                for (i, b) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", b)?;
+                }
+                write!(f, ">>")
+            }
+            Term::Map(ref pairs) => {
+                write!(f, "#{{")?;
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{} => {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_elements(f: &mut fmt::Formatter, elements: &[Term]) -> fmt::Result {
+    for (i, e) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", e)?;
+    }
+    Ok(())
+}
+
+const RESERVED_WORDS: &[&str] = &[
+    "after", "and", "andalso", "band", "begin", "bnot", "bor", "bsl", "bsr", "bxor", "case",
+    "catch", "cond", "div", "else", "end", "fun", "if", "let", "maybe", "not", "of", "or",
+    "orelse", "receive", "rem", "try", "when", "xor",
+];
+
+fn write_atom(f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+    let is_plain = name.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+        && !RESERVED_WORDS.contains(&name);
+    if is_plain {
+        return write!(f, "{}", name);
+    }
+    write!(f, "'")?;
+    for c in name.chars() {
+        match c {
+            '\'' => write!(f, "\\'")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            _ => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "'")
+}
@@ -1,15 +1,153 @@
-use erl_tokenize::LexicalToken;
-use std;
+use erl_pp::MacroCall;
+use erl_tokenize::tokens::{AtomToken, SymbolToken};
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{LexicalToken, Position, PositionRange};
+use std::collections::{BTreeMap, HashSet};
 use std::marker::PhantomData;
 
 use crate::traits::{Preprocessor, TokenRead};
-use crate::{Error, Result};
+use crate::{Error, Result, Span};
+
+/// Expansion of a macro call made by the preprocessor.
+///
+/// The expanded tokens keep the positions in the macro definition (or in the call arguments),
+/// so this is needed to report errors about them at the call site.
+#[derive(Debug, Clone)]
+pub struct MacroExpansion {
+    /// The name of the called macro.
+    pub name: String,
+
+    /// The span of the macro call (e.g., `?FOO(1, 2)`).
+    pub call: Span,
+
+    /// The index of the form which contains the macro call
+    /// (i.e., the number of `.` tokens read before the call).
+    pub form: usize,
+
+    /// The tokens produced by the expansion (nested macro calls are expanded).
+    pub tokens: Vec<LexicalToken>,
+}
+impl MacroExpansion {
+    /// Returns `true` if one of the expanded tokens starts at `position`.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.tokens.iter().any(|t| t.start_position() == *position)
+    }
+}
+
+/// Token stream adapter which lets `?=` (of `cst::exprs::MaybeMatch`) pass through `erl_pp::Preprocessor`.
+///
+/// `erl_pp` regards `?` as the start of a macro call and drops it if no macro name follows,
+/// so `{ok, X} ?= f()` would be read as `{ok, X} = f()`.
+/// This adapter replaces such a `?` with a placeholder, which is restored by `TokenReader`.
+/// `IncludeExpander` does the same.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::Expr;
+/// use erl_parse::traits::CollectTokens;
+/// use erl_parse::{MaybeMatchTokens, Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "maybe {ok, X} ?= f(), X end";
+/// let tokens = MaybeMatchTokens::new(Lexer::new(text));
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+/// let expr: Expr = parser.parse().unwrap();
+/// assert_eq!(expr.to_erlang_source(), text);
+/// ```
+#[derive(Debug)]
+pub struct MaybeMatchTokens<T> {
+    inner: T,
+    next: Option<erl_tokenize::Result<LexicalToken>>,
+}
+impl<T> MaybeMatchTokens<T>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+{
+    pub fn new(inner: T) -> Self {
+        MaybeMatchTokens { inner, next: None }
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+impl<T> Iterator for MaybeMatchTokens<T>
+where
+    T: Iterator<Item = erl_tokenize::Result<LexicalToken>>,
+{
+    type Item = erl_tokenize::Result<LexicalToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = match self.next.take().or_else(|| self.inner.next())? {
+            Err(e) => return Some(Err(e)),
+            Ok(t) => t,
+        };
+        if !is_symbol(&token, Symbol::Question) {
+            return Some(Ok(token));
+        }
+        self.next = self.inner.next();
+        match self.next {
+            Some(Ok(ref next)) => Some(Ok(hide_question(token, next))),
+            _ => Some(Ok(token)),
+        }
+    }
+}
+
+/// Replaces `question` with a placeholder if it is `?` of `?=` (i.e., `next` is the adjacent `=`).
+///
+/// The placeholder is the atom `'?'` at the position of `?`.
+/// Since `=` starts right after the position, it is never confused with an atom in the source code.
+pub(crate) fn hide_question(question: LexicalToken, next: &LexicalToken) -> LexicalToken {
+    if is_symbol(&question, Symbol::Question)
+        && is_symbol(next, Symbol::Match)
+        && is_adjacent(&question.start_position(), &next.start_position())
+    {
+        AtomToken::from_value("?", question.start_position()).into()
+    } else {
+        question
+    }
+}
+
+/// Returns `true` if `token` may be a placeholder made by `hide_question`.
+fn is_placeholder(token: &LexicalToken) -> bool {
+    token
+        .as_atom_token()
+        .is_some_and(|t| t.value() == "?" && t.text() == "'?'")
+}
+
+/// Restores `?` from `placeholder` made by `hide_question` (`next` is the token following it).
+fn restore_question(placeholder: &LexicalToken, next: &LexicalToken) -> Option<LexicalToken> {
+    if is_placeholder(placeholder)
+        && is_symbol(next, Symbol::Match)
+        && is_adjacent(&placeholder.start_position(), &next.start_position())
+    {
+        Some(SymbolToken::from_value(Symbol::Question, placeholder.start_position()).into())
+    } else {
+        None
+    }
+}
+
+fn is_symbol(token: &LexicalToken, symbol: Symbol) -> bool {
+    token.as_symbol_token().is_some_and(|t| t.value() == symbol)
+}
+
+/// Returns `true` if `next` is right after a character at `position`.
+fn is_adjacent(position: &Position, next: &Position) -> bool {
+    position.filepath() == next.filepath() && position.offset() + 1 == next.offset()
+}
 
 #[derive(Debug)]
 pub struct TokenReader<T, E> {
     inner: T,
     unread: Vec<LexicalToken>,
-    _phantom: PhantomData<E>,
+    expansions: Vec<MacroExpansion>,
+    expanding: bool,
+    seen_calls: HashSet<Position>,
+    last_position: Option<Position>,
+    dots: usize,
+    pending_error: Option<Error>,
+    _phantom: PhantomData<fn() -> E>,
 }
 impl<T, E> TokenReader<T, E>
 where
@@ -20,6 +158,12 @@ where
         TokenReader {
             inner,
             unread: Vec::new(),
+            expansions: Vec::new(),
+            expanding: false,
+            seen_calls: HashSet::new(),
+            last_position: None,
+            dots: 0,
+            pending_error: None,
             _phantom: PhantomData,
         }
     }
@@ -32,6 +176,78 @@ where
     pub fn into_inner(self) -> T {
         self.inner
     }
+
+    /// Returns the macro expansions read so far, in the order of reading.
+    ///
+    /// Expansions are tracked only if the inner reader records its macro calls
+    /// (see `Preprocessor::macro_calls`).
+    pub fn macro_expansions(&self) -> &[MacroExpansion] {
+        &self.expansions
+    }
+
+    /// Returns the macro expansion which produced the token starting at `position`
+    /// in the `form`-th form of the module.
+    ///
+    /// If the same tokens are expanded more than once in the form, the first expansion is returned.
+    pub fn macro_expansion_at(&self, form: usize, position: &Position) -> Option<&MacroExpansion> {
+        self.expansions
+            .iter()
+            .filter(|e| e.form == form)
+            .find(|e| e.contains(position))
+    }
+
+    fn new_macro_call(&mut self) -> Option<MacroCall> {
+        let calls = self.inner.macro_calls()?;
+        if calls.len() == self.seen_calls.len() {
+            return None;
+        }
+        let seen = &self.seen_calls;
+        let (position, call) = self
+            .last_position
+            .as_ref()
+            .and_then(|last| {
+                calls
+                    .range(last.clone()..)
+                    .find(|(p, _)| !seen.contains(*p))
+            })
+            .or_else(|| calls.iter().find(|(p, _)| !seen.contains(*p)))?;
+        let call = call.clone();
+        self.seen_calls.insert(position.clone());
+        Some(call)
+    }
+
+    fn track_token(&mut self, token: &LexicalToken) {
+        if let Some(call) = self.new_macro_call() {
+            self.expansions.push(MacroExpansion {
+                name: call.name.value().to_owned(),
+                call: Span::of(&call),
+                form: self.dots,
+                tokens: Vec::new(),
+            });
+            self.expanding = true;
+        }
+        if self.expanding {
+            let expansion = self.expansions.last_mut().expect("never fails");
+            let position = token.start_position();
+            let call_end = &expansion.call.end;
+            if position.filepath() == call_end.filepath() && position.offset() >= call_end.offset()
+            {
+                self.expanding = false;
+            } else {
+                expansion.tokens.push(token.clone());
+            }
+        }
+        if !self.expanding {
+            self.last_position = Some(token.start_position());
+        }
+        if token
+            .as_symbol_token()
+            .map(SymbolToken::value)
+            .is_some_and(|s| s == Symbol::Dot)
+        {
+            self.dots += 1;
+        }
+    }
 }
 impl<T, E> Preprocessor for TokenReader<T, E>
 where
@@ -43,17 +259,44 @@ where
     fn undef_macro(&mut self, name: &str) {
         self.inner.undef_macro(name);
     }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        self.inner.macro_calls()
+    }
 }
 impl<T, E> TokenRead for TokenReader<T, E>
 where
-    T: Iterator<Item = std::result::Result<LexicalToken, E>> + Preprocessor,
+    T: Iterator<Item = std::result::Result<LexicalToken, E>> + Preprocessor + Send,
     Error: From<E>,
 {
     fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
-        match self.unread.pop().map(Ok).or_else(|| self.inner.next()) {
+        if let Some(t) = self.unread.pop() {
+            return Ok(Some(t));
+        }
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        match self.inner.next() {
             None => Ok(None),
             Some(Err(e)) => Err(e.into()),
-            Some(Ok(t)) => Ok(Some(t)),
+            Some(Ok(t)) => {
+                self.track_token(&t);
+                if is_placeholder(&t) {
+                    // Restores `?` of `?=` (see `MaybeMatchTokens`)
+                    match self.inner.next() {
+                        None => {}
+                        Some(Err(e)) => self.pending_error = Some(e.into()),
+                        Some(Ok(next)) => {
+                            self.track_token(&next);
+                            let question = restore_question(&t, &next);
+                            self.unread.push(next);
+                            if let Some(question) = question {
+                                return Ok(Some(question));
+                            }
+                        }
+                    }
+                }
+                Ok(Some(t))
+            }
         }
     }
     fn unread_token(&mut self, token: LexicalToken) {
@@ -0,0 +1,173 @@
+use erl_tokenize::tokens::{
+    AtomToken, CharToken, FloatToken, IntegerToken, KeywordToken, StringToken, SymbolToken,
+    VariableToken,
+};
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{LexicalToken, PositionRange};
+
+/// This trait allows for enumerating the tokens of a CST node.
+pub trait CollectTokens {
+    /// Appends the tokens of this node to `tokens` in the source order.
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>);
+
+    /// Returns the tokens of this node in the source order.
+    fn tokens(&self) -> Vec<LexicalToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    /// Generates Erlang source code of this node.
+    ///
+    /// Tokens are separated by a space or a newline (after each form),
+    /// so the original layout and comments are not preserved.
+    /// To reproduce the original text, use `cst::Trivia::reproduce` instead.
+    fn to_erlang_source(&self) -> String {
+        let tokens = self.tokens();
+        let mut text = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 {
+                if is_form_end(&tokens, i - 1) {
+                    text.push('\n');
+                } else if needs_space(&tokens, i) {
+                    text.push(' ');
+                }
+            }
+            text.push_str(token.text());
+        }
+        text
+    }
+}
+impl CollectTokens for LexicalToken {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        tokens.push(self.clone());
+    }
+}
+impl<T: CollectTokens> CollectTokens for Option<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        if let Some(ref x) = *self {
+            x.collect_tokens(tokens);
+        }
+    }
+}
+impl<T: CollectTokens> CollectTokens for Box<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        (**self).collect_tokens(tokens);
+    }
+}
+impl<T: CollectTokens> CollectTokens for Vec<T> {
+    fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+        for x in self {
+            x.collect_tokens(tokens);
+        }
+    }
+}
+
+macro_rules! impl_collect_tokens {
+    ($($t:ty),*) => {
+        $(impl CollectTokens for $t {
+            fn collect_tokens(&self, tokens: &mut Vec<LexicalToken>) {
+                tokens.push(self.clone().into());
+            }
+        })*
+    };
+}
+impl_collect_tokens!(
+    AtomToken,
+    CharToken,
+    FloatToken,
+    IntegerToken,
+    KeywordToken,
+    StringToken,
+    SymbolToken,
+    VariableToken
+);
+
+fn symbol(token: &LexicalToken) -> Option<Symbol> {
+    token.as_symbol_token().map(|t| t.value())
+}
+
+/// Returns `true` if `tokens[i]` is a `.` terminating a form.
+///
+/// The only other `.` is the one of a record field access (`#` `RecordName` `.` `AtomToken`).
+pub(crate) fn is_form_end(tokens: &[LexicalToken], i: usize) -> bool {
+    let is_record_name = i >= 2
+        && (symbol(&tokens[i - 2]) == Some(Symbol::Sharp)
+            && tokens[i - 1].as_atom_token().is_some()
+            || i >= 3
+                && symbol(&tokens[i - 3]) == Some(Symbol::Sharp)
+                && symbol(&tokens[i - 2]) == Some(Symbol::Question)
+                && (tokens[i - 1].as_atom_token().is_some()
+                    || tokens[i - 1].as_variable_token().is_some()));
+    symbol(&tokens[i]) == Some(Symbol::Dot) && !is_record_name
+}
+
+/// Returns `true` if `token` is a `.` terminating a form in `text`.
+///
+/// Unlike `is_form_end`, this looks at the character following the `.`,
+/// which is whitespace, a comment or the end of `text` unless it is the `.` of a record field access.
+pub(crate) fn is_form_end_in(text: &str, token: &LexicalToken) -> bool {
+    is_symbol(token, Symbol::Dot)
+        && text[token.end_position().offset()..]
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || c == '%')
+}
+
+/// Returns `true` if `token` is the symbol `expected`.
+pub(crate) fn is_symbol(token: &LexicalToken, expected: Symbol) -> bool {
+    symbol(token) == Some(expected)
+}
+
+/// Returns `true` if a space is needed (or preferred) between `tokens[i - 1]` and `tokens[i]`.
+pub(crate) fn needs_space(tokens: &[LexicalToken], i: usize) -> bool {
+    let prev = &tokens[i - 1];
+    let next = &tokens[i];
+    let is_form_start = i == 1 || is_form_end(tokens, i - 2);
+    if prev.as_string_token().is_some()
+        && next.as_string_token().is_some()
+        && prev.end_position() == next.start_position()
+    {
+        // The tokens of a triple-quoted string (see `cst::TripleQuotedString`)
+        return false;
+    }
+    match symbol(prev) {
+        Some(Symbol::OpenParen)
+        | Some(Symbol::OpenSquare)
+        | Some(Symbol::OpenBrace)
+        | Some(Symbol::DoubleLeftAngle)
+        | Some(Symbol::Sharp)
+        | Some(Symbol::Question)
+        | Some(Symbol::DoubleQuestion)
+        | Some(Symbol::Colon)
+        | Some(Symbol::Dot) => return false,
+        Some(Symbol::Hyphen) if is_form_start => return false,
+        _ => {}
+    }
+    match symbol(next) {
+        Some(Symbol::CloseParen)
+        | Some(Symbol::CloseSquare)
+        | Some(Symbol::CloseBrace)
+        | Some(Symbol::DoubleRightAngle)
+        | Some(Symbol::Comma)
+        | Some(Symbol::Semicolon)
+        | Some(Symbol::Colon)
+        | Some(Symbol::Dot) => false,
+        // The strict generator arrow `<:=`
+        Some(Symbol::MapMatch) => symbol(prev) != Some(Symbol::Less),
+        Some(Symbol::Sharp) => {
+            // `X#r.f`, `(X)#{}` or `#{}#{}`
+            !(prev.as_variable_token().is_some()
+                || matches!(
+                    symbol(prev),
+                    Some(Symbol::CloseParen) | Some(Symbol::CloseBrace)
+                ))
+        }
+        Some(Symbol::OpenParen) => {
+            !(prev.as_atom_token().is_some()
+                || prev.as_variable_token().is_some()
+                || symbol(prev) == Some(Symbol::CloseParen))
+        }
+        _ => true,
+    }
+}
@@ -11,6 +11,11 @@ use crate::{ErrorKind, Result};
 pub trait Expect: Sized {
     type Value: ?Sized + Debug;
     fn expect(&self, expected: &Self::Value) -> Result<()>;
+
+    /// Returns the source text of the expected token (e.g., `)` for `Symbol::CloseParen`).
+    fn describe(expected: &Self::Value) -> String {
+        format!("{:?}", expected)
+    }
 }
 impl Expect for AtomToken {
     type Value = str;
@@ -18,6 +23,9 @@ impl Expect for AtomToken {
         track_assert_eq!(self.value(), expected, ErrorKind::InvalidInput);
         Ok(())
     }
+    fn describe(expected: &Self::Value) -> String {
+        expected.to_owned()
+    }
 }
 impl Expect for CharToken {
     type Value = char;
@@ -50,6 +58,9 @@ impl Expect for KeywordToken {
         track_assert_eq!(self.value(), *expected, ErrorKind::InvalidInput);
         Ok(())
     }
+    fn describe(expected: &Self::Value) -> String {
+        expected.as_str().to_owned()
+    }
 }
 impl Expect for StringToken {
     type Value = str;
@@ -64,6 +75,9 @@ impl Expect for SymbolToken {
         track_assert_eq!(self.value(), *expected, ErrorKind::InvalidInput);
         Ok(())
     }
+    fn describe(expected: &Self::Value) -> String {
+        expected.as_str().to_owned()
+    }
 }
 impl Expect for VariableToken {
     type Value = str;
@@ -71,4 +85,7 @@ impl Expect for VariableToken {
         track_assert_eq!(self.value(), expected, ErrorKind::InvalidInput);
         Ok(())
     }
+    fn describe(expected: &Self::Value) -> String {
+        expected.to_owned()
+    }
 }
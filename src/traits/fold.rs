@@ -0,0 +1,167 @@
+use erl_tokenize::tokens::{
+    AtomToken, CharToken, FloatToken, IntegerToken, KeywordToken, StringToken, SymbolToken,
+    VariableToken,
+};
+use erl_tokenize::LexicalToken;
+
+use crate::cst::forms::FunDecl;
+use crate::cst::{Expr, Form, GuardTest, ModuleDecl, Pattern, Type};
+
+/// This trait allows for rewriting CST trees.
+///
+/// Each method receives a node and returns the node which replaces it.
+/// The default implementations rewrite the children of the node by calling `Foldable::map_children`,
+/// so an implementation only needs to override the methods for the nodes it cares about
+/// (and can call `map_children` itself to continue the traversal into their children).
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::{Expr, Literal};
+/// use erl_parse::traits::{CollectTokens, Fold, Foldable};
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::tokens::AtomToken;
+/// use erl_tokenize::{Lexer, PositionRange};
+///
+/// // Rewrites `io:format(..)` to `logger:info(..)`
+/// struct IoFormatToLogger;
+/// impl Fold for IoFormatToLogger {
+///     fn fold_expr(&mut self, expr: Expr) -> Expr {
+///         let atom = |name, old: &Expr| {
+///             let token = AtomToken::from_text(name, old.start_position()).unwrap();
+///             Expr::Literal(Literal::Atom(token))
+///         };
+///         match expr.map_children(self) {
+///             Expr::FunCall(mut call) => {
+///                 if let Some(ref mut module) = call.module {
+///                     if module.name.to_string() == "io" && call.name.to_string() == "format" {
+///                         module.name = atom("logger", &module.name);
+///                         call.name = atom("info", &call.name);
+///                     }
+///                 }
+///                 Expr::FunCall(call)
+///             }
+///             expr => expr,
+///         }
+///     }
+/// }
+///
+/// let text = r#"io:format("~p", [io:format("a")])"#;
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+/// let expr: Expr = parser.parse().unwrap();
+/// let expr = IoFormatToLogger.fold_expr(expr);
+/// assert_eq!(expr.to_erlang_source(), r#"logger:info("~p", [logger:info("a")])"#);
+/// ```
+pub trait Fold {
+    fn fold_module_decl(&mut self, module: ModuleDecl) -> ModuleDecl {
+        module.map_children(self)
+    }
+    fn fold_form(&mut self, form: Form) -> Form {
+        form.map_children(self)
+    }
+    fn fold_fun_decl(&mut self, decl: FunDecl) -> FunDecl {
+        decl.map_children(self)
+    }
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        expr.map_children(self)
+    }
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        pattern.map_children(self)
+    }
+    fn fold_guard_test(&mut self, test: GuardTest) -> GuardTest {
+        test.map_children(self)
+    }
+    fn fold_type(&mut self, ty: Type) -> Type {
+        ty.map_children(self)
+    }
+    fn fold_atom_token(&mut self, token: AtomToken) -> AtomToken {
+        token
+    }
+    fn fold_variable_token(&mut self, token: VariableToken) -> VariableToken {
+        token
+    }
+    fn fold_char_token(&mut self, token: CharToken) -> CharToken {
+        token
+    }
+    fn fold_float_token(&mut self, token: FloatToken) -> FloatToken {
+        token
+    }
+    fn fold_integer_token(&mut self, token: IntegerToken) -> IntegerToken {
+        token
+    }
+    fn fold_keyword_token(&mut self, token: KeywordToken) -> KeywordToken {
+        token
+    }
+    fn fold_string_token(&mut self, token: StringToken) -> StringToken {
+        token
+    }
+    fn fold_symbol_token(&mut self, token: SymbolToken) -> SymbolToken {
+        token
+    }
+}
+
+/// This trait is implemented by every CST node (and token) to be rewritten by a `Fold`.
+pub trait Foldable: Sized {
+    /// Rewrites the children of this node by `folder`, keeping the node itself.
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self;
+
+    /// Rewrites this node by `folder`.
+    ///
+    /// For nodes having the corresponding `Fold` method (e.g., `Expr` and `Fold::fold_expr`),
+    /// this calls the method; otherwise, this is the same as `map_children`.
+    fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        self.map_children(folder)
+    }
+}
+macro_rules! impl_foldable_token {
+    ($($t:ty => $method:ident),*) => {
+        $(impl Foldable for $t {
+            fn map_children<F: Fold + ?Sized>(self, _folder: &mut F) -> Self {
+                self
+            }
+            fn fold_with<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+                folder.$method(self)
+            }
+        })*
+    };
+}
+impl_foldable_token!(
+    AtomToken => fold_atom_token,
+    CharToken => fold_char_token,
+    FloatToken => fold_float_token,
+    IntegerToken => fold_integer_token,
+    KeywordToken => fold_keyword_token,
+    StringToken => fold_string_token,
+    SymbolToken => fold_symbol_token,
+    VariableToken => fold_variable_token
+);
+impl Foldable for LexicalToken {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        match self {
+            LexicalToken::Atom(t) => LexicalToken::Atom(t.fold_with(folder)),
+            LexicalToken::Char(t) => LexicalToken::Char(t.fold_with(folder)),
+            LexicalToken::Float(t) => LexicalToken::Float(t.fold_with(folder)),
+            LexicalToken::Integer(t) => LexicalToken::Integer(t.fold_with(folder)),
+            LexicalToken::Keyword(t) => LexicalToken::Keyword(t.fold_with(folder)),
+            LexicalToken::String(t) => LexicalToken::String(t.fold_with(folder)),
+            LexicalToken::Symbol(t) => LexicalToken::Symbol(t.fold_with(folder)),
+            LexicalToken::Variable(t) => LexicalToken::Variable(t.fold_with(folder)),
+        }
+    }
+}
+impl<T: Foldable> Foldable for Option<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        self.map(|x| x.fold_with(folder))
+    }
+}
+impl<T: Foldable> Foldable for Box<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        Box::new((*self).fold_with(folder))
+    }
+}
+impl<T: Foldable> Foldable for Vec<T> {
+    fn map_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self {
+        self.into_iter().map(|x| x.fold_with(folder)).collect()
+    }
+}
@@ -1,11 +1,16 @@
 use erl_tokenize::values::Symbol;
 
+pub use self::collect_tokens::CollectTokens;
+pub(crate) use self::collect_tokens::{is_form_end, is_form_end_in, is_symbol, needs_space};
 pub use self::expect::Expect;
+pub use self::fold::{Fold, Foldable};
 pub use self::parse::{Parse, ParseTail};
 pub use self::preprocessor::Preprocessor;
 pub use self::token_read::TokenRead;
 
+mod collect_tokens;
 mod expect;
+mod fold;
 mod parse;
 mod preprocessor;
 mod token_read;
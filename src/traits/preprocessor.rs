@@ -1,25 +1,37 @@
-use erl_pp::{self, MacroDef};
-use erl_tokenize::{Lexer, LexicalToken};
+use erl_pp::{self, MacroCall, MacroDef};
+use erl_tokenize::{Lexer, LexicalToken, Position};
+use std::collections::BTreeMap;
 
 pub trait Preprocessor {
     fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>);
     fn undef_macro(&mut self, name: &str);
+
+    /// Returns the macro calls expanded so far (keyed by their start positions), if they are recorded.
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        None
+    }
 }
-impl<'a> Preprocessor for &'a mut dyn Preprocessor {
+impl Preprocessor for &mut dyn Preprocessor {
     fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
         (*self).define_macro(name, replacement);
     }
     fn undef_macro(&mut self, name: &str) {
         (*self).undef_macro(name);
     }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        (**self).macro_calls()
+    }
 }
-impl<'a, T> Preprocessor for &'a mut erl_pp::Preprocessor<T> {
+impl<T> Preprocessor for &mut erl_pp::Preprocessor<T> {
     fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
         (*self).define_macro(name, replacement);
     }
     fn undef_macro(&mut self, name: &str) {
         (*self).undef_macro(name);
     }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        Some(erl_pp::Preprocessor::macro_calls(*self))
+    }
 }
 impl<T> Preprocessor for erl_pp::Preprocessor<T> {
     fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
@@ -29,6 +41,9 @@ impl<T> Preprocessor for erl_pp::Preprocessor<T> {
     fn undef_macro(&mut self, name: &str) {
         self.macros_mut().remove(name);
     }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        Some(erl_pp::Preprocessor::macro_calls(self))
+    }
 }
 impl<T> Preprocessor for Lexer<T> {
     fn define_macro(&mut self, _name: &str, _replacement: Vec<LexicalToken>) {}
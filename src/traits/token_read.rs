@@ -1,9 +1,15 @@
-use erl_tokenize::LexicalToken;
+use erl_pp::MacroCall;
+use erl_tokenize::{LexicalToken, Position};
+use std::collections::BTreeMap;
 
 use crate::traits::Preprocessor;
 use crate::{ErrorKind, Result};
 
-pub trait TokenRead: Preprocessor {
+/// Source of the tokens to be parsed.
+///
+/// This is `Send` because deeply nested constructs are parsed on another thread
+/// if the stack of the current thread is running low.
+pub trait TokenRead: Preprocessor + Send {
     fn try_read_token(&mut self) -> Result<Option<LexicalToken>>;
     fn read_token(&mut self) -> Result<LexicalToken> {
         if let Some(token) = track!(self.try_read_token())? {
@@ -14,15 +20,18 @@ pub trait TokenRead: Preprocessor {
     }
     fn unread_token(&mut self, token: LexicalToken);
 }
-impl<'a> Preprocessor for &'a mut dyn TokenRead {
+impl Preprocessor for &mut dyn TokenRead {
     fn define_macro(&mut self, name: &str, replacement: Vec<LexicalToken>) {
         (*self).define_macro(name, replacement);
     }
     fn undef_macro(&mut self, name: &str) {
         (*self).undef_macro(name);
     }
+    fn macro_calls(&self) -> Option<&BTreeMap<Position, MacroCall>> {
+        (**self).macro_calls()
+    }
 }
-impl<'a> TokenRead for &'a mut dyn TokenRead {
+impl TokenRead for &mut dyn TokenRead {
     fn try_read_token(&mut self) -> Result<Option<LexicalToken>> {
         (*self).try_read_token()
     }
@@ -0,0 +1,104 @@
+//! Emulation of parse transforms.
+//!
+//! Parse transforms (`-compile({parse_transform, Module}).`) are Erlang modules run by the compiler,
+//! so the code seen by this crate differs from the compiled one.
+//! Embedders can register Rust-side emulators of known transforms (e.g., `lager_transform`)
+//! to a `TransformRegistry`, which rewrites parsed modules as the transforms would,
+//! so that analyses over the modules see realistic code.
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cst::ModuleDecl;
+
+/// Emulator of a parse transform, which rewrites a module.
+///
+/// Functions of `ModuleDecl -> ModuleDecl` are emulators,
+/// so a `Fold` implementation can be registered as `|m| MyFold.fold_module_decl(m)`.
+pub trait TransformEmulator {
+    fn transform(&self, module: ModuleDecl) -> ModuleDecl;
+}
+impl<F> TransformEmulator for F
+where
+    F: Fn(ModuleDecl) -> ModuleDecl,
+{
+    fn transform(&self, module: ModuleDecl) -> ModuleDecl {
+        self(module)
+    }
+}
+
+/// Emulators of parse transforms keyed by the names of the transform modules.
+///
+/// # Examples
+///
+/// ```
+/// use erl_parse::cst::{Form, ModuleDecl};
+/// use erl_parse::transform::TransformRegistry;
+/// use erl_parse::{Parser, TokenReader};
+/// use erl_pp::Preprocessor;
+/// use erl_tokenize::Lexer;
+///
+/// let text = "-module(foo).\n-compile([{parse_transform, drop_funs}, {parse_transform, other}]).\nf() -> ok.";
+/// let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+/// let module: ModuleDecl = parser.parse().unwrap();
+/// assert_eq!(module.parse_transforms(), ["drop_funs", "other"]);
+///
+/// let mut registry = TransformRegistry::new();
+/// registry.register("drop_funs", |mut m: ModuleDecl| {
+///     m.forms.retain(|f| !matches!(*f, Form::FunDecl(_)));
+///     m
+/// });
+/// let (module, unknown) = registry.apply(module);
+/// assert_eq!(module.forms.len(), 2);
+/// assert_eq!(unknown, ["other"]);
+/// ```
+#[derive(Default)]
+pub struct TransformRegistry {
+    emulators: HashMap<String, Box<dyn TransformEmulator + Send + Sync>>,
+}
+impl TransformRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `emulator` as the emulator of the parse transform `module`,
+    /// replacing the previously registered one (if any).
+    pub fn register<E>(&mut self, module: &str, emulator: E)
+    where
+        E: TransformEmulator + Send + Sync + 'static,
+    {
+        self.emulators.insert(module.to_owned(), Box::new(emulator));
+    }
+
+    pub fn is_registered(&self, module: &str) -> bool {
+        self.emulators.contains_key(module)
+    }
+
+    /// Applies the emulators of the parse transforms specified in `module` in the order of the specification.
+    ///
+    /// Returns the rewritten module and the transforms which have no emulators (and are thus skipped).
+    pub fn apply(&self, module: ModuleDecl) -> (ModuleDecl, Vec<String>) {
+        let transforms = module
+            .parse_transforms()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let mut module = module;
+        let mut unknown = Vec::new();
+        for name in transforms {
+            match self.emulators.get(&name) {
+                Some(emulator) => module = emulator.transform(module),
+                None => unknown.push(name),
+            }
+        }
+        (module, unknown)
+    }
+}
+impl fmt::Debug for TransformRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names = self.emulators.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_struct("TransformRegistry")
+            .field("emulators", &names)
+            .finish()
+    }
+}
@@ -0,0 +1,161 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+/// An OTP application found in a workspace.
+#[derive(Debug, Clone)]
+pub struct App {
+    pub name: String,
+    pub root: PathBuf,
+    pub src_dirs: Vec<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Applications listed in the `applications` and `included_applications` entries
+    /// of the `.app.src` (or `.app`) file.
+    pub deps: Vec<String>,
+}
+impl App {
+    /// Tries to interpret `dir` as the root directory of an OTP application.
+    ///
+    /// A directory is regarded as an application if it has `src/*.app.src` or `ebin/*.app`.
+    /// If `force` is `true`, a directory having a `src/` subdirectory is also accepted
+    /// and the application name is derived from the directory name.
+    pub fn detect<P: AsRef<Path>>(dir: P, force: bool) -> Result<Option<Self>> {
+        let root = dir.as_ref().to_path_buf();
+        let src = root.join("src");
+        let app_file = track!(find_file_with_suffix(&src, ".app.src"))?
+            .map(|p| (p, ".app.src"))
+            .or(track!(find_file_with_suffix(&root.join("ebin"), ".app"))?.map(|p| (p, ".app")));
+        let (name, deps) = if let Some((path, suffix)) = app_file {
+            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let name = file_name[..file_name.len() - suffix.len()].to_owned();
+            let text = track!(fs::read_to_string(&path).map_err(crate::Error::from))?;
+            (name, scan_app_deps(&text))
+        } else if force && src.is_dir() {
+            let dir_name = root.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let name = dir_name.split('-').next().unwrap_or(dir_name).to_owned();
+            (name, Vec::new())
+        } else {
+            return Ok(None);
+        };
+
+        let mut src_dirs = Vec::new();
+        if src.is_dir() {
+            src_dirs.push(src);
+        }
+        let mut include_dirs = Vec::new();
+        let include = root.join("include");
+        if include.is_dir() {
+            include_dirs.push(include);
+        }
+        Ok(Some(App {
+            name,
+            root,
+            src_dirs,
+            include_dirs,
+            deps,
+        }))
+    }
+
+    /// Returns `true` if `path` is located under the root directory of this application.
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        path.as_ref().starts_with(&self.root)
+    }
+
+    /// Returns `true` if this application declares `app` as a dependency (or is `app` itself).
+    pub fn depends_on(&self, app: &str) -> bool {
+        self.name == app || self.deps.iter().any(|d| d == app)
+    }
+
+    /// Returns the `.erl` files under the source directories of this application.
+    pub fn source_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for dir in &self.src_dirs {
+            track!(collect_files(dir, "erl", &mut files))?;
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Returns the `.hrl` files under the source and include directories of this application.
+    pub fn header_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for dir in self.include_dirs.iter().chain(self.src_dirs.iter()) {
+            track!(collect_files(dir, "hrl", &mut files))?;
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Returns the first (in path order) file in `dir` whose name ends with `suffix`.
+fn find_file_with_suffix(dir: &Path, suffix: &str) -> Result<Option<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut found = None;
+    for entry in track!(fs::read_dir(dir).map_err(crate::Error::from))? {
+        let path = track!(entry.map_err(crate::Error::from))?.path();
+        let matched = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with(suffix));
+        if matched && path.is_file() && found.as_ref().is_none_or(|f| path < *f) {
+            found = Some(path);
+        }
+    }
+    Ok(found)
+}
+
+pub(crate) fn collect_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in track!(fs::read_dir(dir).map_err(crate::Error::from))? {
+        let path = track!(entry.map_err(crate::Error::from))?.path();
+        if path.is_dir() {
+            if !is_symlink(&path) {
+                track!(collect_files(&path, extension, files))?;
+            }
+        } else if path.extension().and_then(|e| e.to_str()) == Some(extension) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if `path` is a symbolic link (not to be followed when searching directories).
+pub(crate) fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Extracts the atoms of `{applications, [...]}` and `{included_applications, [...]}`.
+fn scan_app_deps(text: &str) -> Vec<String> {
+    let tokens = Lexer::new(text)
+        .take_while(|t| t.is_ok())
+        .filter_map(|t| t.ok())
+        .collect::<Vec<_>>();
+    let mut deps = Vec::new();
+    for (i, t) in tokens.iter().enumerate() {
+        let key = t.as_atom_token().map(|t| t.value());
+        if key != Some("applications") && key != Some("included_applications") {
+            continue;
+        }
+        let is_list = tokens.get(i + 1).map(LexicalToken::text) == Some(",")
+            && tokens.get(i + 2).map(LexicalToken::text) == Some("[");
+        if !is_list {
+            continue;
+        }
+        for t in &tokens[i + 3..] {
+            match *t {
+                LexicalToken::Atom(ref a) => deps.push(a.value().to_owned()),
+                LexicalToken::Symbol(ref s) if s.value() == Symbol::Comma => {}
+                _ => break,
+            }
+        }
+    }
+    deps
+}
@@ -0,0 +1,802 @@
+use erl_tokenize::values::{Keyword, Symbol};
+use erl_tokenize::{Lexer, LexicalToken, Position, PositionRange};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::traits::{is_form_end_in, is_symbol};
+use crate::{Error, ErrorKind, Result};
+
+/// Version of the on-disk index format.
+///
+/// Index files written with a different version are ignored by `WorkspaceIndex::read_from`.
+pub const INDEX_FORMAT_VERSION: u32 = 3;
+
+const MAGIC: &str = "erl_parse_index";
+
+/// Line and column (both 1-origin) of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+impl Location {
+    pub(super) fn of(position: &Position) -> Self {
+        Location {
+            line: position.line(),
+            column: position.column(),
+        }
+    }
+}
+
+/// A function, type, record or macro defined in a file.
+///
+/// `arity` is `None` for records and macros without parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    pub name: String,
+    pub arity: Option<usize>,
+    pub location: Location,
+}
+
+/// A function call (or `fun` reference) found in a function body.
+///
+/// `module` is `None` for local calls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRef {
+    pub module: Option<String>,
+    pub name: String,
+    pub arity: usize,
+    pub location: Location,
+}
+
+/// Symbols defined and referred in a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileIndex {
+    /// Modification time of the file (nanoseconds since the UNIX epoch) at the time of indexing.
+    ///
+    /// `0` if the index was not built from the file on disk (see `Workspace::update_file`).
+    pub mtime: u64,
+
+    /// Length of the file (in bytes) at the time of indexing.
+    pub len: u64,
+
+    /// `content_hash` of the indexed text.
+    pub hash: u64,
+    pub module: Option<String>,
+    pub functions: Vec<Definition>,
+    pub types: Vec<Definition>,
+    pub records: Vec<Definition>,
+    pub macros: Vec<Definition>,
+    pub calls: Vec<CallRef>,
+}
+impl FileIndex {
+    /// Builds the index of `text`.
+    ///
+    /// Like `analysis::scan_dependencies`, this works on the token level
+    /// and neither preprocesses `text` nor builds a CST.
+    pub fn build(text: &str) -> Result<Self> {
+        let mut index = FileIndex {
+            hash: content_hash(text),
+            ..FileIndex::default()
+        };
+        let mut form = Vec::new();
+        for token in Lexer::new(text) {
+            let token = track!(token.map_err(Error::from))?;
+            if is_form_end_in(text, &token) {
+                index.handle_form(&form);
+                form.clear();
+            } else {
+                form.push(token);
+            }
+        }
+        index.handle_form(&form);
+        Ok(index)
+    }
+
+    fn handle_form(&mut self, form: &[LexicalToken]) {
+        if form.len() >= 2 && is_symbol(&form[0], Symbol::Hyphen) {
+            self.handle_attr(form);
+        } else if form.len() >= 2 && form[0].as_atom_token().is_some() {
+            self.handle_fun_decl(form);
+        }
+    }
+
+    fn handle_attr(&mut self, form: &[LexicalToken]) {
+        let name = form[1].as_atom_token().map_or("", |t| t.value());
+        let mut i = 2;
+        if form.get(i).is_some_and(|t| is_symbol(t, Symbol::OpenParen)) {
+            i += 1;
+        }
+        if name == "define" {
+            self.handle_define(form, i);
+            return;
+        }
+        let target = match form.get(i).and_then(LexicalToken::as_atom_token) {
+            None => return,
+            Some(t) => t,
+        };
+        match name {
+            "module" => self.module = Some(target.value().to_owned()),
+            "record" => self.records.push(Definition {
+                name: target.value().to_owned(),
+                arity: None,
+                location: Location::of(&target.start_position()),
+            }),
+            "type" | "opaque" | "nominal" => {
+                let arity = form
+                    .get(i + 1)
+                    .filter(|t| is_symbol(t, Symbol::OpenParen))
+                    .map(|_| count_args(form, i + 1).0);
+                if let Some(arity) = arity {
+                    self.types.push(Definition {
+                        name: target.value().to_owned(),
+                        arity: Some(arity),
+                        location: Location::of(&target.start_position()),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_define(&mut self, form: &[LexicalToken], i: usize) {
+        let target = match form.get(i) {
+            Some(t @ LexicalToken::Atom(_)) | Some(t @ LexicalToken::Variable(_)) => t,
+            _ => return,
+        };
+        let arity = form
+            .get(i + 1)
+            .filter(|t| is_symbol(t, Symbol::OpenParen))
+            .map(|_| count_args(form, i + 1).0);
+        let name = match *target {
+            LexicalToken::Atom(ref t) => t.value(),
+            _ => target.text(),
+        };
+        self.macros.push(Definition {
+            name: name.to_owned(),
+            arity,
+            location: Location::of(&target.start_position()),
+        });
+    }
+
+    fn handle_fun_decl(&mut self, form: &[LexicalToken]) {
+        if !is_symbol(&form[1], Symbol::OpenParen) {
+            return;
+        }
+        let (arity, body_start) = count_args(form, 1);
+        self.functions.push(Definition {
+            name: form[0].text().to_owned(),
+            arity: Some(arity),
+            location: Location::of(&form[0].start_position()),
+        });
+
+        let mut depth = 0usize;
+        let mut i = body_start;
+        while i < form.len() {
+            let token = &form[i];
+            if opens_block(form, i) {
+                depth += 1;
+            } else if closes_block(token) {
+                depth = depth.saturating_sub(1);
+            } else if depth == 0 && is_symbol(token, Symbol::Semicolon) {
+                // The head of the next clause
+                if form
+                    .get(i + 2)
+                    .is_some_and(|t| is_symbol(t, Symbol::OpenParen))
+                {
+                    i = count_args(form, i + 2).1;
+                    continue;
+                }
+            } else if let Some(call) = call_at(form, i) {
+                self.calls.push(call);
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `text`.
+///
+/// Unlike `std::collections::hash_map::DefaultHasher`, the result is stable across Rust releases.
+pub fn content_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Kind of a symbol returned by `WorkspaceIndex::workspace_symbols`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SymbolKind {
+    Function,
+    Record,
+    Type,
+    Macro,
+}
+
+/// A symbol matched by `WorkspaceIndex::workspace_symbols`.
+#[derive(Debug, Clone)]
+pub struct SymbolMatch<'a> {
+    pub kind: SymbolKind,
+    pub path: &'a Path,
+
+    /// The name of the module defining the symbol (`None` for header files).
+    pub module: Option<&'a str>,
+    pub definition: &'a Definition,
+
+    /// The larger, the better.
+    pub score: u32,
+}
+
+/// Matches `query` against `name` as a case-insensitive subsequence.
+///
+/// Returns `None` if `query` does not match.
+/// Otherwise, each matched character adds one point, with bonuses for matches
+/// at the beginning of `name`, at word boundaries (after `_` or at a lowercase-to-uppercase change)
+/// and right after the previous match. An exact (case-insensitive) match gets the highest score.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    let query = query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    let mut score = 0;
+    let mut matched = 0;
+    let mut prev: Option<char> = None;
+    let mut prev_matched = false;
+    for c in name.chars() {
+        let is_match = matched < query.len() && c.to_lowercase().eq(Some(query[matched]));
+        if is_match {
+            score += 1;
+            match prev {
+                None => score += 8,
+                Some(p) if p == '_' || (p.is_lowercase() && c.is_uppercase()) => score += 6,
+                _ => {}
+            }
+            if prev_matched {
+                score += 4;
+            }
+            matched += 1;
+        }
+        prev_matched = is_match;
+        prev = Some(c);
+    }
+    if matched < query.len() {
+        return None;
+    }
+    if name.chars().count() == query.len() {
+        score += 100;
+    }
+    Some(score)
+}
+
+/// Indices of the files in a workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceIndex {
+    files: BTreeMap<PathBuf, FileIndex>,
+}
+impl WorkspaceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn files(&self) -> &BTreeMap<PathBuf, FileIndex> {
+        &self.files
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&FileIndex> {
+        self.files.get(path.as_ref())
+    }
+
+    pub fn insert(&mut self, path: PathBuf, index: FileIndex) {
+        self.files.insert(path, index);
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<FileIndex> {
+        self.files.remove(path.as_ref())
+    }
+
+    /// Returns the definitions of the function `module:name/arity`.
+    ///
+    /// If `arity` is `None`, functions of any arity are returned.
+    pub fn find_functions(
+        &self,
+        module: &str,
+        name: &str,
+        arity: Option<usize>,
+    ) -> Vec<(&Path, &Definition)> {
+        self.files
+            .iter()
+            .filter(|(_, f)| f.module.as_deref() == Some(module))
+            .flat_map(|(path, f)| f.functions.iter().map(move |d| (path.as_path(), d)))
+            .filter(|(_, d)| d.name == name && (arity.is_none() || d.arity == arity))
+            .collect()
+    }
+
+    /// Returns the calls (and `fun` references) to the function `module:name/arity`.
+    ///
+    /// Local calls are taken into account if they are made in `module` itself.
+    /// If `arity` is `None`, calls of any arity are returned.
+    pub fn find_callers(
+        &self,
+        module: &str,
+        name: &str,
+        arity: Option<usize>,
+    ) -> Vec<(&Path, &CallRef)> {
+        self.files
+            .iter()
+            .flat_map(|(path, f)| {
+                let is_local = f.module.as_deref() == Some(module);
+                f.calls
+                    .iter()
+                    .filter(move |c| {
+                        c.module.as_deref().map_or(is_local, |m| m == module)
+                            && c.name == name
+                            && arity.is_none_or(|a| a == c.arity)
+                    })
+                    .map(move |c| (path.as_path(), c))
+            })
+            .collect()
+    }
+
+    /// Returns the definitions of the record `name`.
+    pub fn find_records(&self, name: &str) -> Vec<(&Path, &Definition)> {
+        self.files
+            .iter()
+            .flat_map(|(path, f)| f.records.iter().map(move |d| (path.as_path(), d)))
+            .filter(|(_, d)| d.name == name)
+            .collect()
+    }
+
+    /// Returns the functions, records, types and macros whose names fuzzily match `query`.
+    ///
+    /// The characters of `query` must appear in the name in the same order, ignoring case.
+    /// Matches are ranked by `fuzzy_score` (best first), then by name length.
+    /// An empty `query` matches every symbol.
+    pub fn workspace_symbols(&self, query: &str) -> Vec<SymbolMatch<'_>> {
+        let mut matches = Vec::new();
+        for (path, f) in &self.files {
+            let kinds = [
+                (SymbolKind::Function, &f.functions),
+                (SymbolKind::Record, &f.records),
+                (SymbolKind::Type, &f.types),
+                (SymbolKind::Macro, &f.macros),
+            ];
+            for &(kind, defs) in &kinds {
+                for d in defs.iter() {
+                    if let Some(score) = fuzzy_score(query, &d.name) {
+                        matches.push(SymbolMatch {
+                            kind,
+                            path,
+                            module: f.module.as_deref(),
+                            definition: d,
+                            score,
+                        });
+                    }
+                }
+            }
+        }
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.definition.name.len().cmp(&b.definition.name.len()))
+                .then_with(|| a.definition.name.cmp(&b.definition.name))
+        });
+        matches
+    }
+
+    /// Re-indexes `path` if it has been modified since the last indexing.
+    ///
+    /// The modification time and the length are checked first and the content hash only if they differ,
+    /// so unchanged files are not read at all.
+    /// Returns `true` if the index of the file was (re)built.
+    pub fn refresh<P: AsRef<Path>>(&mut self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+        let (mtime, len) = track!(file_stamp(path))?;
+        if let Some(index) = self.files.get_mut(path) {
+            if index.mtime == mtime && index.len == len {
+                return Ok(false);
+            }
+            let text = track!(fs::read_to_string(path).map_err(Error::from))?;
+            if index.hash == content_hash(&text) {
+                index.mtime = mtime;
+                index.len = len;
+                return Ok(false);
+            }
+            *index = track!(FileIndex::build(&text))?;
+            index.mtime = mtime;
+            index.len = len;
+        } else {
+            let text = track!(fs::read_to_string(path).map_err(Error::from))?;
+            let mut index = track!(FileIndex::build(&text))?;
+            index.mtime = mtime;
+            index.len = len;
+            self.files.insert(path.to_path_buf(), index);
+        }
+        Ok(true)
+    }
+
+    /// Writes this index in the on-disk format.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut out = format!("{}\t{}\n", MAGIC, INDEX_FORMAT_VERSION);
+        for (path, index) in &self.files {
+            out += &format!(
+                "file\t{}\t{}\t{}\t{:016x}\n",
+                escape(&path.to_string_lossy()),
+                index.mtime,
+                index.len,
+                index.hash
+            );
+            if let Some(ref module) = index.module {
+                out += &format!("module\t{}\n", escape(module));
+            }
+            for (tag, defs) in &[("function", &index.functions), ("type", &index.types)] {
+                for d in defs.iter() {
+                    out += &format!(
+                        "{}\t{}\t{}\t{}\t{}\n",
+                        tag,
+                        escape(&d.name),
+                        d.arity.unwrap_or(0),
+                        d.location.line,
+                        d.location.column
+                    );
+                }
+            }
+            for d in &index.records {
+                out += &format!(
+                    "record\t{}\t{}\t{}\n",
+                    escape(&d.name),
+                    d.location.line,
+                    d.location.column
+                );
+            }
+            for d in &index.macros {
+                let arity = d.arity.map_or_else(String::new, |a| a.to_string());
+                out += &format!(
+                    "macro\t{}\t{}\t{}\t{}\n",
+                    escape(&d.name),
+                    arity,
+                    d.location.line,
+                    d.location.column
+                );
+            }
+            for c in &index.calls {
+                out += &format!(
+                    "call\t{}\t{}\t{}\t{}\t{}\n",
+                    escape(c.module.as_deref().unwrap_or("")),
+                    escape(&c.name),
+                    c.arity,
+                    c.location.line,
+                    c.location.column
+                );
+            }
+        }
+        track!(writer.write_all(out.as_bytes()).map_err(Error::from))?;
+        Ok(())
+    }
+
+    /// Reads an index written by `write_to`.
+    ///
+    /// Returns `Ok(None)` if the index was written in another format version.
+    pub fn read_from<R: BufRead>(reader: R) -> Result<Option<Self>> {
+        let mut lines = reader.lines();
+        let header = match lines.next() {
+            None => return Ok(None),
+            Some(line) => track!(line.map_err(Error::from))?,
+        };
+        let version = header
+            .strip_prefix(MAGIC)
+            .and_then(|v| v.strip_prefix('\t'))
+            .and_then(|v| v.parse::<u32>().ok());
+        track_assert!(
+            version.is_some(),
+            ErrorKind::InvalidInput,
+            "Not an index file"
+        );
+        if version != Some(INDEX_FORMAT_VERSION) {
+            return Ok(None);
+        }
+
+        let mut this = WorkspaceIndex::new();
+        let mut current = None;
+        for (i, line) in lines.enumerate() {
+            let line = track!(line.map_err(Error::from))?;
+            let fields = line.split('\t').map(unescape).collect::<Vec<_>>();
+            let fields = fields.iter().map(String::as_str).collect::<Vec<_>>();
+            let entry = track!(parse_entry(&fields), "line={}", i + 2)?;
+            if let Entry::File(path, index) = entry {
+                current = Some(path.clone());
+                this.files.insert(path, index);
+                continue;
+            }
+            let path = track_assert_some!(current.as_ref(), ErrorKind::InvalidInput);
+            let index = this.files.get_mut(path).expect("Never fails");
+            match entry {
+                Entry::File(..) => unreachable!(),
+                Entry::Module(m) => index.module = Some(m),
+                Entry::Function(d) => index.functions.push(d),
+                Entry::Type(d) => index.types.push(d),
+                Entry::Record(d) => index.records.push(d),
+                Entry::Macro(d) => index.macros.push(d),
+                Entry::Call(c) => index.calls.push(c),
+            }
+        }
+        Ok(Some(this))
+    }
+
+    /// Saves this index to the file `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = track!(fs::File::create(path).map_err(Error::from))?;
+        track!(self.write_to(std::io::BufWriter::new(file)))
+    }
+
+    /// Loads an index from the file `path`.
+    ///
+    /// Returns `Ok(None)` if the file does not exist or has an incompatible format version.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        let file = match fs::File::open(path) {
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            file => track!(file.map_err(Error::from))?,
+        };
+        track!(Self::read_from(std::io::BufReader::new(file)))
+    }
+}
+
+/// Returns the modification time (nanoseconds since the UNIX epoch) and the length of `path`.
+///
+/// Both are compared since the resolution of the modification time depends on the file system.
+pub(crate) fn file_stamp(path: &Path) -> Result<(u64, u64)> {
+    let metadata = track!(fs::metadata(path).map_err(Error::from))?;
+    let mtime = track!(metadata.modified().map_err(Error::from))?;
+    let nanos = mtime
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX));
+    Ok((nanos, metadata.len()))
+}
+
+enum Entry {
+    File(PathBuf, FileIndex),
+    Module(String),
+    Function(Definition),
+    Type(Definition),
+    Record(Definition),
+    Macro(Definition),
+    Call(CallRef),
+}
+
+fn parse_entry(fields: &[&str]) -> Result<Entry> {
+    fn num<T: std::str::FromStr>(s: &str) -> Result<T> {
+        s.parse().map_err(|_| {
+            track!(
+                Error::from(ErrorKind::InvalidInput),
+                "Not a number: {:?}",
+                s
+            )
+        })
+    }
+    let location = |line: &str, column: &str| -> Result<Location> {
+        Ok(Location {
+            line: track!(num(line))?,
+            column: track!(num(column))?,
+        })
+    };
+    Ok(match *fields {
+        ["file", path, mtime, len, hash] => {
+            let hash = track!(
+                u64::from_str_radix(hash, 16).map_err(|_| Error::from(ErrorKind::InvalidInput))
+            )?;
+            let index = FileIndex {
+                mtime: track!(num(mtime))?,
+                len: track!(num(len))?,
+                hash,
+                ..FileIndex::default()
+            };
+            Entry::File(PathBuf::from(path), index)
+        }
+        ["module", module] => Entry::Module(module.to_owned()),
+        [tag @ "function", name, arity, line, column]
+        | [tag @ "type", name, arity, line, column] => {
+            let d = Definition {
+                name: name.to_owned(),
+                arity: Some(track!(num(arity))?),
+                location: track!(location(line, column))?,
+            };
+            if tag == "function" {
+                Entry::Function(d)
+            } else {
+                Entry::Type(d)
+            }
+        }
+        ["record", name, line, column] => Entry::Record(Definition {
+            name: name.to_owned(),
+            arity: None,
+            location: track!(location(line, column))?,
+        }),
+        ["macro", name, arity, line, column] => Entry::Macro(Definition {
+            name: name.to_owned(),
+            arity: if arity.is_empty() {
+                None
+            } else {
+                Some(track!(num(arity))?)
+            },
+            location: track!(location(line, column))?,
+        }),
+        ["call", module, name, arity, line, column] => Entry::Call(CallRef {
+            module: if module.is_empty() {
+                None
+            } else {
+                Some(module.to_owned())
+            },
+            name: name.to_owned(),
+            arity: track!(num(arity))?,
+            location: track!(location(line, column))?,
+        }),
+        _ => track_panic!(ErrorKind::InvalidInput, "Malformed entry: {:?}", fields),
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// Returns the number of the arguments enclosed by the parenthesis at `form[open]`
+/// and the index of the token following the closing parenthesis.
+pub(super) fn count_args(form: &[LexicalToken], open: usize) -> (usize, usize) {
+    let mut depth = 0;
+    let mut arity = 0;
+    let mut i = open;
+    while i < form.len() {
+        let token = &form[i];
+        if opens_block(form, i) {
+            depth += 1;
+            if depth == 1 {
+                let is_empty = form
+                    .get(i + 1)
+                    .is_some_and(|t| is_symbol(t, Symbol::CloseParen));
+                arity = if is_empty { 0 } else { 1 };
+            }
+        } else if closes_block(token) {
+            depth -= 1;
+            if depth == 0 {
+                return (arity, i + 1);
+            }
+        } else if depth == 1 && is_symbol(token, Symbol::Comma) {
+            arity += 1;
+        }
+        i += 1;
+    }
+    (arity, i)
+}
+
+pub(super) fn opens_block(form: &[LexicalToken], i: usize) -> bool {
+    match form[i] {
+        LexicalToken::Symbol(ref t) => matches!(
+            t.value(),
+            Symbol::OpenParen | Symbol::OpenSquare | Symbol::OpenBrace | Symbol::DoubleLeftAngle
+        ),
+        LexicalToken::Keyword(ref t) => match t.value() {
+            Keyword::Begin | Keyword::Case | Keyword::If | Keyword::Receive | Keyword::Try => true,
+            Keyword::Fun => {
+                // `fun (...) -> ... end` or `fun Name(...) -> ... end`, but not `fun foo/1`
+                let next = form.get(i + 1);
+                next.is_some_and(|t| is_symbol(t, Symbol::OpenParen))
+                    || (next.and_then(LexicalToken::as_variable_token).is_some()
+                        && form
+                            .get(i + 2)
+                            .is_some_and(|t| is_symbol(t, Symbol::OpenParen)))
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub(super) fn closes_block(token: &LexicalToken) -> bool {
+    match *token {
+        LexicalToken::Symbol(ref t) => matches!(
+            t.value(),
+            Symbol::CloseParen
+                | Symbol::CloseSquare
+                | Symbol::CloseBrace
+                | Symbol::DoubleRightAngle
+        ),
+        LexicalToken::Keyword(ref t) => t.value() == Keyword::End,
+        _ => false,
+    }
+}
+
+/// Recognizes `name(...)`, `module:name(...)`, `fun name/N` and `fun module:name/N` at `form[i]`.
+fn call_at(form: &[LexicalToken], i: usize) -> Option<CallRef> {
+    let prev = |n: usize| i.checked_sub(n).map(|j| &form[j]);
+    let is_variable =
+        |t: &LexicalToken, name: &str| t.as_variable_token().map(|v| v.value()) == Some(name);
+    let is_fun_ref = form[i].as_keyword_token().map(|t| t.value()) == Some(Keyword::Fun);
+    if is_fun_ref {
+        let (module, name_index) = match form.get(i + 2) {
+            Some(t) if is_symbol(t, Symbol::Colon) => (
+                Some(form.get(i + 1)?.as_atom_token()?.value().to_owned()),
+                i + 3,
+            ),
+            _ => (None, i + 1),
+        };
+        let name = form.get(name_index)?.as_atom_token()?;
+        if !is_symbol(form.get(name_index + 1)?, Symbol::Slash) {
+            return None;
+        }
+        let arity = form.get(name_index + 2)?.as_integer_token()?.value();
+        return Some(CallRef {
+            module,
+            name: name.value().to_owned(),
+            arity: arity.to_string().parse().ok()?,
+            location: Location::of(&form[i].start_position()),
+        });
+    }
+
+    let name = form[i].as_atom_token()?;
+    if !form
+        .get(i + 1)
+        .is_some_and(|t| is_symbol(t, Symbol::OpenParen))
+    {
+        return None;
+    }
+    let after = |symbol| prev(1).is_some_and(|t| is_symbol(t, symbol));
+    if after(Symbol::Sharp) || after(Symbol::Question) || after(Symbol::Colon) && prev(2).is_none()
+    {
+        return None;
+    }
+    let (module, start) = if after(Symbol::Colon) {
+        if is_variable(prev(2)?, "MODULE")
+            && prev(3).is_some_and(|t| is_symbol(t, Symbol::Question))
+        {
+            // `?MODULE:name(...)`
+            return Some(CallRef {
+                module: None,
+                name: name.value().to_owned(),
+                arity: count_args(form, i + 1).0,
+                location: Location::of(&prev(3)?.start_position()),
+            });
+        }
+        let module = prev(2)?.as_atom_token()?;
+        if prev(3).is_some_and(|t| is_symbol(t, Symbol::Colon)) {
+            return None;
+        }
+        (Some(module.value().to_owned()), prev(2)?.start_position())
+    } else {
+        if prev(1)
+            .and_then(LexicalToken::as_keyword_token)
+            .map(|t| t.value())
+            == Some(Keyword::Fun)
+        {
+            return None;
+        }
+        (None, name.start_position())
+    };
+    Some(CallRef {
+        module,
+        name: name.value().to_owned(),
+        arity: count_args(form, i + 1).0,
+        location: Location::of(&start),
+    })
+}
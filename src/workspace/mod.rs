@@ -0,0 +1,396 @@
+//! Multi-root workspace model.
+//!
+//! A workspace consists of one or more root directories, each of which contains
+//! OTP applications (e.g., the project itself, `apps/*` of an umbrella project, or `_build/*/lib/*`).
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use self::app::App;
+pub use self::index::{
+    content_hash, fuzzy_score, CallRef, Definition, FileIndex, Location, SymbolKind, SymbolMatch,
+    WorkspaceIndex, INDEX_FORMAT_VERSION,
+};
+pub use self::resolve::ResolvedDefinition;
+
+use crate::analysis;
+use crate::Result;
+
+mod app;
+mod index;
+mod resolve;
+
+/// A set of OTP applications discovered from root directories.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    roots: Vec<PathBuf>,
+    apps: Vec<App>,
+
+    /// The source files of each application found when it was added (or the index was refreshed).
+    app_sources: BTreeMap<String, Vec<PathBuf>>,
+    modules: BTreeMap<String, usize>,
+    files: BTreeMap<PathBuf, analysis::Dependencies>,
+    index: WorkspaceIndex,
+}
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `root` to this workspace and discovers the applications under it.
+    ///
+    /// `root` itself is treated as an application if it has a `src/` directory.
+    pub fn add_root<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
+        let root = root.as_ref().to_path_buf();
+        if let Some(app) = track!(App::detect(&root, true))? {
+            track!(self.add_app(app))?;
+        }
+        track!(self.walk(&root))?;
+        self.roots.push(root);
+        Ok(())
+    }
+
+    /// Adds `app` to this workspace.
+    ///
+    /// If an application with the same name already exists, it is replaced.
+    pub fn add_app(&mut self, app: App) -> Result<()> {
+        let sources = track!(app.source_files())?;
+        self.app_sources.insert(app.name.clone(), sources);
+        if let Some(i) = self.apps.iter().position(|a| a.name == app.name) {
+            self.apps[i] = app;
+        } else {
+            self.apps.push(app);
+        }
+        self.apps.sort_by(|a, b| a.name.cmp(&b.name));
+        self.rebuild_module_index();
+        Ok(())
+    }
+
+    /// Updates the content of the file `path` and refreshes the indices depending on it.
+    ///
+    /// Returns the files whose analysis results may have been changed by this update,
+    /// i.e., `path` itself and the files which (transitively) include it.
+    pub fn update_file<P: AsRef<Path>>(&mut self, path: P, text: &str) -> Result<Vec<PathBuf>> {
+        let path = path.as_ref().to_path_buf();
+        let deps = track!(analysis::scan_dependencies(text))?;
+        // `text` may differ from the file on disk (e.g., an unsaved editor buffer),
+        // so the mtime is left `0` for `refresh_index` to recheck the file
+        let file_index = track!(FileIndex::build(text))?;
+        self.index.insert(path.clone(), file_index);
+
+        let old = self.files.insert(path.clone(), deps);
+        let new = &self.files[&path];
+        let module_changed = old.as_ref().map(|d| &d.module) != Some(&new.module);
+        if old.is_none() || module_changed {
+            self.rebuild_module_index();
+        }
+        Ok(self.affected_files(&path))
+    }
+
+    /// Forgets the file `path`.
+    ///
+    /// Returns the files whose analysis results may have been changed by this removal.
+    pub fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> Vec<PathBuf> {
+        let path = path.as_ref();
+        let mut affected = self.affected_files(path);
+        self.index.remove(path);
+        let mut removed = self.files.remove(path).is_some();
+        if !path.is_file() {
+            for sources in self.app_sources.values_mut() {
+                let len = sources.len();
+                sources.retain(|p| p != path);
+                removed |= sources.len() != len;
+            }
+        }
+        if removed {
+            self.rebuild_module_index();
+        }
+        affected.retain(|p| p != path);
+        affected
+    }
+
+    /// Returns the symbol index of this workspace.
+    pub fn index(&self) -> &WorkspaceIndex {
+        &self.index
+    }
+
+    /// Brings the symbol index up to date with the source and header files on disk.
+    ///
+    /// The modules of the applications are also rescanned
+    /// (otherwise, the source files are listed only when the applications are added).
+    /// Returns the files which have been (re)indexed.
+    pub fn refresh_index(&mut self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for app in &self.apps {
+            let sources = track!(app.source_files())?;
+            files.extend(sources.iter().cloned());
+            files.extend(track!(app.header_files())?);
+            self.app_sources.insert(app.name.clone(), sources);
+        }
+        self.rebuild_module_index();
+        let stale = self
+            .index
+            .files()
+            .keys()
+            .filter(|f| !f.is_file())
+            .cloned()
+            .collect::<Vec<_>>();
+        for file in stale {
+            self.index.remove(file);
+        }
+
+        let mut updated = Vec::new();
+        for file in files {
+            if track!(self.index.refresh(&file); file)? {
+                updated.push(file);
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Loads the symbol index saved by `save_index`.
+    ///
+    /// Returns `false` if there is no usable index at `path`;
+    /// in either case, `refresh_index` should be called afterwards to validate the entries.
+    pub fn load_index<P: AsRef<Path>>(&mut self, path: P) -> Result<bool> {
+        if let Some(index) = track!(WorkspaceIndex::load(path))? {
+            self.index = index;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub fn save_index<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        track!(self.index.save(path))
+    }
+
+    /// Returns the dependencies of `path` as of the last `update_file` call.
+    pub fn file_dependencies<P: AsRef<Path>>(&self, path: P) -> Option<&analysis::Dependencies> {
+        self.files.get(path.as_ref())
+    }
+
+    /// Returns the files which directly include `header` (via `-include` or `-include_lib`).
+    ///
+    /// Only the files registered by `update_file` are taken into account.
+    pub fn includers_of<P: AsRef<Path>>(&self, header: P) -> Vec<PathBuf> {
+        let header = header.as_ref();
+        self.files
+            .iter()
+            .filter(|(file, deps)| {
+                let includes = deps
+                    .includes
+                    .iter()
+                    .filter_map(|i| self.resolve_include(file, i));
+                let include_libs = deps
+                    .include_libs
+                    .iter()
+                    .filter_map(|i| self.resolve_include_lib(i));
+                includes.chain(include_libs).any(|p| p == header)
+            })
+            .map(|(file, _)| file.clone())
+            .collect()
+    }
+
+    /// Resolves the path of an `-include` directive written in `file`.
+    ///
+    /// The directory of `file` and the include directories of its application are searched.
+    pub fn resolve_include<P: AsRef<Path>>(&self, file: P, path: &str) -> Option<PathBuf> {
+        let file = file.as_ref();
+        let mut candidates = Vec::new();
+        if let Some(dir) = file.parent() {
+            candidates.push(dir.join(path));
+        }
+        if let Some(app) = self.app_of_file(file) {
+            candidates.extend(app.include_dirs.iter().map(|d| d.join(path)));
+        }
+        candidates
+            .into_iter()
+            .find(|p| p.is_file() || self.files.contains_key(p))
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Returns the applications in this workspace, ordered by name.
+    pub fn apps(&self) -> &[App] {
+        &self.apps
+    }
+
+    pub fn app(&self, name: &str) -> Option<&App> {
+        self.apps.iter().find(|a| a.name == name)
+    }
+
+    /// Returns the (innermost) application which contains `path`.
+    pub fn app_of_file<P: AsRef<Path>>(&self, path: P) -> Option<&App> {
+        self.apps
+            .iter()
+            .filter(|a| a.contains(path.as_ref()))
+            .max_by_key(|a| a.root.components().count())
+    }
+
+    /// Returns the application which defines the module `module`.
+    pub fn app_of_module(&self, module: &str) -> Option<&App> {
+        self.modules.get(module).map(|&i| &self.apps[i])
+    }
+
+    /// Returns `true` if code in the application `from` may refer to the application `to`.
+    ///
+    /// Applications which are not part of this workspace (e.g., OTP itself) are always visible.
+    pub fn is_visible(&self, from: &str, to: &str) -> bool {
+        match self.app(from) {
+            None => true,
+            Some(_) if self.app(to).is_none() => true,
+            Some(app) => app.depends_on(to),
+        }
+    }
+
+    /// Resolves the path of an `-include_lib` directive (e.g., `"foo/include/foo.hrl"`).
+    pub fn resolve_include_lib(&self, path: &str) -> Option<PathBuf> {
+        let mut components = path.splitn(2, '/');
+        let app = self.app(components.next()?)?;
+        let path = app.root.join(components.next()?);
+        if path.is_file() || self.files.contains_key(&path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Checks that every module in this workspace only refers to visible applications.
+    ///
+    /// `-include_lib`, `-behaviour`, `-import`, parse transforms and remote calls
+    /// (`m:f(...)` and `fun m:f/N`) are examined.
+    pub fn check_boundaries(&self) -> Result<Vec<BoundaryViolation>> {
+        let mut violations = Vec::new();
+        for app in &self.apps {
+            let mut files = track!(app.source_files())?;
+            files.extend(self.files.keys().filter(|f| is_source_of(app, f)).cloned());
+            files.sort();
+            files.dedup();
+            for file in files {
+                let (deps, calls) = match (self.files.get(&file), self.index.files().get(&file)) {
+                    (Some(deps), Some(index)) => (deps.clone(), index.calls.clone()),
+                    _ => {
+                        let text = track!(fs::read_to_string(&file).map_err(crate::Error::from))?;
+                        let deps = track!(analysis::scan_dependencies(&text))?;
+                        (deps, track!(FileIndex::build(&text))?.calls)
+                    }
+                };
+                let mut check = |kind, target: &str, to_app: Option<&App>| {
+                    if let Some(to) = to_app {
+                        if !self.is_visible(&app.name, &to.name) {
+                            violations.push(BoundaryViolation {
+                                file: file.clone(),
+                                from_app: app.name.clone(),
+                                to_app: to.name.clone(),
+                                kind,
+                                target: target.to_owned(),
+                            });
+                        }
+                    }
+                };
+                for path in &deps.include_libs {
+                    let to = path.split('/').next().and_then(|a| self.app(a));
+                    check(ReferenceKind::IncludeLib, path, to);
+                }
+                for m in &deps.behaviours {
+                    check(ReferenceKind::Behaviour, m, self.app_of_module(m));
+                }
+                for m in &deps.imports {
+                    check(ReferenceKind::Import, m, self.app_of_module(m));
+                }
+                for m in &deps.parse_transforms {
+                    check(ReferenceKind::ParseTransform, m, self.app_of_module(m));
+                }
+                let mut seen = BTreeSet::new();
+                for call in &calls {
+                    if let Some(ref m) = call.module {
+                        let target = format!("{}:{}/{}", m, call.name, call.arity);
+                        if seen.insert(target.clone()) {
+                            check(ReferenceKind::Call, &target, self.app_of_module(m));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    fn affected_files(&self, path: &Path) -> Vec<PathBuf> {
+        let mut affected = BTreeSet::new();
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(file) = stack.pop() {
+            if affected.insert(file.clone()) {
+                stack.extend(self.includers_of(&file));
+            }
+        }
+        affected.into_iter().collect()
+    }
+
+    fn rebuild_module_index(&mut self) {
+        self.modules.clear();
+        for (i, app) in self.apps.iter().enumerate() {
+            for file in self.app_sources.get(&app.name).into_iter().flatten() {
+                if let Some(module) = file.file_stem().and_then(|s| s.to_str()) {
+                    self.modules.insert(module.to_owned(), i);
+                }
+            }
+        }
+        for (file, deps) in &self.files {
+            let app = self.apps.iter().position(|a| is_source_of(a, file));
+            if let (Some(i), Some(module)) = (app, deps.module.as_ref()) {
+                self.modules.insert(module.clone(), i);
+            }
+        }
+    }
+
+    fn walk(&mut self, dir: &Path) -> Result<()> {
+        let mut entries = Vec::new();
+        for entry in track!(fs::read_dir(dir).map_err(crate::Error::from))? {
+            entries.push(track!(entry.map_err(crate::Error::from))?.path());
+        }
+        entries.sort();
+        for path in entries {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let skip = name.starts_with('.')
+                || ["src", "include", "ebin", "priv", "test", "c_src"].contains(&name);
+            if skip || !path.is_dir() || app::is_symlink(&path) {
+                continue;
+            }
+            if let Some(app) = track!(App::detect(&path, false))? {
+                track!(self.add_app(app))?;
+            }
+            track!(self.walk(&path))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_source_of(app: &App, file: &Path) -> bool {
+    file.extension().and_then(|e| e.to_str()) == Some("erl")
+        && app.src_dirs.iter().any(|d| file.starts_with(d))
+}
+
+/// Kind of a cross-application reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReferenceKind {
+    IncludeLib,
+    Behaviour,
+    Import,
+    ParseTransform,
+
+    /// A remote call (`m:f(...)`) or a remote function reference (`fun m:f/N`).
+    Call,
+}
+
+/// A reference to an application which is not a declared dependency of the referring application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryViolation {
+    pub file: PathBuf,
+    pub from_app: String,
+    pub to_app: String,
+    pub kind: ReferenceKind,
+    pub target: String,
+}
@@ -0,0 +1,352 @@
+use erl_tokenize::values::Symbol;
+use erl_tokenize::{Lexer, LexicalToken, PositionRange};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::index::{closes_block, count_args, opens_block};
+use super::{Definition, FileIndex, Location, SymbolKind, Workspace};
+use crate::traits::{is_form_end_in, is_symbol};
+use crate::{analysis, Error, Result};
+
+/// Definition of a symbol found by `Workspace::resolve`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedDefinition {
+    pub kind: SymbolKind,
+
+    /// The file which defines the symbol.
+    pub path: PathBuf,
+    pub definition: Definition,
+}
+
+/// Symbol referred to at a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Reference {
+    Function {
+        module: Option<String>,
+        name: String,
+        arity: usize,
+    },
+    Type {
+        module: Option<String>,
+        name: String,
+        arity: usize,
+    },
+    Record(String),
+    Macro {
+        name: String,
+        arity: Option<usize>,
+    },
+}
+
+impl Workspace {
+    /// Resolves the symbol at `location` in the file `path` (whose current content is `text`)
+    /// to its definition.
+    ///
+    /// The following references are resolved:
+    ///
+    /// - local and remote function calls, `fun` references and export entries,
+    ///   where local calls of the functions imported by `-import` are resolved to the imported module,
+    /// - local and remote types in `-spec`, `-callback`, `-type` and `-record` attributes,
+    /// - records (`#name`) and macros (`?NAME`) defined in the file or in its (transitively) included headers.
+    ///
+    /// Like `FileIndex`, this works on the token level, so it also works on files being edited.
+    /// Remote symbols are looked up in the symbol index (see `refresh_index` and `update_file`),
+    /// and headers are located by `resolve_include` and `resolve_include_lib`.
+    /// Returns `Ok(None)` if there is no symbol at `location` or its definition is not found.
+    pub fn resolve<P: AsRef<Path>>(
+        &self,
+        path: P,
+        text: &str,
+        location: Location,
+    ) -> Result<Option<ResolvedDefinition>> {
+        let path = path.as_ref();
+        let mut tokens = Vec::new();
+        let mut forms = Vec::new();
+        let mut form_start = 0;
+        for token in Lexer::new(text) {
+            let token = track!(token.map_err(Error::from))?;
+            let is_end = is_form_end_in(text, &token);
+            tokens.push(token);
+            if is_end {
+                forms.push(form_start..tokens.len() - 1);
+                form_start = tokens.len();
+            }
+        }
+        forms.push(form_start..tokens.len());
+
+        let i = tokens.iter().position(|t| {
+            Location::of(&t.start_position()) <= location
+                && location < Location::of(&t.end_position())
+        });
+        let i = match i {
+            None => return Ok(None),
+            Some(i) => i,
+        };
+        let reference = forms
+            .iter()
+            .find(|r| r.contains(&i))
+            .and_then(|r| reference_at(&tokens[r.clone()], i - r.start));
+        let reference = match reference {
+            None => return Ok(None),
+            Some(r) => r,
+        };
+
+        let this = track!(FileIndex::build(text))?;
+        let headers = track!(self.headers(path, text))?;
+        let local = |kind, name: &str, arity| {
+            if let Some(d) = find(&this, kind, name, arity) {
+                return Some(resolved(kind, path, d));
+            }
+            headers.iter().find_map(|h| {
+                let index = self.file_index(h)?;
+                let d = find(&index, kind, name, arity)?;
+                Some(resolved(kind, h, d))
+            })
+        };
+        let in_module = |module: &str| module == this.module.as_deref().unwrap_or("");
+
+        Ok(match reference {
+            Reference::Function {
+                module,
+                name,
+                arity,
+            } => {
+                let module = match module {
+                    Some(m) if !in_module(&m) => Some(m),
+                    _ => match local(SymbolKind::Function, &name, Some(arity)) {
+                        Some(d) => return Ok(Some(d)),
+                        None => imported_module(&tokens, &forms, &name, arity),
+                    },
+                };
+                module.and_then(|m| {
+                    let (path, d) = *self.index.find_functions(&m, &name, Some(arity)).first()?;
+                    Some(resolved(SymbolKind::Function, path, d))
+                })
+            }
+            Reference::Type {
+                module: Some(ref m),
+                ref name,
+                arity,
+            } if !in_module(m) => self
+                .index
+                .files()
+                .iter()
+                .filter(|(_, f)| f.module.as_deref() == Some(m.as_str()))
+                .find_map(|(path, f)| {
+                    let d = find(f, SymbolKind::Type, name, Some(arity))?;
+                    Some(resolved(SymbolKind::Type, path, d))
+                }),
+            Reference::Type { name, arity, .. } => local(SymbolKind::Type, &name, Some(arity)),
+            Reference::Record(name) => local(SymbolKind::Record, &name, None),
+            Reference::Macro { name, arity } => local(SymbolKind::Macro, &name, arity),
+        })
+    }
+
+    /// Returns the headers (transitively) included by the file `path` whose content is `text`,
+    /// in the order of their first inclusion.
+    fn headers(&self, path: &Path, text: &str) -> Result<Vec<PathBuf>> {
+        let mut files = vec![(
+            path.to_path_buf(),
+            track!(analysis::scan_dependencies(text))?,
+        )];
+        let mut i = 0;
+        while i < files.len() {
+            let (file, deps) = files[i].clone();
+            let includes = deps
+                .includes
+                .iter()
+                .filter_map(|h| self.resolve_include(&file, h));
+            let include_libs = deps
+                .include_libs
+                .iter()
+                .filter_map(|h| self.resolve_include_lib(h));
+            for header in includes.chain(include_libs) {
+                if files.iter().any(|(f, _)| *f == header) {
+                    continue;
+                }
+                let deps = self.files.get(&header).cloned().or_else(|| {
+                    let text = fs::read_to_string(&header).ok()?;
+                    analysis::scan_dependencies(&text).ok()
+                });
+                files.push((header, deps.unwrap_or_default()));
+            }
+            i += 1;
+        }
+        Ok(files.into_iter().skip(1).map(|(f, _)| f).collect())
+    }
+
+    /// Returns the index of `path`, which is built from the file on disk if `path` is not indexed.
+    fn file_index(&self, path: &Path) -> Option<Cow<'_, FileIndex>> {
+        if let Some(index) = self.index.get(path) {
+            return Some(Cow::Borrowed(index));
+        }
+        let text = fs::read_to_string(path).ok()?;
+        FileIndex::build(&text).ok().map(Cow::Owned)
+    }
+}
+
+fn resolved(kind: SymbolKind, path: &Path, definition: &Definition) -> ResolvedDefinition {
+    ResolvedDefinition {
+        kind,
+        path: path.to_path_buf(),
+        definition: definition.clone(),
+    }
+}
+
+fn find<'a>(
+    index: &'a FileIndex,
+    kind: SymbolKind,
+    name: &str,
+    arity: Option<usize>,
+) -> Option<&'a Definition> {
+    let defs = match kind {
+        SymbolKind::Function => &index.functions,
+        SymbolKind::Record => &index.records,
+        SymbolKind::Type => &index.types,
+        SymbolKind::Macro => &index.macros,
+    };
+    defs.iter()
+        .find(|d| d.name == name && (arity.is_none() || d.arity == arity))
+}
+
+/// Recognizes the symbol referred to by `form[i]`.
+///
+/// The arity of a macro is `None` if it is called without arguments.
+fn reference_at(form: &[LexicalToken], i: usize) -> Option<Reference> {
+    let prev = |n: usize| i.checked_sub(n).map(|j| &form[j]);
+    let after = |symbol| prev(1).is_some_and(|t| is_symbol(t, symbol));
+    if after(Symbol::Question) || after(Symbol::DoubleQuestion) {
+        let name = match form[i] {
+            LexicalToken::Atom(ref t) => t.value().to_owned(),
+            LexicalToken::Variable(ref t) => t.value().to_owned(),
+            _ => return None,
+        };
+        let arity = form
+            .get(i + 1)
+            .filter(|t| is_symbol(t, Symbol::OpenParen))
+            .map(|_| count_args(form, i + 1).0);
+        return Some(Reference::Macro { name, arity });
+    }
+
+    let name = form[i].as_atom_token()?.value().to_owned();
+    let attr = form
+        .get(1)
+        .filter(|_| is_symbol(&form[0], Symbol::Hyphen))
+        .and_then(LexicalToken::as_atom_token)
+        .map(|t| t.value());
+    let attr_arg = if form.get(2).is_some_and(|t| is_symbol(t, Symbol::OpenParen)) {
+        3
+    } else {
+        2
+    };
+    if after(Symbol::Sharp) || (attr == Some("record") && i == attr_arg) {
+        return Some(Reference::Record(name));
+    }
+
+    let module = if after(Symbol::Colon) {
+        let is_module_macro = prev(2)
+            .and_then(LexicalToken::as_variable_token)
+            .is_some_and(|t| t.value() == "MODULE")
+            && prev(3).is_some_and(|t| is_symbol(t, Symbol::Question));
+        if is_module_macro {
+            None
+        } else {
+            Some(prev(2)?.as_atom_token()?.value().to_owned())
+        }
+    } else {
+        None
+    };
+    let next = form.get(i + 1)?;
+    if is_symbol(next, Symbol::Slash) {
+        let arity = form.get(i + 2)?.as_integer_token()?.value();
+        return Some(Reference::Function {
+            module,
+            name,
+            arity: arity.to_string().parse().ok()?,
+        });
+    }
+    if !is_symbol(next, Symbol::OpenParen) {
+        return None;
+    }
+    let arity = count_args(form, i + 1).0;
+    let is_spec_head = |name_index: usize| {
+        i == name_index
+            || (i == name_index + 2
+                && form
+                    .get(name_index + 1)
+                    .is_some_and(|t| is_symbol(t, Symbol::Colon)))
+    };
+    let is_type = match attr {
+        Some("spec") | Some("callback") => !is_spec_head(attr_arg),
+        Some("type") | Some("opaque") | Some("nominal") => true,
+        Some("record") => in_record_field_type(form, i),
+        _ => false,
+    };
+    if is_type {
+        Some(Reference::Type {
+            module,
+            name,
+            arity,
+        })
+    } else {
+        Some(Reference::Function {
+            module,
+            name,
+            arity,
+        })
+    }
+}
+
+/// Returns `true` if `form[i]` is in the type of a field of a `-record` attribute (i.e., after `::`).
+fn in_record_field_type(form: &[LexicalToken], i: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_type = false;
+    for (j, token) in form.iter().enumerate().take(i) {
+        if opens_block(form, j) {
+            depth += 1;
+        } else if closes_block(token) {
+            depth = depth.saturating_sub(1);
+        } else if depth == 2 {
+            if is_symbol(token, Symbol::DoubleColon) {
+                in_type = true;
+            } else if is_symbol(token, Symbol::Match) || is_symbol(token, Symbol::Comma) {
+                in_type = false;
+            }
+        }
+    }
+    in_type
+}
+
+/// Returns the module from which `name/arity` is imported by an `-import` attribute.
+fn imported_module(
+    tokens: &[LexicalToken],
+    forms: &[std::ops::Range<usize>],
+    name: &str,
+    arity: usize,
+) -> Option<String> {
+    forms.iter().find_map(|r| {
+        let form = &tokens[r.clone()];
+        let is_import = form.len() > 3
+            && is_symbol(&form[0], Symbol::Hyphen)
+            && form[1]
+                .as_atom_token()
+                .is_some_and(|t| t.value() == "import");
+        if !is_import {
+            return None;
+        }
+        let module = form[3].as_atom_token()?.value();
+        let imported = form.windows(3).any(|w| {
+            w[0].as_atom_token().is_some_and(|t| t.value() == name)
+                && is_symbol(&w[1], Symbol::Slash)
+                && w[2]
+                    .as_integer_token()
+                    .is_some_and(|t| t.value().to_string() == arity.to_string())
+        });
+        if imported {
+            Some(module.to_owned())
+        } else {
+            None
+        }
+    })
+}
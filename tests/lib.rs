@@ -4,10 +4,15 @@ extern crate erl_tokenize;
 #[macro_use]
 extern crate trackable;
 
-use erl_parse::cst::{Expr, Form, Pattern, Type};
-use erl_parse::{Parser, TokenReader};
+use erl_parse::abstract_format::ToAbstract;
+use erl_parse::cst::commons::parts::BinaryOp;
+use erl_parse::cst::forms::{FunDecl, RecordDecl, TypeDecl};
+use erl_parse::cst::{Expr, Form, GuardTest, ModuleDecl, Pattern, Type};
+use erl_parse::term::Term;
+use erl_parse::traits::{CollectTokens, Parse};
+use erl_parse::{Parser, Recording, TokenReader};
 use erl_pp::Preprocessor;
-use erl_tokenize::{Lexer, PositionRange};
+use erl_tokenize::{Lexer, LexicalToken, PositionRange};
 
 macro_rules! parse_expr {
     ($text:expr) => {
@@ -41,6 +46,38 @@ macro_rules! parse_form {
     };
 }
 
+/// Checks that `value` consists of the tokens of `text`, and that its generated source code is parsable.
+fn check_tokens<T: CollectTokens + Parse>(text: &str, value: &T) {
+    let texts = |tokens: Vec<LexicalToken>| {
+        tokens
+            .iter()
+            .map(|t| t.text().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let expected = Lexer::new(text).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        texts(value.tokens()),
+        texts(expected.clone()),
+        "text={:?}",
+        text
+    );
+
+    let source = value.to_erlang_source();
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(&source))));
+    let reparsed: T = track_try_unwrap!(parser.parse(), "source={:?}", source);
+    assert_eq!(
+        texts(reparsed.tokens()),
+        texts(expected),
+        "source={:?}",
+        source
+    );
+}
+
+fn parse_module(text: &str) -> ModuleDecl {
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    track_try_unwrap!(parser.parse(), "text={:?}", text)
+}
+
 #[test]
 fn parse_expr_works() {
     // literals
@@ -97,6 +134,9 @@ fn parse_expr_works() {
     // list comprehension
     parse_expr!("[x || _ <- [1,2,3]]");
     parse_expr!("[x || X <- [1,2,3], filter(X), _ <= <<1,2,3>>]");
+    parse_expr!("[X#foo.bar || X <- Xs]");
+    parse_expr!("[[Y || Y <- X] || X <- [[1], [2, 3]]]");
+    parse_expr!("[[1, 2 | []], {[a || _ <- []]}]");
 
     // bitstring
     parse_expr!("<<>>");
@@ -302,6 +342,35 @@ fn parse_type_works() {
 
     // union
     parse_type!("10 | 1 + 2 | (foo | {a, b, c}) | baz");
+    parse_type!("A :: atom() | 1..10 | [A]");
+    parse_type!("#{atom() => fun((a) -> b) | any()} | <<_:8, _:_*8>>");
+}
+
+#[test]
+fn type_precedence_works() {
+    let text = "-1..2 * 3 + 4 | a | b";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let ty: Type = track_try_unwrap!(parser.parse());
+    assert_eq!(ty.end_position().offset(), text.len());
+
+    // ((-1)..((2 * 3) + 4)) | (a | b)
+    let union = match ty {
+        Type::Union(x) => x,
+        _ => panic!(),
+    };
+    assert!(matches!(union.right, Type::Union(_)));
+    let range = match union.left {
+        Type::Range(x) => x,
+        _ => panic!(),
+    };
+    assert!(matches!(range.low, Type::UnaryOpCall(_)));
+    match range.high {
+        Type::BinaryOpCall(ref x) => {
+            assert!(matches!(x.op, BinaryOp::Plus(_)));
+            assert!(matches!(x.left, Type::BinaryOpCall(_)));
+        }
+        _ => panic!(),
+    }
 }
 
 #[test]
@@ -324,6 +393,23 @@ fn parse_form_works() {
     // file attribute>
     parse_form!(r#"-file("/path/to/file", 10)."#);
 
+    // compile attribute
+    parse_form!("-compile(export_all).");
+    parse_form!("-compile([export_all, {inline, [foo/1, bar/2]}]).");
+
+    // vsn attribute
+    parse_form!(r#"-vsn("1.0.0")."#);
+    parse_form!("-vsn(1).");
+
+    // feature attribute
+    parse_form!("-feature(maybe_expr, enable).");
+
+    // doc attributes
+    parse_form!(r#"-moduledoc "Foo"."#);
+    parse_form!(r#"-doc("Foo")."#);
+    parse_form!(r#"-doc #{since => "1.0"}."#);
+    parse_form!("-doc false.");
+
     // wild attribute
     parse_form!("-my_attr([1, {2, 3}, #{}]).");
 
@@ -337,15 +423,23 @@ fn parse_form_works() {
     parse_form!("-spec foo:bar () -> ok.");
     parse_form!("-spec foo:bar (a) -> ok when B :: integer(); (B) -> err.");
 
+    // parenthesized spec
+    parse_form!("-spec(foo(a) -> ok).");
+    parse_form!("-spec(foo:bar(A) -> ok when A :: atom(); (b) -> err).");
+
     // callback
     parse_form!("-callback foo () -> ok.");
     parse_form!("-callback foo (a) -> ok; (b) -> err.");
     parse_form!("-callback foo (a) -> ok; (B) -> err when B :: integer().");
+    parse_form!("-callback(foo(a, b) -> ok).");
 
     // fun declaration
     parse_form!("foo () -> ok.");
     parse_form!("foo (A, {B, _}) -> A + B.");
     parse_form!("foo (A) when is_integer(A) -> ok; foo (B) -> {error, B}.");
+    parse_form!("foo(X) when is_integer(X), X > 0; X =:= undefined -> ok.");
+    parse_form!("foo(X) when not is_atom(X) andalso -X + 1 < 2 orelse X#r.f == a -> ok.");
+    parse_form!("foo(X) when erlang:is_list(X) and (length(X) > 0) -> ok.");
 
     // record declaration
     parse_form!("-record(foo, {}).");
@@ -356,4 +450,4189 @@ fn parse_form_works() {
     parse_form!("-type foo() :: integer().");
     parse_form!("-type foo(A, B) :: {A, B}.");
     parse_form!("-opaque foo() :: integer().");
+    parse_form!("-nominal meter() :: integer().");
+    parse_form!("-type(foo(A) :: [A]).");
+}
+
+#[test]
+fn fun_decl_works() {
+    let text = "foo(X) when X > 0 -> X; foo(_) -> 0.";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let decl: FunDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(decl.name(), "foo");
+    assert_eq!(decl.arity(), 1);
+    assert_eq!(decl.clauses.iter().count(), 2);
+    assert!(decl.clauses.iter().next().unwrap().guard.is_some());
+    assert_eq!(decl.end_position().offset(), text.len());
+}
+
+#[test]
+fn type_decl_works() {
+    let text = "-nominal(pair(A, B) :: {A, B}).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let decl: TypeDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(decl.name(), "pair");
+    assert_eq!(decl.arity(), 2);
+    assert!(decl.is_nominal());
+    assert!(!decl.is_opaque());
+}
+
+#[test]
+fn record_decl_works() {
+    let text = "-record(foo, {a = 1 :: integer(), b, c :: atom()}).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let decl: RecordDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(decl.name(), "foo");
+    let fields = decl.fields().map(|f| f.name()).collect::<Vec<_>>();
+    assert_eq!(fields, ["a", "b", "c"]);
+    let a = decl.fields().next().unwrap();
+    assert!(a.field_default.is_some() && a.field_type.is_some());
+    let a_text = &text[a.start_position().offset()..a.end_position().offset()];
+    assert_eq!(a_text, "a = 1 :: integer()");
+
+    let text = "-record(empty, {}).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let decl: RecordDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(decl.fields().count(), 0);
+    assert_eq!(decl.end_position().offset(), text.len());
+}
+
+#[test]
+fn module_decl_works() {
+    let module = parse_module(
+        "-module(foo).\n-record(r, {}).\n-type t() :: r.\nfoo() -> ok.\nbar(_) -> #r{}.\n",
+    );
+    assert_eq!(module.forms.len(), 5);
+    assert_eq!(module.module_name(), Some("foo"));
+    let funs = module.fun_decls().map(|f| f.name()).collect::<Vec<_>>();
+    assert_eq!(funs, ["foo", "bar"]);
+    assert_eq!(module.record_decls().count(), 1);
+    assert_eq!(module.type_decls().count(), 1);
+}
+
+#[test]
+fn find_duplicates_works() {
+    let module = parse_module(
+        r#"-module(foo).
+-export([foo/1, bar/0, foo/1]).
+-record(r, {a}).
+-record(r, {b}).
+-type t() :: integer().
+-type t(A) :: A.
+foo(_) -> ok.
+bar() -> ok.
+foo(_) -> ng.
+foo() -> ok."#,
+    );
+    let codes = erl_parse::analysis::find_duplicates(&module)
+        .into_iter()
+        .map(|d| (d.code, d.start_position.line()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("duplicate_export", 2),
+            ("duplicate_record", 4),
+            ("duplicate_function", 9)
+        ]
+    );
+
+    let text = "-define(A, 1). -define(A(X), X). -define(A, 2). -undef(A). -define(A, 3).";
+    let mut pp = Preprocessor::new(Lexer::new(text));
+    for t in pp.by_ref() {
+        track_try_unwrap!(t.map_err(erl_parse::Error::from));
+    }
+    let diagnostics = erl_parse::analysis::find_duplicate_macros(pp.directives());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].start_position.offset(), 33);
+}
+
+#[test]
+fn check_patterns_works() {
+    let module = parse_module(
+        r#"-module(foo).
+f("a" ++ X, [$b] ++ Y, -1, 2 * (3 + 4), <<Z:(N * 8)>>) -> {X, Y, Z, N}.
+f(X ++ "a", A + 1, not true, #{K + 1 := V}) -> ok.
+f(#{a => 1, _ := 2, (K = 1) := 3}) -> {A, B} = {1 < 2, [1] ++ []}."#,
+    );
+    let diagnostics = erl_parse::analysis::check_patterns(&module);
+    let codes = diagnostics
+        .iter()
+        .map(|d| {
+            (
+                d.code,
+                d.start_position.line(),
+                d.start_position.column(),
+                d.message.to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("illegal_pattern", 3, 3, "illegal pattern".to_owned()),
+            ("illegal_pattern", 3, 13, "illegal pattern".to_owned()),
+            ("illegal_pattern", 3, 20, "illegal pattern".to_owned()),
+            ("illegal_pattern", 4, 7, "illegal pattern".to_owned()),
+            (
+                "unbound_underscore",
+                4,
+                13,
+                "variable '_' is unbound".to_owned()
+            ),
+            (
+                "illegal_map_key",
+                4,
+                22,
+                "illegal map key in pattern".to_owned()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn check_record_defaults_works() {
+    let module = parse_module(
+        r#"-module(foo).
+-record(r, {a = 1 + 2, b = length([x, y]), c = erlang:system_time(),
+            d = make_ref(), e = 1 / 0, f = X, g = #s{}, h = fun f/0, i}).
+-record(s, {a = [{k, <<"v">>}] :: list()})."#,
+    );
+    let diagnostics = erl_parse::analysis::check_record_defaults(&module);
+    let codes = diagnostics
+        .iter()
+        .map(|d| (d.code, d.start_position.line(), d.start_position.column()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("record_default_call", 2, 48),
+            ("record_default_call", 3, 17),
+            ("record_default_exception", 3, 33),
+            ("record_default_not_constant", 3, 44),
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].message.to_string(),
+        "default value of #r.c calls erlang:system_time, which is not a compile-time constant"
+    );
+    assert_eq!(
+        diagnostics[2].message.to_string(),
+        "evaluation of the default value of #r.e will fail with a 'badarith' exception"
+    );
+}
+
+#[test]
+fn interface_works() {
+    use erl_parse::analysis::interface;
+
+    let module = parse_module(
+        r#"-module(my_server).
+-behaviour(gen_server).
+-export([start/1, init/1, start/1]).
+-export_type([state/0, id/0, missing/1]).
+-callback handle(term()) -> ok.
+-callback format(term()) -> iolist().
+-optional_callbacks([format/1]).
+-opaque state() :: #{}.
+-type id() :: integer().
+-spec start(id()) -> {ok, pid()}.
+start(Id) -> gen_server:start(?MODULE, Id, []).
+init(Id) -> {ok, Id}.
+helper() -> ok."#,
+    );
+    let summary = interface(&module);
+    assert_eq!(summary.module.as_deref(), Some("my_server"));
+    assert_eq!(summary.behaviours, ["gen_server"]);
+    let exports = summary
+        .exports
+        .iter()
+        .map(|f| (f.name.as_str(), f.arity, f.spec.is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(exports, [("start", 1, true), ("init", 1, false)]);
+    assert_eq!(
+        summary.export("start", 1).and_then(|f| f.spec.as_deref()),
+        Some("-spec start(id()) -> {ok, pid()}.")
+    );
+    let types = summary
+        .types
+        .iter()
+        .map(|t| (t.name.as_str(), t.opaque, t.definition.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            ("state", true, None),
+            ("id", false, Some("-type id() :: integer().")),
+            ("missing", false, None)
+        ]
+    );
+    let callbacks = summary
+        .callbacks
+        .iter()
+        .map(|c| (c.name.as_str(), c.arity, c.optional))
+        .collect::<Vec<_>>();
+    assert_eq!(callbacks, [("handle", 1, false), ("format", 1, true)]);
+
+    let module = parse_module("-module(foo).\n-compile([export_all]).\nf() -> ok.\ng(_) -> ok.");
+    assert_eq!(interface(&module).exports.len(), 2);
+}
+
+#[test]
+fn check_spec_clauses_works() {
+    let module = parse_module(
+        r#"-module(foo).
+-type color() :: red | green.
+-spec f(integer() | undefined, color()) -> ok.
+f(1, red) -> ok;
+f(undefined, blue) -> ok;
+f(foo, X) -> X;
+f([], {a}) -> ok.
+-spec g(0..9, [atom(), ...]) -> ok; (string(), #r{}) -> ok.
+g(-1, _) -> ok;
+g("abc", {r, 1}) -> ok;
+g(1, []) -> ok;
+g(X = 2.0, _) -> X.
+-spec foo:h(atom()) -> ok.
+h(1) -> ok.
+-spec i(other:t(), fun()) -> ok.
+i(1, F) -> F."#,
+    );
+    let diagnostics = erl_parse::analysis::check_spec_clauses(&module);
+    let codes = diagnostics
+        .iter()
+        .map(|d| {
+            (
+                d.code,
+                d.start_position.line(),
+                d.start_position.column(),
+                d.related[0].start_position.line(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("spec_mismatch", 5, 14, 3),
+            ("spec_mismatch", 6, 3, 3),
+            ("spec_mismatch", 7, 3, 3),
+            ("spec_mismatch", 9, 3, 8),
+            ("spec_mismatch", 11, 6, 8),
+            ("spec_mismatch", 12, 3, 8),
+            ("spec_mismatch", 14, 3, 13),
+        ]
+    );
+    assert_eq!(
+        diagnostics[1].message.to_string(),
+        "this clause never matches the spec of f/2: `foo` is not of type `integer() | undefined`"
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.severity == erl_parse::Severity::Warning));
+}
+
+#[test]
+fn find_duplicate_keys_works() {
+    let module = parse_module(
+        r#"-module(foo).
+foo(M, R) when M =:= #{a => 1, a => 2} ->
+    X = #{{b, [1]} => 1, c => 2, {b, [1]} => 3, -1 => 4, - 1 => 5, 1 => 6, 1.0 => 7},
+    Y = M#{d := 1, K => 2, K => 3, d => 4},
+    Z = #r{a = 1, b = 2, a = 3},
+    R#r{c = 1, c = 2}."#,
+    );
+    let diagnostics = erl_parse::analysis::find_duplicate_keys(&module);
+    let codes = diagnostics
+        .iter()
+        .map(|d| {
+            let first = &d.related[0];
+            (
+                d.code,
+                d.start_position.line(),
+                d.start_position.column(),
+                first.start_position.column(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("duplicate_map_key", 2, 32, 24),
+            ("duplicate_map_key", 3, 34, 11),
+            ("duplicate_map_key", 3, 58, 49),
+            ("duplicate_map_key", 4, 36, 12),
+            ("duplicate_record_field", 5, 26, 12),
+            ("duplicate_record_field", 6, 16, 9)
+        ]
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.severity == erl_parse::Severity::Warning));
+}
+
+#[test]
+fn find_clones_works() {
+    use erl_parse::analysis::{expr_hash, find_clones};
+
+    let a = parse_module(
+        r#"-module(a).
+f(X) ->
+    case lists:keyfind(X, 1, get_table()) of
+        {X, Value} -> {ok, Value};
+        false -> error
+    end.
+g(Y) -> case lists:keyfind(Y, 1, get_table()) of {Y, V} -> {ok, V}; false -> error end."#,
+    );
+    let b = parse_module(
+        r#"-module(b).
+h(Key) ->
+    case lists:keyfind(Key, 1, get_table()) of
+        {Key, Val} -> {ok, Val};
+        false -> error
+    end.
+i(A, B) -> {A, B, A}.
+j(A, B) -> {A, A, B}."#,
+    );
+    let clones = find_clones(vec![&a, &b], 10);
+    assert_eq!(clones.len(), 1);
+    assert_eq!(clones[0].size, 30);
+    let lines = clones[0]
+        .spans
+        .iter()
+        .map(|s| s.start_line_column())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [(3, 5), (7, 9), (3, 5)]);
+
+    let body = |m: &ModuleDecl, i: usize| match m.forms[i] {
+        Form::FunDecl(ref f) => f.clauses.item.body.exprs.item.clone(),
+        _ => unreachable!(),
+    };
+    assert_ne!(expr_hash(&body(&b, 2)), expr_hash(&body(&b, 3)));
+    assert_eq!(find_clones(vec![&b], 4).len(), 0);
+}
+
+#[test]
+fn scan_dependencies_works() {
+    let text = r#"-module(foo).
+-behaviour(gen_server).
+-include("foo.hrl").
+-include_lib("kernel/include/logger.hrl").
+-import(lists, [map/2]).
+-compile([export_all, {parse_transform, lager_transform}]).
+foo() -> ?LOG_INFO("-module(bar).").
+"#;
+    let deps = track_try_unwrap!(erl_parse::analysis::scan_dependencies(text));
+    assert_eq!(deps.module.as_deref(), Some("foo"));
+    assert_eq!(deps.behaviours, ["gen_server"]);
+    assert_eq!(deps.includes, ["foo.hrl"]);
+    assert_eq!(deps.include_libs, ["kernel/include/logger.hrl"]);
+    assert_eq!(deps.imports, ["lists"]);
+    assert_eq!(deps.parse_transforms, ["lager_transform"]);
+}
+
+#[test]
+fn guard_test_precedence_works() {
+    let text = "X > 0 + 1 * 2 andalso not Y orelse Z";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let test: GuardTest = track_try_unwrap!(parser.parse());
+    assert_eq!(test.end_position().offset(), text.len());
+
+    // ((X > (0 + (1 * 2))) andalso (not Y)) orelse Z
+    let orelse = match test {
+        GuardTest::BinaryOpCall(x) => x,
+        _ => panic!(),
+    };
+    assert!(matches!(orelse.op, BinaryOp::Orelse(_)));
+    let andalso = match orelse.left {
+        GuardTest::BinaryOpCall(x) => x,
+        _ => panic!(),
+    };
+    assert!(matches!(andalso.op, BinaryOp::Andalso(_)));
+    assert!(matches!(andalso.right, GuardTest::UnaryOpCall(_)));
+    let greater = match andalso.left {
+        GuardTest::BinaryOpCall(x) => x,
+        _ => panic!(),
+    };
+    assert!(matches!(greater.op, BinaryOp::Greater(_)));
+    match greater.right {
+        GuardTest::BinaryOpCall(ref x) => assert!(matches!(x.op, BinaryOp::Plus(_))),
+        _ => panic!(),
+    }
+
+    // comparison operators are non-associative
+    for text in ["A < B < C", "A =:= B == C", "A > B + 1 =< C andalso D"] {
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        assert!(parser.parse::<GuardTest>().is_err(), "text={:?}", text);
+    }
+    let text = "(A < B) < C andalso A < B orelse C";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let test: GuardTest = track_try_unwrap!(parser.parse());
+    assert_eq!(test.end_position().offset(), text.len());
+}
+
+#[test]
+fn workspace_boundaries_works() {
+    use erl_parse::workspace::ReferenceKind;
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("erl_parse_ws_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let write = |path: &str, text: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    };
+    write(
+        "apps/foo/src/foo.app.src",
+        "{application, foo, [{applications, [kernel, stdlib]}]}.",
+    );
+    write(
+        "apps/foo/src/foo.erl",
+        concat!(
+            "-module(foo).\n-include_lib(\"bar/include/bar.hrl\").\n-behaviour(bar_behaviour).\n",
+            "f() -> bar_behaviour:g(), lists:map(fun bar_behaviour:h/1, []), bar_behaviour:g().\n",
+        ),
+    );
+    write(
+        "apps/bar/src/bar.app.src",
+        "{application, bar, [{applications, [kernel, foo]}]}.",
+    );
+    write(
+        "apps/bar/src/bar_behaviour.erl",
+        "-module(bar_behaviour).\n",
+    );
+    write("apps/bar/include/bar.hrl", "");
+
+    let mut ws = erl_parse::workspace::Workspace::new();
+    track_try_unwrap!(ws.add_root(&root));
+    let names = ws
+        .apps()
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["bar", "foo"]);
+    assert_eq!(
+        ws.app_of_file(root.join("apps/foo/src/foo.erl"))
+            .map(|a| a.name.as_str()),
+        Some("foo")
+    );
+    assert_eq!(
+        ws.app_of_module("bar_behaviour").map(|a| a.name.as_str()),
+        Some("bar")
+    );
+    assert!(ws.is_visible("bar", "foo"));
+    assert!(!ws.is_visible("foo", "bar"));
+    assert!(ws.is_visible("foo", "stdlib"));
+    assert_eq!(
+        ws.resolve_include_lib("bar/include/bar.hrl"),
+        Some(root.join("apps/bar/include/bar.hrl"))
+    );
+
+    let violations = track_try_unwrap!(ws.check_boundaries());
+    let targets = violations
+        .iter()
+        .map(|v| v.target.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        [
+            "bar/include/bar.hrl",
+            "bar_behaviour",
+            "bar_behaviour:g/0",
+            "bar_behaviour:h/1"
+        ]
+    );
+    assert_eq!(violations[2].kind, ReferenceKind::Call);
+    assert!(violations
+        .iter()
+        .all(|v| v.from_app == "foo" && v.to_app == "bar"));
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn workspace_update_file_works() {
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("erl_parse_ws_update_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("include")).unwrap();
+    fs::write(root.join("src/foo.app.src"), "{application, foo, []}.").unwrap();
+    fs::write(root.join("include/foo.hrl"), "").unwrap();
+    fs::write(root.join("src/foo_inner.hrl"), "").unwrap();
+
+    let mut ws = erl_parse::workspace::Workspace::new();
+    track_try_unwrap!(ws.add_root(&root));
+
+    let foo = root.join("src/foo.erl");
+    let affected = track_try_unwrap!(ws.update_file(&foo, "-module(foo).\n"));
+    assert_eq!(affected, vec![foo.clone()]);
+    assert_eq!(
+        ws.app_of_module("foo").map(|a| a.name.as_str()),
+        Some("foo")
+    );
+
+    let inner = root.join("src/foo_inner.hrl");
+    let text = "-module(foo).\n-include(\"foo.hrl\").\n-include(\"foo_inner.hrl\").\n";
+    track_try_unwrap!(ws.update_file(&foo, text));
+    track_try_unwrap!(ws.update_file(&inner, "-include(\"foo.hrl\").\n"));
+    assert_eq!(
+        ws.includers_of(root.join("include/foo.hrl")),
+        [foo.clone(), inner.clone()]
+    );
+    let affected = track_try_unwrap!(ws.update_file(&inner, ""));
+    assert_eq!(affected, [foo.clone(), inner.clone()]);
+
+    assert_eq!(ws.remove_file(&inner), vec![foo.clone()]);
+    assert!(ws.file_dependencies(&inner).is_none());
+
+    track_try_unwrap!(ws.update_file(&foo, "-module(bar).\n"));
+    assert_eq!(
+        ws.app_of_module("bar").map(|a| a.name.as_str()),
+        Some("foo")
+    );
+
+    // Files created on disk are found by `refresh_index`
+    let baz = root.join("src/baz.erl");
+    fs::write(&baz, "-module(baz).\n").unwrap();
+    assert!(ws.app_of_module("baz").is_none());
+    track_try_unwrap!(ws.refresh_index());
+    assert_eq!(
+        ws.app_of_module("baz").map(|a| a.name.as_str()),
+        Some("foo")
+    );
+    fs::remove_file(&baz).unwrap();
+    ws.remove_file(&baz);
+    assert!(ws.app_of_module("baz").is_none());
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn workspace_index_works() {
+    use erl_parse::workspace::{FileIndex, SymbolKind, WorkspaceIndex};
+    use std::fs;
+
+    let text = r#"-module(foo).
+-record(state, {a}).
+-type t(A) :: [A].
+foo(X, #state{a = A}) when X > 0 -> lists:map(fun bar/1, [X | A]);
+foo(_, S) -> S#state.a, ?MODULE:baz(fun(Y) -> Y, ok end).
+-define(DEFAULT_TIMEOUT, 5000).
+-define(IS_STATE(S), is_record(S, state)).
+"#;
+    let index = track_try_unwrap!(FileIndex::build(text));
+    assert_eq!(index.module.as_deref(), Some("foo"));
+    let funs = index
+        .functions
+        .iter()
+        .map(|d| (d.name.as_str(), d.arity))
+        .collect::<Vec<_>>();
+    assert_eq!(funs, [("foo", Some(2))]);
+    assert_eq!(index.types[0].name, "t");
+    assert_eq!(index.types[0].arity, Some(1));
+    assert_eq!(index.records[0].name, "state");
+    let macros = index
+        .macros
+        .iter()
+        .map(|d| (d.name.as_str(), d.arity))
+        .collect::<Vec<_>>();
+    assert_eq!(macros, [("DEFAULT_TIMEOUT", None), ("IS_STATE", Some(1))]);
+    let calls = index
+        .calls
+        .iter()
+        .map(|c| {
+            (
+                c.module.as_deref(),
+                c.name.as_str(),
+                c.arity,
+                c.location.line,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            (Some("lists"), "map", 2, 4),
+            (None, "bar", 1, 4),
+            (None, "baz", 1, 5)
+        ]
+    );
+
+    let mut ws_index = WorkspaceIndex::new();
+    ws_index.insert("src/foo.erl".into(), index);
+    assert_eq!(ws_index.find_functions("foo", "foo", Some(2)).len(), 1);
+    assert_eq!(ws_index.find_functions("foo", "foo", Some(1)).len(), 0);
+    assert_eq!(ws_index.find_callers("foo", "bar", None).len(), 1);
+    assert_eq!(ws_index.find_callers("lists", "map", Some(2)).len(), 1);
+    assert_eq!(ws_index.find_callers("bar", "bar", None).len(), 0);
+    assert_eq!(ws_index.find_records("state").len(), 1);
+
+    let symbols = ws_index
+        .workspace_symbols("st")
+        .iter()
+        .map(|s| (s.kind, s.definition.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        symbols,
+        [
+            (SymbolKind::Record, "state"),
+            (SymbolKind::Macro, "IS_STATE")
+        ]
+    );
+    assert_eq!(
+        ws_index.workspace_symbols("dt")[0].definition.name,
+        "DEFAULT_TIMEOUT"
+    );
+    assert!(ws_index.workspace_symbols("xyz").is_empty());
+    assert_eq!(ws_index.workspace_symbols("").len(), 5);
+
+    let mut buf = Vec::new();
+    track_try_unwrap!(ws_index.write_to(&mut buf));
+    let loaded = track_try_unwrap!(WorkspaceIndex::read_from(&buf[..]));
+    assert_eq!(loaded.as_ref(), Some(&ws_index));
+
+    let old_version = b"erl_parse_index\t0\n";
+    assert_eq!(
+        track_try_unwrap!(WorkspaceIndex::read_from(&old_version[..])),
+        None
+    );
+    assert!(WorkspaceIndex::read_from(&b"foo\n"[..]).is_err());
+
+    // Workspace
+    let root = std::env::temp_dir().join(format!("erl_parse_ws_index_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/foo.erl"), text).unwrap();
+
+    let mut ws = erl_parse::workspace::Workspace::new();
+    track_try_unwrap!(ws.add_root(&root));
+    assert_eq!(track_try_unwrap!(ws.refresh_index()).len(), 1);
+    assert!(track_try_unwrap!(ws.refresh_index()).is_empty());
+    let index_file = root.join("index");
+    track_try_unwrap!(ws.save_index(&index_file));
+
+    let mut ws = erl_parse::workspace::Workspace::new();
+    track_try_unwrap!(ws.add_root(&root));
+    assert!(track_try_unwrap!(ws.load_index(&index_file)));
+    assert!(track_try_unwrap!(ws.refresh_index()).is_empty());
+    assert_eq!(ws.index().files().len(), 1);
+
+    // Modifications within the same second are detected
+    let foo = root.join("src/foo.erl");
+    fs::write(&foo, format!("{}extra() -> ok.\n", text)).unwrap();
+    assert_eq!(track_try_unwrap!(ws.refresh_index()), vec![foo.clone()]);
+    assert_eq!(ws.index().find_functions("foo", "extra", Some(0)).len(), 1);
+
+    // The disk content wins over an unsaved buffer once refreshed
+    track_try_unwrap!(ws.update_file(&foo, "-module(foo).\nunsaved() -> ok.\n"));
+    assert_eq!(
+        ws.index().find_functions("foo", "unsaved", Some(0)).len(),
+        1
+    );
+    assert_eq!(track_try_unwrap!(ws.refresh_index()), vec![foo]);
+    assert!(ws.index().find_functions("foo", "unsaved", None).is_empty());
+    assert_eq!(ws.index().find_functions("foo", "foo", Some(2)).len(), 1);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn workspace_resolve_works() {
+    use erl_parse::workspace::{Location, SymbolKind, Workspace};
+    use std::fs;
+
+    let root = std::env::temp_dir().join(format!("erl_parse_ws_resolve_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let write = |path: &str, text: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    };
+    write("apps/foo/src/foo.app.src", "{application, foo, []}.");
+    write(
+        "apps/foo/include/foo.hrl",
+        "-include(\"foo_inner.hrl\").\n-record(state, {timeout :: timeout()}).\n",
+    );
+    write(
+        "apps/foo/include/foo_inner.hrl",
+        "-define(TIMEOUT, 5000).\n",
+    );
+    write("apps/bar/src/bar.app.src", "{application, bar, []}.");
+    write(
+        "apps/bar/src/bar.erl",
+        "-module(bar).\n-type t() :: ok.\nrun(X) -> X.\nrun(X, Y) -> {X, Y}.\n",
+    );
+    write(
+        "apps/bar/include/bar.hrl",
+        "-define(BAR_MACRO(X), {bar, X}).\n",
+    );
+    let text = r#"-module(foo).
+-include("foo.hrl").
+-include_lib("bar/include/bar.hrl").
+-import(bar, [run/1]).
+-export([start/0]).
+-spec start() -> bar:t().
+start() ->
+    X = #state{timeout = ?TIMEOUT},
+    bar:run(X, fun helper/1),
+    helper(?BAR_MACRO(1)),
+    run([1]).
+helper(X) -> X.
+"#;
+    let foo = root.join("apps/foo/src/foo.erl");
+    write("apps/foo/src/foo.erl", text);
+
+    let mut ws = Workspace::new();
+    track_try_unwrap!(ws.add_root(&root));
+    track_try_unwrap!(ws.refresh_index());
+    let resolve = |line, column| {
+        track_try_unwrap!(ws.resolve(&foo, text, Location { line, column })).map(|r| {
+            let path = r
+                .path
+                .strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            (r.kind, path, r.definition.name, r.definition.location.line)
+        })
+    };
+    let function = SymbolKind::Function;
+    let foo_erl = "apps/foo/src/foo.erl".to_owned();
+    let bar_erl = "apps/bar/src/bar.erl".to_owned();
+    assert_eq!(
+        resolve(5, 10),
+        Some((function, foo_erl.clone(), "start".to_owned(), 7))
+    );
+    assert_eq!(
+        resolve(6, 22),
+        Some((SymbolKind::Type, bar_erl.clone(), "t".to_owned(), 2))
+    );
+    assert_eq!(
+        resolve(8, 10),
+        Some((
+            SymbolKind::Record,
+            "apps/foo/include/foo.hrl".to_owned(),
+            "state".to_owned(),
+            2
+        ))
+    );
+    assert_eq!(
+        resolve(8, 30),
+        Some((
+            SymbolKind::Macro,
+            "apps/foo/include/foo_inner.hrl".to_owned(),
+            "TIMEOUT".to_owned(),
+            1
+        ))
+    );
+    assert_eq!(
+        resolve(9, 9),
+        Some((function, bar_erl.clone(), "run".to_owned(), 4))
+    );
+    assert_eq!(
+        resolve(9, 25),
+        Some((function, foo_erl.clone(), "helper".to_owned(), 12))
+    );
+    assert_eq!(resolve(9, 5), None);
+    assert_eq!(
+        resolve(10, 5),
+        Some((function, foo_erl, "helper".to_owned(), 12))
+    );
+    assert_eq!(
+        resolve(10, 13),
+        Some((
+            SymbolKind::Macro,
+            "apps/bar/include/bar.hrl".to_owned(),
+            "BAR_MACRO".to_owned(),
+            1
+        ))
+    );
+    assert_eq!(
+        resolve(11, 5),
+        Some((function, bar_erl, "run".to_owned(), 3))
+    );
+    assert_eq!(resolve(8, 5), None);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn classify_lines_works() {
+    use erl_parse::analysis::classify_lines;
+
+    let text = r#"%% comment
+-module(foo).
+-define(FOO,
+        1).
+
+foo() -> "multi
+line". % trailing
+"#;
+    let lines = classify_lines(text);
+    assert_eq!(lines.len(), 8);
+    assert!(lines[0].is_comment_only());
+    assert!(lines[1].code && !lines[1].directive);
+    assert!(lines[2].directive && lines[3].directive && !lines[3].code);
+    assert!(lines[4].is_blank());
+    assert!(lines[5].code && lines[5].string);
+    assert!(lines[6].string && lines[6].comment && lines[6].code);
+    assert!(lines[7].is_blank());
+
+    // Falls back to line-based classification on tokenize errors
+    let lines = classify_lines("foo() -> ok.\nbar() -> \"unterminated\n% comment\n");
+    assert!(lines[0].code);
+    assert!(lines[1].code);
+    assert!(lines[2].is_comment_only());
+}
+
+#[test]
+fn parse_template_expr_works() {
+    use erl_parse::template::parse_template_expr;
+
+    let text = " {a, {b}} }} rest";
+    let (body, end) = track_try_unwrap!(parse_template_expr(text, &["}}"]));
+    assert_eq!(body.exprs.iter().count(), 1);
+    assert_eq!(&text[end..], "}} rest");
+
+    let text = "X = \"%>\", X ++ Y %> <p>";
+    let (body, end) = track_try_unwrap!(parse_template_expr(text, &["%>", "}}"]));
+    assert_eq!(body.exprs.iter().count(), 2);
+    assert_eq!(&text[end..], "%> <p>");
+
+    let (_, end) = track_try_unwrap!(parse_template_expr("foo(1)", &["}}"]));
+    assert_eq!(end, 6);
+    assert!(parse_template_expr("foo(1) bar }}", &["}}"]).is_err());
+}
+
+#[test]
+fn parsetools_works() {
+    use erl_parse::parsetools::{parse_leex, parse_yecc};
+
+    let xrl = r#"%% A lexer
+Definitions.
+
+D = [0-9]
+WS = [\s\t\n]
+
+Rules.
+
+{D}+ : {token, {integer, TokenLine, list_to_integer(TokenChars)}}.
+"[^"]*" :
+  S = lists:sublist(TokenChars, 2, TokenLen - 2),
+  {token, {string, TokenLine, S}}.
+{WS}+ : skip_token. % whitespace
+
+Erlang code.
+
+-export([foo/0]).
+foo() -> ok.
+"#;
+    let leex = track_try_unwrap!(parse_leex(xrl));
+    let defs = leex
+        .definitions
+        .iter()
+        .map(|d| (d.name.as_str(), d.value.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(defs, [("D", "[0-9]"), ("WS", "[\\s\\t\\n]")]);
+    let rules = leex
+        .rules
+        .iter()
+        .map(|r| (r.regex.as_str(), r.line, r.action.exprs.iter().count()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rules,
+        [("{D}+", 9, 1), ("\"[^\"]*\"", 10, 2), ("{WS}+", 13, 1)]
+    );
+    assert_eq!(leex.rules[1].action.start_position().line(), 11);
+    assert_eq!(leex.code.forms.len(), 2);
+
+    let yrl = r#"Nonterminals list elems elem.
+Terminals '(' ')' atom.
+Rootsymbol list.
+
+list -> '(' ')' : [].
+list -> '(' elems ')' : '$2'.
+elems -> elem : ['$1'].
+elems -> elem elems : ['$1' | '$2'].
+elem -> atom.
+
+Erlang code.
+
+value_of({_, _, V}) -> V.
+"#;
+    let yecc = track_try_unwrap!(parse_yecc(yrl));
+    let decls = yecc
+        .declarations
+        .iter()
+        .map(|d| (d.name.text(), d.values.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decls,
+        [("Nonterminals", 3), ("Terminals", 3), ("Rootsymbol", 1)]
+    );
+    assert_eq!(yecc.rules.len(), 5);
+    assert_eq!(yecc.rules[1].symbols.len(), 3);
+    assert!(yecc.rules[3].action.is_some());
+    assert!(yecc.rules[4].action.is_none());
+    assert_eq!(yecc.code.forms.len(), 1);
+    assert_eq!(yecc.code.forms[0].start_position().line(), 13);
+}
+
+#[test]
+fn elixir_calls_works() {
+    use erl_parse::analysis::{elixir_dependencies, elixir_module_name, find_elixir_calls};
+    use erl_parse::workspace::{FileIndex, WorkspaceIndex};
+
+    assert_eq!(elixir_module_name("Elixir.Enum"), Some("Enum"));
+    assert_eq!(elixir_module_name("lists"), None);
+
+    let text = "-module(foo).\nf(X) -> 'Elixir.Enum':map(X, fun 'Elixir.My.Mod':id/1), lists:reverse(X).\n";
+    let index = track_try_unwrap!(FileIndex::build(text));
+    let calls = find_elixir_calls(&index)
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(calls, ["Enum.map/2", "My.Mod.id/1"]);
+
+    let mut ws_index = WorkspaceIndex::new();
+    ws_index.insert("foo.erl".into(), index);
+    let deps = elixir_dependencies(&ws_index);
+    let used = deps["foo"].iter().map(String::as_str).collect::<Vec<_>>();
+    assert_eq!(used, ["Enum", "My.Mod"]);
+}
+
+#[test]
+fn parse_module_resilient_works() {
+    use erl_parse::Span;
+
+    let text = "-module(foo).\nf() -> X#r.f ++.\ng() -> ok.\nh( -> .\n";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let (module, errors) = parser.parse_module_resilient();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(module.forms.len(), 4);
+    assert!(matches!(module.forms[1], Form::Error(_)));
+    assert_eq!(module.forms[1].start_position().line(), 2);
+    assert_eq!(module.forms[1].end_position().line(), 2);
+    assert!(matches!(module.forms[2], Form::FunDecl(_)));
+    assert!(matches!(module.forms[3], Form::Error(_)));
+
+    // Well-formed forms which cannot be parsed are kept as `Form::Unparsed`
+    let text = "-module(foo).\nf(X) -> X |> g(1).\ng(X) -> [X.\n";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let (module, errors) = parser.parse_module_resilient();
+    assert_eq!(errors.len(), 2);
+    match module.forms[1] {
+        Form::Unparsed(ref x) => {
+            assert_eq!(x.tokens.len(), 13);
+            assert_eq!(
+                x.reason,
+                "unexpected token `|` while parsing function `f/1` starting at 2:1"
+            );
+            assert_eq!(Span::of(x).text(text), Some("f(X) -> X |> g(1)."));
+        }
+        ref form => panic!("{:?}", form),
+    }
+    assert!(matches!(module.forms[2], Form::Error(_)));
+}
+
+#[test]
+fn naming_lints_works() {
+    use erl_parse::lint::{check_naming, LintConfig};
+
+    let config = track_try_unwrap!(LintConfig::parse(
+        "[naming]\nmin_variable_length = 2 # comment\nmodule_prefixes = [\"my_\"]\n"
+    ));
+    let text = "-module(foo).\nfooBar(Req_id, _ignored_var) -> Req_id.\nf(A, Ab) -> {A, Ab}.\n";
+    let diagnostics = track_try_unwrap!(check_naming(text, &config.naming));
+    let codes = diagnostics.iter().map(|d| d.code).collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            "module_prefix",
+            "function_name_case",
+            "variable_name_case",
+            "variable_name_case",
+            "short_variable_name"
+        ]
+    );
+    assert_eq!(diagnostics[2].fixes.len(), 1);
+    assert_eq!(
+        diagnostics[2].fixes[0].apply(text),
+        "-module(foo).\nfooBar(ReqId, _ignored_var) -> ReqId.\nf(A, Ab) -> {A, Ab}.\n"
+    );
+    assert_eq!(
+        diagnostics[3].fixes[0].title.to_string(),
+        "rename _ignored_var to _IgnoredVar"
+    );
+    assert!(diagnostics[4].fixes.is_empty());
+
+    // Macro names are not variables
+    let text = "-module(my_foo).\n-define(MAX_SIZE, 10).\n-define(DBL(Val), Val * 2).\n\
+                -ifdef(TEST_MODE).\n-undef(TEST_MODE).\n-endif.\n\
+                f(Size) -> ?DBL(Size) < ?MAX_SIZE andalso ??Size =/= ?MODULE_STRING.\n";
+    assert!(track_try_unwrap!(check_naming(text, &config.naming)).is_empty());
+    let text = "-define(LIMIT(Max_val), Max_val).\n";
+    let diagnostics = track_try_unwrap!(check_naming(text, &config.naming));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].fixes[0].apply(text),
+        "-define(LIMIT(MaxVal), MaxVal).\n"
+    );
+
+    assert!(LintConfig::parse("[naming]\nunknown = 1\n").is_err());
+}
+
+#[test]
+fn boolean_case_lint_works() {
+    use erl_parse::lint::{check_boolean_case, BooleanCaseStyle, LintConfig};
+
+    let text = r#"-module(foo).
+f(X) -> case X > 0 of true -> g(X); false -> false end.
+g(X) -> case is_ok(X) of false -> ok; true -> error end.
+h(X) -> case X of true -> false; false -> true end.
+i(X) -> case X of true -> ok; _ -> ng end.
+"#;
+    let module = parse_module(text);
+    let config = track_try_unwrap!(LintConfig::parse(
+        "[boolean_case]\nstyle = \"short_circuit\"\n"
+    ));
+    let diagnostics = check_boolean_case(&module, &config.boolean_case);
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+        diagnostics[0].fixes[0].apply(text).lines().nth(1),
+        Some("f(X) -> X > 0 andalso g(X).")
+    );
+    assert!(diagnostics[1].fixes.is_empty());
+    assert_eq!(
+        diagnostics[2].fixes[0].apply(text).lines().nth(3),
+        Some("h(X) -> not X.")
+    );
+
+    let mut config = config.boolean_case;
+    config.style = BooleanCaseStyle::If;
+    let diagnostics = check_boolean_case(&module, &config);
+    assert_eq!(
+        diagnostics[0].fixes[0].apply(text).lines().nth(1),
+        Some("f(X) -> if X > 0 -> g(X); true -> false end.")
+    );
+    assert_eq!(
+        diagnostics[0].fixes[0].title.to_string(),
+        "rewrite with `if`"
+    );
+
+    // The replacement is enclosed in parentheses unless it is in a body
+    let text = r#"j(X) -> 1 + case X of true -> g(X); false -> false end.
+k(X) -> g(case X of true -> false; false -> true end).
+l(X) -> begin case X of true -> g(X); false -> false end end.
+"#;
+    let module = parse_module(text);
+    config.style = BooleanCaseStyle::ShortCircuit;
+    let fixed = check_boolean_case(&module, &config)
+        .iter()
+        .map(|d| d.fixes[0].apply(text))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fixed[0].lines().next(),
+        Some("j(X) -> 1 + (X andalso g(X)).")
+    );
+    assert_eq!(fixed[1].lines().nth(1), Some("k(X) -> g((not X))."));
+    assert_eq!(
+        fixed[2].lines().nth(2),
+        Some("l(X) -> begin X andalso g(X) end.")
+    );
+
+    config.enabled = false;
+    assert!(check_boolean_case(&module, &config).is_empty());
+    assert!(LintConfig::parse("[boolean_case]\nstyle = \"when\"\n").is_err());
+}
+
+#[test]
+fn function_size_lints_works() {
+    use erl_parse::analysis::function_metrics;
+    use erl_parse::lint::{check_function_size, LintConfig};
+
+    let text = r#"-module(foo).
+f(0) -> ok;
+f(1) -> ok;
+f(_) -> ng.
+g(X) ->
+    case X of
+        [_ | _] -> [begin fun() -> Y end end || Y <- X];
+        _ -> fun g/1
+    end.
+"#;
+    let module = parse_module(text);
+    let metrics = function_metrics(&module);
+    assert_eq!(metrics.len(), 2);
+    assert_eq!((metrics[0].lines, metrics[0].clauses), (3, 3));
+    assert_eq!(metrics[0].max_nesting, 0);
+    assert_eq!((metrics[1].lines, metrics[1].clauses), (5, 1));
+    assert_eq!(metrics[1].max_nesting, 4);
+
+    let config = track_try_unwrap!(LintConfig::parse(
+        "[function_size]\nmax_lines = 4\nmax_clauses = 2\nmax_nesting = 3\n"
+    ));
+    let diagnostics = check_function_size(&module, &config.function_size);
+    let codes = diagnostics
+        .iter()
+        .map(|d| (d.code, d.start_position.line()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            ("too_many_clauses", 2),
+            ("long_function", 5),
+            ("deep_nesting", 5)
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].message.to_string(),
+        "function f/1 has 3 clauses (max: 2)"
+    );
+    assert!(check_function_size(&module, &Default::default()).is_empty());
+}
+
+#[test]
+fn check_missing_specs_works() {
+    use erl_parse::lint::{check_missing_specs, LintConfig};
+
+    let text = r#"-module(foo).
+-export([f/1, g/0, 'H'/2, i/0]).
+-spec f(integer()) -> ok.
+f(_) -> ok.
+g() -> ok.
+'H'(A, B) -> {A, B}.
+j() -> ok.
+"#;
+    let module = parse_module(text);
+    let diagnostics = check_missing_specs(&module, &Default::default());
+    let codes = diagnostics
+        .iter()
+        .map(|d| (d.code, d.start_position.line(), d.message.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            (
+                "missing_spec",
+                5,
+                "exported function g/0 has no spec".to_owned()
+            ),
+            (
+                "missing_spec",
+                6,
+                "exported function H/2 has no spec".to_owned()
+            ),
+        ]
+    );
+    let fixed = diagnostics[1].fixes[0].apply(text);
+    assert!(
+        fixed.contains("g() -> ok.\n-spec 'H'(term(), term()) -> term().\n'H'(A, B) -> {A, B}.")
+    );
+    let fixed = parse_module(&fixed);
+    assert_eq!(check_missing_specs(&fixed, &Default::default()).len(), 1);
+
+    let config = track_try_unwrap!(LintConfig::parse("[missing_spec]\nenabled = false\n"));
+    assert!(check_missing_specs(&module, &config.missing_spec).is_empty());
+}
+
+#[test]
+fn lint_config_discovery_works() {
+    use erl_parse::lint::{check_all, LintConfig};
+
+    let root = std::env::temp_dir().join(format!("erl_parse_lint_{}", std::process::id()));
+    let src = root.join("src");
+    std::fs::create_dir_all(&src).unwrap();
+
+    let path = root.join(LintConfig::FILE_NAME);
+    std::fs::write(&path, "[function_size]\nmax_clauses = 1\n").unwrap();
+    let (found, config) = track_try_unwrap!(LintConfig::discover(&src)).unwrap();
+    assert_eq!(found, path);
+    assert_eq!(config.function_size.max_clauses, 1);
+
+    let text =
+        "-module(foo).\nfooBar(0) -> ok;\nfooBar(_) -> case x of true -> a; false -> b end.\n";
+    let module = parse_module(text);
+    let diagnostics = track_try_unwrap!(check_all(text, &module, &config));
+    let codes = diagnostics.iter().map(|d| d.code).collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        ["function_name_case", "too_many_clauses", "boolean_case"]
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn memo_works() {
+    let text = r#"-module(foo).
+-record(r, {a = [[{1}]] :: [[{integer()}]]}).
+f(X) when is_list(X), X =/= [[[a]]] -> Y = [[[{X, #r{}}]]], [Z || [Z] <- Y];
+f(#{a := [[B]]} = M) -> M#{b => (((B)))}.
+"#;
+    let parse = |memo| {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let mut parser = Parser::new(reader).memo(memo);
+        let module: ModuleDecl = track_try_unwrap!(parser.parse());
+        module.to_erlang_source()
+    };
+    assert_eq!(parse(true), parse(false));
+
+    let text = format!("{}x{}", "[".repeat(30), "]".repeat(30));
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new(text.as_str())));
+    let mut parser = Parser::new(reader).memo(true);
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    assert_eq!(expr.to_erlang_source(), text);
+
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new("f(X) -> [X.")));
+    let mut parser = Parser::new(reader).memo(true);
+    assert!(parser.parse::<ModuleDecl>().is_err());
+}
+
+#[test]
+fn parse_deeply_nested_expr_works() {
+    parse_expr!("{1, [a, B | [c]], -(2 + 3) * X, not [] ++ {}}");
+    parse_expr!("[{X} || X <- [[1], {2}]]");
+    parse_expr!("{[1, 2] = X, f(X)}");
+
+    // Dropping (and traversing) such a CST is still recursive, so the results are disposed.
+    let parse = |text: &str| -> Expr {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let limits = erl_parse::Limits {
+            max_depth: None,
+            ..erl_parse::Limits::new()
+        };
+        track_try_unwrap!(Parser::new(reader).limits(limits).parse())
+    };
+    let depth = 100_000;
+    let text = format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+    let expr = parse(&text);
+    assert!(matches!(expr, Expr::List(_)));
+    erl_parse::cst::dispose(expr);
+
+    let text = vec!["f(X)"; depth].join(" ++ ");
+    let expr = parse(&text);
+    assert!(matches!(expr, Expr::BinaryOpCall(_)));
+    erl_parse::cst::dispose(expr);
+
+    let text = format!("[{}]", vec!["{a, 1}"; depth].join(", "));
+    let expr = parse(&text);
+    assert!(matches!(expr, Expr::List(_)));
+    erl_parse::cst::dispose(expr);
+
+    // Non-literal nesting is parsed recursively (on other threads if the stack is running low)
+    let depth = 1200;
+    let texts = [
+        format!("{}a{}", r#"{<<"a">>, "#.repeat(depth), "}".repeat(depth)),
+        format!("{}X{}", "f(".repeat(depth), ")".repeat(depth)),
+        format!("{}1{}", "#{a => ".repeat(depth), "}".repeat(depth)),
+        format!(
+            "{}1{}",
+            "case X of _ -> ".repeat(depth),
+            " end".repeat(depth)
+        ),
+    ];
+    for text in &texts {
+        let expr = parse(text);
+        assert_eq!(expr.end_position().offset(), text.len());
+        erl_parse::cst::dispose(expr);
+    }
+}
+
+#[test]
+fn collection_accessors_works() {
+    let parse = |text: &str| -> Expr {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        track_try_unwrap!(Parser::new(reader).parse())
+    };
+    let sources = |xs: Vec<&Expr>| xs.iter().map(|x| x.to_erlang_source()).collect::<Vec<_>>();
+
+    if let Expr::Tuple(x) = parse("{a, [b], c}") {
+        assert_eq!(x.len(), 3);
+        assert_eq!(sources(x.iter().collect()), ["a", "[b]", "c"]);
+    } else {
+        panic!()
+    }
+    if let Expr::List(x) = parse("[1, 2 | T]") {
+        assert_eq!(x.len(), 2);
+        assert_eq!(sources(x.iter().collect()), ["1", "2"]);
+        assert_eq!(x.tail().map(|t| t.to_erlang_source()), Some("T".to_owned()));
+        assert!(!x.is_proper_list());
+    } else {
+        panic!()
+    }
+    if let Expr::List(x) = parse("[]") {
+        assert!(x.is_empty());
+        assert!(x.is_proper_list());
+    } else {
+        panic!()
+    }
+    if let Expr::Map(x) = parse("#{a => 1, b => 2}") {
+        assert_eq!(x.len(), 2);
+        assert_eq!(sources(x.iter().map(|f| &f.key).collect()), ["a", "b"]);
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn trivia_works() {
+    use erl_parse::cst::Trivia;
+    use erl_tokenize::Token;
+
+    let text = "%% Header\n\n-module(foo). % trailing\n\n%% Doc\nf() ->\n    ok.\n\n";
+    let trivia = track_try_unwrap!(Trivia::build(text));
+    let tokens = Lexer::new(text).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(trivia.reproduce(&tokens), text);
+
+    let module = parse_module(text);
+    let leading = trivia
+        .leading(&module.forms[1])
+        .iter()
+        .map(Token::text)
+        .collect::<String>();
+    assert_eq!(leading, "\n%% Doc\n");
+    let trailing = trivia
+        .trailing(&module.forms[0])
+        .iter()
+        .map(Token::text)
+        .collect::<String>();
+    assert_eq!(trailing, " % trailing\n");
+    assert_eq!(trivia.end_of_file().len(), 1);
+}
+
+#[test]
+fn to_erlang_source_works() {
+    use erl_parse::cst::Trivia;
+
+    let text = "-module(foo).\n\n%% Doc\nf(X, R) ->   % comment\n    Y = X#r.a + 1,\n    lists:map(fun(Z) -> -Z end, [Y | ?MODULE:g(R)]).\n";
+    let module = parse_module(text);
+    assert_eq!(
+        module.to_string(),
+        "-module(foo).\nf(X, R) -> Y = X#r.a + 1, lists:map(fun (Z) -> - Z end, [Y | foo:g(R)])."
+    );
+    assert_eq!(module.forms[1].to_string().lines().count(), 1);
+
+    let text = "-module(foo).\n\n%% Doc\nf(X) ->   % comment\n    X#r.a.\n";
+    let module = parse_module(text);
+    let trivia = track_try_unwrap!(Trivia::build(text));
+    assert_eq!(trivia.reproduce(&module.tokens()), text);
+
+    macro_rules! round_trip {
+        ($t:ty, [$($text:expr),*]) => {
+            $({
+                let mut parser =
+                    Parser::new(TokenReader::new(Preprocessor::new(Lexer::new($text))));
+                let value: $t = track_try_unwrap!(parser.parse(), "text={:?}", $text);
+                check_tokens($text, &value);
+            })*
+        };
+    }
+    round_trip!(
+        Expr,
+        [
+            r#""foo" "bar""#,
+            "\"\"\"\n  abc\n  \"\"\"",
+            "{1, [2 | T], #{a => 1}, <<X:8/big, \"s\"/binary>>}",
+            "- 1 - -X + (+2)",
+            "R#r.a#s.b",
+            "M#{a := 1}#r{b = 2}",
+            "fun foo/1 =:= fun m:f/2",
+            "m:f(X, 1)",
+            "[X || X <- L, X > 0] ++ [K || K := _ <- M]",
+            "<< <<B>> || <<B>> <= Bin >>",
+            "begin X = 1, case X of 1 when X > 0; X < 0 -> ok; _ -> error end end",
+            "try f() of _ -> ok catch throw:E:S -> {E, S} after g() end",
+            "receive {a, X} -> X after 10 -> timeout end",
+            "fun (X) -> X; (_) -> ok end",
+            "fun F(0) -> 0; F(N) -> F(N - 1) end",
+            "catch 1 bsl 2 andalso not true orelse X =/= Y"
+        ]
+    );
+    round_trip!(
+        Pattern,
+        [
+            "{A, [B | _], #{k := V}, <<C:4, _/binary>>}",
+            "#r{a = 1} = \"pre\" ++ Rest",
+            "-1"
+        ]
+    );
+    round_trip!(
+        Type,
+        [
+            "fun((...) -> ok) | fun((a, b) -> [term(), ...])",
+            "#{a := 1..10, atom() => -1}",
+            "<<_:8, _:_*4>>",
+            "#r{a :: m:t(A)} | t()"
+        ]
+    );
+    round_trip!(
+        Form,
+        [
+            "-module(foo).",
+            "-export([f/1]).",
+            "-record(r, {a = 1 :: integer(), b}).",
+            "-type t(A) :: {A, [A]}.",
+            "-spec f(X) -> X when X :: integer(); (atom()) -> ok.",
+            "-callback g() -> term().",
+            "f(X) when X > 0 -> ok; f(_) -> error."
+        ]
+    );
+}
+
+#[test]
+fn hover_info_works() {
+    use erl_parse::analysis::hover_info;
+    use erl_parse::cst::Trivia;
+
+    let text = r#"-module(foo).
+
+%% unrelated
+
+%% @doc Adds one.
+%% Really.
+-spec inc(integer()) -> integer().
+inc(X) ->
+    X + 1.
+
+-doc("Doubles.").
+dbl(X) -> X * 2.
+"#;
+    let module = parse_module(text);
+    let trivia = track_try_unwrap!(Trivia::build(text));
+
+    let inc = text.find("X + 1").unwrap();
+    let info = hover_info(&module, Some(&trivia), inc).unwrap();
+    assert_eq!((info.name.as_str(), info.arity), ("inc", 1));
+    assert_eq!(
+        info.spec.as_deref(),
+        Some("-spec inc(integer()) -> integer().")
+    );
+    assert_eq!(info.doc.as_deref(), Some("Adds one.\nReally."));
+    assert_eq!(info.start_position().line(), 8);
+    assert_eq!(
+        info.to_markdown(),
+        "```erlang\n-spec inc(integer()) -> integer().\n```\n\nAdds one.\nReally."
+    );
+
+    let dbl = text.find("X * 2").unwrap();
+    let info = hover_info(&module, None, dbl).unwrap();
+    assert_eq!(info.doc.as_deref(), Some("Doubles."));
+    assert!(info.spec.is_none());
+
+    assert!(hover_info(&module, None, 0).is_none());
+}
+
+#[test]
+fn fold_works() {
+    use erl_parse::traits::{Fold, Foldable};
+    use erl_tokenize::tokens::VariableToken;
+
+    struct Identity;
+    impl Fold for Identity {}
+
+    struct RenameVar;
+    impl Fold for RenameVar {
+        fn fold_variable_token(&mut self, token: VariableToken) -> VariableToken {
+            if token.value() == "X" {
+                VariableToken::from_value("Input", token.start_position()).unwrap()
+            } else {
+                token
+            }
+        }
+    }
+
+    let text = "-module(foo).\n-spec f(X) -> X when X :: integer().\nf(X) -> [Y || Y <- X, Y > #{a => X}].\n";
+    let module = parse_module(text);
+    let expected = module.to_string();
+    assert_eq!(
+        Identity.fold_module_decl(module.clone()).to_string(),
+        expected
+    );
+    assert_eq!(
+        module.fold_with(&mut RenameVar).to_string(),
+        expected.replace('X', "Input")
+    );
+}
+
+#[test]
+fn parser_snapshot_works() {
+    let text = "-module(foo). f() -> ok.";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let snapshot = parser.snapshot();
+    let _: Form = track_try_unwrap!(parser.parse());
+    assert!(parser.reader().inner().macros().contains_key("MODULE"));
+
+    parser.restore(&snapshot);
+    assert!(!parser.reader().inner().macros().contains_key("MODULE"));
+    let token: LexicalToken = track_try_unwrap!(parser.parse());
+    assert_eq!(token.text(), "-");
+
+    parser.restore(&snapshot);
+    let form: Form = track_try_unwrap!(parser.parse());
+    assert!(matches!(form, Form::ModuleAttr(_)));
+    parser.release(snapshot);
+
+    let form: Form = track_try_unwrap!(parser.parse());
+    assert!(matches!(form, Form::FunDecl(_)));
+    assert!(track_try_unwrap!(parser.eos()));
+}
+
+#[test]
+fn recording_works() {
+    let text = "-module(foo).\n%% comment\nf(X) -> X#r.a.\n";
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+    let mut parser = Parser::new(Recording::new(reader));
+
+    let _: Form = track_try_unwrap!(parser.parse());
+    assert_eq!(parser.reader().source(text), Some("-module(foo)."));
+    let tokens = parser.reader_mut().take_tokens();
+    assert_eq!(tokens.len(), 6);
+
+    let _: Form = track_try_unwrap!(parser.parse());
+    let recorded = parser.reader().tokens();
+    assert_eq!(
+        recorded.iter().map(|t| t.text()).collect::<Vec<_>>(),
+        ["f", "(", "X", ")", "->", "X", "#", "r", ".", "a", "."]
+    );
+    assert_eq!(parser.reader().source(text), Some("f(X) -> X#r.a."));
+    assert!(track_try_unwrap!(parser.eos()));
+}
+
+#[test]
+fn doc_attr_works() {
+    let text = r#"-module(foo).
+-feature(maybe_expr, disable).
+-moduledoc """
+    Foo.
+    """.
+-doc #{since => <<"1.0">>}.
+-doc("Bar " "baz").
+bar() -> ok."#;
+    let module = parse_module(text);
+    let docs = module
+        .forms
+        .iter()
+        .filter_map(|f| match *f {
+            Form::FeatureAttr(ref x) => {
+                assert_eq!(x.feature_name.value(), "maybe_expr");
+                assert!(!x.is_enabled());
+                None
+            }
+            Form::DocAttr(ref x) => Some((x.is_module_doc(), x.text())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        docs,
+        [
+            (true, Some("Foo.".to_owned())),
+            (false, None),
+            (false, Some("Bar baz".to_owned()))
+        ]
+    );
+
+    let abstract_forms = module
+        .forms
+        .iter()
+        .map(|f| f.to_abstract().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        abstract_forms[1],
+        "{attribute,2,feature,{maybe_expr,disable}}"
+    );
+    assert_eq!(
+        abstract_forms[3],
+        "{attribute,6,doc,#{since => <<49,46,48>>}}"
+    );
+}
+
+#[test]
+fn abstract_format_works() {
+    fn expr(text: &str) -> String {
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        let value: Expr = track_try_unwrap!(parser.parse(), "text={:?}", text);
+        value.to_abstract().to_string()
+    }
+    fn form(text: &str) -> String {
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        let value: Form = track_try_unwrap!(parser.parse(), "text={:?}", text);
+        value.to_abstract().to_string()
+    }
+
+    assert_eq!(
+        expr("[1, 2 | T]"),
+        "{cons,1,{integer,1,1},{cons,1,{integer,1,2},{var,1,'T'}}}"
+    );
+    assert_eq!(
+        expr("foo:bar(X)"),
+        "{call,1,{remote,1,{atom,1,foo},{atom,1,bar}},[{var,1,'X'}]}"
+    );
+    assert_eq!(expr("X#r.a"), "{record_field,1,{var,1,'X'},r,{atom,1,a}}");
+    assert_eq!(
+        expr("#{a => 1}"),
+        "{map,1,[{map_field_assoc,1,{atom,1,a},{integer,1,1}}]}"
+    );
+    assert_eq!(expr("fun foo/1"), "{'fun',1,{function,foo,1}}");
+    assert_eq!(expr(r#""ab" "c""#), "{string,1,[97,98,99]}");
+    assert_eq!(
+        expr("<<X:8/integer-unit:1>>"),
+        "{bin,1,[{bin_element,1,{var,1,'X'},{integer,1,8},[integer,{unit,1}]}]}"
+    );
+    assert_eq!(
+        expr("try a catch error:R:S -> R after ok end"),
+        concat!(
+            "{'try',1,[{atom,1,a}],[],",
+            "[{clause,1,[{tuple,1,[{atom,1,error},{var,1,'R'},{var,1,'S'}]}],[],[{var,1,'R'}]}],",
+            "[{atom,1,ok}]}"
+        )
+    );
+    assert_eq!(
+        expr("[X || {X} <- Xs, X > 1]"),
+        concat!(
+            "{lc,1,{var,1,'X'},[{generate,1,{tuple,1,[{var,1,'X'}]},{var,1,'Xs'}},",
+            "{op,1,'>',{var,1,'X'},{integer,1,1}}]}"
+        )
+    );
+
+    assert_eq!(
+        form("-record(r, {a = 1 :: integer(), b})."),
+        concat!(
+            "{attribute,1,record,{r,[{typed_record_field,",
+            "{record_field,1,{atom,1,a},{integer,1,1}},{type,1,integer,[]}},",
+            "{record_field,1,{atom,1,b}}]}}"
+        )
+    );
+    assert_eq!(
+        form("-spec f(A) -> [A] when A :: atom()."),
+        concat!(
+            "{attribute,1,spec,{{f,1},[{type,1,bounded_fun,[",
+            "{type,1,'fun',[{type,1,product,[{var,1,'A'}]},{type,1,list,[{var,1,'A'}]}]},",
+            "[{type,1,constraint,[{atom,1,is_subtype},[{var,1,'A'},{type,1,atom,[]}]]}]]}]}}"
+        )
+    );
+    assert_eq!(
+        form("-type t() :: foo | bar:baz() | t2(1..3)."),
+        concat!(
+            "{attribute,1,type,{t,{type,1,union,[{atom,1,foo},",
+            "{remote_type,1,[{atom,1,bar},{atom,1,baz},[]]},",
+            "{user_type,1,t2,[{type,1,range,[{integer,1,1},{integer,1,3}]}]}]},[]}}"
+        )
+    );
+    assert_eq!(
+        form("-behaviour(gen_server)."),
+        "{attribute,1,behaviour,gen_server}"
+    );
+    assert_eq!(
+        form("-dialyzer({nowarn_function, [f/1]})."),
+        "{attribute,1,dialyzer,{nowarn_function,[{f,1}]}}"
+    );
+    assert_eq!(
+        form("f(X) when X > 0; is_atom(X) -> ok."),
+        concat!(
+            "{function,1,f,1,[{clause,1,[{var,1,'X'}],",
+            "[[{op,1,'>',{var,1,'X'},{integer,1,0}}],[{call,1,{atom,1,is_atom},[{var,1,'X'}]}]],",
+            "[{atom,1,ok}]}]}"
+        )
+    );
+
+    assert_eq!(Term::atom("Foo").to_string(), "'Foo'");
+    assert_eq!(Term::from(1e20).to_string(), "1.0e20");
+    assert_eq!(Term::string("hi").as_string().as_deref(), Some("hi"));
+}
+
+#[test]
+fn error_context_works() {
+    let text = "f() -> foo(1, 2 3).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let e = parser.parse::<Form>().err().unwrap();
+    let diagnostic = parser.error_diagnostic(&e);
+    assert_eq!(
+        diagnostic.message.to_string(),
+        "unexpected token `3` while parsing the 2nd argument starting at 1:15, \
+         in the call starting at 1:8, in function `f/0` starting at 1:1"
+    );
+    assert_eq!(diagnostic.start_position.offset(), 16);
+    assert_eq!(diagnostic.related.len(), 3);
+
+    let text = "-export([foo/1, bar/]).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let e = parser.parse::<Form>().err().unwrap();
+    let contexts = parser
+        .error_contexts()
+        .map(|c| c.description.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(contexts, ["the `-export` attribute"]);
+    assert!(parser
+        .error_diagnostic(&e)
+        .message
+        .to_string()
+        .starts_with("unexpected token `]`"));
+}
+
+#[test]
+fn diagnostic_render_works() {
+    use erl_parse::messages::English;
+
+    let text = "-module(foo).\nf(X) ->\n\tcase X of a -> b.";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let e = parser.parse::<ModuleDecl>().err().unwrap();
+    let diagnostic = parser.error_diagnostic(&e);
+    assert_eq!(diagnostic.expected, ["=", ",", ";", "end"]);
+    assert_eq!(
+        diagnostic.render(text, &English),
+        "3:18: error[syntax_error]: unexpected token `.` while parsing the `case` expression \
+         starting at 3:2, in function `f/1` starting at 2:1\n  |\n3 | \tcase X of a -> b.\n  | \t                ^\n  \
+         = expected one of `=`, `,`, `;`, `end`"
+    );
+
+    // Positions out of the text are rendered without snippets
+    let rendered = diagnostic.render("", &English);
+    assert_eq!(rendered.lines().count(), 2);
+}
+
+#[test]
+fn message_catalog_works() {
+    use erl_parse::messages::{write_catalog, English, MessageCatalog, TEMPLATES};
+    use std::collections::HashMap;
+
+    let text = "f() -> foo(1, 2 3).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let e = parser.parse::<Form>().err().unwrap();
+    let diagnostic = parser.error_diagnostic(&e);
+
+    let mut catalog = HashMap::new();
+    for &(id, template) in &[
+        ("unexpected_token", "jeton inattendu `{token}`"),
+        ("error_in_context", "{error} dans {context}"),
+        ("nested_context", "{inner}, dans {outer}"),
+        ("context_position", "{context} ({line}:{column})"),
+        ("argument_context", "l'argument n°{index}"),
+        ("call_context", "l'appel"),
+        ("function_context", "la fonction `{name}/{arity}`"),
+    ] {
+        catalog.insert(id.to_owned(), template.to_owned());
+    }
+    assert_eq!(
+        diagnostic.format(&catalog),
+        "1:17: error[syntax_error]: jeton inattendu `3` dans l'argument n°2 (1:15), \
+         dans l'appel (1:8), dans la fonction `f/0` (1:1)"
+    );
+    assert_eq!(diagnostic.to_string(), diagnostic.format(&English));
+
+    for t in TEMPLATES {
+        assert_eq!(English.template(t.id), Some(t.template));
+        for p in t.params {
+            assert!(t.template.contains(&format!("{{{}}}", p)) || t.id == "argument_context");
+        }
+    }
+    let mut buf = Vec::new();
+    track_try_unwrap!(write_catalog(&mut buf));
+    let json = String::from_utf8(buf).unwrap();
+    assert!(json.starts_with("{\n  \"unexpected_token\": {\"template\": \"unexpected token `{token}`\", \"params\": [\"token\"]},\n"));
+    assert_eq!(json.lines().count(), TEMPLATES.len() + 2);
+}
+
+#[test]
+fn etf_works() {
+    use erl_parse::etf;
+    use num::BigInt;
+
+    let encode = |t: Term| etf::encode(&t);
+    assert_eq!(encode(Term::atom("foo")), [131, 119, 3, b'f', b'o', b'o']);
+    assert_eq!(encode(Term::string("ab")), [131, 107, 0, 2, b'a', b'b']);
+    assert_eq!(encode(Term::List(vec![])), [131, 106]);
+    assert_eq!(
+        encode(Term::Tuple(vec![Term::atom("ok"), Term::from(-1i64)])),
+        [131, 104, 2, 119, 2, b'o', b'k', 98, 255, 255, 255, 255]
+    );
+    assert_eq!(
+        encode(Term::from(1.5)),
+        [131, 70, 63, 248, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        encode(Term::from(BigInt::from(1u32) << 64u32)),
+        [131, 110, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+    );
+    assert_eq!(
+        encode(Term::from(-(BigInt::from(1u32) << 40u32))),
+        [131, 110, 6, 1, 0, 0, 0, 0, 0, 1]
+    );
+    assert_eq!(
+        encode(Term::ImproperList(
+            vec![Term::atom("a")],
+            Box::new(Term::atom("b"))
+        )),
+        [131, 108, 0, 0, 0, 1, 119, 1, b'a', 119, 1, b'b']
+    );
+    assert_eq!(
+        encode(Term::List(vec![Term::from(256u32)])),
+        [131, 108, 0, 0, 0, 1, 98, 0, 0, 1, 0, 106]
+    );
+    assert_eq!(
+        encode(Term::Binary(vec![1, 2])),
+        [131, 109, 0, 0, 0, 2, 1, 2]
+    );
+    assert_eq!(
+        encode(Term::Map(vec![(Term::atom("a"), Term::from(1u32))])),
+        [131, 116, 0, 0, 0, 1, 119, 1, b'a', 97, 1]
+    );
+
+    let text = "-module(foo).\n";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let mut buf = Vec::new();
+    track_try_unwrap!(etf::write_term(&mut buf, &module.to_abstract(), true));
+    let size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    assert_eq!(size, buf.len() - 4);
+    assert_eq!(&buf[4..], &etf::encode(&module.to_abstract())[..]);
+}
+
+#[test]
+fn input_limits_works() {
+    use erl_parse::{Guarded, InputLimits};
+
+    let text = "-module(foo).\nf() -> [1, 2, 3].\n";
+    let parse = |limits: InputLimits| {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let mut parser = Parser::new(Guarded::new(reader, limits));
+        let result = parser.parse::<ModuleDecl>();
+        (result.is_ok(), parser.reader().token_count())
+    };
+    assert_eq!(parse(InputLimits::new()), (true, 18));
+    assert_eq!(parse(InputLimits::new().max_tokens(18)), (true, 18));
+    assert!(!parse(InputLimits::new().max_tokens(17)).0);
+    assert!(!parse(InputLimits::new().max_bytes(10)).0);
+
+    // Whitespaces and comments between tokens are counted
+    let text = "%% comment\n-module(foo).";
+    let parse = |limits: InputLimits| {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let mut parser = Parser::new(Guarded::new(reader, limits));
+        let result = parser.parse::<ModuleDecl>();
+        (result.is_ok(), parser.reader().byte_count())
+    };
+    assert_eq!(parse(InputLimits::new()), (true, text.len()));
+    assert!(parse(InputLimits::new().max_bytes(text.len())).0);
+    assert!(!parse(InputLimits::new().max_bytes(text.len() - 1)).0);
+
+    let limits = InputLimits::new().max_bytes(4);
+    assert_eq!(track_try_unwrap!(limits.read_source(&b"abcd"[..])), "abcd");
+    let e = limits.read_source(&b"abcde"[..]).err().unwrap();
+    assert!(e.to_string().contains("exceeds the limit of 4 bytes"));
+}
+
+#[test]
+fn limits_works() {
+    use erl_parse::{ErrorKind, Limits};
+
+    let text = "-module(foo).\nf() -> [1, 2, 3].\ng(X) -> X ++ [X].\n";
+    let parse = |limits: Limits| {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let mut parser = Parser::new(reader).limits(limits);
+        match parser.parse::<ModuleDecl>() {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                assert!(matches!(e.kind(), ErrorKind::LimitExceeded));
+                Err(parser.error_diagnostic(&e).message.to_string())
+            }
+        }
+    };
+    assert!(parse(Limits::new()).is_ok());
+    assert!(parse(Limits::new().max_forms(3)).is_ok());
+    assert!(parse(Limits::new().max_forms(2)).is_err());
+    assert!(parse(Limits::new().max_tokens_per_form(12)).is_ok());
+    assert!(parse(Limits::new().max_tokens_per_form(11)).is_err());
+    assert!(parse(Limits::new().max_depth(3)).is_ok());
+    let message = parse(Limits::new().max_depth(2)).err().unwrap();
+    assert!(message.contains("the input exceeds the parser limits"));
+
+    let text = format!("f() -> {}1{}.", "{".repeat(100_000), "}".repeat(100_000));
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new(text.as_str())));
+    let mut parser = Parser::new(reader).limits(Limits::new().max_depth(1000));
+    assert!(parser.parse::<ModuleDecl>().is_err());
+
+    // Every recursive construct counts toward `max_depth`
+    let nest = |open: &str, close: &str, depth: usize| {
+        format!("f() -> {}1{}.", open.repeat(depth), close.repeat(depth))
+    };
+    let parse_deep = |text: &str, limits: Limits| {
+        let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+        let mut parser = Parser::new(reader).limits(limits);
+        match parser.parse::<ModuleDecl>() {
+            Ok(_) => true,
+            Err(e) => {
+                assert!(matches!(e.kind(), ErrorKind::LimitExceeded), "{}", e);
+                false
+            }
+        }
+    };
+    let nestings = [
+        (r#"{<<"a">>, "#, "}"),
+        ("g(", ")"),
+        ("#{a => ", "}"),
+        ("[X || X <- ", "]"),
+        ("begin ", " end"),
+        ("- (", ")"),
+    ];
+    for &(open, close) in &nestings {
+        assert!(!parse_deep(
+            &nest(open, close, 5000),
+            Limits::new().max_depth(500)
+        ));
+        assert!(parse_deep(&nest(open, close, 100), Limits::new()));
+        assert!(!parse_deep(
+            &nest(open, close, Limits::DEFAULT_MAX_DEPTH + 1),
+            Limits::new()
+        ));
+    }
+    let text = format!("-type t() :: {}a{}.", "[".repeat(5000), "]".repeat(5000));
+    assert!(!parse_deep(&text, Limits::new().max_depth(500)));
+    let text = format!(
+        "f() when {}true{} -> ok.",
+        "not (".repeat(5000),
+        ")".repeat(5000)
+    );
+    assert!(!parse_deep(&text, Limits::new().max_depth(500)));
+}
+
+#[test]
+fn ast_works() {
+    use erl_parse::ast::{self, BinaryOp, ExprKind, FormKind, Literal, PatternKind, TypeKind};
+
+    let text = r#"-module(foo).
+-export([bar/1]).
+-behaviour(gen_server).
+-spec bar(integer() | atom() | [term()]) -> ok.
+bar([X | T]) when is_integer(X), X > 0 -> (X + 1) * 2;
+bar(_) -> "a" "b".
+"#;
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let module = ast::Module::from(&module);
+    assert_eq!(module.name(), Some("foo"));
+    assert_eq!(module.forms.len(), 5);
+
+    match module.forms[1].kind {
+        FormKind::Export(ref exports) => {
+            assert_eq!((exports[0].name.as_str(), exports[0].arity), ("bar", 1));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(
+        module.forms[2].kind,
+        FormKind::Behaviour("gen_server".to_owned())
+    );
+    match module.forms[3].kind {
+        FormKind::Spec { ref clauses, .. } => match clauses[0].args[0].kind {
+            TypeKind::Union(ref members) => assert_eq!(members.len(), 3),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+
+    let fun = module.functions().next().unwrap();
+    assert_eq!(
+        (fun.name.as_str(), fun.arity, fun.clauses.len()),
+        ("bar", 1, 2)
+    );
+    let clause = &fun.clauses[0];
+    match clause.patterns[0].kind {
+        PatternKind::List {
+            ref elements,
+            tail: Some(ref tail),
+        } => {
+            assert_eq!(elements.len(), 1);
+            assert_eq!(tail.kind, PatternKind::Variable("T".to_owned()));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(clause.guards.len(), 1);
+    assert_eq!(clause.guards[0].len(), 2);
+    match clause.body[0].kind {
+        ExprKind::BinaryOp { op, ref left, .. } => {
+            assert_eq!(op, BinaryOp::Mul);
+            match left.kind {
+                ExprKind::BinaryOp { op, .. } => assert_eq!(op.as_str(), "+"),
+                _ => panic!(),
+            }
+            assert_eq!(
+                &text[left.span.start.offset()..left.span.end.offset()],
+                "(X + 1)"
+            );
+        }
+        _ => panic!(),
+    }
+    assert_eq!(
+        fun.clauses[1].body[0].kind,
+        ExprKind::Literal(Literal::String("ab".to_owned()))
+    );
+
+    let expr: Expr = track_try_unwrap!(Parser::new(TokenReader::new(Preprocessor::new(
+        Lexer::new("M#{a := 1}")
+    )))
+    .parse());
+    match ast::Expr::from(expr).kind {
+        ExprKind::Map {
+            base: Some(_),
+            ref fields,
+        } => assert!(fields[0].exact),
+        _ => panic!(),
+    }
+
+    let text = "-module(foo).\nf(\"ab\" ++ T) -> {<<\"c\">>, [\"d\" | T]}.";
+    let module: ModuleDecl = track_try_unwrap!(Parser::new(TokenReader::new(Preprocessor::new(
+        Lexer::new(text)
+    )))
+    .parse());
+    let mut module = ast::Module::from(&module);
+    module.strings_to_lists();
+    let clause = &module.functions().next().unwrap().clauses[0];
+    match clause.patterns[0].kind {
+        PatternKind::BinaryOp { ref left, .. } => match left.kind {
+            PatternKind::List { ref elements, .. } => assert_eq!(
+                elements.iter().map(|e| e.kind.clone()).collect::<Vec<_>>(),
+                [
+                    PatternKind::Literal(Literal::Integer(97u32.into())),
+                    PatternKind::Literal(Literal::Integer(98u32.into()))
+                ]
+            ),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+    match clause.body[0].kind {
+        ExprKind::Tuple(ref xs) => {
+            match xs[0].kind {
+                ExprKind::Bits(ref elems) => assert_eq!(
+                    elems[0].value.kind,
+                    ExprKind::Literal(Literal::String("c".to_owned()))
+                ),
+                _ => panic!(),
+            }
+            match xs[1].kind {
+                ExprKind::List { ref elements, .. } => assert!(matches!(
+                    elements[0].kind,
+                    ExprKind::List { ref elements, .. } if elements.len() == 1
+                )),
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn deterministic_output_works() {
+    use erl_parse::messages::write_catalog;
+    use erl_parse::workspace::{FileIndex, WorkspaceIndex};
+
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    track_try_unwrap!(write_catalog(&mut first));
+    track_try_unwrap!(write_catalog(&mut second));
+    assert_eq!(first, second);
+
+    let texts = [
+        ("c.erl", "-module(c).\nf() -> a:g(), b:h().\n"),
+        ("a.erl", "-module(a).\n-record(r, {}).\ng() -> ok.\n"),
+        ("b.erl", "-module(b).\n-define(M, 1).\nh() -> ?M.\n"),
+    ];
+    let write = |order: &[usize]| {
+        let mut ws_index = WorkspaceIndex::new();
+        for &i in order {
+            let (path, text) = texts[i];
+            ws_index.insert(path.into(), track_try_unwrap!(FileIndex::build(text)));
+        }
+        let mut buf = Vec::new();
+        track_try_unwrap!(ws_index.write_to(&mut buf));
+        buf
+    };
+    assert_eq!(write(&[0, 1, 2]), write(&[2, 1, 0]));
+
+    let text = "-module(foo).\n-export([f/0, g/0, f/0, g/0]).\n-record(r, {}).\n-record(r, {}).\nf() -> ok.\ng() -> ok.\nf() -> ok.\n";
+    let diagnostics = || {
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        let module: ModuleDecl = track_try_unwrap!(parser.parse());
+        erl_parse::analysis::find_duplicates(&module)
+            .iter()
+            .map(|d| format!("{}:{} {}", d.start_position.line(), d.code, d))
+            .collect::<Vec<_>>()
+    };
+    let expected = diagnostics();
+    assert_eq!(expected.len(), 4);
+    for _ in 0..10 {
+        assert_eq!(diagnostics(), expected);
+    }
+}
+
+#[test]
+fn incremental_reparse_works() {
+    use erl_parse::traits::CollectTokens;
+    use erl_parse::{ParsedModule, TextEdit};
+    use erl_tokenize::Position;
+
+    fn tokens(module: &ParsedModule) -> Vec<(String, usize, usize, usize)> {
+        module
+            .to_module_decl()
+            .tokens()
+            .iter()
+            .map(|t| {
+                let p = t.start_position();
+                (t.text().to_owned(), p.offset(), p.line(), p.column())
+            })
+            .collect()
+    }
+    fn edit(module: &mut ParsedModule, target: &str, new_text: &str) -> std::ops::Range<usize> {
+        let start = module.text().find(target).unwrap();
+        let edit = TextEdit {
+            start_position: Position::new() + start,
+            end_position: Position::new() + start + target.len(),
+            new_text: new_text.to_owned(),
+        };
+        let reparsed = track_try_unwrap!(module.reparse(&edit));
+        let fresh = ParsedModule::parse(module.text().to_owned());
+        assert_eq!(tokens(module), tokens(&fresh));
+        assert_eq!(
+            module.segment_ranges().collect::<Vec<_>>(),
+            fresh.segment_ranges().collect::<Vec<_>>()
+        );
+        assert_eq!(module.errors().count(), fresh.errors().count());
+        reparsed
+    }
+
+    let text = r#"-module(foo).
+-define(ONE, 1).
+f() -> ?ONE.
+-ifdef(TEST).
+t() -> ok.
+-endif.
+g(X) -> X#r.a. % comment
+h() -> [1, 2].
+"#;
+    let mut module = ParsedModule::parse(text.to_owned());
+    assert_eq!(module.forms().count(), 4);
+    assert_eq!(module.segment_ranges().count(), 6);
+
+    // Only the edited form is reparsed, and the following forms are relocated
+    assert_eq!(edit(&mut module, "?ONE", "?ONE +\n  2"), 1..2);
+    assert_eq!(edit(&mut module, "X#r.a", "{X}"), 2..3);
+    assert_eq!(edit(&mut module, "[1, 2]", "[]"), 3..4);
+
+    // Splitting and joining forms
+    assert_eq!(edit(&mut module, "{X}.", "{X}.\nk() -> 3."), 2..4);
+    assert_eq!(edit(&mut module, "3. % comment\nh() ->", "3,"), 3..4);
+    assert_eq!(module.forms().count(), 4);
+
+    // Errors are confined to the edited form
+    assert_eq!(edit(&mut module, "{X}", "{X"), 2..3);
+    assert_eq!(module.errors().count(), 1);
+    assert!(matches!(module.forms().nth(2), Some(Form::Error(_))));
+    assert_eq!(edit(&mut module, "{X", "{X}"), 2..3);
+    assert_eq!(module.errors().count(), 0);
+
+    // Editing a directive makes the following forms reparsed
+    assert_eq!(edit(&mut module, "ONE, 1", "ONE, 2"), 1..4);
+    assert_eq!(edit(&mut module, "-ifdef(TEST).\n", ""), 2..5);
+
+    let edit = TextEdit {
+        start_position: Position::new() + 1000,
+        end_position: Position::new() + 1000,
+        new_text: String::new(),
+    };
+    assert!(module.reparse(&edit).is_err());
+}
+
+#[test]
+fn evaluator_works() {
+    use erl_parse::analysis::{EvalError, Evaluator};
+    use erl_parse::ast;
+    use erl_parse::term::Term;
+
+    let text = r#"-module(foo).
+sum([]) -> 0;
+sum([H | T]) -> H + sum(T).
+rev(L) -> rev(L, []).
+rev([H | T], Acc) -> rev(T, [H | Acc]);
+rev([], Acc) -> Acc.
+kind(X) ->
+    case X of
+        {ok, V} when is_integer(V), V > 10 -> big;
+        {ok, _} -> small;
+        "abc" ++ _ -> prefixed;
+        _ -> other
+    end.
+keys() -> M = #{a => 1, b => 2}, M#{a := 3, c => quot(7, 2) + 7 rem 4}.
+quot(A, B) -> A div B.
+bad(X) -> X + 1.
+side_effect() -> self() ! hello.
+bool(X) -> if X > 1.5 andalso X =< 2 -> true; true -> false end.
+cmp() -> {1 == 1.0, 1 =:= 1.0, a < {}, {} < [], [1] < [1, 2], "b" > "abc", -2 bsl 2}.
+"#;
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let module = ast::Module::from(&module);
+    let int = |n: i64| Term::from(n);
+    let list = |xs: &[i64]| Term::List(xs.iter().map(|&n| Term::from(n)).collect());
+
+    let mut e = Evaluator::new(&module);
+    assert_eq!(e.call("sum", &[list(&[1, 2, 3])]), Ok(int(6)));
+    assert_eq!(e.call("rev", &[list(&[1, 2, 3])]), Ok(list(&[3, 2, 1])));
+    let ok = |n| Term::Tuple(vec![Term::atom("ok"), int(n)]);
+    assert_eq!(e.call("kind", &[ok(11)]), Ok(Term::atom("big")));
+    assert_eq!(e.call("kind", &[ok(1)]), Ok(Term::atom("small")));
+    assert_eq!(
+        e.call("kind", &[Term::string("abcd")]),
+        Ok(Term::atom("prefixed"))
+    );
+    assert_eq!(e.call("kind", &[Term::atom("x")]), Ok(Term::atom("other")));
+    assert_eq!(
+        e.call("keys", &[]),
+        Ok(Term::Map(vec![
+            (Term::atom("a"), int(3)),
+            (Term::atom("b"), int(2)),
+            (Term::atom("c"), int(6)),
+        ]))
+    );
+    assert_eq!(e.call("bool", &[Term::Float(1.75)]), Ok(Term::atom("true")));
+    assert_eq!(e.call("bool", &[int(1)]), Ok(Term::atom("false")));
+    let t = Term::atom("true");
+    let f = Term::atom("false");
+    assert_eq!(
+        e.call("cmp", &[]),
+        Ok(Term::Tuple(vec![
+            t.clone(),
+            f,
+            t.clone(),
+            t.clone(),
+            t.clone(),
+            t,
+            int(-8)
+        ]))
+    );
+
+    assert_eq!(
+        e.call("bad", &[Term::atom("a")]),
+        Err(EvalError::Exception(Term::atom("badarith")))
+    );
+    assert_eq!(
+        e.call("quot", &[int(1), int(0)]),
+        Err(EvalError::Exception(Term::atom("badarith")))
+    );
+    assert_eq!(e.call("side_effect", &[]), Err(EvalError::Unsupported));
+    assert_eq!(e.call("undefined", &[]), Err(EvalError::Unsupported));
+
+    let long = list(&(0..1000).collect::<Vec<_>>());
+    let mut e = Evaluator::new(&module).fuel(500);
+    assert_eq!(
+        e.call("rev", std::slice::from_ref(&long)),
+        Err(EvalError::OutOfFuel)
+    );
+    assert_eq!(e.remaining_fuel(), 0);
+    let mut e = Evaluator::new(&module).fuel(1_000_000);
+    assert_eq!(e.call("sum", &[long]), Err(EvalError::TooDeep));
+}
+
+#[test]
+fn module_parser_works() {
+    use erl_parse::traits::CollectTokens;
+    use erl_parse::ModuleParser;
+
+    let mut text = "-module(foo).\n".to_owned();
+    for i in 0..2000 {
+        text += &format!("f{}(X) -> {{X, ?MODULE, {}}}.\n", i, i);
+    }
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new(&text)));
+    let mut count = 0;
+    for form in ModuleParser::new(reader) {
+        let form = track_try_unwrap!(form);
+        if count > 0 {
+            assert_eq!(
+                form.to_erlang_source(),
+                format!("f{}(X) -> {{X, foo, {}}}.", count - 1, count - 1)
+            );
+        }
+        count += 1;
+    }
+    assert_eq!(count, 2001);
+
+    let text = "f() -> ok.\ng() -> [.\nh() -> 'a.";
+    let reader = TokenReader::new(Preprocessor::new(Lexer::new(text)));
+    let mut forms = ModuleParser::new(reader);
+    assert!(forms.next().unwrap().is_ok());
+    let e = forms.next().unwrap().err().unwrap();
+    assert_eq!(forms.parser().error_diagnostic(&e).start_position.line(), 2);
+    assert!(forms.next().unwrap().is_err());
+    assert!(forms.next().is_none());
+}
+
+#[test]
+fn ast_links_works() {
+    use erl_parse::ast::{self, AstNode, ExprKind, FormKind, Links};
+    use erl_parse::cst::{Node, NodeKind};
+
+    let text = r#"-module(foo).
+-record(r, {a = [1, 2] :: list()}).
+-spec f(atom()) -> ok.
+f(X) when X > 0 -> (X + 1) * 2;
+f([Y | _]) -> case Y of {Z} -> Z end.
+"#;
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let cst: ModuleDecl = track_try_unwrap!(parser.parse());
+    let ast = ast::Module::from(&cst);
+    let links = Links::new(&cst, &ast);
+
+    let clauses = match ast.forms[3].kind {
+        FormKind::Function(ref f) => &f.clauses,
+        _ => panic!(),
+    };
+    let id = links.cst_clause(&clauses[0]).unwrap();
+    assert_eq!(links.cst_node(id).unwrap().kind(), NodeKind::Clause);
+
+    // Guards are linked to guard tests.
+    let guard = &clauses[0].guards[0][0];
+    let id = links.cst_expr(guard).unwrap();
+    match links.cst_node(id) {
+        Some(Node::GuardTest(x)) => assert_eq!(x.to_erlang_source(), "X > 0"),
+        n => panic!("{:?}", n),
+    }
+
+    // Parenthesized nodes are kept in the CST.
+    let left = match clauses[0].body[0].kind {
+        ExprKind::BinaryOp { ref left, .. } => left,
+        _ => panic!(),
+    };
+    let id = links.cst_expr(left).unwrap();
+    match links.cst_node(id) {
+        Some(Node::Expr(Expr::Parenthesized(x))) => {
+            assert_eq!(x.to_erlang_source(), "(X + 1)")
+        }
+        n => panic!("{:?}", n),
+    }
+    match links.ast_node(id) {
+        Some(AstNode::Expr(x)) => assert_eq!(x, &**left),
+        n => panic!("{:?}", n),
+    }
+
+    // Every CST node is linked except the items of parenthesized nodes.
+    let mut unlinked = Vec::new();
+    for form in Node::forms(&cst) {
+        form.walk(|node| {
+            let span = ast::Span::of(&node);
+            let id = links.find(node.kind(), &span).unwrap();
+            match links.ast_node(id) {
+                Some(ast_node) => {
+                    assert_eq!(ast_node.span(), &span);
+                    assert_eq!(links.cst_id(ast_node), Some(id));
+                }
+                None => unlinked.push(&text[span.start.offset()..span.end.offset()]),
+            }
+        });
+    }
+    assert_eq!(unlinked, ["X + 1"]);
+}
+
+#[test]
+fn find_node_at_works() {
+    use erl_parse::cst::{Node, NodeKind};
+
+    let text = "-module(foo).\nf(X) ->\n    case X of {Y} -> Y + 1 end.\n";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+
+    let offset = text.find("1 end").unwrap();
+    let path = module.find_node_at(offset);
+    let kinds = path.iter().map(Node::kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            NodeKind::Form,
+            NodeKind::Clause,
+            NodeKind::Expr,
+            NodeKind::Clause,
+            NodeKind::Expr,
+            NodeKind::Expr
+        ]
+    );
+    let sources = path
+        .iter()
+        .skip(2)
+        .map(|n| text[n.start_position().offset()..n.end_position().offset()].to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sources,
+        ["case X of {Y} -> Y + 1 end", "{Y} -> Y + 1", "Y + 1", "1"]
+    );
+
+    let path = module.find_node_at_line_column(3, 16);
+    match path.last() {
+        Some(Node::Pattern(p)) => assert_eq!(p.to_erlang_source(), "Y"),
+        n => panic!("{:?}", n),
+    }
+
+    assert_eq!(module.find_node_at(3).len(), 1);
+    assert!(module.find_node_at(text.len()).is_empty());
+}
+
+#[test]
+fn span_works() {
+    use erl_parse::{ast, Span, Spanned};
+
+    let text = "-module(foo).\nf() ->\n    {a, b}.\n";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+
+    let path = module.find_node_at(text.find("{a").unwrap());
+    let tuple = path.last().unwrap().span();
+    assert_eq!(tuple.byte_range(), 25..31);
+    assert_eq!(tuple.start_line_column(), (3, 5));
+    assert_eq!(tuple.end_line_column(), (3, 11));
+    assert_eq!(tuple.text(text), Some("{a, b}"));
+    assert!(tuple.contains_offset(25));
+    assert!(!tuple.contains_offset(31));
+
+    // CST and AST nodes share the same span type.
+    let form = module.forms[1].span();
+    let ast_form = ast::Form::from(&module.forms[1]);
+    assert_eq!(ast_form.span(), form);
+    assert!(form.contains(&tuple));
+    assert!(!tuple.contains(&form));
+    assert_eq!(Span::of(&ast_form).text(text), Some("f() ->\n    {a, b}."));
+}
+
+#[test]
+fn maybe_expr_works() {
+    use erl_parse::abstract_format::ToAbstract;
+    use erl_parse::ast::{self, ExprKind};
+
+    // `?=` is parsed only without `erl_pp`, which drops the `?`
+    let parse = |text: &str| -> Expr {
+        let mut parser = Parser::new(TokenReader::new(Lexer::new(text)));
+        let value: Expr = track_try_unwrap!(parser.parse(), "text={:?}", text);
+        assert_eq!(value.end_position().offset(), text.len());
+        assert_eq!(value.tokens().len(), Lexer::new(text).count());
+        value
+    };
+    let expr = parse("maybe {ok, X} ?= f(), X end");
+    assert_eq!(expr.to_erlang_source(), "maybe {ok, X} ?= f(), X end");
+    parse("maybe ok ?= f(), g() else error -> ng; _ -> ok end");
+    parse("maybe X = Y ?= f(), [X] end");
+    parse("maybe maybe 1 end end");
+    parse_expr!("maybe {ok, X} = f(), X end");
+    parse_expr!("maybe begin 1 end else _ -> 2 end");
+
+    // `maybe` is an atom unless an expression follows
+    parse_expr!("[maybe, maybe(1), maybe:f(), maybe + 1]");
+    parse_expr!("case X of maybe -> 1 end");
+
+    let expr = parse("maybe {ok, X} ?= f(), X else {error, _} -> ng end");
+    match ast::Expr::from(&expr).kind {
+        ExprKind::Maybe {
+            ref body,
+            ref else_clauses,
+        } => {
+            assert_eq!(body.len(), 2);
+            assert!(matches!(body[0].kind, ExprKind::MaybeMatch { .. }));
+            assert_eq!(else_clauses.len(), 1);
+        }
+        _ => panic!(),
+    }
+    assert_eq!(
+        expr.to_abstract().to_string(),
+        "{'maybe',1,[{maybe_match,1,{tuple,1,[{atom,1,ok},{var,1,'X'}]},{call,1,{atom,1,f},[]}},{var,1,'X'}],{'else',1,[{clause,1,[{tuple,1,[{atom,1,error},{var,1,'_'}]}],[],[{atom,1,ng}]}]}}"
+    );
+
+    let mut parser = Parser::new(TokenReader::new(Lexer::new("maybe X ? = f() end")));
+    assert!(parser.parse::<Expr>().is_err());
+
+    // Through `erl_pp`
+    let text = "-module(foo).\n-define(OK(X), {ok, X}).\nf() -> maybe ?OK(A) ?= g(), '?' = A end.";
+    let tokens = erl_parse::MaybeMatchTokens::new(Lexer::new(text));
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let source = module.forms[1].to_erlang_source();
+    assert!(source.contains("{ok, A} ?= g(), '?' = A"), "{}", source);
+
+    let tokens = erl_parse::IncludeExpander::new(Lexer::new(text), erl_parse::IncludeCache::new());
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms[1].to_erlang_source(), source);
+}
+
+#[test]
+fn include_cache_works() {
+    use erl_parse::{IncludeCache, IncludeExpander};
+
+    let root = std::env::temp_dir().join(format!("erl_parse_include_{}", std::process::id()));
+    let include = root.join("kernel-9.0/include");
+    std::fs::create_dir_all(&include).unwrap();
+    std::fs::write(include.join("logger.hrl"), "-include(\"levels.hrl\").\n").unwrap();
+    std::fs::write(include.join("levels.hrl"), "-define(LEVEL, info).\n").unwrap();
+
+    let parse = |cache: IncludeCache, root: &std::path::Path| {
+        let text = "-module(foo).\n-include_lib(\"kernel/include/logger.hrl\").\nf() -> ?LEVEL.";
+        let tokens = IncludeExpander::new(Lexer::new(text), cache).code_path(root);
+        let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+        let module: ModuleDecl = track_try_unwrap!(parser.parse());
+        module.forms.len()
+    };
+    let cache = IncludeCache::new();
+    assert_eq!(parse(cache.clone(), &root), 2);
+    assert_eq!(cache.len(), 2);
+
+    let threads = (0..4)
+        .map(|_| {
+            let cache = cache.clone();
+            let root = root.clone();
+            std::thread::spawn(move || parse(cache, &root))
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        assert_eq!(t.join().unwrap(), 2);
+    }
+    assert_eq!(cache.len(), 2);
+
+    assert!(cache.invalidate(include.join("levels.hrl")));
+    assert_eq!(cache.len(), 1);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn transform_registry_works() {
+    use erl_parse::cst::Literal;
+    use erl_parse::traits::{Fold, Foldable};
+    use erl_parse::transform::TransformRegistry;
+    use erl_tokenize::tokens::AtomToken;
+
+    // Rewrites `lager:Level(..)` to `logger:Level(..)`
+    struct Lager;
+    impl Fold for Lager {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            match expr.map_children(self) {
+                Expr::FunCall(mut call) => {
+                    if let Some(ref mut module) = call.module {
+                        if module.name.to_string() == "lager" {
+                            let position = module.name.start_position();
+                            let token = AtomToken::from_text("logger", position).unwrap();
+                            module.name = Expr::Literal(Literal::Atom(token));
+                        }
+                    }
+                    Expr::FunCall(call)
+                }
+                expr => expr,
+            }
+        }
+    }
+
+    let module = parse_module(
+        r#"-module(foo).
+-compile({parse_transform, lager_transform}).
+-compile([export_all, {parse_transform, ms_transform}]).
+f() -> lager:info("~p", [lager:warning("a")])."#,
+    );
+    assert_eq!(
+        module.parse_transforms(),
+        ["lager_transform", "ms_transform"]
+    );
+
+    let mut registry = TransformRegistry::new();
+    registry.register("lager_transform", |m| Lager.fold_module_decl(m));
+    assert!(registry.is_registered("lager_transform"));
+    let (module, unknown) = registry.apply(module);
+    assert_eq!(unknown, ["ms_transform"]);
+    assert_eq!(
+        module.forms[3].to_erlang_source(),
+        r#"f() -> logger:info("~p", [logger:warning("a")])."#
+    );
+}
+
+#[test]
+fn include_resolver_works() {
+    use erl_parse::{
+        FileSystemResolver, IncludeCache, IncludeExpander, IncludeResolver, MemoryResolver,
+    };
+    use std::path::Path;
+
+    let mut resolver = MemoryResolver::new();
+    resolver.insert("/app/src/a.hrl", "-include_lib(\"kernel/include/b.hrl\").");
+    resolver.insert("kernel/include/b.hrl", "-define(B, b).");
+    assert_eq!(
+        resolver.locate("a.hrl", false, Some(Path::new("/app/src"))),
+        Some("/app/src/a.hrl".into())
+    );
+    assert_eq!(resolver.locate("a.hrl", false, None), None);
+
+    let cache = IncludeCache::new();
+    let mut lexer = Lexer::new("-include(\"a.hrl\").\nf() -> ?B.");
+    lexer.set_filepath("/app/src/foo.erl");
+    let tokens = IncludeExpander::with_resolver(lexer, cache.clone(), resolver);
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms.len(), 1);
+    assert_eq!(cache.len(), 2);
+
+    // Unresolved files are left to the preprocessor
+    let tokens = IncludeExpander::with_resolver(
+        Lexer::new("-include(\"a.hrl\").\nf() -> ?B."),
+        IncludeCache::new(),
+        MemoryResolver::new(),
+    );
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(tokens)));
+    assert!(parser.parse::<ModuleDecl>().is_err());
+
+    // The latest version of an application is preferred
+    let root = std::env::temp_dir().join(format!("erl_parse_resolver_{}", std::process::id()));
+    for version in &["9.2", "10.0", "9.10"] {
+        let include = root.join(format!("kernel-{}/include", version));
+        std::fs::create_dir_all(&include).unwrap();
+        std::fs::write(include.join("logger.hrl"), "").unwrap();
+    }
+    let resolver = FileSystemResolver::default().code_path(&root);
+    assert_eq!(
+        resolver.locate("kernel/include/logger.hrl", true, None),
+        Some(root.join("kernel-10.0/include/logger.hrl"))
+    );
+
+    // The buffers in memory are cached separately from the files on disk
+    let header = root.join("a.hrl");
+    std::fs::write(&header, "-define(A, disk).\n").unwrap();
+    let mut resolver = MemoryResolver::new();
+    resolver.insert(&header, "-define(A, memory).\n");
+    let cache = IncludeCache::new();
+    let has_atom =
+        |tokens: &[erl_tokenize::LexicalToken], atom: &str| tokens.iter().any(|t| t.text() == atom);
+    let tokens = track_try_unwrap!(cache.tokens_with(&header, &resolver));
+    assert!(has_atom(&tokens, "memory"));
+    let tokens = track_try_unwrap!(cache.tokens(&header));
+    assert!(has_atom(&tokens, "disk"));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.invalidate(&header));
+    assert!(cache.is_empty());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn map_comprehension_works() {
+    use erl_parse::ast::{self, ExprKind, Qualifier};
+
+    parse_expr!("#{K => V + 1 || K := V <- M}");
+    parse_expr!("#{K => V || {K, V} <- L, is_atom(K)}");
+    parse_expr!("[{K, V} || K := V <- M, V > 0]");
+    parse_expr!("<<<<K, V>> || K := V <- #{1 => 2}>>");
+    parse_expr!("#{K => #{} || K := #{a := _} <- M}");
+    parse_expr!("#{a => 1, b => [X || X <- L]}");
+
+    let text = "#{K => V || K := V <- M}";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    assert!(matches!(expr, Expr::MapComprehension(_)));
+    match ast::Expr::from(&expr).kind {
+        ExprKind::MapComprehension { ref qualifiers, .. } => {
+            assert!(matches!(qualifiers[0], Qualifier::MapGenerator { .. }));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(
+        expr.to_abstract().to_string(),
+        "{mc,1,{map_field_assoc,1,{var,1,'K'},{var,1,'V'}},[{m_generate,1,{map_field_exact,1,{var,1,'K'},{var,1,'V'}},{var,1,'M'}}]}"
+    );
+}
+
+#[test]
+fn macro_args_mismatch_works() {
+    use erl_parse::ErrorKind;
+
+    let text = "-define(F(X, Y), {X, Y}).\nf() -> ?F(1).";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let e = parser.parse::<ModuleDecl>().err().unwrap();
+    match *e.kind() {
+        ErrorKind::MacroArgsMismatched(ref m) => {
+            assert_eq!(m.name, "F");
+            assert_eq!((m.params, m.args), (Some(2), Some(1)));
+            assert_eq!(m.call.text(text), Some("?F(1)"));
+            assert_eq!(m.definition.text(text), Some("-define(F(X, Y), {X, Y})."));
+        }
+        ref kind => panic!("{:?}", kind),
+    }
+
+    let diagnostic = parser.error_diagnostic(&e);
+    assert_eq!(diagnostic.code, "macro_args_mismatched");
+    assert!(diagnostic
+        .message
+        .to_string()
+        .starts_with("macro `F` is defined with 2 argument(s), but invoked with 1 argument(s)"));
+    assert_eq!(diagnostic.start_position.offset(), 33);
+    let related = &diagnostic.related[0];
+    assert_eq!(related.message.to_string(), "macro `F` is defined here");
+    assert_eq!(related.start_position.offset(), 0);
+}
+
+#[test]
+fn head_mismatch_works() {
+    use erl_parse::ErrorKind;
+
+    parse_form!("f(X) -> X; f(_) -> ok.");
+
+    let text = "-module(foo).\nf(X) -> X;\nf(X, Y) -> Y.\ng() -> ok.";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let (module, errors) = parser.parse_module_resilient();
+    assert_eq!(module.forms.len(), 3);
+    assert_eq!(errors.len(), 1);
+    match *errors[0].kind() {
+        ErrorKind::HeadMismatch(ref m) => {
+            assert_eq!((m.previous.as_str(), m.name.as_str()), ("f/1", "f/2"));
+            assert_eq!(m.previous_head.text(text), Some("f(X)"));
+            assert_eq!(m.head.text(text), Some("f(X, Y)"));
+        }
+        ref kind => panic!("{:?}", kind),
+    }
+
+    let diagnostic = parser.error_diagnostic(&errors[0]);
+    assert_eq!(diagnostic.code, "head_mismatch");
+    assert_eq!(
+        diagnostic.message.to_string(),
+        "head mismatch: previous function f/1 is distinct from f/2. Is the semicolon in f/1 unwanted?"
+    );
+    assert_eq!(diagnostic.start_position.line(), 3);
+    assert_eq!(diagnostic.related[0].start_position.line(), 2);
+}
+
+#[test]
+fn guard_bif_whitelist_works() {
+    use erl_parse::{ErrorKind, IllegalGuardExpr};
+
+    let parse = |text: &str, strict: bool| {
+        let mut parser =
+            Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text)))).strict(strict);
+        let result = parser.parse::<ModuleDecl>();
+        result.err().map(|e| (parser.error_diagnostic(&e), e))
+    };
+
+    let text =
+        "f(X) when is_record(X, r), erlang:abs(X) > 1; node() =:= X -> if X -- [] =:= [] -> X end.";
+    assert!(parse(text, false).is_none());
+    let (diagnostic, _) = parse(text, true).unwrap();
+    assert_eq!(diagnostic.code, "illegal_guard_expr");
+    assert_eq!(
+        diagnostic.message.to_string(),
+        "operator `--` is illegal in guard"
+    );
+    assert_eq!(diagnostic.start_position.offset(), 67);
+
+    let text = "f(X) when is_atom(X) -> ok.\ng(X) when X > 0, foo(X) -> case X of Y when is_list(Y, 1) -> Y end.";
+    assert!(parse(text, false).is_none());
+    let (diagnostic, e) = parse(text, true).unwrap();
+    match *e.kind() {
+        ErrorKind::IllegalGuardExpr(ref x) => match **x {
+            IllegalGuardExpr::Call {
+                module: None,
+                ref name,
+                arity: 1,
+                ref span,
+            } => {
+                assert_eq!(name, "foo");
+                assert_eq!(span.text(text), Some("foo(X)"));
+            }
+            ref x => panic!("{:?}", x),
+        },
+        ref kind => panic!("{:?}", kind),
+    }
+    assert_eq!(
+        diagnostic.message.to_string(),
+        "call to local/imported function foo/1 is illegal in guard"
+    );
+
+    let text = "f(X) -> receive Y when lists:member(Y, X) -> Y end.";
+    let (diagnostic, _) = parse(text, true).unwrap();
+    assert_eq!(
+        diagnostic.message.to_string(),
+        "call to remote function lists:member/2 is illegal in guard"
+    );
+}
+
+#[test]
+fn strict_generator_works() {
+    use erl_parse::ast::{self, ExprKind, Qualifier};
+    use erl_parse::cst::exprs::parts::{GeneratorArrow, Qualifier as CstQualifier};
+
+    parse_expr!("[X || {ok, X} <:- L]");
+    parse_expr!("<<X || <<X:8>> <:= B>>");
+    parse_expr!("#{K => V || K := V <:- M}");
+    parse_expr!("[X || X <- L, Y <:- L, X < Y]");
+
+    let text = "[X || X <:- L, <<Y>> <:= B, K := V <:- M, X <- L]";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    let qualifiers = match expr {
+        Expr::ListComprehension(ref x) => x.qualifiers.iter().collect::<Vec<_>>(),
+        _ => panic!(),
+    };
+    assert!(matches!(
+        qualifiers[0],
+        CstQualifier::Generator(ref g) if matches!(g.arrow, GeneratorArrow::StrictList(..))
+    ));
+    assert!(matches!(
+        qualifiers[1],
+        CstQualifier::Generator(ref g) if matches!(g.arrow, GeneratorArrow::StrictBinary(..))
+    ));
+    match ast::Expr::from(&expr).kind {
+        ExprKind::ListComprehension { ref qualifiers, .. } => {
+            let strict = qualifiers
+                .iter()
+                .map(|q| match *q {
+                    Qualifier::Generator { strict, .. }
+                    | Qualifier::MapGenerator { strict, .. } => strict,
+                    Qualifier::Filter(_) => panic!(),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(strict, [true, true, true, false]);
+        }
+        _ => panic!(),
+    }
+    assert!(expr
+        .to_abstract()
+        .to_string()
+        .contains("{generate_strict,1,{var,1,'X'},{var,1,'L'}},{b_generate_strict,1,"));
+    assert_eq!(expr.to_erlang_source(), text);
+
+    // The symbols of strict arrows must be adjacent
+    let text = "[X || X < :- L]";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    assert!(parser.parse::<Expr>().is_err());
+}
+
+#[test]
+fn triple_quoted_string_works() {
+    use erl_parse::ast;
+    use erl_parse::cst::Literal;
+
+    let text = "\"\"\"\n    foo\n      bar\n\n    baz\n    \"\"\"";
+    parse_expr!(text);
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    match expr {
+        Expr::Literal(Literal::TripleQuotedString(ref x)) => {
+            assert_eq!(x.value(), "foo\n  bar\n\nbaz");
+        }
+        _ => panic!("{:?}", expr),
+    }
+    assert_eq!(
+        ast::Expr::from(&expr).kind,
+        ast::ExprKind::Literal(ast::Literal::String("foo\n  bar\n\nbaz".to_owned()))
+    );
+    assert_eq!(expr.to_erlang_source(), text);
+
+    // Adjacent strings with separators are concatenated as usual
+    let text = "\"\" \"\nfoo\n\" \"\"";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    assert!(matches!(expr, Expr::Literal(Literal::String { .. })));
+}
+
+#[test]
+fn adjacent_strings_works() {
+    use erl_parse::cst::Literal;
+
+    parse_expr!("\"foo\" \"bar\"\n  \"baz\"");
+    parse_pattern!("\"foo\" \"bar\" ++ X");
+
+    let text = "f(\"a\" \"b\\n\" ?S)";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    parser.define_macro("S", vec![Lexer::new("\"c\"").next().unwrap().unwrap()]);
+    let expr: Expr = track_try_unwrap!(parser.parse());
+    let literal = match expr {
+        Expr::FunCall(ref x) => match x.args.args.as_ref().and_then(|a| a.iter().next()) {
+            Some(Expr::Literal(ref l)) => l.clone(),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    };
+    assert!(matches!(literal, Literal::String { ref tail, .. } if tail.len() == 2));
+    assert_eq!(literal.string_value().as_deref(), Some("ab\nc"));
+    assert_eq!(
+        expr.to_abstract().to_string(),
+        "{call,1,{atom,1,f},[{string,1,[97,98,10,99]}]}"
+    );
+}
+
+#[test]
+fn macro_expansion_works() {
+    let text =
+        "-module(foo).\n-define(PAIR(X), {X, bad + 1}).\nf() -> ?PAIR(a).\ng() -> [?PAIR(b), ok].";
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let expansions = parser.reader().macro_expansions();
+    assert_eq!(expansions.len(), 2);
+    assert_eq!(expansions[0].name, "PAIR");
+    assert_eq!(expansions[0].form, 1);
+    assert_eq!(expansions[1].form, 2);
+    assert_eq!(expansions[1].tokens.len(), 7);
+    assert_eq!(
+        &text[expansions[1].call.start.offset()..expansions[1].call.end.offset()],
+        "?PAIR(b)"
+    );
+
+    // `bad` is located in the definition, but the call site can be found
+    let bad = module.forms[2]
+        .tokens()
+        .into_iter()
+        .find(|t| t.text() == "bad")
+        .unwrap();
+    assert_eq!(bad.start_position().line(), 2);
+    let expansion = parser
+        .reader()
+        .macro_expansion_at(2, &bad.start_position())
+        .unwrap();
+    assert_eq!(expansion.call.start.line(), 4);
+    assert!(parser
+        .reader()
+        .macro_expansion_at(0, &bad.start_position())
+        .is_none());
+}
+
+#[test]
+fn render_file_works() {
+    use erl_parse::render::render_file;
+    use erl_parse::MemoryResolver;
+
+    let mut resolver = MemoryResolver::new();
+    resolver.insert(
+        "/src/a.hrl",
+        "-record(r, {a}).\n-define(NEW(A), #r{a = A}).",
+    );
+    let text = "-module(foo).\n-include(\"a.hrl\").\n-define(ONE, 1).\nf() -> ?NEW(?ONE).";
+    let rendered = track_try_unwrap!(render_file("/src/foo.erl", text, resolver));
+    assert_eq!(
+        rendered,
+        "-module(foo).\n%% from /src/a.hrl:1\n-record(r, {a}).\n%% expanded ?NEW (line 4)\nf() -> #r {a = 1}.\n"
+    );
+}
+
+#[test]
+fn minify_works() {
+    use erl_parse::render::minify;
+
+    let text = r#"%% Header comment
+-module(foo).
+
+%% Doc comment
+f(X) ->
+    case X of
+        a -> 1; % one
+        _ -> 2
+    end.
+g() -> ok. h() -> ok.
+
+
+i() ->
+    "multi
+line".
+"#;
+    let minified = track_try_unwrap!(minify(text));
+    assert_eq!(
+        minified,
+        "\n-module(foo).\n\n\nf(X) -> case X of a -> 1; _ -> 2 end.\n\n\n\n\ng() -> ok. h() -> ok.\n\n\ni() -> \"multi\nline\".\n"
+    );
+
+    let original = parse_module(text);
+    let minified = parse_module(&minified);
+    let lines = |m: &ModuleDecl| {
+        m.forms
+            .iter()
+            .map(|f| f.start_position().line())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(lines(&original), lines(&minified));
+}
+
+#[test]
+fn parser_builder_works() {
+    use erl_parse::{Limits, MacroMode, ParserBuilder};
+
+    let text = "f() -> ok.\ng( -> ok.\nh() -> ?A.";
+    let reader = || TokenReader::new(Lexer::new(text));
+
+    let mut parser = ParserBuilder::new().build(reader());
+    assert!(!parser.is_recovering());
+    let (module, errors) = parser.parse_module();
+    assert_eq!(module.forms.len(), 1);
+    assert_eq!(errors.len(), 1);
+
+    let builder = ParserBuilder::new()
+        .recover(true)
+        .macros(MacroMode::Preserve)
+        .limits(Limits::new().max_forms(10))
+        .strict(true);
+    let mut parser = builder.build(reader());
+    assert!(parser.is_no_preprocess() && parser.is_strict());
+    let (module, errors) = parser.parse_module();
+    assert_eq!(module.forms.len(), 3);
+    assert_eq!(errors.len(), 1);
+
+    let mut parser = builder.limits(Limits::new().max_forms(1)).build(reader());
+    let (module, errors) = parser.parse_module();
+    assert_eq!(module.forms.len(), 1);
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn golden_check_works() {
+    use erl_parse::golden;
+
+    let root = std::env::temp_dir().join(format!("erl_parse_golden_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let input = root.join("foo.erl");
+    let expected = root.join("foo.expected");
+    std::fs::write(&input, "-module(foo).\n-define(A, 1).\nf(X)->\n  ?A+X.\n").unwrap();
+
+    std::fs::write(
+        &expected,
+        "-module(foo).\n-define(A, 1).\nf(X) -> ?A + X.\n",
+    )
+    .unwrap();
+    track_try_unwrap!(golden::check(&input, &expected));
+
+    std::fs::write(&expected, "-module(foo).\n-define(A, 1).\nf(X) -> X.\n").unwrap();
+    let e = golden::check(&input, &expected).unwrap_err();
+    assert!(e.to_string().contains("line=3"), "{}", e);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn otp_release_works() {
+    use erl_parse::cst::Literal;
+    use erl_parse::{ErrorKind, OtpRelease, ParserBuilder, SyntaxFeature};
+
+    let parse = |text: &str, release: OtpRelease| {
+        let builder = ParserBuilder::new().otp_release(release);
+        let mut parser = builder.build(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        let e = parser.parse::<ModuleDecl>().err()?;
+        let message = parser.error_diagnostic(&e).message.to_string();
+        Some((e, message))
+    };
+    let unsupported = |text: &str, release: OtpRelease| {
+        let (e, message) = parse(text, release).unwrap();
+        match *e.kind() {
+            ErrorKind::UnsupportedFeature(ref f) => (f.feature, f.required_release, message),
+            _ => panic!("{}", e),
+        }
+    };
+
+    let text = "f(M) -> maybe {ok, X} ?= M, X end.";
+    assert!(parse(text, OtpRelease::Otp27).is_none());
+    assert_eq!(
+        unsupported(text, OtpRelease::Otp24),
+        (
+            SyntaxFeature::MaybeExpr,
+            OtpRelease::Otp25,
+            "maybe expressions require OTP 25 or later".to_owned()
+        )
+    );
+
+    // OTP 25 and 26 require the feature to be enabled
+    assert_eq!(
+        unsupported(text, OtpRelease::Otp26).2,
+        "maybe expressions require `-feature(maybe_expr, enable).`"
+    );
+    let enabled = format!("-feature(maybe_expr, enable).\n{}", text);
+    assert!(parse(&enabled, OtpRelease::Otp25).is_none());
+    let disabled = format!("{}\n-feature(maybe_expr, disable).\n{}", enabled, text);
+    assert_eq!(
+        unsupported(&disabled, OtpRelease::Otp26).0,
+        SyntaxFeature::MaybeExpr
+    );
+
+    let text = "f(M) -> [X || X <:- M].";
+    assert!(parse(text, OtpRelease::Otp28).is_none());
+    assert_eq!(
+        unsupported(text, OtpRelease::Otp27).0,
+        SyntaxFeature::StrictGenerator
+    );
+
+    let text = "f(M) -> [K || K := _ <- M].";
+    assert_eq!(
+        unsupported(text, OtpRelease::Otp25).0,
+        SyntaxFeature::MapComprehension
+    );
+
+    let text = "f() -> \"\"\"\n  abc\n  \"\"\".";
+    assert!(parse(text, OtpRelease::Otp27).is_none());
+    assert!(parse(text, OtpRelease::Otp26).is_none());
+
+    // Before OTP 27, `"""` is an empty string followed by another string
+    let text = "\"\"\"\n  abc\n  \"\"\"";
+    let literal = |release: OtpRelease| {
+        let builder = ParserBuilder::new().otp_release(release);
+        let mut parser = builder.build(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+        parser.parse::<Literal>().unwrap()
+    };
+    match literal(OtpRelease::Otp26) {
+        Literal::String { ref head, ref tail } => {
+            assert_eq!(head.value(), "");
+            assert_eq!(tail.len(), 2);
+            assert_eq!(tail[0].value(), "\n  abc\n  ");
+        }
+        x => panic!("{:?}", x),
+    }
+    assert!(matches!(
+        literal(OtpRelease::Otp27),
+        Literal::TripleQuotedString(_)
+    ));
+    assert_eq!(OtpRelease::from_number(26), Some(OtpRelease::Otp26));
+    assert_eq!(OtpRelease::Otp26.number(), 26);
+}
+
+#[test]
+fn no_preprocess_works() {
+    use erl_parse::ast;
+    use erl_parse::cst::forms::Directive;
+
+    let text = r#"-module(foo).
+-define(PAIR(X, Y), {X, Y}).
+-define(LIST, a, b).
+-ifdef(TEST).
+-include_lib("eunit/include/eunit.hrl").
+-else.
+-if(?OTP_RELEASE >= 27).
+-endif.
+-endif.
+-spec f(?T) -> ?T.
+f(?A = X) when ?IS_OK(X) -> ?MODULE:g(?PAIR({1, 2}, begin a, b end)), ?LOG("~p", [X]).
+"#;
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms.len(), 11);
+    assert_eq!(module.tokens().len(), Lexer::new(text).count());
+    let directives = module
+        .forms
+        .iter()
+        .filter_map(|f| match *f {
+            Form::Directive(ref x) => Some(x),
+            _ => None,
+        })
+        .collect::<Vec<&Directive>>();
+    assert_eq!(directives.len(), 8);
+    assert_eq!(directives[0].macro_name(), Some("PAIR"));
+    let replacement = directives[1].replacement().unwrap();
+    assert_eq!(
+        replacement.iter().map(|t| t.text()).collect::<String>(),
+        "a,b"
+    );
+    assert_eq!(directives[2].macro_name(), Some("TEST"));
+    assert_eq!(directives[5].name.value(), "if");
+
+    match ast::Form::from(&module.forms[10]).kind {
+        ast::FormKind::Function(ref f) => {
+            let clause = &f.clauses[0];
+            assert!(matches!(
+                clause.patterns[0].kind,
+                ast::PatternKind::Match { ref left, .. }
+                    if left.kind == ast::PatternKind::MacroCall(ast::MacroCall { name: "A".to_owned(), args: None })
+            ));
+            match clause.body[0].kind {
+                ast::ExprKind::Call { ref args, .. } => assert_eq!(
+                    args[0].kind,
+                    ast::ExprKind::MacroCall(ast::MacroCall {
+                        name: "PAIR".to_owned(),
+                        args: Some(vec!["{1, 2}".to_owned(), "begin a, b end".to_owned()]),
+                    })
+                ),
+                ref x => panic!("{:?}", x),
+            }
+        }
+        ref x => panic!("{:?}", x),
+    }
+
+    // Macro calls as record names
+    let text = r#"-type t() :: #?R{}.
+f(#?R{a = A} = X) when X#?R.a > 0 -> Y = #?R{a = 1}, [Z#?R.a || Z <- [X#?R{a = 2}, Y]], #?MODULE.a.
+"#;
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms.len(), 2);
+    assert_eq!(module.tokens().len(), Lexer::new(text).count());
+    assert_eq!(
+        module.forms[1].to_string(),
+        "f(#?R {a = A} = X) when X#?R.a > 0 -> Y = #?R {a = 1}, [Z#?R.a || Z <- [X#?R {a = 2}, Y]], #?MODULE.a."
+    );
+    match ast::Form::from(&module.forms[1]).kind {
+        ast::FormKind::Function(ref f) => {
+            assert!(matches!(
+                f.clauses[0].body[0].kind,
+                ast::ExprKind::Match { ref value, .. }
+                    if matches!(value.kind, ast::ExprKind::Record { ref name, .. } if name == "?R")
+            ));
+            assert!(matches!(
+                f.clauses[0].body[2].kind,
+                ast::ExprKind::RecordIndex { ref name, ref field } if name == "?MODULE" && field == "a"
+            ));
+        }
+        ref x => panic!("{:?}", x),
+    }
+
+    // Macro calls are errors unless the mode is enabled
+    let mut parser = Parser::new(TokenReader::new(Lexer::new("f() -> ?A.")));
+    assert!(parser.parse::<ModuleDecl>().is_err());
+    let mut parser = Parser::new(TokenReader::new(Lexer::new("f() -> #?R{}.")));
+    assert!(parser.parse::<ModuleDecl>().is_err());
+}
+
+#[test]
+fn all_branches_works() {
+    use erl_parse::AllBranches;
+
+    let text = r#"-module(foo).
+-ifdef(TEST).
+-define(LEVEL, debug).
+-ifndef(NO_EXPORT).
+-export([f/0]).
+-endif.
+-else.
+-define(LEVEL, info).
+-endif.
+-if(?OTP_RELEASE >= 27).
+f() -> ?LEVEL.
+-elif(true).
+f() -> old.
+-endif.
+"#;
+    let tokens = AllBranches::new(Lexer::new(text));
+    let branches = tokens.branches();
+    let mut pp = Preprocessor::new(tokens);
+    pp.macros_mut().insert(
+        "OTP_RELEASE".to_owned(),
+        erl_pp::MacroDef::Dynamic(vec![Lexer::new("27").next().unwrap().unwrap()]),
+    );
+    let mut parser = Parser::new(TokenReader::new(pp));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms.len(), 4);
+    assert_eq!(module.forms[2].to_string(), "f() -> info.");
+    assert_eq!(branches.to_vec().len(), 5);
+
+    let conditions = |i: usize| {
+        branches
+            .conditions_at(&module.forms[i].start_position())
+            .into_iter()
+            .map(|b| {
+                let condition = b.condition.iter().map(|t| t.text()).collect::<Vec<_>>();
+                format!("{} {}", b.directive, condition.join(" "))
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(conditions(0).is_empty());
+    assert_eq!(conditions(1), ["ifdef TEST", "ifndef NO_EXPORT"]);
+    assert_eq!(conditions(2), ["if ? OTP_RELEASE >= 27"]);
+    assert_eq!(conditions(3), ["elif true"]);
+    let blocks = branches.conditions_at(&module.forms[3].start_position());
+    assert_eq!(blocks[0].block, 2);
+}
+
+#[test]
+fn diagnostics_sink_works() {
+    use erl_parse::messages::English;
+    use erl_parse::ParserBuilder;
+
+    let text = "-module(foo).\nf() -> foo(1 2).\ng() -> ok.\nh( -> ok.\ni() -> [1, 2.";
+    let reader = || TokenReader::new(Preprocessor::new(Lexer::new(text)));
+
+    let mut parser = ParserBuilder::new().recover(true).build(reader());
+    let (module, errors) = parser.parse_module();
+    assert_eq!(module.forms.len(), 5);
+    assert_eq!(errors.len(), 3);
+    let diagnostics = parser.take_diagnostics();
+    let lines = diagnostics
+        .iter()
+        .map(|d| d.start_position.line())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [2, 4, 5]);
+    assert!(diagnostics.iter().all(|d| d.code == "syntax_error"));
+    assert!(diagnostics[0]
+        .message
+        .format(&English)
+        .contains("in function `f/0`"));
+    assert!(parser.diagnostics().is_empty());
+
+    // Without recovery, only the first error is reported
+    let mut parser = ParserBuilder::new().build(reader());
+    let (_, errors) = parser.parse_module();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(parser.diagnostics().len(), 1);
+}
+
+#[test]
+fn parse_fragments_works() {
+    use erl_parse::builtin::{parse_expr, parse_form, parse_guard, parse_pattern, parse_type};
+
+    let expr = track_try_unwrap!(parse_expr("#{port => 8080, hosts => [\"a\", \"b\"]}."));
+    assert_eq!(expr.to_string(), "#{port => 8080, hosts => [\"a\", \"b\"]}");
+    assert!(parse_expr("foo(1).\n").is_ok());
+    assert!(parse_expr("1. 2").is_err());
+    assert!(parse_expr("1 +").is_err());
+
+    let pattern = track_try_unwrap!(parse_pattern("{ok, [H | _]}"));
+    assert!(matches!(pattern, Pattern::Tuple(_)));
+    assert!(parse_pattern("f(X)").is_err());
+
+    let ty = track_try_unwrap!(parse_type("#{atom() => [integer()]}"));
+    assert!(matches!(ty, Type::Map(_)));
+
+    let guard = track_try_unwrap!(parse_guard("is_integer(X), X > 0; X =:= undefined"));
+    assert_eq!(guard.iter().count(), 2);
+
+    let form = track_try_unwrap!(parse_form("f(X) -> X + 1."));
+    assert!(matches!(form, Form::FunDecl(_)));
+    assert!(parse_form("f(X) -> X + 1").is_err());
+}
+
+#[test]
+fn cst_eval_works() {
+    use erl_parse::builtin::parse_expr;
+    use erl_parse::cst::eval::eval;
+
+    let eval_str = |text: &str| eval(&track_try_unwrap!(parse_expr(text))).map(|t| t.to_string());
+    assert_eq!(eval_str("-(3 + 4) * 2 rem 5").as_deref(), Some("-4"));
+    assert_eq!(eval_str("1 - 2 + 3 * 4 - 5").as_deref(), Some("6"));
+    assert_eq!(eval_str("7 div -2 + 1.5").as_deref(), Some("-1.5"));
+    assert_eq!(eval_str("-9 bsr 1 bor (1 bsl 4)").as_deref(), Some("-5"));
+    assert_eq!(
+        eval_str("\"ab\" \"c\" ++ [$d]").as_deref(),
+        Some("[97,98,99,100]")
+    );
+    assert_eq!(
+        eval_str("#{k => {a, <<\"x\", 1>>}}").as_deref(),
+        Some("#{k => {a,<<120,1>>}}")
+    );
+    assert_eq!(eval_str("[1 | 2]").as_deref(), Some("[1|2]"));
+    assert_eq!(eval_str("1 / 0"), None);
+    assert_eq!(eval_str("a + 1"), None);
+    assert_eq!(eval_str("1 bsl 100000"), None);
+    assert_eq!(eval_str("foo()"), None);
+}
+
+#[test]
+fn consult_works() {
+    use erl_parse::term::{consult, consult_file, Term};
+
+    let text = r#"%% sys.config
+[{kernel, [{logger_level, info}]},
+ {foo, [{port, 8080}, {timeout, 60 * 1000}, {name, <<"foo">>}, {opts, #{'a b' => 1.5}}]}].
+{"unicode ✓", $a, [1 | 2]}.
+"#;
+    let terms = track_try_unwrap!(consult(text));
+    assert_eq!(terms.len(), 2);
+    assert_eq!(
+        terms[0].to_string(),
+        "[{kernel,[{logger_level,info}]},{foo,[{port,8080},{timeout,60000},{name,<<102,111,111>>},\
+         {opts,#{'a b' => 1.5}}]}]"
+    );
+    assert_eq!(
+        terms[1].to_string(),
+        "{[117,110,105,99,111,100,101,32,10003],97,[1|2]}"
+    );
+    assert_eq!(track_try_unwrap!(consult("")), Vec::<Term>::new());
+    assert!(consult("foo(1).").is_err());
+    assert!(consult("{a, b} {c}.").is_err());
+    assert!(consult("-define(A, 1).").is_err());
+
+    let path =
+        std::env::temp_dir().join(format!("erl_parse_consult_{}.app.src", std::process::id()));
+    std::fs::write(
+        &path,
+        "{application, foo, [{applications, [kernel, stdlib]}]}.\n",
+    )
+    .unwrap();
+    let terms = track_try_unwrap!(consult_file(&path));
+    assert!(matches!(terms[0], Term::Tuple(ref x) if x.len() == 3));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn syntax_extensions_work() {
+    use erl_parse::cst::commons::parts::ExtensionPart;
+    use erl_parse::cst::Node;
+    use erl_parse::extension::{ExtensionInput, Extensions, SyntaxExtension};
+    use erl_parse::{ast, ErrorKind, ParserBuilder};
+
+    /// `-route Method Path -> Handler.`
+    struct Route;
+    impl SyntaxExtension for Route {
+        fn parse(&self, input: &mut dyn ExtensionInput) -> erl_parse::Result<()> {
+            track!(input.read_token())?;
+            track!(input.parse_expr())?;
+            let arrow = track!(input.read_token())?;
+            if arrow.text() != "->" {
+                track_panic!(ErrorKind::UnexpectedToken(arrow));
+            }
+            track!(input.parse_expr())?;
+            Ok(())
+        }
+    }
+
+    /// `sql "QUERY"`
+    struct Sql;
+    impl SyntaxExtension for Sql {
+        fn parse(&self, input: &mut dyn ExtensionInput) -> erl_parse::Result<()> {
+            let is_string = matches!(track!(input.peek_token())?, Some(LexicalToken::String(_)));
+            track_assert!(is_string, ErrorKind::InvalidInput);
+            track!(input.read_token())?;
+            Ok(())
+        }
+    }
+
+    let text = r#"-module(foo).
+-route get "/users" -> users_handler.
+f(Id) -> sql "SELECT * FROM users WHERE id = ?" ++ [Id].
+g() -> sql:query().
+-other(1).
+"#;
+    let extensions = Extensions::new()
+        .attribute("route", Route)
+        .attribute("spec", Route)
+        .expr_prefix("sql", Sql);
+    let mut parser = ParserBuilder::new()
+        .extensions(extensions)
+        .build(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(module.forms.len(), 5);
+    assert_eq!(module.to_erlang_source(), text.trim_end());
+
+    match module.forms[1] {
+        Form::ExtensionAttr(ref x) => {
+            assert_eq!(x.attr_name.value(), "route");
+            assert_eq!(x.parts.len(), 4);
+            assert!(matches!(x.parts[3], ExtensionPart::Expr(Expr::Literal(_))));
+        }
+        ref f => panic!("{:?}", f),
+    }
+    assert!(matches!(module.forms[4], Form::WildAttr(_)));
+
+    let mut sql = Vec::new();
+    let mut calls = 0;
+    for form in Node::forms(&module) {
+        form.walk(|node| match node {
+            Node::Expr(Expr::Extension(x)) => sql.push(x.to_erlang_source()),
+            Node::Expr(Expr::FunCall(_)) => calls += 1,
+            _ => {}
+        });
+    }
+    assert_eq!(sql, ["sql \"SELECT * FROM users WHERE id = ?\""]);
+    assert_eq!(calls, 1);
+
+    let ast = ast::Form::from(&module.forms[1]);
+    assert_eq!(
+        ast.kind,
+        ast::FormKind::Extension(ast::Extension {
+            name: "route".to_owned(),
+            body: "get \"/users\" -> users_handler".to_owned(),
+        })
+    );
+
+    // Without the extensions, the attribute is a syntax error
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(
+        "-route get \"/users\" -> users_handler.",
+    )));
+    assert!(parser.parse::<Form>().is_err());
+}
+
+#[test]
+fn module_index_works() {
+    use erl_parse::analysis::ModuleIndex;
+
+    let text = r#"-module(foo).
+-export([f/1, g/0]).
+-export_type([t/0]).
+-import(lists, [map/2]).
+-define(N, 10).
+-define(ADD(A, B), A + B).
+-record(r, {a = ?N, b}).
+-type t() :: #r{}.
+-opaque o(A) :: [A].
+-behaviour(gen_server).
+f(X) -> map(fun(Y) -> ?ADD(X, Y) end, [1]).
+g() -> ok.
+h() -> ok.
+"#;
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    let index = ModuleIndex::new(&module);
+
+    assert_eq!(index.module.as_deref(), Some("foo"));
+    let functions: Vec<_> = index
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), f.arity))
+        .collect();
+    assert_eq!(functions, [("f", Some(1)), ("g", Some(0)), ("h", Some(0))]);
+    assert_eq!(
+        index.function("g", 0).unwrap().span.start_line_column(),
+        (12, 1)
+    );
+    assert!(index.is_exported("f", 1));
+    assert!(!index.is_exported("h", 0));
+
+    assert_eq!(index.import("map", 2).unwrap().module, "lists");
+    assert!(index.record("r").is_some());
+    assert!(index.type_decl("o", 1).is_some());
+    assert!(index.is_type_exported("t", 0));
+    assert!(!index.is_type_exported("o", 1));
+    assert!(index.macro_def("N", None).is_some());
+    assert!(index.macro_def("ADD", Some(2)).is_some());
+    assert!(index.macro_def("ADD", None).is_none());
+    assert_eq!(index.attributes("behaviour").count(), 1);
+    assert_eq!(index.attributes.len(), 10);
+
+    let module: ModuleDecl = track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(
+        "-compile(export_all).\nf() -> ok."
+    )))
+    .parse());
+    let index = ModuleIndex::new(&module);
+    assert!(index.is_exported("f", 0));
+    assert!(!index.is_exported("g", 0));
+}
+
+#[test]
+fn call_graph_works() {
+    use erl_parse::analysis::{call_graph, Callee};
+
+    let text = r#"-module(foo).
+-import(lists, [map/2]).
+f(X) when length(X) > 0 ->
+    map(fun g/1, X),
+    Y = fun(Z) -> bar:baz(Z) end,
+    apply(foo, h, [1, 2]),
+    apply(M, h, []),
+    erlang:apply(fun g/1, [1]),
+    F = fun bar:qux/0,
+    M:f(),
+    h(X, Y).
+g(_) -> ok.
+h(_, _) -> undefined_local().
+"#;
+    let module: ModuleDecl =
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse());
+    let edges: Vec<_> = call_graph(&module)
+        .into_iter()
+        .map(|e| {
+            let callee = match e.callee {
+                Callee::Function(f) => f.to_string(),
+                Callee::Unknown => "?".to_owned(),
+            };
+            (e.caller.to_string(), callee, e.span.start_line_column().0)
+        })
+        .collect();
+    let expected = [
+        ("foo:f/1", "erlang:length/1", 3),
+        ("foo:f/1", "lists:map/2", 4),
+        ("foo:f/1", "foo:g/1", 4),
+        ("foo:f/1", "bar:baz/1", 5),
+        ("foo:f/1", "foo:h/2", 6),
+        ("foo:f/1", "?", 7),
+        ("foo:f/1", "?", 8),
+        ("foo:f/1", "foo:g/1", 8),
+        ("foo:f/1", "bar:qux/0", 9),
+        ("foo:f/1", "?", 10),
+        ("foo:f/1", "foo:h/2", 11),
+        ("foo:h/2", "foo:undefined_local/0", 13),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|&(a, b, c)| (a.to_owned(), b.to_owned(), c))
+        .collect();
+    assert_eq!(edges, expected);
+}
+
+#[test]
+fn call_resolutions_work() {
+    use erl_parse::analysis::{CallResolutions, ModuleIndex, Resolution};
+
+    let text = r#"-module(foo).
+-compile({no_auto_import, [error/1]}).
+-import(lists, [map/2, max/1]).
+-record(r, {a = self()}).
+f(X) when is_list(X) ->
+    error(map(fun size/1, X)),
+    spawn(fun() -> max(X) end),
+    size(X).
+size(_) -> 0.
+"#;
+    let module: ModuleDecl =
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse());
+    let index = ModuleIndex::new(&module);
+    let resolutions = CallResolutions::new(&module, &index);
+    let calls: Vec<_> = resolutions
+        .iter()
+        .map(|(span, r)| {
+            let r = match *r {
+                Resolution::Local => "local".to_owned(),
+                Resolution::Imported { ref module } => format!("imported from {}", module),
+                Resolution::AutoImported => "auto-imported".to_owned(),
+                Resolution::Undefined => "undefined".to_owned(),
+            };
+            (span.text(text).unwrap(), r)
+        })
+        .collect();
+    let expected = [
+        ("self()", "auto-imported"),
+        ("is_list(X)", "auto-imported"),
+        ("error(map(fun size/1, X))", "undefined"),
+        ("map(fun size/1, X)", "imported from lists"),
+        ("fun size/1", "local"),
+        ("spawn(fun() -> max(X) end)", "auto-imported"),
+        ("max(X)", "imported from lists"),
+        ("size(X)", "local"),
+    ];
+    assert_eq!(
+        calls,
+        expected
+            .iter()
+            .map(|&(s, r)| (s, r.to_owned()))
+            .collect::<Vec<_>>()
+    );
+
+    let span = resolutions.undefined().next().cloned().unwrap();
+    assert_eq!(span.start_line_column(), (6, 5));
+    assert_eq!(resolutions.get(&span), Some(&Resolution::Undefined));
+
+    // BIFs listed by `erl_internal:bif/2`
+    let text = "g(T) -> A = alias(), alias([]), unalias(A), tuple_to_list(T).";
+    let module: ModuleDecl =
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse());
+    let index = ModuleIndex::new(&module);
+    let resolutions = CallResolutions::new(&module, &index);
+    assert_eq!(resolutions.iter().count(), 4);
+    assert!(resolutions
+        .iter()
+        .all(|(_, r)| *r == Resolution::AutoImported));
+}
+
+#[test]
+fn check_variables_works() {
+    use erl_parse::analysis::check_variables;
+
+    let text = r#"-module(foo).
+f(X, Unused, _Ignored) ->
+    case X of
+        {a, Y} -> Z = Y;
+        b -> Z = 1, W = 2
+    end,
+    g(Z, W, V),
+    F = fun(X) -> X end,
+    [A || A <- X, A > 0],
+    try h(X) of
+        R -> R
+    catch
+        _:E:St -> E
+    end,
+    R.
+g(A, A, B) when B > 0 -> #{A := C} = B, <<S:8, D:S/binary>> = C, D.
+h(X) ->
+    (X > 0) andalso (T = true),
+    T.
+"#;
+    let module: ModuleDecl =
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse());
+    let diagnostics: Vec<_> = check_variables(&module)
+        .iter()
+        .map(|d| {
+            let (line, column) = (d.start_position.line(), d.start_position.column());
+            format!("{}:{}: {}", line, column, d.message)
+        })
+        .collect();
+    assert_eq!(
+        diagnostics,
+        [
+            "2:6: variable 'Unused' is unused",
+            "7:10: variable 'W' unsafe in 'case' (line 3, column 5)",
+            "7:13: variable 'V' is unbound",
+            "8:5: variable 'F' is unused",
+            "15:5: variable 'R' unsafe in 'try' (line 10, column 5)",
+            "19:5: variable 'T' unsafe in 'andalso' (line 18, column 5)",
+        ]
+    );
+}
+
+#[test]
+fn format_works() {
+    use erl_parse::format::{format_text, CommaPlacement, FormatOptions};
+
+    let text = r#"-module(foo). % the module
+-export([f/1,
+         g/2]).
+
+
+%% Spec of f/1.
+-spec f(integer()) -> {ok, integer()} | error.
+f(X) when X > 0 -> % positive
+  Y = X * 2,{ok,Y};
+f(_) ->
+        error.
+g(Name, Opts) -> try lists:keyfind(Name, 1, Opts) of {Name, Value} -> Value; false -> undefined catch _:_ -> error end.
+"#;
+    let formatted = track_try_unwrap!(format_text(text, &FormatOptions::new().max_width(40)));
+    assert_eq!(
+        formatted,
+        r#"-module(foo). % the module
+-export([f/1, g/2]).
+
+%% Spec of f/1.
+-spec f(integer()) -> {ok, integer()}
+    | error.
+f(X) when X > 0 -> % positive
+    Y = X * 2,
+    {ok, Y};
+f(_) -> error.
+
+g(Name, Opts) ->
+    try
+        lists:keyfind(Name, 1, Opts)
+    of
+        {Name, Value} -> Value;
+        false -> undefined
+    catch
+        _:_ -> error
+    end.
+"#
+    );
+    let reformatted =
+        track_try_unwrap!(format_text(&formatted, &FormatOptions::new().max_width(40)));
+    assert_eq!(reformatted, formatted);
+
+    let options = FormatOptions::new()
+        .max_width(30)
+        .indent(2)
+        .comma_placement(CommaPlacement::Leading);
+    let formatted = track_try_unwrap!(format_text(
+        "h() -> #{alpha => 1, beta => [2, 3], gamma => 4}.",
+        &options
+    ));
+    assert_eq!(
+        formatted,
+        "h() ->\n  #{ alpha => 1\n   , beta => [2, 3]\n   , gamma => 4\n   }.\n"
+    );
+
+    // Macro calls as record names
+    let formatted = track_try_unwrap!(format_text(
+        "f(X) -> #?MODULE{a = X#?R.a}.",
+        &FormatOptions::new()
+    ));
+    assert_eq!(formatted, "f(X) -> #?MODULE{a = X#?R.a}.\n");
+}
+
+#[test]
+fn cst_diff_works() {
+    use erl_parse::cst::{diff, ChangeKind, NodeKind};
+
+    let old_text = r#"-module(foo).
+-export([f/1]).
+f(X) ->
+    Y = X + 1,
+    {ok, Y}.
+g(A) -> [A, b].
+"#;
+    let new_text = r#"-module(foo).
+-export([f/1, g/1]).
+
+f(X) -> Y = X * 1, log(Y), {ok, Y}. % comment
+g(A) ->
+    [A, b, c].
+"#;
+    let parse = |text| -> ModuleDecl {
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse())
+    };
+    let changes: Vec<_> = diff(&parse(old_text), &parse(new_text))
+        .into_iter()
+        .map(|c| {
+            let old = c.old.map(|s| s.text(old_text).unwrap());
+            let new = c.new.map(|s| s.text(new_text).unwrap());
+            (c.kind, c.node_kind, old, new)
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (
+                ChangeKind::Modified,
+                NodeKind::Form,
+                Some("-export([f/1])."),
+                Some("-export([f/1, g/1]).")
+            ),
+            (
+                ChangeKind::Modified,
+                NodeKind::Expr,
+                Some("X + 1"),
+                Some("X * 1")
+            ),
+            (ChangeKind::Added, NodeKind::Expr, None, Some("log(Y)")),
+            (ChangeKind::Added, NodeKind::Expr, None, Some("c")),
+        ]
+    );
+
+    let module = parse(old_text);
+    assert!(diff(&module, &module).is_empty());
+}
+
+#[test]
+fn node_index_works() {
+    use erl_parse::cst::{Node, NodeId, NodeIndex, NodeKind};
+    use erl_tokenize::PositionRange;
+
+    let text = r#"-module(foo).
+-spec f(integer()) -> integer().
+f(X) when X > 0 ->
+    case X of
+        1 -> [Y || Y <- lists:seq(1, X)];
+        _ -> fun(Z) -> Z end
+    end.
+"#;
+    let module: ModuleDecl =
+        track_try_unwrap!(Parser::new(TokenReader::new(Lexer::new(text))).parse());
+    let index = NodeIndex::new(&module);
+
+    let mut count = 0;
+    for form in Node::forms(&module) {
+        form.walk(|_| count += 1);
+    }
+    assert_eq!(index.len(), count);
+
+    for (id, node) in index.iter() {
+        let span = index.span(id).unwrap();
+        assert_eq!(span.start, node.start_position());
+        match index.parent(id) {
+            None => assert_eq!(node.kind(), NodeKind::Form),
+            Some(parent) => {
+                assert!(parent < id);
+                let parent_span = index.span(parent).unwrap();
+                assert!(parent_span.start.offset() <= span.start.offset());
+                assert!(span.end.offset() <= parent_span.end.offset());
+                let children = index.node(parent).unwrap().children();
+                assert!(children.iter().any(|c| c.start_position() == span.start
+                    && c.end_position() == span.end
+                    && c.kind() == node.kind()));
+            }
+        }
+    }
+
+    let offset = text.find("Z end").unwrap();
+    let id = index.find_innermost_at(offset).unwrap();
+    assert_eq!(index.span(id).unwrap().text(text), Some("Z"));
+    let kinds: Vec<_> = index
+        .ancestors(id)
+        .map(|id| index.node(id).unwrap().kind())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            NodeKind::Clause,
+            NodeKind::Expr,
+            NodeKind::Clause,
+            NodeKind::Expr,
+            NodeKind::Clause,
+            NodeKind::Form
+        ]
+    );
+    assert_eq!(
+        index.root_of(id),
+        NodeId(
+            index
+                .find(NodeKind::Form, index.span(index.root_of(id)).unwrap())
+                .unwrap()
+                .0
+        )
+    );
+    assert!(index.node(NodeId(index.len())).is_none());
+}
+
+#[test]
+fn parse_directory_works() {
+    use erl_parse::cst::Form;
+    use erl_parse::project::{parse_directory, ProjectOptions};
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("erl_parse_project_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in &["apps/a/src", "apps/a/include", "apps/b/src", ".git"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(
+        dir.join("apps/a/include/a.hrl"),
+        "-record(r, {x = 0}).\n-define(X, 1).\n",
+    )
+    .unwrap();
+    for i in 0..8 {
+        fs::write(
+            dir.join(format!("apps/a/src/m{}.erl", i)),
+            format!(
+                "-module(m{}).\n-include(\"../include/a.hrl\").\nf() -> #r{{x = ?X}}.\n",
+                i
+            ),
+        )
+        .unwrap();
+    }
+    fs::write(
+        dir.join("apps/b/src/b.erl"),
+        "-module(b).\nf( -> ok.\ng() -> ok.\nh() -> ].\n",
+    )
+    .unwrap();
+    fs::write(dir.join("apps/b/src/README.md"), "not erlang").unwrap();
+    fs::write(dir.join(".git/x.erl"), "ignored").unwrap();
+
+    let summary = |threads| {
+        let project = track_try_unwrap!(parse_directory(
+            &[dir.join("apps")],
+            &ProjectOptions::new().threads(threads)
+        ));
+        project
+            .files()
+            .iter()
+            .map(|f| {
+                let forms = f.module.as_ref().map_or(0, |m| m.forms.len());
+                let errors = f
+                    .module
+                    .iter()
+                    .flat_map(|m| &m.forms)
+                    .filter(|f| matches!(f, Form::Error(_)))
+                    .count();
+                let path = f.path.strip_prefix(&dir).unwrap().to_path_buf();
+                (path, forms, errors, f.diagnostics.len())
+            })
+            .collect::<Vec<_>>()
+    };
+    let files = summary(1);
+    assert_eq!(files.len(), 10);
+    assert_eq!(
+        files[0],
+        (std::path::PathBuf::from("apps/a/include/a.hrl"), 1, 0, 0)
+    );
+    for f in &files[1..9] {
+        assert_eq!((f.1, f.2, f.3), (3, 0, 0), "{:?}", f.0);
+    }
+    assert_eq!(
+        files[9],
+        (std::path::PathBuf::from("apps/b/src/b.erl"), 4, 2, 2)
+    );
+    assert_eq!(summary(4), files);
+
+    // Symbolic links to directories are not followed
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink(dir.join("apps"), dir.join("apps/b/src/cycle")).unwrap();
+        symlink(dir.join("apps/a/src"), dir.join("apps/b/a_src")).unwrap();
+        assert_eq!(summary(2), files);
+    }
+
+    let project = track_try_unwrap!(parse_directory(&[&dir], &ProjectOptions::new()));
+    assert!(project.has_errors());
+    let diagnostics = project.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics
+        .iter()
+        .all(|(p, _)| p.ends_with("apps/b/src/b.erl")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn header_decl_works() {
+    use erl_parse::analysis::ModuleIndex;
+    use erl_parse::cst::HeaderDecl;
+
+    let text = r#"%% Common definitions
+-ifndef(FOO_HRL).
+-define(FOO_HRL, true).
+-include_lib("kernel/include/logger.hrl").
+-record(state, {name :: binary(), count = 0 :: non_neg_integer()}).
+-type state() :: #state{}.
+-define(LOG(Msg), ?LOG_INFO(Msg)).
+-export_type([state/0]).
+-endif.
+"#;
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let header: HeaderDecl = track_try_unwrap!(parser.parse());
+    assert_eq!(header.forms.len(), 8);
+    assert_eq!(
+        header.record_decls().map(|r| r.name()).collect::<Vec<_>>(),
+        ["state"]
+    );
+    assert_eq!(
+        header
+            .macro_defs()
+            .filter_map(|d| d.macro_name())
+            .collect::<Vec<_>>(),
+        ["FOO_HRL", "LOG"]
+    );
+
+    let module = header.into_module();
+    let index = ModuleIndex::new(&module);
+    assert!(index.record("state").is_some());
+    assert!(index.type_decl("state", 0).is_some());
+    assert!(index.macro_def("LOG", Some(1)).is_some());
+
+    let text = "-record(r, {}).\n-module(foo).\n";
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    assert!(parser.parse::<HeaderDecl>().is_err());
+}
+
+#[test]
+fn escript_works() {
+    use erl_parse::escript::{mask_shebang, Escript, EscriptHeader};
+    use erl_tokenize::PositionRange;
+
+    let text = "#!/usr/bin/env escript\r\n%%! -pa ebin\n-mode(compile).\nmain(_) -> ok.\n";
+    let escript = track_try_unwrap!(Escript::parse(text));
+    let header = escript.header.as_ref().unwrap();
+    assert_eq!(header.shebang, "/usr/bin/env escript");
+    assert_eq!(header.emulator_flags(), [("-pa", vec!["ebin"])]);
+    assert_eq!(escript.module.forms.len(), 2);
+    let main = escript.main().unwrap();
+    assert_eq!(main.start_position().offset(), text.find("main").unwrap());
+    assert_eq!(main.start_position().line(), 4);
+    assert_eq!(mask_shebang(text).len(), text.len());
+
+    // `%%!` is recognized on the third line only after a comment line
+    let header = EscriptHeader::parse("#!escript\n%% -*- erlang -*-\n%%! -smp\n").unwrap();
+    assert_eq!(header.emulator_args, ["-smp"]);
+    let header = EscriptHeader::parse("#!escript\nmain(_) -> ok.\n%%! -smp\n").unwrap();
+    assert!(header.emulator_args.is_empty());
+
+    let escript = track_try_unwrap!(Escript::parse("main(Args) -> Args."));
+    assert!(escript.header.is_none());
+    assert!(escript.main().is_some());
+    assert!(Escript::parse("#!escript\nmain( -> ok.").is_err());
+}
+
+#[test]
+fn attribute_forms_work() {
+    use erl_parse::analysis::interface;
+    use erl_parse::cst::forms::DialyzerOption;
+
+    let text = r#"-module(foo).
+-behavior(gen_server).
+-behaviour(supervisor).
+-on_load(init/0).
+-dialyzer({[no_return, no_match], [f/1, g/2]}).
+-dialyzer(no_improper_lists).
+"#;
+    let mut parser = Parser::new(TokenReader::new(Preprocessor::new(Lexer::new(text))));
+    let module: ModuleDecl = track_try_unwrap!(parser.parse());
+    check_tokens(text, &module);
+
+    let names: Vec<_> = module
+        .forms
+        .iter()
+        .filter_map(|f| match *f {
+            Form::BehaviourAttr(ref x) => Some(x.behaviour_name.value()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, ["gen_server", "supervisor"]);
+    assert_eq!(interface(&module).behaviours, ["gen_server", "supervisor"]);
+
+    match module.forms[3] {
+        Form::OnLoadAttr(ref x) => assert_eq!((x.fun_name(), x.arity()), ("init", 0)),
+        _ => panic!(),
+    }
+    match module.forms[4] {
+        Form::DialyzerAttr(ref x) => assert_eq!(
+            x.options(),
+            [DialyzerOption {
+                warnings: vec!["no_return", "no_match"],
+                functions: vec![("f", 1), ("g", 2)],
+            }]
+        ),
+        _ => panic!(),
+    }
+
+    let abstract_forms: Vec<_> = module
+        .forms
+        .iter()
+        .map(|f| f.to_abstract().to_string())
+        .collect();
+    assert_eq!(abstract_forms[1], "{attribute,2,behavior,gen_server}");
+    assert_eq!(abstract_forms[3], "{attribute,4,on_load,{init,0}}");
+    assert_eq!(
+        abstract_forms[5],
+        "{attribute,6,dialyzer,no_improper_lists}"
+    );
+
+    let mut parser = Parser::new(TokenReader::new(Lexer::new("-on_load(init).")));
+    assert!(parser.parse::<Form>().is_err());
+    let text = "-behaviour(?MODULE).";
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text))).no_preprocess(true);
+    let form: Form = track_try_unwrap!(parser.parse());
+    assert!(matches!(form, Form::WildAttr(_)));
+}
+
+#[test]
+fn spec_constraints_work() {
+    use erl_parse::ast;
+    use erl_parse::cst::types::Constraint;
+
+    let text = "-spec f(A, B) -> fun((A) -> B) when A :: atom() | integer(), is_subtype(B, [A]).";
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text)));
+    let form: Form = track_try_unwrap!(parser.parse());
+    check_tokens(text, &form);
+
+    let spec = match form {
+        Form::FunSpec(ref x) => x,
+        _ => panic!(),
+    };
+    let clause = spec.clauses.iter().next().unwrap();
+    let constraints: Vec<_> = clause
+        .constraints
+        .iter()
+        .flat_map(|c| c.constraints.iter())
+        .collect();
+    match *constraints[0] {
+        Constraint::Subtype(ref x) => assert_eq!(x.var.value(), "A"),
+        _ => panic!(),
+    }
+    match *constraints[1] {
+        Constraint::IsSubtype(ref x) => assert_eq!(x.var.value(), "B"),
+        _ => panic!(),
+    }
+    assert!(matches!(*constraints[0].bound(), Type::Union(_)));
+    assert!(matches!(*constraints[1].bound(), Type::List(_)));
+
+    match ast::Form::from(&form).kind {
+        ast::FormKind::Spec { ref clauses, .. } => {
+            let vars: Vec<_> = clauses[0]
+                .constraints
+                .iter()
+                .map(|c| c.var.as_str())
+                .collect();
+            assert_eq!(vars, ["A", "B"]);
+        }
+        _ => panic!(),
+    }
+
+    let text = "-spec f(A) -> ok when is_list(A).";
+    let mut parser = Parser::new(TokenReader::new(Lexer::new(text)));
+    assert!(parser.parse::<Form>().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip_works() {
+    let text = r#"-module(foo).
+-record(r, {a = 1 :: integer()}).
+-spec f(A) -> {ok, A} when A :: #r{}.
+f(X) ->
+    case X of
+        #r{a = A} when A > 0 -> [Y || Y <- "abc", Y =/= $b];
+        _ -> <<1:8, 2.5/float>>
+    end.
+"#;
+    let module = parse_module(text);
+    let json = serde_json::to_string(&module).unwrap();
+    let decoded: ModuleDecl = serde_json::from_str(&json).unwrap();
+
+    // `Debug` includes the positions of the tokens
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", module));
+    let positions = |module: &ModuleDecl| {
+        let mut tokens = Vec::new();
+        module.collect_tokens(&mut tokens);
+        tokens
+            .iter()
+            .map(|t| (t.text().to_owned(), t.start_position(), t.end_position()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(positions(&decoded), positions(&module));
 }